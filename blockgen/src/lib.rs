@@ -10,7 +10,8 @@ use crate::miner::{
 use cfx_parameters::consensus::GENESIS_GAS_LIMIT;
 use cfx_types::{Address, H256, U256};
 use cfxcore::{
-    block_parameters::*, consensus::consensus_inner::StateBlameInfo, pow::*,
+    block_data_manager::BlockOrigin, block_parameters::*,
+    consensus::consensus_inner::StateBlameInfo, pow::*,
     verification::compute_transaction_root, ConsensusGraph,
     ConsensusGraphTrait, SharedSynchronizationGraph,
     SharedSynchronizationService, SharedTransactionPool, Stopable,
@@ -194,7 +195,6 @@ impl BlockGenerator {
         }
     }
 
-    // TODO: should not hold and pass write lock to consensus.
     fn assemble_new_block_impl(
         &self, parent_hash: H256, mut referees: Vec<H256>,
         blame_info: StateBlameInfo, block_gas_limit: U256,
@@ -210,7 +210,7 @@ impl BlockGenerator {
 
         trace!("{} txs packed", transactions.len());
         let consensus_graph = self.consensus_graph();
-        let mut consensus_inner = consensus_graph.inner.write();
+        let consensus_inner = consensus_graph.inner.read();
         // referees are retrieved before locking inner, so we need to
         // filter out the blocks that should be removed by possible
         // checkpoint making that happens before we acquire the inner lock
@@ -221,8 +221,8 @@ impl BlockGenerator {
         let adaptive = if let Some(x) = adaptive_opt {
             x
         } else {
-            consensus_graph.check_mining_adaptive_block(
-                &mut *consensus_inner,
+            consensus_graph.predict_mining_adaptive_block(
+                &*consensus_inner,
                 &parent_hash,
                 &referees,
                 &expected_difficulty,
@@ -410,7 +410,12 @@ impl BlockGenerator {
     /// Update and sync a new block
     pub fn on_mined_block(&self, block: Block) {
         // FIXME: error handling.
-        self.sync.on_mined_block(block).ok();
+        self.sync.on_mined_block(block, BlockOrigin::Miner).ok();
+    }
+
+    /// Update and sync a new block generated through the test RPCs.
+    fn on_generated_block(&self, block: Block) {
+        self.sync.on_mined_block(block, BlockOrigin::Local).ok();
     }
 
     /// Check if we need to mine on a new block
@@ -581,7 +586,7 @@ impl BlockGenerator {
             block.transactions.len(),
             block.size(),
         );
-        self.on_mined_block(block);
+        self.on_generated_block(block);
 
         consensus_graph.wait_for_generation(&hash);
         Ok(hash)
@@ -616,9 +621,9 @@ impl BlockGenerator {
             block.transactions.len(),
             block.size(),
         );
-        self.on_mined_block(block);
+        self.on_generated_block(block);
 
-        debug!("generate_block finished on_mined_block()");
+        debug!("generate_block finished on_generated_block()");
         // FIXME: We should add a flag to enable/disable this wait
         // Ensure that when `generate**` function returns, the block has been
         // handled by Consensus This order is assumed by some tests, and
@@ -675,7 +680,7 @@ impl BlockGenerator {
     pub fn start_mining(bg: Arc<BlockGenerator>, _payload_len: u32) {
         let mut current_mining_block = None;
         let mut recent_mining_blocks = vec![];
-        let mut recent_mining_problems = vec![];
+        let mut recent_mining_problems: Vec<ProofOfWorkProblem> = vec![];
         let mut current_problem: Option<ProofOfWorkProblem> = None;
         let sleep_duration =
             time::Duration::from_millis(BLOCKGEN_LOOP_SLEEP_IN_MILISECS);
@@ -691,7 +696,15 @@ impl BlockGenerator {
         let mut last_assemble = SystemTime::now();
         loop {
             match *bg.state.read() {
-                MiningState::Stop => return,
+                MiningState::Stop => {
+                    for problem in &recent_mining_problems {
+                        bg.txpool.release_provisionally_packed(
+                            &problem.block_hash,
+                            false, /* mined */
+                        );
+                    }
+                    return;
+                }
                 _ => {}
             }
 
@@ -714,8 +727,14 @@ impl BlockGenerator {
                 if recent_mining_blocks.len()
                     == bg.pow_config.pow_problem_window_size
                 {
+                    // A solution of the oldest template is not accepted any
+                    // more.
                     recent_mining_blocks.remove(0);
-                    recent_mining_problems.remove(0);
+                    let abandoned_problem = recent_mining_problems.remove(0);
+                    bg.txpool.release_provisionally_packed(
+                        &abandoned_problem.block_hash,
+                        false, /* mined */
+                    );
                 }
 
                 // set a mining problem
@@ -743,6 +762,10 @@ impl BlockGenerator {
                 last_notify = SystemTime::now();
                 current_problem = Some(problem);
 
+                bg.txpool.mark_provisionally_packed(
+                    problem.block_hash,
+                    &current_mining_block.as_ref().unwrap().transactions,
+                );
                 recent_mining_blocks
                     .push(current_mining_block.clone().unwrap());
                 recent_mining_problems.push(problem);
@@ -784,6 +807,10 @@ impl BlockGenerator {
                 if new_solution.is_ok() {
                     let solution = new_solution.unwrap();
                     let mut mined_block = maybe_mined_block.unwrap();
+                    bg.txpool.release_provisionally_packed(
+                        &mined_block.block_header.problem_hash(),
+                        true, /* mined */
+                    );
                     mined_block.block_header.set_nonce(solution.nonce);
                     mined_block.block_header.compute_hash();
                     bg.on_mined_block(mined_block);
@@ -203,7 +203,11 @@ pub fn initialize_common_modules(
 
     let consensus_conf = conf.consensus_config();
     let vm = VmFactory::new(1024 * 32);
-    let machine = Arc::new(new_machine_with_builtin(conf.common_params(), vm));
+    let common_params = conf.common_params();
+    common_params
+        .validate_reward_params(consensus_conf.inner_conf.timer_chain_beta)?;
+    common_params.validate_deferred_state_params()?;
+    let machine = Arc::new(new_machine_with_builtin(common_params, vm));
 
     let genesis_block = genesis_block(
         &storage_manager,
@@ -215,6 +219,8 @@ pub fn initialize_common_modules(
         conf.raw_conf.chain_id,
     );
     debug!("Initialize genesis_block={:?}", genesis_block);
+    conf.genesis_commitments()?
+        .check(&genesis_block.block_header)?;
 
     let pow_config = conf.pow_config();
     let pow = Arc::new(PowComputer::new(pow_config.use_octopus()));
@@ -228,6 +234,7 @@ pub fn initialize_common_modules(
         conf.data_mananger_config(),
         pow.clone(),
     ));
+    data_man.check_stored_true_genesis()?;
 
     let verification_config = conf.verification_config(machine.clone());
     let txpool = Arc::new(TransactionPool::new(
@@ -239,11 +246,12 @@ pub fn initialize_common_modules(
 
     let statistics = Arc::new(Statistics::new());
     let notifications = Notifications::init();
+    let clock = system_clock();
 
     let consensus = Arc::new(ConsensusGraph::new(
         consensus_conf,
         txpool.clone(),
-        statistics,
+        statistics.clone(),
         data_man.clone(),
         pow_config.clone(),
         pow.clone(),
@@ -251,11 +259,12 @@ pub fn initialize_common_modules(
         conf.execution_config(),
         conf.verification_config(machine.clone()),
         node_type,
+        clock.clone(),
     ));
 
     let sync_config = conf.sync_graph_config();
 
-    let sync_graph = Arc::new(SynchronizationGraph::new(
+    let sync_graph = Arc::new(SynchronizationGraph::with_clock(
         consensus.clone(),
         verification_config,
         pow_config,
@@ -263,6 +272,7 @@ pub fn initialize_common_modules(
         sync_config,
         notifications.clone(),
         machine.clone(),
+        clock,
     ));
 
     let network = {
@@ -376,6 +386,19 @@ pub fn initialize_not_light_node_modules(
     ));
     sync.register().unwrap();
 
+    if let Some(feed) = &conf.raw_conf.replica_block_feed {
+        ReplicaBlockFeed::start(
+            ReplicaFeedSource::parse(feed),
+            sync.clone(),
+            consensus.clone(),
+            conf.raw_conf
+                .replica_consistency_snapshot
+                .as_ref()
+                .map(Into::into),
+        )
+        .map_err(|e| format!("Failed to start replica block feed: {}", e))?;
+    }
+
     if let Some(print_memory_usage_period_s) =
         conf.raw_conf.print_memory_usage_period_s
     {
@@ -416,6 +439,38 @@ pub fn initialize_not_light_node_modules(
         ).expect("Memory usage thread start fails");
     }
 
+    if let Some(watchdog_conf) = conf.chain_watchdog_config() {
+        let watchdog =
+            ChainWatchdog::new(watchdog_conf, &consensus, statistics);
+        spawn_periodic(
+            "Chain Watchdog",
+            watchdog.sample_period(),
+            exit.clone(),
+            move || watchdog.sample(),
+        );
+    }
+
+    if let Some(auditor_conf) = conf.deferred_root_auditor_config() {
+        let auditor = DeferredRootAuditor::new(auditor_conf, &consensus);
+        spawn_periodic(
+            "Deferred Root Auditor",
+            auditor.audit_period(),
+            exit.clone(),
+            move || auditor.audit(),
+        );
+    }
+
+    if let Some(watcher_conf) = conf.config_watcher_config() {
+        let watcher =
+            ConfigWatcher::new(watcher_conf, conf, &consensus, &sync)?;
+        spawn_periodic(
+            "Config Watcher",
+            watcher.poll_period(),
+            exit.clone(),
+            move || watcher.check(),
+        );
+    }
+
     let (maybe_txgen, maybe_direct_txgen) = initialize_txgens(
         consensus.clone(),
         txpool.clone(),
@@ -558,6 +613,26 @@ pub fn initialize_not_light_node_modules(
     ))
 }
 
+/// Spawn a thread named `name` that calls `f` every `interval`, until `f`
+/// returns `false` or the client exits.
+fn spawn_periodic<F>(
+    name: &str, interval: Duration, exit: Arc<(Mutex<bool>, Condvar)>, mut f: F,
+) where F: FnMut() -> bool + Send + 'static {
+    thread::Builder::new()
+        .name(name.into())
+        .spawn(move || loop {
+            let mut exit_lock = exit.0.lock();
+            if !exit.1.wait_for(&mut exit_lock, interval).timed_out() {
+                return;
+            }
+            drop(exit_lock);
+            if !f() {
+                return;
+            }
+        })
+        .unwrap_or_else(|e| panic!("{} thread start fails: {}", name, e));
+}
+
 pub fn initialize_txgens(
     consensus: Arc<ConsensusGraph>, txpool: Arc<TransactionPool>,
     sync: Arc<SynchronizationService>, secret_store: SharedSecretStore,
@@ -727,6 +802,7 @@ pub mod delegate_convert {
 pub use crate::configuration::Configuration;
 use crate::{
     accounts::{account_provider, keys_path},
+    config_watcher::ConfigWatcher,
     configuration::parse_config_address_string,
     rpc::{
         extractor::RpcExtractor,
@@ -743,11 +819,16 @@ use cfx_storage::StorageManager;
 use cfx_types::{address_util::AddressUtil, Address, U256};
 use cfxcore::{
     block_data_manager::BlockDataManager,
+    clock::system_clock,
+    consensus::consensus_inner::deferred_root_auditor::DeferredRootAuditor,
     machine::{new_machine_with_builtin, Machine},
     pow::PowComputer,
     spec::genesis::{self, genesis_block, DEV_GENESIS_KEY_PAIR_2},
-    statistics::Statistics,
-    sync::SyncPhaseType,
+    statistics::{chain_watchdog::ChainWatchdog, Statistics},
+    sync::{
+        replica_feed::{ReplicaBlockFeed, ReplicaFeedSource},
+        SyncPhaseType,
+    },
     vm_factory::VmFactory,
     ConsensusGraph, LightProvider, NodeType, Notifications, Stopable,
     SynchronizationGraph, SynchronizationService, TransactionPool,
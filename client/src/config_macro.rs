@@ -66,39 +66,10 @@ macro_rules! build_config{
             // Replace the ones from config file with the ones
             // from commandline if duplicates.
             pub fn parse(matches: &clap::ArgMatches) -> Result<RawConfiguration, String> {
-                let mut config = RawConfiguration::default();
-
-                let config_filename = matches.value_of("config");
-                if config_filename.is_some() {
-                    let mut config_file = File::open(config_filename.unwrap())
-                        .map_err(|e| format!("failed to open configuration file: {:?}", e))?;
-
-                    let mut config_str = String::new();
-                    config_file
-                        .read_to_string(&mut config_str)
-                        .map_err(|e| format!("failed to read configuration file: {:?}", e))?;
-
-                    let config_value = config_str.parse::<toml::Value>()
-                        .map_err(|e| format!("failed to parse configuration file: {:?}", e))?;
-                    $(
-                        if let Some(value) = config_value.get(stringify!($name)) {
-                            config.$name = if_option!(
-                                $($type)+,
-                                THEN{ Some(value.clone().try_into().map_err(|_| concat!("Invalid ", stringify!($name)).to_owned())?) }
-                                ELSE{ value.clone().try_into().map_err(|_| concat!("Invalid ", stringify!($name)).to_owned())? }
-                            );
-                        }
-                    )*
-                    $(
-                        if let Some(value) = config_value.get(stringify!($c_name)) {
-                            config.$c_name = if_option!(
-                                $($c_type)+,
-                                THEN{ Some($converter(value.as_str().unwrap())?) }
-                                ELSE{ $converter(value.as_str().unwrap())? }
-                            )
-                        }
-                    )*
-                }
+                let mut config = match matches.value_of("config") {
+                    Some(config_filename) => RawConfiguration::from_file(config_filename)?,
+                    None => RawConfiguration::default(),
+                };
 
                 $(
                     if let Some(value) = matches.value_of(underscore_to_hyphen!(stringify!($name))) {
@@ -120,6 +91,42 @@ macro_rules! build_config{
                 )*
                 Ok(config)
             }
+
+            // Parse arguments from config file only, and use the default
+            // values for the ones not in it.
+            pub fn from_file(config_filename: &str) -> Result<RawConfiguration, String> {
+                let mut config = RawConfiguration::default();
+
+                let mut config_file = File::open(config_filename)
+                    .map_err(|e| format!("failed to open configuration file: {:?}", e))?;
+
+                let mut config_str = String::new();
+                config_file
+                    .read_to_string(&mut config_str)
+                    .map_err(|e| format!("failed to read configuration file: {:?}", e))?;
+
+                let config_value = config_str.parse::<toml::Value>()
+                    .map_err(|e| format!("failed to parse configuration file: {:?}", e))?;
+                $(
+                    if let Some(value) = config_value.get(stringify!($name)) {
+                        config.$name = if_option!(
+                            $($type)+,
+                            THEN{ Some(value.clone().try_into().map_err(|_| concat!("Invalid ", stringify!($name)).to_owned())?) }
+                            ELSE{ value.clone().try_into().map_err(|_| concat!("Invalid ", stringify!($name)).to_owned())? }
+                        );
+                    }
+                )*
+                $(
+                    if let Some(value) = config_value.get(stringify!($c_name)) {
+                        config.$c_name = if_option!(
+                            $($c_type)+,
+                            THEN{ Some($converter(value.as_str().unwrap())?) }
+                            ELSE{ $converter(value.as_str().unwrap())? }
+                        )
+                    }
+                )*
+                Ok(config)
+            }
         }
     }
 }
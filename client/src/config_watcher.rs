@@ -0,0 +1,303 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::configuration::{Configuration, RawConfiguration};
+use cfxcore::{ConsensusGraph, SynchronizationService};
+use parking_lot::Mutex;
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    sync::{Arc, Weak},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Clone, Debug)]
+pub struct ConfigWatcherConfig {
+    /// The configuration file to watch.
+    pub config_file: String,
+    /// The interval between two checks of the file.
+    pub poll_period: Duration,
+    /// The file where the applied and the rejected changes are appended.
+    pub audit_log_path: PathBuf,
+}
+
+macro_rules! tunable_parameters {
+    ($($name:ident: $type:ty),* $(,)?) => {
+        /// The configuration parameters that can be changed while the node
+        /// is running.
+        #[derive(Clone, Debug, PartialEq)]
+        struct TunableParameters {
+            $($name: $type,)*
+        }
+
+        impl TunableParameters {
+            fn from_raw(raw: &RawConfiguration) -> Self {
+                TunableParameters {
+                    $($name: raw.$name.clone(),)*
+                }
+            }
+
+            /// Return the parameters with the values changed from `old_file`
+            /// to `new_file` taken from `new_file`, so a value set on the
+            /// command line is kept until it is changed in the file.
+            fn merge(&self, old_file: &Self, new_file: &Self) -> Self {
+                TunableParameters {
+                    $($name: if old_file.$name != new_file.$name {
+                        new_file.$name.clone()
+                    } else {
+                        self.$name.clone()
+                    },)*
+                }
+            }
+
+            fn changes(&self, new: &Self) -> Vec<String> {
+                let mut changes = Vec::new();
+                $(
+                    if self.$name != new.$name {
+                        changes.push(format!(
+                            "{}: {:?} -> {:?}",
+                            stringify!($name),
+                            self.$name,
+                            new.$name
+                        ));
+                    }
+                )*
+                changes
+            }
+        }
+    };
+}
+
+tunable_parameters! {
+    ledger_cache_size: usize,
+    sync_expire_block_timeout_s: u64,
+    optimistic_execution_min_depth: u64,
+    optimistic_execution_max_depth: u64,
+    get_logs_epoch_batch_size: usize,
+    get_logs_filter_max_epoch_range: Option<u64>,
+}
+
+impl TunableParameters {
+    fn validate(&self) -> Result<(), String> {
+        if self.ledger_cache_size == 0 {
+            return Err("ledger_cache_size must be positive".into());
+        }
+        if self.sync_expire_block_timeout_s == 0 {
+            return Err("sync_expire_block_timeout_s must be positive".into());
+        }
+        if self.optimistic_execution_min_depth
+            > self.optimistic_execution_max_depth
+        {
+            return Err("optimistic_execution_min_depth must not exceed \
+                        optimistic_execution_max_depth"
+                .into());
+        }
+        if self.get_logs_epoch_batch_size == 0 {
+            return Err("get_logs_epoch_batch_size must be positive".into());
+        }
+        if self.get_logs_filter_max_epoch_range == Some(0) {
+            return Err(
+                "get_logs_filter_max_epoch_range must be positive".into()
+            );
+        }
+        Ok(())
+    }
+
+    fn apply(&self, consensus: &ConsensusGraph, sync: &SynchronizationService) {
+        consensus
+            .data_man
+            .set_ledger_cache_size(self.ledger_cache_size);
+        sync.protocol_config()
+            .sync_expire_block_timeout
+            .set(Duration::from_secs(self.sync_expire_block_timeout_s));
+        consensus.set_optimistic_execution_depth(
+            self.optimistic_execution_min_depth,
+            self.optimistic_execution_max_depth,
+        );
+        consensus
+            .config
+            .get_logs_epoch_batch_size
+            .set(self.get_logs_epoch_batch_size);
+        consensus
+            .config
+            .get_logs_filter_max_epoch_range
+            .set(self.get_logs_filter_max_epoch_range);
+    }
+}
+
+struct WatcherState {
+    /// The modification time of the file when it was last loaded.
+    modified: Option<SystemTime>,
+    /// The parameters in the file when it was last loaded.
+    file_params: TunableParameters,
+    /// The parameters in use.
+    live_params: TunableParameters,
+}
+
+/// `ConfigWatcher` reloads the configuration file when it is modified, and
+/// applies the changed tunable parameters to the running node.
+///
+/// The other parameters in the file are ignored until the node is restarted.
+/// A set of changes is applied only if the resulting parameters are valid,
+/// and every applied or rejected set is recorded in the audit log.
+pub struct ConfigWatcher {
+    config: ConfigWatcherConfig,
+    consensus: Weak<ConsensusGraph>,
+    sync: Weak<SynchronizationService>,
+    state: Mutex<WatcherState>,
+}
+
+impl ConfigWatcher {
+    pub fn new(
+        config: ConfigWatcherConfig, conf: &Configuration,
+        consensus: &Arc<ConsensusGraph>, sync: &Arc<SynchronizationService>,
+    ) -> Result<Self, String>
+    {
+        let modified = fs::metadata(&config.config_file)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        let file_params = TunableParameters::from_raw(
+            &RawConfiguration::from_file(&config.config_file)?,
+        );
+        let state = WatcherState {
+            modified,
+            file_params,
+            live_params: TunableParameters::from_raw(&conf.raw_conf),
+        };
+        Ok(ConfigWatcher {
+            config,
+            consensus: Arc::downgrade(consensus),
+            sync: Arc::downgrade(sync),
+            state: Mutex::new(state),
+        })
+    }
+
+    pub fn poll_period(&self) -> Duration { self.config.poll_period }
+
+    /// Reload the configuration file if it is modified since the last load.
+    /// Return `false` if the node is shut down.
+    pub fn check(&self) -> bool {
+        let (consensus, sync) =
+            match (self.consensus.upgrade(), self.sync.upgrade()) {
+                (Some(consensus), Some(sync)) => (consensus, sync),
+                _ => return false,
+            };
+        let mut state = self.state.lock();
+        let modified = match fs::metadata(&self.config.config_file)
+            .and_then(|metadata| metadata.modified())
+        {
+            Ok(modified) => modified,
+            Err(e) => {
+                warn!("Failed to check configuration file: {}", e);
+                return true;
+            }
+        };
+        if state.modified == Some(modified) {
+            return true;
+        }
+        state.modified = Some(modified);
+
+        let new_file_params =
+            match RawConfiguration::from_file(&self.config.config_file) {
+                Ok(raw_conf) => TunableParameters::from_raw(&raw_conf),
+                Err(e) => {
+                    warn!("Failed to reload configuration file: {}", e);
+                    return true;
+                }
+            };
+        let new_live_params = state
+            .live_params
+            .merge(&state.file_params, &new_file_params);
+        let changes = state.live_params.changes(&new_live_params).join(", ");
+        if changes.is_empty() {
+            state.file_params = new_file_params;
+            return true;
+        }
+        if let Err(e) = new_live_params.validate() {
+            // `file_params` is kept, so the rejected values are still seen
+            // as changes when the file is corrected.
+            warn!("Rejected configuration changes {}: {}", changes, e);
+            self.audit(&format!("rejected {}: {}", changes, e));
+            return true;
+        }
+        new_live_params.apply(&consensus, &sync);
+        info!("Applied configuration changes {}", changes);
+        self.audit(&format!("applied {}", changes));
+        state.file_params = new_file_params;
+        state.live_params = new_live_params;
+        true
+    }
+
+    fn audit(&self, entry: &str) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let result = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.config.audit_log_path)
+            .and_then(|mut file| writeln!(file, "{} {}", timestamp, entry));
+        if let Err(e) = result {
+            warn!(
+                "Failed to write configuration audit log {:?}: {}",
+                self.config.audit_log_path, e
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TunableParameters;
+
+    fn params() -> TunableParameters {
+        TunableParameters {
+            ledger_cache_size: 1024,
+            sync_expire_block_timeout_s: 7200,
+            optimistic_execution_min_depth: 1,
+            optimistic_execution_max_depth: 4,
+            get_logs_epoch_batch_size: 32,
+            get_logs_filter_max_epoch_range: None,
+        }
+    }
+
+    #[test]
+    fn test_merge_keeps_unchanged_overrides() {
+        let old_file = params();
+        // Set on the command line.
+        let mut live = params();
+        live.ledger_cache_size = 2048;
+
+        let mut new_file = params();
+        new_file.get_logs_epoch_batch_size = 16;
+        let merged = live.merge(&old_file, &new_file);
+        assert_eq!(merged.ledger_cache_size, 2048);
+        assert_eq!(merged.get_logs_epoch_batch_size, 16);
+        assert_eq!(
+            live.changes(&merged),
+            vec!["get_logs_epoch_batch_size: 32 -> 16".to_string()]
+        );
+
+        new_file.ledger_cache_size = 512;
+        let merged = live.merge(&old_file, &new_file);
+        assert_eq!(merged.ledger_cache_size, 512);
+        assert_eq!(live.changes(&merged).len(), 2);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(params().validate().is_ok());
+        let mut invalid = params();
+        invalid.optimistic_execution_min_depth = 5;
+        assert!(invalid.validate().is_err());
+        let mut invalid = params();
+        invalid.get_logs_epoch_batch_size = 0;
+        assert!(invalid.validate().is_err());
+        let mut invalid = params();
+        invalid.get_logs_filter_max_epoch_range = Some(0);
+        assert!(invalid.validate().is_err());
+    }
+}
@@ -2,9 +2,12 @@
 // Conflux is free software and distributed under GNU General Public License.
 // See http://www.gnu.org/licenses/
 
-use crate::rpc::{
-    impls::RpcImplConfiguration, rpc_apis::ApiSet, HttpConfiguration,
-    TcpConfiguration, WsConfiguration,
+use crate::{
+    config_watcher::ConfigWatcherConfig,
+    rpc::{
+        impls::RpcImplConfiguration, rpc_apis::ApiSet, HttpConfiguration,
+        TcpConfiguration, WsConfiguration,
+    },
 };
 use cfx_addr::{cfx_addr_decode, Network};
 use cfx_internal_common::{ChainIdParams, ChainIdParamsInner};
@@ -22,15 +25,25 @@ use cfxcore::{
         DEFAULT_LEDGER_CACHE_SIZE,
         DEFAULT_TARGET_DIFFICULTIES_CACHE_SIZE_IN_COUNT,
     },
+    config_cell::ConfigCell,
     consensus::{
-        consensus_inner::consensus_executor::ConsensusExecutionConfiguration,
+        consensus_inner::{
+            consensus_executor::ConsensusExecutionConfiguration,
+            deferred_root_auditor::DeferredRootAuditorConfig,
+            risk_model::RiskModelConfig,
+            weight_injection_detector::WeightInjectionDetectorConfig,
+        },
+        epoch_exporter::{EpochExportConfig, EpochExportFormat},
         ConsensusConfig, ConsensusInnerConfig,
     },
     consensus_internal_parameters::*,
     consensus_parameters::*,
     light_protocol::LightNodeConfiguration,
     machine::Machine,
-    spec::CommonParams,
+    spec::{
+        genesis::GenesisCommitments, CommonParams, DeferredStateEpochCount,
+    },
+    statistics::chain_watchdog::ChainWatchdogConfig,
     sync::{ProtocolConfiguration, StateSyncConfiguration, SyncGraphConfig},
     sync_parameters::*,
     transaction_pool::TxPoolConfig,
@@ -49,6 +62,7 @@ lazy_static! {
 }
 const BLOCK_DB_DIR_NAME: &str = "blockchain_db";
 const NET_CONFIG_DB_DIR_NAME: &str = "net_config";
+const CONFIG_AUDIT_LOG_FILE_NAME: &str = "config_changes.log";
 
 // usage:
 // ```
@@ -99,6 +113,10 @@ build_config! {
         (debug_invalid_state_root, (bool), false)
         (debug_invalid_state_root_epoch, (Option<String>), None)
         (debug_dump_dir_invalid_state_root, (String), "./storage_db/debug_dump_invalid_state_root/".to_string())
+        (debug_weight_audit_interval, (Option<u64>), None)
+        (debug_dump_dir_weight_audit, (String), "./storage_db/debug_dump_weight_audit/".to_string())
+        (debug_shadow_optimistic_execution, (bool), false)
+        (debug_dump_dir_shadow_execution, (String), "./storage_db/debug_dump_shadow_execution/".to_string())
         // Controls block generation speed.
         // Only effective in `dev` mode
         (dev_block_interval_ms, (Option<u64>), None)
@@ -122,22 +140,36 @@ build_config! {
         // Genesis section.
         (adaptive_weight_beta, (u64), ADAPTIVE_WEIGHT_DEFAULT_BETA)
         (anticone_penalty_ratio, (u64), ANTICONE_PENALTY_RATIO)
+        (anticone_penalty_upper_epoch_count, (u64), ANTICONE_PENALTY_UPPER_EPOCH_COUNT)
         (chain_id, (Option<u32>), None)
         (execute_genesis, (bool), true)
         (default_transition_time, (Option<u64>), None)
         // Snapshot Epoch Count is a consensus parameter. This flag overrides
         // the parameter, which only take effect in `dev` mode.
         (dev_snapshot_epoch_count, (u32), SNAPSHOT_EPOCHS_CAPACITY)
+        (deferred_state_epoch_count, (u64), DEFERRED_STATE_EPOCH_COUNT)
+        // The deferred state epoch count is not changed if not set.
+        (deferred_state_epoch_count_transition_height, (Option<u64>), None)
+        (deferred_state_epoch_count_after_transition, (Option<u64>), None)
         (era_epoch_count, (u64), ERA_DEFAULT_EPOCH_COUNT)
         (heavy_block_difficulty_ratio, (u64), HEAVY_BLOCK_DEFAULT_DIFFICULTY_RATIO)
         (genesis_accounts, (Option<String>), None)
+        // The expected commitments of the genesis block in hex. The node does
+        // not start if the genesis block computed from the genesis accounts
+        // has different ones.
+        (genesis_receipts_root, (Option<String>), None)
         (genesis_secrets, (Option<String>), None)
+        (genesis_state_root, (Option<String>), None)
         (initial_difficulty, (Option<u64>), None)
+        (max_sender_gas_per_epoch, (Option<u64>), None)
+        (max_sender_gas_per_epoch_transition_height, (Option<u64>), None)
+        (epoch_reward_bound_transition_height, (Option<u64>), None)
         (tanzanite_transition_height, (u64), TANZANITE_HEIGHT)
         (unnamed_21autumn_transition_number, (Option<u64>), None)
         (unnamed_21autumn_transition_height, (Option<u64>), None)
         (unnamed_21autumn_cip71_deferred_transition, (Option<u64>), None)
         (referee_bound, (usize), REFEREE_DEFAULT_BOUND)
+        (reward_epoch_count, (u64), REWARD_EPOCH_COUNT)
         (timer_chain_beta, (u64), TIMER_CHAIN_DEFAULT_BETA)
         (timer_chain_block_difficulty_ratio, (u64), TIMER_CHAIN_BLOCK_DEFAULT_DIFFICULTY_RATIO)
         // FIXME: this is part of spec.
@@ -201,6 +233,7 @@ build_config! {
         (received_tx_index_maintain_timeout_ms, (u64), 300_000)
         (request_block_with_public, (bool), false)
         (send_tx_period_ms, (u64), 1300)
+        (snapshot_archive_dir, (Option<String>), None)
         (snapshot_candidate_request_timeout_ms, (u64), 10_000)
         (snapshot_chunk_request_timeout_ms, (u64), 30_000)
         (snapshot_manifest_request_timeout_ms, (u64), 30_000)
@@ -235,6 +268,8 @@ build_config! {
         (tx_cache_index_maintain_timeout_ms, (u64), 300_000)
         (tx_pool_size, (usize), 200_000)
         (tx_pool_min_tx_gas_price, (u64), 1)
+        (tx_pool_packing_fairness_min_gas_price, (Option<u64>), None)
+        (tx_pool_expiry_epochs, (Option<u64>), None)
         (tx_weight_scaling, (u64), 1)
         (tx_weight_exp, (u8), 1)
 
@@ -271,17 +306,63 @@ build_config! {
         // General/Unclassified section.
         (account_provider_refresh_time_ms, (u64), 1000)
         (check_phase_change_period_ms, (u64), 1000)
+        // The chain watchdog is disabled if the period is not set.
+        (chain_watchdog_period_ms, (Option<u64>), None)
+        (chain_watchdog_max_execution_lag, (u64), 100)
+        (chain_watchdog_sync_stall_timeout_s, (u64), 600)
+        (chain_watchdog_dump_dir, (Option<String>), None)
+        // The deferred root auditor is disabled if not set.
+        (deferred_root_audit_period_ms, (Option<u64>), None)
+        (deferred_root_audit_depth, (u64), 200)
+        (deferred_root_audit_dump_dir, (Option<String>), None)
+        // The configuration file is not reloaded if not set.
+        (config_reload_period_ms, (Option<u64>), None)
         (enable_optimistic_execution, (bool), true)
+        (optimistic_execution_min_depth, (u64), 1)
+        (optimistic_execution_max_depth, (u64), 4)
+        // The epoch sets are kept in memory if not set.
+        (epoch_set_eviction_depth, (Option<u64>), None)
         (future_block_buffer_capacity, (usize), 32768)
+        (block_propagation_history_size, (usize), 10000)
+        // Block synchronization is not limited by memory if not set.
+        (sync_memory_budget_mb, (Option<usize>), None)
+        (sync_max_consensus_queue_len, (Option<usize>), None)
+        // The orphan headers are all kept in memory if not set.
+        (sync_graph_max_orphan_headers, (Option<usize>), None)
         (get_logs_filter_max_limit, (Option<usize>), None)
         (get_logs_filter_max_epoch_range, (Option<u64>), None)
         (get_logs_epoch_batch_size, (usize), 32)
+        (finalized_epoch_outbox_risk_threshold, (Option<f64>), None)
+        (confirmation_risk_model, (String), "heuristic".to_string())
+        (confirmation_risk_attacker_fraction, (f64), 0.2)
+        // The weight injection detector is disabled if not set.
+        (weight_injection_window_s, (Option<u64>), None)
+        (weight_injection_burst_ratio, (f64), 3.0)
+        (weight_injection_author_concentration, (f64), 0.8)
+        (weight_injection_stale_block_lag, (u64), 20)
+        (weight_injection_unusual_weight_ratio, (f64), 0.5)
+        (weight_injection_alert_confirmation_risk, (Option<f64>), None)
+        (epoch_export_dir, (Option<String>), None)
         (max_trans_count_received_in_catch_up, (u64), 60_000)
         (persist_tx_index, (bool), false)
+        (index_transactions_by_sender, (bool), false)
         (persist_block_number_index, (bool), false)
+        (pinned_pivot_header_count, (usize), 1000)
         (print_memory_usage_period_s, (Option<u64>), None)
+        (replica_block_feed, (Option<String>), None)
+        (header_graph_mode, (bool), false)
+        // The account read cache of the RPCs is disabled if it is 0.
+        (account_read_cache_size, (usize), 100_000)
+        (replica_consistency_snapshot, (Option<String>), None)
         (target_block_gas_limit, (u64), DEFAULT_TARGET_BLOCK_GAS_LIMIT)
         (executive_trace, (bool), false)
+        (execution_worker_count, (usize), 1)
+        (head_execution_weight, (u32), 4)
+        (replay_execution_weight, (u32), 1)
+        (enable_execution_dependency_prefetch, (bool), true)
+        (execution_verification_concurrency, (usize), 1)
+        (enable_block_log_index, (bool), false)
+        (epoch_execution_checkpoint_interval, (Option<usize>), None)
 
         // TreeGraph Section.
         (candidate_pivot_waiting_timeout_ms, (u64), 10_000)
@@ -326,18 +407,23 @@ build_config! {
             vec![ProvideExtraSnapshotSyncConfig::StableCheckpoint],
             ProvideExtraSnapshotSyncConfig::parse_config_list)
         (node_type, (Option<NodeType>), None, NodeType::from_str)
+        (epoch_export_format, (EpochExportFormat), EpochExportFormat::Jsonl,
+            EpochExportFormat::from_str)
         (public_rpc_apis, (ApiSet), ApiSet::Safe, ApiSet::from_str)
     }
 }
 
 pub struct Configuration {
     pub raw_conf: RawConfiguration,
+    /// The configuration file given on the command line.
+    pub config_file: Option<String>,
 }
 
 impl Default for Configuration {
     fn default() -> Self {
         Configuration {
             raw_conf: Default::default(),
+            config_file: None,
         }
     }
 }
@@ -346,6 +432,7 @@ impl Configuration {
     pub fn parse(matches: &clap::ArgMatches) -> Result<Configuration, String> {
         let mut config = Configuration::default();
         config.raw_conf = RawConfiguration::parse(matches)?;
+        config.config_file = matches.value_of("config").map(Into::into);
 
         if config.is_dev_mode() {
             if config.raw_conf.jsonrpc_ws_port.is_none() {
@@ -486,6 +573,25 @@ impl Configuration {
         (db_dir, db_config)
     }
 
+    pub fn genesis_commitments(&self) -> Result<GenesisCommitments, String> {
+        let parse = |name: &str, hex: &Option<String>| match hex {
+            Some(hex) => H256::from_str(hex.trim_start_matches("0x"))
+                .map(Some)
+                .map_err(|e| format!("Invalid {}: {:?}", name, e)),
+            None => Ok(None),
+        };
+        Ok(GenesisCommitments {
+            state_root: parse(
+                "genesis_state_root",
+                &self.raw_conf.genesis_state_root,
+            )?,
+            receipts_root: parse(
+                "genesis_receipts_root",
+                &self.raw_conf.genesis_receipts_root,
+            )?,
+        })
+    }
+
     pub fn chain_id_params(&self) -> ChainIdParams {
         if CHAIN_ID.read().is_none() {
             let mut to_init = CHAIN_ID.write();
@@ -507,11 +613,12 @@ impl Configuration {
     }
 
     pub fn consensus_config(&self) -> ConsensusConfig {
-        let enable_optimistic_execution = if DEFERRED_STATE_EPOCH_COUNT <= 1 {
-            false
-        } else {
-            self.raw_conf.enable_optimistic_execution
-        };
+        let enable_optimistic_execution =
+            if self.common_params().deferred_state_epoch_count.max() <= 1 {
+                false
+            } else {
+                self.raw_conf.enable_optimistic_execution
+            };
         let mut conf = ConsensusConfig {
             chain_id: self.chain_id_params(),
             inner_conf: ConsensusInnerConfig {
@@ -525,6 +632,12 @@ impl Configuration {
                 timer_chain_beta: self.raw_conf.timer_chain_beta,
                 era_epoch_count: self.raw_conf.era_epoch_count,
                 enable_optimistic_execution,
+                optimistic_execution_min_depth: self
+                    .raw_conf
+                    .optimistic_execution_min_depth,
+                optimistic_execution_max_depth: self
+                    .raw_conf
+                    .optimistic_execution_max_depth,
                 enable_state_expose: self.raw_conf.enable_state_expose,
 
                 debug_dump_dir_invalid_state_root: if self
@@ -547,14 +660,59 @@ impl Configuration {
                     }
                     None => None,
                 },
+                debug_weight_audit_interval: self
+                    .raw_conf
+                    .debug_weight_audit_interval,
+                debug_dump_dir_weight_audit: self
+                    .raw_conf
+                    .debug_weight_audit_interval
+                    .map(|_| self.raw_conf.debug_dump_dir_weight_audit.clone()),
+                epoch_set_eviction_depth: self
+                    .raw_conf
+                    .epoch_set_eviction_depth,
             },
             bench_mode: false,
             transaction_epoch_bound: self.raw_conf.transaction_epoch_bound,
             referee_bound: self.raw_conf.referee_bound,
-            get_logs_epoch_batch_size: self.raw_conf.get_logs_epoch_batch_size,
-            get_logs_filter_max_epoch_range: self.raw_conf.get_logs_filter_max_epoch_range,
+            get_logs_epoch_batch_size: ConfigCell::new(
+                self.raw_conf.get_logs_epoch_batch_size,
+            ),
+            get_logs_filter_max_epoch_range: ConfigCell::new(
+                self.raw_conf.get_logs_filter_max_epoch_range,
+            ),
             sync_state_starting_epoch: self.raw_conf.sync_state_starting_epoch,
             sync_state_epoch_gap: self.raw_conf.sync_state_epoch_gap,
+            finalized_epoch_outbox_risk_threshold: self
+                .raw_conf
+                .finalized_epoch_outbox_risk_threshold,
+            confirmation_risk_model: self.confirmation_risk_model(),
+            weight_injection_detector: self
+                .raw_conf
+                .weight_injection_window_s
+                .map(|window_s| WeightInjectionDetectorConfig {
+                    window: Duration::from_secs(window_s.max(1)),
+                    burst_ratio: self.raw_conf.weight_injection_burst_ratio,
+                    author_concentration: self
+                        .raw_conf
+                        .weight_injection_author_concentration,
+                    stale_block_lag: self
+                        .raw_conf
+                        .weight_injection_stale_block_lag,
+                    unusual_weight_ratio: self
+                        .raw_conf
+                        .weight_injection_unusual_weight_ratio,
+                    alert_confirmation_risk: self
+                        .raw_conf
+                        .weight_injection_alert_confirmation_risk,
+                }),
+            epoch_export: self.raw_conf.epoch_export_dir.clone().map(|dir| {
+                EpochExportConfig {
+                    dir,
+                    format: self.raw_conf.epoch_export_format,
+                }
+            }),
+            header_graph_mode: self.raw_conf.header_graph_mode,
+            account_read_cache_size: self.raw_conf.account_read_cache_size,
         };
         match self.raw_conf.node_type {
             Some(NodeType::Archive) => {
@@ -752,9 +910,9 @@ impl Configuration {
                 .raw_conf
                 .max_unprocessed_block_size_mb
                 * 1_000_000,
-            sync_expire_block_timeout: Duration::from_secs(
+            sync_expire_block_timeout: ConfigCell::new(Duration::from_secs(
                 self.raw_conf.sync_expire_block_timeout_s,
-            ),
+            )),
             allow_phase_change_without_peer: if self.is_dev_mode() {
                 true
             } else {
@@ -775,6 +933,7 @@ impl Configuration {
             manifest_request_timeout: Duration::from_millis(
                 self.raw_conf.snapshot_manifest_request_timeout_ms,
             ),
+            archive_dir: self.raw_conf.snapshot_archive_dir.clone(),
         }
     }
 
@@ -813,6 +972,10 @@ impl Configuration {
                 * self.raw_conf.era_epoch_count as f64)
                 as usize,
             strict_tx_index_gc: self.raw_conf.strict_tx_index_gc,
+            pinned_pivot_header_count: self.raw_conf.pinned_pivot_header_count,
+            index_transactions_by_sender: self
+                .raw_conf
+                .index_transactions_by_sender,
         };
 
         // By default, we do not keep the block data for additional period,
@@ -851,6 +1014,65 @@ impl Configuration {
         conf
     }
 
+    pub fn chain_watchdog_config(&self) -> Option<ChainWatchdogConfig> {
+        self.raw_conf.chain_watchdog_period_ms.map(|period_ms| {
+            ChainWatchdogConfig {
+                sample_period: Duration::from_millis(period_ms),
+                max_execution_lag_epochs: self
+                    .raw_conf
+                    .chain_watchdog_max_execution_lag,
+                sync_stall_timeout: Duration::from_secs(
+                    self.raw_conf.chain_watchdog_sync_stall_timeout_s,
+                ),
+                diagnostic_dump_dir: self
+                    .raw_conf
+                    .chain_watchdog_dump_dir
+                    .clone(),
+            }
+        })
+    }
+
+    pub fn deferred_root_auditor_config(
+        &self,
+    ) -> Option<DeferredRootAuditorConfig> {
+        self.raw_conf
+            .deferred_root_audit_period_ms
+            .map(|period_ms| DeferredRootAuditorConfig {
+                audit_period: Duration::from_millis(period_ms),
+                depth: self.raw_conf.deferred_root_audit_depth,
+                dump_dir: self.raw_conf.deferred_root_audit_dump_dir.clone(),
+            })
+    }
+
+    fn confirmation_risk_model(&self) -> RiskModelConfig {
+        match self.raw_conf.confirmation_risk_model.as_str() {
+            "heuristic" => RiskModelConfig::Heuristic,
+            "analytic" => {
+                let fraction =
+                    self.raw_conf.confirmation_risk_attacker_fraction;
+                if !(fraction > 0.0 && fraction < 0.5) {
+                    panic!("Invalid confirmation_risk_attacker_fraction parameter!");
+                }
+                RiskModelConfig::Analytic {
+                    attacker_hash_power_fraction: fraction,
+                }
+            }
+            _ => panic!("Invalid confirmation_risk_model parameter!"),
+        }
+    }
+
+    pub fn config_watcher_config(&self) -> Option<ConfigWatcherConfig> {
+        let config_file = self.config_file.clone()?;
+        self.raw_conf.config_reload_period_ms.map(|period_ms| {
+            ConfigWatcherConfig {
+                config_file,
+                poll_period: Duration::from_millis(period_ms),
+                audit_log_path: Path::new(&self.raw_conf.conflux_data_dir)
+                    .join(CONFIG_AUDIT_LOG_FILE_NAME),
+            }
+        })
+    }
+
     pub fn sync_graph_config(&self) -> SyncGraphConfig {
         SyncGraphConfig {
             future_block_buffer_capacity: self
@@ -858,6 +1080,16 @@ impl Configuration {
                 .future_block_buffer_capacity,
             enable_state_expose: self.raw_conf.enable_state_expose,
             is_consortium: self.raw_conf.is_consortium,
+            block_propagation_history_size: self
+                .raw_conf
+                .block_propagation_history_size,
+            memory_budget_bytes: self
+                .raw_conf
+                .sync_memory_budget_mb
+                .map(|mb| mb * 1024 * 1024),
+            max_consensus_queue_len: self.raw_conf.sync_max_consensus_queue_len,
+            header_graph_mode: self.raw_conf.header_graph_mode,
+            max_orphan_headers: self.raw_conf.sync_graph_max_orphan_headers,
         }
     }
 
@@ -889,6 +1121,10 @@ impl Configuration {
                 DEFAULT_TARGET_BLOCK_GAS_LIMIT / 2,
             )),
             min_tx_price: self.raw_conf.tx_pool_min_tx_gas_price,
+            packing_fairness_min_gas_price: self
+                .raw_conf
+                .tx_pool_packing_fairness_min_gas_price,
+            expiry_epochs: self.raw_conf.tx_pool_expiry_epochs,
             tx_weight_scaling: self.raw_conf.tx_weight_scaling,
             tx_weight_exp: self.raw_conf.tx_weight_exp,
             target_block_gas_limit: self.raw_conf.target_block_gas_limit,
@@ -954,6 +1190,27 @@ impl Configuration {
     pub fn execution_config(&self) -> ConsensusExecutionConfiguration {
         ConsensusExecutionConfiguration {
             executive_trace: self.raw_conf.executive_trace,
+            execution_worker_count: self.raw_conf.execution_worker_count,
+            head_execution_weight: self.raw_conf.head_execution_weight,
+            replay_execution_weight: self.raw_conf.replay_execution_weight,
+            enable_dependency_prefetch: self
+                .raw_conf
+                .enable_execution_dependency_prefetch,
+            execution_verification_concurrency: self
+                .raw_conf
+                .execution_verification_concurrency,
+            enable_block_log_index: self.raw_conf.enable_block_log_index,
+            epoch_execution_checkpoint_interval: self
+                .raw_conf
+                .epoch_execution_checkpoint_interval,
+            debug_dump_dir_shadow_execution: if self
+                .raw_conf
+                .debug_shadow_optimistic_execution
+            {
+                Some(self.raw_conf.debug_dump_dir_shadow_execution.clone())
+            } else {
+                None
+            },
         }
     }
 
@@ -1048,9 +1305,33 @@ impl Configuration {
 
         params.chain_id = self.chain_id_params();
         params.anticone_penalty_ratio = self.raw_conf.anticone_penalty_ratio;
+        params.reward_epoch_count = self.raw_conf.reward_epoch_count;
+        params.anticone_penalty_upper_epoch_count =
+            self.raw_conf.anticone_penalty_upper_epoch_count;
+        params.deferred_state_epoch_count = DeferredStateEpochCount {
+            initial: self.raw_conf.deferred_state_epoch_count,
+            after_transition: self
+                .raw_conf
+                .deferred_state_epoch_count_after_transition
+                .unwrap_or(self.raw_conf.deferred_state_epoch_count),
+            transition_height: self
+                .raw_conf
+                .deferred_state_epoch_count_transition_height
+                .unwrap_or(u64::MAX),
+        };
+        params.max_sender_gas_per_epoch =
+            self.raw_conf.max_sender_gas_per_epoch.map(U256::from);
 
         params.transition_heights.cip40 =
             self.raw_conf.tanzanite_transition_height;
+        params.transition_heights.sender_gas_budget = self
+            .raw_conf
+            .max_sender_gas_per_epoch_transition_height
+            .unwrap_or(default_transition_time);
+        params.transition_heights.epoch_reward_bound = self
+            .raw_conf
+            .epoch_reward_bound_transition_height
+            .unwrap_or(default_transition_time);
         params.transition_numbers.cip62 = if self.is_test_or_dev_mode() {
             0u64
         } else {
@@ -19,6 +19,7 @@ mod config_macro;
 pub mod accounts;
 pub mod archive;
 pub mod common;
+pub mod config_watcher;
 pub mod configuration;
 pub mod full;
 pub mod light;
@@ -36,7 +36,10 @@ use primitives::{
 use random_crash::*;
 use rlp::Rlp;
 use rustc_hex::ToHex;
-use std::{collections::BTreeMap, net::SocketAddr, sync::Arc};
+use std::{
+    collections::BTreeMap, convert::TryFrom, net::SocketAddr, sync::Arc,
+    time::Duration,
+};
 use txgen::{DirectTransactionGenerator, TransactionGenerator};
 // To convert from RpcResult to BoxFuture by delegate! macro automatically.
 use crate::{
@@ -54,22 +57,29 @@ use crate::{
         types::{
             sign_call, Account as RpcAccount, AccountPendingInfo,
             AccountPendingTransactions, BlameInfo, Block as RpcBlock,
-            BlockHashOrEpochNumber, Bytes, CallRequest,
-            CheckBalanceAgainstTransactionResponse, ConsensusGraphStates,
-            EpochNumber, EstimateGasAndCollateralResponse, Log as RpcLog,
-            LogFilter as RpcFilter, PackedOrExecuted, Receipt as RpcReceipt,
-            RewardInfo as RpcRewardInfo, SendTxRequest, Status as RpcStatus,
+            BlockConsensusInfo, BlockHashOrEpochNumber, BlockPropagationRecord,
+            Bytes, CallRequest, CheckBalanceAgainstTransactionResponse,
+            ConsensusBlockIdQuery, ConsensusBlockIds, ConsensusGraphStates,
+            EpochConfirmationRisk, EpochExportCursor, EpochNumber,
+            EpochPackingStatistics, EstimateGasAndCollateralResponse,
+            ExecutionWorkerStatus, GhastDecision, Log as RpcLog,
+            LogFilter as RpcFilter, PackedOrExecuted, PivotBlockAnnotation,
+            PivotSwitchEvent, Receipt as RpcReceipt,
+            RewardDetails as RpcRewardDetails, RewardInfo as RpcRewardInfo,
+            SendRawTransactionResult, SendTxRequest, SnapshotArchiveInfo,
+            StateAvailability, StateRegenerationProgress, Status as RpcStatus,
             SyncGraphStates, Transaction as RpcTransaction, TxPoolPendingInfo,
-            TxWithPoolInfo,
+            TxSkipDiagnosis, TxWithPoolInfo,
         },
         RpcResult,
     },
 };
 use cfx_addr::Network;
-use cfx_parameters::consensus_internal::REWARD_EPOCH_COUNT;
 use cfxcore::{
-    consensus::{MaybeExecutedTxExtraInfo, TransactionInfo},
-    consensus_parameters::DEFERRED_STATE_EPOCH_COUNT,
+    consensus::{
+        consensus_inner::state_regeneration::StateRegenerationBudget,
+        MaybeExecutedTxExtraInfo, TransactionInfo,
+    },
     executive::revert_reason_decode,
     spec::genesis::{
         genesis_contract_address_four_year, genesis_contract_address_two_year,
@@ -191,10 +201,11 @@ impl RpcImpl {
             address, epoch_num
         );
 
-        let state_db = self
-            .consensus
-            .get_state_db_by_epoch_number(epoch_num, "num")?;
-        let acc = state_db.get_account(&address.hex_address)?;
+        let acc = self.consensus_graph().get_account_by_epoch_number(
+            &address.hex_address,
+            epoch_num,
+            "num",
+        )?;
 
         Ok(acc.map_or(U256::zero(), |acc| acc.balance).into())
     }
@@ -391,23 +402,21 @@ impl RpcImpl {
         let tx: TransactionWithSignature =
             invalid_params_check("raw", Rlp::new(&raw.into_vec()).as_val())?;
 
-        if tx.transaction_type() == TransactionType::EthereumLike {
-            if let Ok(pubkey) = tx.recover_public() {
-                if !is_compatible_public(&pubkey) {
-                    bail!(invalid_params("tx", "Sending Ethereum like transaction from invalid address: the sender address should start by 0x1 (by Ethereum address rule)."))
-                }
-            } else {
-                bail!(invalid_params(
-                    "tx",
-                    "Can not recover pubkey for Ethereum like tx"
-                ))
-            }
-        }
+        Self::check_ethereum_like_sender(&tx)
+            .map_err(|e| invalid_params("tx", e))?;
 
         let r = self.send_transaction_with_signature(tx);
         if r.is_ok() && self.config.dev_pack_tx_immediately {
-            // Try to pack and execute this new tx.
-            for _ in 0..DEFERRED_STATE_EPOCH_COUNT {
+            // Try to pack and execute this new tx. The tx is packed in the
+            // next epoch, which is executed when the block committing to its
+            // state is about to be generated.
+            let packed_epoch = self.consensus.best_epoch_number() + 1;
+            let epoch_count = self
+                .consensus
+                .deferred_state_epoch_count()
+                .first_committing_height(packed_epoch)
+                - packed_epoch;
+            for _ in 0..epoch_count {
                 self.generate_one_block(
                     1, /* num_txs */
                     self.sync
@@ -448,14 +457,90 @@ impl RpcImpl {
         })
     }
 
-    fn send_transaction_with_signature(
-        &self, tx: TransactionWithSignature,
-    ) -> RpcResult<H256> {
+    fn send_raw_transactions(
+        &self, raw_txs: Vec<Bytes>,
+    ) -> RpcResult<Vec<SendRawTransactionResult>> {
+        let _timer = ScopeTimer::time_scope(SEND_RAW_TX_TIMER.as_ref());
+        info!(
+            "RPC Request: cfx_sendRawTransactions count={}",
+            raw_txs.len()
+        );
+        if self.sync.catch_up_mode() {
+            warn!("Ignore send_raw_transactions request. Cannot send transaction when the node is still in catch-up mode.");
+            bail!(request_rejected_in_catch_up_mode(None));
+        }
+
+        let mut results = vec![None; raw_txs.len()];
+        let mut indices = Vec::new();
+        let mut transactions = Vec::new();
+        for (index, raw) in raw_txs.into_iter().enumerate() {
+            let decoded: Result<TransactionWithSignature, _> =
+                Rlp::new(&raw.into_vec()).as_val();
+            let tx = match decoded {
+                Ok(tx) => tx,
+                Err(e) => {
+                    results[index] = Some(SendRawTransactionResult::rejected(
+                        None,
+                        format!("{:?}", e),
+                    ));
+                    continue;
+                }
+            };
+            if let Err(e) = Self::check_receiver_address(&tx)
+                .and_then(|_| Self::check_ethereum_like_sender(&tx))
+            {
+                results[index] = Some(SendRawTransactionResult::rejected(
+                    Some(tx.hash()),
+                    e.into(),
+                ));
+                continue;
+            }
+            indices.push((index, tx.hash()));
+            transactions.push(tx);
+        }
+
+        let (passed, inserted) = self.tx_pool.insert_transactions(transactions);
+        for ((index, hash), result) in indices.into_iter().zip(inserted) {
+            results[index] = Some(SendRawTransactionResult::new(hash, result));
+        }
+        self.sync.append_received_transactions(passed);
+        Ok(results
+            .into_iter()
+            .map(|result| result.expect("every transaction has a result"))
+            .collect())
+    }
+
+    fn check_receiver_address(
+        tx: &TransactionWithSignature,
+    ) -> Result<(), &'static str> {
         if let Call(address) = &tx.transaction.action {
             if !address.is_valid_address() {
-                bail!(invalid_params("tx", "Sending transactions to invalid address. The first four bits must be 0x0 (built-in/reserved), 0x1 (user-account), or 0x8 (contract)."));
+                return Err("Sending transactions to invalid address. The first four bits must be 0x0 (built-in/reserved), 0x1 (user-account), or 0x8 (contract).");
+            }
+        }
+        Ok(())
+    }
+
+    fn check_ethereum_like_sender(
+        tx: &TransactionWithSignature,
+    ) -> Result<(), &'static str> {
+        if tx.transaction_type() == TransactionType::EthereumLike {
+            if let Ok(pubkey) = tx.recover_public() {
+                if !is_compatible_public(&pubkey) {
+                    return Err("Sending Ethereum like transaction from invalid address: the sender address should start by 0x1 (by Ethereum address rule).");
+                }
+            } else {
+                return Err("Can not recover pubkey for Ethereum like tx");
             }
         }
+        Ok(())
+    }
+
+    fn send_transaction_with_signature(
+        &self, tx: TransactionWithSignature,
+    ) -> RpcResult<H256> {
+        Self::check_receiver_address(&tx)
+            .map_err(|e| invalid_params("tx", e))?;
         if self.sync.catch_up_mode() {
             warn!("Ignore send_transaction request {}. Cannot send transaction when the node is still in catch-up mode.", tx.hash());
             bail!(request_rejected_in_catch_up_mode(None));
@@ -647,6 +732,7 @@ impl RpcImpl {
                     block_number,
                     prior_gas_used,
                     tx_exec_error_msg,
+                    tx_exec_error_info,
                 }) => {
                     let epoch_number = self
                         .consensus
@@ -666,6 +752,7 @@ impl RpcImpl {
                         block_number,
                         maybe_state_root,
                         tx_exec_error_msg,
+                        tx_exec_error_info,
                         *self.sync.network.get_network_type(),
                     )?)
                 }
@@ -771,6 +858,7 @@ impl RpcImpl {
             exec_info.block_receipts.block_number,
             exec_info.maybe_state_root.clone(),
             tx_exec_error_msg,
+            exec_info.block_receipts.tx_execution_error_info(id),
             *self.sync.network.get_network_type(),
         )?;
 
@@ -1058,8 +1146,10 @@ impl RpcImpl {
             .consensus_graph()
             .get_height_from_epoch_number(epoch.clone().into_primitive())?
             .into();
+        let reward_epoch_count =
+            self.tx_pool.machine().params().reward_epoch_count;
         let (epoch_later_number, overflow) =
-            epoch_height.overflowing_add(REWARD_EPOCH_COUNT.into());
+            epoch_height.overflowing_add(reward_epoch_count.into());
         if overflow {
             bail!(invalid_params("epoch", "Epoch number overflows!"));
         }
@@ -1102,6 +1192,59 @@ impl RpcImpl {
         Ok(ret)
     }
 
+    fn get_block_reward_details(
+        &self, block_hash: H256,
+    ) -> RpcResult<Option<RpcRewardDetails>> {
+        info!(
+            "RPC Request: cfx_getBlockRewardDetails block_hash={:?}",
+            block_hash
+        );
+        let epoch_number =
+            match self.consensus.get_block_epoch_number(&block_hash) {
+                Some(epoch_number) => epoch_number,
+                None => return Ok(None),
+            };
+        let reward_epoch_count =
+            self.tx_pool.machine().params().reward_epoch_count;
+        let epoch_later = match self.consensus.get_hash_from_epoch_number(
+            EpochNumber::Num((epoch_number + reward_epoch_count).into())
+                .into_primitive(),
+        ) {
+            Ok(hash) => hash,
+            // The reward is not computed yet.
+            Err(_) => return Ok(None),
+        };
+        let data_man = self.consensus.get_data_manager();
+        let reward_result = data_man.block_reward_result_by_hash_with_epoch(
+            &block_hash,
+            &epoch_later,
+            false, // update_pivot_assumption
+            true,  // update_cache
+        );
+        let details = data_man
+            .block_reward_details_by_hash_with_epoch(&block_hash, &epoch_later);
+        Ok(match (reward_result, details) {
+            (Some(reward_result), Some(details)) => {
+                Some(RpcRewardDetails::new(block_hash, reward_result, details))
+            }
+            _ => None,
+        })
+    }
+
+    fn pivot_segment(
+        &self, from_epoch: U64, to_epoch: U64,
+    ) -> RpcResult<Vec<PivotBlockAnnotation>> {
+        info!(
+            "RPC Request: cfx_getPivotSegment from_epoch={:?} to_epoch={:?}",
+            from_epoch, to_epoch
+        );
+        let segment = self
+            .consensus_graph()
+            .get_pivot_segment(from_epoch.as_u64(), to_epoch.as_u64())
+            .map_err(|e| invalid_params("epoch", e))?;
+        Ok(segment.into_iter().map(PivotBlockAnnotation::new).collect())
+    }
+
     fn call(
         &self, request: CallRequest, epoch: Option<EpochNumber>,
     ) -> RpcResult<Bytes> {
@@ -1127,10 +1270,15 @@ impl RpcImpl {
             ExecutionOutcome::ExecutionErrorBumpNonce(
                 ExecutionError::VmError(vm::Error::Reverted),
                 executed,
-            ) => bail!(call_execution_error(
-                "Transaction reverted".into(),
-                executed.output
-            )),
+            ) => {
+                let revert_reason = revert_reason_decode(&executed.output);
+                let message = if revert_reason.is_empty() {
+                    "Transaction reverted".to_owned()
+                } else {
+                    format!("Transaction reverted: {}", revert_reason)
+                };
+                bail!(call_execution_error(message, executed.output))
+            }
             ExecutionOutcome::ExecutionErrorBumpNonce(e, _) => {
                 bail!(call_execution_error(
                     "Transaction execution failed".into(),
@@ -1340,6 +1488,22 @@ impl RpcImpl {
             .get_pivot_chain_and_weight(height_range)?)
     }
 
+    fn get_block_total_weight(
+        &self, block_hash: H256,
+    ) -> RpcResult<Option<U256>> {
+        self.consensus_graph()
+            .get_block_total_weight(&block_hash)
+            .map(|weight| {
+                u128::try_from(weight).map(U256::from).map_err(|_| {
+                    let mut error = JsonRpcError::internal_error();
+                    error.data =
+                        Some(format!("Invalid total weight {}", weight).into());
+                    error.into()
+                })
+            })
+            .transpose()
+    }
+
     fn get_executed_info(&self, block_hash: H256) -> RpcResult<(H256, H256)> {
         let commitment = self
             .consensus
@@ -1368,11 +1532,180 @@ impl RpcImpl {
         Ok(ConsensusGraphStates::new(consensus_graph_states))
     }
 
+    pub fn consensus_block_ids(
+        &self, query: ConsensusBlockIdQuery,
+    ) -> RpcResult<Option<ConsensusBlockIds>> {
+        let consensus_graph = self.consensus_graph();
+        let ids = match query {
+            ConsensusBlockIdQuery::Hash(hash) => {
+                consensus_graph.block_ids_by_hash(&hash)
+            }
+            ConsensusBlockIdQuery::StableId(stable_id) => {
+                consensus_graph.block_ids_by_stable_id(stable_id.as_u64())
+            }
+            ConsensusBlockIdQuery::ArenaIndex(index) => {
+                consensus_graph.block_ids_by_arena_index(index.as_usize())
+            }
+        };
+        Ok(ids.map(ConsensusBlockIds::new))
+    }
+
+    pub fn ghast_decision(
+        &self, block_hash: H256,
+    ) -> RpcResult<Option<GhastDecision>> {
+        Ok(self
+            .consensus_graph()
+            .ghast_decision(&block_hash)
+            .map(GhastDecision::new))
+    }
+
+    pub fn block_consensus_info(
+        &self, block_hash: H256,
+    ) -> RpcResult<Option<BlockConsensusInfo>> {
+        Ok(self
+            .consensus_graph()
+            .get_block_consensus_info(&block_hash)
+            .map(BlockConsensusInfo::new))
+    }
+
+    pub fn chain_weight_proof(
+        &self, anchor_height: U64, end_height: U64,
+    ) -> RpcResult<Bytes> {
+        let proof = self
+            .consensus_graph()
+            .chain_weight_proof(anchor_height.as_u64(), end_height.as_u64())?;
+        Ok(Bytes::new(rlp::encode(&proof)))
+    }
+
     pub fn sync_graph_state(&self) -> RpcResult<SyncGraphStates> {
         let sync_graph_states = STATE_EXPOSER.sync_graph.lock().retrieve();
         Ok(SyncGraphStates::new(sync_graph_states))
     }
 
+    pub fn block_propagation_record(
+        &self, block_hash: H256,
+    ) -> RpcResult<Option<BlockPropagationRecord>> {
+        Ok(self
+            .sync
+            .get_synchronization_graph()
+            .block_propagation
+            .record_by_hash(&block_hash)
+            .map(BlockPropagationRecord::new))
+    }
+
+    pub fn block_propagation_records(
+        &self, limit: usize,
+    ) -> RpcResult<Vec<BlockPropagationRecord>> {
+        Ok(self
+            .sync
+            .get_synchronization_graph()
+            .block_propagation
+            .recent_records(limit)
+            .into_iter()
+            .map(BlockPropagationRecord::new)
+            .collect())
+    }
+
+    pub fn execution_worker_status(&self) -> RpcResult<ExecutionWorkerStatus> {
+        Ok(ExecutionWorkerStatus::new(
+            self.consensus_graph().execution_worker_status(),
+        ))
+    }
+
+    pub fn state_availability(&self) -> RpcResult<StateAvailability> {
+        Ok(StateAvailability::new(
+            self.consensus_graph().state_availability(),
+        ))
+    }
+
+    pub fn pivot_switch_history(
+        &self, from_seq: U64, limit: usize,
+    ) -> RpcResult<Vec<PivotSwitchEvent>> {
+        Ok(self
+            .consensus_graph()
+            .pivot_switch_history(from_seq.as_u64(), limit)
+            .into_iter()
+            .map(PivotSwitchEvent::new)
+            .collect())
+    }
+
+    pub fn export_epochs(
+        &self, max_epochs: U64,
+    ) -> RpcResult<EpochExportCursor> {
+        let cursor =
+            self.consensus_graph().export_epochs(max_epochs.as_u64())?;
+        Ok(EpochExportCursor::new(cursor))
+    }
+
+    pub fn export_snapshot_archive(
+        &self, snapshot_epoch_number: U64,
+    ) -> RpcResult<SnapshotArchiveInfo> {
+        let consensus_graph = self.consensus_graph();
+        let snapshot_height = snapshot_epoch_number.as_u64();
+        let confirmed_epoch_number =
+            consensus_graph.confirmation_meter.get_confirmed_epoch_num();
+        if snapshot_height > confirmed_epoch_number {
+            bail!(invalid_params(
+                "snapshot_epoch_number",
+                format!(
+                    "Epoch {} is not confirmed, the latest confirmed epoch is {}",
+                    snapshot_height, confirmed_epoch_number
+                )
+            ));
+        }
+        let snapshot_epoch_count: u64 = consensus_graph
+            .data_man
+            .storage_manager
+            .get_storage_manager()
+            .get_snapshot_epoch_count()
+            .into();
+        if snapshot_height % snapshot_epoch_count != 0 {
+            bail!(invalid_params(
+                "snapshot_epoch_number",
+                format!(
+                    "The snapshots are taken every {} epochs",
+                    snapshot_epoch_count
+                )
+            ));
+        }
+        let snapshot_epoch_id = consensus_graph.get_hash_from_epoch_number(
+            EpochNumber::Num(snapshot_epoch_number).into_primitive(),
+        )?;
+        let manifest = self
+            .sync
+            .export_snapshot_archive(&snapshot_epoch_id, snapshot_height)?;
+        Ok(SnapshotArchiveInfo::new(&manifest))
+    }
+
+    pub fn set_execution_worker_count(&self, count: usize) -> RpcResult<()> {
+        self.consensus_graph().set_execution_worker_count(count);
+        Ok(())
+    }
+
+    pub fn epoch_packing_statistics(
+        &self, limit: usize,
+    ) -> RpcResult<EpochPackingStatistics> {
+        let (records, total) =
+            self.consensus.get_statistics().epoch_packing_records(limit);
+        Ok(EpochPackingStatistics::new(records, total))
+    }
+
+    pub fn tx_skip_diagnosis(
+        &self, tx_hash: H256,
+    ) -> RpcResult<Option<TxSkipDiagnosis>> {
+        match self
+            .consensus
+            .get_statistics()
+            .diagnose_skipped_transaction(&tx_hash)
+        {
+            None => Ok(None),
+            Some(diagnosis) => Ok(Some(TxSkipDiagnosis::new(
+                diagnosis,
+                *self.sync.network.get_network_type(),
+            )?)),
+        }
+    }
+
     /// Return (block_info.status, state_valid)
     /// Return Error if either field is missing
     pub fn get_block_status(&self, block_hash: H256) -> RpcResult<(u8, bool)> {
@@ -1432,6 +1765,40 @@ impl RpcImpl {
         Ok(())
     }
 
+    pub fn set_epoch_execution_delay(
+        &self, epoch_hash: H256, delay_ms: Option<u64>,
+    ) -> RpcResult<()> {
+        self.consensus_graph()
+            .set_epoch_execution_delay(epoch_hash, delay_ms);
+        Ok(())
+    }
+
+    pub fn drop_anticone_cache_entries(
+        &self, block_hashes: Vec<H256>,
+    ) -> RpcResult<usize> {
+        Ok(self
+            .consensus_graph()
+            .drop_anticone_cache_entries(&block_hashes))
+    }
+
+    pub fn force_pivot_reevaluation(&self, from_height: u64) -> RpcResult<()> {
+        self.consensus_graph().force_pivot_reevaluation(from_height);
+        Ok(())
+    }
+
+    pub fn regenerate_state(
+        &self, block_hash: H256, max_epochs: usize, max_wall_time_ms: u64,
+    ) -> RpcResult<StateRegenerationProgress> {
+        let progress = self.consensus_graph().regenerate_state(
+            &block_hash,
+            StateRegenerationBudget {
+                max_epochs,
+                max_wall_time: Duration::from_millis(max_wall_time_ms),
+            },
+        )?;
+        Ok(StateRegenerationProgress::new(progress))
+    }
+
     // estimate response size, return error if it is too large
     // note: this is a potentially expensive check
     fn check_response_size<T: Serialize>(&self, response: &T) -> RpcResult<()> {
@@ -1552,7 +1919,10 @@ impl Cfx for CfxHandler {
                 -> BoxFuture<Option<RpcBlock>>;
             fn block_by_block_number(&self, block_number: U64, include_txs: bool) -> BoxFuture<Option<RpcBlock>>;
             fn confirmation_risk_by_hash(&self, block_hash: H256) -> JsonRpcResult<Option<U256>>;
+            fn epoch_confirmation_risk(&self, epoch_number: EpochNumber) -> JsonRpcResult<Option<EpochConfirmationRisk>>;
             fn blocks_by_epoch(&self, num: EpochNumber) -> JsonRpcResult<Vec<H256>>;
+            fn blocks_by_epoch_page(&self, num: EpochNumber, offset: U64, limit: U64) -> JsonRpcResult<Vec<H256>>;
+            fn epoch_size(&self, num: EpochNumber) -> JsonRpcResult<U64>;
             fn skipped_blocks_by_epoch(&self, num: EpochNumber) -> JsonRpcResult<Vec<H256>>;
             fn epoch_number(&self, epoch_num: Option<EpochNumber>) -> JsonRpcResult<U256>;
             fn gas_price(&self) -> BoxFuture<U256>;
@@ -1588,7 +1958,10 @@ impl Cfx for CfxHandler {
             ) -> BoxFuture<CheckBalanceAgainstTransactionResponse>;
             fn get_logs(&self, filter: RpcFilter) -> BoxFuture<Vec<RpcLog>>;
             fn get_block_reward_info(&self, num: EpochNumber) -> JsonRpcResult<Vec<RpcRewardInfo>>;
+            fn get_block_reward_details(&self, block_hash: H256) -> JsonRpcResult<Option<RpcRewardDetails>>;
+            fn pivot_segment(&self, from_epoch: U64, to_epoch: U64) -> JsonRpcResult<Vec<PivotBlockAnnotation>>;
             fn send_raw_transaction(&self, raw: Bytes) -> JsonRpcResult<H256>;
+            fn send_raw_transactions(&self, raw_txs: Vec<Bytes>) -> JsonRpcResult<Vec<SendRawTransactionResult>>;
             fn storage_at(&self, addr: RpcAddress, pos: H256, epoch_number: Option<EpochNumber>)
                 -> BoxFuture<Option<H256>>;
             fn transaction_by_hash(&self, hash: H256) -> BoxFuture<Option<RpcTransaction>>;
@@ -1640,6 +2013,7 @@ impl TestRpc for TestRpcImpl {
                 &self, parent_hash: H256, referee: Vec<H256>, raw_txs: Bytes, adaptive: Option<bool>)
                 -> JsonRpcResult<H256>;
             fn get_pivot_chain_and_weight(&self, height_range: Option<(u64, u64)>) -> JsonRpcResult<Vec<(H256, U256)>>;
+            fn get_block_total_weight(&self, block_hash: H256) -> JsonRpcResult<Option<U256>>;
             fn get_executed_info(&self, block_hash: H256) -> JsonRpcResult<(H256, H256)> ;
             fn generate_fixed_block(
                 &self, parent_hash: H256, referee: Vec<H256>, num_txs: usize, adaptive: bool, difficulty: Option<u64>)
@@ -1655,6 +2029,10 @@ impl TestRpc for TestRpcImpl {
             fn get_block_status(&self, block_hash: H256) -> JsonRpcResult<(u8, bool)>;
             fn send_usable_genesis_accounts(& self, account_start_index: usize) -> JsonRpcResult<Bytes>;
             fn set_db_crash(&self, crash_probability: f64, crash_exit_code: i32) -> JsonRpcResult<()>;
+            fn set_epoch_execution_delay(&self, epoch_hash: H256, delay_ms: Option<u64>) -> JsonRpcResult<()>;
+            fn drop_anticone_cache_entries(&self, block_hashes: Vec<H256>) -> JsonRpcResult<usize>;
+            fn force_pivot_reevaluation(&self, from_height: u64) -> JsonRpcResult<()>;
+            fn regenerate_state(&self, block_hash: H256, max_epochs: usize, max_wall_time_ms: u64) -> JsonRpcResult<StateRegenerationProgress>;
         }
     }
 }
@@ -1701,8 +2079,22 @@ impl LocalRpc for LocalRpcImpl {
         to self.rpc_impl {
             fn current_sync_phase(&self) -> JsonRpcResult<String>;
             fn consensus_graph_state(&self) -> JsonRpcResult<ConsensusGraphStates>;
+            fn consensus_block_ids(&self, query: ConsensusBlockIdQuery) -> JsonRpcResult<Option<ConsensusBlockIds>>;
+            fn ghast_decision(&self, block_hash: H256) -> JsonRpcResult<Option<GhastDecision>>;
+            fn block_consensus_info(&self, block_hash: H256) -> JsonRpcResult<Option<BlockConsensusInfo>>;
+            fn chain_weight_proof(&self, anchor_height: U64, end_height: U64) -> JsonRpcResult<Bytes>;
             fn epoch_receipts(&self, epoch: BlockHashOrEpochNumber) -> JsonRpcResult<Option<Vec<Vec<RpcReceipt>>>>;
             fn sync_graph_state(&self) -> JsonRpcResult<SyncGraphStates>;
+            fn block_propagation_record(&self, block_hash: H256) -> JsonRpcResult<Option<BlockPropagationRecord>>;
+            fn block_propagation_records(&self, limit: usize) -> JsonRpcResult<Vec<BlockPropagationRecord>>;
+            fn execution_worker_status(&self) -> JsonRpcResult<ExecutionWorkerStatus>;
+            fn set_execution_worker_count(&self, count: usize) -> JsonRpcResult<()>;
+            fn state_availability(&self) -> JsonRpcResult<StateAvailability>;
+            fn pivot_switch_history(&self, from_seq: U64, limit: usize) -> JsonRpcResult<Vec<PivotSwitchEvent>>;
+            fn export_epochs(&self, max_epochs: U64) -> JsonRpcResult<EpochExportCursor>;
+            fn export_snapshot_archive(&self, snapshot_epoch_number: U64) -> JsonRpcResult<SnapshotArchiveInfo>;
+            fn epoch_packing_statistics(&self, limit: usize) -> JsonRpcResult<EpochPackingStatistics>;
+            fn tx_skip_diagnosis(&self, tx_hash: H256) -> JsonRpcResult<Option<TxSkipDiagnosis>>;
             fn send_transaction(
                 &self, tx: SendTxRequest, password: Option<String>) -> BoxFuture<H256>;
             fn sign_transaction(&self, tx: SendTxRequest, password: Option<String>) -> JsonRpcResult<String>;
@@ -6,7 +6,7 @@ use crate::rpc::{
     types::{
         errors::check_rpc_address_network, Block as RpcBlock,
         BlockHashOrEpochNumber, Bytes, CheckBalanceAgainstTransactionResponse,
-        EpochNumber, RpcAddress, Status as RpcStatus,
+        EpochConfirmationRisk, EpochNumber, RpcAddress, Status as RpcStatus,
         Transaction as RpcTransaction, TxPoolPendingInfo, TxWithPoolInfo,
     },
     RpcResult,
@@ -38,6 +38,7 @@ use primitives::{
 };
 use std::{
     collections::{BTreeMap, HashSet},
+    convert::TryFrom,
     net::SocketAddr,
     sync::Arc,
     time::Duration,
@@ -65,6 +66,25 @@ where F: Fn(Arc<SignedTransaction>) -> T {
     addr_grouped_txs
 }
 
+/// Scale a confirmation risk in `[0, 1]` to `[0, 2^256 - 1]`.
+pub fn scale_confirmation_risk(risk: f64) -> U256 {
+    let risk: BigDecimal = risk.into();
+    let scale = BigInt::parse_bytes(
+        b"FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF",
+        16,
+    )
+    .expect("failed to unwrap U256::max into bigInt");
+
+    //TODO: there's a precision problem here, it should be fine under a
+    // (2^256 - 1) scale
+    let scaled_risk: BigInt = (risk * scale)
+        .to_bigint()
+        .expect("failed to convert scaled risk to bigInt");
+    let (sign, big_endian_bytes) = scaled_risk.to_bytes_be();
+    assert_ne!(sign, num_bigint::Sign::Minus);
+    U256::from(big_endian_bytes.as_slice())
+}
+
 pub fn check_balance_against_transaction(
     user_account: Option<Account>, contract_account: Option<Account>,
     is_sponsored: bool, gas_limit: U256, gas_price: U256, storage_limit: U256,
@@ -245,22 +265,26 @@ impl RpcImpl {
         let result = consensus_graph
             .confirmation_meter
             .confirmation_risk_by_hash(inner, block_hash.into());
-        if result.is_none() {
-            Ok(None)
-        } else {
-            let risk: BigDecimal = result.unwrap().into();
-            let scale = BigInt::parse_bytes(b"FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF", 16).expect("failed to unwrap U256::max into bigInt");
-
-            //TODO: there's a precision problem here, it should be fine under a
-            // (2^256 - 1) scale
-            let scaled_risk: BigInt = (risk * scale)
-                .to_bigint()
-                .expect("failed to convert scaled risk to bigInt");
-            let (sign, big_endian_bytes) = scaled_risk.to_bytes_be();
-            assert_ne!(sign, num_bigint::Sign::Minus);
-            let rpc_result = U256::from(big_endian_bytes.as_slice());
-            Ok(Some(rpc_result.into()))
-        }
+        Ok(result.map(scale_confirmation_risk))
+    }
+
+    pub fn epoch_confirmation_risk(
+        &self, epoch_number: EpochNumber,
+    ) -> JsonRpcResult<Option<EpochConfirmationRisk>> {
+        let consensus_graph = self.consensus_graph();
+        let epoch_number = consensus_graph
+            .get_height_from_epoch_number(epoch_number.into())
+            .map_err(RpcError::invalid_params)?;
+        Ok(consensus_graph
+            .epoch_confirmation_risk(epoch_number)
+            .map(|risk| EpochConfirmationRisk {
+                epoch_number: epoch_number.into(),
+                confirmation_risk: scale_confirmation_risk(risk),
+                risk_model: consensus_graph
+                    .confirmation_meter
+                    .risk_model_name()
+                    .into(),
+            }))
     }
 
     pub fn block_by_hash(
@@ -298,7 +322,7 @@ impl RpcImpl {
         let inner = &*consensus_graph.inner.read();
         let block_hash: H256 = block_hash.into();
         let pivot_hash: H256 = pivot_hash.into();
-        let epoch_number = epoch_number.as_usize() as u64;
+        let epoch_number = epoch_number.as_u64();
 
         info!(
             "RPC Request: cfx_getBlockByHashWithPivotAssumption block_hash={:?} pivot_hash={:?} epoch_number={:?}",
@@ -391,6 +415,32 @@ impl RpcImpl {
             .and_then(|vec| Ok(vec.into_iter().map(|x| x.into()).collect()))
     }
 
+    pub fn blocks_by_epoch_page(
+        &self, num: EpochNumber, offset: U64, limit: U64,
+    ) -> JsonRpcResult<Vec<H256>> {
+        info!(
+            "RPC Request: cfx_getBlocksByEpochPage epoch_number={:?} offset={:?} limit={:?}",
+            num, offset, limit
+        );
+
+        // Offsets beyond the epoch return an empty page and oversized limits
+        // are rejected, so saturating the conversions is safe.
+        let offset = usize::try_from(offset.as_u64()).unwrap_or(usize::MAX);
+        let limit = usize::try_from(limit.as_u64()).unwrap_or(usize::MAX);
+        self.consensus_graph()
+            .get_block_hashes_by_epoch_page(num.into(), offset, limit)
+            .map_err(RpcError::invalid_params)
+    }
+
+    pub fn epoch_size(&self, num: EpochNumber) -> JsonRpcResult<U64> {
+        info!("RPC Request: cfx_getEpochSize epoch_number={:?}", num);
+
+        self.consensus_graph()
+            .get_epoch_size(num.into())
+            .map(|size| U64::from(size as u64))
+            .map_err(RpcError::invalid_params)
+    }
+
     pub fn skipped_blocks_by_epoch(
         &self, num: EpochNumber,
     ) -> JsonRpcResult<Vec<H256>> {
@@ -590,48 +640,22 @@ impl RpcImpl {
     }
 
     pub fn get_status(&self) -> RpcResult<RpcStatus> {
-        let consensus_graph = self.consensus_graph();
-
-        let (best_info, block_number) = {
-            // keep read lock to maintain consistent view
-            let _inner = &*consensus_graph.inner.read();
-
-            let best_info = self.consensus.best_info();
-
-            let block_number = self
-                .consensus
-                .get_block_number(&best_info.best_block_hash)?
-                .ok_or("block_number is missing for best_hash")?
-                // The returned block_number of `best_hash` does not include `best_hash` itself.
-                + 1;
-
-            (best_info, block_number)
-        };
-
-        let tx_count = self.tx_pool.total_unpacked();
-
-        let latest_checkpoint = consensus_graph
-            .get_height_from_epoch_number(EpochNumber::LatestCheckpoint.into())?
-            .into();
-
-        let latest_confirmed = consensus_graph
-            .get_height_from_epoch_number(EpochNumber::LatestConfirmed.into())?
-            .into();
-
-        let latest_state = consensus_graph
-            .get_height_from_epoch_number(EpochNumber::LatestState.into())?
-            .into();
+        let status = self.consensus_graph().chain_status();
 
         Ok(RpcStatus {
-            best_hash: best_info.best_block_hash.into(),
-            block_number: block_number.into(),
-            chain_id: best_info.chain_id.into(),
-            epoch_number: best_info.best_epoch_number.into(),
-            latest_checkpoint,
-            latest_confirmed,
-            latest_state,
+            best_hash: status.best_block_hash.into(),
+            // `best_block_number` does not include the best block itself.
+            block_number: (status.best_block_number + 1).into(),
+            chain_id: status.chain_id.into(),
+            epoch_number: status.best_epoch_number.into(),
+            epoch_executed_block_bound: (status.epoch_executed_block_bound
+                as u64)
+                .into(),
+            latest_checkpoint: status.era_genesis_height.into(),
+            latest_confirmed: status.latest_confirmed_epoch_number.into(),
+            latest_state: status.best_state_epoch_number.into(),
             network_id: self.network.network_id().into(),
-            pending_tx_number: tx_count.into(),
+            pending_tx_number: status.tx_pool_size.into(),
         })
     }
 
@@ -696,6 +720,8 @@ impl RpcImpl {
             if self.tx_pool.check_tx_packed_in_deferred_pool(&hash) {
                 ret.packed = true;
             }
+            ret.provisionally_packed_template =
+                self.tx_pool.provisionally_packed_template(&hash);
             let (local_nonce, local_balance) =
                 self.tx_pool.get_local_account_info(&tx.sender());
             let (state_nonce, state_balance) = self
@@ -823,14 +849,20 @@ impl RpcImpl {
     }
 
     pub fn txpool_status(&self) -> JsonRpcResult<BTreeMap<String, usize>> {
-        let (ready_len, deferred_len, received_len, unexecuted_len) =
-            self.tx_pool.stats();
+        let (
+            ready_len,
+            deferred_len,
+            received_len,
+            unexecuted_len,
+            expired_len,
+        ) = self.tx_pool.stats();
 
         let mut ret: BTreeMap<String, usize> = BTreeMap::new();
         ret.insert("ready".into(), ready_len);
         ret.insert("deferred".into(), deferred_len);
         ret.insert("received".into(), received_len);
         ret.insert("unexecuted".into(), unexecuted_len);
+        ret.insert("expired".into(), expired_len);
 
         Ok(ret)
     }
@@ -25,7 +25,9 @@ use primitives::{
     Account, DepositInfo, StorageRoot, TransactionWithSignature, VoteStakeInfo,
 };
 use rlp::Encodable;
-use std::{collections::BTreeMap, net::SocketAddr, sync::Arc};
+use std::{
+    collections::BTreeMap, convert::TryFrom, net::SocketAddr, sync::Arc,
+};
 // To convert from RpcResult to BoxFuture by delegate! macro automatically.
 use crate::{
     common::delegate_convert,
@@ -39,13 +41,19 @@ use crate::{
         types::{
             Account as RpcAccount, AccountPendingInfo,
             AccountPendingTransactions, BlameInfo, Block as RpcBlock,
-            BlockHashOrEpochNumber, Bytes, CallRequest,
-            CheckBalanceAgainstTransactionResponse, ConsensusGraphStates,
-            EpochNumber, EstimateGasAndCollateralResponse, Log as RpcLog,
-            LogFilter as RpcFilter, Receipt as RpcReceipt,
-            RewardInfo as RpcRewardInfo, RpcAddress, SendTxRequest,
-            SponsorInfo, Status as RpcStatus, SyncGraphStates, TokenSupplyInfo,
-            Transaction as RpcTransaction, TxPoolPendingInfo, TxWithPoolInfo,
+            BlockConsensusInfo, BlockHashOrEpochNumber, BlockPropagationRecord,
+            Bytes, CallRequest, CheckBalanceAgainstTransactionResponse,
+            ConsensusBlockIdQuery, ConsensusBlockIds, ConsensusGraphStates,
+            EpochConfirmationRisk, EpochExportCursor, EpochNumber,
+            EpochPackingStatistics, EstimateGasAndCollateralResponse,
+            ExecutionWorkerStatus, GhastDecision, Log as RpcLog,
+            LogFilter as RpcFilter, PivotBlockAnnotation, PivotSwitchEvent,
+            Receipt as RpcReceipt, RewardDetails as RpcRewardDetails,
+            RewardInfo as RpcRewardInfo, RpcAddress, SendRawTransactionResult,
+            SendTxRequest, SnapshotArchiveInfo, SponsorInfo, StateAvailability,
+            StateRegenerationProgress, Status as RpcStatus, SyncGraphStates,
+            TokenSupplyInfo, Transaction as RpcTransaction, TxPoolPendingInfo,
+            TxSkipDiagnosis, TxWithPoolInfo,
         },
         RpcBoxFuture, RpcResult,
     },
@@ -672,6 +680,7 @@ impl RpcImpl {
                 maybe_state_root,
                 // Can not offer error_message from light node.
                 None,
+                Default::default(),
                 *light.get_network_type(),
             )?;
 
@@ -908,6 +917,59 @@ impl RpcImpl {
         Ok(hashes)
     }
 
+    pub fn blocks_by_epoch_page(
+        &self, epoch: EpochNumber, offset: U64, limit: U64,
+    ) -> RpcResult<Vec<H256>> {
+        info!(
+            "RPC Request: cfx_getBlocksByEpochPage epoch_number={:?} offset={:?} limit={:?}",
+            epoch, offset, limit
+        );
+
+        let height = self
+            .light
+            .get_height_from_epoch_number(epoch.into())
+            .map_err(|e| e.to_string())
+            .map_err(RpcError::invalid_params)?;
+
+        let offset = usize::try_from(offset.as_u64()).unwrap_or(usize::MAX);
+        let limit = usize::try_from(limit.as_u64()).unwrap_or(usize::MAX);
+        let hashes = self
+            .consensus
+            .as_any()
+            .downcast_ref::<ConsensusGraph>()
+            .expect("downcast should succeed")
+            .get_block_hashes_by_epoch_page(
+                primitives::EpochNumber::Number(height),
+                offset,
+                limit,
+            )
+            .map_err(RpcError::invalid_params)?;
+
+        Ok(hashes)
+    }
+
+    pub fn epoch_size(&self, epoch: EpochNumber) -> RpcResult<U64> {
+        info!("RPC Request: cfx_getEpochSize epoch_number={:?}", epoch);
+
+        let height = self
+            .light
+            .get_height_from_epoch_number(epoch.into())
+            .map_err(|e| e.to_string())
+            .map_err(RpcError::invalid_params)?;
+
+        let size = self
+            .consensus
+            .as_any()
+            .downcast_ref::<ConsensusGraph>()
+            .expect("downcast should succeed")
+            .inner
+            .read()
+            .epoch_size(height)
+            .map_err(RpcError::invalid_params)?;
+
+        Ok(U64::from(size as u64))
+    }
+
     pub fn gas_price(&self) -> RpcBoxFuture<U256> {
         info!("RPC Request: cfx_gasPrice");
 
@@ -1036,6 +1098,7 @@ impl Cfx for CfxHandler {
         to self.common {
             fn best_block_hash(&self) -> JsonRpcResult<H256>;
             fn confirmation_risk_by_hash(&self, block_hash: H256) -> JsonRpcResult<Option<U256>>;
+            fn epoch_confirmation_risk(&self, epoch_number: EpochNumber) -> JsonRpcResult<Option<EpochConfirmationRisk>>;
             fn get_client_version(&self) -> JsonRpcResult<String>;
             fn get_status(&self) -> JsonRpcResult<RpcStatus>;
             fn skipped_blocks_by_epoch(&self, num: EpochNumber) -> JsonRpcResult<Vec<H256>>;
@@ -1050,11 +1113,13 @@ impl Cfx for CfxHandler {
             fn block_by_hash_with_pivot_assumption(&self, block_hash: H256, pivot_hash: H256, epoch_number: U64) -> BoxFuture<RpcBlock>;
             fn block_by_hash(&self, hash: H256, include_txs: bool) -> BoxFuture<Option<RpcBlock>>;
             fn blocks_by_epoch(&self, num: EpochNumber) -> JsonRpcResult<Vec<H256>>;
+            fn blocks_by_epoch_page(&self, num: EpochNumber, offset: U64, limit: U64) -> JsonRpcResult<Vec<H256>>;
             fn check_balance_against_transaction(&self, account_addr: RpcAddress, contract_addr: RpcAddress, gas_limit: U256, gas_price: U256, storage_limit: U256, epoch: Option<EpochNumber>) -> BoxFuture<CheckBalanceAgainstTransactionResponse>;
             fn code(&self, address: RpcAddress, epoch_num: Option<EpochNumber>) -> BoxFuture<Bytes>;
             fn collateral_for_storage(&self, address: RpcAddress, num: Option<EpochNumber>) -> BoxFuture<U256>;
             fn deposit_list(&self, address: RpcAddress, num: Option<EpochNumber>) -> BoxFuture<Vec<DepositInfo>>;
             fn epoch_number(&self, epoch_num: Option<EpochNumber>) -> JsonRpcResult<U256>;
+            fn epoch_size(&self, num: EpochNumber) -> JsonRpcResult<U64>;
             fn gas_price(&self) -> BoxFuture<U256>;
             fn get_logs(&self, filter: RpcFilter) -> BoxFuture<Vec<RpcLog>>;
             fn interest_rate(&self, num: Option<EpochNumber>) -> BoxFuture<U256>;
@@ -1078,7 +1143,10 @@ impl Cfx for CfxHandler {
         fn call(&self, request: CallRequest, epoch: Option<EpochNumber>) -> JsonRpcResult<Bytes>;
         fn estimate_gas_and_collateral(&self, request: CallRequest, epoch_num: Option<EpochNumber>) -> JsonRpcResult<EstimateGasAndCollateralResponse>;
         fn get_block_reward_info(&self, num: EpochNumber) -> JsonRpcResult<Vec<RpcRewardInfo>>;
+        fn get_block_reward_details(&self, block_hash: H256) -> JsonRpcResult<Option<RpcRewardDetails>>;
         fn get_supply_info(&self, epoch_num: Option<EpochNumber>) -> JsonRpcResult<TokenSupplyInfo>;
+        fn send_raw_transactions(&self, raw_txs: Vec<Bytes>) -> JsonRpcResult<Vec<SendRawTransactionResult>>;
+        fn pivot_segment(&self, from_epoch: U64, to_epoch: U64) -> JsonRpcResult<Vec<PivotBlockAnnotation>>;
     }
 }
 
@@ -1123,10 +1191,15 @@ impl TestRpc for TestRpcImpl {
         fn generate_one_block_with_direct_txgen(&self, num_txs: usize, block_size_limit: usize, num_txs_simple: usize, num_txs_erc20: usize) -> JsonRpcResult<H256>;
         fn generate_one_block(&self, num_txs: usize, block_size_limit: usize) -> JsonRpcResult<H256>;
         fn get_block_status(&self, block_hash: H256) -> JsonRpcResult<(u8, bool)>;
+        fn get_block_total_weight(&self, block_hash: H256) -> JsonRpcResult<Option<U256>>;
         fn get_executed_info(&self, block_hash: H256) -> JsonRpcResult<(H256, H256)> ;
         fn get_pivot_chain_and_weight(&self, height_range: Option<(u64, u64)>) -> JsonRpcResult<Vec<(H256, U256)>>;
         fn send_usable_genesis_accounts(&self, account_start_index: usize) -> JsonRpcResult<Bytes>;
         fn set_db_crash(&self, crash_probability: f64, crash_exit_code: i32) -> JsonRpcResult<()>;
+        fn set_epoch_execution_delay(&self, epoch_hash: H256, delay_ms: Option<u64>) -> JsonRpcResult<()>;
+        fn drop_anticone_cache_entries(&self, block_hashes: Vec<H256>) -> JsonRpcResult<usize>;
+        fn force_pivot_reevaluation(&self, from_height: u64) -> JsonRpcResult<()>;
+        fn regenerate_state(&self, block_hash: H256, max_epochs: usize, max_wall_time_ms: u64) -> JsonRpcResult<StateRegenerationProgress>;
     }
 }
 
@@ -1168,10 +1241,24 @@ impl LocalRpc for DebugRpcImpl {
     }
 
     not_supported! {
+        fn block_propagation_record(&self, block_hash: H256) -> JsonRpcResult<Option<BlockPropagationRecord>>;
+        fn block_propagation_records(&self, limit: usize) -> JsonRpcResult<Vec<BlockPropagationRecord>>;
+        fn consensus_block_ids(&self, query: ConsensusBlockIdQuery) -> JsonRpcResult<Option<ConsensusBlockIds>>;
         fn consensus_graph_state(&self) -> JsonRpcResult<ConsensusGraphStates>;
         fn current_sync_phase(&self) -> JsonRpcResult<String>;
+        fn epoch_packing_statistics(&self, limit: usize) -> JsonRpcResult<EpochPackingStatistics>;
         fn epoch_receipts(&self, epoch: BlockHashOrEpochNumber) -> JsonRpcResult<Option<Vec<Vec<RpcReceipt>>>>;
+        fn execution_worker_status(&self) -> JsonRpcResult<ExecutionWorkerStatus>;
+        fn ghast_decision(&self, block_hash: H256) -> JsonRpcResult<Option<GhastDecision>>;
+        fn block_consensus_info(&self, block_hash: H256) -> JsonRpcResult<Option<BlockConsensusInfo>>;
+        fn chain_weight_proof(&self, anchor_height: U64, end_height: U64) -> JsonRpcResult<Bytes>;
+        fn pivot_switch_history(&self, from_seq: U64, limit: usize) -> JsonRpcResult<Vec<PivotSwitchEvent>>;
+        fn export_epochs(&self, max_epochs: U64) -> JsonRpcResult<EpochExportCursor>;
+        fn export_snapshot_archive(&self, snapshot_epoch_number: U64) -> JsonRpcResult<SnapshotArchiveInfo>;
+        fn set_execution_worker_count(&self, count: usize) -> JsonRpcResult<()>;
+        fn state_availability(&self) -> JsonRpcResult<StateAvailability>;
         fn sign_transaction(&self, tx: SendTxRequest, password: Option<String>) -> JsonRpcResult<String>;
         fn sync_graph_state(&self) -> JsonRpcResult<SyncGraphStates>;
+        fn tx_skip_diagnosis(&self, tx_hash: H256) -> JsonRpcResult<Option<TxSkipDiagnosis>>;
     }
 }
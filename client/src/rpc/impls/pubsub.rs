@@ -5,6 +5,7 @@
 use crate::rpc::{
     error_codes,
     helpers::{EpochQueue, SubscriberId, Subscribers},
+    impls::common::scale_confirmation_risk,
     metadata::Metadata,
     traits::PubSub,
     types::{
@@ -13,10 +14,11 @@ use crate::rpc::{
     },
 };
 use cfx_addr::Network;
-use cfx_parameters::consensus::DEFERRED_STATE_EPOCH_COUNT;
+use cfx_parameters::consensus_internal::CONFIRMATION_METER_MIN_MAINTAINED_RISK;
 use cfx_types::H256;
 use cfxcore::{
-    channel::Channel, BlockDataManager, Notifications, SharedConsensusGraph,
+    channel::Channel, BlockDataManager, ConsensusGraph, Notifications,
+    SharedConsensusGraph,
 };
 use futures::{
     compat::Future01CompatExt,
@@ -48,6 +50,7 @@ pub struct PubSubClient {
     heads_subscribers: Arc<RwLock<Subscribers<Client>>>,
     epochs_subscribers: Arc<RwLock<Subscribers<Client>>>,
     logs_subscribers: Arc<RwLock<Subscribers<(Client, LogFilter)>>>,
+    confirmation_subscribers: Arc<RwLock<Subscribers<(Client, H256)>>>,
     epochs_ordered: Arc<Channel<(u64, Vec<H256>)>>,
 }
 
@@ -61,6 +64,8 @@ impl PubSubClient {
         let heads_subscribers = Arc::new(RwLock::new(Subscribers::default()));
         let epochs_subscribers = Arc::new(RwLock::new(Subscribers::default()));
         let logs_subscribers = Arc::new(RwLock::new(Subscribers::default()));
+        let confirmation_subscribers =
+            Arc::new(RwLock::new(Subscribers::default()));
 
         let handler = Arc::new(ChainNotificationHandler {
             executor,
@@ -89,6 +94,7 @@ impl PubSubClient {
             heads_subscribers,
             epochs_subscribers,
             logs_subscribers,
+            confirmation_subscribers,
             epochs_ordered: notifications.epochs_ordered.clone(),
         }
     }
@@ -98,6 +104,13 @@ impl PubSubClient {
         Arc::downgrade(&self.handler)
     }
 
+    // The number of epochs to hold back from the tip of the pivot chain, so
+    // that the epochs are published after they are executed. The deepest
+    // deferred state epoch count is used, so it holds across its transition.
+    fn executed_epoch_queue_capacity(&self) -> usize {
+        (self.handler.consensus.deferred_state_epoch_count().max() - 1) as usize
+    }
+
     // Start an async loop that continuously receives epoch notifications and
     // publishes the corresponding epochs to subscriber `id`, keeping their
     // original order. The loop terminates when subscriber `id` unsubscribes.
@@ -116,7 +129,7 @@ impl PubSubClient {
         // we only process epochs once they have been executed
         let mut queue = EpochQueue::<Vec<H256>>::with_capacity(
             if sub_epoch == SubscriptionEpoch::LatestState {
-                (DEFERRED_STATE_EPOCH_COUNT - 1) as usize
+                self.executed_epoch_queue_capacity()
             } else {
                 0
             },
@@ -175,7 +188,7 @@ impl PubSubClient {
         // use a queue to make sure we only process an epoch once it has been
         // executed for sure
         let mut queue = EpochQueue::<Vec<H256>>::with_capacity(
-            (DEFERRED_STATE_EPOCH_COUNT - 1) as usize,
+            self.executed_epoch_queue_capacity(),
         );
 
         // loop asynchronously
@@ -218,6 +231,66 @@ impl PubSubClient {
         let fut = fut.unit_error().boxed().compat();
         self.handler.executor.spawn(fut);
     }
+
+    // Start an async loop that recomputes the confirmation risk of the block
+    // or transaction subscribed by `id` on each new epoch, and publishes it
+    // when it changes. The loop terminates when subscriber `id` unsubscribes
+    // or when the risk reaches the minimum maintained by the confirmation
+    // meter, after which it no longer changes.
+    fn start_confirmation_loop(&self, id: SubscriberId) {
+        trace!("start_confirmation_loop({:?})", id);
+
+        // clone everything we use in our async loop
+        let subscribers = self.confirmation_subscribers.clone();
+        let epochs_ordered = self.epochs_ordered.clone();
+        let handler = self.handler.clone();
+
+        // subscribe to the `epochs_ordered` channel
+        let mut receiver = epochs_ordered.subscribe();
+
+        // loop asynchronously
+        let fut = async move {
+            let mut last_result = None;
+
+            // publish the current risk without waiting for the next epoch
+            loop {
+                // retrieve subscriber
+                let (sub, hash) = match subscribers.read().get(&id) {
+                    Some(sub) => sub.clone(),
+                    None => {
+                        // unsubscribed, terminate loop
+                        epochs_ordered.unsubscribe(receiver.id);
+                        return;
+                    }
+                };
+
+                let result = handler.confirmation_risk(&hash);
+                if last_result != Some(result) {
+                    handler.notify_confirmation_risk(&sub, hash, result).await;
+                    last_result = Some(result);
+                }
+
+                if let (_, Some(risk)) = result {
+                    if risk <= CONFIRMATION_METER_MIN_MAINTAINED_RISK {
+                        // confirmed, no more updates
+                        epochs_ordered.unsubscribe(receiver.id);
+                        return;
+                    }
+                }
+
+                match receiver.recv().await {
+                    Some((epoch, _)) => {
+                        trace!("confirmation_loop({:?}): {:?}", id, epoch)
+                    }
+                    None => return,
+                }
+            }
+        };
+
+        // run futures@0.3 future on tokio@0.1 executor
+        let fut = fut.unit_error().boxed().compat();
+        self.handler.executor.spawn(fut);
+    }
 }
 
 /// PubSub notification handler.
@@ -317,6 +390,49 @@ impl ChainNotificationHandler {
         .await
     }
 
+    async fn notify_confirmation_risk(
+        &self, subscriber: &Client, hash: H256,
+        result: (Option<H256>, Option<f64>),
+    )
+    {
+        trace!("notify_confirmation_risk({:?}, {:?})", hash, result);
+
+        let (block_hash, risk) = result;
+
+        Self::notify_async(
+            subscriber,
+            pubsub::Result::ConfirmationRisk {
+                hash,
+                block_hash,
+                confirmation_risk: risk.map(scale_confirmation_risk),
+            },
+        )
+        .await
+    }
+
+    // return the block including `hash` (or `hash` itself if it is a block)
+    // and the confirmation risk of that block
+    fn confirmation_risk(&self, hash: &H256) -> (Option<H256>, Option<f64>) {
+        let consensus_graph =
+            match self.consensus.as_any().downcast_ref::<ConsensusGraph>() {
+                Some(graph) => graph,
+                None => return (None, None),
+            };
+
+        let block_hash = if self.data_man.block_header_by_hash(hash).is_some() {
+            Some(*hash)
+        } else {
+            self.data_man
+                .transaction_index_by_hash(hash, false /* update_cache */)
+                .map(|index| index.block_hash)
+        };
+
+        let risk = block_hash
+            .and_then(|h| consensus_graph.confirmation_risk_by_hash(h));
+
+        (block_hash, risk)
+    }
+
     async fn notify_logs(
         &self, subscriber: &Client, filter: LogFilter, epoch: (u64, Vec<H256>),
     ) {
@@ -519,6 +635,23 @@ impl PubSub for PubSubClient {
                 "logs",
                 "Expected filter parameter.",
             ),
+            // --------- confirmationRisk ---------
+            (
+                pubsub::Kind::ConfirmationRisk,
+                Some(pubsub::Params::Hash(hash)),
+            ) => {
+                let id = self
+                    .confirmation_subscribers
+                    .write()
+                    .push(subscriber, hash);
+
+                self.start_confirmation_loop(id);
+                return;
+            }
+            (pubsub::Kind::ConfirmationRisk, _) => error_codes::invalid_params(
+                "confirmationRisk",
+                "Expected block or transaction hash parameter.",
+            ),
             _ => error_codes::unimplemented(None),
         };
 
@@ -531,7 +664,8 @@ impl PubSub for PubSubClient {
         let res0 = self.heads_subscribers.write().remove(&id).is_some();
         let res1 = self.epochs_subscribers.write().remove(&id).is_some();
         let res2 = self.logs_subscribers.write().remove(&id).is_some();
+        let res3 = self.confirmation_subscribers.write().remove(&id).is_some();
 
-        Ok(res0 || res1 || res2)
+        Ok(res0 || res1 || res2 || res3)
     }
 }
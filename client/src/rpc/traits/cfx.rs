@@ -4,13 +4,15 @@
 
 use super::super::types::{
     Account as RpcAccount, AccountPendingInfo, Block, Bytes, CallRequest,
-    CheckBalanceAgainstTransactionResponse, EpochNumber,
+    CheckBalanceAgainstTransactionResponse, EpochConfirmationRisk, EpochNumber,
     EstimateGasAndCollateralResponse, Log as RpcLog, LogFilter as RpcFilter,
-    Receipt as RpcReceipt, RewardInfo as RpcRewardInfo, SponsorInfo,
-    Status as RpcStatus, TokenSupplyInfo, Transaction,
+    Receipt as RpcReceipt, RewardDetails as RpcRewardDetails,
+    RewardInfo as RpcRewardInfo, SponsorInfo, Status as RpcStatus,
+    TokenSupplyInfo, Transaction,
 };
 use crate::rpc::types::{
-    AccountPendingTransactions, BlockHashOrEpochNumber, RpcAddress,
+    AccountPendingTransactions, BlockHashOrEpochNumber, PivotBlockAnnotation,
+    RpcAddress, SendRawTransactionResult,
 };
 use cfx_types::{H256, U256, U64};
 use jsonrpc_core::{BoxFuture, Result as JsonRpcResult};
@@ -154,6 +156,14 @@ pub trait Cfx {
     #[rpc(name = "cfx_sendRawTransaction")]
     fn send_raw_transaction(&self, raw_tx: Bytes) -> JsonRpcResult<H256>;
 
+    /// Sends a batch of signed transactions, returning the result of each
+    /// transaction in the same order. A transaction that is rejected does not
+    /// affect the others.
+    #[rpc(name = "cfx_sendRawTransactions")]
+    fn send_raw_transactions(
+        &self, raw_txs: Vec<Bytes>,
+    ) -> JsonRpcResult<Vec<SendRawTransactionResult>>;
+
     //        /// @alias of `cfx_sendRawTransaction`.
     //        #[rpc(name = "cfx_submitTransaction")]
     //        fn submit_transaction(&self, Bytes) -> JsonRpcResult<H256>;
@@ -206,6 +216,26 @@ pub trait Cfx {
         &self, epoch_number: EpochNumber,
     ) -> JsonRpcResult<Vec<H256>>;
 
+    /// Returns at most `limit` blocks of the epoch from the `offset`-th block
+    /// in the execution order, for epochs too large for
+    /// `cfx_getBlocksByEpoch`.
+    #[rpc(name = "cfx_getBlocksByEpochPage")]
+    fn blocks_by_epoch_page(
+        &self, epoch_number: EpochNumber, offset: U64, limit: U64,
+    ) -> JsonRpcResult<Vec<H256>>;
+
+    /// Returns the number of blocks in the epoch.
+    #[rpc(name = "cfx_getEpochSize")]
+    fn epoch_size(&self, epoch_number: EpochNumber) -> JsonRpcResult<U64>;
+
+    /// Returns the pivot blocks from `from_epoch` to `to_epoch` (both
+    /// inclusive) with their epoch sizes, gas used, difficulties, flags and
+    /// confirmation risks.
+    #[rpc(name = "cfx_getPivotSegment")]
+    fn pivot_segment(
+        &self, from_epoch: U64, to_epoch: U64,
+    ) -> JsonRpcResult<Vec<PivotBlockAnnotation>>;
+
     #[rpc(name = "cfx_getSkippedBlocksByEpoch")]
     fn skipped_blocks_by_epoch(
         &self, epoch_number: EpochNumber,
@@ -239,6 +269,13 @@ pub trait Cfx {
         &self, block_hash: H256,
     ) -> JsonRpcResult<Option<U256>>;
 
+    /// Returns the confirmation risk of the pivot block of an epoch, with
+    /// the name of the model computing it.
+    #[rpc(name = "cfx_getEpochConfirmationRisk")]
+    fn epoch_confirmation_risk(
+        &self, epoch_number: EpochNumber,
+    ) -> JsonRpcResult<Option<EpochConfirmationRisk>>;
+
     #[rpc(name = "cfx_getStatus")]
     fn get_status(&self) -> JsonRpcResult<RpcStatus>;
 
@@ -248,6 +285,13 @@ pub trait Cfx {
         &self, num: EpochNumber,
     ) -> JsonRpcResult<Vec<RpcRewardInfo>>;
 
+    /// Returns the reward of a block on the pivot chain with the anticone
+    /// penalty and the reasons it gets no base reward.
+    #[rpc(name = "cfx_getBlockRewardDetails")]
+    fn get_block_reward_details(
+        &self, block_hash: H256,
+    ) -> JsonRpcResult<Option<RpcRewardDetails>>;
+
     /// Return the client version as a string
     #[rpc(name = "cfx_clientVersion")]
     fn get_client_version(&self) -> JsonRpcResult<String>;
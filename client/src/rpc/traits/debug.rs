@@ -3,12 +3,16 @@
 // See http://www.gnu.org/licenses/
 
 use super::super::types::{
-    BlockHashOrEpochNumber, Bytes as RpcBytes, ConsensusGraphStates,
-    Receipt as RpcReceipt, RpcAddress, SyncGraphStates,
-    Transaction as RpcTransaction, TxPoolPendingInfo, TxWithPoolInfo,
+    BlockConsensusInfo, BlockHashOrEpochNumber, BlockPropagationRecord,
+    Bytes as RpcBytes, ConsensusBlockIdQuery, ConsensusBlockIds,
+    ConsensusGraphStates, EpochExportCursor, EpochPackingStatistics,
+    ExecutionWorkerStatus, GhastDecision, PivotSwitchEvent,
+    Receipt as RpcReceipt, RpcAddress, SnapshotArchiveInfo, StateAvailability,
+    SyncGraphStates, Transaction as RpcTransaction, TxPoolPendingInfo,
+    TxSkipDiagnosis, TxWithPoolInfo,
 };
 use crate::rpc::types::SendTxRequest;
-use cfx_types::{H256, H520, U128};
+use cfx_types::{H256, H520, U128, U64};
 use jsonrpc_core::{BoxFuture, Result as JsonRpcResult};
 use jsonrpc_derive::rpc;
 use network::{
@@ -82,6 +86,105 @@ pub trait LocalRpc {
     #[rpc(name = "sync_graph_state")]
     fn sync_graph_state(&self) -> JsonRpcResult<SyncGraphStates>;
 
+    /// Returns the stable ID, the hash and the current arena index of a block
+    /// in the consensus graph, looked up by any of them.
+    #[rpc(name = "consensus_block_ids")]
+    fn consensus_block_ids(
+        &self, query: ConsensusBlockIdQuery,
+    ) -> JsonRpcResult<Option<ConsensusBlockIds>>;
+
+    /// Recomputes the adaptive and parent decisions of a block in the
+    /// consensus graph in its past view, and returns the intermediate values.
+    /// This is slow for the blocks far from the force confirmed block.
+    #[rpc(name = "ghast_decision")]
+    fn ghast_decision(
+        &self, block_hash: H256,
+    ) -> JsonRpcResult<Option<GhastDecision>>;
+
+    /// Returns the consensus state of a block in the current era: its epoch,
+    /// the adaptive, heavy and timer flags, the weights and the anticone size.
+    /// Returns `None` if the block is not in the consensus graph.
+    #[rpc(name = "block_consensus_info")]
+    fn block_consensus_info(
+        &self, block_hash: H256,
+    ) -> JsonRpcResult<Option<BlockConsensusInfo>>;
+
+    /// Returns the RLP encoded proof of the cumulative weight of the pivot
+    /// blocks after the era genesis at `anchor_height` up to `end_height`,
+    /// for the relays of light bridges.
+    #[rpc(name = "chain_weight_proof")]
+    fn chain_weight_proof(
+        &self, anchor_height: U64, end_height: U64,
+    ) -> JsonRpcResult<RpcBytes>;
+
+    /// Returns the propagation record of a block received in the recorded
+    /// history.
+    #[rpc(name = "block_propagation_record")]
+    fn block_propagation_record(
+        &self, block_hash: H256,
+    ) -> JsonRpcResult<Option<BlockPropagationRecord>>;
+
+    /// Returns at most `limit` latest block propagation records, the newest
+    /// first.
+    #[rpc(name = "block_propagation_records")]
+    fn block_propagation_records(
+        &self, limit: usize,
+    ) -> JsonRpcResult<Vec<BlockPropagationRecord>>;
+
+    /// Returns the number of execution workers and the cost of the executed
+    /// epochs.
+    #[rpc(name = "execution_worker_status")]
+    fn execution_worker_status(&self) -> JsonRpcResult<ExecutionWorkerStatus>;
+
+    /// Changes the number of execution workers, at most 2. The extra workers
+    /// exit after finishing their current tasks.
+    #[rpc(name = "set_execution_worker_count")]
+    fn set_execution_worker_count(&self, count: usize) -> JsonRpcResult<()>;
+
+    /// Returns the numbers of duplicated, stale-nonce, future-nonce and
+    /// unaffordable transactions in at most `limit` latest executed epochs,
+    /// the newest first, and their totals since the node starts.
+    #[rpc(name = "epoch_packing_statistics")]
+    fn epoch_packing_statistics(
+        &self, limit: usize,
+    ) -> JsonRpcResult<EpochPackingStatistics>;
+
+    /// Explains why the transaction is not executed normally in one of the
+    /// latest executed epochs, with the execution order of the transactions
+    /// of its sender in that epoch. Returns `None` if no such epoch is kept.
+    #[rpc(name = "tx_skip_diagnosis")]
+    fn tx_skip_diagnosis(
+        &self, tx_hash: H256,
+    ) -> JsonRpcResult<Option<TxSkipDiagnosis>>;
+
+    /// Returns the range of epochs whose states are kept in the storage and
+    /// the epochs in the range whose states cannot be read, so a caller can
+    /// tell whether a call at an epoch can be served.
+    #[rpc(name = "state_availability")]
+    fn state_availability(&self) -> JsonRpcResult<StateAvailability>;
+
+    /// Returns at most `limit` pivot chain switches recorded by the node,
+    /// starting from the sequence number `from_seq`.
+    #[rpc(name = "pivot_switch_history")]
+    fn pivot_switch_history(
+        &self, from_seq: U64, limit: usize,
+    ) -> JsonRpcResult<Vec<PivotSwitchEvent>>;
+
+    /// Exports at most `max_epochs` confirmed epochs after the export cursor
+    /// to the files in `epoch_export_dir`, and returns the new cursor.
+    #[rpc(name = "export_epochs")]
+    fn export_epochs(
+        &self, max_epochs: U64,
+    ) -> JsonRpcResult<EpochExportCursor>;
+
+    /// Exports the snapshot at the confirmed pivot block of
+    /// `snapshot_epoch_number` to an archive in `snapshot_archive_dir`, which
+    /// a syncing node can restore the state from.
+    #[rpc(name = "export_snapshot_archive")]
+    fn export_snapshot_archive(
+        &self, snapshot_epoch_number: U64,
+    ) -> JsonRpcResult<SnapshotArchiveInfo>;
+
     #[rpc(name = "cfx_sendTransaction")]
     fn send_transaction(
         &self, tx: SendTxRequest, password: Option<String>,
@@ -2,7 +2,7 @@
 // Conflux is free software and distributed under GNU General Public License.
 // See http://www.gnu.org/licenses/
 
-use super::super::types::{BlameInfo, Block, Bytes};
+use super::super::types::{BlameInfo, Block, Bytes, StateRegenerationProgress};
 use cfx_types::{H256, U256};
 use cfxcore::PeerInfo;
 use jsonrpc_core::Result as RpcResult;
@@ -96,6 +96,11 @@ pub trait TestRpc {
         &self, height_range: Option<(u64, u64)>,
     ) -> RpcResult<Vec<(H256, U256)>>;
 
+    #[rpc(name = "getBlockTotalWeight")]
+    fn get_block_total_weight(
+        &self, block_hash: H256,
+    ) -> RpcResult<Option<U256>>;
+
     #[rpc(name = "getExecutedInfo")]
     fn get_executed_info(&self, block_hash: H256) -> RpcResult<(H256, H256)>;
     #[rpc(name = "test_sendUsableGenesisAccounts")]
@@ -110,4 +115,31 @@ pub trait TestRpc {
 
     #[rpc(name = "save_node_db")]
     fn save_node_db(&self) -> RpcResult<()>;
+
+    /// Delay every execution of the epoch by `delay_ms` milliseconds, or
+    /// remove the delay if it is `null`.
+    #[rpc(name = "test_setEpochExecutionDelay")]
+    fn set_epoch_execution_delay(
+        &self, epoch_hash: H256, delay_ms: Option<u64>,
+    ) -> RpcResult<()>;
+
+    /// Drop the cached anticone sets of the blocks and return the number of
+    /// dropped entries.
+    #[rpc(name = "test_dropAnticoneCacheEntries")]
+    fn drop_anticone_cache_entries(
+        &self, block_hashes: Vec<H256>,
+    ) -> RpcResult<usize>;
+
+    /// Make the next block inserted to consensus re-evaluate the pivot chain
+    /// from `from_height`.
+    #[rpc(name = "test_forcePivotReevaluation")]
+    fn force_pivot_reevaluation(&self, from_height: u64) -> RpcResult<()>;
+
+    /// Replay at most `max_epochs` epochs needed for the state of the block
+    /// within `max_wall_time_ms` milliseconds. Call it again to continue
+    /// until the returned progress is done.
+    #[rpc(name = "test_regenerateState")]
+    fn regenerate_state(
+        &self, block_hash: H256, max_epochs: usize, max_wall_time_ms: u64,
+    ) -> RpcResult<StateRegenerationProgress>;
 }
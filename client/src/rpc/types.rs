@@ -6,45 +6,74 @@ mod account;
 pub mod address;
 mod blame_info;
 mod block;
+mod block_consensus_info;
+mod block_propagation_record;
 mod bytes;
 pub mod call_request;
+mod consensus_block_ids;
 mod consensus_graph_states;
+mod epoch_confirmation_risk;
+mod epoch_export_cursor;
 mod epoch_number;
+mod epoch_packing_statistics;
 pub mod errors;
+mod execution_worker_status;
 mod filter;
+mod ghast_decision;
 mod index;
 mod log;
+mod pivot_block_annotation;
+mod pivot_switch_event;
 mod provenance;
 pub mod pubsub;
 mod receipt;
 mod reward_info;
+mod send_raw_transaction_result;
+mod snapshot_archive_info;
 mod sponsor_info;
+mod state_availability;
+mod state_regeneration_progress;
 mod status;
 mod sync_graph_states;
 mod token_supply_info;
 mod trace;
 mod trace_filter;
 mod transaction;
+mod tx_skip_diagnosis;
 
 pub use self::{
     account::Account,
     address::RpcAddress,
     blame_info::BlameInfo,
     block::{Block, BlockTransactions, Header},
+    block_consensus_info::BlockConsensusInfo,
+    block_propagation_record::BlockPropagationRecord,
     bytes::Bytes,
     call_request::{
         sign_call, CallRequest, CheckBalanceAgainstTransactionResponse,
         EstimateGasAndCollateralResponse, SendTxRequest, MAX_GAS_CALL_REQUEST,
     },
+    consensus_block_ids::{ConsensusBlockIdQuery, ConsensusBlockIds},
     consensus_graph_states::ConsensusGraphStates,
+    epoch_confirmation_risk::EpochConfirmationRisk,
+    epoch_export_cursor::EpochExportCursor,
     epoch_number::{BlockHashOrEpochNumber, EpochNumber},
+    epoch_packing_statistics::EpochPackingStatistics,
+    execution_worker_status::ExecutionWorkerStatus,
     filter::LogFilter,
+    ghast_decision::GhastDecision,
     index::Index,
     log::Log,
+    pivot_block_annotation::PivotBlockAnnotation,
+    pivot_switch_event::PivotSwitchEvent,
     provenance::Origin,
     receipt::Receipt,
-    reward_info::RewardInfo,
+    reward_info::{RewardDetails, RewardInfo},
+    send_raw_transaction_result::SendRawTransactionResult,
+    snapshot_archive_info::SnapshotArchiveInfo,
     sponsor_info::SponsorInfo,
+    state_availability::StateAvailability,
+    state_regeneration_progress::StateRegenerationProgress,
     status::Status,
     sync_graph_states::SyncGraphStates,
     token_supply_info::TokenSupplyInfo,
@@ -56,4 +85,5 @@ pub use self::{
         AccountPendingInfo, AccountPendingTransactions, PackedOrExecuted,
         Transaction, TxPoolPendingInfo, TxWithPoolInfo,
     },
+    tx_skip_diagnosis::TxSkipDiagnosis,
 };
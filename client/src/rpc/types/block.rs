@@ -198,6 +198,7 @@ impl Block {
                                             } else {
                                                 Some(tx_exec_error_msg.clone())
                                             },
+                                            execution_result.block_receipts.tx_execution_error_info(idx),
                                             network,
                                         )?)),
                                         network,
@@ -318,8 +319,8 @@ impl Block {
             BlockTransactions::Full(vec) => Ok(PrimitiveBlock::new(
                 BlockHeaderBuilder::new()
                     .with_parent_hash(self.parent_hash.into())
-                    .with_height(self.height.as_usize() as u64)
-                    .with_timestamp(self.timestamp.as_usize() as u64)
+                    .with_height(self.height.as_u64())
+                    .with_timestamp(self.timestamp.as_u64())
                     .with_author(miner)
                     .with_transactions_root(self.transactions_root.into())
                     .with_deferred_state_root(self.deferred_state_root.into())
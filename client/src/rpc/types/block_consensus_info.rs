@@ -0,0 +1,64 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfx_types::{H256, U64};
+use cfxcore::consensus::BlockConsensusInfo as PrimitiveBlockConsensusInfo;
+
+/// The result of `block_consensus_info`. The weights are decimal strings
+/// because they may exceed the range of JSON numbers.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockConsensusInfo {
+    pub hash: H256,
+    pub stable_id: U64,
+    pub height: U64,
+    pub parent_hash: H256,
+    pub epoch_number: Option<U64>,
+    pub last_pivot_in_past: U64,
+    pub adaptive: bool,
+    pub is_heavy: bool,
+    pub is_timer: bool,
+    pub activated: bool,
+    pub pending: bool,
+    pub partial_invalid: bool,
+    pub partial_invalid_cause: Option<String>,
+    pub stable: bool,
+    pub block_weight: String,
+    pub subtree_weight: String,
+    pub past_era_weight: Option<String>,
+    pub anticone_size: Option<usize>,
+    pub state_valid: Option<bool>,
+    pub state_valid_source: Option<String>,
+}
+
+impl BlockConsensusInfo {
+    pub fn new(info: PrimitiveBlockConsensusInfo) -> Self {
+        Self {
+            hash: info.hash,
+            stable_id: info.stable_id.into(),
+            height: info.height.into(),
+            parent_hash: info.parent_hash,
+            epoch_number: info.epoch_number.map(Into::into),
+            last_pivot_in_past: info.last_pivot_in_past.into(),
+            adaptive: info.adaptive,
+            is_heavy: info.is_heavy,
+            is_timer: info.is_timer,
+            activated: info.activated,
+            pending: info.pending,
+            partial_invalid: info.partial_invalid,
+            partial_invalid_cause: info
+                .partial_invalid_cause
+                .map(|cause| format!("{:?}", cause)),
+            stable: info.stable,
+            block_weight: info.block_weight.to_string(),
+            subtree_weight: info.subtree_weight.to_string(),
+            past_era_weight: info.past_era_weight.map(|w| w.to_string()),
+            anticone_size: info.anticone_size,
+            state_valid: info.state_valid,
+            state_valid_source: info
+                .state_valid_source
+                .map(|source| format!("{:?}", source)),
+        }
+    }
+}
@@ -0,0 +1,39 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfx_types::{H256, U64};
+use cfxcore::block_data_manager::{
+    BlockOrigin, BlockPropagationRecord as PrimitiveBlockPropagationRecord,
+};
+
+#[derive(Debug, Serialize, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockPropagationRecord {
+    pub block_hash: H256,
+    /// One of "local", "miner" and "peer".
+    pub origin: String,
+    /// Milliseconds since the unix epoch.
+    pub first_seen: U64,
+    /// Milliseconds since the unix epoch.
+    pub graph_ready: U64,
+    /// Milliseconds from `first_seen` to `graph_ready`.
+    pub time_to_graph_ready: U64,
+}
+
+impl BlockPropagationRecord {
+    pub fn new(record: PrimitiveBlockPropagationRecord) -> Self {
+        let origin = match record.origin {
+            BlockOrigin::Local => "local",
+            BlockOrigin::Miner => "miner",
+            BlockOrigin::Peer => "peer",
+        };
+        Self {
+            block_hash: record.block_hash,
+            origin: origin.into(),
+            first_seen: record.first_seen_ms.into(),
+            graph_ready: record.graph_ready_ms.into(),
+            time_to_graph_ready: record.time_to_graph_ready_ms().into(),
+        }
+    }
+}
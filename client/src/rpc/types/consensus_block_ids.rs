@@ -0,0 +1,36 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfx_types::{H256, U64};
+use cfxcore::consensus::ConsensusBlockIds as PrimitiveConsensusBlockIds;
+
+/// The identifier used to look up a block in the consensus graph.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ConsensusBlockIdQuery {
+    Hash(H256),
+    StableId(U64),
+    ArenaIndex(U64),
+}
+
+#[derive(Debug, Serialize, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsensusBlockIds {
+    /// The sequence number of the block, which is not reused by other blocks
+    /// while the node is running.
+    pub stable_id: U64,
+    pub hash: H256,
+    /// The current index of the block in the consensus graph arena.
+    pub arena_index: U64,
+}
+
+impl ConsensusBlockIds {
+    pub fn new(ids: PrimitiveConsensusBlockIds) -> Self {
+        Self {
+            stable_id: ids.stable_id.into(),
+            hash: ids.hash,
+            arena_index: (ids.arena_index as u64).into(),
+        }
+    }
+}
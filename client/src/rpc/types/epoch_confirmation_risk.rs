@@ -0,0 +1,16 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfx_types::{U256, U64};
+
+/// The result of `cfx_getEpochConfirmationRisk`.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct EpochConfirmationRisk {
+    pub epoch_number: U64,
+    /// Scaled in the same way as `cfx_getConfirmationRiskByHash`.
+    pub confirmation_risk: U256,
+    /// The name of the configured model computing the risk.
+    pub risk_model: String,
+}
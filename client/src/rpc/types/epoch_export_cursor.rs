@@ -0,0 +1,32 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfx_types::{H256, U64};
+use cfxcore::consensus::epoch_exporter::EpochExportCursor as PrimitiveEpochExportCursor;
+
+/// The export cursor in the result of `export_epochs`.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct EpochExportCursor {
+    /// The first epoch not exported yet.
+    pub next_epoch_number: U64,
+    /// The pivot block of the last exported epoch.
+    pub last_epoch_hash: H256,
+    /// The lengths of the exported files in bytes.
+    pub transactions_file_len: U64,
+    pub receipts_file_len: U64,
+    pub logs_file_len: U64,
+}
+
+impl EpochExportCursor {
+    pub fn new(cursor: PrimitiveEpochExportCursor) -> Self {
+        Self {
+            next_epoch_number: cursor.next_epoch_number.into(),
+            last_epoch_hash: cursor.last_epoch_hash,
+            transactions_file_len: cursor.transactions_file_len.into(),
+            receipts_file_len: cursor.receipts_file_len.into(),
+            logs_file_len: cursor.logs_file_len.into(),
+        }
+    }
+}
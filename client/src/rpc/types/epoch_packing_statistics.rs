@@ -0,0 +1,80 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfx_types::{H256, U64};
+use cfxcore::statistics::epoch_packing::{
+    EpochPackingCounters as PrimitiveEpochPackingCounters,
+    EpochPackingRecord as PrimitiveEpochPackingRecord,
+};
+
+#[derive(Debug, Serialize, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EpochPackingCounters {
+    pub transaction_count: U64,
+    /// Transactions already executed earlier in the same epoch.
+    pub duplicated_count: U64,
+    /// Transactions whose nonces are lower than the sender nonces.
+    pub stale_nonce_count: U64,
+    /// Transactions whose nonces are higher than the sender nonces.
+    pub future_nonce_count: U64,
+    /// Transactions whose senders or sponsors cannot afford the costs.
+    pub insufficient_balance_count: U64,
+    /// Transactions skipped because their senders exceed the gas budget of a
+    /// sender in an epoch.
+    pub sender_gas_budget_exceeded_count: U64,
+}
+
+impl EpochPackingCounters {
+    fn new(counters: PrimitiveEpochPackingCounters) -> Self {
+        Self {
+            transaction_count: counters.transaction_count.into(),
+            duplicated_count: counters.duplicated_count.into(),
+            stale_nonce_count: counters.stale_nonce_count.into(),
+            future_nonce_count: counters.future_nonce_count.into(),
+            insufficient_balance_count: counters
+                .insufficient_balance_count
+                .into(),
+            sender_gas_budget_exceeded_count: counters
+                .sender_gas_budget_exceeded_count
+                .into(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EpochPackingRecord {
+    pub epoch_hash: H256,
+    pub epoch_number: U64,
+    pub counters: EpochPackingCounters,
+}
+
+#[derive(Debug, Serialize, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EpochPackingStatistics {
+    /// The latest executed epochs on the pivot chain, the newest first.
+    pub epochs: Vec<EpochPackingRecord>,
+    /// The sum of the counters of the epochs executed since the node starts.
+    pub total: EpochPackingCounters,
+}
+
+impl EpochPackingStatistics {
+    pub fn new(
+        records: Vec<PrimitiveEpochPackingRecord>,
+        total: PrimitiveEpochPackingCounters,
+    ) -> Self
+    {
+        Self {
+            epochs: records
+                .into_iter()
+                .map(|record| EpochPackingRecord {
+                    epoch_hash: record.epoch_hash,
+                    epoch_number: record.epoch_height.into(),
+                    counters: EpochPackingCounters::new(record.counters),
+                })
+                .collect(),
+            total: EpochPackingCounters::new(total),
+        }
+    }
+}
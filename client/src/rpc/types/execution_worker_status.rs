@@ -0,0 +1,61 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfx_types::U64;
+use cfxcore::consensus::consensus_inner::consensus_executor::{
+    ExecutionTaskStats as PrimitiveExecutionTaskStats,
+    ExecutionWorkerStatus as PrimitiveExecutionWorkerStatus,
+};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Serialize, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecutionTaskStats {
+    pub task_count: U64,
+    /// Milliseconds spent on executing the epochs.
+    pub wall_time: U64,
+    /// Milliseconds of CPU time of the workers, which is 0 if the platform
+    /// does not support per-thread CPU time.
+    pub cpu_time: U64,
+}
+
+impl ExecutionTaskStats {
+    fn new(stats: PrimitiveExecutionTaskStats) -> Self {
+        Self {
+            task_count: stats.task_count.into(),
+            wall_time: (stats.wall_time.as_millis() as u64).into(),
+            cpu_time: (stats.cpu_time.as_millis() as u64).into(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecutionWorkerStatus {
+    pub worker_count: U64,
+    /// The epochs executed for the pivot chain.
+    pub head: ExecutionTaskStats,
+    /// The epochs replayed to compute the state of forks.
+    pub replay: ExecutionTaskStats,
+    /// The epochs executed for each origin, e.g. `new_block` or
+    /// `fork_replay`.
+    pub by_origin: BTreeMap<String, ExecutionTaskStats>,
+}
+
+impl ExecutionWorkerStatus {
+    pub fn new(status: PrimitiveExecutionWorkerStatus) -> Self {
+        Self {
+            worker_count: (status.worker_count as u64).into(),
+            head: ExecutionTaskStats::new(status.head_stats),
+            replay: ExecutionTaskStats::new(status.replay_stats),
+            by_origin: status
+                .origin_stats
+                .into_iter()
+                .map(|(origin, stats)| {
+                    (origin.name().into(), ExecutionTaskStats::new(stats))
+                })
+                .collect(),
+        }
+    }
+}
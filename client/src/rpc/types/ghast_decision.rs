@@ -0,0 +1,76 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfx_types::{H256, U64};
+use cfxcore::consensus::{
+    GhastAncestorDecision as PrimitiveGhastAncestorDecision,
+    GhastDecision as PrimitiveGhastDecision,
+};
+
+/// An ancestor checked in the adaptive decision of a block. The weights are
+/// decimal strings because they may be negative.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct GhastAncestorDecision {
+    pub hash: H256,
+    pub height: U64,
+    pub timer_chain_height: U64,
+    pub checked: bool,
+    /// f(x) of the ancestor.
+    pub subtree_weight: String,
+    /// g(x) of the ancestor.
+    pub sibling_weight: String,
+}
+
+impl GhastAncestorDecision {
+    fn new(ancestor: PrimitiveGhastAncestorDecision) -> Self {
+        Self {
+            hash: ancestor.hash,
+            height: ancestor.height.into(),
+            timer_chain_height: ancestor.timer_chain_height.into(),
+            checked: ancestor.checked,
+            subtree_weight: ancestor.subtree_weight.to_string(),
+            sibling_weight: ancestor.sibling_weight.to_string(),
+        }
+    }
+}
+
+/// The result of `ghast_decision`.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct GhastDecision {
+    pub hash: H256,
+    pub parent_hash: H256,
+    pub force_confirm_hash: H256,
+    pub timer_chain_height: U64,
+    pub timer_chain_beta: U64,
+    pub adjusted_beta: String,
+    pub ancestors: Vec<GhastAncestorDecision>,
+    pub adaptive: bool,
+    pub correct_parent: bool,
+    pub recorded_adaptive: bool,
+    pub recorded_partial_invalid: bool,
+}
+
+impl GhastDecision {
+    pub fn new(decision: PrimitiveGhastDecision) -> Self {
+        Self {
+            hash: decision.hash,
+            parent_hash: decision.parent_hash,
+            force_confirm_hash: decision.force_confirm_hash,
+            timer_chain_height: decision.timer_chain_height.into(),
+            timer_chain_beta: decision.timer_chain_beta.into(),
+            adjusted_beta: decision.adjusted_beta.to_string(),
+            ancestors: decision
+                .ancestors
+                .into_iter()
+                .map(GhastAncestorDecision::new)
+                .collect(),
+            adaptive: decision.adaptive,
+            correct_parent: decision.correct_parent,
+            recorded_adaptive: decision.recorded_adaptive,
+            recorded_partial_invalid: decision.recorded_partial_invalid,
+        }
+    }
+}
@@ -0,0 +1,41 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::rpc::impls::common::scale_confirmation_risk;
+use cfx_types::{H256, U256, U64};
+use cfxcore::consensus::PivotBlockAnnotation as PrimitivePivotBlockAnnotation;
+
+/// A pivot block in the result of `cfx_getPivotSegment`.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PivotBlockAnnotation {
+    pub epoch_number: U64,
+    pub hash: H256,
+    /// The number of executed blocks in the epoch, including the pivot block.
+    pub epoch_size: U64,
+    /// `None` if the epoch is not executed yet.
+    pub epoch_gas_used: Option<U256>,
+    pub difficulty: U256,
+    pub adaptive: bool,
+    pub stable: bool,
+    /// Scaled in the same way as `cfx_getConfirmationRiskByHash`.
+    pub confirmation_risk: Option<U256>,
+}
+
+impl PivotBlockAnnotation {
+    pub fn new(annotation: PrimitivePivotBlockAnnotation) -> Self {
+        Self {
+            epoch_number: annotation.epoch_number.into(),
+            hash: annotation.hash,
+            epoch_size: (annotation.epoch_size as u64).into(),
+            epoch_gas_used: annotation.epoch_gas_used,
+            difficulty: annotation.difficulty,
+            adaptive: annotation.adaptive,
+            stable: annotation.stable,
+            confirmation_risk: annotation
+                .confirmation_risk
+                .map(scale_confirmation_risk),
+        }
+    }
+}
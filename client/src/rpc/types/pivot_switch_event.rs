@@ -0,0 +1,34 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfx_types::{H256, U64};
+use cfxcore::block_data_manager::PivotSwitchEvent as PrimitivePivotSwitchEvent;
+
+/// A pivot chain switch in the result of `pivot_switch_history`.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PivotSwitchEvent {
+    pub seq: U64,
+    /// The height of the first pivot block that is replaced.
+    pub fork_height: U64,
+    pub old_tip: H256,
+    pub new_tip: H256,
+    /// The block whose arrival caused the switch.
+    pub trigger_block: H256,
+    /// The unix timestamp in seconds when the switch was recorded.
+    pub timestamp: U64,
+}
+
+impl PivotSwitchEvent {
+    pub fn new(event: PrimitivePivotSwitchEvent) -> Self {
+        Self {
+            seq: event.seq.into(),
+            fork_height: event.fork_height.into(),
+            old_tip: event.old_tip,
+            new_tip: event.new_tip,
+            trigger_block: event.trigger_block,
+            timestamp: event.timestamp.into(),
+        }
+    }
+}
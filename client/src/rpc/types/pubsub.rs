@@ -51,6 +51,14 @@ pub enum Result {
     /// Chain reorg
     #[serde(rename_all = "camelCase")]
     ChainReorg { revert_to: U256 },
+
+    /// Confirmation risk of a subscribed block or transaction
+    #[serde(rename_all = "camelCase")]
+    ConfirmationRisk {
+        hash: H256,
+        block_hash: Option<H256>,
+        confirmation_risk: Option<U256>,
+    },
 }
 
 /// Subscription kind.
@@ -68,6 +76,8 @@ pub enum Kind {
     Syncing,
     /// Epoch
     Epochs,
+    /// Confirmation risk of a block or a transaction.
+    ConfirmationRisk,
 }
 
 /// Subscription epoch.
@@ -90,6 +100,8 @@ pub enum Params {
     Logs(LogFilter),
     /// Epoch parameters.
     Epochs(SubscriptionEpoch),
+    /// Block or transaction hash.
+    Hash(H256),
 }
 
 impl Default for Params {
@@ -112,6 +124,11 @@ impl<'a> Deserialize<'a> for Params {
             return Ok(v);
         }
 
+        // try to interpret as a block or transaction hash
+        if let Ok(v) = from_value(v.clone()).map(Params::Hash) {
+            return Ok(v);
+        }
+
         // otherwise, interpret as epoch
         from_value(v).map(Params::Epochs).map_err(|e| {
             D::Error::custom(format!("Invalid Pub-Sub parameters: {}", e))
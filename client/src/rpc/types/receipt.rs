@@ -9,6 +9,7 @@ use cfxcore::{executive::contract_address, vm::CreateContractAddress};
 use primitives::{
     receipt::{
         Receipt as PrimitiveReceipt, StorageChange as PrimitiveStorageChange,
+        TxExecutionErrorInfo,
     },
     transaction::Action,
     SignedTransaction as PrimitiveTransaction, TransactionIndex,
@@ -66,6 +67,14 @@ pub struct Receipt {
     /// is None if tx execution is successful or it can not be offered.
     /// Error message can not be offered by light client.
     pub tx_exec_error_msg: Option<String>,
+    /// The reason decoded from the revert data if the transaction is
+    /// reverted with a recognized reason, e.g. `require(condition, reason)`.
+    pub revert_reason: Option<String>,
+    /// The block where the transaction is executed, if it is not executed in
+    /// this block because it is already executed earlier in the same epoch.
+    pub effective_block_hash: Option<H256>,
+    /// The index of the transaction in `effective_block_hash`.
+    pub effective_index: Option<U64>,
     // Whether gas costs were covered by the sponsor.
     pub gas_covered_by_sponsor: bool,
     // Whether storage costs were covered by the sponsor.
@@ -82,7 +91,7 @@ impl Receipt {
         transaction_index: TransactionIndex, prior_gas_used: U256,
         epoch_number: Option<u64>, block_number: u64,
         maybe_state_root: Option<H256>, tx_exec_error_msg: Option<String>,
-        network: Network,
+        tx_exec_error_info: TxExecutionErrorInfo, network: Network,
     ) -> Result<Receipt, String>
     {
         let PrimitiveReceipt {
@@ -119,6 +128,11 @@ impl Receipt {
             .map(Into::into)
             .unwrap_or_default();
 
+        let TxExecutionErrorInfo {
+            executed_position: effective_position,
+            revert_reason,
+        } = tx_exec_error_info;
+
         Ok(Receipt {
             transaction_hash: transaction.hash.into(),
             index: U64::from(transaction_index.index),
@@ -143,6 +157,12 @@ impl Receipt {
                 .map_or_else(Default::default, Into::into),
             epoch_number: epoch_number.map(U64::from),
             tx_exec_error_msg,
+            revert_reason,
+            effective_block_hash: effective_position
+                .as_ref()
+                .map(|position| position.block_hash),
+            effective_index: effective_position
+                .map(|position| U64::from(position.index)),
             gas_covered_by_sponsor: gas_sponsor_paid,
             storage_covered_by_sponsor: storage_sponsor_paid,
             storage_collateralized,
@@ -1,6 +1,6 @@
 use super::RpcAddress;
 use cfx_types::{H256, U256};
-use cfxcore::block_data_manager::BlockRewardResult;
+use cfxcore::block_data_manager::{BlockRewardDetails, BlockRewardResult};
 
 #[derive(Debug, Serialize, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -25,3 +25,45 @@ impl RewardInfo {
         }
     }
 }
+
+/// The reward of a block with how its base reward is computed.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RewardDetails {
+    block_hash: H256,
+    /// The pivot block of the epoch in which the reward is computed.
+    epoch_hash: H256,
+    total_reward: U256,
+    base_reward: U256,
+    tx_fee: U256,
+    epoch_difficulty: U256,
+    base_reward_per_block: U256,
+    anticone_difficulty: U256,
+    anticone_overlimited: bool,
+    no_reward: bool,
+    insufficient_pow_quality: bool,
+    anticone_penalty: U256,
+}
+
+impl RewardDetails {
+    pub fn new(
+        block_hash: H256, reward_result: BlockRewardResult,
+        details: BlockRewardDetails,
+    ) -> Self
+    {
+        RewardDetails {
+            block_hash,
+            epoch_hash: details.epoch_hash,
+            total_reward: reward_result.total_reward,
+            base_reward: reward_result.base_reward,
+            tx_fee: reward_result.tx_fee,
+            epoch_difficulty: details.epoch_difficulty,
+            base_reward_per_block: details.base_reward_per_block,
+            anticone_difficulty: details.anticone_difficulty,
+            anticone_overlimited: details.anticone_overlimited,
+            no_reward: details.no_reward,
+            insufficient_pow_quality: details.insufficient_pow_quality,
+            anticone_penalty: details.anticone_penalty,
+        }
+    }
+}
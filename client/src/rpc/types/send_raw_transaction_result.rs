@@ -0,0 +1,52 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfx_types::H256;
+use cfxcore::transaction_pool::TxInsertionResult;
+
+/// The result of a raw transaction in `cfx_sendRawTransactions`.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SendRawTransactionResult {
+    /// The hash of the transaction, or `None` if the raw transaction cannot be
+    /// decoded.
+    pub hash: Option<H256>,
+    /// One of `added`, `replaced` and `rejected`.
+    pub status: String,
+    /// The hash of the transaction replaced by this one.
+    pub replaced: Option<H256>,
+    /// The reason why the transaction is rejected.
+    pub reason: Option<String>,
+}
+
+impl SendRawTransactionResult {
+    pub fn new(hash: H256, result: TxInsertionResult) -> Self {
+        match result {
+            TxInsertionResult::Added => Self {
+                hash: Some(hash),
+                status: "added".into(),
+                replaced: None,
+                reason: None,
+            },
+            TxInsertionResult::Replaced(replaced) => Self {
+                hash: Some(hash),
+                status: "replaced".into(),
+                replaced: Some(replaced),
+                reason: None,
+            },
+            TxInsertionResult::Rejected { reason } => {
+                Self::rejected(Some(hash), reason)
+            }
+        }
+    }
+
+    pub fn rejected(hash: Option<H256>, reason: String) -> Self {
+        Self {
+            hash,
+            status: "rejected".into(),
+            replaced: None,
+            reason: Some(reason),
+        }
+    }
+}
@@ -0,0 +1,30 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfx_types::{H256, U64};
+use cfxcore::sync::SnapshotArchiveManifest;
+
+/// The snapshot archive written by `export_snapshot_archive`.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotArchiveInfo {
+    /// The pivot block of the snapshot.
+    pub snapshot_epoch_id: H256,
+    pub snapshot_height: U64,
+    /// The merkle root of the snapshot, which the chunks are verified with
+    /// when the archive is imported.
+    pub merkle_root: H256,
+    pub chunk_count: U64,
+}
+
+impl SnapshotArchiveInfo {
+    pub fn new(manifest: &SnapshotArchiveManifest) -> Self {
+        Self {
+            snapshot_epoch_id: manifest.snapshot_epoch_id,
+            snapshot_height: manifest.snapshot_height.into(),
+            merkle_root: manifest.merkle_root,
+            chunk_count: (manifest.chunk_hashes.len() as u64).into(),
+        }
+    }
+}
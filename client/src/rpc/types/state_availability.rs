@@ -0,0 +1,25 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfx_types::U64;
+use cfxcore::consensus::StateAvailability as PrimitiveStateAvailability;
+
+#[derive(Debug, Serialize, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StateAvailability {
+    pub lower_bound: U64,
+    pub upper_bound: U64,
+    /// The epochs between the bounds whose states cannot be read.
+    pub gaps: Vec<U64>,
+}
+
+impl StateAvailability {
+    pub fn new(availability: PrimitiveStateAvailability) -> Self {
+        Self {
+            lower_bound: availability.lower_bound.into(),
+            upper_bound: availability.upper_bound.into(),
+            gaps: availability.gaps.into_iter().map(Into::into).collect(),
+        }
+    }
+}
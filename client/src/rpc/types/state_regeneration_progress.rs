@@ -0,0 +1,28 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfx_types::{H256, U64};
+use cfxcore::consensus::consensus_inner::state_regeneration::StateRegenerationProgress as PrimitiveStateRegenerationProgress;
+
+#[derive(Debug, Serialize, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StateRegenerationProgress {
+    pub block_hash: H256,
+    /// The number of epochs to replay in total, which is 0 if the state is
+    /// already available.
+    pub total_epochs: U64,
+    pub replayed_epochs: U64,
+    pub done: bool,
+}
+
+impl StateRegenerationProgress {
+    pub fn new(progress: PrimitiveStateRegenerationProgress) -> Self {
+        Self {
+            block_hash: progress.block_hash,
+            total_epochs: (progress.total_epochs as u64).into(),
+            replayed_epochs: (progress.replayed_epochs as u64).into(),
+            done: progress.is_done(),
+        }
+    }
+}
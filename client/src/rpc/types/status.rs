@@ -26,4 +26,6 @@ pub struct Status {
     pub latest_confirmed: U64,
     /// The latest executed epoch.
     pub latest_state: U64,
+    /// The maximum number of blocks executed in an epoch.
+    pub epoch_executed_block_bound: U64,
 }
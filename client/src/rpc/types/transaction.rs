@@ -152,6 +152,9 @@ impl Transaction {
 pub struct TxWithPoolInfo {
     pub exist: bool,
     pub packed: bool,
+    /// The latest block template of the local miner including the
+    /// transaction, which is not propagated while it is set.
+    pub provisionally_packed_template: Option<H256>,
     pub local_nonce: U256,
     pub local_balance: U256,
     pub state_nonce: U256,
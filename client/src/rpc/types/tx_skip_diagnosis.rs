@@ -0,0 +1,115 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::rpc::types::RpcAddress;
+use cfx_addr::Network;
+use cfx_types::{H256, U256, U64};
+use cfxcore::statistics::nonce_order::{
+    SenderTxRecord as PrimitiveSenderTxRecord,
+    TxSkipDiagnosis as PrimitiveTxSkipDiagnosis,
+    TxSkipReason as PrimitiveTxSkipReason,
+};
+
+#[derive(Debug, Serialize, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum TxSkipReason {
+    /// The transaction is already executed earlier in the same epoch.
+    Duplicated {
+        executed_block_hash: H256,
+        executed_index: U64,
+    },
+    /// The nonce is lower than the sender nonce.
+    NonceTooOld {
+        expected: U256,
+    },
+    /// The nonce is higher than the sender nonce.
+    NonceTooNew {
+        expected: U256,
+    },
+    /// The sender or the sponsor cannot afford the costs.
+    InsufficientBalance,
+    /// The sender exceeds the gas budget of a sender in an epoch.
+    SenderGasBudgetExceeded,
+    EpochHeightOutOfBound,
+    InvalidRecipient,
+}
+
+impl TxSkipReason {
+    fn new(reason: PrimitiveTxSkipReason) -> Self {
+        match reason {
+            PrimitiveTxSkipReason::Duplicated {
+                executed_block_hash,
+                executed_index,
+            } => TxSkipReason::Duplicated {
+                executed_block_hash,
+                executed_index: (executed_index as u64).into(),
+            },
+            PrimitiveTxSkipReason::NonceTooOld { expected } => {
+                TxSkipReason::NonceTooOld { expected }
+            }
+            PrimitiveTxSkipReason::NonceTooNew { expected } => {
+                TxSkipReason::NonceTooNew { expected }
+            }
+            PrimitiveTxSkipReason::InsufficientBalance => {
+                TxSkipReason::InsufficientBalance
+            }
+            PrimitiveTxSkipReason::SenderGasBudgetExceeded => {
+                TxSkipReason::SenderGasBudgetExceeded
+            }
+            PrimitiveTxSkipReason::EpochHeightOutOfBound => {
+                TxSkipReason::EpochHeightOutOfBound
+            }
+            PrimitiveTxSkipReason::InvalidRecipient => {
+                TxSkipReason::InvalidRecipient
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SenderTxRecord {
+    pub hash: H256,
+    pub nonce: U256,
+    pub block_hash: H256,
+    pub transaction_index: U64,
+    /// `None` if the transaction is executed normally.
+    pub skip_reason: Option<TxSkipReason>,
+}
+
+#[derive(Debug, Serialize, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TxSkipDiagnosis {
+    pub epoch_hash: H256,
+    pub epoch_number: U64,
+    pub sender: RpcAddress,
+    pub reason: TxSkipReason,
+    /// The transactions of the sender in the epoch in the execution order,
+    /// including the skipped one.
+    pub sender_order: Vec<SenderTxRecord>,
+}
+
+impl TxSkipDiagnosis {
+    pub fn new(
+        diagnosis: PrimitiveTxSkipDiagnosis, network: Network,
+    ) -> Result<Self, String> {
+        Ok(Self {
+            epoch_hash: diagnosis.epoch_hash,
+            epoch_number: diagnosis.epoch_height.into(),
+            sender: RpcAddress::try_from_h160(diagnosis.sender, network)?,
+            reason: TxSkipReason::new(diagnosis.reason),
+            sender_order: diagnosis
+                .sender_order
+                .into_iter()
+                .map(|tx: PrimitiveSenderTxRecord| SenderTxRecord {
+                    hash: tx.hash,
+                    nonce: tx.nonce,
+                    block_hash: tx.block_hash,
+                    transaction_index: (tx.index as u64).into(),
+                    skip_reason: tx.skip_reason.map(TxSkipReason::new),
+                })
+                .collect(),
+        })
+    }
+}
@@ -10,7 +10,7 @@ use crate::{
     configuration::Configuration, rpc::RpcBlock,
 };
 use cfx_types::H256;
-use cfxcore::ConsensusGraphTrait;
+use cfxcore::{block_data_manager::BlockOrigin, ConsensusGraphTrait};
 use parking_lot::{Condvar, Mutex};
 use primitives::Block;
 use serde_json::Value;
@@ -93,7 +93,7 @@ fn test_load_chain() {
         handle
             .other_components
             .sync
-            .on_mined_block(primitive_block)
+            .on_mined_block(primitive_block, BlockOrigin::Local)
             .unwrap();
     }
 
@@ -24,6 +24,9 @@ pub struct ComputeEpochDebugRecord {
     pub parent_state_root: StateRootWithAuxInfo,
     pub reward_epoch_hash: Option<H256>,
     pub anticone_penalty_cutoff_epoch_hash: Option<H256>,
+    /// The execution that produces this record, e.g. `debug_recompute#42`.
+    #[serde(default)]
+    pub pipeline_id: String,
 
     // Blocks.
     pub block_hashes: Vec<H256>,
@@ -51,6 +54,28 @@ pub struct ComputeEpochDebugRecord {
     pub state_ops: Vec<StateOp>,
 }
 
+/// A block whose weight maintained in a link-cut tree of the consensus graph
+/// is different from the one recomputed from scratch.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WeightDivergence {
+    pub block_hash: H256,
+    pub height: u64,
+    pub maintained: i128,
+    pub recomputed: i128,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WeightAuditDebugRecord {
+    /// The last inserted block before the audit.
+    pub block_hash: H256,
+    pub sequence_number: u64,
+    pub era_genesis_hash: H256,
+    /// Divergences of the subtree weights in `weight_tree`.
+    pub subtree_weight_divergences: Vec<WeightDivergence>,
+    /// Divergences of the values in `adaptive_tree`.
+    pub adaptive_weight_divergences: Vec<WeightDivergence>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub enum StateOp {
     IncentiveLevelOp {
@@ -76,6 +101,7 @@ impl Default for ComputeEpochDebugRecord {
             ),
             reward_epoch_hash: None,
             anticone_penalty_cutoff_epoch_hash: None,
+            pipeline_id: Default::default(),
             block_hashes: Default::default(),
             block_txs: Default::default(),
             transactions: Default::default(),
@@ -8,6 +8,9 @@ extern crate lazy_static;
 pub mod internal_contract_addresses;
 
 pub mod consensus {
+    /// The default deferred execution depth. The depth of a chain is set by
+    /// `CommonParams::deferred_state_epoch_count`, which is not larger than
+    /// this value.
     pub const DEFERRED_STATE_EPOCH_COUNT: u64 = 5;
     pub const EPOCH_SET_PERSISTENCE_DELAY: u64 = DEFERRED_STATE_EPOCH_COUNT;
 
@@ -52,11 +55,19 @@ pub mod consensus_internal {
     /// `ANTICONE_PENALTY_UPPER_EPOCH_COUNT`. If we cannot cache receipts of
     /// recent `REWARD_EPOCH_COUNT` epochs, the receipts will be loaded from
     /// db, which may lead to performance downgrade
+    ///
+    /// These are the default values of the reward window in the chain spec.
+    /// `REWARD_EPOCH_COUNT` is also the maximum reward window, which the data
+    /// kept for reward computation is sized with.
     pub const REWARD_EPOCH_COUNT: u64 = 12;
     pub const ANTICONE_PENALTY_UPPER_EPOCH_COUNT: u64 = 10;
     pub const ANTICONE_PENALTY_RATIO: u64 = 100;
-    /// The maximum number of blocks to be executed in each epoch
+    /// The maximum number of blocks to be executed in each epoch, which is
+    /// the default of `CommonParams::epoch_executed_block_bound`.
     pub const EPOCH_EXECUTED_BLOCK_BOUND: usize = 200;
+    /// The maximum number of epochs replayed to compute a missing state
+    /// without an explicit budget.
+    pub const MAX_IMPLICIT_STATE_REPLAY_EPOCHS: usize = 100;
     // The initial base mining reward in uCFX.
     pub const INITIAL_BASE_MINING_REWARD_IN_UCFX: u64 = 7_000_000;
     // The average number of blocks mined per quarter.
@@ -97,6 +108,24 @@ pub mod consensus_internal {
     /// to update the meter every 20 blocks. Note that confirmation meter
     /// update is CPU intensive if the tree graph is in a unstable state.
     pub const CONFIRMATION_METER_UPDATE_FREQUENCY: usize = 20;
+
+    /// The maximum number of block weights added up in one cumulative weight,
+    /// e.g. the past weight of a pivot block or a subtree weight. It is the
+    /// number of blocks generated in 10000 years at the target block
+    /// generation rate.
+    pub const MAX_WEIGHT_SUMMED_BLOCK_COUNT: u64 =
+        super::staking::BLOCKS_PER_YEAR * 10000;
+    /// The adaptive weight tree stores `2 * SubTW(x) - SubTW(P(x)) + W(P(x))`,
+    /// so a cumulative weight must stay representable after being multiplied
+    /// by this factor.
+    pub const CUMULATIVE_WEIGHT_HEADROOM: u64 = 4;
+    /// The maximum weight of a single block. Larger weights are saturated, so
+    /// that a cumulative weight never exceeds `MAX_CUMULATIVE_WEIGHT`.
+    pub const MAX_BLOCK_WEIGHT: i128 = i128::MAX
+        / (MAX_WEIGHT_SUMMED_BLOCK_COUNT as i128
+            * CUMULATIVE_WEIGHT_HEADROOM as i128);
+    pub const MAX_CUMULATIVE_WEIGHT: i128 =
+        MAX_BLOCK_WEIGHT * MAX_WEIGHT_SUMMED_BLOCK_COUNT as i128;
 }
 
 pub mod rpc {
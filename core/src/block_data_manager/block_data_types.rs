@@ -1,13 +1,15 @@
 use crate::trace::trace::BlockExecTraces;
-use cfx_internal_common::{DatabaseDecodable, DatabaseEncodable};
-use cfx_types::{Bloom, H256, U256};
+use cfx_internal_common::{
+    DatabaseDecodable, DatabaseEncodable, StateRootWithAuxInfo,
+};
+use cfx_types::{Address, Bloom, H256, U256};
 use malloc_size_of::{MallocSizeOf, MallocSizeOfOps};
 use malloc_size_of_derive::MallocSizeOf as DeriveMallocSizeOf;
-use primitives::BlockReceipts;
+use primitives::{BlockReceipts, Receipt};
 use rlp::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
 use rlp_derive::{RlpDecodable, RlpEncodable};
 use smart_default::SmartDefault;
-use std::sync::Arc;
+use std::{collections::BTreeMap, sync::Arc};
 
 /// The start block number of an epoch. It equals to the past executed number of
 /// blocks in the previous epoch + 1. For the true genesis, it equals 0.
@@ -69,6 +71,192 @@ impl Default for BlockRewardResult {
 
 pub type BlockRewardsInfo = BlockDataWithMultiVersion<H256, BlockRewardResult>;
 
+/// How the base reward in the `BlockRewardResult` of a block on the local
+/// pivot chain is computed, so the miner can tell why it is lower than the
+/// base reward per block.
+#[derive(RlpEncodable, RlpDecodable, Clone, Debug, Default)]
+pub struct BlockRewardDetails {
+    /// The epoch in which the reward is computed, i.e. the version of the
+    /// `BlockRewardResult`.
+    pub epoch_hash: H256,
+    pub epoch_difficulty: U256,
+    /// The base reward of a block of the reward epoch before the penalty.
+    pub base_reward_per_block: U256,
+    /// The difficulty of the blocks in the anticone counted in the penalty.
+    /// It is zero if the block is invalid for rewards.
+    pub anticone_difficulty: U256,
+    /// The anticone difficulty reaches the limit, so the block gets no base
+    /// reward.
+    pub anticone_overlimited: bool,
+    /// The block gets no base reward because it is partially invalid, it
+    /// votes for an invalid state, or its anticone is overlimited.
+    pub no_reward: bool,
+    /// The pow quality of the block is lower than the epoch difficulty, so
+    /// it gets no base reward.
+    pub insufficient_pow_quality: bool,
+    pub anticone_penalty: U256,
+}
+
+/// Token issuance of an executed epoch on the local pivot chain. The rewards
+/// given out in an epoch belong to an earlier epoch (`REWARD_EPOCH_COUNT`
+/// before it). `total_issued` is the cumulative issuance after the rewards are
+/// given out, i.e. `total_issued_tokens` of the state of this epoch.
+#[derive(
+    RlpEncodable, RlpDecodable, Clone, Copy, Debug, Default, DeriveMallocSizeOf,
+)]
+pub struct EpochIssuance {
+    /// Base rewards (after anticone penalty) plus secondary rewards.
+    pub minted: U256,
+    /// Transaction fees lost because they are only packed by partially
+    /// invalid blocks.
+    pub burnt: U256,
+    pub total_issued: U256,
+}
+
+/// The receipt of a system transaction injected by the executor.
+#[derive(RlpEncodable, RlpDecodable, Clone, Debug)]
+pub struct SystemTransactionReceipt {
+    pub transaction_hash: H256,
+    pub receipt: Receipt,
+    /// Empty if the transaction is executed successfully.
+    pub error_message: String,
+}
+
+/// The receipts of the system transactions executed in an epoch, keyed by the
+/// pivot block hash. They are stored apart from the block receipts, and are
+/// not included in the receipts root or the logs bloom of the epoch.
+#[derive(RlpEncodable, RlpDecodable, Clone, Debug, Default)]
+pub struct EpochSystemTransactionReceipts {
+    pub before_user_transactions: Vec<SystemTransactionReceipt>,
+    pub after_user_transactions: Vec<SystemTransactionReceipt>,
+}
+
+impl EpochSystemTransactionReceipts {
+    pub fn is_empty(&self) -> bool {
+        self.before_user_transactions.is_empty()
+            && self.after_user_transactions.is_empty()
+    }
+}
+
+/// The cursor to resume the execution of an epoch in the middle, keyed by
+/// the pivot block hash. The state after executing the first
+/// `executed_block_count` blocks of the epoch is committed as the epoch
+/// `checkpoint_epoch_id` in the storage, and the receipts of these blocks are
+/// stored as usual.
+#[derive(RlpEncodable, RlpDecodable, Clone, Debug)]
+pub struct EpochExecutionCheckpoint {
+    pub block_count: u64,
+    pub executed_block_count: u64,
+    pub checkpoint_epoch_id: H256,
+    pub state_root_with_aux_info: StateRootWithAuxInfo,
+    /// The receipts of the system transactions executed before the user
+    /// transactions of the first block.
+    pub before_user_transactions: Vec<SystemTransactionReceipt>,
+    /// The ids of the states committed by the earlier checkpoints of the
+    /// epoch, which are removed with this one after the epoch is committed.
+    pub earlier_checkpoint_epoch_ids: Vec<H256>,
+}
+
+impl EpochExecutionCheckpoint {
+    pub fn all_checkpoint_epoch_ids(&self) -> Vec<H256> {
+        let mut epoch_ids = self.earlier_checkpoint_epoch_ids.clone();
+        epoch_ids.push(self.checkpoint_epoch_id);
+        epoch_ids
+    }
+}
+
+/// The nonce allocation state of a local account, persisted so the nonces
+/// allocated before a restart are not reused while their transactions may
+/// still be propagating.
+#[derive(RlpEncodable, RlpDecodable, Clone, Debug, Default, PartialEq, Eq)]
+pub struct LocalNonceRecord {
+    pub next_nonce: U256,
+    /// The unix timestamp in seconds of the last allocation.
+    pub last_allocation_time: u64,
+}
+
+/// The position of a log in a block.
+#[derive(
+    RlpEncodable,
+    RlpDecodable,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+)]
+pub struct LogPosition {
+    pub transaction_index: u32,
+    /// The index of the log in the logs of the transaction.
+    pub transaction_log_index: u32,
+}
+
+#[derive(RlpEncodable, RlpDecodable, Clone, Debug)]
+pub struct AddressLogPositions {
+    pub address: Address,
+    /// In the ascending order.
+    pub positions: Vec<LogPosition>,
+}
+
+/// The positions of the logs emitted by each address in a block, so a log
+/// filter with addresses can skip the blocks matching the bloom only by
+/// false positives, and locate the matching logs without scanning the
+/// receipts. It is built from the receipts of the block executed in the epoch
+/// `epoch_hash`.
+#[derive(RlpEncodable, RlpDecodable, Clone, Debug)]
+pub struct BlockLogIndex {
+    pub epoch_hash: H256,
+    /// In the ascending order of the addresses.
+    pub entries: Vec<AddressLogPositions>,
+}
+
+impl BlockLogIndex {
+    pub fn new(epoch_hash: H256, receipts: &[Receipt]) -> Self {
+        let mut positions_by_address: BTreeMap<Address, Vec<LogPosition>> =
+            BTreeMap::new();
+        for (transaction_index, receipt) in receipts.iter().enumerate() {
+            for (transaction_log_index, log) in receipt.logs.iter().enumerate()
+            {
+                positions_by_address.entry(log.address).or_default().push(
+                    LogPosition {
+                        transaction_index: transaction_index as u32,
+                        transaction_log_index: transaction_log_index as u32,
+                    },
+                );
+            }
+        }
+        Self {
+            epoch_hash,
+            entries: positions_by_address
+                .into_iter()
+                .map(|(address, positions)| AddressLogPositions {
+                    address,
+                    positions,
+                })
+                .collect(),
+        }
+    }
+
+    /// Return the positions of the logs emitted by any of `addresses`, in
+    /// the ascending order.
+    pub fn positions_of_any(&self, addresses: &[Address]) -> Vec<LogPosition> {
+        let mut positions = Vec::new();
+        for address in addresses {
+            if let Ok(i) = self
+                .entries
+                .binary_search_by(|entry| entry.address.cmp(address))
+            {
+                positions.extend_from_slice(&self.entries[i].positions);
+            }
+        }
+        positions.sort();
+        positions.dedup();
+        positions
+    }
+}
+
 #[derive(Clone, Debug, DeriveMallocSizeOf)]
 pub struct DataVersionTuple<Version, T>(pub Version, pub T);
 
@@ -232,6 +420,8 @@ pub struct LocalBlockInfo {
     status: BlockStatus,
     enter_consensus_seq_num: u64,
     pub instance_id: u64,
+    /// Only set for the `PartialInvalid` blocks.
+    partial_invalid_cause: Option<PartialInvalidCause>,
 }
 
 impl LocalBlockInfo {
@@ -240,11 +430,30 @@ impl LocalBlockInfo {
             status,
             enter_consensus_seq_num: seq_num,
             instance_id,
+            partial_invalid_cause: None,
+        }
+    }
+
+    /// Set the cause of a `PartialInvalid` block. It is ignored for the
+    /// blocks with other statuses.
+    pub fn with_partial_invalid_cause(
+        mut self, cause: Option<PartialInvalidCause>,
+    ) -> Self {
+        if self.status == BlockStatus::PartialInvalid {
+            self.partial_invalid_cause =
+                Some(cause.unwrap_or(PartialInvalidCause::Unknown));
         }
+        self
     }
 
     pub fn get_status(&self) -> BlockStatus { self.status }
 
+    /// `Some(PartialInvalidCause::Unknown)` for the `PartialInvalid` blocks
+    /// persisted before the causes are recorded.
+    pub fn get_partial_invalid_cause(&self) -> Option<PartialInvalidCause> {
+        self.partial_invalid_cause
+    }
+
     pub fn get_seq_num(&self) -> u64 { self.enter_consensus_seq_num }
 
     pub fn get_instance_id(&self) -> u64 { self.instance_id }
@@ -253,25 +462,66 @@ impl LocalBlockInfo {
 impl Encodable for LocalBlockInfo {
     fn rlp_append(&self, stream: &mut RlpStream) {
         let status = self.status.to_db_status();
+        // The cause is appended only when it is set, so the other records
+        // keep the old format.
+        let item_count = if self.partial_invalid_cause.is_some() {
+            4
+        } else {
+            3
+        };
         stream
-            .begin_list(3)
+            .begin_list(item_count)
             .append(&status)
             .append(&self.enter_consensus_seq_num)
             .append(&self.instance_id);
+        if let Some(cause) = &self.partial_invalid_cause {
+            stream.append(&cause.to_db_cause());
+        }
     }
 }
 
-impl Decodable for LocalBlockInfo {
-    fn decode(rlp: &Rlp) -> Result<LocalBlockInfo, DecoderError> {
-        let status: u8 = rlp.val_at(0)?;
+impl LocalBlockInfo {
+    /// The first payload format version with the partial invalid cause.
+    pub const PARTIAL_INVALID_CAUSE_VERSION: u32 = 2;
+
+    /// Decode a payload of the format `version`. The records before
+    /// `PARTIAL_INVALID_CAUSE_VERSION` do not have the cause, so the cause of
+    /// a `PartialInvalid` block in them is `Unknown`.
+    pub fn db_decode_with_version(
+        version: u32, bytes: &[u8],
+    ) -> Result<Self, DecoderError> {
+        Self::decode_with_version(version, &Rlp::new(bytes))
+    }
+
+    fn decode_with_version(
+        version: u32, rlp: &Rlp,
+    ) -> Result<Self, DecoderError> {
+        let status = BlockStatus::from_db_status(rlp.val_at(0)?)?;
+        let partial_invalid_cause = if version
+            >= Self::PARTIAL_INVALID_CAUSE_VERSION
+            && rlp.item_count()? > 3
+        {
+            Some(PartialInvalidCause::from_db_cause(rlp.val_at(3)?))
+        } else if status == BlockStatus::PartialInvalid {
+            Some(PartialInvalidCause::Unknown)
+        } else {
+            None
+        };
         Ok(LocalBlockInfo {
-            status: BlockStatus::from_db_status(status),
+            status,
             enter_consensus_seq_num: rlp.val_at(1)?,
             instance_id: rlp.val_at(2)?,
+            partial_invalid_cause,
         })
     }
 }
 
+impl Decodable for LocalBlockInfo {
+    fn decode(rlp: &Rlp) -> Result<LocalBlockInfo, DecoderError> {
+        Self::decode_with_version(Self::PARTIAL_INVALID_CAUSE_VERSION, rlp)
+    }
+}
+
 /// The validity status of a block. If a block's status among all honest nodes
 /// is guaranteed to have no conflict, which means if some honest nodes think a
 /// block is not `Pending`, their decision will be the same status.
@@ -281,22 +531,52 @@ pub enum BlockStatus {
     Invalid = 1,
     PartialInvalid = 2,
     Pending = 3,
+    /// The block was removed from the sync graph because it was not ready
+    /// for too long. This status is local and the block never entered
+    /// consensus, so it can be inserted again if it arrives later.
+    Expired = 4,
 }
 
 impl BlockStatus {
-    fn from_db_status(db_status: u8) -> Self {
+    fn from_db_status(db_status: u8) -> Result<Self, DecoderError> {
         match db_status {
-            0 => BlockStatus::Valid,
-            1 => BlockStatus::Invalid,
-            2 => BlockStatus::PartialInvalid,
-            3 => BlockStatus::Pending,
-            _ => panic!("Read unknown block status from db"),
+            0 => Ok(BlockStatus::Valid),
+            1 => Ok(BlockStatus::Invalid),
+            2 => Ok(BlockStatus::PartialInvalid),
+            3 => Ok(BlockStatus::Pending),
+            4 => Ok(BlockStatus::Expired),
+            _ => Err(DecoderError::Custom("Unknown block status")),
         }
     }
 
     pub fn to_db_status(&self) -> u8 { *self as u8 }
 }
 
+/// The reason why a block is `PartialInvalid`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, DeriveMallocSizeOf)]
+pub enum PartialInvalidCause {
+    /// The cause is not recorded, e.g. in the records of older versions.
+    Unknown = 0,
+    /// The parent is not the heaviest choice in the past view of the block,
+    /// or it is not in the subtree of the force confirmed block.
+    IncorrectParent = 1,
+    WrongDifficulty = 2,
+    WrongAdaptive = 3,
+}
+
+impl PartialInvalidCause {
+    fn from_db_cause(db_cause: u8) -> Self {
+        match db_cause {
+            1 => PartialInvalidCause::IncorrectParent,
+            2 => PartialInvalidCause::WrongDifficulty,
+            3 => PartialInvalidCause::WrongAdaptive,
+            _ => PartialInvalidCause::Unknown,
+        }
+    }
+
+    pub fn to_db_cause(&self) -> u8 { *self as u8 }
+}
+
 /// The checkpoint information stored in the database
 #[derive(RlpEncodable, RlpDecodable, Clone)]
 pub struct CheckpointHashes {
@@ -313,6 +593,161 @@ impl CheckpointHashes {
     }
 }
 
+/// An epoch that is executed and confirmed with a risk lower than the
+/// configured threshold, queued for external consumers. `seq` increases by one
+/// for each event, so consumers can acknowledge a prefix of the queue.
+#[derive(Clone, Debug, RlpEncodable, RlpDecodable)]
+pub struct FinalizedEpochEvent {
+    pub seq: u64,
+    pub epoch_number: u64,
+    pub epoch_hash: H256,
+    pub block_hashes: Vec<H256>,
+}
+
+/// A switch of the pivot chain to a different branch, i.e. not an extension
+/// of the pivot chain. `seq` increases by one for each event.
+#[derive(Clone, Debug, RlpEncodable, RlpDecodable)]
+pub struct PivotSwitchEvent {
+    pub seq: u64,
+    /// The first height where the old and the new pivot chains differ.
+    pub fork_height: u64,
+    pub old_tip: H256,
+    pub new_tip: H256,
+    /// The block whose insertion caused the switch.
+    pub trigger_block: H256,
+    /// The unix timestamp in seconds when the switch is recorded.
+    pub timestamp: u64,
+}
+
+/// The change of the terminal set between two persisted versions. The terminal
+/// set in the database is the full list under the legacy key with all the
+/// deltas since the last compaction applied in order.
+#[derive(Clone, Debug, Default, RlpEncodable, RlpDecodable)]
+pub struct TerminalsDelta {
+    pub added: Vec<H256>,
+    pub removed: Vec<H256>,
+}
+
+/// The epoch sets of a pivot block evicted from the consensus graph memory.
+/// The blocks are recorded by hashes because their arena indices are not
+/// kept after they are removed from the consensus graph.
+#[derive(Clone, Debug, Default, RlpEncodable, RlpDecodable)]
+pub struct EvictedEpochSets {
+    pub blockset_in_own_view_of_epoch: Vec<H256>,
+    pub ordered_executable_epoch_blocks: Vec<H256>,
+}
+
+/// The transactions of a sender executed in an epoch on the local pivot chain,
+/// in the order of execution. The entry is stale if `pivot_hash` is no longer
+/// the pivot block of the epoch.
+#[derive(Clone, Debug, RlpEncodable, RlpDecodable)]
+pub struct SenderEpochTransactions {
+    pub epoch_number: u64,
+    pub pivot_hash: H256,
+    pub transaction_hashes: Vec<H256>,
+}
+
+/// The epochs in which a sender has indexed transactions among a bucket of
+/// consecutive epochs, as a bitmap of the epoch offsets in the bucket.
+#[derive(Clone, Debug, Default, PartialEq, RlpEncodable, RlpDecodable)]
+pub struct SenderTransactionEpochs {
+    pub bitmap: Vec<u8>,
+}
+
+impl SenderTransactionEpochs {
+    pub fn contains(&self, offset: u64) -> bool {
+        self.bitmap
+            .get((offset / 8) as usize)
+            .map_or(false, |byte| byte & (1 << (offset % 8)) != 0)
+    }
+
+    pub fn insert(&mut self, offset: u64) {
+        let index = (offset / 8) as usize;
+        if self.bitmap.len() <= index {
+            self.bitmap.resize(index + 1, 0);
+        }
+        self.bitmap[index] |= 1 << (offset % 8);
+    }
+
+    pub fn remove(&mut self, offset: u64) {
+        if let Some(byte) = self.bitmap.get_mut((offset / 8) as usize) {
+            *byte &= !(1 << (offset % 8));
+        }
+        while self.bitmap.last() == Some(&0) {
+            self.bitmap.pop();
+        }
+    }
+
+    pub fn is_empty(&self) -> bool { self.bitmap.is_empty() }
+
+    /// The offsets in the ascending order.
+    pub fn offsets<'a>(&'a self) -> impl Iterator<Item = u64> + 'a {
+        (0..self.bitmap.len() as u64 * 8)
+            .filter(move |offset| self.contains(*offset))
+    }
+}
+
+/// The senders whose transactions executed in an epoch are indexed, so the
+/// index of the epoch can be removed without scanning the blocks.
+#[derive(Clone, Debug, Default, RlpEncodable, RlpDecodable)]
+pub struct EpochTransactionSenders {
+    pub senders: Vec<Address>,
+}
+
+/// The persisted position of the finalized epoch outbox.
+/// Events in `[first_unacked_seq, next_seq)` are kept in the database.
+#[derive(Clone, Copy, Debug, Default, RlpEncodable, RlpDecodable)]
+pub struct FinalizedEpochOutboxProgress {
+    pub next_epoch_number: u64,
+    pub next_seq: u64,
+    pub first_unacked_seq: u64,
+    pub last_epoch_hash: H256,
+}
+
+/// Where a block is first seen by this node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockOrigin {
+    /// Generated locally, e.g. through the test RPCs.
+    Local = 0,
+    /// Mined by the mining worker or a connected miner.
+    Miner = 1,
+    /// Received from a peer.
+    Peer = 2,
+}
+
+impl Encodable for BlockOrigin {
+    fn rlp_append(&self, s: &mut RlpStream) { s.append(&(*self as u8)); }
+}
+
+impl Decodable for BlockOrigin {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        match rlp.as_val::<u8>()? {
+            0 => Ok(BlockOrigin::Local),
+            1 => Ok(BlockOrigin::Miner),
+            2 => Ok(BlockOrigin::Peer),
+            _ => Err(DecoderError::Custom("Unknown block origin")),
+        }
+    }
+}
+
+/// The propagation metadata of a block received in this run.
+/// Timestamps are in milliseconds since the unix epoch.
+#[derive(Clone, Debug, RlpEncodable, RlpDecodable)]
+pub struct BlockPropagationRecord {
+    pub block_hash: H256,
+    pub origin: BlockOrigin,
+    pub first_seen_ms: u64,
+    pub graph_ready_ms: u64,
+}
+
+impl BlockPropagationRecord {
+    /// The time from the block header is first seen to the block becomes
+    /// graph-ready and is sent to consensus.
+    pub fn time_to_graph_ready_ms(&self) -> u64 {
+        self.graph_ready_ms.saturating_sub(self.first_seen_ms)
+    }
+}
+
 /// Verified roots of blamed headers stored on disk on light nodes.
 #[derive(Clone, Debug, RlpEncodable, RlpDecodable)]
 pub struct BlamedHeaderVerifiedRoots {
@@ -325,6 +760,55 @@ impl MallocSizeOf for BlamedHeaderVerifiedRoots {
     fn size_of(&self, _ops: &mut MallocSizeOfOps) -> usize { 0 }
 }
 
+/// The first byte of a record in a versioned envelope. A record written before
+/// the envelope is introduced is a single RLP item, which never starts with
+/// `0x00` unless it is one byte long.
+const VERSIONED_RECORD_PREFIX: u8 = 0x00;
+
+/// The type and the payload format version of a persisted record. The
+/// `version` is the latest format, which is used to write new records.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RecordSchema {
+    /// The short id of the record type stored in the envelope.
+    pub id: u8,
+    pub version: u32,
+}
+
+/// Wrap `payload` into an envelope of `0x00 ++ rlp([id, version, payload])`.
+pub fn db_encode_versioned(schema: &RecordSchema, payload: &[u8]) -> Vec<u8> {
+    let mut rlp_stream = RlpStream::new_list(3);
+    rlp_stream
+        .append(&schema.id)
+        .append(&schema.version)
+        .append(&payload.to_vec());
+    let mut encoded = vec![VERSIONED_RECORD_PREFIX];
+    encoded.extend_from_slice(&rlp_stream.drain());
+    encoded
+}
+
+/// Unwrap a record of `schema` and return its payload format version and its
+/// payload. A record without an envelope is in the format before versioning,
+/// so it is returned as version 0 with the whole record as the payload.
+pub fn db_decode_versioned<'a>(
+    schema: &RecordSchema, bytes: &'a [u8],
+) -> Result<(u32, &'a [u8]), DecoderError> {
+    if bytes.len() <= 1 || bytes[0] != VERSIONED_RECORD_PREFIX {
+        return Ok((0, bytes));
+    }
+    let rlp = Rlp::new(&bytes[1..]);
+    if rlp.item_count()? != 3 {
+        return Err(DecoderError::RlpIncorrectListLen);
+    }
+    if rlp.val_at::<u8>(0)? != schema.id {
+        return Err(DecoderError::Custom("Unexpected record id"));
+    }
+    let version: u32 = rlp.val_at(1)?;
+    if version > schema.version {
+        return Err(DecoderError::Custom("Unsupported record version"));
+    }
+    Ok((version, rlp.at(2)?.data()?))
+}
+
 pub fn db_encode_list<T>(list: &[T]) -> Vec<u8>
 where T: DatabaseEncodable {
     let mut rlp_stream = RlpStream::new();
@@ -350,4 +834,173 @@ impl_db_encoding_as_rlp!(LocalBlockInfo);
 impl_db_encoding_as_rlp!(CheckpointHashes);
 impl_db_encoding_as_rlp!(EpochExecutionContext);
 impl_db_encoding_as_rlp!(BlockRewardResult);
+impl_db_encoding_as_rlp!(BlockRewardDetails);
+impl_db_encoding_as_rlp!(EpochIssuance);
+impl_db_encoding_as_rlp!(EpochSystemTransactionReceipts);
+impl_db_encoding_as_rlp!(BlockLogIndex);
+impl_db_encoding_as_rlp!(LocalNonceRecord);
+impl_db_encoding_as_rlp!(EpochExecutionCheckpoint);
+impl_db_encoding_as_rlp!(FinalizedEpochEvent);
+impl_db_encoding_as_rlp!(PivotSwitchEvent);
+impl_db_encoding_as_rlp!(FinalizedEpochOutboxProgress);
+impl_db_encoding_as_rlp!(BlockPropagationRecord);
 impl_db_encoding_as_rlp!(BlamedHeaderVerifiedRoots);
+impl_db_encoding_as_rlp!(TerminalsDelta);
+impl_db_encoding_as_rlp!(EvictedEpochSets);
+impl_db_encoding_as_rlp!(SenderEpochTransactions);
+impl_db_encoding_as_rlp!(SenderTransactionEpochs);
+impl_db_encoding_as_rlp!(EpochTransactionSenders);
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        db_decode_list, db_decode_versioned, db_encode_list,
+        db_encode_versioned, BlockStatus, LocalBlockInfo, PartialInvalidCause,
+        RecordSchema, SenderEpochTransactions, SenderTransactionEpochs,
+    };
+    use cfx_internal_common::{DatabaseDecodable, DatabaseEncodable};
+    use cfx_types::H256;
+    use rlp::RlpStream;
+
+    const SCHEMA: RecordSchema = RecordSchema {
+        id: 1,
+        version: LocalBlockInfo::PARTIAL_INVALID_CAUSE_VERSION,
+    };
+
+    fn decode(bytes: &[u8]) -> LocalBlockInfo {
+        let (version, payload) = db_decode_versioned(&SCHEMA, bytes).unwrap();
+        LocalBlockInfo::db_decode_with_version(version, payload).unwrap()
+    }
+
+    #[test]
+    fn test_decode_old_local_block_info() {
+        // A record written before the envelope and the cause.
+        let mut stream = RlpStream::new_list(3);
+        stream
+            .append(&BlockStatus::PartialInvalid.to_db_status())
+            .append(&5u64)
+            .append(&7u64);
+        let legacy = stream.out();
+        let info = decode(&legacy);
+        assert!(info.get_status() == BlockStatus::PartialInvalid);
+        assert_eq!(info.get_seq_num(), 5);
+        assert_eq!(info.get_instance_id(), 7);
+        assert_eq!(
+            info.get_partial_invalid_cause(),
+            Some(PartialInvalidCause::Unknown)
+        );
+
+        // A record in the envelope of version 1, before the cause.
+        let v1 = db_encode_versioned(
+            &RecordSchema {
+                id: SCHEMA.id,
+                version: 1,
+            },
+            &legacy,
+        );
+        let info = decode(&v1);
+        assert_eq!(info.get_seq_num(), 5);
+        assert_eq!(
+            info.get_partial_invalid_cause(),
+            Some(PartialInvalidCause::Unknown)
+        );
+
+        // A valid block never has a cause.
+        let mut stream = RlpStream::new_list(3);
+        stream
+            .append(&BlockStatus::Valid.to_db_status())
+            .append(&5u64)
+            .append(&7u64);
+        assert_eq!(decode(&stream.out()).get_partial_invalid_cause(), None);
+
+        // A status unknown to this version is an error, not a panic.
+        let mut stream = RlpStream::new_list(3);
+        stream.append(&5u8).append(&5u64).append(&7u64);
+        let bytes = db_encode_versioned(&SCHEMA, &stream.out());
+        let (version, payload) = db_decode_versioned(&SCHEMA, &bytes).unwrap();
+        assert!(
+            LocalBlockInfo::db_decode_with_version(version, payload).is_err()
+        );
+    }
+
+    #[test]
+    fn test_local_block_info_round_trip() {
+        let info = LocalBlockInfo::new(BlockStatus::PartialInvalid, 5, 7)
+            .with_partial_invalid_cause(Some(
+                PartialInvalidCause::WrongDifficulty,
+            ));
+        let decoded = decode(&db_encode_versioned(&SCHEMA, &info.db_encode()));
+        assert!(decoded.get_status() == BlockStatus::PartialInvalid);
+        assert_eq!(decoded.get_seq_num(), 5);
+        assert_eq!(decoded.get_instance_id(), 7);
+        assert_eq!(
+            decoded.get_partial_invalid_cause(),
+            Some(PartialInvalidCause::WrongDifficulty)
+        );
+    }
+
+    #[test]
+    fn test_decode_legacy_record() {
+        let schema = RecordSchema { id: 20, version: 1 };
+        let entries = vec![SenderEpochTransactions {
+            epoch_number: 3,
+            pivot_hash: H256::from_low_u64_be(3),
+            transaction_hashes: vec![H256::from_low_u64_be(4)],
+        }];
+        let legacy = db_encode_list(&entries);
+
+        // A record written before the envelope is returned as version 0.
+        let (version, payload) = db_decode_versioned(&schema, &legacy).unwrap();
+        assert_eq!(version, 0);
+        let decoded: Vec<SenderEpochTransactions> =
+            db_decode_list(payload).unwrap();
+        assert_eq!(decoded.len(), 1);
+        assert_eq!(decoded[0].epoch_number, 3);
+        assert_eq!(
+            decoded[0].transaction_hashes,
+            entries[0].transaction_hashes
+        );
+
+        let versioned = db_encode_versioned(&schema, &legacy);
+        assert_eq!(
+            db_decode_versioned(&schema, &versioned).unwrap(),
+            (1, &legacy[..])
+        );
+
+        // The records of other types or newer versions are rejected.
+        let other = RecordSchema { id: 19, version: 1 };
+        assert!(db_decode_versioned(&other, &versioned).is_err());
+        let newer = db_encode_versioned(
+            &RecordSchema {
+                id: schema.id,
+                version: 2,
+            },
+            &legacy,
+        );
+        assert!(db_decode_versioned(&schema, &newer).is_err());
+    }
+
+    #[test]
+    fn test_sender_transaction_epochs() {
+        let mut epochs = SenderTransactionEpochs::default();
+        assert!(epochs.is_empty());
+        for offset in &[1023, 3, 9] {
+            epochs.insert(*offset);
+        }
+        assert!(epochs.contains(3));
+        assert!(!epochs.contains(4));
+        assert!(!epochs.contains(2048));
+        assert_eq!(epochs.offsets().collect::<Vec<_>>(), vec![3, 9, 1023]);
+
+        let decoded: SenderTransactionEpochs =
+            DatabaseDecodable::db_decode(&epochs.db_encode()).unwrap();
+        assert_eq!(decoded, epochs);
+
+        // The trailing empty bytes are dropped after a removal.
+        epochs.remove(1023);
+        assert_eq!(epochs.bitmap.len(), 2);
+        epochs.remove(3);
+        epochs.remove(9);
+        assert!(epochs.is_empty());
+    }
+}
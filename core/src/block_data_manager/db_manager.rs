@@ -1,9 +1,15 @@
 use crate::{
     block_data_manager::{
-        db_decode_list, db_encode_list, BlamedHeaderVerifiedRoots,
-        BlockExecutionResultWithEpoch, BlockRewardResult, BlockTracesWithEpoch,
-        CheckpointHashes, DataVersionTuple, EpochExecutionContext,
-        LocalBlockInfo,
+        db_decode_list, db_decode_versioned, db_encode_list,
+        db_encode_versioned, BlamedHeaderVerifiedRoots,
+        BlockExecutionResultWithEpoch, BlockLogIndex, BlockPropagationRecord,
+        BlockRewardDetails, BlockRewardResult, BlockTracesWithEpoch,
+        CheckpointHashes, DataVersionTuple, EpochExecutionCheckpoint,
+        EpochExecutionContext, EpochIssuance, EpochSystemTransactionReceipts,
+        EpochTransactionSenders, EvictedEpochSets, FinalizedEpochEvent,
+        FinalizedEpochOutboxProgress, LocalBlockInfo, LocalNonceRecord,
+        PivotSwitchEvent, RecordSchema, SenderEpochTransactions,
+        SenderTransactionEpochs, TerminalsDelta,
     },
     db::{
         COL_BLAMED_HEADER_VERIFIED_ROOTS, COL_BLOCKS, COL_BLOCK_TRACES,
@@ -19,11 +25,11 @@ use cfx_internal_common::{
 use cfx_storage::{
     storage_db::KeyValueDbTrait, KvdbRocksdb, KvdbSqlite, KvdbSqliteStatements,
 };
-use cfx_types::H256;
+use cfx_types::{Address, H256};
 use db::SystemDB;
 use malloc_size_of::{MallocSizeOf, MallocSizeOfOps};
 use primitives::{Block, BlockHeader, SignedTransaction, TransactionIndex};
-use rlp::Rlp;
+use rlp::{DecoderError, Rlp};
 use std::{collections::HashMap, fs, path::Path, sync::Arc};
 use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
@@ -36,8 +42,104 @@ const EPOCH_CONSENSUS_EXECUTION_INFO_SUFFIX_BYTE: u8 = 5;
 const EPOCH_EXECUTED_BLOCK_SET_SUFFIX_BYTE: u8 = 6;
 const EPOCH_SKIPPED_BLOCK_SET_SUFFIX_BYTE: u8 = 7;
 const BLOCK_REWARD_RESULT_SUFFIX_BYTE: u8 = 8;
+const EPOCH_ISSUANCE_SUFFIX_BYTE: u8 = 9;
+const BLOCK_PROPAGATION_RECORD_SUFFIX_BYTE: u8 = 10;
+const EPOCH_SYSTEM_TRANSACTION_RECEIPTS_SUFFIX_BYTE: u8 = 11;
+const BLOCK_LOG_INDEX_SUFFIX_BYTE: u8 = 12;
+const EPOCH_EXECUTION_CHECKPOINT_SUFFIX_BYTE: u8 = 13;
+const EVICTED_EPOCH_SETS_SUFFIX_BYTE: u8 = 14;
+const BLOCK_REWARD_DETAILS_SUFFIX_BYTE: u8 = 15;
+const PIVOT_HASH_SUFFIX_BYTE: u8 = 16;
+const PIVOT_EPOCH_NUMBER_SUFFIX_BYTE: u8 = 17;
 const BLOCK_TERMINAL_KEY: &[u8] = b"block_terminals";
+const BLOCK_TERMINAL_DELTA_COUNT_KEY: &[u8] = b"block_terminal_delta_count";
+const BLOCK_TERMINAL_DELTA_KEY_PREFIX: &[u8] = b"block_terminal_delta";
 const GC_PROGRESS_KEY: &[u8] = b"gc_progress";
+const FINALIZED_EPOCH_OUTBOX_PROGRESS_KEY: &[u8] = b"finalized_epoch_outbox";
+const FINALIZED_EPOCH_EVENT_KEY_PREFIX: &[u8] = b"finalized_epoch_event";
+const BLOCK_PROPAGATION_NEXT_SEQ_KEY: &[u8] = b"block_propagation_next_seq";
+const BLOCK_PROPAGATION_SLOT_KEY_PREFIX: &[u8] = b"block_propagation_slot";
+const BLOCK_BODY_AVAILABILITY_START_KEY: &[u8] =
+    b"block_body_availability_start_height";
+const BLOCK_BODY_AVAILABILITY_KEY_PREFIX: &[u8] =
+    b"block_body_availability_height";
+const PENDING_INVALID_BLOCKS_KEY: &[u8] = b"pending_invalid_blocks";
+const SPILLED_HEADERS_KEY_PREFIX: &[u8] = b"spilled_headers";
+const LOCAL_NONCE_KEY_PREFIX: &[u8] = b"local_nonce";
+const SENDER_EPOCH_TRANSACTIONS_KEY_PREFIX: &[u8] =
+    b"sender_epoch_transactions";
+const SENDER_TRANSACTION_EPOCHS_KEY_PREFIX: &[u8] =
+    b"sender_transaction_epochs";
+const EPOCH_TRANSACTION_SENDERS_KEY_PREFIX: &[u8] =
+    b"epoch_transaction_senders";
+const PIVOT_SWITCH_NEXT_SEQ_KEY: &[u8] = b"pivot_switch_next_seq";
+const PIVOT_SWITCH_EVENT_KEY_PREFIX: &[u8] = b"pivot_switch_event";
+const TRUE_GENESIS_HEADER_KEY: &[u8] = b"true_genesis_header";
+
+// The schemas of the consensus records written in versioned envelopes. A
+// record in an older format is still decoded after its schema version is
+// bumped, so the decoding of each older version needs to be kept. The ids are
+// persisted, so an id must never be changed or reused.
+const LOCAL_BLOCK_INFO_SCHEMA: RecordSchema = RecordSchema {
+    id: 1,
+    version: LocalBlockInfo::PARTIAL_INVALID_CAUSE_VERSION,
+};
+const CHECKPOINT_SCHEMA: RecordSchema = RecordSchema { id: 2, version: 1 };
+const TERMINALS_SCHEMA: RecordSchema = RecordSchema { id: 3, version: 1 };
+const TERMINALS_DELTA_SCHEMA: RecordSchema = RecordSchema { id: 4, version: 1 };
+const TERMINALS_DELTA_COUNT_SCHEMA: RecordSchema =
+    RecordSchema { id: 5, version: 1 };
+const INSTANCE_ID_SCHEMA: RecordSchema = RecordSchema { id: 6, version: 1 };
+const FINALIZED_EPOCH_OUTBOX_PROGRESS_SCHEMA: RecordSchema =
+    RecordSchema { id: 7, version: 1 };
+const FINALIZED_EPOCH_EVENT_SCHEMA: RecordSchema =
+    RecordSchema { id: 8, version: 1 };
+const BLOCK_PROPAGATION_NEXT_SEQ_SCHEMA: RecordSchema =
+    RecordSchema { id: 9, version: 1 };
+const BLOCK_PROPAGATION_SLOT_SCHEMA: RecordSchema =
+    RecordSchema { id: 10, version: 1 };
+const GC_PROGRESS_SCHEMA: RecordSchema = RecordSchema { id: 11, version: 1 };
+// The ids 12 and 13 were used by the block body availability in buckets of
+// heights, which is now recorded by heights.
+const PENDING_INVALID_BLOCKS_SCHEMA: RecordSchema =
+    RecordSchema { id: 14, version: 1 };
+const SPILLED_HEADERS_SCHEMA: RecordSchema =
+    RecordSchema { id: 15, version: 1 };
+const LOCAL_NONCE_SCHEMA: RecordSchema = RecordSchema { id: 16, version: 1 };
+const PIVOT_SWITCH_NEXT_SEQ_SCHEMA: RecordSchema =
+    RecordSchema { id: 17, version: 1 };
+const PIVOT_SWITCH_EVENT_SCHEMA: RecordSchema =
+    RecordSchema { id: 18, version: 1 };
+const TRUE_GENESIS_HEADER_SCHEMA: RecordSchema =
+    RecordSchema { id: 19, version: 1 };
+// The id 20 was used by the transactions of a sender in a bucket of epochs,
+// which are now indexed by the sender and the epoch.
+const EPOCH_SYSTEM_TRANSACTION_RECEIPTS_SCHEMA: RecordSchema =
+    RecordSchema { id: 21, version: 1 };
+const BLOCK_REWARD_DETAILS_SCHEMA: RecordSchema =
+    RecordSchema { id: 22, version: 1 };
+const EPOCH_ISSUANCE_SCHEMA: RecordSchema = RecordSchema { id: 23, version: 1 };
+const EPOCH_EXECUTION_CHECKPOINT_SCHEMA: RecordSchema =
+    RecordSchema { id: 24, version: 1 };
+const EVICTED_EPOCH_SETS_SCHEMA: RecordSchema =
+    RecordSchema { id: 25, version: 1 };
+const BLOCK_LOG_INDEX_SCHEMA: RecordSchema =
+    RecordSchema { id: 26, version: 1 };
+const PIVOT_HASH_SCHEMA: RecordSchema = RecordSchema { id: 27, version: 1 };
+const PIVOT_EPOCH_NUMBER_SCHEMA: RecordSchema =
+    RecordSchema { id: 28, version: 1 };
+const BLOCK_PROPAGATION_RECORD_SCHEMA: RecordSchema =
+    RecordSchema { id: 29, version: 1 };
+const SENDER_EPOCH_TRANSACTIONS_SCHEMA: RecordSchema =
+    RecordSchema { id: 30, version: 1 };
+const SENDER_TRANSACTION_EPOCHS_SCHEMA: RecordSchema =
+    RecordSchema { id: 31, version: 1 };
+const EPOCH_TRANSACTION_SENDERS_SCHEMA: RecordSchema =
+    RecordSchema { id: 32, version: 1 };
+const BLOCK_BODY_AVAILABILITY_START_SCHEMA: RecordSchema =
+    RecordSchema { id: 33, version: 1 };
+const BLOCK_BODY_AVAILABILITY_SCHEMA: RecordSchema =
+    RecordSchema { id: 34, version: 1 };
 
 #[derive(Clone, Copy, Hash, Ord, PartialOrd, Eq, PartialEq, EnumIter)]
 enum DBTable {
@@ -223,23 +325,32 @@ impl DBManager {
     pub fn insert_local_block_info_to_db(
         &self, block_hash: &H256, value: &LocalBlockInfo,
     ) {
-        self.insert_encodable_val(
+        self.insert_versioned_val(
             DBTable::Blocks,
             &local_block_info_key(block_hash),
+            &LOCAL_BLOCK_INFO_SCHEMA,
             value,
         );
     }
 
     /// Get block info from db.
+    /// A record that cannot be decoded, e.g. with a status written by a newer
+    /// version, is ignored, so the block is treated as not processed yet.
     pub fn local_block_info_from_db(
         &self, block_hash: &H256,
     ) -> Option<LocalBlockInfo> {
-        self.load_decodable_val(
+        self.load_versioned_val_with(
             DBTable::Blocks,
             &local_block_info_key(block_hash),
+            &LOCAL_BLOCK_INFO_SCHEMA,
+            LocalBlockInfo::db_decode_with_version,
         )
     }
 
+    pub fn remove_local_block_info_from_db(&self, block_hash: &H256) {
+        self.remove_from_db(DBTable::Blocks, &local_block_info_key(block_hash));
+    }
+
     pub fn insert_blamed_header_verified_roots_to_db(
         &self, block_height: u64, value: &BlamedHeaderVerifiedRoots,
     ) {
@@ -340,6 +451,31 @@ impl DBManager {
         self.remove_from_db(DBTable::Blocks, &block_reward_result_key(hash))
     }
 
+    pub fn insert_block_reward_details_to_db(
+        &self, hash: &H256, details: &BlockRewardDetails,
+    ) {
+        self.insert_versioned_val(
+            DBTable::Blocks,
+            &block_reward_details_key(hash),
+            &BLOCK_REWARD_DETAILS_SCHEMA,
+            details,
+        )
+    }
+
+    pub fn block_reward_details_from_db(
+        &self, hash: &H256,
+    ) -> Option<BlockRewardDetails> {
+        self.load_versioned_val(
+            DBTable::Blocks,
+            &block_reward_details_key(hash),
+            &BLOCK_REWARD_DETAILS_SCHEMA,
+        )
+    }
+
+    pub fn remove_block_reward_details_from_db(&self, hash: &H256) {
+        self.remove_from_db(DBTable::Blocks, &block_reward_details_key(hash))
+    }
+
     pub fn remove_block_trace_from_db(&self, hash: &H256) {
         self.remove_from_db(DBTable::BlockTraces, hash.as_bytes())
     }
@@ -351,16 +487,38 @@ impl DBManager {
     pub fn insert_checkpoint_hashes_to_db(
         &self, checkpoint_prev: &H256, checkpoint_cur: &H256,
     ) {
-        self.insert_encodable_val(
+        self.insert_versioned_val(
             DBTable::Misc,
             b"checkpoint",
+            &CHECKPOINT_SCHEMA,
             &CheckpointHashes::new(*checkpoint_prev, *checkpoint_cur),
         );
     }
 
+    /// Store the header of the true genesis the database is created with.
+    pub fn insert_true_genesis_header_to_db(&self, header: &BlockHeader) {
+        self.insert_versioned_val(
+            DBTable::Misc,
+            TRUE_GENESIS_HEADER_KEY,
+            &TRUE_GENESIS_HEADER_SCHEMA,
+            header,
+        );
+    }
+
+    pub fn true_genesis_header_from_db(&self) -> Option<BlockHeader> {
+        self.load_versioned_val(
+            DBTable::Misc,
+            TRUE_GENESIS_HEADER_KEY,
+            &TRUE_GENESIS_HEADER_SCHEMA,
+        )
+    }
+
     pub fn checkpoint_hashes_from_db(&self) -> Option<(H256, H256)> {
-        let checkpoints: CheckpointHashes =
-            self.load_decodable_val(DBTable::Misc, b"checkpoint")?;
+        let checkpoints: CheckpointHashes = self.load_versioned_val(
+            DBTable::Misc,
+            b"checkpoint",
+            &CHECKPOINT_SCHEMA,
+        )?;
         Some((checkpoints.prev_hash, checkpoints.cur_hash))
     }
 
@@ -402,16 +560,91 @@ impl DBManager {
         )
     }
 
+    pub fn insert_pivot_hash_to_db(&self, epoch: u64, hash: &H256) {
+        self.insert_versioned_val(
+            DBTable::EpochNumbers,
+            &pivot_hash_key(epoch),
+            &PIVOT_HASH_SCHEMA,
+            hash,
+        );
+    }
+
+    pub fn pivot_hash_from_db(&self, epoch: u64) -> Option<H256> {
+        self.load_versioned_val(
+            DBTable::EpochNumbers,
+            &pivot_hash_key(epoch),
+            &PIVOT_HASH_SCHEMA,
+        )
+    }
+
+    pub fn insert_pivot_epoch_number_to_db(&self, hash: &H256, epoch: u64) {
+        self.insert_versioned_val(
+            DBTable::Blocks,
+            &pivot_epoch_number_key(hash),
+            &PIVOT_EPOCH_NUMBER_SCHEMA,
+            &epoch,
+        );
+    }
+
+    pub fn pivot_epoch_number_from_db(&self, hash: &H256) -> Option<u64> {
+        self.load_versioned_val(
+            DBTable::Blocks,
+            &pivot_epoch_number_key(hash),
+            &PIVOT_EPOCH_NUMBER_SCHEMA,
+        )
+    }
+
     pub fn insert_terminals_to_db(&self, terminals: &Vec<H256>) {
-        self.insert_encodable_list(
+        self.insert_versioned_list(
             DBTable::Misc,
             BLOCK_TERMINAL_KEY,
+            &TERMINALS_SCHEMA,
             terminals,
         );
     }
 
     pub fn terminals_from_db(&self) -> Option<Vec<H256>> {
-        self.load_decodable_list(DBTable::Misc, BLOCK_TERMINAL_KEY)
+        self.load_versioned_list(
+            DBTable::Misc,
+            BLOCK_TERMINAL_KEY,
+            &TERMINALS_SCHEMA,
+        )
+    }
+
+    pub fn insert_terminals_delta_to_db(
+        &self, seq: u64, delta: &TerminalsDelta,
+    ) {
+        self.insert_versioned_val(
+            DBTable::Misc,
+            &terminals_delta_key(seq),
+            &TERMINALS_DELTA_SCHEMA,
+            delta,
+        );
+    }
+
+    pub fn terminals_delta_from_db(&self, seq: u64) -> Option<TerminalsDelta> {
+        self.load_versioned_val(
+            DBTable::Misc,
+            &terminals_delta_key(seq),
+            &TERMINALS_DELTA_SCHEMA,
+        )
+    }
+
+    pub fn insert_terminals_delta_count_to_db(&self, count: u64) {
+        self.insert_versioned_val(
+            DBTable::Misc,
+            BLOCK_TERMINAL_DELTA_COUNT_KEY,
+            &TERMINALS_DELTA_COUNT_SCHEMA,
+            &count,
+        );
+    }
+
+    pub fn terminals_delta_count_from_db(&self) -> Option<u64> {
+        self.load_versioned_val(
+            DBTable::Misc,
+            BLOCK_TERMINAL_DELTA_COUNT_KEY,
+            &TERMINALS_DELTA_COUNT_SCHEMA,
+        )
     }
 
     pub fn insert_epoch_execution_commitment_to_db(
@@ -441,11 +674,16 @@ impl DBManager {
     }
 
     pub fn insert_instance_id_to_db(&self, instance_id: u64) {
-        self.insert_encodable_val(DBTable::Misc, b"instance", &instance_id);
+        self.insert_versioned_val(
+            DBTable::Misc,
+            b"instance",
+            &INSTANCE_ID_SCHEMA,
+            &instance_id,
+        );
     }
 
     pub fn instance_id_from_db(&self) -> Option<u64> {
-        self.load_decodable_val(DBTable::Misc, b"instance")
+        self.load_versioned_val(DBTable::Misc, b"instance", &INSTANCE_ID_SCHEMA)
     }
 
     pub fn insert_execution_context_to_db(
@@ -471,16 +709,500 @@ impl DBManager {
         self.remove_from_db(DBTable::Blocks, &epoch_execution_context_key(hash))
     }
 
+    pub fn insert_epoch_issuance_to_db(
+        &self, hash: &H256, issuance: &EpochIssuance,
+    ) {
+        self.insert_versioned_val(
+            DBTable::Blocks,
+            &epoch_issuance_key(hash),
+            &EPOCH_ISSUANCE_SCHEMA,
+            issuance,
+        )
+    }
+
+    pub fn epoch_issuance_from_db(&self, hash: &H256) -> Option<EpochIssuance> {
+        self.load_versioned_val(
+            DBTable::Blocks,
+            &epoch_issuance_key(hash),
+            &EPOCH_ISSUANCE_SCHEMA,
+        )
+    }
+
+    pub fn remove_epoch_issuance_from_db(&self, hash: &H256) {
+        self.remove_from_db(DBTable::Blocks, &epoch_issuance_key(hash))
+    }
+
+    pub fn insert_epoch_system_transaction_receipts_to_db(
+        &self, hash: &H256, receipts: &EpochSystemTransactionReceipts,
+    ) {
+        self.insert_versioned_val(
+            DBTable::Blocks,
+            &epoch_system_transaction_receipts_key(hash),
+            &EPOCH_SYSTEM_TRANSACTION_RECEIPTS_SCHEMA,
+            receipts,
+        )
+    }
+
+    pub fn epoch_system_transaction_receipts_from_db(
+        &self, hash: &H256,
+    ) -> Option<EpochSystemTransactionReceipts> {
+        // These records are always written in the envelope.
+        self.load_versioned_val_with(
+            DBTable::Blocks,
+            &epoch_system_transaction_receipts_key(hash),
+            &EPOCH_SYSTEM_TRANSACTION_RECEIPTS_SCHEMA,
+            |version, payload| match version {
+                0 => Err(DecoderError::Custom("Missing record envelope")),
+                _ => EpochSystemTransactionReceipts::db_decode(payload),
+            },
+        )
+    }
+
+    pub fn remove_epoch_system_transaction_receipts_from_db(
+        &self, hash: &H256,
+    ) {
+        self.remove_from_db(
+            DBTable::Blocks,
+            &epoch_system_transaction_receipts_key(hash),
+        )
+    }
+
+    pub fn insert_epoch_execution_checkpoint_to_db(
+        &self, hash: &H256, checkpoint: &EpochExecutionCheckpoint,
+    ) {
+        self.insert_versioned_val(
+            DBTable::Blocks,
+            &epoch_execution_checkpoint_key(hash),
+            &EPOCH_EXECUTION_CHECKPOINT_SCHEMA,
+            checkpoint,
+        )
+    }
+
+    pub fn epoch_execution_checkpoint_from_db(
+        &self, hash: &H256,
+    ) -> Option<EpochExecutionCheckpoint> {
+        self.load_versioned_val(
+            DBTable::Blocks,
+            &epoch_execution_checkpoint_key(hash),
+            &EPOCH_EXECUTION_CHECKPOINT_SCHEMA,
+        )
+    }
+
+    pub fn remove_epoch_execution_checkpoint_from_db(&self, hash: &H256) {
+        self.remove_from_db(
+            DBTable::Blocks,
+            &epoch_execution_checkpoint_key(hash),
+        )
+    }
+
+    /// The sets are dropped from the memory after being written, so the
+    /// insertion failure is returned to the caller instead of panicking.
+    pub fn insert_evicted_epoch_sets_to_db(
+        &self, hash: &H256, epoch_sets: &EvictedEpochSets,
+    ) -> Result<(), String> {
+        self.try_insert_to_db(
+            DBTable::Blocks,
+            &evicted_epoch_sets_key(hash),
+            db_encode_versioned(
+                &EVICTED_EPOCH_SETS_SCHEMA,
+                &epoch_sets.db_encode(),
+            ),
+        )
+    }
+
+    pub fn evicted_epoch_sets_from_db(
+        &self, hash: &H256,
+    ) -> Option<EvictedEpochSets> {
+        self.load_versioned_val(
+            DBTable::Blocks,
+            &evicted_epoch_sets_key(hash),
+            &EVICTED_EPOCH_SETS_SCHEMA,
+        )
+    }
+
+    pub fn remove_evicted_epoch_sets_from_db(&self, hash: &H256) {
+        self.remove_from_db(DBTable::Blocks, &evicted_epoch_sets_key(hash))
+    }
+
+    pub fn insert_block_log_index_to_db(
+        &self, hash: &H256, index: &BlockLogIndex,
+    ) {
+        self.insert_versioned_val(
+            DBTable::Blocks,
+            &block_log_index_key(hash),
+            &BLOCK_LOG_INDEX_SCHEMA,
+            index,
+        )
+    }
+
+    pub fn block_log_index_from_db(
+        &self, hash: &H256,
+    ) -> Option<BlockLogIndex> {
+        self.load_versioned_val(
+            DBTable::Blocks,
+            &block_log_index_key(hash),
+            &BLOCK_LOG_INDEX_SCHEMA,
+        )
+    }
+
+    pub fn remove_block_log_index_from_db(&self, hash: &H256) {
+        self.remove_from_db(DBTable::Blocks, &block_log_index_key(hash))
+    }
+
+    pub fn insert_finalized_epoch_outbox_progress_to_db(
+        &self, progress: &FinalizedEpochOutboxProgress,
+    ) {
+        self.insert_versioned_val(
+            DBTable::Misc,
+            FINALIZED_EPOCH_OUTBOX_PROGRESS_KEY,
+            &FINALIZED_EPOCH_OUTBOX_PROGRESS_SCHEMA,
+            progress,
+        );
+    }
+
+    pub fn finalized_epoch_outbox_progress_from_db(
+        &self,
+    ) -> Option<FinalizedEpochOutboxProgress> {
+        self.load_versioned_val(
+            DBTable::Misc,
+            FINALIZED_EPOCH_OUTBOX_PROGRESS_KEY,
+            &FINALIZED_EPOCH_OUTBOX_PROGRESS_SCHEMA,
+        )
+    }
+
+    pub fn insert_finalized_epoch_event_to_db(
+        &self, event: &FinalizedEpochEvent,
+    ) {
+        self.insert_versioned_val(
+            DBTable::Misc,
+            &finalized_epoch_event_key(event.seq),
+            &FINALIZED_EPOCH_EVENT_SCHEMA,
+            event,
+        );
+    }
+
+    pub fn finalized_epoch_event_from_db(
+        &self, seq: u64,
+    ) -> Option<FinalizedEpochEvent> {
+        self.load_versioned_val(
+            DBTable::Misc,
+            &finalized_epoch_event_key(seq),
+            &FINALIZED_EPOCH_EVENT_SCHEMA,
+        )
+    }
+
+    pub fn remove_finalized_epoch_event_from_db(&self, seq: u64) {
+        self.remove_from_db(DBTable::Misc, &finalized_epoch_event_key(seq))
+    }
+
+    pub fn insert_local_nonce_record_to_db(
+        &self, address: &Address, record: &LocalNonceRecord,
+    ) {
+        self.insert_versioned_val(
+            DBTable::Misc,
+            &local_nonce_key(address),
+            &LOCAL_NONCE_SCHEMA,
+            record,
+        );
+    }
+
+    pub fn local_nonce_record_from_db(
+        &self, address: &Address,
+    ) -> Option<LocalNonceRecord> {
+        self.load_versioned_val(
+            DBTable::Misc,
+            &local_nonce_key(address),
+            &LOCAL_NONCE_SCHEMA,
+        )
+    }
+
+    pub fn insert_sender_epoch_transactions_to_db(
+        &self, sender: &Address, entry: &SenderEpochTransactions,
+    ) {
+        self.insert_versioned_val(
+            DBTable::Misc,
+            &sender_epoch_transactions_key(sender, entry.epoch_number),
+            &SENDER_EPOCH_TRANSACTIONS_SCHEMA,
+            entry,
+        );
+    }
+
+    pub fn sender_epoch_transactions_from_db(
+        &self, sender: &Address, epoch_number: u64,
+    ) -> Option<SenderEpochTransactions> {
+        self.load_versioned_val(
+            DBTable::Misc,
+            &sender_epoch_transactions_key(sender, epoch_number),
+            &SENDER_EPOCH_TRANSACTIONS_SCHEMA,
+        )
+    }
+
+    pub fn remove_sender_epoch_transactions_from_db(
+        &self, sender: &Address, epoch_number: u64,
+    ) {
+        self.remove_from_db(
+            DBTable::Misc,
+            &sender_epoch_transactions_key(sender, epoch_number),
+        )
+    }
+
+    pub fn insert_sender_transaction_epochs_to_db(
+        &self, sender: &Address, bucket: u64, epochs: &SenderTransactionEpochs,
+    ) {
+        self.insert_versioned_val(
+            DBTable::Misc,
+            &sender_transaction_epochs_key(sender, bucket),
+            &SENDER_TRANSACTION_EPOCHS_SCHEMA,
+            epochs,
+        );
+    }
+
+    pub fn sender_transaction_epochs_from_db(
+        &self, sender: &Address, bucket: u64,
+    ) -> Option<SenderTransactionEpochs> {
+        self.load_versioned_val(
+            DBTable::Misc,
+            &sender_transaction_epochs_key(sender, bucket),
+            &SENDER_TRANSACTION_EPOCHS_SCHEMA,
+        )
+    }
+
+    pub fn remove_sender_transaction_epochs_from_db(
+        &self, sender: &Address, bucket: u64,
+    ) {
+        self.remove_from_db(
+            DBTable::Misc,
+            &sender_transaction_epochs_key(sender, bucket),
+        )
+    }
+
+    pub fn insert_epoch_transaction_senders_to_db(
+        &self, epoch_number: u64, senders: &EpochTransactionSenders,
+    ) {
+        self.insert_versioned_val(
+            DBTable::Misc,
+            &epoch_transaction_senders_key(epoch_number),
+            &EPOCH_TRANSACTION_SENDERS_SCHEMA,
+            senders,
+        );
+    }
+
+    pub fn epoch_transaction_senders_from_db(
+        &self, epoch_number: u64,
+    ) -> Option<EpochTransactionSenders> {
+        self.load_versioned_val(
+            DBTable::Misc,
+            &epoch_transaction_senders_key(epoch_number),
+            &EPOCH_TRANSACTION_SENDERS_SCHEMA,
+        )
+    }
+
+    pub fn remove_epoch_transaction_senders_from_db(&self, epoch_number: u64) {
+        self.remove_from_db(
+            DBTable::Misc,
+            &epoch_transaction_senders_key(epoch_number),
+        )
+    }
+
+    pub fn insert_pivot_switch_event_to_db(&self, event: &PivotSwitchEvent) {
+        self.insert_versioned_val(
+            DBTable::Misc,
+            &pivot_switch_event_key(event.seq),
+            &PIVOT_SWITCH_EVENT_SCHEMA,
+            event,
+        );
+    }
+
+    pub fn pivot_switch_event_from_db(
+        &self, seq: u64,
+    ) -> Option<PivotSwitchEvent> {
+        self.load_versioned_val(
+            DBTable::Misc,
+            &pivot_switch_event_key(seq),
+            &PIVOT_SWITCH_EVENT_SCHEMA,
+        )
+    }
+
+    pub fn insert_pivot_switch_next_seq_to_db(&self, next_seq: u64) {
+        self.insert_versioned_val(
+            DBTable::Misc,
+            PIVOT_SWITCH_NEXT_SEQ_KEY,
+            &PIVOT_SWITCH_NEXT_SEQ_SCHEMA,
+            &next_seq,
+        );
+    }
+
+    pub fn pivot_switch_next_seq_from_db(&self) -> Option<u64> {
+        self.load_versioned_val(
+            DBTable::Misc,
+            PIVOT_SWITCH_NEXT_SEQ_KEY,
+            &PIVOT_SWITCH_NEXT_SEQ_SCHEMA,
+        )
+    }
+
+    pub fn insert_block_propagation_record_to_db(
+        &self, record: &BlockPropagationRecord,
+    ) {
+        self.insert_versioned_val(
+            DBTable::Blocks,
+            &block_propagation_record_key(&record.block_hash),
+            &BLOCK_PROPAGATION_RECORD_SCHEMA,
+            record,
+        );
+    }
+
+    pub fn block_propagation_record_from_db(
+        &self, hash: &H256,
+    ) -> Option<BlockPropagationRecord> {
+        self.load_versioned_val(
+            DBTable::Blocks,
+            &block_propagation_record_key(hash),
+            &BLOCK_PROPAGATION_RECORD_SCHEMA,
+        )
+    }
+
+    pub fn remove_block_propagation_record_from_db(&self, hash: &H256) {
+        self.remove_from_db(
+            DBTable::Blocks,
+            &block_propagation_record_key(hash),
+        )
+    }
+
+    pub fn insert_block_propagation_next_seq_to_db(&self, next_seq: u64) {
+        self.insert_versioned_val(
+            DBTable::Misc,
+            BLOCK_PROPAGATION_NEXT_SEQ_KEY,
+            &BLOCK_PROPAGATION_NEXT_SEQ_SCHEMA,
+            &next_seq,
+        );
+    }
+
+    pub fn block_propagation_next_seq_from_db(&self) -> Option<u64> {
+        self.load_versioned_val(
+            DBTable::Misc,
+            BLOCK_PROPAGATION_NEXT_SEQ_KEY,
+            &BLOCK_PROPAGATION_NEXT_SEQ_SCHEMA,
+        )
+    }
+
+    pub fn insert_block_propagation_slot_to_db(&self, slot: u64, hash: &H256) {
+        self.insert_versioned_val(
+            DBTable::Misc,
+            &block_propagation_slot_key(slot),
+            &BLOCK_PROPAGATION_SLOT_SCHEMA,
+            hash,
+        );
+    }
+
+    pub fn block_propagation_slot_from_db(&self, slot: u64) -> Option<H256> {
+        self.load_versioned_val(
+            DBTable::Misc,
+            &block_propagation_slot_key(slot),
+            &BLOCK_PROPAGATION_SLOT_SCHEMA,
+        )
+    }
+
+    pub fn insert_block_body_availability_start_to_db(&self, height: u64) {
+        self.insert_versioned_val(
+            DBTable::Misc,
+            BLOCK_BODY_AVAILABILITY_START_KEY,
+            &BLOCK_BODY_AVAILABILITY_START_SCHEMA,
+            &height,
+        );
+    }
+
+    pub fn block_body_availability_start_from_db(&self) -> Option<u64> {
+        self.load_versioned_val(
+            DBTable::Misc,
+            BLOCK_BODY_AVAILABILITY_START_KEY,
+            &BLOCK_BODY_AVAILABILITY_START_SCHEMA,
+        )
+    }
+
+    /// Persist the hashes of the blocks whose bodies are in the database at
+    /// `height`.
+    pub fn insert_block_body_availability_to_db(
+        &self, height: u64, hashes: &Vec<H256>,
+    ) {
+        self.insert_versioned_list(
+            DBTable::Misc,
+            &block_body_availability_key(height),
+            &BLOCK_BODY_AVAILABILITY_SCHEMA,
+            hashes,
+        );
+    }
+
+    pub fn block_body_availability_from_db(
+        &self, height: u64,
+    ) -> Option<Vec<H256>> {
+        self.load_versioned_list(
+            DBTable::Misc,
+            &block_body_availability_key(height),
+            &BLOCK_BODY_AVAILABILITY_SCHEMA,
+        )
+    }
+
+    pub fn remove_block_body_availability_from_db(&self, height: u64) {
+        self.remove_from_db(DBTable::Misc, &block_body_availability_key(height))
+    }
+
+    pub fn insert_pending_invalid_blocks_to_db(&self, hashes: &Vec<H256>) {
+        self.insert_versioned_list(
+            DBTable::Misc,
+            PENDING_INVALID_BLOCKS_KEY,
+            &PENDING_INVALID_BLOCKS_SCHEMA,
+            hashes,
+        );
+    }
+
+    pub fn pending_invalid_blocks_from_db(&self) -> Option<Vec<H256>> {
+        self.load_versioned_list(
+            DBTable::Misc,
+            PENDING_INVALID_BLOCKS_KEY,
+            &PENDING_INVALID_BLOCKS_SCHEMA,
+        )
+    }
+
+    pub fn insert_spilled_headers_to_db(
+        &self, parent_hash: &H256, hashes: &Vec<H256>,
+    ) {
+        self.insert_versioned_list(
+            DBTable::Misc,
+            &spilled_headers_key(parent_hash),
+            &SPILLED_HEADERS_SCHEMA,
+            hashes,
+        );
+    }
+
+    pub fn spilled_headers_from_db(
+        &self, parent_hash: &H256,
+    ) -> Option<Vec<H256>> {
+        self.load_versioned_list(
+            DBTable::Misc,
+            &spilled_headers_key(parent_hash),
+            &SPILLED_HEADERS_SCHEMA,
+        )
+    }
+
+    pub fn remove_spilled_headers_from_db(&self, parent_hash: &H256) {
+        self.remove_from_db(DBTable::Misc, &spilled_headers_key(parent_hash))
+    }
+
     pub fn insert_gc_progress_to_db(&self, next_to_process: u64) {
-        self.insert_encodable_val(
+        self.insert_versioned_val(
             DBTable::Misc,
             GC_PROGRESS_KEY,
+            &GC_PROGRESS_SCHEMA,
             &next_to_process,
         );
     }
 
     pub fn gc_progress_from_db(&self) -> Option<u64> {
-        self.load_decodable_val(DBTable::Misc, GC_PROGRESS_KEY)
+        self.load_versioned_val(
+            DBTable::Misc,
+            GC_PROGRESS_KEY,
+            &GC_PROGRESS_SCHEMA,
+        )
     }
 
     /// The functions below are private utils used by the DBManager to access
@@ -493,6 +1215,17 @@ impl DBManager {
             .expect("db insertion failure");
     }
 
+    fn try_insert_to_db(
+        &self, table: DBTable, db_key: &[u8], value: Vec<u8>,
+    ) -> Result<(), String> {
+        self.table_db
+            .get(&table)
+            .unwrap()
+            .put(db_key, &value)
+            .map(|_| ())
+            .map_err(|e| format!("db insertion failure: {:?}", e))
+    }
+
     fn remove_from_db(&self, table: DBTable, db_key: &[u8]) {
         self.table_db
             .get(&table)
@@ -544,6 +1277,79 @@ impl DBManager {
         let encoded = self.load_from_db(table, db_key)?;
         Some(db_decode_list(&encoded).expect("decode succeeds"))
     }
+
+    fn insert_versioned_val<V>(
+        &self, table: DBTable, db_key: &[u8], schema: &RecordSchema, value: &V,
+    ) where V: DatabaseEncodable {
+        self.insert_to_db(
+            table,
+            db_key,
+            db_encode_versioned(schema, &value.db_encode()),
+        )
+    }
+
+    fn insert_versioned_list<V>(
+        &self, table: DBTable, db_key: &[u8], schema: &RecordSchema,
+        value: &Vec<V>,
+    ) where
+        V: DatabaseEncodable,
+    {
+        self.insert_to_db(
+            table,
+            db_key,
+            db_encode_versioned(schema, &db_encode_list(value)),
+        )
+    }
+
+    /// All the versions of the records loaded with this function, including
+    /// the records without an envelope (version 0), share the same payload
+    /// format so far.
+    fn load_versioned_val<V>(
+        &self, table: DBTable, db_key: &[u8], schema: &RecordSchema,
+    ) -> Option<V>
+    where V: DatabaseDecodable {
+        self.load_versioned_val_with(
+            table,
+            db_key,
+            schema,
+            |_version, payload| V::db_decode(payload),
+        )
+    }
+
+    /// Load a record whose payload format differs between the versions with
+    /// `decode`, which is given the payload format version.
+    /// A record that cannot be decoded, e.g. one that is corrupted or written
+    /// with an unknown schema, is ignored as if it were not in the database.
+    fn load_versioned_val_with<V, F>(
+        &self, table: DBTable, db_key: &[u8], schema: &RecordSchema, decode: F,
+    ) -> Option<V>
+    where F: FnOnce(u32, &[u8]) -> Result<V, DecoderError> {
+        let encoded = self.load_from_db(table, db_key)?;
+        match db_decode_versioned(schema, &encoded)
+            .and_then(|(version, payload)| decode(version, payload))
+        {
+            Ok(value) => Some(value),
+            Err(e) => {
+                warn!(
+                    "Ignore the record {} with key {:?} in db: {:?}",
+                    schema.id, db_key, e
+                );
+                None
+            }
+        }
+    }
+
+    fn load_versioned_list<V>(
+        &self, table: DBTable, db_key: &[u8], schema: &RecordSchema,
+    ) -> Option<Vec<V>>
+    where V: DatabaseDecodable {
+        self.load_versioned_val_with(
+            table,
+            db_key,
+            schema,
+            |_version, payload| db_decode_list(payload),
+        )
+    }
 }
 
 fn append_suffix(h: &H256, suffix: u8) -> Vec<u8> {
@@ -563,6 +1369,86 @@ fn blamed_header_verified_roots_key(block_height: u64) -> [u8; 8] {
     height_key
 }
 
+fn finalized_epoch_event_key(seq: u64) -> Vec<u8> {
+    let mut key = FINALIZED_EPOCH_EVENT_KEY_PREFIX.to_vec();
+    let mut seq_key = [0; 8];
+    LittleEndian::write_u64(&mut seq_key[0..8], seq);
+    key.extend_from_slice(&seq_key);
+    key
+}
+
+fn terminals_delta_key(seq: u64) -> Vec<u8> {
+    let mut key = BLOCK_TERMINAL_DELTA_KEY_PREFIX.to_vec();
+    let mut seq_key = [0; 8];
+    LittleEndian::write_u64(&mut seq_key[0..8], seq);
+    key.extend_from_slice(&seq_key);
+    key
+}
+
+fn block_propagation_slot_key(slot: u64) -> Vec<u8> {
+    let mut key = BLOCK_PROPAGATION_SLOT_KEY_PREFIX.to_vec();
+    let mut slot_key = [0; 8];
+    LittleEndian::write_u64(&mut slot_key[0..8], slot);
+    key.extend_from_slice(&slot_key);
+    key
+}
+
+fn block_body_availability_key(height: u64) -> Vec<u8> {
+    let mut key = BLOCK_BODY_AVAILABILITY_KEY_PREFIX.to_vec();
+    let mut height_key = [0; 8];
+    LittleEndian::write_u64(&mut height_key[0..8], height);
+    key.extend_from_slice(&height_key);
+    key
+}
+
+fn pivot_switch_event_key(seq: u64) -> Vec<u8> {
+    let mut key = PIVOT_SWITCH_EVENT_KEY_PREFIX.to_vec();
+    let mut seq_key = [0; 8];
+    LittleEndian::write_u64(&mut seq_key[0..8], seq);
+    key.extend_from_slice(&seq_key);
+    key
+}
+
+fn local_nonce_key(address: &Address) -> Vec<u8> {
+    let mut key = LOCAL_NONCE_KEY_PREFIX.to_vec();
+    key.extend_from_slice(address.as_bytes());
+    key
+}
+
+fn spilled_headers_key(parent_hash: &H256) -> Vec<u8> {
+    let mut key = SPILLED_HEADERS_KEY_PREFIX.to_vec();
+    key.extend_from_slice(parent_hash.as_bytes());
+    key
+}
+
+fn sender_epoch_transactions_key(
+    sender: &Address, epoch_number: u64,
+) -> Vec<u8> {
+    let mut key = SENDER_EPOCH_TRANSACTIONS_KEY_PREFIX.to_vec();
+    key.extend_from_slice(sender.as_bytes());
+    let mut epoch_key = [0; 8];
+    LittleEndian::write_u64(&mut epoch_key[0..8], epoch_number);
+    key.extend_from_slice(&epoch_key);
+    key
+}
+
+fn sender_transaction_epochs_key(sender: &Address, bucket: u64) -> Vec<u8> {
+    let mut key = SENDER_TRANSACTION_EPOCHS_KEY_PREFIX.to_vec();
+    key.extend_from_slice(sender.as_bytes());
+    let mut bucket_key = [0; 8];
+    LittleEndian::write_u64(&mut bucket_key[0..8], bucket);
+    key.extend_from_slice(&bucket_key);
+    key
+}
+
+fn epoch_transaction_senders_key(epoch_number: u64) -> Vec<u8> {
+    let mut key = EPOCH_TRANSACTION_SENDERS_KEY_PREFIX.to_vec();
+    let mut epoch_key = [0; 8];
+    LittleEndian::write_u64(&mut epoch_key[0..8], epoch_number);
+    key.extend_from_slice(&epoch_key);
+    key
+}
+
 fn block_body_key(block_hash: &H256) -> Vec<u8> {
     append_suffix(block_hash, BLOCK_BODY_SUFFIX_BYTE)
 }
@@ -581,6 +1467,17 @@ fn skipped_epoch_set_key(epoch_number: u64) -> [u8; 9] {
     epoch_key
 }
 
+fn pivot_hash_key(epoch_number: u64) -> [u8; 9] {
+    let mut epoch_key = [0; 9];
+    LittleEndian::write_u64(&mut epoch_key[0..8], epoch_number);
+    epoch_key[8] = PIVOT_HASH_SUFFIX_BYTE;
+    epoch_key
+}
+
+fn pivot_epoch_number_key(hash: &H256) -> Vec<u8> {
+    append_suffix(hash, PIVOT_EPOCH_NUMBER_SUFFIX_BYTE)
+}
+
 fn block_execution_result_key(hash: &H256) -> Vec<u8> {
     append_suffix(hash, BLOCK_EXECUTION_RESULT_SUFFIX_BYTE)
 }
@@ -589,10 +1486,38 @@ fn block_reward_result_key(hash: &H256) -> Vec<u8> {
     append_suffix(hash, BLOCK_REWARD_RESULT_SUFFIX_BYTE)
 }
 
+fn block_reward_details_key(hash: &H256) -> Vec<u8> {
+    append_suffix(hash, BLOCK_REWARD_DETAILS_SUFFIX_BYTE)
+}
+
 fn epoch_execution_context_key(hash: &H256) -> Vec<u8> {
     append_suffix(hash, EPOCH_EXECUTION_CONTEXT_SUFFIX_BYTE)
 }
 
+fn epoch_issuance_key(hash: &H256) -> Vec<u8> {
+    append_suffix(hash, EPOCH_ISSUANCE_SUFFIX_BYTE)
+}
+
+fn epoch_system_transaction_receipts_key(hash: &H256) -> Vec<u8> {
+    append_suffix(hash, EPOCH_SYSTEM_TRANSACTION_RECEIPTS_SUFFIX_BYTE)
+}
+
+fn epoch_execution_checkpoint_key(hash: &H256) -> Vec<u8> {
+    append_suffix(hash, EPOCH_EXECUTION_CHECKPOINT_SUFFIX_BYTE)
+}
+
+fn evicted_epoch_sets_key(hash: &H256) -> Vec<u8> {
+    append_suffix(hash, EVICTED_EPOCH_SETS_SUFFIX_BYTE)
+}
+
+fn block_log_index_key(hash: &H256) -> Vec<u8> {
+    append_suffix(hash, BLOCK_LOG_INDEX_SUFFIX_BYTE)
+}
+
+fn block_propagation_record_key(hash: &H256) -> Vec<u8> {
+    append_suffix(hash, BLOCK_PROPAGATION_RECORD_SUFFIX_BYTE)
+}
+
 fn epoch_consensus_epoch_execution_commitment_key(hash: &H256) -> Vec<u8> {
     append_suffix(hash, EPOCH_CONSENSUS_EXECUTION_INFO_SUFFIX_BYTE)
 }
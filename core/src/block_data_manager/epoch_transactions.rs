@@ -0,0 +1,125 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use super::BlockDataManager;
+use cfx_types::H256;
+use primitives::{receipt::Receipt, BlockReceipts, SignedTransaction};
+use std::{sync::Arc, vec};
+
+/// A transaction in an epoch with its receipt, yielded by
+/// `BlockDataManager::epoch_transactions_iter`.
+#[derive(Clone, Debug)]
+pub struct EpochTransaction {
+    pub block_hash: H256,
+    /// The index of the transaction in its block.
+    pub tx_index: usize,
+    pub transaction: Arc<SignedTransaction>,
+    pub receipt: Receipt,
+}
+
+struct LoadedBlock {
+    hash: H256,
+    transactions: Vec<Arc<SignedTransaction>>,
+    receipts: Arc<BlockReceipts>,
+    next_tx_index: usize,
+}
+
+/// Iterates the transactions of an epoch in the execution order. Only the
+/// transactions and the receipts of the block being iterated are loaded, and
+/// the blocks read from the database are not put into the block cache.
+///
+/// An error is yielded if the body or the receipts of a block are missing,
+/// and the iteration stops after it.
+pub struct EpochTransactionsIter<'a> {
+    data_man: &'a BlockDataManager,
+    epoch_hash: H256,
+    block_hashes: vec::IntoIter<H256>,
+    current: Option<LoadedBlock>,
+    failed: bool,
+}
+
+impl<'a> EpochTransactionsIter<'a> {
+    pub(super) fn new(
+        data_man: &'a BlockDataManager, epoch_hash: H256,
+        block_hashes: Vec<H256>,
+    ) -> Self
+    {
+        Self {
+            data_man,
+            epoch_hash,
+            block_hashes: block_hashes.into_iter(),
+            current: None,
+            failed: false,
+        }
+    }
+
+    fn load_block(&self, hash: H256) -> Result<LoadedBlock, String> {
+        let transactions = self
+            .data_man
+            .block_transactions_by_hash(&hash)
+            .ok_or_else(|| format!("Block body of {:?} not found", hash))?;
+        let receipts = self
+            .data_man
+            .block_execution_result_by_hash_with_epoch(
+                &hash,
+                &self.epoch_hash,
+                false, /* update_pivot_assumption */
+                false, /* update_cache */
+            )
+            .ok_or_else(|| {
+                format!(
+                    "Receipts of block {:?} in epoch {:?} not found",
+                    hash, self.epoch_hash
+                )
+            })?
+            .block_receipts;
+        if receipts.receipts.len() != transactions.len() {
+            return Err(format!(
+                "Block {:?} has {} transactions but {} receipts",
+                hash,
+                transactions.len(),
+                receipts.receipts.len()
+            ));
+        }
+        Ok(LoadedBlock {
+            hash,
+            transactions,
+            receipts,
+            next_tx_index: 0,
+        })
+    }
+}
+
+impl<'a> Iterator for EpochTransactionsIter<'a> {
+    type Item = Result<EpochTransaction, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        loop {
+            if let Some(block) = &mut self.current {
+                if block.next_tx_index < block.transactions.len() {
+                    let tx_index = block.next_tx_index;
+                    block.next_tx_index += 1;
+                    return Some(Ok(EpochTransaction {
+                        block_hash: block.hash,
+                        tx_index,
+                        transaction: block.transactions[tx_index].clone(),
+                        receipt: block.receipts.receipts[tx_index].clone(),
+                    }));
+                }
+            }
+            let hash = self.block_hashes.next()?;
+            match self.load_block(hash) {
+                Ok(block) => self.current = Some(block),
+                Err(e) => {
+                    self.current = None;
+                    self.failed = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
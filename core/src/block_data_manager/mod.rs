@@ -13,7 +13,7 @@ use cfx_storage::{
     state_manager::StateIndex, utils::guarded_value::*, StorageManager,
     StorageManagerTrait, StorageStateTrait,
 };
-use cfx_types::{Bloom, H256};
+use cfx_types::{Address, Bloom, H256};
 use malloc_size_of::{new_malloc_size_ops, MallocSizeOf, MallocSizeOfOps};
 use malloc_size_of_derive::MallocSizeOf as DeriveMallocSizeOf;
 use parking_lot::{Mutex, RwLock, RwLockReadGuard, RwLockUpgradableReadGuard};
@@ -28,17 +28,20 @@ use primitives::{
 };
 use rlp::DecoderError;
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
+    mem,
     sync::Arc,
 };
 use threadpool::ThreadPool;
 pub mod block_data_types;
 pub mod db_gc_manager;
 pub mod db_manager;
+pub mod epoch_transactions;
 pub mod tx_data_manager;
 use crate::{
     block_data_manager::{
-        db_manager::DBManager, tx_data_manager::TransactionDataManager,
+        db_manager::DBManager, epoch_transactions::EpochTransactionsIter,
+        tx_data_manager::TransactionDataManager,
     },
     trace::trace::{BlockExecTraces, TransactionExecTraces},
 };
@@ -57,6 +60,25 @@ lazy_static! {
 
 pub const NULLU64: u64 = !0;
 
+/// The full terminal list is rewritten after this number of deltas.
+const TERMINALS_DELTA_COMPACTION_INTERVAL: u64 = 1000;
+/// The maximum number of heights whose block body availability is kept in
+/// memory.
+const MAX_CACHED_BLOCK_BODY_AVAILABILITY_HEIGHTS: usize = 16384;
+
+/// The number of epochs whose transactions of a sender are indexed in one
+/// record.
+pub const SENDER_TRANSACTION_INDEX_BUCKET_SIZE: u64 = 1024;
+
+const MB: usize = 1024 * 1024;
+
+/// Return the preferred and the maximum sizes in bytes of the ledger cache
+/// for the `CacheConfig`.
+fn ledger_cache_sizes(cache_conf: &CacheConfig) -> (usize, usize) {
+    let max_cache_size = cache_conf.ledger_mb() * MB;
+    (max_cache_size * 3 / 4, max_cache_size)
+}
+
 #[derive(DeriveMallocSizeOf)]
 pub struct InvalidBlockSet {
     capacity: usize,
@@ -92,9 +114,77 @@ impl InvalidBlockSet {
     }
 }
 
+/// The headers of the latest pivot blocks that are kept in memory regardless
+/// of the cache garbage collection.
+#[derive(Default, DeriveMallocSizeOf)]
+pub struct PinnedBlockHeaders {
+    headers: HashMap<H256, Arc<BlockHeader>>,
+    #[ignore_malloc_size_of = "insignificant"]
+    hash_by_height: BTreeMap<u64, H256>,
+}
+
+impl PinnedBlockHeaders {
+    pub fn get(&self, hash: &H256) -> Option<Arc<BlockHeader>> {
+        self.headers.get(hash).cloned()
+    }
+
+    /// Unpin the headers with height not smaller than `height`.
+    fn unpin_from(&mut self, height: u64) {
+        for (_, hash) in self.hash_by_height.split_off(&height) {
+            self.headers.remove(&hash);
+        }
+    }
+
+    /// Unpin the headers with height smaller than `height`.
+    fn unpin_before(&mut self, height: u64) {
+        let kept = self.hash_by_height.split_off(&height);
+        for (_, hash) in mem::replace(&mut self.hash_by_height, kept) {
+            self.headers.remove(&hash);
+        }
+    }
+
+    fn pin(&mut self, header: Arc<BlockHeader>) {
+        self.hash_by_height.insert(header.height(), header.hash());
+        self.headers.insert(header.hash(), header);
+    }
+}
+
+/// The terminal set that is last written to the database.
+#[derive(DeriveMallocSizeOf)]
+struct PersistedTerminals {
+    terminals: HashSet<H256>,
+    /// The number of deltas written since the last compaction.
+    delta_count: u64,
+}
+
+/// The hashes of the blocks whose bodies are in the database, grouped by
+/// heights. The availability is unknown below `start_height`, because the
+/// bodies written by older versions are not recorded. It is unknown at all
+/// heights if `start_height` is `None`.
+#[derive(DeriveMallocSizeOf)]
+struct BlockBodyAvailability {
+    start_height: Option<u64>,
+    #[ignore_malloc_size_of = "insignificant"]
+    heights: BTreeMap<u64, HashSet<H256>>,
+}
+
+impl BlockBodyAvailability {
+    fn is_tracked(&self, height: u64) -> bool {
+        self.start_height.map_or(false, |start| height >= start)
+    }
+}
+
 #[derive(DeriveMallocSizeOf)]
 pub struct BlockDataManager {
     block_headers: RwLock<HashMap<H256, Arc<BlockHeader>>>,
+    /// The headers of the latest `pinned_pivot_header_count` pivot blocks,
+    /// which are frequently used in verification and difficulty computation.
+    pinned_block_headers: RwLock<PinnedBlockHeaders>,
+    /// Loaded from the database on the first insertion of terminals.
+    persisted_terminals: Mutex<Option<PersistedTerminals>>,
+    block_body_availability: Mutex<BlockBodyAvailability>,
+    /// Serialize the read-modify-write of the sender transaction records.
+    sender_transaction_index_lock: Mutex<()>,
     blocks: RwLock<HashMap<H256, Arc<Block>>>,
     compact_blocks: RwLock<HashMap<H256, CompactBlock>>,
     block_receipts: RwLock<HashMap<H256, BlockReceiptsInfo>>,
@@ -173,13 +263,11 @@ impl BlockDataManager {
         pow: Arc<PowComputer>,
     ) -> Self
     {
-        let mb = 1024 * 1024;
-        let max_cache_size = cache_conf.ledger_mb() * mb;
-        let pref_cache_size = max_cache_size * 3 / 4;
+        let (pref_cache_size, max_cache_size) = ledger_cache_sizes(&cache_conf);
         let cache_man = Arc::new(Mutex::new(CacheManager::new(
             pref_cache_size,
             max_cache_size,
-            3 * mb,
+            3 * MB,
         )));
         let tx_data_manager = TransactionDataManager::new(
             config.tx_cache_index_maintain_timeout,
@@ -194,9 +282,17 @@ impl BlockDataManager {
         };
         let previous_db_progress =
             db_manager.gc_progress_from_db().unwrap_or(0);
+        let block_body_availability = BlockBodyAvailability {
+            start_height: db_manager.block_body_availability_start_from_db(),
+            heights: BTreeMap::new(),
+        };
 
         let data_man = Self {
             block_headers: RwLock::new(HashMap::new()),
+            pinned_block_headers: Default::default(),
+            persisted_terminals: Default::default(),
+            block_body_availability: Mutex::new(block_body_availability),
+            sender_transaction_index_lock: Mutex::new(()),
             blocks: RwLock::new(HashMap::new()),
             compact_blocks: Default::default(),
             block_receipts: Default::default(),
@@ -232,6 +328,9 @@ impl BlockDataManager {
             ))),
         };
 
+        // It checks whether the database is new by the instance id, so it is
+        // called before the instance id is written.
+        data_man.initialize_block_body_availability();
         data_man.initialize_instance_id();
 
         let cur_era_genesis_hash =
@@ -321,6 +420,31 @@ impl BlockDataManager {
         data_man
     }
 
+    /// Check the true genesis against the one stored when the database was
+    /// created. The genesis is stored if the database has none, e.g. it is
+    /// new or created by an older version.
+    pub fn check_stored_true_genesis(&self) -> Result<(), String> {
+        let genesis = &self.true_genesis.block_header;
+        match self.db_manager.true_genesis_header_from_db() {
+            None => {
+                self.db_manager.insert_true_genesis_header_to_db(genesis);
+                Ok(())
+            }
+            Some(stored) if stored.hash() == genesis.hash() => Ok(()),
+            Some(stored) => Err(format!(
+                "The genesis block {:?} (state root {:?}, receipts root {:?}) \
+                 differs from the genesis block {:?} (state root {:?}, \
+                 receipts root {:?}) the database is created with",
+                genesis.hash(),
+                genesis.deferred_state_root(),
+                genesis.deferred_receipts_root(),
+                stored.hash(),
+                stored.deferred_state_root(),
+                stored.deferred_receipts_root()
+            )),
+        }
+    }
+
     pub fn get_instance_id(&self) -> u64 { *self.instance_id.lock() }
 
     pub fn initialize_instance_id(&self) {
@@ -391,6 +515,13 @@ impl BlockDataManager {
     ) {
         if persistent {
             self.db_manager.insert_block_body_to_db(block.as_ref());
+            // The availability is recorded after the body is written, so a
+            // crash in between only makes us request the body again.
+            self.set_block_body_availability(
+                hash,
+                block.block_header.height(),
+                true,
+            );
         }
         self.cache_man.lock().note_used(CacheId::Block(hash));
         self.blocks.write().insert(hash, block);
@@ -399,11 +530,140 @@ impl BlockDataManager {
     /// remove block body in memory cache and db
     pub fn remove_block_body(&self, hash: &H256, remove_db: bool) {
         if remove_db {
+            match self.block_header_by_hash(hash) {
+                Some(header) => self.set_block_body_availability(
+                    *hash,
+                    header.height(),
+                    false,
+                ),
+                None => warn!(
+                    "Block body availability of {:?} is not updated: header missing",
+                    hash
+                ),
+            }
             self.db_manager.remove_block_body_from_db(hash);
         }
         self.blocks.write().remove(hash);
     }
 
+    /// Start tracking the block body availability if it is not tracked yet.
+    /// All the heights are tracked in a new database. In an existing database,
+    /// the bodies written by older versions are not higher than the known
+    /// terminals, so the heights above them are tracked. If the terminals are
+    /// unknown, the availability stays unknown at all heights until the next
+    /// start.
+    fn initialize_block_body_availability(&self) {
+        let mut availability = self.block_body_availability.lock();
+        if availability.start_height.is_some() {
+            return;
+        }
+        let start_height = if self.db_manager.instance_id_from_db().is_none() {
+            0
+        } else {
+            match self.terminals_from_db() {
+                None => return,
+                Some(terminals) => {
+                    terminals
+                        .iter()
+                        .filter_map(|hash| {
+                            self.db_manager.block_header_from_db(hash)
+                        })
+                        .map(|header| header.height())
+                        .max()
+                        .unwrap_or(0)
+                        + 1
+                }
+            }
+        };
+        self.db_manager
+            .insert_block_body_availability_start_to_db(start_height);
+        availability.start_height = Some(start_height);
+    }
+
+    /// Return whether the body of a block is in the database according to the
+    /// persisted availability records, without reading the body. Return
+    /// `None` if the availability at the height is unknown.
+    pub fn block_body_availability(
+        &self, hash: &H256, height: u64,
+    ) -> Option<bool> {
+        let mut availability = self.block_body_availability.lock();
+        if !availability.is_tracked(height) {
+            return None;
+        }
+        Some(
+            self.load_block_body_availability_at(&mut availability, height)
+                .contains(hash),
+        )
+    }
+
+    /// Return whether the body of a block is in memory or in the database. The
+    /// database is only probed if the availability is not tracked.
+    pub fn block_body_available(&self, hash: &H256) -> bool {
+        if self.blocks.read().contains_key(hash) {
+            return true;
+        }
+        let tracked = self.block_header_by_hash(hash).and_then(|header| {
+            self.block_body_availability(hash, header.height())
+        });
+        match tracked {
+            Some(available) => available,
+            None => self.block_by_hash(hash, false).is_some(),
+        }
+    }
+
+    /// Only the record at `height` is rewritten, which holds the few blocks
+    /// at the same height.
+    fn set_block_body_availability(
+        &self, hash: H256, height: u64, available: bool,
+    ) {
+        let mut availability = self.block_body_availability.lock();
+        if !availability.is_tracked(height) {
+            return;
+        }
+        let hashes =
+            self.load_block_body_availability_at(&mut availability, height);
+        let changed = if available {
+            hashes.insert(hash)
+        } else {
+            hashes.remove(&hash)
+        };
+        if changed {
+            if hashes.is_empty() {
+                self.db_manager
+                    .remove_block_body_availability_from_db(height);
+            } else {
+                self.db_manager.insert_block_body_availability_to_db(
+                    height,
+                    &hashes.iter().cloned().collect(),
+                );
+            }
+        }
+    }
+
+    fn load_block_body_availability_at<'a>(
+        &self, availability: &'a mut BlockBodyAvailability, height: u64,
+    ) -> &'a mut HashSet<H256> {
+        if !availability.heights.contains_key(&height)
+            && availability.heights.len()
+                >= MAX_CACHED_BLOCK_BODY_AVAILABILITY_HEIGHTS
+        {
+            // The heights are persisted on every change, so any of them can
+            // be evicted. The lowest one is the least likely to be used.
+            let lowest = availability.heights.keys().next().cloned();
+            if let Some(lowest) = lowest {
+                availability.heights.remove(&lowest);
+            }
+        }
+        let db_manager = &self.db_manager;
+        availability.heights.entry(height).or_insert_with(|| {
+            db_manager
+                .block_body_availability_from_db(height)
+                .unwrap_or_default()
+                .into_iter()
+                .collect()
+        })
+    }
+
     /// TODO Also set block header
     pub fn block_by_hash(
         &self, hash: &H256, update_cache: bool,
@@ -425,6 +685,30 @@ impl BlockDataManager {
         self.db_manager.block_from_db(hash)
     }
 
+    /// Return the transactions of a block from the block cache or the
+    /// database. Unlike `block_by_hash`, the block read from the database is
+    /// not cached.
+    pub fn block_transactions_by_hash(
+        &self, hash: &H256,
+    ) -> Option<Vec<Arc<SignedTransaction>>> {
+        if let Some(block) = self.blocks.read().get(hash) {
+            return Some(block.transactions.clone());
+        }
+        self.db_manager.block_body_from_db(hash)
+    }
+
+    /// Iterate the transactions of the executed epoch `epoch_number` on the
+    /// current pivot chain with their receipts, loading one block at a time.
+    /// Return `None` if the epoch set is not in the database.
+    pub fn epoch_transactions_iter(
+        &self, epoch_number: u64,
+    ) -> Option<EpochTransactionsIter> {
+        let block_hashes =
+            self.executed_epoch_set_hashes_from_db(epoch_number)?;
+        let epoch_hash = *block_hashes.last()?;
+        Some(EpochTransactionsIter::new(self, epoch_hash, block_hashes))
+    }
+
     pub fn blocks_by_hash_list(
         &self, hashes: &Vec<H256>, update_cache: bool,
     ) -> Option<Vec<Arc<Block>>> {
@@ -454,11 +738,16 @@ impl BlockDataManager {
         // blockchain and we should not remove it here.
         if self
             .local_block_info_by_hash(hash)
-            .map(|info| info.get_status() == BlockStatus::Invalid)
+            .map(|info| {
+                info.get_status() == BlockStatus::Invalid
+                    || info.get_status() == BlockStatus::Expired
+            })
             .unwrap_or(true)
         {
-            self.remove_block_header(hash, remove_db);
+            // The body is removed first because its height is read from the
+            // header.
             self.remove_block_body(hash, remove_db);
+            self.remove_block_header(hash, remove_db);
         }
     }
 
@@ -555,6 +844,9 @@ impl BlockDataManager {
     pub fn block_header_by_hash(
         &self, hash: &H256,
     ) -> Option<Arc<BlockHeader>> {
+        if let Some(header) = self.pinned_block_headers.read().get(hash) {
+            return Some(header);
+        }
         self.get(
             hash,
             &self.block_headers,
@@ -586,6 +878,41 @@ impl BlockDataManager {
         self.block_headers.write().remove(hash);
     }
 
+    pub fn pinned_pivot_header_count(&self) -> usize {
+        self.config.pinned_pivot_header_count
+    }
+
+    /// Pin the headers of the pivot blocks starting from `start_height`.
+    /// The headers pinned before at these heights are replaced, and only the
+    /// latest `pinned_pivot_header_count` headers are kept.
+    pub fn pin_pivot_block_headers(
+        &self, start_height: u64, pivot_hashes: &[H256],
+    ) {
+        let count = self.config.pinned_pivot_header_count as u64;
+        if count == 0 {
+            return;
+        }
+        let end_height = start_height + pivot_hashes.len() as u64;
+        let first_pinned_height = end_height.saturating_sub(count);
+        let headers: Vec<_> = pivot_hashes
+            .iter()
+            .skip(first_pinned_height.saturating_sub(start_height) as usize)
+            .filter_map(|hash| self.block_header_by_hash(hash))
+            .collect();
+        let mut pinned = self.pinned_block_headers.write();
+        pinned.unpin_from(start_height);
+        pinned.unpin_before(first_pinned_height);
+        for header in headers {
+            pinned.pin(header);
+        }
+    }
+
+    /// Unpin the headers below `height`. This is called when the checkpoint
+    /// moves, since the blocks before the new era genesis may be removed.
+    pub fn unpin_block_headers_before(&self, height: u64) {
+        self.pinned_block_headers.write().unpin_before(height);
+    }
+
     pub fn block_height_by_hash(&self, hash: &H256) -> Option<u64> {
         let result = self.block_header_by_hash(hash)?;
         Some(result.height())
@@ -728,12 +1055,36 @@ impl BlockDataManager {
         )
     }
 
+    pub fn insert_block_reward_details(
+        &self, hash: &H256, details: &BlockRewardDetails,
+    ) {
+        self.db_manager
+            .insert_block_reward_details_to_db(hash, details);
+    }
+
+    /// Return the reward details of a block if its reward is computed in the
+    /// epoch `assumed_epoch_later`.
+    pub fn block_reward_details_by_hash_with_epoch(
+        &self, hash: &H256, assumed_epoch_later: &H256,
+    ) -> Option<BlockRewardDetails> {
+        self.db_manager
+            .block_reward_details_from_db(hash)
+            .filter(|details| details.epoch_hash == *assumed_epoch_later)
+    }
+
     pub fn remove_block_result(&self, hash: &H256, remove_db: bool) {
         self.block_receipts.write().remove(hash);
         self.block_rewards.write().remove(hash);
         if remove_db {
             self.db_manager.remove_block_execution_result_from_db(hash);
             self.db_manager.remove_block_reward_result_from_db(hash);
+            self.db_manager.remove_block_reward_details_from_db(hash);
+            self.db_manager.remove_block_log_index_from_db(hash);
+            // Only exist if `hash` is a pivot block.
+            self.db_manager
+                .remove_epoch_system_transaction_receipts_from_db(hash);
+            self.db_manager
+                .remove_epoch_execution_checkpoint_from_db(hash);
         }
     }
 
@@ -832,6 +1183,212 @@ impl BlockDataManager {
         }
     }
 
+    /// Change the size of the ledger cache to `ledger` MB.
+    pub fn set_ledger_cache_size(&self, ledger: usize) {
+        let (pref_cache_size, max_cache_size) =
+            ledger_cache_sizes(&CacheConfig {
+                ledger,
+                ..Default::default()
+            });
+        self.cache_man
+            .lock()
+            .set_cache_size(pref_cache_size, max_cache_size);
+    }
+
+    /// Record `hash` as the pivot block of `epoch_number` when the epoch is
+    /// executed on the local pivot chain. The index is kept after the blocks
+    /// are checkpointed, and a pivot block executed later at the same height
+    /// replaces the old one.
+    pub fn insert_pivot_epoch_index(&self, epoch_number: u64, hash: &H256) {
+        self.db_manager.insert_pivot_hash_to_db(epoch_number, hash);
+        self.db_manager
+            .insert_pivot_epoch_number_to_db(hash, epoch_number);
+    }
+
+    pub fn pivot_hash_by_epoch_number(
+        &self, epoch_number: u64,
+    ) -> Option<H256> {
+        self.db_manager.pivot_hash_from_db(epoch_number)
+    }
+
+    /// Return the epoch number of `hash` if it is still the indexed pivot
+    /// block of the epoch.
+    pub fn epoch_number_by_pivot_hash(&self, hash: &H256) -> Option<u64> {
+        let epoch_number = self.db_manager.pivot_epoch_number_from_db(hash)?;
+        if self.pivot_hash_by_epoch_number(epoch_number) == Some(*hash) {
+            Some(epoch_number)
+        } else {
+            None
+        }
+    }
+
+    pub fn sender_transaction_index_enabled(&self) -> bool {
+        self.config.index_transactions_by_sender
+    }
+
+    /// Index the transactions executed in the epoch of `pivot_hash` by their
+    /// senders. The executed transactions are told by their transaction
+    /// indices, so it is called after the indices of the epoch are inserted.
+    /// The index of the epoch is replaced when another pivot block at the
+    /// same height is executed.
+    pub fn insert_sender_transaction_index(
+        &self, epoch_number: u64, pivot_hash: &H256,
+        epoch_block_hashes: &[H256],
+    )
+    {
+        if !self.config.index_transactions_by_sender {
+            return;
+        }
+        let mut transactions_by_sender: BTreeMap<Address, Vec<H256>> =
+            BTreeMap::new();
+        for block_hash in epoch_block_hashes {
+            let block = match self
+                .block_by_hash(block_hash, false /* update_cache */)
+            {
+                Some(block) => block,
+                None => {
+                    warn!(
+                        "Skip indexing epoch {} by senders: block {:?} is \
+                         missing",
+                        epoch_number, block_hash
+                    );
+                    return;
+                }
+            };
+            for (index, tx) in block.transactions.iter().enumerate() {
+                let executed_in_block = self
+                    .transaction_index_by_hash(&tx.hash, false)
+                    .map_or(false, |tx_index| {
+                        tx_index.block_hash == *block_hash
+                            && tx_index.index == index
+                    });
+                if executed_in_block {
+                    transactions_by_sender
+                        .entry(tx.sender)
+                        .or_default()
+                        .push(tx.hash);
+                }
+            }
+        }
+
+        let _lock = self.sender_transaction_index_lock.lock();
+        // The senders indexed for another pivot block of the epoch.
+        if let Some(indexed) = self
+            .db_manager
+            .epoch_transaction_senders_from_db(epoch_number)
+        {
+            for sender in indexed.senders {
+                if !transactions_by_sender.contains_key(&sender) {
+                    self.remove_sender_epoch_transactions(
+                        &sender,
+                        epoch_number,
+                    );
+                }
+            }
+        }
+        if transactions_by_sender.is_empty() {
+            self.db_manager
+                .remove_epoch_transaction_senders_from_db(epoch_number);
+            return;
+        }
+        let bucket = epoch_number / SENDER_TRANSACTION_INDEX_BUCKET_SIZE;
+        let offset = epoch_number % SENDER_TRANSACTION_INDEX_BUCKET_SIZE;
+        let mut senders = Vec::with_capacity(transactions_by_sender.len());
+        for (sender, transaction_hashes) in transactions_by_sender {
+            self.db_manager.insert_sender_epoch_transactions_to_db(
+                &sender,
+                &SenderEpochTransactions {
+                    epoch_number,
+                    pivot_hash: *pivot_hash,
+                    transaction_hashes,
+                },
+            );
+            let mut epochs = self
+                .db_manager
+                .sender_transaction_epochs_from_db(&sender, bucket)
+                .unwrap_or_default();
+            if !epochs.contains(offset) {
+                epochs.insert(offset);
+                self.db_manager.insert_sender_transaction_epochs_to_db(
+                    &sender, bucket, &epochs,
+                );
+            }
+            senders.push(sender);
+        }
+        self.db_manager.insert_epoch_transaction_senders_to_db(
+            epoch_number,
+            &EpochTransactionSenders { senders },
+        );
+    }
+
+    /// Remove the transactions of `sender` in the epoch from the index. It is
+    /// called with `sender_transaction_index_lock` held.
+    fn remove_sender_epoch_transactions(
+        &self, sender: &Address, epoch_number: u64,
+    ) {
+        self.db_manager
+            .remove_sender_epoch_transactions_from_db(sender, epoch_number);
+        let bucket = epoch_number / SENDER_TRANSACTION_INDEX_BUCKET_SIZE;
+        if let Some(mut epochs) = self
+            .db_manager
+            .sender_transaction_epochs_from_db(sender, bucket)
+        {
+            epochs.remove(epoch_number % SENDER_TRANSACTION_INDEX_BUCKET_SIZE);
+            if epochs.is_empty() {
+                self.db_manager
+                    .remove_sender_transaction_epochs_from_db(sender, bucket);
+            } else {
+                self.db_manager.insert_sender_transaction_epochs_to_db(
+                    sender, bucket, &epochs,
+                );
+            }
+        }
+    }
+
+    /// Remove the index of the transactions executed in the epoch by their
+    /// senders.
+    fn remove_sender_transaction_index(&self, epoch_number: u64) {
+        let _lock = self.sender_transaction_index_lock.lock();
+        if let Some(indexed) = self
+            .db_manager
+            .epoch_transaction_senders_from_db(epoch_number)
+        {
+            for sender in indexed.senders {
+                self.remove_sender_epoch_transactions(&sender, epoch_number);
+            }
+            self.db_manager
+                .remove_epoch_transaction_senders_from_db(epoch_number);
+        }
+    }
+
+    /// Return the indexed transactions of `sender` in the epochs of `bucket`
+    /// whose pivot blocks are still indexed, in the ascending order of the
+    /// epochs.
+    pub fn sender_transactions_in_bucket(
+        &self, sender: &Address, bucket: u64,
+    ) -> Vec<SenderEpochTransactions> {
+        let epochs = match self
+            .db_manager
+            .sender_transaction_epochs_from_db(sender, bucket)
+        {
+            Some(epochs) => epochs,
+            None => return Vec::new(),
+        };
+        epochs
+            .offsets()
+            .filter_map(|offset| {
+                self.db_manager.sender_epoch_transactions_from_db(
+                    sender,
+                    bucket * SENDER_TRANSACTION_INDEX_BUCKET_SIZE + offset,
+                )
+            })
+            .filter(|entry| {
+                self.pivot_hash_by_epoch_number(entry.epoch_number)
+                    == Some(entry.pivot_hash)
+            })
+            .collect()
+    }
+
     pub fn insert_local_block_info(&self, hash: &H256, info: LocalBlockInfo) {
         self.insert(
             *hash,
@@ -997,12 +1554,84 @@ impl BlockDataManager {
         Some(res)
     }
 
+    /// Persist the terminal set. Only the change since the last call is
+    /// appended as a delta, and the full list is rewritten every
+    /// `TERMINALS_DELTA_COMPACTION_INTERVAL` deltas.
     pub fn insert_terminals_to_db(&self, terminals: Vec<H256>) {
-        self.db_manager.insert_terminals_to_db(&terminals)
+        let mut persisted_opt = self.persisted_terminals.lock();
+        let persisted = persisted_opt.get_or_insert_with(|| {
+            let (terminals, delta_count) =
+                self.terminals_and_delta_count_from_db();
+            PersistedTerminals {
+                terminals: terminals.unwrap_or_default().into_iter().collect(),
+                delta_count,
+            }
+        });
+        let new_terminals: HashSet<H256> = terminals.iter().cloned().collect();
+        let delta = TerminalsDelta {
+            added: terminals
+                .iter()
+                .filter(|hash| !persisted.terminals.contains(*hash))
+                .cloned()
+                .collect(),
+            removed: persisted
+                .terminals
+                .iter()
+                .filter(|hash| !new_terminals.contains(*hash))
+                .cloned()
+                .collect(),
+        };
+        if delta.added.is_empty() && delta.removed.is_empty() {
+            return;
+        }
+        self.db_manager
+            .insert_terminals_delta_to_db(persisted.delta_count, &delta);
+        persisted.delta_count += 1;
+        self.db_manager
+            .insert_terminals_delta_count_to_db(persisted.delta_count);
+        if persisted.delta_count >= TERMINALS_DELTA_COMPACTION_INTERVAL {
+            // If we crash before the count is reset, all the deltas are
+            // replayed on the new full list, which still results in the same
+            // set because every change since the last compaction is in the
+            // deltas.
+            self.db_manager.insert_terminals_to_db(&terminals);
+            self.db_manager.insert_terminals_delta_count_to_db(0);
+            persisted.delta_count = 0;
+        }
+        persisted.terminals = new_terminals;
     }
 
+    /// Load the terminal set. The full list written by older versions without
+    /// any delta is loaded as is.
     pub fn terminals_from_db(&self) -> Option<Vec<H256>> {
-        self.db_manager.terminals_from_db()
+        self.terminals_and_delta_count_from_db().0
+    }
+
+    fn terminals_and_delta_count_from_db(&self) -> (Option<Vec<H256>>, u64) {
+        let mut terminals_opt = self.db_manager.terminals_from_db();
+        let delta_count =
+            self.db_manager.terminals_delta_count_from_db().unwrap_or(0);
+        let mut terminal_set: HashSet<H256> =
+            terminals_opt.iter().flatten().cloned().collect();
+        for seq in 0..delta_count {
+            let delta = self
+                .db_manager
+                .terminals_delta_from_db(seq)
+                .expect("terminals delta before the count exists");
+            let terminals = terminals_opt.get_or_insert_with(Vec::new);
+            if !delta.removed.is_empty() {
+                let removed: HashSet<H256> =
+                    delta.removed.into_iter().collect();
+                terminals.retain(|hash| !removed.contains(hash));
+                terminal_set.retain(|hash| !removed.contains(hash));
+            }
+            for hash in delta.added {
+                if terminal_set.insert(hash) {
+                    terminals.push(hash);
+                }
+            }
+        }
+        (terminals_opt, delta_count)
     }
 
     pub fn insert_executed_epoch_set_hashes_to_db(
@@ -1185,6 +1814,195 @@ impl BlockDataManager {
             .remove_epoch_execution_context_from_db(block_hash);
     }
 
+    /// Issuance is only persisted for epochs executed on the local pivot
+    /// chain, so the value is overwritten if the epoch is re-executed.
+    pub fn insert_epoch_issuance(
+        &self, epoch_hash: &H256, issuance: &EpochIssuance,
+    ) {
+        self.db_manager
+            .insert_epoch_issuance_to_db(epoch_hash, issuance);
+    }
+
+    pub fn epoch_issuance_by_hash(
+        &self, epoch_hash: &H256,
+    ) -> Option<EpochIssuance> {
+        self.db_manager.epoch_issuance_from_db(epoch_hash)
+    }
+
+    pub fn remove_epoch_issuance_from_db(&self, epoch_hash: &H256) {
+        self.db_manager.remove_epoch_issuance_from_db(epoch_hash);
+    }
+
+    pub fn insert_epoch_system_transaction_receipts(
+        &self, epoch_hash: &H256, receipts: &EpochSystemTransactionReceipts,
+    ) {
+        self.db_manager
+            .insert_epoch_system_transaction_receipts_to_db(
+                epoch_hash, receipts,
+            );
+    }
+
+    pub fn remove_epoch_system_transaction_receipts(&self, epoch_hash: &H256) {
+        self.db_manager
+            .remove_epoch_system_transaction_receipts_from_db(epoch_hash);
+    }
+
+    /// Return `None` if no system transaction is executed in the epoch, or
+    /// the epoch is not executed.
+    pub fn epoch_system_transaction_receipts_by_hash(
+        &self, epoch_hash: &H256,
+    ) -> Option<EpochSystemTransactionReceipts> {
+        self.db_manager
+            .epoch_system_transaction_receipts_from_db(epoch_hash)
+    }
+
+    pub fn insert_epoch_execution_checkpoint(
+        &self, epoch_hash: &H256, checkpoint: &EpochExecutionCheckpoint,
+    ) {
+        self.db_manager
+            .insert_epoch_execution_checkpoint_to_db(epoch_hash, checkpoint);
+    }
+
+    /// Return the checkpoint of the unfinished execution of an epoch.
+    pub fn epoch_execution_checkpoint(
+        &self, epoch_hash: &H256,
+    ) -> Option<EpochExecutionCheckpoint> {
+        self.db_manager
+            .epoch_execution_checkpoint_from_db(epoch_hash)
+    }
+
+    pub fn remove_epoch_execution_checkpoint(&self, epoch_hash: &H256) {
+        self.db_manager
+            .remove_epoch_execution_checkpoint_from_db(epoch_hash);
+    }
+
+    /// The epoch sets of a pivot block are written to the database when they
+    /// are evicted from the consensus graph, and removed after they are
+    /// reloaded.
+    pub fn insert_evicted_epoch_sets(
+        &self, epoch_hash: &H256, epoch_sets: &EvictedEpochSets,
+    ) -> Result<(), String> {
+        self.db_manager
+            .insert_evicted_epoch_sets_to_db(epoch_hash, epoch_sets)
+    }
+
+    pub fn evicted_epoch_sets(
+        &self, epoch_hash: &H256,
+    ) -> Option<EvictedEpochSets> {
+        self.db_manager.evicted_epoch_sets_from_db(epoch_hash)
+    }
+
+    pub fn remove_evicted_epoch_sets(&self, epoch_hash: &H256) {
+        self.db_manager
+            .remove_evicted_epoch_sets_from_db(epoch_hash);
+    }
+
+    pub fn insert_block_log_index(&self, hash: &H256, index: &BlockLogIndex) {
+        self.db_manager.insert_block_log_index_to_db(hash, index);
+    }
+
+    /// Return the log index of a block if it is built from the receipts of
+    /// the block executed in the epoch `assumed_epoch`.
+    pub fn block_log_index_by_hash_with_epoch(
+        &self, hash: &H256, assumed_epoch: &H256,
+    ) -> Option<BlockLogIndex> {
+        self.db_manager
+            .block_log_index_from_db(hash)
+            .filter(|index| index.epoch_hash == *assumed_epoch)
+    }
+
+    pub fn insert_finalized_epoch_outbox_progress(
+        &self, progress: &FinalizedEpochOutboxProgress,
+    ) {
+        self.db_manager
+            .insert_finalized_epoch_outbox_progress_to_db(progress);
+    }
+
+    pub fn finalized_epoch_outbox_progress(
+        &self,
+    ) -> Option<FinalizedEpochOutboxProgress> {
+        self.db_manager.finalized_epoch_outbox_progress_from_db()
+    }
+
+    pub fn insert_finalized_epoch_event(&self, event: &FinalizedEpochEvent) {
+        self.db_manager.insert_finalized_epoch_event_to_db(event);
+    }
+
+    pub fn finalized_epoch_event(
+        &self, seq: u64,
+    ) -> Option<FinalizedEpochEvent> {
+        self.db_manager.finalized_epoch_event_from_db(seq)
+    }
+
+    pub fn remove_finalized_epoch_event(&self, seq: u64) {
+        self.db_manager.remove_finalized_epoch_event_from_db(seq);
+    }
+
+    pub fn insert_block_propagation_record(
+        &self, record: &BlockPropagationRecord,
+    ) {
+        self.db_manager
+            .insert_block_propagation_record_to_db(record);
+    }
+
+    pub fn block_propagation_record_by_hash(
+        &self, hash: &H256,
+    ) -> Option<BlockPropagationRecord> {
+        self.db_manager.block_propagation_record_from_db(hash)
+    }
+
+    pub fn remove_block_propagation_record(&self, hash: &H256) {
+        self.db_manager
+            .remove_block_propagation_record_from_db(hash);
+    }
+
+    pub fn insert_pivot_switch_event(&self, event: &PivotSwitchEvent) {
+        self.db_manager.insert_pivot_switch_event_to_db(event);
+    }
+
+    pub fn pivot_switch_event(&self, seq: u64) -> Option<PivotSwitchEvent> {
+        self.db_manager.pivot_switch_event_from_db(seq)
+    }
+
+    pub fn insert_pivot_switch_next_seq(&self, next_seq: u64) {
+        self.db_manager.insert_pivot_switch_next_seq_to_db(next_seq);
+    }
+
+    pub fn pivot_switch_next_seq(&self) -> Option<u64> {
+        self.db_manager.pivot_switch_next_seq_from_db()
+    }
+
+    pub fn insert_local_nonce_record(
+        &self, address: &Address, record: &LocalNonceRecord,
+    ) {
+        self.db_manager
+            .insert_local_nonce_record_to_db(address, record);
+    }
+
+    pub fn local_nonce_record(
+        &self, address: &Address,
+    ) -> Option<LocalNonceRecord> {
+        self.db_manager.local_nonce_record_from_db(address)
+    }
+
+    pub fn insert_block_propagation_next_seq(&self, next_seq: u64) {
+        self.db_manager
+            .insert_block_propagation_next_seq_to_db(next_seq);
+    }
+
+    pub fn block_propagation_next_seq(&self) -> Option<u64> {
+        self.db_manager.block_propagation_next_seq_from_db()
+    }
+
+    pub fn insert_block_propagation_slot(&self, slot: u64, hash: &H256) {
+        self.db_manager
+            .insert_block_propagation_slot_to_db(slot, hash);
+    }
+
+    pub fn block_propagation_slot(&self, slot: u64) -> Option<H256> {
+        self.db_manager.block_propagation_slot_from_db(slot)
+    }
+
     pub fn epoch_executed(&self, epoch_hash: &H256) -> bool {
         // `block_receipts_root` is not computed when recovering from db
         self.get_epoch_execution_commitment(epoch_hash).is_some()
@@ -1281,6 +2099,83 @@ impl BlockDataManager {
         self.invalid_block_set.write().insert(block_hash);
     }
 
+    /// Mark a block removed from the sync graph because it was not ready for
+    /// too long. The block is not marked if it has other local info, e.g. it
+    /// has entered consensus in a previous run.
+    pub fn mark_block_expired(&self, block_hash: &H256) {
+        let expirable = self
+            .local_block_info_by_hash(block_hash)
+            .map(|info| info.get_status() == BlockStatus::Expired)
+            .unwrap_or(true);
+        if expirable {
+            let block_info =
+                LocalBlockInfo::new(BlockStatus::Expired, NULLU64, NULLU64);
+            self.insert_local_block_info(block_hash, block_info);
+        }
+    }
+
+    /// Remove the expired mark of a block that arrives again, so it is
+    /// processed as a new block.
+    pub fn clear_block_expired(&self, block_hash: &H256) {
+        self.local_block_info.write().remove(block_hash);
+        self.db_manager.remove_local_block_info_from_db(block_hash);
+    }
+
+    /// Persist the blocks that are known to be invalid but not yet removed,
+    /// so their removal can be resumed after a restart.
+    pub fn insert_pending_invalid_blocks(&self, hashes: &Vec<H256>) {
+        self.db_manager.insert_pending_invalid_blocks_to_db(hashes);
+    }
+
+    /// Mark the blocks left pending by `insert_pending_invalid_blocks` before
+    /// the last shutdown as invalid and remove them. Return the number of the
+    /// blocks.
+    pub fn remove_pending_invalid_blocks_from_db(&self) -> usize {
+        let hashes = self
+            .db_manager
+            .pending_invalid_blocks_from_db()
+            .unwrap_or_default();
+        for hash in &hashes {
+            self.invalidate_block(*hash);
+            self.remove_useless_block(hash, true /* remove_db */);
+        }
+        if !hashes.is_empty() {
+            self.db_manager
+                .insert_pending_invalid_blocks_to_db(&Vec::new());
+        }
+        hashes.len()
+    }
+
+    /// Record the pending headers moved out of the sync graph that wait for
+    /// the header of `parent_hash`. They are added to the recorded ones if
+    /// `append` is set, otherwise the records left before, e.g. by a previous
+    /// run, are overwritten. The headers themselves are already persisted.
+    pub fn insert_spilled_headers(
+        &self, parent_hash: &H256, hashes: &Vec<H256>, append: bool,
+    ) {
+        let mut all_hashes = if append {
+            self.db_manager
+                .spilled_headers_from_db(parent_hash)
+                .unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+        all_hashes.extend_from_slice(hashes);
+        self.db_manager
+            .insert_spilled_headers_to_db(parent_hash, &all_hashes);
+    }
+
+    /// Remove and return the pending headers recorded by
+    /// `insert_spilled_headers` for `parent_hash`.
+    pub fn take_spilled_headers(&self, parent_hash: &H256) -> Vec<H256> {
+        let hashes = self
+            .db_manager
+            .spilled_headers_from_db(parent_hash)
+            .unwrap_or_default();
+        self.db_manager.remove_spilled_headers_from_db(parent_hash);
+        hashes
+    }
+
     /// Check if a block is already marked as invalid.
     pub fn verified_invalid(
         &self, block_hash: &H256,
@@ -1458,6 +2353,13 @@ impl BlockDataManager {
         self.tx_data_manager.recover_block(block)
     }
 
+    pub fn recover_unsigned_tx_each(
+        &self, transactions: &Vec<TransactionWithSignature>,
+    ) -> Vec<Result<Arc<SignedTransaction>, DecoderError>> {
+        let _timer = MeterTimer::time_func(TX_POOL_RECOVER_TIMER.as_ref());
+        self.tx_data_manager.recover_unsigned_tx_each(transactions)
+    }
+
     pub fn recover_unsigned_tx_with_order(
         &self, transactions: &Vec<TransactionWithSignature>,
     ) -> Result<Vec<Arc<SignedTransaction>>, DecoderError> {
@@ -1562,6 +2464,7 @@ impl BlockDataManager {
     pub fn new_checkpoint(
         &self, new_checkpoint_height: u64, best_epoch_number: u64,
     ) {
+        self.unpin_block_headers_before(new_checkpoint_height);
         let mut gc_progress = self.gc_progress.lock();
         gc_progress.gc_end = new_checkpoint_height;
         gc_progress.last_consensus_best_epoch = best_epoch_number;
@@ -1596,6 +2499,11 @@ impl BlockDataManager {
         {
             if base_epoch > defer_epochs as u64 {
                 let epoch_to_remove = base_epoch - defer_epochs as u64;
+                // The index by senders is built from the transaction index,
+                // so they are kept for the same epochs.
+                if self.config.index_transactions_by_sender {
+                    self.remove_sender_transaction_index(epoch_to_remove);
+                }
                 match self.all_epoch_set_hashes_from_db(epoch_to_remove) {
                     None => warn!(
                         "GC epoch set is missing! epoch_to_remove: {}",
@@ -1656,7 +2564,10 @@ impl BlockDataManager {
         self.gc_epoch_with_defer(
             base_epoch,
             self.config.additional_maintained_reward_epoch_count,
-            |h| self.db_manager.remove_block_reward_result_from_db(h),
+            |h| {
+                self.db_manager.remove_block_reward_result_from_db(h);
+                self.db_manager.remove_block_reward_details_from_db(h);
+            },
         );
         self.gc_epoch_with_defer(
             base_epoch,
@@ -1705,6 +2616,12 @@ pub struct DataManagerConfiguration {
     pub additional_maintained_transaction_index_epoch_count: Option<usize>,
     pub checkpoint_gc_time_in_epoch_count: usize,
     pub strict_tx_index_gc: bool,
+    /// The number of latest pivot block headers kept in memory.
+    pub pinned_pivot_header_count: usize,
+    /// Whether to index the transactions executed on the local pivot chain
+    /// by their senders. The index is garbage collected with the transaction
+    /// index.
+    pub index_transactions_by_sender: bool,
 }
 
 impl MallocSizeOf for DataManagerConfiguration {
@@ -1729,6 +2646,8 @@ impl DataManagerConfiguration {
             additional_maintained_transaction_index_epoch_count: None,
             checkpoint_gc_time_in_epoch_count: 1,
             strict_tx_index_gc: true,
+            pinned_pivot_header_count: 0,
+            index_transactions_by_sender: false,
         }
     }
 }
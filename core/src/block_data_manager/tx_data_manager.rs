@@ -147,6 +147,25 @@ impl TransactionDataManager {
             .collect())
     }
 
+    /// Recover public keys for `transactions` and keep the order. All
+    /// transactions are recovered in one batch first, and only if some of
+    /// them cannot be recovered, they are recovered one by one to find out
+    /// which ones are invalid.
+    pub fn recover_unsigned_tx_each(
+        &self, transactions: &Vec<TransactionWithSignature>,
+    ) -> Vec<Result<Arc<SignedTransaction>, DecoderError>> {
+        match self.recover_unsigned_tx_with_order(transactions) {
+            Ok(signed_trans) => signed_trans.into_iter().map(Ok).collect(),
+            Err(_) => transactions
+                .iter()
+                .map(|tx| {
+                    self.recover_unsigned_tx_with_order(&vec![tx.clone()])
+                        .map(|mut signed| signed.pop().expect("recovered"))
+                })
+                .collect(),
+        }
+    }
+
     /// Recover public key for `uncached_trans` and keep the corresponding index
     /// unchanged.
     ///
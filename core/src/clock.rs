@@ -0,0 +1,81 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// The source of wall-clock time for components that compare block
+/// timestamps or expire data by time.
+pub trait Clock: Send + Sync {
+    /// The duration since the unix epoch.
+    fn now(&self) -> Duration;
+
+    fn now_as_secs(&self) -> u64 { self.now().as_secs() }
+}
+
+pub type SharedClock = Arc<dyn Clock>;
+
+/// The clock backed by the system time.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("system time is after unix epoch")
+    }
+}
+
+pub fn system_clock() -> SharedClock { Arc::new(SystemClock) }
+
+/// A clock that only moves when it is told to. It is used in tests and
+/// simulations so that time-dependent behaviors are deterministic.
+pub struct ManualClock {
+    now_ms: AtomicU64,
+}
+
+impl ManualClock {
+    pub fn new(now: Duration) -> Self {
+        ManualClock {
+            now_ms: AtomicU64::new(now.as_millis() as u64),
+        }
+    }
+
+    pub fn set(&self, now: Duration) {
+        self.now_ms.store(now.as_millis() as u64, Ordering::SeqCst);
+    }
+
+    pub fn advance(&self, delta: Duration) {
+        self.now_ms
+            .fetch_add(delta.as_millis() as u64, Ordering::SeqCst);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        Duration::from_millis(self.now_ms.load(Ordering::SeqCst))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Clock, ManualClock};
+    use std::time::Duration;
+
+    #[test]
+    fn test_manual_clock() {
+        let clock = ManualClock::new(Duration::from_secs(100));
+        assert_eq!(clock.now_as_secs(), 100);
+        clock.advance(Duration::from_millis(1500));
+        assert_eq!(clock.now(), Duration::from_millis(101_500));
+        assert_eq!(clock.now_as_secs(), 101);
+        clock.set(Duration::from_secs(10));
+        assert_eq!(clock.now_as_secs(), 10);
+    }
+}
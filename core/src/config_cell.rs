@@ -0,0 +1,37 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use parking_lot::RwLock;
+use std::fmt::{Debug, Formatter};
+
+/// A configuration value that can be changed while the node is running. The
+/// components holding it read the value every time it is used, so a new
+/// value takes effect from the next use.
+pub struct ConfigCell<T> {
+    value: RwLock<T>,
+}
+
+impl<T: Copy> ConfigCell<T> {
+    pub fn new(value: T) -> Self {
+        ConfigCell {
+            value: RwLock::new(value),
+        }
+    }
+
+    pub fn get(&self) -> T { *self.value.read() }
+
+    /// Set the value and return the old one.
+    pub fn set(&self, value: T) -> T {
+        std::mem::replace(&mut *self.value.write(), value)
+    }
+}
+
+/// The clone is a separate cell with the current value.
+impl<T: Copy> Clone for ConfigCell<T> {
+    fn clone(&self) -> Self { ConfigCell::new(self.get()) }
+}
+
+impl<T: Copy + Debug> Debug for ConfigCell<T> {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result { self.get().fmt(f) }
+}
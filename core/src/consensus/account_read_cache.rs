@@ -0,0 +1,119 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfx_types::{Address, H256};
+use lru_time_cache::LruCache;
+use parking_lot::Mutex;
+use primitives::Account;
+
+/// The cache is split by address, so the concurrent reads of different
+/// accounts rarely wait for each other.
+const SHARD_COUNT: usize = 16;
+
+struct CachedAccount {
+    epoch_height: u64,
+    /// `None` if the account does not exist in the state.
+    account: Option<Account>,
+}
+
+/// `AccountReadCache` keeps the account records read from the states of the
+/// pivot blocks by the RPCs, keyed by the pivot block hash and the address.
+/// The state of a pivot block never changes, so a record is never wrong for
+/// its key, and the records of the pivot blocks switched out of the pivot
+/// chain are only dropped to free the space.
+pub struct AccountReadCache {
+    shards: Vec<Mutex<LruCache<(H256, Address), CachedAccount>>>,
+}
+
+impl AccountReadCache {
+    pub fn new(capacity: usize) -> Self {
+        let shard_capacity = (capacity / SHARD_COUNT).max(1);
+        AccountReadCache {
+            shards: (0..SHARD_COUNT)
+                .map(|_| Mutex::new(LruCache::with_capacity(shard_capacity)))
+                .collect(),
+        }
+    }
+
+    /// Return `Some(None)` if the account is cached as not existing, and
+    /// `None` if it is not cached.
+    pub fn get(
+        &self, pivot_hash: &H256, address: &Address,
+    ) -> Option<Option<Account>> {
+        self.shard(address)
+            .lock()
+            .get(&(*pivot_hash, *address))
+            .map(|cached| cached.account.clone())
+    }
+
+    pub fn insert(
+        &self, pivot_hash: H256, epoch_height: u64, address: Address,
+        account: Option<Account>,
+    )
+    {
+        self.shard(&address).lock().insert(
+            (pivot_hash, address),
+            CachedAccount {
+                epoch_height,
+                account,
+            },
+        );
+    }
+
+    /// Drop the records of the epochs after `fork_height`, whose pivot blocks
+    /// may have been switched out of the pivot chain.
+    pub fn invalidate_after(&self, fork_height: u64) {
+        for shard in &self.shards {
+            let mut shard = shard.lock();
+            let stale: Vec<_> = shard
+                .peek_iter()
+                .filter(|(_, cached)| cached.epoch_height > fork_height)
+                .map(|(key, _)| *key)
+                .collect();
+            for key in stale {
+                shard.remove(&key);
+            }
+        }
+    }
+
+    fn shard(
+        &self, address: &Address,
+    ) -> &Mutex<LruCache<(H256, Address), CachedAccount>> {
+        &self.shards[address.as_bytes()[19] as usize % SHARD_COUNT]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AccountReadCache;
+    use cfx_types::{Address, H256};
+    use primitives::Account;
+
+    #[test]
+    fn test_invalidate_after_fork() {
+        let cache = AccountReadCache::new(64);
+        let address = Address::random();
+        let mut account = Account::default();
+        account.balance = 100.into();
+        for height in 1..=3 {
+            cache.insert(
+                H256::from_low_u64_be(height),
+                height,
+                address,
+                Some(account.clone()),
+            );
+        }
+        cache.insert(H256::from_low_u64_be(3), 3, Address::random(), None);
+        assert_eq!(
+            cache.get(&H256::from_low_u64_be(2), &address),
+            Some(Some(account.clone()))
+        );
+
+        // The pivot chain switches after epoch 1.
+        cache.invalidate_after(1);
+        assert!(cache.get(&H256::from_low_u64_be(1), &address).is_some());
+        assert!(cache.get(&H256::from_low_u64_be(2), &address).is_none());
+        assert!(cache.get(&H256::from_low_u64_be(3), &address).is_none());
+    }
+}
@@ -2,6 +2,7 @@
 // Conflux is free software and distributed under GNU General Public License.
 // See http://www.gnu.org/licenses/
 
+use super::anticone_set::AnticoneSet;
 use hibitset::{BitSet, BitSetLike};
 use malloc_size_of::{MallocSizeOf, MallocSizeOfOps};
 use std::{
@@ -10,15 +11,16 @@ use std::{
 };
 
 const CACHE_INDEX_STRIDE: usize = 1000;
-const MAX_ANTICONE_SIZE: usize = 300;
+const MAX_ANTICONE_ENTRY_COUNT: usize = 300;
 
 /// AnticoneCache keeps only the anticone set of the recent CACHE_INDEX_STRIDE
-/// blocks. It also removes a block anticone set from it if the set is larger
-/// than MAX_ANTICONE_SIZE
+/// blocks. It also removes a block anticone set from it if the set takes more
+/// than MAX_ANTICONE_ENTRY_COUNT entries. Large anticone sets are compressed,
+/// so they are still cached if the blocks in them are mostly consecutive.
 pub struct AnticoneCache {
     max_seen_index: usize,
     seq_number: u64,
-    data: HashMap<usize, (HashSet<usize>, u64)>,
+    data: HashMap<usize, (AnticoneSet, u64)>,
 }
 
 impl MallocSizeOf for AnticoneCache {
@@ -39,13 +41,10 @@ impl AnticoneCache {
     pub fn update(&mut self, me: usize, anticone: &BitSet) {
         self.seq_number += 1;
         self.max_seen_index = max(self.max_seen_index, me);
-        // BitSet does not have len() method
-        if anticone.len() < MAX_ANTICONE_SIZE {
-            let mut tmp = HashSet::new();
-            for index in anticone.iter() {
-                tmp.insert(index as usize);
-            }
-            self.data.insert(me, (tmp, self.seq_number));
+        if let Some(anticone_set) =
+            AnticoneSet::from_bitset(anticone, MAX_ANTICONE_ENTRY_COUNT - 1)
+        {
+            self.data.insert(me, (anticone_set, self.seq_number));
         }
 
         if anticone.len() < self.data.len() {
@@ -54,7 +53,7 @@ impl AnticoneCache {
                 if self.data.contains_key(&index_usize) {
                     let s = &mut self.data.get_mut(&index_usize).unwrap().0;
                     s.insert(me);
-                    if s.len() > MAX_ANTICONE_SIZE {
+                    if s.entry_count() > MAX_ANTICONE_ENTRY_COUNT {
                         self.data.remove(&index_usize);
                     }
                 }
@@ -71,13 +70,13 @@ impl AnticoneCache {
                 if anticone.contains(*k as u32) {
                     v.0.insert(me);
                 }
-                (v.0.len() <= MAX_ANTICONE_SIZE)
+                (v.0.entry_count() <= MAX_ANTICONE_ENTRY_COUNT)
                     && (seq_number - v.1 <= CACHE_INDEX_STRIDE as u64)
             });
         }
     }
 
-    pub fn get(&self, me: usize) -> Option<&HashSet<usize>> {
+    pub fn get(&self, me: usize) -> Option<&AnticoneSet> {
         if let Some(v) = self.data.get(&me) {
             Some(&v.0)
         } else {
@@ -85,10 +84,12 @@ impl AnticoneCache {
         }
     }
 
+    pub fn remove(&mut self, me: usize) { self.data.remove(&me); }
+
     pub fn intersect_update(&mut self, era_blockset: &HashSet<usize>) {
         let seq_number = self.seq_number;
         self.data.retain(|_, (s, seq)| {
-            s.retain(|v| era_blockset.contains(v));
+            s.retain(|v| era_blockset.contains(&v));
             seq_number - *seq <= CACHE_INDEX_STRIDE as u64
         });
     }
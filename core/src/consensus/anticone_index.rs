@@ -0,0 +1,77 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use malloc_size_of::{MallocSizeOf, MallocSizeOfOps};
+use std::collections::{BTreeSet, HashMap};
+
+/// AnticoneIndex indexes the blocks of the current era by their epoch numbers.
+///
+/// The anticone of a block only contains the blocks in the epochs after the
+/// last pivot block in its past, and the blocks not in any epoch yet, whose
+/// epoch number is `NULLU64`. When the anticone of the parent is not in
+/// `AnticoneCache`, the candidates are enumerated from this index, so the cost
+/// is bounded by the recent epochs instead of the whole arena.
+#[derive(Default)]
+pub struct AnticoneIndex {
+    by_epoch: BTreeSet<(u64, usize)>,
+    epoch_of: HashMap<usize, u64>,
+}
+
+impl MallocSizeOf for AnticoneIndex {
+    fn size_of(&self, ops: &mut MallocSizeOfOps) -> usize {
+        // `BTreeSet` is not supported, so its size is estimated.
+        self.by_epoch.len() * std::mem::size_of::<(u64, usize)>()
+            + self.epoch_of.size_of(ops)
+    }
+}
+
+impl AnticoneIndex {
+    pub fn set_epoch_number(&mut self, index: usize, epoch_number: u64) {
+        if let Some(old) = self.epoch_of.insert(index, epoch_number) {
+            self.by_epoch.remove(&(old, index));
+        }
+        self.by_epoch.insert((epoch_number, index));
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        if let Some(old) = self.epoch_of.remove(&index) {
+            self.by_epoch.remove(&(old, index));
+        }
+    }
+
+    /// Return the blocks whose epoch numbers are larger than `epoch_number`.
+    pub fn blocks_after(
+        &self, epoch_number: u64,
+    ) -> impl Iterator<Item = usize> + '_ {
+        let start = epoch_number.saturating_add(1);
+        self.by_epoch
+            .range((start, 0)..)
+            .filter(move |(epoch, _)| *epoch > epoch_number)
+            .map(|(_, index)| *index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AnticoneIndex;
+    use cfx_parameters::consensus::NULLU64;
+
+    #[test]
+    fn test_blocks_after() {
+        let mut index = AnticoneIndex::default();
+        index.set_epoch_number(0, 1);
+        index.set_epoch_number(1, 2);
+        index.set_epoch_number(2, NULLU64);
+        index.set_epoch_number(3, 3);
+        let mut after: Vec<_> = index.blocks_after(1).collect();
+        after.sort();
+        assert_eq!(after, vec![1, 2, 3]);
+
+        // A pivot chain switch moves the block to another epoch.
+        index.set_epoch_number(3, 1);
+        index.remove(2);
+        assert_eq!(index.blocks_after(1).collect::<Vec<_>>(), vec![1]);
+        assert!(index.blocks_after(NULLU64).next().is_none());
+    }
+}
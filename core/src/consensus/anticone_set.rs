@@ -0,0 +1,212 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use hibitset::{BitSet, BitSetLike};
+use malloc_size_of_derive::MallocSizeOf as DeriveMallocSizeOf;
+use std::collections::HashSet;
+
+/// The anticone sets with more blocks are kept as ranges.
+const SPARSE_ANTICONE_MAX_SIZE: usize = 64;
+
+/// A set of arena indices in an anticone. Small sets are kept in a hash set.
+/// Larger ones are kept as the ranges of consecutive indices, because the
+/// blocks withheld and released together, which make up most of a large
+/// anticone, are inserted into the arena one after another.
+#[derive(Clone, Debug, DeriveMallocSizeOf)]
+pub enum AnticoneSet {
+    Sparse(HashSet<usize>),
+    /// The sorted, disjoint and non-adjacent ranges `[start, end)`, and the
+    /// number of indices in them.
+    Ranges {
+        ranges: Vec<(usize, usize)>,
+        len: usize,
+    },
+}
+
+impl AnticoneSet {
+    /// Convert `bitset` unless it needs more than `max_entry_count` entries.
+    pub fn from_bitset(
+        bitset: &BitSet, max_entry_count: usize,
+    ) -> Option<Self> {
+        let mut sparse = HashSet::new();
+        for index in bitset.iter() {
+            sparse.insert(index as usize);
+            if sparse.len() > SPARSE_ANTICONE_MAX_SIZE {
+                break;
+            }
+        }
+        if sparse.len() <= SPARSE_ANTICONE_MAX_SIZE {
+            return if sparse.len() <= max_entry_count {
+                Some(AnticoneSet::Sparse(sparse))
+            } else {
+                None
+            };
+        }
+        // `BitSet` iterates in the ascending order.
+        let mut ranges = Vec::new();
+        let mut len = 0;
+        for index in bitset.iter() {
+            let index = index as usize;
+            match ranges.last_mut() {
+                Some((_, end)) if *end == index => *end += 1,
+                _ => {
+                    if ranges.len() == max_entry_count {
+                        return None;
+                    }
+                    ranges.push((index, index + 1));
+                }
+            }
+            len += 1;
+        }
+        Some(AnticoneSet::Ranges { ranges, len })
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            AnticoneSet::Sparse(set) => set.len(),
+            AnticoneSet::Ranges { len, .. } => *len,
+        }
+    }
+
+    /// The number of the indices or the ranges kept, which the memory usage
+    /// is proportional to.
+    pub fn entry_count(&self) -> usize {
+        match self {
+            AnticoneSet::Sparse(set) => set.len(),
+            AnticoneSet::Ranges { ranges, .. } => ranges.len(),
+        }
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        match self {
+            AnticoneSet::Sparse(set) => set.contains(&index),
+            AnticoneSet::Ranges { ranges, .. } => {
+                Self::find_range(ranges, index).is_ok()
+            }
+        }
+    }
+
+    pub fn insert(&mut self, index: usize) -> bool {
+        match self {
+            AnticoneSet::Sparse(set) => {
+                if !set.insert(index) {
+                    return false;
+                }
+                if set.len() > SPARSE_ANTICONE_MAX_SIZE {
+                    let mut indices: Vec<usize> = set.drain().collect();
+                    indices.sort();
+                    *self = Self::from_sorted(indices);
+                }
+                true
+            }
+            AnticoneSet::Ranges { ranges, len } => {
+                let pos = match Self::find_range(ranges, index) {
+                    Ok(_) => return false,
+                    Err(pos) => pos,
+                };
+                let joins_prev = pos > 0 && ranges[pos - 1].1 == index;
+                let joins_next =
+                    pos < ranges.len() && ranges[pos].0 == index + 1;
+                match (joins_prev, joins_next) {
+                    (true, true) => {
+                        ranges[pos - 1].1 = ranges[pos].1;
+                        ranges.remove(pos);
+                    }
+                    (true, false) => ranges[pos - 1].1 += 1,
+                    (false, true) => ranges[pos].0 = index,
+                    (false, false) => ranges.insert(pos, (index, index + 1)),
+                }
+                *len += 1;
+                true
+            }
+        }
+    }
+
+    pub fn retain<F: FnMut(usize) -> bool>(&mut self, mut f: F) {
+        if let AnticoneSet::Sparse(set) = self {
+            set.retain(|index| f(*index));
+            return;
+        }
+        let indices = self.iter().filter(|index| f(*index)).collect();
+        *self = Self::from_sorted(indices);
+    }
+
+    /// Iterate over the indices. The order is ascending for the ranges and
+    /// arbitrary for the small sets.
+    pub fn iter<'a>(&'a self) -> Box<dyn Iterator<Item = usize> + 'a> {
+        match self {
+            AnticoneSet::Sparse(set) => Box::new(set.iter().cloned()),
+            AnticoneSet::Ranges { ranges, .. } => {
+                Box::new(ranges.iter().flat_map(|(start, end)| *start..*end))
+            }
+        }
+    }
+
+    fn from_sorted(indices: Vec<usize>) -> Self {
+        if indices.len() <= SPARSE_ANTICONE_MAX_SIZE {
+            return AnticoneSet::Sparse(indices.into_iter().collect());
+        }
+        let len = indices.len();
+        let mut ranges: Vec<(usize, usize)> = Vec::new();
+        for index in indices {
+            match ranges.last_mut() {
+                Some((_, end)) if *end == index => *end += 1,
+                _ => ranges.push((index, index + 1)),
+            }
+        }
+        AnticoneSet::Ranges { ranges, len }
+    }
+
+    /// Return the position of the range containing `index`, or the position
+    /// to insert a range starting at `index`.
+    fn find_range(
+        ranges: &Vec<(usize, usize)>, index: usize,
+    ) -> Result<usize, usize> {
+        ranges.binary_search_by(|(start, end)| {
+            if index < *start {
+                std::cmp::Ordering::Greater
+            } else if index >= *end {
+                std::cmp::Ordering::Less
+            } else {
+                std::cmp::Ordering::Equal
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AnticoneSet, SPARSE_ANTICONE_MAX_SIZE};
+    use hibitset::BitSet;
+
+    #[test]
+    fn test_anticone_ranges() {
+        let mut bitset = BitSet::new();
+        for index in (100..200).chain(300..310) {
+            bitset.add(index);
+        }
+        assert!(AnticoneSet::from_bitset(&bitset, 1).is_none());
+        let mut set = AnticoneSet::from_bitset(&bitset, 2).unwrap();
+        assert_eq!((set.len(), set.entry_count()), (110, 2));
+        assert!(set.contains(199) && !set.contains(200));
+
+        // Fill the gap between the ranges.
+        assert!(set.insert(250));
+        assert!(!set.insert(250));
+        for index in (200..250).chain(251..300) {
+            set.insert(index);
+        }
+        assert_eq!((set.len(), set.entry_count()), (210, 1));
+        assert_eq!(
+            set.iter().collect::<Vec<_>>(),
+            (100..310).collect::<Vec<_>>()
+        );
+
+        set.retain(|index| index % 2 == 0);
+        assert_eq!((set.len(), set.entry_count()), (105, 105));
+        set.retain(|index| index < 100 + 2 * SPARSE_ANTICONE_MAX_SIZE);
+        assert!(matches!(set, AnticoneSet::Sparse(_)));
+        assert_eq!(set.len(), SPARSE_ANTICONE_MAX_SIZE);
+    }
+}
@@ -0,0 +1,110 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfx_types::H256;
+use std::{
+    convert::TryInto,
+    sync::atomic::{fence, AtomicU64, Ordering},
+};
+
+/// The best block hash and the best epoch number, read without any lock.
+///
+/// It is a sequence lock over atomic words. The writer makes `seq` odd while
+/// it changes the words, and a reader retries if `seq` is odd or changes
+/// during its read, so the hash and the epoch number read are always from
+/// the same update. Like `BestInformation`, the values may be older than the
+/// consensus graph.
+#[derive(Default)]
+pub struct BestHead {
+    seq: AtomicU64,
+    block_hash: [AtomicU64; 4],
+    epoch_number: AtomicU64,
+}
+
+impl BestHead {
+    /// Only one thread may store at a time.
+    pub fn store(&self, block_hash: &H256, epoch_number: u64) {
+        let seq = self.seq.load(Ordering::Relaxed);
+        self.seq.store(seq.wrapping_add(1), Ordering::Relaxed);
+        fence(Ordering::Release);
+        for (word, chunk) in
+            self.block_hash.iter().zip(block_hash.as_bytes().chunks(8))
+        {
+            word.store(
+                u64::from_le_bytes(chunk.try_into().expect("8 bytes")),
+                Ordering::Relaxed,
+            );
+        }
+        self.epoch_number.store(epoch_number, Ordering::Relaxed);
+        self.seq.store(seq.wrapping_add(2), Ordering::Release);
+    }
+
+    pub fn load(&self) -> (H256, u64) {
+        loop {
+            let seq = self.seq.load(Ordering::Acquire);
+            if seq % 2 == 1 {
+                std::hint::spin_loop();
+                continue;
+            }
+            let mut bytes = [0u8; 32];
+            for (word, chunk) in self.block_hash.iter().zip(bytes.chunks_mut(8))
+            {
+                chunk.copy_from_slice(
+                    &word.load(Ordering::Relaxed).to_le_bytes(),
+                );
+            }
+            let epoch_number = self.epoch_number.load(Ordering::Relaxed);
+            fence(Ordering::Acquire);
+            if self.seq.load(Ordering::Relaxed) == seq {
+                return (H256::from(bytes), epoch_number);
+            }
+        }
+    }
+
+    pub fn block_hash(&self) -> H256 { self.load().0 }
+
+    pub fn epoch_number(&self) -> u64 { self.load().1 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BestHead;
+    use cfx_types::H256;
+    use std::{sync::Arc, thread};
+
+    #[test]
+    fn test_store_and_load() {
+        let head = BestHead::default();
+        assert_eq!(head.load(), (H256::zero(), 0));
+        let hash = H256::from_low_u64_be(0x0102_0304_0506_0708);
+        head.store(&hash, 10);
+        assert_eq!(head.block_hash(), hash);
+        assert_eq!(head.epoch_number(), 10);
+    }
+
+    #[test]
+    fn test_concurrent_loads_are_consistent() {
+        let head = Arc::new(BestHead::default());
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let head = head.clone();
+                thread::spawn(move || {
+                    for _ in 0..10000 {
+                        let (hash, epoch_number) = head.load();
+                        assert_eq!(hash, H256::repeat_byte(epoch_number as u8));
+                    }
+                })
+            })
+            .collect();
+        for epoch_number in 0..10000u64 {
+            head.store(
+                &H256::repeat_byte(epoch_number as u8),
+                epoch_number % 256,
+            );
+        }
+        for reader in readers {
+            reader.join().unwrap();
+        }
+    }
+}
@@ -0,0 +1,189 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::{pow::PowComputer, verification::VerificationConfig};
+use cfx_types::{H256, U256};
+use primitives::BlockHeader;
+use rlp_derive::{RlpDecodable, RlpEncodable};
+
+/// The maximum number of pivot headers in one proof. A longer segment is
+/// proven with several proofs, each anchored at the last header of the
+/// previous one.
+pub const MAX_CHAIN_WEIGHT_PROOF_HEADERS: u64 = 10_000;
+
+/// A proof that a pivot segment carries a cumulative weight, for the relays of
+/// light bridges that only trust an anchor block, e.g. an era genesis
+/// published as a checkpoint.
+///
+/// The proof is the header chain from the anchor, so its size is linear in
+/// the length of the segment. The weight of a header is its difficulty, which
+/// only counts if the proof of work of the header meets it.
+#[derive(Clone, Debug, RlpEncodable, RlpDecodable)]
+pub struct ChainWeightProof {
+    pub anchor_hash: H256,
+    pub anchor_height: u64,
+    /// The pivot headers after the anchor in ascending height order.
+    pub headers: Vec<BlockHeader>,
+}
+
+/// The pivot segment proven by a `ChainWeightProof`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProvenChainWeight {
+    pub end_hash: H256,
+    pub end_height: u64,
+    /// The total difficulty of the headers after the anchor.
+    pub cumulative_weight: U256,
+}
+
+impl ChainWeightProof {
+    /// Verify the proof against a trusted anchor. The anchor height must be
+    /// an era boundary. Return the proven segment if it links to the anchor
+    /// and carries at least `min_weight`.
+    pub fn verify(
+        &self, pow: &PowComputer, era_epoch_count: u64, trusted_anchor: &H256,
+        min_weight: &U256,
+    ) -> Result<ProvenChainWeight, String>
+    {
+        if self.anchor_hash != *trusted_anchor {
+            return Err(format!(
+                "untrusted anchor {:?}, expected {:?}",
+                self.anchor_hash, trusted_anchor
+            ));
+        }
+        if self.anchor_height % era_epoch_count != 0 {
+            return Err(format!(
+                "anchor height {} is not an era boundary",
+                self.anchor_height
+            ));
+        }
+        if self.headers.is_empty() {
+            return Err("no header in proof".into());
+        }
+        if self.headers.len() as u64 > MAX_CHAIN_WEIGHT_PROOF_HEADERS {
+            return Err(format!(
+                "too many headers in proof: {}",
+                self.headers.len()
+            ));
+        }
+
+        let mut parent_hash = self.anchor_hash;
+        let mut height = self.anchor_height;
+        let mut cumulative_weight = U256::zero();
+        for header in &self.headers {
+            height += 1;
+            if *header.parent_hash() != parent_hash {
+                return Err(format!(
+                    "header {:?} does not link to {:?}",
+                    header.hash(),
+                    parent_hash
+                ));
+            }
+            if header.height() != height {
+                return Err(format!(
+                    "header {:?} has height {}, expected {}",
+                    header.hash(),
+                    header.height(),
+                    height
+                ));
+            }
+            let difficulty = header.difficulty();
+            if difficulty.is_zero()
+                || VerificationConfig::get_or_compute_header_pow_quality(
+                    pow, header,
+                ) < *difficulty
+            {
+                return Err(format!(
+                    "header {:?} does not meet its difficulty",
+                    header.hash()
+                ));
+            }
+            cumulative_weight = cumulative_weight
+                .checked_add(*difficulty)
+                .ok_or_else(|| "cumulative weight overflows".to_string())?;
+            parent_hash = header.hash();
+        }
+
+        if cumulative_weight < *min_weight {
+            return Err(format!(
+                "insufficient weight {}, expected at least {}",
+                cumulative_weight, min_weight
+            ));
+        }
+        Ok(ProvenChainWeight {
+            end_hash: parent_hash,
+            end_height: height,
+            cumulative_weight,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ChainWeightProof;
+    use crate::pow::PowComputer;
+    use cfx_types::{H256, U256};
+    use primitives::{BlockHeader, BlockHeaderBuilder};
+
+    const ERA_EPOCH_COUNT: u64 = 100;
+
+    fn new_proof(anchor_hash: H256, count: u64) -> ChainWeightProof {
+        let mut headers: Vec<BlockHeader> = Vec::new();
+        let mut parent_hash = anchor_hash;
+        for height in ERA_EPOCH_COUNT + 1..=ERA_EPOCH_COUNT + count {
+            let header = BlockHeaderBuilder::new()
+                .with_parent_hash(parent_hash)
+                .with_height(height)
+                .with_difficulty(U256::one())
+                .build();
+            parent_hash = header.hash();
+            headers.push(header);
+        }
+        ChainWeightProof {
+            anchor_hash,
+            anchor_height: ERA_EPOCH_COUNT,
+            headers,
+        }
+    }
+
+    #[test]
+    fn test_verify_chain_weight() {
+        let pow = PowComputer::new(false);
+        let anchor = H256::from_low_u64_be(1);
+        let proof = new_proof(anchor, 5);
+        let proven = proof
+            .verify(&pow, ERA_EPOCH_COUNT, &anchor, &U256::from(5))
+            .unwrap();
+        assert_eq!(proven.end_height, ERA_EPOCH_COUNT + 5);
+        assert_eq!(proven.end_hash, proof.headers[4].hash());
+        assert_eq!(proven.cumulative_weight, U256::from(5));
+
+        assert!(proof
+            .verify(&pow, ERA_EPOCH_COUNT, &anchor, &U256::from(6))
+            .is_err());
+    }
+
+    #[test]
+    fn test_reject_bad_anchor_or_link() {
+        let pow = PowComputer::new(false);
+        let anchor = H256::from_low_u64_be(1);
+        let proof = new_proof(anchor, 5);
+        assert!(proof
+            .verify(
+                &pow,
+                ERA_EPOCH_COUNT,
+                &H256::from_low_u64_be(2),
+                &U256::one()
+            )
+            .is_err());
+        assert!(proof
+            .verify(&pow, ERA_EPOCH_COUNT * 3, &anchor, &U256::one())
+            .is_err());
+
+        let mut broken = proof.clone();
+        broken.headers.remove(2);
+        assert!(broken
+            .verify(&pow, ERA_EPOCH_COUNT, &anchor, &U256::one())
+            .is_err());
+    }
+}
@@ -0,0 +1,54 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfx_types::H256;
+use parking_lot::Mutex;
+use std::{collections::HashMap, time::Duration};
+
+/// `ChaosHooks` lets integration tests perturb the consensus graph to
+/// reproduce rare interleavings deterministically, e.g. a pivot chain reorg
+/// while an epoch is being executed optimistically. All hooks are inactive
+/// unless set through the test RPCs.
+#[derive(Default)]
+pub struct ChaosHooks {
+    /// The extra time to wait before executing each epoch.
+    execution_delays: Mutex<HashMap<H256, Duration>>,
+    /// The pivot chain height from which the next activated block
+    /// re-evaluates the pivot chain.
+    forced_pivot_reevaluation_height: Mutex<Option<u64>>,
+}
+
+impl ChaosHooks {
+    /// Delay the execution of the epoch `epoch_hash` by `delay`, or remove
+    /// the delay if `delay` is `None`. The delay applies to every execution
+    /// of the epoch until it is removed.
+    pub fn set_execution_delay(
+        &self, epoch_hash: H256, delay: Option<Duration>,
+    ) {
+        let mut execution_delays = self.execution_delays.lock();
+        match delay {
+            Some(delay) => {
+                execution_delays.insert(epoch_hash, delay);
+            }
+            None => {
+                execution_delays.remove(&epoch_hash);
+            }
+        }
+    }
+
+    pub fn execution_delay(&self, epoch_hash: &H256) -> Option<Duration> {
+        self.execution_delays.lock().get(epoch_hash).cloned()
+    }
+
+    /// Make the next activated block handle the pivot chain as if it had
+    /// forked at `from_height`, so the pivot metadata is recomputed and the
+    /// epochs after it are executed again.
+    pub fn force_pivot_reevaluation(&self, from_height: u64) {
+        *self.forced_pivot_reevaluation_height.lock() = Some(from_height);
+    }
+
+    pub fn take_forced_pivot_reevaluation(&self) -> Option<u64> {
+        self.forced_pivot_reevaluation_height.lock().take()
+    }
+}
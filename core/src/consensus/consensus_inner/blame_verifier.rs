@@ -6,7 +6,7 @@ use crate::{
     block_data_manager::BlockDataManager, channel::Channel,
     consensus::consensus_inner::ConsensusGraphInner, Notifications,
 };
-use cfx_parameters::{consensus::*, light::BLAME_CHECK_OFFSET};
+use cfx_parameters::light::BLAME_CHECK_OFFSET;
 use primitives::BlockHeader;
 use std::{collections::VecDeque, sync::Arc};
 
@@ -61,8 +61,8 @@ impl BlameVerifier {
     fn header_from_height(
         &self, inner: &ConsensusGraphInner, height: u64,
     ) -> Option<Arc<BlockHeader>> {
-        let pivot_index = inner.height_to_pivot_index(height);
-        let pivot_arena_index = inner.pivot_chain[pivot_index];
+        let pivot_arena_index =
+            inner.checked_pivot_block_arena_index(height)?;
         let pivot_hash = inner.arena[pivot_arena_index].hash;
         self.data_man.block_header_by_hash(&pivot_hash)
     }
@@ -71,10 +71,7 @@ impl BlameVerifier {
         &self, inner: &ConsensusGraphInner, height: u64,
     ) -> Option<u64> {
         // check if `height` is available in memory
-        let pivot_index = match height {
-            h if h < inner.get_cur_era_genesis_height() => return None,
-            h => inner.height_to_pivot_index(h),
-        };
+        let pivot_index = inner.checked_height_to_pivot_index(height)?;
 
         inner
             .find_first_trusted_starting_from(
@@ -200,7 +197,9 @@ impl BlameVerifier {
         }
 
         // convert epoch number into pivot height
-        let height = epoch + DEFERRED_STATE_EPOCH_COUNT;
+        let height = inner
+            .deferred_state_epoch_count
+            .first_committing_height(epoch);
 
         // check blame
         debug!(
@@ -307,8 +306,10 @@ impl BlameVerifier {
                 self.blame_sender.send((height, Some(w)));
 
                 // skip all subsequent headers requested
-                assert!(w > DEFERRED_STATE_EPOCH_COUNT);
-                let witness_epoch = w - DEFERRED_STATE_EPOCH_COUNT;
+                let deferred_state_epoch_count =
+                    inner.deferred_state_epoch_count.at(w);
+                assert!(w > deferred_state_epoch_count);
+                let witness_epoch = w - deferred_state_epoch_count;
                 self.next_epoch_to_process = witness_epoch + 1;
             }
         }
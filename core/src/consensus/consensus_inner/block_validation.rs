@@ -0,0 +1,185 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use super::{weight::difficulty_to_weight, ConsensusGraphInner};
+use crate::block_data_manager::PartialInvalidCause;
+use cfx_parameters::consensus::NULL;
+use cfx_types::H256;
+use hibitset::BitSetLike;
+use primitives::BlockHeader;
+use std::iter;
+
+/// The result of comparing the blame and the deferred roots in a header with
+/// the local execution results.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DeferredRootsVerdict {
+    Matched,
+    Mismatched {
+        expected_blame: u32,
+        expected_state_root: H256,
+        expected_receipts_root: H256,
+        expected_logs_bloom_hash: H256,
+    },
+    /// The local execution results are not available, e.g. the deferred epoch
+    /// is not executed on this node.
+    Unavailable(String),
+}
+
+/// The verdict of `ConsensusGraph::validate_block`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlockValidationVerdict {
+    /// `None` if the block would be valid when it is inserted now. It is the
+    /// cause recorded in the local block info if the block is inserted.
+    pub partial_invalid_cause: Option<PartialInvalidCause>,
+    pub deferred_roots: DeferredRootsVerdict,
+}
+
+impl BlockValidationVerdict {
+    pub fn is_valid(&self) -> bool {
+        self.partial_invalid_cause.is_none()
+            && self.deferred_roots == DeferredRootsVerdict::Matched
+    }
+}
+
+impl ConsensusGraphInner {
+    /// Run the checks that decide whether a block is partially invalid for a
+    /// block that is not inserted, against the current graph.
+    ///
+    /// It only reads the graph. The subtree weights without the anticone of
+    /// the block are computed from scratch instead of being removed from the
+    /// link-cut trees, as in the checks of a block with a large anticone.
+    pub fn check_block_partial_validity(
+        &self, header: &BlockHeader,
+    ) -> Result<Option<PartialInvalidCause>, String> {
+        if self.hash_to_arena_indices.contains_key(&header.hash()) {
+            return Err("The block is already in the consensus graph".into());
+        }
+        let parent = *self
+            .hash_to_arena_indices
+            .get(header.parent_hash())
+            .ok_or("The parent is not in the consensus graph")?;
+        if self.arena[parent].era_block == NULL {
+            return Err("The parent is out of the current era".into());
+        }
+        let mut referees = Vec::with_capacity(header.referee_hashes().len());
+        for referee_hash in header.referee_hashes() {
+            let referee = *self
+                .hash_to_arena_indices
+                .get(referee_hash)
+                .ok_or_else(|| {
+                    format!(
+                        "Referee {:?} is not in the consensus graph",
+                        referee_hash
+                    )
+                })?;
+            referees.push(referee);
+        }
+        for index in iter::once(&parent).chain(referees.iter()) {
+            if self.arena[*index].data.inactive_dependency_cnt != 0 {
+                return Err(format!(
+                    "Block {:?} in the past view is not activated",
+                    self.arena[*index].hash
+                ));
+            }
+        }
+
+        let (anticone, anticone_barrier, epoch_set) =
+            self.compute_anticone_of_new_block(parent, &referees);
+        let timer_chain_tuple =
+            self.compute_timer_chain_tuple(parent, &referees, Some(&anticone));
+
+        let force_confirm =
+            self.compute_force_confirm(Some(&timer_chain_tuple));
+        if self.lca(parent, force_confirm) != force_confirm {
+            debug!(
+                "The parent is not in the subtree of the force confirmed block \
+                 {:?}",
+                self.arena[force_confirm].hash
+            );
+            return Ok(Some(PartialInvalidCause::IncorrectParent));
+        }
+
+        let subtree_weight =
+            self.compute_subtree_weights(NULL, &anticone_barrier);
+        // The candidates are the blocks in the epoch of the new block, and
+        // the ones in the epochs of the partially invalid or pending
+        // ancestors of `parent`, as in `check_correct_parent`.
+        let mut candidates: Vec<usize> =
+            epoch_set.iter().map(|index| index as usize).collect();
+        let mut p = parent;
+        while p != NULL
+            && (self.arena[p].data.partial_invalid
+                || self.arena[p].data.pending)
+        {
+            candidates
+                .extend(self.get_blockset_in_own_view_of_epoch(p)?.iter());
+            p = self.arena[p].parent;
+        }
+        if let Some(heavier_fork) = self.find_heavier_fork(
+            parent,
+            force_confirm,
+            candidates.iter(),
+            &subtree_weight,
+        ) {
+            debug!(
+                "The fork of {:?} is heavier than the parent",
+                self.arena[heavier_fork].hash
+            );
+            return Ok(Some(PartialInvalidCause::IncorrectParent));
+        }
+
+        if *header.difficulty()
+            != self.expected_difficulty(&self.arena[parent].hash)
+        {
+            return Ok(Some(PartialInvalidCause::WrongDifficulty));
+        }
+
+        let adaptive = self.adaptive_weight_impl_brutal(
+            parent,
+            &subtree_weight,
+            Some(&timer_chain_tuple),
+            force_confirm,
+            difficulty_to_weight(header.difficulty()),
+        );
+        if header.adaptive() != adaptive {
+            return Ok(Some(PartialInvalidCause::WrongAdaptive));
+        }
+        Ok(None)
+    }
+
+    /// Check whether `parent` is the correct parent choice among the forks
+    /// of `candidates` with the subtree weights `subtree_weight`, and return
+    /// the block that shows it is not. It is either a candidate in the
+    /// subtree of `parent`, or the root of a fork heavier than the one of
+    /// `parent`. The candidates forking before `force_confirm` are skipped.
+    pub(super) fn find_heavier_fork<'a>(
+        &self, parent: usize, force_confirm: usize,
+        candidates: impl Iterator<Item = &'a usize>,
+        subtree_weight: &Vec<i128>,
+    ) -> Option<usize>
+    {
+        let force_confirm_height = self.arena[force_confirm].height;
+        for candidate in candidates {
+            let lca = self.lca(*candidate, parent);
+            assert!(lca != *candidate);
+            // If it is outside current era, we will skip!
+            if lca == NULL || self.arena[lca].height < force_confirm_height {
+                continue;
+            }
+            if lca == parent {
+                return Some(*candidate);
+            }
+
+            let fork = self.ancestor_at(*candidate, self.arena[lca].height + 1);
+            let pivot = self.ancestor_at(parent, self.arena[lca].height + 1);
+            if ConsensusGraphInner::is_heavier(
+                (subtree_weight[fork], &self.arena[fork].hash),
+                (subtree_weight[pivot], &self.arena[pivot].hash),
+            ) {
+                return Some(fork);
+            }
+        }
+        None
+    }
+}
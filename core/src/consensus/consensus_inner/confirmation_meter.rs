@@ -2,14 +2,19 @@
 // Conflux is free software and distributed under GNU General Public License.
 // See http://www.gnu.org/licenses/
 
-use crate::consensus::{
-    consensus_inner::{NULL, NULLU64},
-    ConsensusGraphInner, DEFERRED_STATE_EPOCH_COUNT,
+use crate::{
+    clock::SharedClock,
+    consensus::{
+        consensus_inner::{
+            risk_model::RiskModel, weight::difficulty_to_weight, NULL, NULLU64,
+        },
+        ConsensusGraphInner,
+    },
 };
 use cfx_parameters::consensus_internal::*;
 use cfx_types::H256;
 use parking_lot::RwLock;
-use std::{cmp::max, collections::VecDeque, convert::TryFrom};
+use std::{cmp::max, collections::VecDeque, time::Duration};
 
 pub struct TotalWeightInPastMovingDelta {
     pub old: i128,
@@ -22,9 +27,31 @@ pub struct FinalityManager {
     pub risks_less_than: VecDeque<f64>,
 }
 
+impl FinalityManager {
+    /// The confirmation risk of epoch `epoch_num`, which is the maximum risk
+    /// of the maintained epochs not after it.
+    pub fn risk_of_epoch(&self, epoch_num: u64) -> f64 {
+        if epoch_num < self.lowest_epoch_num {
+            return CONFIRMATION_METER_MIN_MAINTAINED_RISK;
+        }
+        let idx = (epoch_num - self.lowest_epoch_num) as usize;
+        if idx < self.risks_less_than.len() {
+            self.risks_less_than
+                .iter()
+                .take(idx + 1)
+                .fold(0.0, |max_risk, risk| max_risk.max(*risk))
+        } else {
+            0.9
+        }
+    }
+}
+
 struct ConfirmationMeterInner {
     total_weight_in_past_2d: TotalWeightInPastMovingDelta,
     finality_manager: FinalityManager,
+    /// The risks of the maintained epochs are raised to at least this value
+    /// until the deadline in the time of the clock.
+    risk_floor: Option<(f64, Duration)>,
 }
 
 impl ConfirmationMeterInner {
@@ -39,6 +66,7 @@ impl ConfirmationMeterInner {
                 lowest_epoch_num: 0,
                 risks_less_than: VecDeque::new(),
             },
+            risk_floor: None,
         }
     }
 }
@@ -55,15 +83,22 @@ impl ConfirmationMeterInner {
 /// confirmation status of a block/transaction.
 pub struct ConfirmationMeter {
     inner: RwLock<ConfirmationMeterInner>,
+    risk_model: Box<dyn RiskModel>,
+    clock: SharedClock,
 }
 
 impl ConfirmationMeter {
-    pub fn new() -> Self {
+    pub fn new(risk_model: Box<dyn RiskModel>, clock: SharedClock) -> Self {
         Self {
             inner: RwLock::new(ConfirmationMeterInner::new()),
+            risk_model,
+            clock,
         }
     }
 
+    /// The name of the model computing the risks.
+    pub fn risk_model_name(&self) -> &'static str { self.risk_model.name() }
+
     pub fn clear(&self) {
         let mut inner = self.inner.write();
         *inner = ConfirmationMeterInner::new();
@@ -154,27 +189,25 @@ impl ConfirmationMeter {
             return Some(0.0);
         }
 
-        let finality = &self.inner.read().finality_manager;
-
-        if epoch_num < finality.lowest_epoch_num {
-            return Some(CONFIRMATION_METER_MIN_MAINTAINED_RISK);
-        }
-
-        let idx = (epoch_num - finality.lowest_epoch_num) as usize;
-        if idx < finality.risks_less_than.len() {
-            let mut max_risk = 0.0;
-            for i in 0..idx + 1 {
-                let risk = *finality.risks_less_than.get(i).unwrap();
-                if max_risk < risk {
-                    max_risk = risk;
-                }
+        let inner = self.inner.read();
+        let risk = inner.finality_manager.risk_of_epoch(epoch_num);
+        match inner.risk_floor {
+            Some((floor, until))
+                if epoch_num >= inner.finality_manager.lowest_epoch_num
+                    && self.clock.now() < until =>
+            {
+                Some(risk.max(floor))
             }
-            Some(max_risk)
-        } else {
-            Some(0.9)
+            _ => Some(risk),
         }
     }
 
+    /// Raise the risks of the epochs not confirmed yet to at least `risk`
+    /// until the clock reaches `until`, e.g. when an attack is suspected.
+    pub fn raise_risks_until(&self, risk: f64, until: Duration) {
+        self.inner.write().risk_floor = Some((risk, until));
+    }
+
     fn confirmation_risk(
         &self, g_inner: &ConsensusGraphInner, w_0: i128, w_4: i128,
         epoch_num: u64,
@@ -182,7 +215,9 @@ impl ConfirmationMeter {
     {
         // Compute w_1
         let idx = g_inner.get_pivot_block_arena_index(epoch_num);
-        let pivot_idx = g_inner.height_to_pivot_index(epoch_num);
+        let pivot_idx = g_inner
+            .checked_height_to_pivot_index(epoch_num)
+            .expect("the epoch should be on the pivot chain");
         let w_1 = g_inner.weight_tree.get(idx);
 
         // Compute w_2
@@ -205,7 +240,7 @@ impl ConfirmationMeter {
         let w_3 = g_inner.pivot_chain_metadata[pivot_idx].past_weight;
 
         // Compute d
-        let d = i128::try_from(g_inner.current_difficulty.low_u128()).unwrap();
+        let d = difficulty_to_weight(&g_inner.current_difficulty);
 
         // Compute n
         let w_2_4 = w_2 + w_4;
@@ -222,50 +257,22 @@ impl ConfirmationMeter {
         // w_4 {}, epoch_num {} genesis {}", m, n, w_0, w_1, w_2, w_3, w_4,
         // epoch_num, g_inner.cur_era_genesis_block_arena_index);
 
-        // Compute risk
-        let m_n_diff = m as f64 - n as f64;
-        let mut risk = 0.9;
-        let threshold_1 = if 0.75 * m as f64 - 22.0 < 2250.0 {
-            0.75 * m as f64 - 22.0
-        } else {
-            2250.0
-        };
-        if m_n_diff >= threshold_1 {
-            return risk;
-        }
-        risk = 0.0001;
-        let threshold_2 = if 0.70 * m as f64 - 22.0 < 1500.0 {
-            0.70 * m as f64 - 22.0
-        } else {
-            1500.0
-        };
-        if m_n_diff >= threshold_2 {
-            return risk;
-        }
-        risk = 0.000001;
-        let threshold_3 = if 0.65 * m as f64 - 22.0 < 750.0 {
-            0.65 * m as f64
-        } else {
-            750.0
-        };
-        if m_n_diff >= threshold_3 {
-            return risk;
-        }
-        risk = 0.00000001;
-        risk
+        self.risk_model.risk(m as u64, n as u64)
     }
 
     /// `ConsensusGraphInner` invokes this function to recompute confirmation
     /// risk of all epochs periodically
     pub fn update_confirmation_risks(&self, g_inner: &ConsensusGraphInner) {
-        if g_inner.pivot_chain.len() > DEFERRED_STATE_EPOCH_COUNT as usize {
+        let next_height =
+            g_inner.pivot_index_to_height(g_inner.pivot_chain.len());
+        let deferred_state_epoch_count =
+            g_inner.deferred_state_epoch_count.at(next_height);
+        if g_inner.pivot_chain.len() > deferred_state_epoch_count as usize {
             let w_0 = g_inner
                 .weight_tree
                 .get(g_inner.cur_era_genesis_block_arena_index);
             let mut risks = VecDeque::new();
-            let mut epoch_num = g_inner
-                .pivot_index_to_height(g_inner.pivot_chain.len())
-                - DEFERRED_STATE_EPOCH_COUNT;
+            let mut epoch_num = next_height - deferred_state_epoch_count;
             let mut count = 0;
             while epoch_num > g_inner.cur_era_genesis_height
                 && count < CONFIRMATION_METER_MAX_NUM_MAINTAINED_RISK
@@ -331,20 +338,25 @@ impl ConfirmationMeter {
         let total_weight = g_inner
             .weight_tree
             .get(g_inner.cur_era_genesis_block_arena_index);
-        let me_index =
-            g_inner.height_to_pivot_index(g_inner.arena[me].data.epoch_number);
+        let me_index = g_inner
+            .checked_height_to_pivot_index(g_inner.arena[me].data.epoch_number)
+            .expect("the epoch of the block should be in the current era");
         let x_3 =
             total_weight - g_inner.pivot_chain_metadata[me_index].past_weight;
 
         let mut adaptive_risk = 0f64;
-        let d = i128::try_from(g_inner.current_difficulty.low_u128()).unwrap();
+        let d = difficulty_to_weight(&g_inner.current_difficulty);
         for i in 0..n {
-            let a_pivot_index = g_inner.height_to_pivot_index(
-                g_inner.cur_era_stable_height + i * psi as u64,
-            );
-            let b_pivot_index = g_inner.height_to_pivot_index(
-                g_inner.cur_era_stable_height + (i + 1) * psi as u64,
-            );
+            let a_pivot_index = g_inner
+                .checked_height_to_pivot_index(
+                    g_inner.cur_era_stable_height + i * psi as u64,
+                )
+                .expect("the stable height should be in the current era");
+            let b_pivot_index = g_inner
+                .checked_height_to_pivot_index(
+                    g_inner.cur_era_stable_height + (i + 1) * psi as u64,
+                )
+                .expect("the checking height should be in the current era");
             let b = g_inner.pivot_chain[b_pivot_index];
             let y = g_inner.weight_tree.get(b);
             let mut x_1 = 0;
@@ -3,17 +3,27 @@
 // See http://www.gnu.org/licenses/
 
 use crate::{
-    block_data_manager::{BlockDataManager, BlockRewardResult},
+    block_data_manager::{
+        BlockDataManager, BlockLogIndex, BlockRewardDetails, BlockRewardResult,
+        EpochExecutionCheckpoint, EpochIssuance,
+        EpochSystemTransactionReceipts, SystemTransactionReceipt,
+    },
     consensus::{
+        chaos_hooks::ChaosHooks,
         consensus_inner::{
             consensus_new_block_handler::ConsensusNewBlockHandler,
-            StateBlameInfo,
+            epoch_prefetcher::EpochDependencyPrefetcher,
+            execution_origin::{ExecutionOrigin, ExecutionPipelineId},
+            execution_queue::{ExecutionTaskClass, WeightedFairQueue},
+            mock_execution::MockExecutionBackend,
+            shadow_execution::ShadowExecution,
+            ReceiptsRecomputeTask, StateBlameInfo,
         },
         ConsensusGraphInner,
     },
     executive::{
-        revert_reason_decode, ExecutionError, ExecutionOutcome, Executive,
-        TransactOptions,
+        reverted_error_message, ExecutionError, ExecutionOutcome, Executive,
+        SystemTransactionPhase, ToRepackError, TransactOptions, TxDropError,
     },
     machine::Machine,
     rpc_errors::{invalid_params_check, Result as RpcResult},
@@ -24,18 +34,26 @@ use crate::{
         },
         State,
     },
+    statistics::{
+        epoch_packing::EpochPackingCounters,
+        lock_contention::InstrumentedRwLock,
+        nonce_order::{EpochNonceOrder, SenderTxRecord, TxSkipReason},
+        SharedStatistics,
+    },
     trace::trace::{ExecTrace, TransactionExecTraces},
     verification::{
-        compute_receipts_root, VerificationConfig, VerifyTxLocalMode,
+        EpochReceiptsFunnel, VerificationConfig, VerifyTxLocalMode,
         VerifyTxMode,
     },
-    vm::{Env, Error as VmErr},
+    vm::{Env, Error as VmErr, Spec},
     SharedTransactionPool,
 };
 use cfx_internal_common::{
     debug::*, EpochExecutionCommitment, StateRootWithAuxInfo,
 };
-use cfx_parameters::consensus::*;
+use cfx_parameters::{
+    consensus::*, consensus_internal::MAX_IMPLICIT_STATE_REPLAY_EPOCHS,
+};
 use cfx_state::{state_trait::*, CleanupMode};
 use cfx_statedb::{Result as DbResult, StateDb};
 use cfx_storage::{
@@ -43,35 +61,38 @@ use cfx_storage::{
     StorageManagerTrait,
 };
 use cfx_types::{
-    address_util::AddressUtil, BigEndianHash, H160, H256, KECCAK_EMPTY_BLOOM,
-    U256, U512,
+    address_util::AddressUtil, Address, BigEndianHash, H160, H256, U256, U512,
 };
 use core::convert::TryFrom;
-use hash::KECCAK_EMPTY_LIST_RLP;
-use metrics::{register_meter_with_group, Meter, MeterTimer};
-use parking_lot::{Mutex, RwLock};
+use hash::keccak;
+use metrics::{
+    register_meter_with_group, Counter, CounterUsize, Meter, MeterTimer,
+};
+use parking_lot::{Condvar, Mutex, MutexGuard};
 use primitives::{
     compute_block_number,
     receipt::{
-        BlockReceipts, Receipt,
+        BlockReceipts, Receipt, TxExecutionErrorInfo,
         TRANSACTION_OUTCOME_EXCEPTION_WITHOUT_NONCE_BUMPING,
         TRANSACTION_OUTCOME_EXCEPTION_WITH_NONCE_BUMPING,
-        TRANSACTION_OUTCOME_SUCCESS,
+        TRANSACTION_OUTCOME_SUCCESS, TX_ALREADY_EXECUTED_ERROR_MESSAGE,
     },
-    Action, Block, BlockHeaderBuilder, EpochId, SignedTransaction,
-    TransactionIndex, MERKLE_NULL_NODE,
+    Action, Block, BlockHeader, EpochId, SignedTransaction, TransactionIndex,
 };
 use rustc_hex::ToHex;
 use std::{
-    collections::{BTreeMap, BTreeSet, HashMap},
+    cmp::{max, min},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     convert::From,
-    fmt::{Debug, Formatter},
+    fmt::{self, Debug, Formatter},
+    mem,
     sync::{
         atomic::{AtomicBool, Ordering::Relaxed},
-        mpsc::{channel, RecvError, Sender, TryRecvError},
+        mpsc::{channel, Sender},
         Arc,
     },
     thread::{self, JoinHandle},
+    time::{Duration, Instant},
 };
 
 lazy_static! {
@@ -84,14 +105,31 @@ lazy_static! {
         );
     static ref GOOD_TPS_METER: Arc<dyn Meter> =
         register_meter_with_group("system_metrics", "good_tps");
+    static ref RECEIPTS_QUARANTINE_COUNTER: Arc<dyn Counter<usize>> =
+        CounterUsize::register_with_group(
+            "system_metrics",
+            "receipts_quarantine_count"
+        );
+    static ref RECEIPTS_QUARANTINE_CLEARED_COUNTER: Arc<dyn Counter<usize>> =
+        CounterUsize::register_with_group(
+            "system_metrics",
+            "receipts_quarantine_cleared_count"
+        );
 }
 
+/// The tasks of a class are executed one at a time, so a worker beyond one per
+/// class would always be idle.
+pub const MAX_EXECUTION_WORKER_COUNT: usize = 2;
+
 /// The RewardExecutionInfo struct includes most information to compute rewards
 /// for old epochs
 pub struct RewardExecutionInfo {
     pub past_block_count: u64,
     pub epoch_blocks: Vec<Arc<Block>>,
     pub epoch_block_no_reward: Vec<bool>,
+    /// Whether the anticone difficulty of each block reaches the limit. Such
+    /// blocks are also marked in `epoch_block_no_reward`.
+    pub epoch_block_anticone_overlimited: Vec<bool>,
     pub epoch_block_anticone_difficulties: Vec<U512>,
 }
 
@@ -102,6 +140,7 @@ impl Debug for RewardExecutionInfo {
             "RewardExecutionInfo{{ past_block_count: {} \
              epoch_blocks: {:?} \
              epoch_block_no_reward: {:?} \
+             epoch_block_anticone_overlimited: {:?} \
              epoch_block_anticone_difficulties: {:?}}}",
             self.past_block_count,
             self.epoch_blocks
@@ -109,18 +148,179 @@ impl Debug for RewardExecutionInfo {
                 .map(|b| b.hash())
                 .collect::<Vec<H256>>(),
             self.epoch_block_no_reward,
+            self.epoch_block_anticone_overlimited,
             self.epoch_block_anticone_difficulties
         )
     }
 }
 
+impl RewardExecutionInfo {
+    /// Return the info of only the last `bound` blocks of the epoch, which
+    /// always include the pivot block.
+    pub fn truncated(&self, bound: usize) -> Self {
+        let skipped = self.epoch_blocks.len().saturating_sub(bound);
+        RewardExecutionInfo {
+            past_block_count: self.past_block_count,
+            epoch_blocks: self.epoch_blocks[skipped..].to_vec(),
+            epoch_block_no_reward: self.epoch_block_no_reward[skipped..]
+                .to_vec(),
+            epoch_block_anticone_overlimited: self
+                .epoch_block_anticone_overlimited[skipped..]
+                .to_vec(),
+            epoch_block_anticone_difficulties: self
+                .epoch_block_anticone_difficulties[skipped..]
+                .to_vec(),
+        }
+    }
+}
+
+/// The result of `ConsensusExecutor::dry_run_epoch`.
+#[derive(Debug)]
+pub struct DryRunEpochResult {
+    pub state_root: StateRootWithAuxInfo,
+    pub receipts_root: H256,
+    pub logs_bloom_hash: H256,
+    pub receipts: Vec<Arc<BlockReceipts>>,
+    pub system_transaction_receipts: EpochSystemTransactionReceipts,
+}
+
+/// The error of computing the state of a block on demand.
+#[derive(Debug)]
+pub enum StateComputationError {
+    /// Too many epochs need to be replayed to compute the state.
+    TooExpensive {
+        epochs_to_replay: usize,
+        max_epochs: usize,
+    },
+    Failed(String),
+}
+
+impl fmt::Display for StateComputationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StateComputationError::TooExpensive {
+                epochs_to_replay,
+                max_epochs,
+            } => write!(
+                f,
+                "Computing the state needs to replay {} epochs, more than {}",
+                epochs_to_replay, max_epochs
+            ),
+            StateComputationError::Failed(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl From<String> for StateComputationError {
+    fn from(e: String) -> Self { StateComputationError::Failed(e) }
+}
+
+impl From<StateComputationError> for String {
+    fn from(e: StateComputationError) -> String { e.to_string() }
+}
+
 #[derive(Debug)]
 enum ExecutionTask {
     ExecuteEpoch(EpochExecutionTask),
     GetResult(GetExecutionResultTask),
+    RecomputeReceipts(Arc<PendingReceiptsRecompute>),
+}
+
+impl ExecutionTask {
+    fn class(&self) -> ExecutionTaskClass {
+        match self {
+            ExecutionTask::ExecuteEpoch(task) => task.class,
+            ExecutionTask::GetResult(task) => task.class,
+            ExecutionTask::RecomputeReceipts(_) => ExecutionTaskClass::Head,
+        }
+    }
+
+    /// The epoch whose execution this task depends on. An epoch depends on
+    /// the state of its parent, and a result or a receipts recompute depends
+    /// on the epoch itself.
+    fn dependency(&self) -> Option<&H256> {
+        match self {
+            ExecutionTask::ExecuteEpoch(task) => task.parent_hash.as_ref(),
+            ExecutionTask::GetResult(task) => Some(&task.epoch_hash),
+            ExecutionTask::RecomputeReceipts(recompute) => {
+                Some(&recompute.task.epoch_hash)
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum ReceiptsRecomputeState {
+    Queued,
+    Running,
+    Finished,
+}
+
+/// A receipts recompute enqueued by
+/// `ConsensusExecutor::enqueue_stale_receipts_recompute`. It is run by an
+/// execution worker, or by the first thread that needs its result before a
+/// worker takes it.
+#[derive(Debug)]
+struct PendingReceiptsRecompute {
+    task: ReceiptsRecomputeTask,
+    state: Mutex<ReceiptsRecomputeState>,
+    finished: Condvar,
+}
+
+impl PendingReceiptsRecompute {
+    fn new(task: ReceiptsRecomputeTask) -> Self {
+        Self {
+            task,
+            state: Mutex::new(ReceiptsRecomputeState::Queued),
+            finished: Condvar::new(),
+        }
+    }
+
+    /// Recompute the receipts, or wait for the thread recomputing them.
+    fn run(&self, handler: &ConsensusExecutionHandler) {
+        {
+            let mut state = self.state.lock();
+            match *state {
+                ReceiptsRecomputeState::Queued => {
+                    *state = ReceiptsRecomputeState::Running;
+                }
+                ReceiptsRecomputeState::Running => {
+                    while *state != ReceiptsRecomputeState::Finished {
+                        self.finished.wait(&mut state);
+                    }
+                    return;
+                }
+                ReceiptsRecomputeState::Finished => return,
+            }
+        }
+        RECEIPTS_QUARANTINE_COUNTER.inc(1);
+        match handler.recompute_epoch_receipts(&self.task) {
+            Ok(Some(receipts_root)) => {
+                warn!(
+                    "Receipts root of epoch {:?} is stale, recomputed {:?}",
+                    self.task.epoch_hash, receipts_root
+                );
+                RECEIPTS_QUARANTINE_CLEARED_COUNTER.inc(1);
+            }
+            Ok(None) => {}
+            Err(e) => {
+                warn!(
+                    "Fail to recompute the receipts of epoch {:?}: {}",
+                    self.task.epoch_hash, e
+                );
+            }
+        }
+        *self.state.lock() = ReceiptsRecomputeState::Finished;
+        self.finished.notify_all();
+    }
+}
 
-    /// Stop task is used to stop the execution thread
-    Stop,
+/// A task with its sequence number, which is increased in the order the tasks
+/// are enqueued.
+#[derive(Debug)]
+struct QueuedTask {
+    seq: u64,
+    task: ExecutionTask,
 }
 
 /// The EpochExecutionTask struct includes all the information needed to execute
@@ -128,6 +328,8 @@ enum ExecutionTask {
 #[derive(Debug)]
 pub struct EpochExecutionTask {
     epoch_hash: H256,
+    /// `None` if the pivot block has no parent in the consensus graph.
+    parent_hash: Option<H256>,
     epoch_block_hashes: Vec<H256>,
     start_block_number: u64,
     reward_info: Option<RewardExecutionInfo>,
@@ -136,23 +338,37 @@ pub struct EpochExecutionTask {
     //  epoch execution, not to be set from task.
     on_local_pivot: bool,
     force_recompute: bool,
+    /// Whether the epoch is executed before the pivot chain reaches its
+    /// deferred execution.
+    optimistic: bool,
+    class: ExecutionTaskClass,
+    pipeline_id: ExecutionPipelineId,
 }
 
 impl EpochExecutionTask {
     pub fn new(
-        epoch_arena_index: usize, inner: &ConsensusGraphInner,
+        epoch_arena_index: usize, inner: &mut ConsensusGraphInner,
         reward_execution_info: Option<RewardExecutionInfo>,
-        on_local_pivot: bool, force_recompute: bool,
+        on_local_pivot: bool, force_recompute: bool, origin: ExecutionOrigin,
     ) -> Self
     {
+        let parent = inner.arena[epoch_arena_index].parent;
         Self {
             epoch_hash: inner.arena[epoch_arena_index].hash,
+            parent_hash: if parent == NULL {
+                None
+            } else {
+                Some(inner.arena[parent].hash)
+            },
             epoch_block_hashes: inner.get_epoch_block_hashes(epoch_arena_index),
             start_block_number: inner
                 .get_epoch_start_block_number(epoch_arena_index),
             reward_info: reward_execution_info,
             on_local_pivot,
             force_recompute,
+            optimistic: false,
+            class: ExecutionTaskClass::Head,
+            pipeline_id: ExecutionPipelineId::next(origin),
         }
     }
 }
@@ -163,122 +379,432 @@ impl EpochExecutionTask {
 struct GetExecutionResultTask {
     pub epoch_hash: H256,
     pub sender: Sender<Option<EpochExecutionCommitment>>,
+    /// The result is returned after all the tasks of this class enqueued
+    /// before, and the executions of `epoch_hash` in the other class
+    /// enqueued before, are finished.
+    pub class: ExecutionTaskClass,
+}
+
+/// The accumulated cost of the epoch execution tasks of a class.
+#[derive(Clone, Debug, Default)]
+pub struct ExecutionTaskStats {
+    pub task_count: u64,
+    pub wall_time: Duration,
+    /// The CPU time of the worker threads. It is zero if the platform does
+    /// not support per-thread CPU time.
+    pub cpu_time: Duration,
+}
+
+impl ExecutionTaskStats {
+    fn add(&mut self, wall_time: Duration, cpu_time: Option<Duration>) {
+        self.task_count += 1;
+        self.wall_time += wall_time;
+        self.cpu_time += cpu_time.unwrap_or_default();
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ExecutionWorkerStatus {
+    pub worker_count: usize,
+    pub head_stats: ExecutionTaskStats,
+    pub replay_stats: ExecutionTaskStats,
+    /// Including the epochs executed synchronously, which are not in any
+    /// class.
+    pub origin_stats: BTreeMap<ExecutionOrigin, ExecutionTaskStats>,
+}
+
+/// The state shared by the execution workers.
+///
+/// A task may depend on the tasks enqueued before it. The tasks of a class
+/// are executed one at a time in the order they are enqueued, and a task
+/// waits for the executions of its dependency in the other class enqueued
+/// before it. So the tasks are executed as if they were executed one by one
+/// in the order they are enqueued. The waits always go to earlier tasks, so
+/// they never form a cycle.
+struct ExecutionScheduler {
+    queue: WeightedFairQueue<QueuedTask>,
+    /// The classes that have a task being executed.
+    busy_classes: HashSet<ExecutionTaskClass>,
+    /// The epochs being executed, so an epoch enqueued as both a head task
+    /// and a replay task is not executed by two workers at the same time.
+    executing_epochs: HashSet<H256>,
+    /// The class and the sequence number of the executions of each epoch
+    /// that are enqueued or being executed.
+    pending_executions: HashMap<H256, Vec<(ExecutionTaskClass, u64)>>,
+    /// The sequence number of the next task.
+    next_seq: u64,
+    /// Increased when a task is enqueued.
+    enqueued_count: u64,
+    worker_count: usize,
+    target_worker_count: usize,
+    stats: HashMap<ExecutionTaskClass, ExecutionTaskStats>,
+    origin_stats: BTreeMap<ExecutionOrigin, ExecutionTaskStats>,
+}
+
+impl ExecutionScheduler {
+    fn new(head_weight: u32, replay_weight: u32) -> Self {
+        Self {
+            queue: WeightedFairQueue::new(head_weight, replay_weight),
+            busy_classes: HashSet::new(),
+            executing_epochs: HashSet::new(),
+            pending_executions: HashMap::new(),
+            next_seq: 0,
+            enqueued_count: 0,
+            worker_count: 0,
+            target_worker_count: 0,
+            stats: HashMap::new(),
+            origin_stats: BTreeMap::new(),
+        }
+    }
+
+    fn push(&mut self, task: ExecutionTask) {
+        let class = task.class();
+        let seq = match &task {
+            ExecutionTask::ExecuteEpoch(task) => {
+                self.register_execution(class, task.epoch_hash)
+            }
+            ExecutionTask::GetResult(_)
+            | ExecutionTask::RecomputeReceipts(_) => self.allocate_seq(),
+        };
+        self.queue.push(class, QueuedTask { seq, task });
+        self.enqueued_count += 1;
+    }
+
+    /// Dequeue the next task that does not wait for any other task, and mark
+    /// its class as busy.
+    fn pop(&mut self) -> Option<(ExecutionTaskClass, QueuedTask)> {
+        let popped = {
+            let ExecutionScheduler {
+                queue,
+                busy_classes,
+                pending_executions,
+                ..
+            } = &mut *self;
+            queue.pop(|class, queued| {
+                !busy_classes.contains(&class)
+                    && !Self::waits_for_other_class(
+                        pending_executions,
+                        class,
+                        queued,
+                    )
+            })
+        };
+        if let Some((class, _)) = &popped {
+            self.busy_classes.insert(*class);
+        }
+        popped
+    }
+
+    fn waits_for_other_class(
+        pending_executions: &HashMap<H256, Vec<(ExecutionTaskClass, u64)>>,
+        class: ExecutionTaskClass, queued: &QueuedTask,
+    ) -> bool
+    {
+        queued
+            .task
+            .dependency()
+            .and_then(|epoch_hash| pending_executions.get(epoch_hash))
+            .map_or(false, |executions| {
+                executions.iter().any(|(execution_class, seq)| {
+                    *execution_class != class && *seq < queued.seq
+                })
+            })
+    }
+
+    /// Record the execution of `epoch_hash` as pending until
+    /// `finish_execution` is called, and return its sequence number.
+    fn register_execution(
+        &mut self, class: ExecutionTaskClass, epoch_hash: H256,
+    ) -> u64 {
+        let seq = self.allocate_seq();
+        self.pending_executions
+            .entry(epoch_hash)
+            .or_default()
+            .push((class, seq));
+        seq
+    }
+
+    fn finish_execution(&mut self, epoch_hash: &H256, seq: u64) {
+        self.executing_epochs.remove(epoch_hash);
+        if let Some(executions) = self.pending_executions.get_mut(epoch_hash) {
+            executions.retain(|(_, execution_seq)| *execution_seq != seq);
+            if executions.is_empty() {
+                self.pending_executions.remove(epoch_hash);
+            }
+        }
+    }
+
+    fn allocate_seq(&mut self) -> u64 {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        seq
+    }
 }
 
 /// ConsensusExecutor processes transaction execution tasks.
 pub struct ConsensusExecutor {
-    /// The thread responsible for execution transactions
-    thread: Mutex<Option<JoinHandle<()>>>,
+    /// The threads responsible for execution transactions. The threads of
+    /// the retired workers are kept until `stop` joins them.
+    threads: Mutex<Vec<JoinHandle<()>>>,
+
+    scheduler: Mutex<ExecutionScheduler>,
 
-    /// The sender to send tasks to be executed by `self.thread`
-    sender: Mutex<Sender<ExecutionTask>>,
+    /// Notified when a task is enqueued or finished, or the worker count
+    /// changes.
+    scheduler_changed: Condvar,
 
-    /// The state indicating whether the thread should be stopped
+    /// The state indicating whether the threads should be stopped
     stopped: AtomicBool,
 
     /// The handler to provide functions to handle `ExecutionTask` and execute
-    /// transactions It is used both asynchronously by `self.thread` and
+    /// transactions It is used both asynchronously by `self.threads` and
     /// synchronously by the executor itself
     pub handler: Arc<ConsensusExecutionHandler>,
 
-    consensus_graph_bench_mode: bool,
+    consensus_inner: Arc<InstrumentedRwLock<ConsensusGraphInner>>,
+
+    /// Executes the epochs instead of the workers in bench mode.
+    mock_backend: Option<MockExecutionBackend>,
+
+    /// Warms the caches for the enqueued epochs if it is enabled.
+    dependency_prefetcher: Option<EpochDependencyPrefetcher>,
+
+    /// The receipts recomputes enqueued and not waited for by
+    /// `wait_for_receipts_recomputes` yet, keyed by the epoch hash.
+    receipts_recomputes: Mutex<HashMap<H256, Arc<PendingReceiptsRecompute>>>,
+
+    /// The test hooks to delay the execution of selected epochs.
+    pub chaos_hooks: ChaosHooks,
 }
 
 impl ConsensusExecutor {
     pub fn start(
         tx_pool: SharedTransactionPool, data_man: Arc<BlockDataManager>,
-        consensus_inner: Arc<RwLock<ConsensusGraphInner>>,
+        consensus_inner: Arc<InstrumentedRwLock<ConsensusGraphInner>>,
         config: ConsensusExecutionConfiguration,
-        verification_config: VerificationConfig, bench_mode: bool,
+        verification_config: VerificationConfig, statistics: SharedStatistics,
+        bench_mode: bool,
     ) -> Arc<Self>
     {
         let machine = tx_pool.machine();
+        let scheduler = ExecutionScheduler::new(
+            config.head_execution_weight,
+            config.replay_execution_weight,
+        );
+        let worker_count = config.execution_worker_count;
+        let dependency_prefetcher =
+            if config.enable_dependency_prefetch && !bench_mode {
+                Some(EpochDependencyPrefetcher::start(data_man.clone()))
+            } else {
+                None
+            };
+        let mock_backend = if bench_mode {
+            Some(MockExecutionBackend::new(data_man.clone()))
+        } else {
+            None
+        };
         let handler = Arc::new(ConsensusExecutionHandler::new(
             tx_pool,
             data_man.clone(),
             config,
             verification_config,
             machine,
+            statistics,
         ));
-        let (sender, receiver) = channel();
 
-        let executor_raw = ConsensusExecutor {
-            thread: Mutex::new(None),
-            sender: Mutex::new(sender),
+        let executor = Arc::new(ConsensusExecutor {
+            threads: Default::default(),
+            scheduler: Mutex::new(scheduler),
+            scheduler_changed: Condvar::new(),
             stopped: AtomicBool::new(false),
-            handler: handler.clone(),
-            consensus_graph_bench_mode: bench_mode,
+            handler,
+            consensus_inner,
+            mock_backend,
+            dependency_prefetcher,
+            receipts_recomputes: Default::default(),
+            chaos_hooks: ChaosHooks::default(),
+        });
+        executor.set_worker_count(worker_count);
+        executor
+    }
+
+    /// Change the number of execution workers. New workers are started
+    /// immediately, and the extra workers exit after finishing their current
+    /// tasks. The count is at least 1 and at most
+    /// `MAX_EXECUTION_WORKER_COUNT`.
+    ///
+    /// The tasks of the same class are executed one at a time, so the head
+    /// and the replay tasks are executed in parallel only with more than one
+    /// worker. A task still waits for the tasks of the other class it depends
+    /// on, as described in `ExecutionScheduler`.
+    pub fn set_worker_count(self: &Arc<Self>, count: usize) {
+        if count > MAX_EXECUTION_WORKER_COUNT {
+            warn!(
+                "Execution worker count {} is capped at {}",
+                count, MAX_EXECUTION_WORKER_COUNT
+            );
+        }
+        let count = min(max(count, 1), MAX_EXECUTION_WORKER_COUNT);
+        let mut scheduler = self.scheduler.lock();
+        if self.stopped.load(Relaxed) {
+            return;
+        }
+        scheduler.target_worker_count = count;
+        let mut threads = self.threads.lock();
+        while scheduler.worker_count < count {
+            let executor = self.clone();
+            // It receives blocks hashes from on_new_block and execute them
+            let handle = thread::Builder::new()
+                .name("Consensus Execution Worker".into())
+                .spawn(move || executor.run_worker())
+                .expect("Cannot fail");
+            threads.push(handle);
+            scheduler.worker_count += 1;
+        }
+        info!("Set execution worker count to {}", count);
+        self.scheduler_changed.notify_all();
+    }
+
+    pub fn worker_status(&self) -> ExecutionWorkerStatus {
+        let scheduler = self.scheduler.lock();
+        let stats_of = |class: ExecutionTaskClass| {
+            scheduler.stats.get(&class).cloned().unwrap_or_default()
         };
-        let executor = Arc::new(executor_raw);
-        let executor_thread = executor.clone();
-        // It receives blocks hashes from on_new_block and execute them
-        let handle = thread::Builder::new()
-            .name("Consensus Execution Worker".into())
-            .spawn(move || loop {
-                if executor_thread.stopped.load(Relaxed) {
-                    // The thread should be stopped. The rest tasks in the queue
-                    // will be discarded.
-                    break;
+        ExecutionWorkerStatus {
+            worker_count: scheduler.target_worker_count,
+            head_stats: stats_of(ExecutionTaskClass::Head),
+            replay_stats: stats_of(ExecutionTaskClass::Replay),
+            origin_stats: scheduler.origin_stats.clone(),
+        }
+    }
+
+    fn run_worker(&self) {
+        while let Some((class, QueuedTask { seq, task })) = self.next_task() {
+            let executed_epoch = match &task {
+                ExecutionTask::ExecuteEpoch(task) => {
+                    Some((task.epoch_hash, task.pipeline_id))
+                }
+                ExecutionTask::GetResult(_)
+                | ExecutionTask::RecomputeReceipts(_) => None,
+            };
+            if let Some(delay) = executed_epoch
+                .and_then(|(hash, _)| self.chaos_hooks.execution_delay(&hash))
+            {
+                debug!(
+                    "Delay execution of epoch {:?} by {:?}",
+                    executed_epoch, delay
+                );
+                thread::sleep(delay);
+            }
+            let start_time = Instant::now();
+            let start_cpu_time = thread_cpu_time();
+            self.handler.handle_execution_work(task);
+            let wall_time = start_time.elapsed();
+            let cpu_time = match (start_cpu_time, thread_cpu_time()) {
+                (Some(start), Some(end)) => end.checked_sub(start),
+                _ => None,
+            };
+
+            let mut scheduler = self.scheduler.lock();
+            scheduler.busy_classes.remove(&class);
+            if let Some((epoch_hash, pipeline_id)) = executed_epoch {
+                debug!(
+                    "Execution {} of epoch {:?} in class {:?} takes {:?}, cpu time {:?}",
+                    pipeline_id, epoch_hash, class, wall_time, cpu_time
+                );
+                scheduler.finish_execution(&epoch_hash, seq);
+                scheduler
+                    .stats
+                    .entry(class)
+                    .or_default()
+                    .add(wall_time, cpu_time);
+                scheduler
+                    .origin_stats
+                    .entry(pipeline_id.origin)
+                    .or_default()
+                    .add(wall_time, cpu_time);
+            }
+            self.scheduler_changed.notify_all();
+        }
+    }
+
+    /// Wait for the next task to execute and mark its class as busy. Return
+    /// `None` if the worker should exit.
+    fn next_task(&self) -> Option<(ExecutionTaskClass, QueuedTask)> {
+        let mut scheduler = self.scheduler.lock();
+        loop {
+            if self.stopped.load(Relaxed) {
+                // The rest tasks in the queue will be discarded.
+                scheduler.worker_count -= 1;
+                return None;
+            }
+            if scheduler.worker_count > scheduler.target_worker_count {
+                scheduler.worker_count -= 1;
+                return None;
+            }
+            if let Some((class, queued)) = scheduler.pop() {
+                if let ExecutionTask::ExecuteEpoch(task) = &queued.task {
+                    // Wait for the worker executing the same epoch, and then
+                    // the execution of this task will be skipped.
+                    while scheduler.executing_epochs.contains(&task.epoch_hash)
+                    {
+                        self.scheduler_changed.wait(&mut scheduler);
+                    }
+                    scheduler.executing_epochs.insert(task.epoch_hash);
                 }
-                let maybe_task = {
+                return Some((class, queued));
+            }
+            if !scheduler.busy_classes.contains(&ExecutionTaskClass::Head) {
+                // No task is available, so we try to optimistically get
+                // later epochs to execute.
+                scheduler.busy_classes.insert(ExecutionTaskClass::Head);
+                let enqueued_count = scheduler.enqueued_count;
+                let backlog = scheduler.queue.len();
+                let maybe_task = MutexGuard::unlocked(&mut scheduler, || {
                     // Here we use `try_write` because some thread
                     // may wait for execution results while holding the
                     // Consensus Inner lock, if we wait on
                     // inner lock here we may get deadlock.
-                    match receiver.try_recv() {
-                        Ok(task) => Some(task),
-                        Err(TryRecvError::Empty) => {
-                            // The channel is empty, so we try to optimistically
-                            // get later epochs to execute.
-                            consensus_inner
-                                .try_write()
-                                .and_then(|mut inner| {
-                                    executor_thread
-                                        .get_optimistic_execution_task(
-                                            &mut *inner,
-                                        )
-                                })
-                                .map(|task| {
-                                    debug!(
-                                        "Get optimistic_execution_task {:?}",
-                                        task
-                                    );
-                                    ExecutionTask::ExecuteEpoch(task)
-                                })
-                        }
-                        Err(TryRecvError::Disconnected) => {
-                            info!("Channel disconnected, stop thread");
-                            break;
-                        }
-                    }
-                };
-                let task = match maybe_task {
-                    Some(task) => task,
-                    None => {
-                        //  Even optimistic tasks are all finished, so we block
-                        // and wait for  new execution
-                        // tasks.  New optimistic tasks
-                        // will only exist if pivot_chain changes,
-                        //  and new tasks will be sent to `receiver` in this
-                        // case, so this waiting will
-                        // not prevent new optimistic tasks from being executed.
-                        match receiver.recv() {
-                            Ok(task) => task,
-                            Err(RecvError) => {
-                                info!("Channel receive error, stop thread");
-                                break;
-                            }
-                        }
+                    self.consensus_inner.try_write().and_then(|mut inner| {
+                        self.get_optimistic_execution_task(&mut *inner, backlog)
+                    })
+                });
+                if let Some(task) = maybe_task {
+                    debug!("Get optimistic_execution_task {:?}", task);
+                    if !scheduler.executing_epochs.contains(&task.epoch_hash) {
+                        scheduler.executing_epochs.insert(task.epoch_hash);
+                        // The replay tasks enqueued later wait for it.
+                        let seq = scheduler.register_execution(
+                            ExecutionTaskClass::Head,
+                            task.epoch_hash,
+                        );
+                        return Some((
+                            ExecutionTaskClass::Head,
+                            QueuedTask {
+                                seq,
+                                task: ExecutionTask::ExecuteEpoch(task),
+                            },
+                        ));
                     }
-                };
-                if !handler.handle_execution_work(task) {
-                    // `task` is `Stop`, so just stop.
-                    break;
                 }
-            })
-            .expect("Cannot fail");
-        *executor.thread.lock() = Some(handle);
-        executor
+                scheduler.busy_classes.remove(&ExecutionTaskClass::Head);
+                if scheduler.enqueued_count != enqueued_count {
+                    // New tasks are enqueued while the lock is released.
+                    continue;
+                }
+            }
+            // Even optimistic tasks are all finished, so we wait for new
+            // execution tasks. New optimistic tasks will only exist if
+            // pivot_chain changes, and new tasks will be enqueued in this
+            // case, so this waiting will not prevent new optimistic tasks
+            // from being executed.
+            self.scheduler_changed.wait(&mut scheduler);
+        }
+    }
+
+    fn send_task(&self, task: ExecutionTask) {
+        self.scheduler.lock().push(task);
+        self.scheduler_changed.notify_all();
     }
 
     // TODO: The comments and method name are not precise,
@@ -293,15 +819,16 @@ impl ConsensusExecutor {
     pub fn wait_for_result(
         &self, epoch_hash: H256,
     ) -> Result<EpochExecutionCommitment, String> {
-        // In consensus_graph_bench_mode execution is skipped.
-        if self.consensus_graph_bench_mode {
-            Ok(EpochExecutionCommitment {
-                state_root_with_aux_info: StateRootWithAuxInfo::genesis(
-                    &MERKLE_NULL_NODE,
-                ),
-                receipts_root: KECCAK_EMPTY_LIST_RLP,
-                logs_bloom_hash: KECCAK_EMPTY_BLOOM,
-            })
+        self.wait_for_result_in_class(epoch_hash, ExecutionTaskClass::Head)
+    }
+
+    /// Like `wait_for_result`, but only wait for the tasks of `class`.
+    fn wait_for_result_in_class(
+        &self, epoch_hash: H256, class: ExecutionTaskClass,
+    ) -> Result<EpochExecutionCommitment, String> {
+        // In bench mode the epochs are executed when they are enqueued.
+        if let Some(mock_backend) = &self.mock_backend {
+            mock_backend.execution_result(&epoch_hash)
         } else {
             if self.handler.data_man.epoch_executed(&epoch_hash) {
                 // The epoch already executed, so we do not need wait for the
@@ -312,13 +839,11 @@ impl ConsensusExecutor {
             }
             let (sender, receiver) = channel();
             debug!("Wait for execution result of epoch {:?}", epoch_hash);
-            self.sender
-                .lock()
-                .send(ExecutionTask::GetResult(GetExecutionResultTask {
-                    epoch_hash,
-                    sender,
-                }))
-                .expect("Cannot fail");
+            self.send_task(ExecutionTask::GetResult(GetExecutionResultTask {
+                epoch_hash,
+                sender,
+                class,
+            }));
             receiver.recv().unwrap().ok_or(
                 "Waiting for an execution result that is not enqueued!"
                     .to_string(),
@@ -326,12 +851,20 @@ impl ConsensusExecutor {
         }
     }
 
+    /// `backlog` is the number of tasks waiting in the queue for the busy
+    /// classes, which limits how far ahead the optimistic execution goes.
     fn get_optimistic_execution_task(
-        &self, inner: &mut ConsensusGraphInner,
+        &self, inner: &mut ConsensusGraphInner, backlog: usize,
     ) -> Option<EpochExecutionTask> {
         if !inner.inner_conf.enable_optimistic_execution {
             return None;
         }
+        let pivot_height = inner.pivot_index_to_height(inner.pivot_chain.len());
+        // The epochs before this height are executed without optimistic
+        // execution.
+        let deferred_boundary = (pivot_height + 1)
+            .saturating_sub(inner.deferred_state_epoch_count.at(pivot_height));
+        let depth = inner.optimistic_depth.depth(inner.clock.now(), backlog);
 
         let epoch_arena_index = {
             let mut state_availability_boundary =
@@ -344,6 +877,22 @@ impl ConsensusExecutor {
                 // the checks of the execution queue and the opt task.
                 return None;
             }
+            if opt_height >= deferred_boundary + depth {
+                // Keep `optimistic_executed_height` so the execution
+                // continues from here when the depth grows.
+                return None;
+            }
+            // An evicted epoch set is read without being reloaded, and the
+            // optimistic execution stops here if it fails to be read.
+            if inner
+                .epoch_misses_body(
+                    inner.get_pivot_block_arena_index(opt_height),
+                )
+                .unwrap_or(true)
+            {
+                // Wait for the block bodies in the header graph mode.
+                return None;
+            }
             let next_opt_height = opt_height + 1;
             if next_opt_height
                 >= inner.pivot_index_to_height(inner.pivot_chain.len())
@@ -361,13 +910,15 @@ impl ConsensusExecutor {
         // will also notify in advance.
         let reward_execution_info =
             self.get_reward_execution_info(inner, epoch_arena_index);
-        let execution_task = EpochExecutionTask::new(
+        let mut execution_task = EpochExecutionTask::new(
             epoch_arena_index,
             inner,
             reward_execution_info,
             true,  /* on_local_pivot */
             false, /* force_compute */
+            ExecutionOrigin::Optimistic,
         );
+        execution_task.optimistic = true;
         Some(execution_task)
     }
 
@@ -381,7 +932,9 @@ impl ConsensusExecutor {
                 // We have to wait here because blame information will determine the reward of each block.
                 // In order to compute the correct blame information locally, we have to wait for the execution to return.
                 let height = inner.arena[pivot_arena_index].height;
-                if !self.consensus_graph_bench_mode
+                // The mock roots do not match the deferred roots in the
+                // headers, so the blames are not checked in bench mode.
+                if self.mock_backend.is_none()
                 {
                     debug!(
                         "wait_and_compute_state_valid_locked, idx = {}, \
@@ -400,6 +953,8 @@ impl ConsensusExecutor {
 
                 let mut epoch_block_no_reward =
                     Vec::with_capacity(epoch_blocks.len());
+                let mut epoch_block_anticone_overlimited =
+                    Vec::with_capacity(epoch_blocks.len());
                 let mut epoch_block_anticone_difficulties =
                     Vec::with_capacity(epoch_blocks.len());
 
@@ -412,15 +967,15 @@ impl ConsensusExecutor {
                 if let Some(r) = anticone_cutoff_epoch_anticone_set_ref_opt {
                     anticone_cutoff_epoch_anticone_set = r.clone();
                 } else {
-                    anticone_cutoff_epoch_anticone_set = ConsensusNewBlockHandler::compute_anticone_hashset_bruteforce(inner, anticone_penalty_cutoff_epoch_arena_index);
+                    anticone_cutoff_epoch_anticone_set = ConsensusNewBlockHandler::compute_anticone_set_bruteforce(inner, anticone_penalty_cutoff_epoch_arena_index);
                 }
-                let ordered_epoch_blocks = inner.get_ordered_executable_epoch_blocks(pivot_arena_index).clone();
+                let ordered_epoch_blocks = inner.ordered_executable_epoch_blocks(pivot_arena_index).clone();
                 for index in ordered_epoch_blocks.iter() {
                     let block_consensus_node = &inner.arena[*index];
 
                     let mut no_reward =
                         block_consensus_node.data.partial_invalid;
-                    if !self.consensus_graph_bench_mode && !no_reward {
+                    if self.mock_backend.is_none() && !no_reward {
                         if *index == pivot_arena_index {
                             no_reward = !inner.arena[pivot_arena_index]
                                 .data
@@ -437,17 +992,18 @@ impl ConsensusExecutor {
                     // anticone_difficulty will not be used, so it's okay to set
                     // it to 0.
                     let mut anticone_difficulty: U512 = 0.into();
+                    let mut anticone_overlimited = false;
                     if !no_reward {
                         let block_consensus_node_anticone_opt =
                             inner.anticone_cache.get(*index);
                         let block_consensus_node_anticone = if let Some(r) = block_consensus_node_anticone_opt {
                             r.clone()
                         } else {
-                            ConsensusNewBlockHandler::compute_anticone_hashset_bruteforce(inner, *index)
+                            ConsensusNewBlockHandler::compute_anticone_set_bruteforce(inner, *index)
                         };
 
-                        for idx in block_consensus_node_anticone {
-                            if inner.is_same_era(idx, pivot_arena_index) && !anticone_cutoff_epoch_anticone_set.contains(&idx) {
+                        for idx in block_consensus_node_anticone.iter() {
+                            if inner.is_same_era(idx, pivot_arena_index) && !anticone_cutoff_epoch_anticone_set.contains(idx) {
                                 anticone_difficulty +=
                                     U512::from(U256::from(inner.block_weight(
                                         idx
@@ -463,17 +1019,20 @@ impl ConsensusExecutor {
                             >= U512::from(self.handler.machine.params().anticone_penalty_ratio)
                         {
                             no_reward = true;
+                            anticone_overlimited = true;
                         }
                         // LINT.ThenChange(consensus/consensus_executor.
                         // rs#ANTICONE_PENALTY_2)
                     }
                     epoch_block_no_reward.push(no_reward);
+                    epoch_block_anticone_overlimited.push(anticone_overlimited);
                     epoch_block_anticone_difficulties.push(anticone_difficulty);
                 }
                 RewardExecutionInfo {
                     past_block_count: inner.arena[pivot_arena_index].past_num_blocks,
                     epoch_blocks,
                     epoch_block_no_reward,
+                    epoch_block_anticone_overlimited,
                     epoch_block_anticone_difficulties,
                 }
             },
@@ -483,16 +1042,29 @@ impl ConsensusExecutor {
     pub fn get_reward_execution_info(
         &self, inner: &mut ConsensusGraphInner, epoch_arena_index: usize,
     ) -> Option<RewardExecutionInfo> {
-        self.get_reward_execution_info_from_index(
-            inner,
-            inner.get_pivot_reward_index(epoch_arena_index),
+        let reward_index =
+            self.get_pivot_reward_index(inner, epoch_arena_index);
+        self.get_reward_execution_info_from_index(inner, reward_index)
+    }
+
+    /// Return the reward epoch pivot block and the anticone penalty cutoff
+    /// epoch pivot block of `epoch_arena_index` with the reward window in the
+    /// chain spec.
+    pub fn get_pivot_reward_index(
+        &self, inner: &ConsensusGraphInner, epoch_arena_index: usize,
+    ) -> Option<(usize, usize)> {
+        let params = self.handler.machine.params();
+        inner.get_pivot_reward_index(
+            epoch_arena_index,
+            params.reward_epoch_count,
+            params.anticone_penalty_upper_epoch_count,
         )
     }
 
     /// Wait for the deferred state to be executed and compute `state_valid` and
     /// `blame_info` for `me`.
     fn wait_and_compute_state_valid_and_blame_info(
-        &self, me: usize, inner_lock: &RwLock<ConsensusGraphInner>,
+        &self, me: usize, inner_lock: &InstrumentedRwLock<ConsensusGraphInner>,
     ) -> Result<(), String> {
         // TODO:
         //  can we only wait for the deferred block?
@@ -509,6 +1081,14 @@ impl ConsensusExecutor {
         for state_block_hash in waiting_blocks {
             self.wait_for_result(state_block_hash)?;
         }
+        let header = {
+            let inner = inner_lock.read();
+            inner.data_man.block_header_by_hash(&inner.arena[me].hash)
+        };
+        if let Some(header) = header {
+            self.enqueue_stale_receipts_recompute(&header, &*inner_lock.read());
+        }
+        self.run_receipts_recomputes();
         // Now we need to wait for the execution information of all missing
         // blocks to come back
         // TODO: can we merge the state valid computation into the consensus
@@ -547,7 +1127,7 @@ impl ConsensusExecutor {
 
     pub fn get_blame_and_deferred_state_for_generation(
         &self, parent_block_hash: &H256,
-        inner_lock: &RwLock<ConsensusGraphInner>,
+        inner_lock: &InstrumentedRwLock<ConsensusGraphInner>,
     ) -> Result<StateBlameInfo, String>
     {
         let (parent_arena_index, last_state_block) = {
@@ -562,10 +1142,7 @@ impl ConsensusExecutor {
             (
                 *parent_opt.unwrap(),
                 inner
-                    .get_state_block_with_delay(
-                        parent_block_hash,
-                        DEFERRED_STATE_EPOCH_COUNT as usize - 1,
-                    )?
+                    .get_deferred_state_block_for_child(parent_block_hash)?
                     .clone(),
             )
         };
@@ -597,14 +1174,43 @@ impl ConsensusExecutor {
     /// The parameters are needed for the thread to execute this epoch without
     /// holding inner lock.
     pub fn enqueue_epoch(&self, task: EpochExecutionTask) -> bool {
-        if !self.consensus_graph_bench_mode {
-            self.sender
-                .lock()
-                .send(ExecutionTask::ExecuteEpoch(task))
-                .is_ok()
+        if let Some(mock_backend) = &self.mock_backend {
+            mock_backend.execute_epoch(
+                task.epoch_hash,
+                &task.epoch_block_hashes,
+                task.start_block_number,
+            );
         } else {
-            true
+            if self.stopped.load(Relaxed) {
+                return false;
+            }
+            if let Some(prefetcher) = &self.dependency_prefetcher {
+                prefetcher.prefetch(
+                    task.epoch_hash,
+                    &task.epoch_block_hashes,
+                    task.reward_info
+                        .as_ref()
+                        .map(|info| info.epoch_blocks.as_slice()),
+                );
+            }
+            self.send_task(ExecutionTask::ExecuteEpoch(task));
         }
+        true
+    }
+
+    /// Enqueue the epoch as a replay task, which is scheduled fairly with the
+    /// epochs on the pivot chain according to the configured weights.
+    ///
+    /// The replay tasks are executed one at a time in the order they are
+    /// enqueued, so the epochs must be enqueued parent before child, starting
+    /// from an epoch whose parent state exists or is computed by a task
+    /// enqueued before it, as `plan_state_computation` returns them. Then the
+    /// parent state of a replayed epoch is computed by an earlier replay task
+    /// or by an earlier head task, which it waits for, so the epoch never
+    /// runs before the state of its parent exists.
+    fn enqueue_replay_epoch(&self, mut task: EpochExecutionTask) -> bool {
+        task.class = ExecutionTaskClass::Replay;
+        self.enqueue_epoch(task)
     }
 
     /// Execute the epoch synchronously
@@ -613,8 +1219,27 @@ impl ConsensusExecutor {
         debug_record: Option<&mut ComputeEpochDebugRecord>,
     )
     {
-        if !self.consensus_graph_bench_mode {
-            self.handler.handle_epoch_execution(task, debug_record)
+        if let Some(mock_backend) = &self.mock_backend {
+            mock_backend.execute_epoch(
+                task.epoch_hash,
+                &task.epoch_block_hashes,
+                task.start_block_number,
+            );
+        } else {
+            let origin = task.pipeline_id.origin;
+            let start_time = Instant::now();
+            let start_cpu_time = thread_cpu_time();
+            self.handler.handle_epoch_execution(task, debug_record);
+            let cpu_time = match (start_cpu_time, thread_cpu_time()) {
+                (Some(start), Some(end)) => end.checked_sub(start),
+                _ => None,
+            };
+            self.scheduler
+                .lock()
+                .origin_stats
+                .entry(origin)
+                .or_default()
+                .add(start_time.elapsed(), cpu_time);
         }
     }
 
@@ -624,22 +1249,92 @@ impl ConsensusExecutor {
         self.handler.call_virtual(tx, epoch_id, epoch_size)
     }
 
-    pub fn stop(&self) {
-        // `stopped` is used to allow the execution thread to stopped even the
-        // queue is not empty and `ExecutionTask::Stop` has not been
-        // processed.
-        self.stopped.store(true, Relaxed);
-
-        // We still need this task because otherwise if the execution queue is
-        // empty the execution thread will block on `recv` forever and
-        // unable to check `stopped`
-        self.sender
+    /// Execute `epoch_blocks`, whose pivot block may not be on the pivot chain
+    /// or in the consensus graph yet, without committing the state or storing
+    /// the results. A block producer uses it to compute the deferred roots of
+    /// a candidate block. The state of the parent of the pivot block must be
+    /// available.
+    pub fn dry_run_epoch(
+        &self, epoch_blocks: &Vec<Arc<Block>>, start_block_number: u64,
+        reward_execution_info: &Option<RewardExecutionInfo>,
+    ) -> Result<DryRunEpochResult, String>
+    {
+        self.handler.dry_run_epoch(
+            epoch_blocks,
+            start_block_number,
+            reward_execution_info,
+        )
+    }
+
+    /// Enqueue the receipts recomputes of the deferred epochs of `header` if
+    /// its deferred roots only mismatch the local receipts roots, which may
+    /// be stale. See `ConsensusGraphInner::stale_receipts_epochs`. An epoch
+    /// is enqueued at most once until `wait_for_receipts_recomputes`, which
+    /// is called before the state of a block is checked.
+    pub fn enqueue_stale_receipts_recompute(
+        &self, header: &BlockHeader, inner: &ConsensusGraphInner,
+    ) {
+        if self.mock_backend.is_some() {
+            return;
+        }
+        for task in inner.stale_receipts_epochs(header) {
+            let recompute = {
+                let mut recomputes = self.receipts_recomputes.lock();
+                if recomputes.contains_key(&task.epoch_hash) {
+                    continue;
+                }
+                let recompute = Arc::new(PendingReceiptsRecompute::new(task));
+                recomputes.insert(recompute.task.epoch_hash, recompute.clone());
+                recompute
+            };
+            self.send_task(ExecutionTask::RecomputeReceipts(recompute));
+        }
+    }
+
+    /// Run the enqueued receipts recomputes not taken by a worker yet on this
+    /// thread, and wait for the others. It is called without holding the
+    /// consensus lock, so the lock is not held during the recomputes.
+    fn run_receipts_recomputes(&self) {
+        let recomputes: Vec<_> =
+            self.receipts_recomputes.lock().values().cloned().collect();
+        for recompute in recomputes {
+            recompute.run(&self.handler);
+        }
+    }
+
+    /// Finish the enqueued receipts recomputes like `run_receipts_recomputes`,
+    /// and mark their epochs as recomputed in `inner`.
+    pub fn wait_for_receipts_recomputes(
+        &self, inner: &mut ConsensusGraphInner,
+    ) {
+        let recomputes: Vec<_> = self
+            .receipts_recomputes
             .lock()
-            .send(ExecutionTask::Stop)
-            .expect("execution receiver exists");
-        if let Some(thread) = self.thread.lock().take() {
+            .drain()
+            .map(|(_, recompute)| recompute)
+            .collect();
+        for recompute in recomputes {
+            recompute.run(&self.handler);
+            inner.set_receipts_recomputed(&recompute.task.epoch_hash);
+        }
+    }
+
+    pub fn stop(&self) {
+        // `stopped` is used to allow the execution threads to stopped even the
+        // queue is not empty. It is set with the scheduler lock held, so the
+        // waiting workers will not miss the notification.
+        {
+            let _scheduler = self.scheduler.lock();
+            self.stopped.store(true, Relaxed);
+            self.scheduler_changed.notify_all();
+        }
+        let threads = mem::replace(&mut *self.threads.lock(), Vec::new());
+        for thread in threads {
             thread.join().ok();
         }
+        if let Some(prefetcher) = &self.dependency_prefetcher {
+            prefetcher.stop();
+        }
     }
 
     /// Binary search to find the starting point so we can execute to the end of
@@ -683,16 +1378,81 @@ impl ConsensusExecutor {
     //  this method contains bugs but it's not a big problem since
     //  it's test-rpc only.
     /// This is a blocking call to force the execution engine to compute the
-    /// state of a block immediately
+    /// state of a block immediately. It is only used by the test RPCs which
+    /// generate blocks on forks, so the number of replayed epochs is not
+    /// bounded by `MAX_IMPLICIT_STATE_REPLAY_EPOCHS`.
     pub fn compute_state_for_block(
         &self, block_hash: &H256, inner: &mut ConsensusGraphInner,
-    ) -> Result<(), String> {
+    ) -> Result<(), StateComputationError> {
+        let _timer = MeterTimer::time_func(
+            CONSENSIS_COMPUTE_STATE_FOR_BLOCK_TIMER.as_ref(),
+        );
+        debug!("compute_state_for_block {:?}", block_hash);
+        if self.enqueue_state_computation(
+            block_hash, inner, None, /* max_replay_epochs */
+        )? {
+            self.wait_for_state_computation(block_hash)?;
+        }
+        Ok(())
+    }
+
+    /// Like `compute_state_for_block`, but the inner lock is only held while
+    /// the epochs are enqueued, so consensus is not blocked while waiting for
+    /// the execution. It fails with `TooExpensive` if more than
+    /// `MAX_IMPLICIT_STATE_REPLAY_EPOCHS` epochs need to be replayed, in which
+    /// case the state should be regenerated with a budget through
+    /// `StateRegenerationService`.
+    pub fn compute_state_for_block_unlocked(
+        &self, block_hash: &H256,
+        inner: &InstrumentedRwLock<ConsensusGraphInner>,
+    ) -> Result<(), StateComputationError> {
         let _timer = MeterTimer::time_func(
             CONSENSIS_COMPUTE_STATE_FOR_BLOCK_TIMER.as_ref(),
         );
+        debug!("compute_state_for_block_unlocked {:?}", block_hash);
+        let need_wait = self.enqueue_state_computation(
+            block_hash,
+            &mut *inner.write(),
+            Some(MAX_IMPLICIT_STATE_REPLAY_EPOCHS),
+        )?;
+        if need_wait {
+            self.wait_for_state_computation(block_hash)?;
+        }
+        Ok(())
+    }
+
+    /// Enqueue the epochs needed to compute the state of `block_hash` as
+    /// replay tasks. Return `false` if the state is already computed.
+    fn enqueue_state_computation(
+        &self, block_hash: &H256, inner: &mut ConsensusGraphInner,
+        max_replay_epochs: Option<usize>,
+    ) -> Result<bool, StateComputationError>
+    {
+        let epochs = match self.plan_state_computation(block_hash, inner)? {
+            Some(epochs) => epochs,
+            None => return Ok(false),
+        };
+        if let Some(max_epochs) = max_replay_epochs {
+            if epochs.len() > max_epochs {
+                return Err(StateComputationError::TooExpensive {
+                    epochs_to_replay: epochs.len(),
+                    max_epochs,
+                });
+            }
+        }
+        for epoch_hash in &epochs {
+            self.enqueue_replay_epoch_by_hash(epoch_hash, inner)?;
+        }
+        Ok(true)
+    }
+
+    /// Return the hashes of the epochs to replay in order to compute the state
+    /// of `block_hash`, or `None` if the state is already computed.
+    pub fn plan_state_computation(
+        &self, block_hash: &H256, inner: &ConsensusGraphInner,
+    ) -> Result<Option<Vec<H256>>, String> {
         // If we already computed the state of the block before, we should not
         // do it again
-        debug!("compute_state_for_block {:?}", block_hash);
         {
             let maybe_state_index =
                 self.handler.data_man.get_state_readonly_index(&block_hash);
@@ -706,7 +1466,7 @@ impl ConsensusExecutor {
                 })
             {
                 if let Ok(Some(_)) = maybe_cached_state_result {
-                    return Ok(());
+                    return Ok(None);
                 } else {
                     return Err("Internal storage error".to_owned());
                 }
@@ -741,54 +1501,70 @@ impl ConsensusExecutor {
             ConsensusExecutor::find_start_chain_index(inner, &chain);
         debug!("Start execution from index {}", start_chain_index);
 
+        let mut epochs = Vec::new();
         // We need the state of the fork point to start executing the fork
         if start_chain_index == 0 {
-            let mut last_state_height =
-                if inner.get_pivot_height() > DEFERRED_STATE_EPOCH_COUNT {
-                    inner.get_pivot_height() - DEFERRED_STATE_EPOCH_COUNT
-                } else {
-                    0
-                };
+            let mut last_state_height = inner.best_state_epoch_number();
 
             last_state_height += 1;
             while last_state_height < fork_height {
                 let epoch_arena_index =
                     inner.get_pivot_block_arena_index(last_state_height);
-                let reward_execution_info =
-                    self.get_reward_execution_info(inner, epoch_arena_index);
-                self.enqueue_epoch(EpochExecutionTask::new(
-                    epoch_arena_index,
-                    inner,
-                    reward_execution_info,
-                    false, /* on_local_pivot */
-                    false, /* force_recompute */
-                ));
+                epochs.push(inner.arena[epoch_arena_index].hash);
                 last_state_height += 1;
             }
         }
 
         for fork_chain_index in start_chain_index..chain.len() {
-            let epoch_arena_index = chain[fork_chain_index];
-            let reward_index = inner.get_pivot_reward_index(epoch_arena_index);
-
-            let reward_execution_info =
-                self.get_reward_execution_info_from_index(inner, reward_index);
-            self.enqueue_epoch(EpochExecutionTask::new(
-                epoch_arena_index,
-                inner,
-                reward_execution_info,
-                false, /* on_local_pivot */
-                false, /* force_recompute */
-            ));
+            epochs.push(inner.arena[chain[fork_chain_index]].hash);
         }
 
-        let epoch_execution_result = self.wait_for_result(*block_hash)?;
+        Ok(Some(epochs))
+    }
+
+    /// Enqueue the epoch `epoch_hash` as a replay task, and wait for its
+    /// execution without holding the inner lock.
+    pub fn replay_epoch(
+        &self, epoch_hash: &H256,
+        inner: &InstrumentedRwLock<ConsensusGraphInner>,
+    ) -> Result<(), String> {
+        self.enqueue_replay_epoch_by_hash(epoch_hash, &mut *inner.write())?;
+        self.wait_for_result_in_class(*epoch_hash, ExecutionTaskClass::Replay)
+            .map(|_| ())
+    }
+
+    fn enqueue_replay_epoch_by_hash(
+        &self, epoch_hash: &H256, inner: &mut ConsensusGraphInner,
+    ) -> Result<(), String> {
+        let epoch_arena_index =
+            *inner.hash_to_arena_indices.get(epoch_hash).ok_or_else(|| {
+                format!("Epoch {:?} is not in the consensus graph", epoch_hash)
+            })?;
+        let reward_execution_info =
+            self.get_reward_execution_info(inner, epoch_arena_index);
+        self.enqueue_replay_epoch(EpochExecutionTask::new(
+            epoch_arena_index,
+            inner,
+            reward_execution_info,
+            false, /* on_local_pivot */
+            false, /* force_recompute */
+            ExecutionOrigin::ForkReplay,
+        ));
+        Ok(())
+    }
+
+    fn wait_for_state_computation(
+        &self, block_hash: &H256,
+    ) -> Result<(), String> {
+        let epoch_execution_result = self.wait_for_result_in_class(
+            *block_hash,
+            ExecutionTaskClass::Replay,
+        )?;
         debug!(
             "Epoch {:?} has state_root={:?} receipts_root={:?} logs_bloom_hash={:?}",
-            inner.arena[me].hash, epoch_execution_result.state_root_with_aux_info,
+            block_hash, epoch_execution_result.state_root_with_aux_info,
             epoch_execution_result.receipts_root, epoch_execution_result.logs_bloom_hash
         );
-
         Ok(())
     }
 }
@@ -800,6 +1576,9 @@ pub struct ConsensusExecutionHandler {
     verification_config: VerificationConfig,
     machine: Arc<Machine>,
     execution_state_prefetcher: Option<Arc<ExecutionStatePrefetcher>>,
+    statistics: SharedStatistics,
+    /// `None` if `debug_dump_dir_shadow_execution` is not set.
+    shadow_execution: Option<ShadowExecution>,
 }
 
 impl ConsensusExecutionHandler {
@@ -807,14 +1586,20 @@ impl ConsensusExecutionHandler {
         tx_pool: SharedTransactionPool, data_man: Arc<BlockDataManager>,
         config: ConsensusExecutionConfiguration,
         verification_config: VerificationConfig, machine: Arc<Machine>,
+        statistics: SharedStatistics,
     ) -> Self
     {
+        let shadow_execution = config
+            .debug_dump_dir_shadow_execution
+            .clone()
+            .map(ShadowExecution::new);
         ConsensusExecutionHandler {
             tx_pool,
             data_man,
             config,
             verification_config,
             machine,
+            statistics,
             execution_state_prefetcher: if DEFAULT_EXECUTION_PREFETCH_THREADS
                 > 0
             {
@@ -830,28 +1615,42 @@ impl ConsensusExecutionHandler {
             } else {
                 None
             },
+            shadow_execution,
         }
     }
 
-    /// Always return `true` for now
-    fn handle_execution_work(&self, task: ExecutionTask) -> bool {
+    fn handle_execution_work(&self, task: ExecutionTask) {
         debug!("Receive execution task: {:?}", task);
         match task {
             ExecutionTask::ExecuteEpoch(task) => {
                 self.handle_epoch_execution(task, None)
             }
             ExecutionTask::GetResult(task) => self.handle_get_result_task(task),
-            ExecutionTask::Stop => return false,
+            ExecutionTask::RecomputeReceipts(recompute) => recompute.run(self),
         }
-        true
     }
 
     fn handle_epoch_execution(
         &self, task: EpochExecutionTask,
-        debug_record: Option<&mut ComputeEpochDebugRecord>,
+        mut debug_record: Option<&mut ComputeEpochDebugRecord>,
     )
     {
         let _timer = MeterTimer::time_func(CONSENSIS_EXECUTION_TIMER.as_ref());
+        debug!(
+            "Start execution {} of epoch {:?}",
+            task.pipeline_id, task.epoch_hash
+        );
+        if let Some(debug_record) = &mut debug_record {
+            debug_record.pipeline_id = task.pipeline_id.to_string();
+        }
+        // The epoch executed optimistically is executed again when the pivot
+        // chain reaches it, instead of being skipped.
+        let optimistic_commitment = match &self.shadow_execution {
+            Some(shadow_execution) if !task.optimistic => {
+                shadow_execution.take_optimistic(&task.epoch_hash)
+            }
+            _ => None,
+        };
         self.compute_epoch(
             &task.epoch_hash,
             &task.epoch_block_hashes,
@@ -859,8 +1658,26 @@ impl ConsensusExecutionHandler {
             &task.reward_info,
             task.on_local_pivot,
             debug_record,
-            task.force_recompute,
+            task.force_recompute || optimistic_commitment.is_some(),
         );
+        if let Some(shadow_execution) = &self.shadow_execution {
+            let commitment = match self
+                .data_man
+                .get_epoch_execution_commitment(&task.epoch_hash)
+            {
+                Some(commitment) => commitment.clone(),
+                None => return,
+            };
+            if task.optimistic {
+                shadow_execution.record_optimistic(task.epoch_hash, commitment);
+            } else if let Some(optimistic_commitment) = optimistic_commitment {
+                shadow_execution.check(
+                    task.epoch_hash,
+                    &optimistic_commitment,
+                    &commitment,
+                );
+            }
+        }
     }
 
     fn handle_get_result_task(&self, task: GetExecutionResultTask) {
@@ -922,6 +1739,16 @@ impl ConsensusExecutionHandler {
                 .expect("must exists");
 
             if on_local_pivot {
+                // The pivot chain may switch back to an executed fork.
+                self.data_man.insert_pivot_epoch_index(
+                    pivot_block_header.height(),
+                    epoch_hash,
+                );
+                self.data_man.insert_sender_transaction_index(
+                    pivot_block_header.height(),
+                    epoch_hash,
+                    epoch_block_hashes,
+                );
                 // Unwrap is safe here because it's guaranteed by outer if.
                 let state_root = &self
                     .data_man
@@ -977,39 +1804,71 @@ impl ConsensusExecutionHandler {
             epoch_blocks.len(),
         );
 
-        let mut state = State::new(StateDb::new(
-            self.data_man
-                .storage_manager
-                .get_state_for_next_epoch(StateIndex::new_for_next_epoch(
+        let snapshot_epoch_count = self.data_man.get_snapshot_epoch_count();
+        let parent_state_index = StateIndex::new_for_next_epoch(
+            pivot_block.block_header.parent_hash(),
+            &self
+                .data_man
+                .get_epoch_execution_commitment(
                     pivot_block.block_header.parent_hash(),
-                    &self
-                        .data_man
-                        .get_epoch_execution_commitment(
-                            pivot_block.block_header.parent_hash(),
-                        )
+                )
+                // Unwrapping is safe because the state exists.
+                .unwrap()
+                .state_root_with_aux_info,
+            pivot_block.block_header.height() - 1,
+            snapshot_epoch_count,
+        );
+        // A checkpoint is committed as a state at the same height as the
+        // epoch, so it cannot be taken in an epoch shifting the snapshot.
+        let can_checkpoint = on_local_pivot
+            && debug_record.is_none()
+            && parent_state_index.maybe_delta_trie_height
+                != Some(snapshot_epoch_count);
+        let checkpoint_interval = self
+            .config
+            .epoch_execution_checkpoint_interval
+            .filter(|interval| {
+                can_checkpoint
+                    && *interval > 0
+                    && epoch_blocks.len() > *interval
+            });
+        let maybe_resumed = match checkpoint_interval {
+            Some(_) => self.resume_epoch_execution(epoch_hash, &epoch_blocks),
+            None => None,
+        };
+        let (mut state, resumption) = match maybe_resumed {
+            Some((state, resumption)) => (state, Some(resumption)),
+            None => (
+                State::new(StateDb::new(
+                    self.data_man
+                        .storage_manager
+                        .get_state_for_next_epoch(parent_state_index)
+                        .expect("No db error")
                         // Unwrapping is safe because the state exists.
-                        .unwrap()
-                        .state_root_with_aux_info,
-                    pivot_block.block_header.height() - 1,
-                    self.data_man.get_snapshot_epoch_count(),
+                        .expect("State exists"),
                 ))
-                .expect("No db error")
-                // Unwrapping is safe because the state exists.
-                .expect("State exists"),
-        ))
-        .expect("Failed to initialize state");
+                .expect("Failed to initialize state"),
+                None,
+            ),
+        };
 
-        let epoch_receipts = self
-            .process_epoch_transactions(
-                *epoch_hash,
-                &mut state,
-                &epoch_blocks,
-                start_block_number,
-                on_local_pivot,
-            )
-            // TODO: maybe propagate the error all the way up so that the
-            // program may restart by itself.
-            .expect("Can not handle db error in consensus, crashing.");
+        // The receipts of the whole epoch are not held at the same time.
+        let mut receipts_funnel =
+            EpochReceiptsFunnel::with_capacity(epoch_blocks.len());
+        self.process_epoch_transactions(
+            *epoch_hash,
+            &mut state,
+            &epoch_blocks,
+            start_block_number,
+            on_local_pivot,
+            true, /* persist_results */
+            checkpoint_interval,
+            resumption,
+            |block_receipts| receipts_funnel.push(&block_receipts),
+        )
+        // TODO: maybe propagate the error all the way up so that the
+        // program may restart by itself.
+        .expect("Can not handle db error in consensus, crashing.");
 
         if let Some(reward_execution_info) = reward_execution_info {
             // Calculate the block reward for blocks inside the epoch
@@ -1049,9 +1908,24 @@ impl ConsensusExecutionHandler {
         self.data_man.insert_epoch_execution_commitment(
             pivot_block.hash(),
             state_root.clone(),
-            compute_receipts_root(&epoch_receipts),
-            BlockHeaderBuilder::compute_block_logs_bloom_hash(&epoch_receipts),
+            receipts_funnel.receipts_root(),
+            receipts_funnel.logs_bloom_hash(),
         );
+        if checkpoint_interval.is_some() {
+            self.remove_epoch_execution_checkpoint(epoch_hash, &state_root);
+        }
+
+        if on_local_pivot {
+            self.data_man.insert_pivot_epoch_index(
+                pivot_block.block_header.height(),
+                epoch_hash,
+            );
+            self.data_man.insert_sender_transaction_index(
+                pivot_block.block_header.height(),
+                epoch_hash,
+                epoch_block_hashes,
+            );
+        }
 
         // persist block number index
         for (index, hash) in epoch_block_hashes.iter().enumerate() {
@@ -1075,12 +1949,194 @@ impl ConsensusExecutionHandler {
             .adjust_upper_bound(&pivot_block.block_header);
     }
 
-    fn process_epoch_transactions(
+    /// Load the checkpoint of the unfinished execution of an epoch, with the
+    /// state and the receipts of the blocks executed before it. Return `None`
+    /// if any of them is not available, and the epoch is executed from the
+    /// start.
+    fn resume_epoch_execution(
+        &self, epoch_hash: &H256, epoch_blocks: &Vec<Arc<Block>>,
+    ) -> Option<(State, EpochExecutionResumption)> {
+        let checkpoint =
+            self.data_man.epoch_execution_checkpoint(epoch_hash)?;
+        let executed_block_count = checkpoint.executed_block_count as usize;
+        if checkpoint.block_count != epoch_blocks.len() as u64
+            || executed_block_count == 0
+            || executed_block_count >= epoch_blocks.len()
+        {
+            warn!(
+                "Ignore the mismatched execution checkpoint of epoch {:?}: \
+                 {:?}",
+                epoch_hash, checkpoint
+            );
+            return None;
+        }
+
+        let mut block_receipts = Vec::with_capacity(executed_block_count);
+        for block in &epoch_blocks[..executed_block_count] {
+            let execution_result =
+                self.data_man.block_execution_result_by_hash_with_epoch(
+                    &block.hash(),
+                    epoch_hash,
+                    false, /* update_pivot_assumption */
+                    false, /* update_cache */
+                )?;
+            block_receipts.push(execution_result.block_receipts);
+        }
+
+        let state_index = self.epoch_execution_checkpoint_state_index(
+            epoch_blocks.last().expect("Epoch not empty"),
+            &checkpoint,
+        );
+        let storage_state = match self
+            .data_man
+            .storage_manager
+            .get_state_for_next_epoch(state_index)
+        {
+            Ok(Some(storage_state)) => storage_state,
+            _ => {
+                warn!(
+                    "The state of the execution checkpoint of epoch {:?} is \
+                     not available",
+                    epoch_hash
+                );
+                return None;
+            }
+        };
+        let state = State::new(StateDb::new(storage_state)).ok()?;
+        info!(
+            "Resume the execution of epoch {:?} after {} of {} blocks",
+            epoch_hash,
+            executed_block_count,
+            epoch_blocks.len()
+        );
+        Some((
+            state,
+            EpochExecutionResumption {
+                checkpoint,
+                block_receipts,
+            },
+        ))
+    }
+
+    /// Commit the state after executing the first `executed_block_count`
+    /// blocks of the epoch, and continue the execution with the committed
+    /// state.
+    fn checkpoint_epoch_execution(
+        &self, state: &mut State, epoch_blocks: &Vec<Arc<Block>>,
+        executed_block_count: usize,
+        before_user_transactions: &Vec<SystemTransactionReceipt>,
+    ) -> DbResult<()>
+    {
+        let pivot_block = epoch_blocks.last().expect("Epoch not empty");
+        let epoch_hash = pivot_block.hash();
+        let mut checkpoint_id_preimage = epoch_hash.as_bytes().to_vec();
+        checkpoint_id_preimage
+            .extend_from_slice(&(executed_block_count as u64).to_le_bytes());
+        let checkpoint_epoch_id = keccak(&checkpoint_id_preimage);
+
+        // The checkpoint is not the state of any epoch, so the transaction
+        // pool is only notified when the epoch is committed.
+        let state_root = state.commit(checkpoint_epoch_id, None)?;
+        // The earlier checkpoints of the epoch, including those of an
+        // interrupted execution, are removed with this one.
+        let earlier_checkpoint_epoch_ids = self
+            .data_man
+            .epoch_execution_checkpoint(&epoch_hash)
+            .map(|checkpoint| checkpoint.all_checkpoint_epoch_ids())
+            .unwrap_or_default();
+        let checkpoint = EpochExecutionCheckpoint {
+            block_count: epoch_blocks.len() as u64,
+            executed_block_count: executed_block_count as u64,
+            checkpoint_epoch_id,
+            state_root_with_aux_info: state_root,
+            before_user_transactions: before_user_transactions.clone(),
+            earlier_checkpoint_epoch_ids,
+        };
+        let state_index = self
+            .epoch_execution_checkpoint_state_index(pivot_block, &checkpoint);
+        let mut next_state = State::new(StateDb::new(
+            self.data_man
+                .storage_manager
+                .get_state_for_next_epoch(state_index)?
+                .expect("The committed state exists"),
+        ))?;
+        state.move_accounts_to_notify(&mut next_state);
+        *state = next_state;
+        self.data_man
+            .insert_epoch_execution_checkpoint(&epoch_hash, &checkpoint);
+        debug!(
+            "Checkpoint the execution of epoch {:?} after {} blocks",
+            epoch_hash, executed_block_count
+        );
+        Ok(())
+    }
+
+    /// Remove the checkpoints of an epoch after its state is committed. The
+    /// parent of the epoch state is set to the parent epoch instead of the
+    /// last checkpoint.
+    fn remove_epoch_execution_checkpoint(
+        &self, epoch_hash: &H256, state_root: &StateRootWithAuxInfo,
+    ) {
+        let checkpoint =
+            match self.data_man.epoch_execution_checkpoint(epoch_hash) {
+                Some(checkpoint) => checkpoint,
+                None => return,
+            };
+        if let Err(e) = self
+            .data_man
+            .storage_manager
+            .get_storage_manager()
+            .remove_transient_epoch_states(
+                &state_root.aux_info.snapshot_epoch_id,
+                epoch_hash,
+                &checkpoint.all_checkpoint_epoch_ids(),
+            )
+        {
+            warn!(
+                "Failed to remove the execution checkpoints of epoch {:?}: \
+                 {:?}",
+                epoch_hash, e
+            );
+        }
+        self.data_man.remove_epoch_execution_checkpoint(epoch_hash);
+    }
+
+    /// The checkpoint state is used as if it were the state of the parent
+    /// epoch.
+    fn epoch_execution_checkpoint_state_index(
+        &self, pivot_block: &Block, checkpoint: &EpochExecutionCheckpoint,
+    ) -> StateIndex {
+        StateIndex::new_for_next_epoch(
+            &checkpoint.checkpoint_epoch_id,
+            &checkpoint.state_root_with_aux_info,
+            pivot_block.block_header.height() - 1,
+            self.data_man.get_snapshot_epoch_count(),
+        )
+    }
+
+    /// Execute the transactions in the epoch, and pass the receipts of each
+    /// block to `on_block_receipts` in order.
+    ///
+    /// With `checkpoint_interval`, an `EpochExecutionCheckpoint` is taken
+    /// every that number of blocks. With `resumption`, the blocks before its
+    /// checkpoint are not executed again, and their stored receipts are
+    /// passed to `on_block_receipts`. Without `persist_results`, the receipts
+    /// and the traces are not kept in the data manager. The receipts of the
+    /// system transactions of the epoch are returned.
+    fn process_epoch_transactions<F>(
         &self, epoch_id: EpochId, state: &mut State,
         epoch_blocks: &Vec<Arc<Block>>, start_block_number: u64,
-        on_local_pivot: bool,
-    ) -> DbResult<Vec<Arc<BlockReceipts>>>
+        on_local_pivot: bool, persist_results: bool,
+        checkpoint_interval: Option<usize>,
+        resumption: Option<EpochExecutionResumption>, mut on_block_receipts: F,
+    ) -> DbResult<EpochSystemTransactionReceipts>
+    where
+        F: FnMut(Arc<BlockReceipts>),
     {
+        let first_block_index = resumption
+            .as_ref()
+            .map_or(0, |resumption| resumption.block_receipts.len());
+
         // Prefetch accounts for transactions.
         // The return value _prefetch_join_handles is used to join all threads
         // before the exit of this function.
@@ -1090,7 +2146,7 @@ impl ConsensusExecutionHandler {
         {
             Some(prefetcher) => {
                 let mut accounts = vec![];
-                for block in epoch_blocks.iter() {
+                for block in epoch_blocks.iter().skip(first_block_index) {
                     for transaction in block.transactions.iter() {
                         accounts.push(&transaction.sender);
                         match transaction.action {
@@ -1118,14 +2174,83 @@ impl ConsensusExecutionHandler {
         drop(prefetch_join_handles);
 
         let pivot_block = epoch_blocks.last().expect("Epoch not empty");
-        let mut epoch_receipts = Vec::with_capacity(epoch_blocks.len());
+        let max_sender_gas_per_epoch = self
+            .machine
+            .params()
+            .max_sender_gas_per_epoch(pivot_block.block_header.height());
         let mut to_pending = Vec::new();
+        // The positions where the transactions are executed in this epoch.
+        // A transaction included in more than one block of an epoch is
+        // executed at its first inclusion in the execution order whose
+        // execution bumps the nonce. Its later inclusions are not executed and
+        // get the same receipts as the transactions dropped for stale nonces,
+        // with the executed position in the error messages.
+        let mut executed_positions: HashMap<H256, TransactionIndex> =
+            HashMap::new();
+        let mut packing_counters = EpochPackingCounters::default();
+        // The gas used by each sender in this epoch, only tracked with
+        // `max_sender_gas_per_epoch`.
+        let mut sender_gas_used: HashMap<Address, U256> = HashMap::new();
+        // The execution order of the transactions of each sender, only
+        // tracked on the local pivot chain for diagnosing the skipped ones.
+        let mut sender_orders: Option<HashMap<Address, Vec<SenderTxRecord>>> =
+            if on_local_pivot {
+                Some(HashMap::new())
+            } else {
+                None
+            };
+        let mut system_transaction_receipts =
+            EpochSystemTransactionReceipts::default();
         let mut block_number = start_block_number;
         let mut last_block_hash =
             pivot_block.block_header.parent_hash().clone();
-        for block in epoch_blocks.iter() {
+        if let Some(resumption) = resumption {
+            // Restore the execution context of the blocks before the
+            // checkpoint from their receipts. The packing counters only count
+            // the blocks executed after it.
+            system_transaction_receipts.before_user_transactions =
+                resumption.checkpoint.before_user_transactions;
+            for (block, block_receipts) in
+                epoch_blocks.iter().zip(resumption.block_receipts)
+            {
+                let mut accumulated_gas_used = U256::zero();
+                for (idx, (transaction, receipt)) in block
+                    .transactions
+                    .iter()
+                    .zip(block_receipts.receipts.iter())
+                    .enumerate()
+                {
+                    if receipt.outcome_status
+                        != TRANSACTION_OUTCOME_EXCEPTION_WITHOUT_NONCE_BUMPING
+                    {
+                        executed_positions.insert(
+                            transaction.hash(),
+                            TransactionIndex {
+                                block_hash: block.hash(),
+                                index: idx,
+                            },
+                        );
+                    }
+                    if max_sender_gas_per_epoch.is_some() {
+                        *sender_gas_used
+                            .entry(transaction.sender)
+                            .or_default() +=
+                            receipt.accumulated_gas_used - accumulated_gas_used;
+                    }
+                    accumulated_gas_used = receipt.accumulated_gas_used;
+                }
+                on_block_receipts(block_receipts);
+            }
+            block_number += first_block_index as u64;
+            last_block_hash = epoch_blocks[first_block_index - 1].hash();
+        }
+        for (block_index, block) in
+            epoch_blocks.iter().enumerate().skip(first_block_index)
+        {
             let mut tx_exec_error_messages =
                 Vec::with_capacity(block.transactions.len());
+            let mut tx_exec_error_infos =
+                Vec::with_capacity(block.transactions.len());
             let mut receipts = Vec::new();
             debug!(
                 "process txs in block: hash={:?}, tx count={:?}",
@@ -1153,12 +2278,105 @@ impl ConsensusExecutionHandler {
                 self.machine.internal_contracts().initialized_at(env.number),
                 spec.contract_start_nonce,
             );
+            if block_index == 0 {
+                system_transaction_receipts.before_user_transactions = self
+                    .execute_system_transactions(
+                        SystemTransactionPhase::BeforeUserTransactions,
+                        state,
+                        &env,
+                        &spec,
+                    )?;
+            }
             block_number += 1;
 
             last_block_hash = block.hash();
             let mut block_traces: Vec<TransactionExecTraces> =
                 Default::default();
             for (idx, transaction) in block.transactions.iter().enumerate() {
+                packing_counters.transaction_count += 1;
+                if let Some(executed_position) =
+                    executed_positions.get(&transaction.hash())
+                {
+                    packing_counters.duplicated_count += 1;
+                    trace!(
+                        "tx already executed in this epoch: transaction={:?}, executed_position={:?}",
+                        transaction, executed_position
+                    );
+                    receipts.push(Receipt::new(
+                        TRANSACTION_OUTCOME_EXCEPTION_WITHOUT_NONCE_BUMPING,
+                        env.accumulated_gas_used,
+                        U256::zero(),
+                        false, /* gas_sponsor_paid */
+                        Vec::new(),
+                        false, /* storage_sponsor_paid */
+                        Vec::new(),
+                        Vec::new(),
+                    ));
+                    tx_exec_error_messages
+                        .push(TX_ALREADY_EXECUTED_ERROR_MESSAGE.into());
+                    tx_exec_error_infos.push(TxExecutionErrorInfo {
+                        executed_position: Some(executed_position.clone()),
+                        revert_reason: None,
+                    });
+                    if self.config.executive_trace {
+                        block_traces.push(Vec::<ExecTrace>::new().into());
+                    }
+                    record_sender_tx(
+                        &mut sender_orders,
+                        transaction,
+                        block.hash(),
+                        idx,
+                        Some(TxSkipReason::Duplicated {
+                            executed_block_hash: executed_position.block_hash,
+                            executed_index: executed_position.index,
+                        }),
+                    );
+                    continue;
+                }
+                if let Some(max_sender_gas) = max_sender_gas_per_epoch {
+                    let used = sender_gas_used
+                        .get(&transaction.sender)
+                        .cloned()
+                        .unwrap_or_default();
+                    if used + transaction.gas > max_sender_gas {
+                        packing_counters.sender_gas_budget_exceeded_count += 1;
+                        trace!(
+                            "tx skipped for the sender gas budget: \
+                             transaction={:?}, used={}",
+                            transaction,
+                            used
+                        );
+                        receipts.push(Receipt::new(
+                            TRANSACTION_OUTCOME_EXCEPTION_WITHOUT_NONCE_BUMPING,
+                            env.accumulated_gas_used,
+                            U256::zero(),
+                            false, /* gas_sponsor_paid */
+                            Vec::new(),
+                            false, /* storage_sponsor_paid */
+                            Vec::new(),
+                            Vec::new(),
+                        ));
+                        tx_exec_error_messages
+                            .push("sender gas budget exceeded".into());
+                        tx_exec_error_infos
+                            .push(TxExecutionErrorInfo::default());
+                        if self.config.executive_trace {
+                            block_traces.push(Vec::<ExecTrace>::new().into());
+                        }
+                        if on_local_pivot {
+                            to_pending.push(transaction.clone());
+                        }
+                        record_sender_tx(
+                            &mut sender_orders,
+                            transaction,
+                            block.hash(),
+                            idx,
+                            Some(TxSkipReason::SenderGasBudgetExceeded),
+                        );
+                        continue;
+                    }
+                }
+                let accumulated_gas_used_before = env.accumulated_gas_used;
                 let tx_outcome_status;
                 let mut transaction_logs = Vec::new();
                 let mut storage_released = Vec::new();
@@ -1178,8 +2396,21 @@ impl ConsensusExecutionHandler {
                 let mut gas_sponsor_paid = false;
                 let mut storage_sponsor_paid = false;
                 let tx_exec_error_msg: String;
+                let revert_reason = r.revert_reason();
+                let skip_reason;
                 match r {
                     ExecutionOutcome::NotExecutedDrop(e) => {
+                        skip_reason = Some(match &e {
+                            TxDropError::OldNonce(expected, _) => {
+                                packing_counters.stale_nonce_count += 1;
+                                TxSkipReason::NonceTooOld {
+                                    expected: *expected,
+                                }
+                            }
+                            TxDropError::InvalidRecipientAddress(_) => {
+                                TxSkipReason::InvalidRecipient
+                            }
+                        });
                         tx_outcome_status =
                             TRANSACTION_OUTCOME_EXCEPTION_WITHOUT_NONCE_BUMPING;
                         tx_exec_error_msg = "tx not executed".into();
@@ -1195,6 +2426,27 @@ impl ConsensusExecutionHandler {
                         }
                     }
                     ExecutionOutcome::NotExecutedToReconsiderPacking(e) => {
+                        skip_reason = Some(match &e {
+                            ToRepackError::InvalidNonce {
+                                expected, ..
+                            } => {
+                                packing_counters.future_nonce_count += 1;
+                                TxSkipReason::NonceTooNew {
+                                    expected: *expected,
+                                }
+                            }
+                            ToRepackError::NotEnoughCashFromSponsor {
+                                ..
+                            }
+                            | ToRepackError::SenderDoesNotExist => {
+                                packing_counters.insufficient_balance_count +=
+                                    1;
+                                TxSkipReason::InsufficientBalance
+                            }
+                            ToRepackError::EpochHeightOutOfBound { .. } => {
+                                TxSkipReason::EpochHeightOutOfBound
+                            }
+                        });
                         tx_outcome_status =
                             TRANSACTION_OUTCOME_EXCEPTION_WITHOUT_NONCE_BUMPING;
                         tx_exec_error_msg = "tx not executed".into();
@@ -1221,15 +2473,21 @@ impl ConsensusExecutionHandler {
                         error,
                         executed,
                     ) => {
+                        skip_reason =
+                            if let ExecutionError::NotEnoughCash { .. } = error
+                            {
+                                packing_counters.insufficient_balance_count +=
+                                    1;
+                                Some(TxSkipReason::InsufficientBalance)
+                            } else {
+                                None
+                            };
                         tx_outcome_status =
                             TRANSACTION_OUTCOME_EXCEPTION_WITH_NONCE_BUMPING;
                         tx_exec_error_msg = if error
                             == ExecutionError::VmError(VmErr::Reverted)
                         {
-                            format!(
-                                "Vm reverted, {}",
-                                revert_reason_decode(&executed.output)
-                            )
+                            reverted_error_message(&executed.output)
                         } else {
                             format!("{:?}", error)
                         };
@@ -1251,6 +2509,7 @@ impl ConsensusExecutionHandler {
                     ExecutionOutcome::Finished(executed) => {
                         tx_outcome_status = TRANSACTION_OUTCOME_SUCCESS;
                         tx_exec_error_msg = String::default();
+                        skip_reason = None;
                         GOOD_TPS_METER.mark(1);
 
                         env.accumulated_gas_used += executed.gas_used;
@@ -1283,23 +2542,49 @@ impl ConsensusExecutionHandler {
                 );
                 receipts.push(receipt);
                 tx_exec_error_messages.push(tx_exec_error_msg);
+                tx_exec_error_infos.push(TxExecutionErrorInfo {
+                    executed_position: None,
+                    revert_reason,
+                });
+                record_sender_tx(
+                    &mut sender_orders,
+                    transaction,
+                    block.hash(),
+                    idx,
+                    skip_reason,
+                );
+                if max_sender_gas_per_epoch.is_some() {
+                    *sender_gas_used.entry(transaction.sender).or_default() +=
+                        env.accumulated_gas_used - accumulated_gas_used_before;
+                }
 
-                if on_local_pivot {
+                if tx_outcome_status
+                    != TRANSACTION_OUTCOME_EXCEPTION_WITHOUT_NONCE_BUMPING
+                {
                     let hash = transaction.hash();
                     let tx_index = TransactionIndex {
                         block_hash: block.hash(),
                         index: idx,
                     };
-                    if tx_outcome_status
-                        != TRANSACTION_OUTCOME_EXCEPTION_WITHOUT_NONCE_BUMPING
-                    {
+                    if on_local_pivot {
                         self.data_man
                             .insert_transaction_index(&hash, &tx_index);
                     }
+                    executed_positions.insert(hash, tx_index);
                 }
             }
 
-            if self.config.executive_trace {
+            if block_index + 1 == epoch_blocks.len() {
+                system_transaction_receipts.after_user_transactions = self
+                    .execute_system_transactions(
+                        SystemTransactionPhase::AfterUserTransactions,
+                        state,
+                        &env,
+                        &spec,
+                    )?;
+            }
+
+            if self.config.executive_trace && persist_results {
                 self.data_man.insert_block_traces(
                     block.hash(),
                     block_traces.into(),
@@ -1313,23 +2598,189 @@ impl ConsensusExecutionHandler {
                 block_number,
                 secondary_reward,
                 tx_execution_error_messages: tx_exec_error_messages,
+                tx_execution_error_infos: tx_exec_error_infos,
             });
-            self.data_man.insert_block_execution_result(
-                block.hash(),
-                pivot_block.hash(),
-                block_receipts.clone(),
-                on_local_pivot,
-            );
+            if persist_results {
+                self.data_man.insert_block_execution_result(
+                    block.hash(),
+                    pivot_block.hash(),
+                    block_receipts.clone(),
+                    on_local_pivot,
+                );
+            }
+            // Like the receipts, only the index on the local pivot chain is
+            // persisted.
+            if self.config.enable_block_log_index && on_local_pivot {
+                self.data_man.insert_block_log_index(
+                    &block.hash(),
+                    &BlockLogIndex::new(
+                        pivot_block.hash(),
+                        &block_receipts.receipts,
+                    ),
+                );
+            }
+
+            on_block_receipts(block_receipts);
+
+            if let Some(interval) = checkpoint_interval {
+                let executed_block_count = block_index + 1;
+                if executed_block_count % interval == 0
+                    && executed_block_count < epoch_blocks.len()
+                {
+                    self.checkpoint_epoch_execution(
+                        state,
+                        epoch_blocks,
+                        executed_block_count,
+                        &system_transaction_receipts.before_user_transactions,
+                    )?;
+                }
+            }
+        }
 
-            epoch_receipts.push(block_receipts);
+        if persist_results && !system_transaction_receipts.is_empty() {
+            self.data_man.insert_epoch_system_transaction_receipts(
+                &pivot_block.hash(),
+                &system_transaction_receipts,
+            );
         }
 
         if on_local_pivot {
             self.tx_pool.recycle_transactions(to_pending);
+            self.statistics.record_epoch_packing(
+                pivot_block.hash(),
+                pivot_block.block_header.height(),
+                packing_counters,
+            );
+        }
+        if let Some(mut senders) = sender_orders {
+            senders.retain(|_, order| {
+                order.iter().any(|tx| tx.skip_reason.is_some())
+            });
+            self.statistics.record_epoch_nonce_order(EpochNonceOrder {
+                epoch_hash: pivot_block.hash(),
+                epoch_height: pivot_block.block_header.height(),
+                senders,
+            });
         }
 
         debug!("Finish processing tx for epoch");
-        Ok(epoch_receipts)
+        Ok(system_transaction_receipts)
+    }
+
+    /// Execute the system transactions of `phase` provided by the machine.
+    /// They are executed in an environment of their own, so the gas used is
+    /// not counted in the block.
+    fn execute_system_transactions(
+        &self, phase: SystemTransactionPhase, state: &mut State, env: &Env,
+        spec: &Spec,
+    ) -> DbResult<Vec<SystemTransactionReceipt>>
+    {
+        let calls = match self.machine.system_transaction_provider() {
+            Some(provider) => {
+                provider.system_transactions(phase, env.epoch_height)
+            }
+            None => return Ok(Vec::new()),
+        };
+        let chain_id = self
+            .machine
+            .params()
+            .chain_id
+            .read()
+            .get_chain_id(env.epoch_height);
+        let mut env = env.clone();
+        env.accumulated_gas_used = U256::zero();
+        let mut receipts = Vec::with_capacity(calls.len());
+        for call in calls {
+            let nonce = state.nonce(&call.sender)?;
+            let transaction =
+                call.into_transaction(nonce, env.epoch_height, chain_id);
+            let base_gas_required = Executive::gas_required_for(
+                transaction.action == Action::Create,
+                &transaction.data,
+                spec,
+            );
+            let (tx_outcome_status, error_message) = if transaction.gas
+                < base_gas_required.into()
+            {
+                (
+                    TRANSACTION_OUTCOME_EXCEPTION_WITHOUT_NONCE_BUMPING,
+                    format!(
+                        "not enough base gas: required={}, got={}",
+                        base_gas_required, transaction.gas
+                    ),
+                )
+            } else {
+                let options = TransactOptions::with_no_tracing();
+                let r =
+                    Executive::new(state, &env, self.machine.as_ref(), spec)
+                        .transact(&transaction, options)?;
+                match r {
+                    ExecutionOutcome::NotExecutedDrop(e) => (
+                        TRANSACTION_OUTCOME_EXCEPTION_WITHOUT_NONCE_BUMPING,
+                        format!("{:?}", e),
+                    ),
+                    ExecutionOutcome::NotExecutedToReconsiderPacking(e) => (
+                        TRANSACTION_OUTCOME_EXCEPTION_WITHOUT_NONCE_BUMPING,
+                        format!("{:?}", e),
+                    ),
+                    ExecutionOutcome::ExecutionErrorBumpNonce(
+                        error,
+                        executed,
+                    ) => {
+                        env.accumulated_gas_used += executed.gas_used;
+                        let error_message = if error
+                            == ExecutionError::VmError(VmErr::Reverted)
+                        {
+                            reverted_error_message(&executed.output)
+                        } else {
+                            format!("{:?}", error)
+                        };
+                        (
+                            TRANSACTION_OUTCOME_EXCEPTION_WITH_NONCE_BUMPING,
+                            error_message,
+                        )
+                    }
+                    ExecutionOutcome::Finished(executed) => {
+                        env.accumulated_gas_used += executed.gas_used;
+                        receipts.push(SystemTransactionReceipt {
+                            transaction_hash: transaction.hash(),
+                            receipt: Receipt::new(
+                                TRANSACTION_OUTCOME_SUCCESS,
+                                env.accumulated_gas_used,
+                                executed.fee,
+                                executed.gas_sponsor_paid,
+                                executed.logs,
+                                executed.storage_sponsor_paid,
+                                executed.storage_collateralized,
+                                executed.storage_released,
+                            ),
+                            error_message: String::default(),
+                        });
+                        continue;
+                    }
+                }
+            };
+            warn!(
+                "System transaction failed: phase={:?}, epoch_height={}, \
+                 transaction={:?}, err={}",
+                phase, env.epoch_height, transaction, error_message
+            );
+            receipts.push(SystemTransactionReceipt {
+                transaction_hash: transaction.hash(),
+                receipt: Receipt::new(
+                    tx_outcome_status,
+                    env.accumulated_gas_used,
+                    U256::zero(),
+                    false, /* gas_sponsor_paid */
+                    Vec::new(),
+                    false, /* storage_sponsor_paid */
+                    Vec::new(),
+                    Vec::new(),
+                ),
+                error_message,
+            });
+        }
+        Ok(receipts)
     }
 
     fn compute_block_base_reward(
@@ -1352,6 +2803,34 @@ impl ConsensusExecutionHandler {
         /// (Fee, SetOfPackingBlockHash)
         struct TxExecutionInfo(U256, BTreeSet<H256>);
 
+        let pivot_height = reward_info
+            .epoch_blocks
+            .last()
+            .expect("Not empty")
+            .block_header
+            .height();
+        // The rewarded blocks are the executed ones, which are bounded when
+        // the epoch set is computed, but a reward info built elsewhere may
+        // not be, so the bound is also enforced here.
+        let bounded_reward_info;
+        let reward_info = match self
+            .machine
+            .params()
+            .epoch_rewarded_block_bound(pivot_height)
+        {
+            Some(bound) if reward_info.epoch_blocks.len() > bound => {
+                warn!(
+                    "Only reward the last {} of {} blocks in epoch {}",
+                    bound,
+                    reward_info.epoch_blocks.len(),
+                    pivot_height
+                );
+                bounded_reward_info = reward_info.truncated(bound);
+                &bounded_reward_info
+            }
+            _ => reward_info,
+        };
+
         let epoch_blocks = &reward_info.epoch_blocks;
         let pivot_block = epoch_blocks.last().expect("Not empty");
         let reward_epoch_hash = pivot_block.hash();
@@ -1360,6 +2839,7 @@ impl ConsensusExecutionHandler {
 
         let epoch_size = epoch_blocks.len();
         let mut epoch_block_total_rewards = Vec::with_capacity(epoch_size);
+        let mut epoch_block_reward_details = Vec::with_capacity(epoch_size);
         // This is the total primary tokens issued in this epoch.
         let mut total_base_reward: U256 = 0.into();
 
@@ -1371,6 +2851,21 @@ impl ConsensusExecutionHandler {
         // Base reward and anticone penalties.
         for (enum_idx, block) in epoch_blocks.iter().enumerate() {
             let no_reward = reward_info.epoch_block_no_reward[enum_idx];
+            let anticone_difficulty =
+                reward_info.epoch_block_anticone_difficulties[enum_idx];
+            let mut reward_details = BlockRewardDetails {
+                epoch_hash: *epoch_later,
+                epoch_difficulty: *epoch_difficulty,
+                base_reward_per_block: U256::try_from(base_reward_per_block)
+                    .unwrap_or(U256::max_value()),
+                anticone_difficulty: U256::try_from(anticone_difficulty)
+                    .unwrap_or(U256::max_value()),
+                anticone_overlimited: reward_info
+                    .epoch_block_anticone_overlimited[enum_idx],
+                no_reward,
+                insufficient_pow_quality: false,
+                anticone_penalty: U256::zero(),
+            };
 
             if no_reward {
                 epoch_block_total_rewards.push(U256::from(0));
@@ -1387,6 +2882,7 @@ impl ConsensusExecutionHandler {
                 let mut reward = if pow_quality >= *epoch_difficulty {
                     base_reward_per_block
                 } else {
+                    reward_details.insufficient_pow_quality = true;
                     debug!(
                         "Block {} pow_quality {} is less than epoch_difficulty {}!",
                         block.hash(), pow_quality, epoch_difficulty
@@ -1403,8 +2899,6 @@ impl ConsensusExecutionHandler {
                 }
 
                 if reward > 0.into() {
-                    let anticone_difficulty =
-                        reward_info.epoch_block_anticone_difficulties[enum_idx];
                     // LINT.IfChange(ANTICONE_PENALTY_2)
                     let anticone_penalty = reward * anticone_difficulty
                         / U512::from(epoch_difficulty)
@@ -1420,6 +2914,8 @@ impl ConsensusExecutionHandler {
 
                     debug_assert!(reward > anticone_penalty);
                     reward -= anticone_penalty;
+                    reward_details.anticone_penalty =
+                        U256::try_from(anticone_penalty).unwrap();
 
                     if debug_record.is_some() {
                         let debug_out = debug_record.as_mut().unwrap();
@@ -1447,6 +2943,7 @@ impl ConsensusExecutionHandler {
                     total_base_reward += reward;
                 }
             }
+            epoch_block_reward_details.push(reward_details);
         }
 
         // Tx fee for each block in this epoch
@@ -1605,6 +3102,10 @@ impl ConsensusExecutionHandler {
                     },
                     true,
                 );
+                self.data_man.insert_block_reward_details(
+                    &block_hash,
+                    &epoch_block_reward_details[enum_idx],
+                );
                 self.data_man
                     .receipts_retain_epoch(&block_hash, &reward_epoch_hash);
             }
@@ -1644,6 +3145,18 @@ impl ConsensusExecutionHandler {
             // The very unlikely case
             state.subtract_total_issued(burnt_fee - new_mint);
         }
+        if on_local_pivot {
+            // Issuance is keyed by the epoch being executed rather than the
+            // rewarded one, so that `total_issued` matches its state.
+            self.data_man.insert_epoch_issuance(
+                epoch_later,
+                &EpochIssuance {
+                    minted: new_mint,
+                    burnt: burnt_fee,
+                    total_issued: state.total_issued_tokens(),
+                },
+            );
+        }
     }
 
     fn recompute_states(
@@ -1677,13 +3190,184 @@ impl ConsensusExecutionHandler {
                 // Unwrapping is safe because the state exists.
                 .unwrap(),
         ))?;
+        let mut epoch_receipts = Vec::with_capacity(epoch_blocks.len());
         self.process_epoch_transactions(
             *pivot_hash,
             &mut state,
             &epoch_blocks,
             start_block_number,
             false,
-        )
+            true, /* persist_results */
+            None, /* checkpoint_interval */
+            None, /* resumption */
+            |block_receipts| epoch_receipts.push(block_receipts),
+        )?;
+        Ok(epoch_receipts)
+    }
+
+    fn dry_run_epoch(
+        &self, epoch_blocks: &Vec<Arc<Block>>, start_block_number: u64,
+        reward_execution_info: &Option<RewardExecutionInfo>,
+    ) -> Result<DryRunEpochResult, String>
+    {
+        let pivot_block = epoch_blocks.last().ok_or("empty epoch")?;
+        let mut state = self.state_for_next_epoch(
+            pivot_block.block_header.parent_hash(),
+            pivot_block.block_header.height() - 1,
+        )?;
+
+        let mut receipts = Vec::with_capacity(epoch_blocks.len());
+        let system_transaction_receipts = self
+            .process_epoch_transactions(
+                pivot_block.hash(),
+                &mut state,
+                epoch_blocks,
+                start_block_number,
+                false, /* on_local_pivot */
+                false, /* persist_results */
+                None,  /* checkpoint_interval */
+                None,  /* resumption */
+                |block_receipts| receipts.push(block_receipts),
+            )
+            .map_err(|e| format!("{:?}", e))?;
+        if let Some(reward_execution_info) = reward_execution_info {
+            self.process_rewards_and_fees(
+                &mut state,
+                reward_execution_info,
+                &pivot_block.hash(),
+                false, /* on_local_pivot */
+                None,  /* debug_record */
+                self.machine.spec(start_block_number).account_start_nonce,
+            );
+        }
+        let state_root = state
+            .compute_state_root(None /* debug_record */)
+            .map_err(|e| format!("{:?}", e))?;
+
+        let mut receipts_funnel =
+            EpochReceiptsFunnel::with_capacity(receipts.len());
+        for block_receipts in &receipts {
+            receipts_funnel.push(block_receipts);
+        }
+        Ok(DryRunEpochResult {
+            state_root,
+            receipts_root: receipts_funnel.receipts_root(),
+            logs_bloom_hash: receipts_funnel.logs_bloom_hash(),
+            receipts,
+            system_transaction_receipts,
+        })
+    }
+
+    /// Execute the epoch of `task` from scratch and compare its receipts root
+    /// with the local commitment. If they differ, the records derived from
+    /// the receipts, i.e. the block receipts, the system transaction
+    /// receipts, the existing log indices of the blocks and the commitment,
+    /// are rewritten, and the new receipts root is returned.
+    ///
+    /// The logs bloom is committed with the same receipts, so nothing is
+    /// rewritten if the recomputed bloom differs from the committed one.
+    pub fn recompute_epoch_receipts(
+        &self, task: &ReceiptsRecomputeTask,
+    ) -> Result<Option<H256>, String> {
+        let commitment = match self
+            .data_man
+            .get_epoch_execution_commitment(&task.epoch_hash)
+        {
+            Some(commitment) => commitment.clone(),
+            None => return Err("the epoch is not executed".into()),
+        };
+        let epoch_blocks = self
+            .data_man
+            .blocks_by_hash_list(
+                &task.epoch_block_hashes,
+                false, /* update_cache */
+            )
+            .ok_or("epoch blocks are missing")?;
+        // The rewards do not change the receipts.
+        let result = self.dry_run_epoch(
+            &epoch_blocks,
+            task.start_block_number,
+            &None, /* reward_execution_info */
+        )?;
+        if result.receipts_root == commitment.receipts_root {
+            return Ok(None);
+        }
+        if result.logs_bloom_hash != commitment.logs_bloom_hash {
+            return Err(format!(
+                "recomputed logs bloom hash {:?} mismatches the committed {:?}",
+                result.logs_bloom_hash, commitment.logs_bloom_hash
+            ));
+        }
+        for (block, block_receipts) in
+            epoch_blocks.iter().zip(result.receipts.into_iter())
+        {
+            // Only the index built in this epoch is used by the log filter.
+            if self
+                .data_man
+                .block_log_index_by_hash_with_epoch(
+                    &block.hash(),
+                    &task.epoch_hash,
+                )
+                .is_some()
+            {
+                self.data_man.insert_block_log_index(
+                    &block.hash(),
+                    &BlockLogIndex::new(
+                        task.epoch_hash,
+                        &block_receipts.receipts,
+                    ),
+                );
+            }
+            self.data_man.insert_block_execution_result(
+                block.hash(),
+                task.epoch_hash,
+                block_receipts,
+                true, /* persistent */
+            );
+        }
+        if result.system_transaction_receipts.is_empty() {
+            self.data_man
+                .remove_epoch_system_transaction_receipts(&task.epoch_hash);
+        } else {
+            self.data_man.insert_epoch_system_transaction_receipts(
+                &task.epoch_hash,
+                &result.system_transaction_receipts,
+            );
+        }
+        self.data_man.insert_epoch_execution_commitment(
+            task.epoch_hash,
+            commitment.state_root_with_aux_info,
+            result.receipts_root,
+            commitment.logs_bloom_hash,
+        );
+        Ok(Some(result.receipts_root))
+    }
+
+    /// Return the uncommitted state to execute the epoch after `epoch_hash`.
+    fn state_for_next_epoch(
+        &self, epoch_hash: &H256, epoch_height: u64,
+    ) -> Result<State, String> {
+        let commitment = self
+            .data_man
+            .get_epoch_execution_commitment(epoch_hash)
+            .ok_or_else(|| {
+                format!("state of epoch {:?} not computed", epoch_hash)
+            })?;
+        State::new(StateDb::new(
+            self.data_man
+                .storage_manager
+                .get_state_for_next_epoch(StateIndex::new_for_next_epoch(
+                    epoch_hash,
+                    &commitment.state_root_with_aux_info,
+                    epoch_height,
+                    self.data_man.get_snapshot_epoch_count(),
+                ))
+                .map_err(|e| format!("{:?}", e))?
+                .ok_or_else(|| {
+                    format!("state of epoch {:?} not available", epoch_hash)
+                })?,
+        ))
+        .map_err(|e| format!("{:?}", e))
     }
 
     pub fn call_virtual(
@@ -1766,4 +3450,928 @@ impl ConsensusExecutionHandler {
 
 pub struct ConsensusExecutionConfiguration {
     pub executive_trace: bool,
+    /// The initial number of the threads executing the epochs, at most
+    /// `MAX_EXECUTION_WORKER_COUNT`. It can be changed at runtime with
+    /// `ConsensusExecutor::set_worker_count`.
+    pub execution_worker_count: usize,
+    /// The weights used to share the execution thread between the epochs on
+    /// the pivot chain and the epochs replayed for `compute_state_for_block`
+    /// when both are pending.
+    pub head_execution_weight: u32,
+    pub replay_execution_weight: u32,
+    /// Whether to load the blocks, the parent state and the reward receipts
+    /// of an epoch into the caches when it is enqueued.
+    pub enable_dependency_prefetch: bool,
+    /// The maximum number of epochs re-executed at the same time to verify
+    /// the execution results claimed by peers. 0 disables the verification.
+    pub execution_verification_concurrency: usize,
+    /// Whether to write a `BlockLogIndex` with the receipts of each block.
+    pub enable_block_log_index: bool,
+    /// The number of blocks between the `EpochExecutionCheckpoint`s in an
+    /// epoch on the local pivot chain. `None` disables the checkpoints.
+    pub epoch_execution_checkpoint_interval: Option<usize>,
+    /// If set, the optimistically executed epochs are executed again when the
+    /// pivot chain reaches them, and the mismatched results are dumped to
+    /// this directory.
+    pub debug_dump_dir_shadow_execution: Option<String>,
+}
+
+/// The execution of an epoch resumed from its `EpochExecutionCheckpoint`.
+struct EpochExecutionResumption {
+    checkpoint: EpochExecutionCheckpoint,
+    /// The receipts of the blocks executed before the checkpoint.
+    block_receipts: Vec<Arc<BlockReceipts>>,
+}
+
+/// Append `transaction` to the execution order of its sender if the orders
+/// are tracked.
+fn record_sender_tx(
+    sender_orders: &mut Option<HashMap<Address, Vec<SenderTxRecord>>>,
+    transaction: &SignedTransaction, block_hash: H256, index: usize,
+    skip_reason: Option<TxSkipReason>,
+)
+{
+    if let Some(sender_orders) = sender_orders {
+        sender_orders
+            .entry(transaction.sender)
+            .or_insert_with(Vec::new)
+            .push(SenderTxRecord {
+                hash: transaction.hash(),
+                nonce: transaction.nonce,
+                block_hash,
+                index,
+                skip_reason,
+            });
+    }
+}
+
+/// The CPU time consumed by the current thread, or `None` if it is not
+/// supported on this platform.
+#[cfg(unix)]
+fn thread_cpu_time() -> Option<Duration> {
+    let mut time = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    let ret = unsafe {
+        libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut time)
+    };
+    if ret == 0 {
+        Some(Duration::new(time.tv_sec as u64, time.tv_nsec as u32))
+    } else {
+        None
+    }
+}
+
+#[cfg(not(unix))]
+fn thread_cpu_time() -> Option<Duration> { None }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        ConsensusExecutionConfiguration, ConsensusExecutionHandler,
+        EpochExecutionTask, ExecutionScheduler, ExecutionTask,
+        GetExecutionResultTask, QueuedTask, RewardExecutionInfo,
+    };
+    use crate::{
+        block_data_manager::{
+            AddressLogPositions, BlockDataManager, BlockLogIndex, DbType,
+            LogPosition,
+        },
+        consensus::consensus_inner::{
+            execution_origin::{ExecutionOrigin, ExecutionPipelineId},
+            execution_queue::ExecutionTaskClass,
+            ReceiptsRecomputeTask,
+        },
+        machine::new_machine_with_builtin,
+        pow::PowComputer,
+        spec::CommonParams,
+        state::State,
+        statistics::Statistics,
+        sync::utils::{
+            create_simple_block_impl,
+            initialize_data_manager_with_genesis_accounts,
+        },
+        transaction_pool::{TransactionPool, TxPoolConfig},
+        verification::VerificationConfig,
+        vm_factory::VmFactory,
+    };
+    use cfx_parameters::{
+        block::{MAX_BLOCK_SIZE_IN_BYTES, REFEREE_DEFAULT_BOUND},
+        consensus::TRANSACTION_DEFAULT_EPOCH_BOUND,
+    };
+    use cfx_statedb::StateDb;
+    use cfx_storage::{StateIndex, StorageManagerTrait};
+    use cfx_types::{address_util::AddressUtil, Address, H256, U256, U512};
+    use keylib::{Generator, KeyPair, Random};
+    use primitives::{
+        receipt::{
+            BlockReceipts, TRANSACTION_OUTCOME_EXCEPTION_WITHOUT_NONCE_BUMPING,
+            TRANSACTION_OUTCOME_SUCCESS,
+        },
+        Action, Block, SignedTransaction, StateRoot, Transaction,
+    };
+    use std::{
+        collections::HashMap,
+        fs,
+        sync::{mpsc::channel, Arc},
+    };
+
+    fn new_execution_handler(
+        db_dir: &str, params: CommonParams, checkpoint_interval: Option<usize>,
+        genesis_accounts: HashMap<Address, U256>,
+    ) -> (ConsensusExecutionHandler, Arc<BlockDataManager>, Arc<Block>)
+    {
+        let vm = VmFactory::new(1024 * 32);
+        let pow = Arc::new(PowComputer::new(true));
+        let (data_man, genesis) = initialize_data_manager_with_genesis_accounts(
+            db_dir,
+            DbType::Rocksdb,
+            pow,
+            vm.clone(),
+            genesis_accounts,
+        );
+        let machine = Arc::new(new_machine_with_builtin(params, vm));
+        let verification_config = VerificationConfig::new(
+            true, /* test_mode */
+            REFEREE_DEFAULT_BOUND,
+            MAX_BLOCK_SIZE_IN_BYTES,
+            TRANSACTION_DEFAULT_EPOCH_BOUND,
+            machine.clone(),
+        );
+        let tx_pool = Arc::new(TransactionPool::new(
+            TxPoolConfig::default(),
+            verification_config.clone(),
+            data_man.clone(),
+            machine.clone(),
+        ));
+        let handler = ConsensusExecutionHandler::new(
+            tx_pool,
+            data_man.clone(),
+            ConsensusExecutionConfiguration {
+                executive_trace: false,
+                execution_worker_count: 1,
+                head_execution_weight: 1,
+                replay_execution_weight: 1,
+                enable_dependency_prefetch: false,
+                execution_verification_concurrency: 1,
+                enable_block_log_index: false,
+                epoch_execution_checkpoint_interval: checkpoint_interval,
+                debug_dump_dir_shadow_execution: None,
+            },
+            verification_config,
+            machine,
+            Arc::new(Statistics::new()),
+        );
+        (handler, data_man, genesis)
+    }
+
+    /// Create `count` transfers from `sender` with consecutive nonces.
+    fn transfers(sender: &KeyPair, count: u64) -> Vec<Arc<SignedTransaction>> {
+        (0..count)
+            .map(|nonce| {
+                let mut recipient = Address::random();
+                recipient.set_user_account_type_bits();
+                Arc::new(
+                    Transaction {
+                        nonce: U256::from(nonce),
+                        gas_price: U256::one(),
+                        gas: U256::from(21000),
+                        action: Action::Call(recipient),
+                        value: U256::from(1000),
+                        storage_limit: 0,
+                        epoch_height: 0,
+                        chain_id: 0,
+                        data: vec![],
+                    }
+                    .sign(sender.secret()),
+                )
+            })
+            .collect()
+    }
+
+    /// Create an epoch of three blocks on `genesis`, each transferring from
+    /// the sender of `transactions`.
+    fn insert_epoch(
+        data_man: &BlockDataManager, genesis: &Block,
+        transactions: &Vec<Arc<SignedTransaction>>,
+    ) -> Vec<H256>
+    {
+        let transactions_by_block =
+            vec![&transactions[..2], &transactions[2..3], &transactions[3..]];
+        let mut epoch_block_hashes = Vec::new();
+        for (i, block_transactions) in
+            transactions_by_block.into_iter().enumerate()
+        {
+            let (_, block) = create_simple_block_impl(
+                genesis.hash(),
+                vec![],
+                1,
+                U256::from(i),
+                U256::from(10),
+                1,
+                false,
+            );
+            let block =
+                Block::new(block.block_header, block_transactions.to_vec());
+            epoch_block_hashes.push(block.hash());
+            data_man.insert_block(Arc::new(block), true /* persistent */);
+        }
+        epoch_block_hashes
+    }
+
+    fn commitment_of(
+        data_man: &BlockDataManager, epoch_hash: &H256,
+    ) -> (StateRoot, H256, H256) {
+        let commitment =
+            data_man.get_epoch_execution_commitment(epoch_hash).unwrap();
+        (
+            commitment.state_root_with_aux_info.state_root.clone(),
+            commitment.receipts_root,
+            commitment.logs_bloom_hash,
+        )
+    }
+
+    #[test]
+    fn test_resumed_epoch_execution() {
+        let transactions = transfers(&Random.generate().unwrap(), 4);
+        let mut genesis_accounts = HashMap::new();
+        genesis_accounts
+            .insert(transactions[0].sender(), U256::from(1_000_000_000u64));
+
+        let uninterrupted_dir = "./test_uninterrupted_execution.db/";
+        let resumed_dir = "./test_resumed_execution.db/";
+        {
+            let (handler, data_man, genesis) = new_execution_handler(
+                uninterrupted_dir,
+                Default::default(),
+                None,
+                genesis_accounts.clone(),
+            );
+            let epoch_block_hashes =
+                insert_epoch(&data_man, &genesis, &transactions);
+            let epoch_hash = *epoch_block_hashes.last().unwrap();
+            handler.compute_epoch(
+                &epoch_hash,
+                &epoch_block_hashes,
+                1,     /* start_block_number */
+                &None, /* reward_execution_info */
+                true,  /* on_local_pivot */
+                None,  /* debug_record */
+                false, /* force_recompute */
+            );
+            let uninterrupted = commitment_of(&data_man, &epoch_hash);
+
+            let (handler, data_man, genesis) = new_execution_handler(
+                resumed_dir,
+                Default::default(),
+                Some(1),
+                genesis_accounts,
+            );
+            let epoch_block_hashes =
+                insert_epoch(&data_man, &genesis, &transactions);
+            assert_eq!(*epoch_block_hashes.last().unwrap(), epoch_hash);
+            let epoch_blocks = data_man
+                .blocks_by_hash_list(&epoch_block_hashes, false)
+                .unwrap();
+
+            // Execute the epoch with the checkpoints, and drop the state
+            // before it is committed, as if the node were interrupted.
+            let parent_state_index = StateIndex::new_for_next_epoch(
+                &genesis.hash(),
+                &data_man
+                    .get_epoch_execution_commitment(&genesis.hash())
+                    .unwrap()
+                    .state_root_with_aux_info,
+                0,
+                data_man.get_snapshot_epoch_count(),
+            );
+            let mut state = State::new(StateDb::new(
+                data_man
+                    .storage_manager
+                    .get_state_for_next_epoch(parent_state_index)
+                    .unwrap()
+                    .unwrap(),
+            ))
+            .unwrap();
+            handler
+                .process_epoch_transactions(
+                    epoch_hash,
+                    &mut state,
+                    &epoch_blocks,
+                    1,    /* start_block_number */
+                    true, /* on_local_pivot */
+                    true, /* persist_results */
+                    Some(1),
+                    None, /* resumption */
+                    |_| {},
+                )
+                .unwrap();
+            drop(state);
+            let checkpoint =
+                data_man.epoch_execution_checkpoint(&epoch_hash).unwrap();
+            assert_eq!(checkpoint.executed_block_count, 2);
+            assert_eq!(checkpoint.earlier_checkpoint_epoch_ids.len(), 1);
+
+            // The execution resumes from the last checkpoint.
+            handler.compute_epoch(
+                &epoch_hash,
+                &epoch_block_hashes,
+                1,     /* start_block_number */
+                &None, /* reward_execution_info */
+                true,  /* on_local_pivot */
+                None,  /* debug_record */
+                false, /* force_recompute */
+            );
+            assert_eq!(commitment_of(&data_man, &epoch_hash), uninterrupted);
+
+            // The checkpoints are removed with their states.
+            assert!(data_man.epoch_execution_checkpoint(&epoch_hash).is_none());
+            let checkpoint_state_index = handler
+                .epoch_execution_checkpoint_state_index(
+                    epoch_blocks.last().unwrap(),
+                    &checkpoint,
+                );
+            assert!(data_man
+                .storage_manager
+                .get_state_for_next_epoch(checkpoint_state_index)
+                .unwrap()
+                .is_none());
+        }
+        fs::remove_dir_all(uninterrupted_dir).ok();
+        fs::remove_dir_all(resumed_dir).ok();
+    }
+
+    /// Execute the epoch of `transactions` at height 1 with the sender gas
+    /// budget, and return the outcome status of each transaction.
+    fn execute_with_sender_gas_budget(
+        db_dir: &str, max_sender_gas_per_epoch: u64, transition_height: u64,
+        transactions: &Vec<Arc<SignedTransaction>>,
+    ) -> Vec<u8>
+    {
+        let mut params = CommonParams::default();
+        params.max_sender_gas_per_epoch =
+            Some(U256::from(max_sender_gas_per_epoch));
+        params.transition_heights.sender_gas_budget = transition_height;
+        let mut genesis_accounts = HashMap::new();
+        genesis_accounts
+            .insert(transactions[0].sender(), U256::from(1_000_000_000u64));
+        let (handler, data_man, genesis) =
+            new_execution_handler(db_dir, params, None, genesis_accounts);
+        let epoch_block_hashes =
+            insert_epoch(&data_man, &genesis, transactions);
+        let epoch_hash = *epoch_block_hashes.last().unwrap();
+        handler.compute_epoch(
+            &epoch_hash,
+            &epoch_block_hashes,
+            1,     /* start_block_number */
+            &None, /* reward_execution_info */
+            true,  /* on_local_pivot */
+            None,  /* debug_record */
+            false, /* force_recompute */
+        );
+        epoch_block_hashes
+            .iter()
+            .flat_map(|hash| {
+                data_man
+                    .block_execution_result_by_hash_with_epoch(
+                        hash,
+                        &epoch_hash,
+                        false, /* update_pivot_assumption */
+                        false, /* update_cache */
+                    )
+                    .unwrap()
+                    .block_receipts
+                    .receipts
+                    .iter()
+                    .map(|receipt| receipt.outcome_status)
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_sender_gas_budget() {
+        let transactions = transfers(&Random.generate().unwrap(), 4);
+
+        // The budget covers two transfers, and the rest are skipped without
+        // bumping the nonce.
+        let db_dir = "./test_sender_gas_budget.db/";
+        assert_eq!(
+            execute_with_sender_gas_budget(db_dir, 50000, 0, &transactions),
+            vec![
+                TRANSACTION_OUTCOME_SUCCESS,
+                TRANSACTION_OUTCOME_SUCCESS,
+                TRANSACTION_OUTCOME_EXCEPTION_WITHOUT_NONCE_BUMPING,
+                TRANSACTION_OUTCOME_EXCEPTION_WITHOUT_NONCE_BUMPING,
+            ]
+        );
+        fs::remove_dir_all(db_dir).ok();
+
+        // The budget does not apply before its transition height.
+        let db_dir = "./test_sender_gas_budget_before_transition.db/";
+        assert_eq!(
+            execute_with_sender_gas_budget(db_dir, 50000, 2, &transactions),
+            vec![TRANSACTION_OUTCOME_SUCCESS; 4]
+        );
+        fs::remove_dir_all(db_dir).ok();
+    }
+
+    #[test]
+    fn test_truncated_reward_execution_info() {
+        let epoch_blocks: Vec<Arc<Block>> = (0..5)
+            .map(|i| {
+                let (_, block) = create_simple_block_impl(
+                    H256::zero(),
+                    vec![],
+                    1,
+                    U256::from(i),
+                    U256::from(10),
+                    1,
+                    false,
+                );
+                Arc::new(block)
+            })
+            .collect();
+        let info = RewardExecutionInfo {
+            past_block_count: 7,
+            epoch_blocks: epoch_blocks.clone(),
+            epoch_block_no_reward: vec![true, false, true, false, false],
+            epoch_block_anticone_overlimited: vec![
+                true, false, false, false, false,
+            ],
+            epoch_block_anticone_difficulties: (0..5u64)
+                .map(U512::from)
+                .collect(),
+        };
+
+        // Only the last blocks, including the pivot block, are kept.
+        let truncated = info.truncated(3);
+        assert_eq!(truncated.past_block_count, 7);
+        assert_eq!(
+            truncated
+                .epoch_blocks
+                .iter()
+                .map(|block| block.hash())
+                .collect::<Vec<_>>(),
+            epoch_blocks[2..]
+                .iter()
+                .map(|block| block.hash())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(truncated.epoch_block_no_reward, vec![true, false, false]);
+        assert_eq!(
+            truncated.epoch_block_anticone_overlimited,
+            vec![false, false, false]
+        );
+        assert_eq!(
+            truncated.epoch_block_anticone_difficulties,
+            vec![U512::from(2), U512::from(3), U512::from(4)]
+        );
+        assert_eq!(info.truncated(10).epoch_blocks.len(), 5);
+
+        // The bound applies from its transition height.
+        let mut params = CommonParams::default();
+        params.epoch_executed_block_bound = 3;
+        params.transition_heights.epoch_reward_bound = 100;
+        assert_eq!(params.epoch_rewarded_block_bound(99), None);
+        assert_eq!(params.epoch_rewarded_block_bound(100), Some(3));
+    }
+
+    /// Execute an epoch of 3 blocks at height 1, and then an epoch at height
+    /// 2 that rewards it. Return whether each block of the first epoch is
+    /// rewarded.
+    fn rewarded_blocks(
+        db_dir: &str, epoch_reward_bound_transition_height: u64,
+    ) -> Vec<bool> {
+        let mut params = CommonParams::default();
+        params.epoch_executed_block_bound = 2;
+        params.transition_heights.epoch_reward_bound =
+            epoch_reward_bound_transition_height;
+        let transactions = transfers(&Random.generate().unwrap(), 4);
+        let mut genesis_accounts = HashMap::new();
+        genesis_accounts
+            .insert(transactions[0].sender(), U256::from(1_000_000_000u64));
+        let (handler, data_man, genesis) =
+            new_execution_handler(db_dir, params, None, genesis_accounts);
+        let reward_block_hashes =
+            insert_epoch(&data_man, &genesis, &transactions);
+        let reward_epoch_hash = *reward_block_hashes.last().unwrap();
+        handler.compute_epoch(
+            &reward_epoch_hash,
+            &reward_block_hashes,
+            1,     /* start_block_number */
+            &None, /* reward_execution_info */
+            true,  /* on_local_pivot */
+            None,  /* debug_record */
+            false, /* force_recompute */
+        );
+
+        let (epoch_hash, block) = create_simple_block_impl(
+            reward_epoch_hash,
+            vec![],
+            2,
+            U256::zero(),
+            U256::from(10),
+            1,
+            false,
+        );
+        data_man.insert_block(Arc::new(block), true /* persistent */);
+        // The reward info has more blocks than the bound, which is not
+        // possible for an epoch set computed by the consensus graph.
+        let reward_info = RewardExecutionInfo {
+            past_block_count: 0,
+            epoch_blocks: reward_block_hashes
+                .iter()
+                .map(|hash| data_man.block_by_hash(hash, false).unwrap())
+                .collect(),
+            epoch_block_no_reward: vec![false; 3],
+            epoch_block_anticone_overlimited: vec![false; 3],
+            epoch_block_anticone_difficulties: vec![U512::zero(); 3],
+        };
+        handler.compute_epoch(
+            &epoch_hash,
+            &vec![epoch_hash],
+            4, /* start_block_number */
+            &Some(reward_info),
+            true,  /* on_local_pivot */
+            None,  /* debug_record */
+            false, /* force_recompute */
+        );
+        reward_block_hashes
+            .iter()
+            .map(|hash| {
+                data_man
+                    .block_reward_details_by_hash_with_epoch(hash, &epoch_hash)
+                    .is_some()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_reward_bounded_epoch_blocks() {
+        // Only the last blocks of the epoch are rewarded from the transition
+        // height.
+        let db_dir = "./test_reward_bounded_epoch_blocks.db/";
+        assert_eq!(rewarded_blocks(db_dir, 1), vec![false, true, true]);
+        fs::remove_dir_all(db_dir).ok();
+
+        let db_dir = "./test_reward_bounded_epoch_blocks_before.db/";
+        assert_eq!(rewarded_blocks(db_dir, 2), vec![true, true, true]);
+        fs::remove_dir_all(db_dir).ok();
+    }
+
+    #[test]
+    fn test_recompute_epoch_receipts() {
+        let transactions = transfers(&Random.generate().unwrap(), 4);
+        let mut genesis_accounts = HashMap::new();
+        genesis_accounts
+            .insert(transactions[0].sender(), U256::from(1_000_000_000u64));
+        let db_dir = "./test_recompute_epoch_receipts.db/";
+        {
+            let (handler, data_man, genesis) = new_execution_handler(
+                db_dir,
+                Default::default(),
+                None,
+                genesis_accounts,
+            );
+            let epoch_block_hashes =
+                insert_epoch(&data_man, &genesis, &transactions);
+            let epoch_hash = *epoch_block_hashes.last().unwrap();
+            handler.compute_epoch(
+                &epoch_hash,
+                &epoch_block_hashes,
+                1,     /* start_block_number */
+                &None, /* reward_execution_info */
+                true,  /* on_local_pivot */
+                None,  /* debug_record */
+                false, /* force_recompute */
+            );
+            let committed = commitment_of(&data_man, &epoch_hash);
+            let stored_receipts = |block_hash: &H256| {
+                data_man
+                    .block_execution_result_by_hash_with_epoch(
+                        block_hash,
+                        &epoch_hash,
+                        false, /* update_pivot_assumption */
+                        false, /* update_cache */
+                    )
+                    .unwrap()
+                    .block_receipts
+                    .receipts
+                    .clone()
+            };
+            let receipts = stored_receipts(&epoch_block_hashes[0]);
+            let task = ReceiptsRecomputeTask {
+                epoch_hash,
+                epoch_block_hashes: epoch_block_hashes.clone(),
+                start_block_number: 1,
+            };
+
+            // The local receipts are correct, so the mismatching block is
+            // confirmed invalid.
+            assert_eq!(handler.recompute_epoch_receipts(&task), Ok(None));
+            assert_eq!(commitment_of(&data_man, &epoch_hash), committed);
+
+            // The stale local receipts, log index and receipts root are
+            // rewritten.
+            let commitment = data_man
+                .get_epoch_execution_commitment(&epoch_hash)
+                .unwrap()
+                .clone();
+            data_man.insert_block_execution_result(
+                epoch_block_hashes[0],
+                epoch_hash,
+                Arc::new(BlockReceipts {
+                    receipts: vec![],
+                    block_number: 1,
+                    secondary_reward: U256::zero(),
+                    tx_execution_error_messages: vec![],
+                    tx_execution_error_infos: vec![],
+                }),
+                true, /* persistent */
+            );
+            let logger = Address::random();
+            data_man.insert_block_log_index(
+                &epoch_block_hashes[0],
+                &BlockLogIndex {
+                    epoch_hash,
+                    entries: vec![AddressLogPositions {
+                        address: logger,
+                        positions: vec![LogPosition {
+                            transaction_index: 0,
+                            transaction_log_index: 0,
+                        }],
+                    }],
+                },
+            );
+            let logged_positions = || {
+                data_man
+                    .block_log_index_by_hash_with_epoch(
+                        &epoch_block_hashes[0],
+                        &epoch_hash,
+                    )
+                    .unwrap()
+                    .positions_of_any(&[logger])
+                    .len()
+            };
+
+            // Nothing is rewritten if the recomputed logs bloom mismatches.
+            data_man.insert_epoch_execution_commitment(
+                epoch_hash,
+                commitment.state_root_with_aux_info.clone(),
+                H256::zero(),
+                H256::zero(),
+            );
+            assert!(handler.recompute_epoch_receipts(&task).is_err());
+            assert!(stored_receipts(&epoch_block_hashes[0]).is_empty());
+            assert_eq!(logged_positions(), 1);
+
+            data_man.insert_epoch_execution_commitment(
+                epoch_hash,
+                commitment.state_root_with_aux_info,
+                H256::zero(),
+                commitment.logs_bloom_hash,
+            );
+            assert_eq!(
+                handler.recompute_epoch_receipts(&task),
+                Ok(Some(committed.1))
+            );
+            assert_eq!(commitment_of(&data_man, &epoch_hash), committed);
+            assert_eq!(stored_receipts(&epoch_block_hashes[0]), receipts);
+            // The transfers emit no logs.
+            assert_eq!(logged_positions(), 0);
+        }
+        fs::remove_dir_all(db_dir).ok();
+    }
+
+    #[test]
+    fn test_dry_run_epoch() {
+        let transactions = transfers(&Random.generate().unwrap(), 4);
+        let mut genesis_accounts = HashMap::new();
+        genesis_accounts
+            .insert(transactions[0].sender(), U256::from(1_000_000_000u64));
+        let db_dir = "./test_dry_run_epoch.db/";
+        {
+            let (handler, data_man, genesis) = new_execution_handler(
+                db_dir,
+                Default::default(),
+                None,
+                genesis_accounts,
+            );
+            let epoch_block_hashes =
+                insert_epoch(&data_man, &genesis, &transactions);
+            let epoch_hash = *epoch_block_hashes.last().unwrap();
+            let epoch_blocks = data_man
+                .blocks_by_hash_list(
+                    &epoch_block_hashes,
+                    false, /* update_cache */
+                )
+                .unwrap();
+            let result = handler
+                .dry_run_epoch(
+                    &epoch_blocks,
+                    1,     /* start_block_number */
+                    &None, /* reward_execution_info */
+                )
+                .unwrap();
+            assert_eq!(result.receipts.len(), epoch_blocks.len());
+
+            // Nothing of the dry run is written to the data manager.
+            assert!(data_man
+                .get_epoch_execution_commitment(&epoch_hash)
+                .is_none());
+            for block_hash in &epoch_block_hashes {
+                assert!(data_man
+                    .block_execution_result_by_hash_with_epoch(
+                        block_hash,
+                        &epoch_hash,
+                        false, /* update_pivot_assumption */
+                        false, /* update_cache */
+                    )
+                    .is_none());
+            }
+
+            // The dry run computes the same roots as the execution.
+            handler.compute_epoch(
+                &epoch_hash,
+                &epoch_block_hashes,
+                1,     /* start_block_number */
+                &None, /* reward_execution_info */
+                true,  /* on_local_pivot */
+                None,  /* debug_record */
+                false, /* force_recompute */
+            );
+            assert_eq!(
+                commitment_of(&data_man, &epoch_hash),
+                (
+                    result.state_root.state_root,
+                    result.receipts_root,
+                    result.logs_bloom_hash
+                )
+            );
+        }
+        fs::remove_dir_all(db_dir).ok();
+    }
+
+    fn execute_epoch_task(
+        epoch: u64, parent: u64, class: ExecutionTaskClass,
+    ) -> ExecutionTask {
+        ExecutionTask::ExecuteEpoch(EpochExecutionTask {
+            epoch_hash: H256::from_low_u64_be(epoch),
+            parent_hash: Some(H256::from_low_u64_be(parent)),
+            epoch_block_hashes: vec![],
+            start_block_number: 0,
+            reward_info: None,
+            on_local_pivot: false,
+            force_recompute: false,
+            optimistic: false,
+            class,
+            pipeline_id: ExecutionPipelineId::next(ExecutionOrigin::NewBlock),
+        })
+    }
+
+    /// Return the epoch of the task, or `None` for the other tasks.
+    fn popped_epoch(
+        popped: &Option<(ExecutionTaskClass, QueuedTask)>,
+    ) -> Option<Option<H256>> {
+        popped.as_ref().map(|(_, queued)| match &queued.task {
+            ExecutionTask::ExecuteEpoch(task) => Some(task.epoch_hash),
+            ExecutionTask::GetResult(_)
+            | ExecutionTask::RecomputeReceipts(_) => None,
+        })
+    }
+
+    /// Finish the popped task as `run_worker` does.
+    fn finish(
+        scheduler: &mut ExecutionScheduler,
+        popped: Option<(ExecutionTaskClass, QueuedTask)>,
+    )
+    {
+        let (class, queued) = popped.unwrap();
+        scheduler.busy_classes.remove(&class);
+        if let ExecutionTask::ExecuteEpoch(task) = &queued.task {
+            scheduler.finish_execution(&task.epoch_hash, queued.seq);
+        }
+    }
+
+    #[test]
+    fn test_head_and_replay_tasks_overlap() {
+        let mut scheduler = ExecutionScheduler::new(1, 1);
+        scheduler.push(execute_epoch_task(1, 0, ExecutionTaskClass::Head));
+        scheduler.push(execute_epoch_task(2, 1, ExecutionTaskClass::Head));
+        scheduler.push(execute_epoch_task(11, 10, ExecutionTaskClass::Replay));
+
+        // The independent tasks of the two classes are executed at the same
+        // time, and the next task of a busy class waits.
+        let head = scheduler.pop();
+        let replay = scheduler.pop();
+        assert_eq!(popped_epoch(&head), Some(Some(H256::from_low_u64_be(1))));
+        assert_eq!(
+            popped_epoch(&replay),
+            Some(Some(H256::from_low_u64_be(11)))
+        );
+        assert!(scheduler.pop().is_none());
+
+        finish(&mut scheduler, replay);
+        assert!(scheduler.pop().is_none());
+        finish(&mut scheduler, head);
+        let head = scheduler.pop();
+        assert_eq!(popped_epoch(&head), Some(Some(H256::from_low_u64_be(2))));
+        finish(&mut scheduler, head);
+        assert!(scheduler.pending_executions.is_empty());
+    }
+
+    #[test]
+    fn test_task_waits_for_other_class() {
+        let mut scheduler = ExecutionScheduler::new(1, 1);
+        let (sender, _receiver) = channel();
+        scheduler.push(execute_epoch_task(1, 0, ExecutionTaskClass::Head));
+        scheduler.push(ExecutionTask::GetResult(GetExecutionResultTask {
+            epoch_hash: H256::from_low_u64_be(1),
+            sender,
+            class: ExecutionTaskClass::Replay,
+        }));
+
+        // The result of the epoch executed by the head class is returned
+        // after the execution.
+        let head = scheduler.pop();
+        assert_eq!(popped_epoch(&head), Some(Some(H256::from_low_u64_be(1))));
+        assert!(scheduler.pop().is_none());
+        finish(&mut scheduler, head);
+        let result = scheduler.pop();
+        assert_eq!(popped_epoch(&result), Some(None));
+        finish(&mut scheduler, result);
+
+        // The replay of a child epoch waits for the state of its parent in
+        // the head class.
+        scheduler.push(execute_epoch_task(2, 1, ExecutionTaskClass::Head));
+        scheduler.push(execute_epoch_task(3, 2, ExecutionTaskClass::Replay));
+        let head = scheduler.pop();
+        assert_eq!(popped_epoch(&head), Some(Some(H256::from_low_u64_be(2))));
+        assert!(scheduler.pop().is_none());
+        finish(&mut scheduler, head);
+        let replay = scheduler.pop();
+        assert_eq!(popped_epoch(&replay), Some(Some(H256::from_low_u64_be(3))));
+        finish(&mut scheduler, replay);
+
+        // A task does not wait for the tasks enqueued after it, so the waits
+        // never form a cycle.
+        scheduler.push(execute_epoch_task(5, 4, ExecutionTaskClass::Replay));
+        scheduler.push(execute_epoch_task(4, 3, ExecutionTaskClass::Head));
+        let replay = scheduler.pop();
+        let head = scheduler.pop();
+        assert!(replay.is_some() && head.is_some());
+        finish(&mut scheduler, replay);
+        finish(&mut scheduler, head);
+        assert!(scheduler.pending_executions.is_empty());
+    }
+
+    #[test]
+    fn test_replay_epoch_runs_after_parent_state() {
+        for (head_weight, replay_weight) in &[(1, 1), (1, 4), (4, 1)] {
+            let mut scheduler =
+                ExecutionScheduler::new(*head_weight, *replay_weight);
+            // The head tasks follow the pivot chain 0 -> 1 -> 2 -> 3, and the
+            // replay tasks execute the fork 1 -> 12 -> 13 -> 14, and then the
+            // epoch 4 after the head tasks enqueued before.
+            let tasks = [
+                (1, 0, ExecutionTaskClass::Head),
+                (12, 1, ExecutionTaskClass::Replay),
+                (13, 12, ExecutionTaskClass::Replay),
+                (2, 1, ExecutionTaskClass::Head),
+                (14, 13, ExecutionTaskClass::Replay),
+                (3, 2, ExecutionTaskClass::Head),
+                (4, 3, ExecutionTaskClass::Replay),
+            ];
+            for (epoch, parent, class) in &tasks {
+                scheduler.push(execute_epoch_task(*epoch, *parent, *class));
+            }
+
+            // Each round all the available tasks are executed in parallel.
+            let mut executed = vec![0];
+            while scheduler.queue.len() != 0 {
+                let mut running = Vec::new();
+                while let Some((class, queued)) = scheduler.pop() {
+                    if let ExecutionTask::ExecuteEpoch(task) = &queued.task {
+                        let parent = task.parent_hash.unwrap().to_low_u64_be();
+                        assert!(
+                            executed.contains(&parent),
+                            "epoch {:?} runs before its parent",
+                            task.epoch_hash
+                        );
+                    }
+                    running.push((class, queued));
+                }
+                assert!(!running.is_empty());
+                for (class, queued) in running {
+                    if let ExecutionTask::ExecuteEpoch(task) = &queued.task {
+                        executed.push(task.epoch_hash.to_low_u64_be());
+                    }
+                    finish(&mut scheduler, Some((class, queued)));
+                }
+            }
+            assert_eq!(executed.len(), tasks.len() + 1);
+        }
+    }
 }
@@ -4,14 +4,21 @@
 
 use super::blame_verifier::BlameVerifier;
 use crate::{
-    block_data_manager::{BlockDataManager, BlockStatus, LocalBlockInfo},
+    block_data_manager::{
+        BlockDataManager, BlockStatus, LocalBlockInfo, PartialInvalidCause,
+    },
     channel::Channel,
     consensus::{
+        anticone_set::AnticoneSet,
         consensus_inner::{
             confirmation_meter::ConfirmationMeter,
             consensus_executor::{ConsensusExecutor, EpochExecutionTask},
+            execution_origin::ExecutionOrigin,
+            pivot_history::PivotSwitch,
+            weight::sum_cumulative_weights,
             ConsensusGraphInner, NULL,
         },
+        debug_recompute::log_weight_audit,
         ConsensusConfig,
     },
     state_exposer::{ConsensusGraphBlockState, STATE_EXPOSER},
@@ -109,7 +116,9 @@ impl ConsensusNewBlockHandler {
         }
         let mut new_era_block_arena_index_set = HashSet::new();
         for (i, _) in &inner.arena {
-            if !old_era_block_arena_index_set.contains(&i) {
+            if !old_era_block_arena_index_set.contains(&i)
+                && !inner.is_arena_index_retired(i)
+            {
                 new_era_block_arena_index_set.insert(i);
             }
         }
@@ -146,7 +155,9 @@ impl ConsensusNewBlockHandler {
 
         // Next we are going to recompute all referee and referrer information
         // in arena
-        let new_era_pivot_index = inner.height_to_pivot_index(new_era_height);
+        let new_era_pivot_index = inner
+            .checked_height_to_pivot_index(new_era_height)
+            .expect("the new era genesis should be in the current era");
         for v in new_era_block_arena_index_set.iter() {
             let me = *v;
             // It is necessary to process `referees` and
@@ -156,10 +167,9 @@ impl ConsensusNewBlockHandler {
             inner.arena[me]
                 .referees
                 .retain(|v| new_era_block_arena_index_set.contains(v));
-            inner.arena[me]
-                .data
-                .blockset_in_own_view_of_epoch
-                .retain(|v| new_era_block_arena_index_set.contains(v));
+            inner.retain_blockset_in_own_view_of_epoch(me, |v| {
+                new_era_block_arena_index_set.contains(v)
+            });
             if !new_era_block_arena_index_set.contains(
                 &inner.arena[me].data.past_view_last_timer_block_arena_index,
             ) {
@@ -194,7 +204,8 @@ impl ConsensusNewBlockHandler {
             let hash = inner.arena[index].hash;
             inner.hash_to_arena_indices.remove(&hash);
             inner.terminal_hashes.remove(&hash);
-            inner.arena.remove(index);
+            inner.remove_evicted_epoch_sets(index);
+            inner.remove_arena_node(index);
             // remove useless data in BlockDataManager
             inner.data_man.remove_epoch_execution_commitment(&hash);
             inner.data_man.remove_epoch_execution_context(&hash);
@@ -255,13 +266,31 @@ impl ConsensusNewBlockHandler {
             inner.block_weight(new_era_block_arena_index);
         for i in 1..inner.pivot_chain_metadata.len() {
             let pivot = inner.pivot_chain[i];
-            inner.pivot_chain_metadata[i].past_weight =
-                inner.pivot_chain_metadata[i - 1].past_weight
-                    + inner.total_weight_in_own_epoch(
-                        &inner.arena[pivot].data.blockset_in_own_view_of_epoch,
+            // The evicted sets are read without being reloaded, and only
+            // reloaded if they fail to be read.
+            let epoch_weight = inner
+                .get_blockset_in_own_view_of_epoch(pivot)
+                .map(|blockset| {
+                    inner.total_weight_in_own_epoch(
+                        &blockset,
+                        new_era_block_arena_index,
+                    )
+                })
+                .unwrap_or_else(|e| {
+                    warn!("{}, so they are reloaded", e);
+                    let blockset =
+                        inner.blockset_in_own_view_of_epoch(pivot).clone();
+                    inner.total_weight_in_own_epoch(
+                        &blockset,
                         new_era_block_arena_index,
                     )
-                    + inner.block_weight(pivot)
+                });
+            inner.pivot_chain_metadata[i].past_weight =
+                sum_cumulative_weights(&[
+                    inner.pivot_chain_metadata[i - 1].past_weight,
+                    epoch_weight,
+                    inner.block_weight(pivot),
+                ])
         }
         for d in inner.pivot_chain_metadata.iter_mut() {
             d.last_pivot_in_past_blocks
@@ -302,6 +331,10 @@ impl ConsensusNewBlockHandler {
             .new_checkpoint(new_era_height, inner.best_epoch_number());
     }
 
+    /// Compute the anticone of `me` without the anticone of its parent. Only
+    /// the blocks in the epochs after the last pivot block in the past of `me`
+    /// can be in the anticone, and they are enumerated from
+    /// `inner.anticone_index` instead of scanning the whole arena.
     pub fn compute_anticone_bruteforce(
         inner: &ConsensusGraphInner, me: usize,
     ) -> BitSet {
@@ -317,8 +350,9 @@ impl ConsensusNewBlockHandler {
                 inner.arena[*referee].data.last_pivot_in_past,
             );
         }
-        let mut visited = BitSet::new();
-        let mut queue = VecDeque::new();
+        let mut scratch = inner.traversal_pool.take(inner.arena.capacity());
+        let visited = &mut scratch.visited;
+        let queue = &mut scratch.queue;
         queue.push_back(me);
         visited.add(me as u32);
         while let Some(index) = queue.pop_front() {
@@ -359,9 +393,10 @@ impl ConsensusNewBlockHandler {
         }
 
         let mut anticone = BitSet::with_capacity(inner.arena.capacity() as u32);
-        for (i, node) in inner.arena.iter() {
-            if node.data.epoch_number > last_in_pivot
-                && !visited.contains(i as u32)
+        for i in inner.anticone_index.blocks_after(last_in_pivot) {
+            let node = &inner.arena[i];
+            if !visited.contains(i as u32)
+                && !inner.is_arena_index_retired(i)
                 && (node.data.activated || node.data.inactive_dependency_cnt == NULL) /* We include only preactivated blocks */
                 && node.era_block != NULL
             /* We exclude out-of-era blocks */
@@ -369,19 +404,16 @@ impl ConsensusNewBlockHandler {
                 anticone.add(i as u32);
             }
         }
+        inner.traversal_pool.give_back(scratch);
         anticone
     }
 
-    pub fn compute_anticone_hashset_bruteforce(
+    pub fn compute_anticone_set_bruteforce(
         inner: &ConsensusGraphInner, me: usize,
-    ) -> HashSet<usize> {
+    ) -> AnticoneSet {
         let s =
             ConsensusNewBlockHandler::compute_anticone_bruteforce(inner, me);
-        let mut ret = HashSet::new();
-        for index in s.iter() {
-            ret.insert(index as usize);
-        }
-        ret
+        AnticoneSet::from_bitset(&s, usize::MAX).expect("not bounded")
     }
 
     /// Note that this function is not a pure computation function. It has the
@@ -406,11 +438,12 @@ impl ConsensusNewBlockHandler {
             anticone = inner.compute_future_bitset(parent);
             anticone.remove(me as u32);
 
-            for index in parent_anticone_opt.unwrap() {
-                anticone.add(*index as u32);
+            for index in parent_anticone_opt.unwrap().iter() {
+                anticone.add(index as u32);
             }
-            let mut my_past = BitSet::new();
-            let mut queue: VecDeque<usize> = VecDeque::new();
+            let mut scratch = inner.traversal_pool.take(inner.arena.capacity());
+            let my_past = &mut scratch.visited;
+            let queue = &mut scratch.queue;
             queue.push_back(me);
             while let Some(index) = queue.pop_front() {
                 if my_past.contains(index as u32) {
@@ -442,6 +475,7 @@ impl ConsensusNewBlockHandler {
             for index in my_past.drain() {
                 anticone.remove(index);
             }
+            inner.traversal_pool.give_back(scratch);
 
             // We only consider non-lagacy blocks when computing anticone.
             for index in anticone.clone().iter() {
@@ -475,43 +509,14 @@ impl ConsensusNewBlockHandler {
         checking_candidate: Iter<usize>,
     ) -> bool
     {
-        let mut valid = true;
-        let parent = inner.arena[me].parent;
-        let force_confirm = inner.arena[me].data.force_confirm;
-        let force_confirm_height = inner.arena[force_confirm].height;
-
-        // Check the pivot selection decision.
-        for consensus_arena_index_in_epoch in checking_candidate {
-            let lca = inner.lca(*consensus_arena_index_in_epoch, parent);
-            assert!(lca != *consensus_arena_index_in_epoch);
-            // If it is outside current era, we will skip!
-            if lca == NULL || inner.arena[lca].height < force_confirm_height {
-                continue;
-            }
-            if lca == parent {
-                valid = false;
-                break;
-            }
-
-            let fork = inner.ancestor_at(
-                *consensus_arena_index_in_epoch,
-                inner.arena[lca].height + 1,
-            );
-            let pivot = inner.ancestor_at(parent, inner.arena[lca].height + 1);
-
-            let fork_subtree_weight = subtree_weight[fork];
-            let pivot_subtree_weight = subtree_weight[pivot];
-
-            if ConsensusGraphInner::is_heavier(
-                (fork_subtree_weight, &inner.arena[fork].hash),
-                (pivot_subtree_weight, &inner.arena[pivot].hash),
-            ) {
-                valid = false;
-                break;
-            }
-        }
-
-        valid
+        inner
+            .find_heavier_fork(
+                inner.arena[me].parent,
+                inner.arena[me].data.force_confirm,
+                checking_candidate,
+                subtree_weight,
+            )
+            .is_none()
     }
 
     fn check_correct_parent(
@@ -632,17 +637,18 @@ impl ConsensusNewBlockHandler {
         valid
     }
 
+    /// Return the cause if the block is partially invalid.
     fn check_block_full_validity(
         &self, new: usize, inner: &mut ConsensusGraphInner, adaptive: bool,
         anticone_barrier: &BitSet, weight_tuple: Option<&Vec<i128>>,
-    ) -> bool
+    ) -> Option<PartialInvalidCause>
     {
         let parent = inner.arena[new].parent;
         let force_confirm = inner.arena[new].data.force_confirm;
 
         if inner.lca(parent, force_confirm) != force_confirm {
             warn!("Partially invalid due to picking incorrect parent (force confirmation {:?} violation). {:?}", force_confirm, inner.arena[new].hash);
-            return false;
+            return Some(PartialInvalidCause::IncorrectParent);
         }
 
         // Check whether the new block select the correct parent block
@@ -656,7 +662,7 @@ impl ConsensusNewBlockHandler {
                 "Partially invalid due to picking incorrect parent. {:?}",
                 inner.arena[new].hash
             );
-            return false;
+            return Some(PartialInvalidCause::IncorrectParent);
         }
 
         // Check whether difficulty is set correctly
@@ -667,23 +673,32 @@ impl ConsensusNewBlockHandler {
                 "Partially invalid due to wrong difficulty. {:?}",
                 inner.arena[new].hash
             );
-            return false;
+            return Some(PartialInvalidCause::WrongDifficulty);
         }
 
         // Check adaptivity match. Note that in bench mode we do not check
         // the adaptive field correctness. We simply override its value
         // with the right one.
         if !self.conf.bench_mode {
-            if inner.arena[new].adaptive != adaptive {
+            let hash = inner.arena[new].hash;
+            let mismatched = inner.arena[new].adaptive != adaptive;
+            if let Some(header) = self.data_man.block_header_by_hash(&hash) {
+                self.statistics.record_adaptive_flag_check(
+                    &hash,
+                    *header.author(),
+                    mismatched,
+                );
+            }
+            if mismatched {
                 warn!(
                     "Partially invalid due to invalid adaptive field. {:?}",
                     inner.arena[new].hash
                 );
-                return false;
+                return Some(PartialInvalidCause::WrongAdaptive);
             }
         }
 
-        return true;
+        None
     }
 
     #[inline]
@@ -748,6 +763,15 @@ impl ConsensusNewBlockHandler {
         }
         let new_stable_height =
             inner.cur_era_stable_height + inner.inner_conf.era_epoch_count;
+        // The state of the new stable block has to be executed, so we cannot
+        // move beyond the epochs deferred for missing block bodies.
+        if let Some(deferred_at) = inner.execution_deferred_at {
+            if new_stable_height + inner.deferred_state_epoch_count.max()
+                >= deferred_at
+            {
+                return inner.cur_era_stable_height;
+            }
+        }
         // We make sure there is an additional era before the best for moving it
         if new_stable_height + inner.inner_conf.era_epoch_count
             > inner.best_epoch_number()
@@ -797,7 +821,7 @@ impl ConsensusNewBlockHandler {
                     .unwrap()
                     .blame as u64
                     + new_genesis_height
-                    + DEFERRED_STATE_EPOCH_COUNT
+                    + inner.deferred_state_epoch_count.max()
                     >= inner.cur_era_stable_height
                 {
                     return inner.cur_era_genesis_block_arena_index;
@@ -884,19 +908,6 @@ impl ConsensusNewBlockHandler {
         self.data_man.insert_terminals_to_db(terminals);
     }
 
-    fn try_clear_blockset_in_own_view_of_epoch(
-        inner: &mut ConsensusGraphInner, me: usize,
-    ) {
-        if inner.arena[me].data.blockset_in_own_view_of_epoch.len() as u64
-            > BLOCKSET_IN_OWN_VIEW_OF_EPOCH_CAP
-        {
-            inner.arena[me].data.blockset_in_own_view_of_epoch =
-                Default::default();
-            inner.arena[me].data.skipped_epoch_blocks = Default::default();
-            inner.arena[me].data.blockset_cleared = true;
-        }
-    }
-
     // This function computes the timer chain in the view of the new block.
     // The first returned value is the fork height of the timer chain.
     // The second is a map that overwrites timer_chain_height values after the
@@ -933,8 +944,10 @@ impl ConsensusNewBlockHandler {
         &self, inner: &mut ConsensusGraphInner, me: usize,
     ) -> BlockStatus {
         debug!(
-            "Start to preactivate block {} index = {}",
-            inner.arena[me].hash, me
+            "Start to preactivate block {} index = {} stable_id = {}",
+            inner.arena[me].hash,
+            me,
+            inner.stable_id(me)
         );
         let parent = inner.arena[me].parent;
         let mut pending = {
@@ -990,7 +1003,7 @@ impl ConsensusNewBlockHandler {
         inner.arena[me].data.force_confirm =
             inner.cur_era_genesis_block_arena_index;
 
-        let fully_valid;
+        let partial_invalid_cause;
 
         // Note that this function also updates the anticone for other nodes, so
         // we have to call it even for pending blocks!
@@ -1024,7 +1037,7 @@ impl ConsensusNewBlockHandler {
                 &timer_chain_tuple,
             );
 
-            fully_valid = self.check_block_full_validity(
+            partial_invalid_cause = self.check_block_full_validity(
                 me,
                 inner,
                 adaptive,
@@ -1032,35 +1045,39 @@ impl ConsensusNewBlockHandler {
                 weight_tuple.as_ref(),
             );
 
-            if self.conf.bench_mode && fully_valid {
+            if self.conf.bench_mode && partial_invalid_cause.is_none() {
                 inner.arena[me].adaptive = adaptive;
             }
         } else {
-            let block_status_in_db = self
+            let block_info_in_db = self
                 .data_man
-                .local_block_info_by_hash(&inner.arena[me].hash)
+                .local_block_info_by_hash(&inner.arena[me].hash);
+            let block_status_in_db = block_info_in_db
                 .map(|info| info.get_status())
                 .unwrap_or(BlockStatus::Pending);
-            fully_valid = block_status_in_db != BlockStatus::PartialInvalid;
+            partial_invalid_cause = block_info_in_db
+                .and_then(|info| info.get_partial_invalid_cause());
             pending = block_status_in_db == BlockStatus::Pending;
             debug!(
-                "Fetch the block validity status {} from the local data base",
-                fully_valid
+                "Fetch the block partial invalid cause {:?} from the local data base",
+                partial_invalid_cause
             );
         }
 
         debug!(
-            "Finish preactivation block {} index = {}",
-            inner.arena[me].hash, me
+            "Finish preactivation block {} index = {} stable_id = {}",
+            inner.arena[me].hash,
+            me,
+            inner.stable_id(me)
         );
         let block_status = if pending {
             BlockStatus::Pending
-        } else if fully_valid {
+        } else if partial_invalid_cause.is_none() {
             BlockStatus::Valid
         } else {
             BlockStatus::PartialInvalid
         };
-        self.persist_block_info(inner, me, block_status);
+        self.persist_block_info(inner, me, block_status, partial_invalid_cause);
 
         block_status
     }
@@ -1231,15 +1248,19 @@ impl ConsensusNewBlockHandler {
             }
             if pivot_changed {
                 // The new subtree is heavier, update pivot chain
-                let fork_pivot_index = inner.height_to_pivot_index(fork_at);
+                let fork_pivot_index = inner
+                    .checked_height_to_pivot_index(fork_at)
+                    .expect("the fork height should be in the current era");
                 assert!(fork_pivot_index < inner.pivot_chain.len());
+                let old_tip =
+                    inner.arena[*inner.pivot_chain.last().unwrap()].hash;
                 for discarded_idx in
                     inner.pivot_chain.split_off(fork_pivot_index)
                 {
                     // Reset the epoch_number of the discarded fork
                     inner.reset_epoch_number_in_epoch(discarded_idx);
-                    ConsensusNewBlockHandler::try_clear_blockset_in_own_view_of_epoch(inner,
-                    discarded_idx);
+                    inner
+                        .try_clear_blockset_in_own_view_of_epoch(discarded_idx);
                 }
                 let mut u = new;
                 loop {
@@ -1282,8 +1303,31 @@ impl ConsensusNewBlockHandler {
                         );
                     }
                 }
+                let new_tip =
+                    inner.arena[*inner.pivot_chain.last().unwrap()].hash;
+                inner.pivot_switches.push(PivotSwitch {
+                    fork_height: fork_at,
+                    old_tip,
+                    new_tip,
+                    trigger_block: inner.arena[me].hash,
+                });
+                inner.optimistic_depth.on_pivot_switch(inner.clock.now());
             }
         };
+        if let Some(height) =
+            self.executor.chaos_hooks.take_forced_pivot_reevaluation()
+        {
+            // Handle the pivot chain as if it switched to itself at `height`.
+            if height > inner.cur_era_stable_height && height < fork_at {
+                debug!(
+                    "Force pivot chain re-evaluation from height {}",
+                    height
+                );
+                fork_at = height;
+                extend_pivot = false;
+                pivot_changed = true;
+            }
+        }
         debug!(
             "Forked at height {}, fork parent block {}",
             fork_at,
@@ -1300,7 +1344,9 @@ impl ConsensusNewBlockHandler {
             if pivot_changed {
                 inner.best_terminals_reorg_height =
                     min(inner.best_terminals_reorg_height, update_at);
-                let update_pivot_index = inner.height_to_pivot_index(update_at);
+                let update_pivot_index = inner
+                    .checked_height_to_pivot_index(update_at)
+                    .expect("the update height should be in the current era");
                 for pivot_index in update_pivot_index..old_pivot_chain_len {
                     for x in &inner.pivot_chain_metadata[pivot_index]
                         .last_pivot_in_past_blocks
@@ -1311,7 +1357,7 @@ impl ConsensusNewBlockHandler {
                 inner.recompute_metadata(fork_at, last_pivot_to_update);
             } else {
                 // pivot chain not extend and not change
-                ConsensusNewBlockHandler::try_clear_blockset_in_own_view_of_epoch(inner, me);
+                inner.try_clear_blockset_in_own_view_of_epoch(me);
                 inner.recompute_metadata(
                     inner.get_pivot_height(),
                     last_pivot_to_update,
@@ -1320,19 +1366,23 @@ impl ConsensusNewBlockHandler {
         } else {
             let height = inner.arena[me].height;
             inner.arena[me].data.last_pivot_in_past = height;
-            let pivot_index = inner.height_to_pivot_index(height);
+            let pivot_index = inner
+                .checked_height_to_pivot_index(height)
+                .expect("the new pivot block should be in the current era");
             inner.pivot_chain_metadata[pivot_index]
                 .last_pivot_in_past_blocks
                 .insert(me);
             let blockset = inner
                 .exchange_or_compute_blockset_in_own_view_of_epoch(me, None);
             inner.pivot_chain_metadata[pivot_index].past_weight =
-                inner.pivot_chain_metadata[pivot_index - 1].past_weight
-                    + inner.total_weight_in_own_epoch(
+                sum_cumulative_weights(&[
+                    inner.pivot_chain_metadata[pivot_index - 1].past_weight,
+                    inner.total_weight_in_own_epoch(
                         &blockset,
                         inner.cur_era_genesis_block_arena_index,
-                    )
-                    + inner.block_weight(me);
+                    ),
+                    inner.block_weight(me),
+                ]);
             inner.exchange_or_compute_blockset_in_own_view_of_epoch(
                 me,
                 Some(blockset),
@@ -1377,10 +1427,34 @@ impl ConsensusNewBlockHandler {
             meter.update_confirmation_risks(inner);
         }
 
+        if pivot_changed {
+            // Keep the headers of the latest pivot blocks in memory. Only the
+            // heights after `fork_at` are changed.
+            let pinned_count = self.data_man.pinned_pivot_header_count();
+            let pin_start_index = max(
+                inner
+                    .checked_height_to_pivot_index(max(
+                        fork_at,
+                        inner.cur_era_genesis_height,
+                    ))
+                    .expect("the height is capped by the era genesis"),
+                inner.pivot_chain.len().saturating_sub(pinned_count),
+            );
+            let pivot_hashes: Vec<H256> = inner.pivot_chain[pin_start_index..]
+                .iter()
+                .map(|index| inner.arena[*index].hash)
+                .collect();
+            self.data_man.pin_pivot_block_headers(
+                inner.pivot_index_to_height(pin_start_index),
+                &pivot_hashes,
+            );
+        }
+
         if pivot_changed {
             if inner.pivot_chain.len() > EPOCH_SET_PERSISTENCE_DELAY as usize {
-                let capped_fork_at_pivot_index =
-                    inner.height_to_pivot_index(capped_fork_at);
+                let capped_fork_at_pivot_index = inner
+                    .checked_height_to_pivot_index(capped_fork_at)
+                    .expect("capped_fork_at should be in the current era");
                 // Starting from old_len ensures that all epochs within
                 // [old_len - delay, new_len - delay) will be inserted to db, so
                 // no epochs will be skipped. Starting from
@@ -1403,6 +1477,10 @@ impl ConsensusNewBlockHandler {
                     inner.persist_epoch_set_hashes(pivot_index);
                 }
             }
+            inner.reset_epoch_set_eviction_height(fork_at);
+            if let Err(e) = inner.evict_cold_epoch_sets() {
+                warn!("Failed to evict the epoch sets: {}", e);
+            }
         }
 
         // Note that after the checkpoint (if happens), the old_pivot_chain_len
@@ -1541,12 +1619,9 @@ impl ConsensusNewBlockHandler {
             // FIXME: we need a function to compute the deferred epoch
             // FIXME: number. the current codebase may not be
             // FIXME: consistent at all places.
-            let mut confirmed_height = meter.get_confirmed_epoch_num();
-            if confirmed_height < DEFERRED_STATE_EPOCH_COUNT {
-                confirmed_height = 0;
-            } else {
-                confirmed_height -= DEFERRED_STATE_EPOCH_COUNT;
-            }
+            let confirmed_height = meter
+                .get_confirmed_epoch_num()
+                .saturating_sub(inner.deferred_state_epoch_count.max());
             // We can not assume that confirmed epoch are already executed,
             // but we can assume that the deferred block are executed.
             self.data_man
@@ -1561,26 +1636,24 @@ impl ConsensusNewBlockHandler {
                 )
                 // FIXME: propogate error.
                 .expect(&concat!(file!(), ":", line!(), ":", column!()));
-            self.set_block_tx_packed(inner, me);
+            if !inner.blocks_without_body.contains(&inner.arena[me].hash) {
+                self.set_block_tx_packed(inner, me);
+            }
             self.delayed_tx_recycle_in_skipped_blocks(inner);
 
-            let to_state_pos = if inner
-                .pivot_index_to_height(inner.pivot_chain.len())
-                < DEFERRED_STATE_EPOCH_COUNT
-            {
-                0
-            } else {
-                inner.pivot_index_to_height(inner.pivot_chain.len())
-                    - DEFERRED_STATE_EPOCH_COUNT
-                    + 1
-            };
+            let to_state_pos = Self::execution_target_height(inner);
+            // The epochs up to `state_at` have been enqueued for the old pivot
+            // chain.
+            let old_deferred_state_epoch_count =
+                inner.deferred_state_epoch_count.at(old_pivot_chain_height);
             let mut state_at = capped_fork_at;
-            if capped_fork_at + DEFERRED_STATE_EPOCH_COUNT
+            if capped_fork_at + old_deferred_state_epoch_count
                 > old_pivot_chain_height
             {
-                if old_pivot_chain_height > DEFERRED_STATE_EPOCH_COUNT {
-                    state_at =
-                        old_pivot_chain_height - DEFERRED_STATE_EPOCH_COUNT + 1;
+                if old_pivot_chain_height > old_deferred_state_epoch_count {
+                    state_at = old_pivot_chain_height
+                        - old_deferred_state_epoch_count
+                        + 1;
                 } else {
                     state_at = 1;
                 }
@@ -1605,8 +1678,13 @@ impl ConsensusNewBlockHandler {
                         state_availability_boundary
                             .pivot_chain
                             .truncate(split_off_index as usize);
-                        for i in inner.height_to_pivot_index(capped_fork_at)
-                            ..inner.pivot_chain.len()
+                        let capped_fork_at_pivot_index = inner
+                            .checked_height_to_pivot_index(capped_fork_at)
+                            .expect(
+                                "capped_fork_at should be in the current era",
+                            );
+                        for i in
+                            capped_fork_at_pivot_index..inner.pivot_chain.len()
                         {
                             state_availability_boundary
                                 .pivot_chain
@@ -1637,22 +1715,7 @@ impl ConsensusNewBlockHandler {
             }
 
             // Apply transactions in the determined total order
-            while state_at < to_state_pos {
-                let epoch_arena_index =
-                    inner.get_pivot_block_arena_index(state_at);
-                let reward_execution_info = self
-                    .executor
-                    .get_reward_execution_info(inner, epoch_arena_index);
-                self.executor.enqueue_epoch(EpochExecutionTask::new(
-                    epoch_arena_index,
-                    inner,
-                    reward_execution_info,
-                    true,  /* on_local_pivot */
-                    false, /* force_recompute */
-                ));
-
-                state_at += 1;
-            }
+            self.enqueue_epochs_for_execution(inner, state_at, to_state_pos);
         }
 
         self.persist_terminals(inner);
@@ -1662,6 +1725,106 @@ impl ConsensusNewBlockHandler {
         );
     }
 
+    /// Return the height of the first epoch whose execution should not start
+    /// yet because it is not deferred enough on the current pivot chain.
+    fn execution_target_height(inner: &ConsensusGraphInner) -> u64 {
+        let pivot_height = inner.pivot_index_to_height(inner.pivot_chain.len());
+        let deferred_state_epoch_count =
+            inner.deferred_state_epoch_count.at(pivot_height);
+        if pivot_height < deferred_state_epoch_count {
+            0
+        } else {
+            pivot_height - deferred_state_epoch_count + 1
+        }
+    }
+
+    /// Enqueue the pivot epochs in `[state_at, to_state_pos)` for execution.
+    /// In the header graph mode, the epochs from the first one with a missing
+    /// block body are deferred until the bodies arrive.
+    fn enqueue_epochs_for_execution(
+        &self, inner: &mut ConsensusGraphInner, mut state_at: u64,
+        to_state_pos: u64,
+    )
+    {
+        // The deferred epochs are evaluated again from `state_at`.
+        if inner
+            .execution_deferred_at
+            .map_or(false, |deferred_at| state_at <= deferred_at)
+        {
+            inner.execution_deferred_at = None;
+        }
+        while state_at < to_state_pos && inner.execution_deferred_at.is_none() {
+            let epoch_arena_index = inner.get_pivot_block_arena_index(state_at);
+            // The epoch set is reloaded if it is evicted, which is needed to
+            // execute the epoch anyway.
+            inner.ordered_executable_epoch_blocks(epoch_arena_index);
+            if inner.epoch_misses_body(epoch_arena_index).unwrap_or(true) {
+                debug!(
+                    "Defer the execution from epoch {} for missing block bodies",
+                    state_at
+                );
+                inner.execution_deferred_at = Some(state_at);
+                break;
+            }
+            let reward_execution_info = self
+                .executor
+                .get_reward_execution_info(inner, epoch_arena_index);
+            self.executor.enqueue_epoch(EpochExecutionTask::new(
+                epoch_arena_index,
+                inner,
+                reward_execution_info,
+                true,  /* on_local_pivot */
+                false, /* force_recompute */
+                ExecutionOrigin::NewBlock,
+            ));
+
+            state_at += 1;
+        }
+    }
+
+    /// Invoked by ConsensusGraph when the body of a block inserted before its
+    /// body arrives in the header graph mode. The execution deferred for the
+    /// block is resumed.
+    pub fn on_new_block_body(
+        &self, inner: &mut ConsensusGraphInner, hash: &H256,
+    ) {
+        if !inner.blocks_without_body.remove(hash) {
+            return;
+        }
+        debug!("Block body {:?} arrives in consensus", hash);
+        if let Some(me) = inner.hash_to_arena_indices.get(hash).cloned() {
+            if inner.arena[me].data.activated {
+                self.set_block_tx_packed(inner, me);
+            }
+        }
+        if let Some(deferred_at) = inner.execution_deferred_at {
+            let to_state_pos = Self::execution_target_height(inner);
+            self.enqueue_epochs_for_execution(inner, deferred_at, to_state_pos);
+        }
+    }
+
+    /// Invoked by ConsensusGraph when the body of a block inserted before its
+    /// body arrives in the header graph mode is found invalid. The block may
+    /// not be processed by consensus yet. The epochs containing the block are
+    /// never executed, and the deferred execution is evaluated again, so the
+    /// execution is only held until the pivot chain switches away from it.
+    pub fn on_invalid_block_body(
+        &self, inner: &mut ConsensusGraphInner, hash: &H256,
+    ) {
+        inner.blocks_without_body.remove(hash);
+        if !inner.blocks_with_invalid_body.insert(*hash) {
+            return;
+        }
+        warn!(
+            "Block {:?} inserted with only its header has an invalid body",
+            hash
+        );
+        if let Some(deferred_at) = inner.execution_deferred_at {
+            let to_state_pos = Self::execution_target_height(inner);
+            self.enqueue_epochs_for_execution(inner, deferred_at, to_state_pos);
+        }
+    }
+
     /// The top level function invoked by ConsensusGraph to insert a new block.
     pub fn on_new_block(
         &self, inner: &mut ConsensusGraphInner, meter: &ConfirmationMeter,
@@ -1686,9 +1849,8 @@ impl ConsensusNewBlockHandler {
                 "parent={:?} not in consensus graph or not in the genesis subtree, inserted as an out-era block stub",
                 parent_hash
             );
-            let block_status_in_db = self
-                .data_man
-                .local_block_info_by_hash(hash)
+            let block_info_in_db = self.data_man.local_block_info_by_hash(hash);
+            let block_status_in_db = block_info_in_db
                 .map(|info| info.get_status())
                 .unwrap_or(BlockStatus::Pending);
             let (sn, me) = inner.insert_out_era_block(
@@ -1699,6 +1861,10 @@ impl ConsensusNewBlockHandler {
                 block_status_in_db,
                 sn,
                 self.data_man.get_instance_id(),
+            )
+            .with_partial_invalid_cause(
+                block_info_in_db
+                    .and_then(|info| info.get_partial_invalid_cause()),
             );
             self.data_man.insert_local_block_info(hash, block_info);
             // If me is NULL, it means that this block does not have any stub,
@@ -1715,6 +1881,15 @@ impl ConsensusNewBlockHandler {
             let (me, indices_len) = inner.insert(&block_header);
             self.statistics
                 .set_consensus_graph_inserted_block_count(indices_len);
+            // The body is checked while holding the lock, so the arrival of
+            // the body cannot be missed by `on_new_block_body`.
+            if self.conf.header_graph_mode
+                && !inner.header_only
+                && !inner.blocks_with_invalid_body.contains(hash)
+                && !self.data_man.block_body_available(hash)
+            {
+                inner.blocks_without_body.insert(*hash);
+            }
             self.update_lcts_initial(inner, me);
             me
         };
@@ -1798,18 +1973,36 @@ impl ConsensusNewBlockHandler {
                 inner.arena[me].data.inactive_dependency_cnt
             );
         }
+
+        if let Some(interval) = inner.inner_conf.debug_weight_audit_interval {
+            if interval != 0
+                && inner.arena[me].data.sequence_number % interval == 0
+            {
+                log_weight_audit(inner, me).ok();
+            }
+        }
     }
 
     fn persist_block_info(
         &self, inner: &mut ConsensusGraphInner, me: usize,
         block_status: BlockStatus,
+        partial_invalid_cause: Option<PartialInvalidCause>,
     )
     {
+        // The block is already marked invalid in the database by the sync
+        // graph.
+        if inner
+            .blocks_with_invalid_body
+            .contains(&inner.arena[me].hash)
+        {
+            return;
+        }
         let block_info = LocalBlockInfo::new(
             block_status,
             inner.arena[me].data.sequence_number,
             self.data_man.get_instance_id(),
-        );
+        )
+        .with_partial_invalid_cause(partial_invalid_cause);
         self.data_man
             .insert_local_block_info(&inner.arena[me].hash, block_info);
         let era_block = inner.arena[me].era_block();
@@ -1835,6 +2028,9 @@ impl ConsensusNewBlockHandler {
     /// avoiding intermediate redundant computation triggered by
     /// on_new_block().
     /// It also recovers receipts_root and logs_bloom_hash in pivot chain.
+    /// The epochs without commitments are recomputed by `compute_epoch`,
+    /// which streams the receipts of each block into `EpochReceiptsFunnel`
+    /// instead of keeping the receipts of the epoch.
     /// This function is only invoked from recover_graph_from_db with
     /// header_only being false.
     pub fn construct_pivot_state(&self, inner: &mut ConsensusGraphInner) {
@@ -1884,11 +2080,15 @@ impl ConsensusNewBlockHandler {
             }
         }
 
-        if inner.pivot_chain.len() < DEFERRED_STATE_EPOCH_COUNT as usize {
+        let deferred_state_epoch_count = inner
+            .deferred_state_epoch_count
+            .at(inner.pivot_index_to_height(inner.pivot_chain.len()))
+            as usize;
+        if inner.pivot_chain.len() < deferred_state_epoch_count {
             return;
         }
         for pivot_index in start_pivot_index + 1
-            ..inner.pivot_chain.len() - DEFERRED_STATE_EPOCH_COUNT as usize + 1
+            ..inner.pivot_chain.len() - deferred_state_epoch_count + 1
         {
             let pivot_arena_index = inner.pivot_chain[pivot_index];
             let pivot_hash = inner.arena[pivot_arena_index].hash;
@@ -1986,6 +2186,7 @@ impl ConsensusNewBlockHandler {
                         reward_execution_info,
                         true, /* on_local_pivot */
                         true, /* force_recompute */
+                        ExecutionOrigin::ConstructPivot,
                     ),
                     None,
                 );
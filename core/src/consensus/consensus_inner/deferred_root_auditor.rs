@@ -0,0 +1,225 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::consensus::ConsensusGraph;
+use cfx_types::H256;
+use metrics::{Counter, CounterUsize};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::{
+    collections::HashSet,
+    fs,
+    path::Path,
+    sync::{Arc, Weak},
+    time::Duration,
+};
+
+lazy_static! {
+    static ref DEFERRED_ROOT_MISMATCH_COUNTER: Arc<dyn Counter<usize>> =
+        CounterUsize::register_with_group(
+            "system_metrics",
+            "deferred_root_mismatch_count"
+        );
+}
+
+#[derive(Clone, Debug)]
+pub struct DeferredRootAuditorConfig {
+    /// The interval between two audits.
+    pub audit_period: Duration,
+    /// The number of the latest pivot blocks checked in each audit.
+    pub depth: u64,
+    /// If set, a record of each mismatch is written to this directory.
+    pub dump_dir: Option<String>,
+}
+
+/// The deferred roots in a pivot block header that differ from the local
+/// execution commitment of its deferred epoch.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeferredRootMismatch {
+    pub pivot_height: u64,
+    pub pivot_hash: H256,
+    pub deferred_epoch_hash: H256,
+    /// Whether the commitment is loaded from the database rather than the
+    /// in-memory cache.
+    pub from_db: bool,
+    pub header_state_root: H256,
+    pub local_state_root: H256,
+    pub header_receipts_root: H256,
+    pub local_receipts_root: H256,
+    pub header_logs_bloom_hash: H256,
+    pub local_logs_bloom_hash: H256,
+}
+
+/// `DeferredRootAuditor` periodically compares the deferred roots in the
+/// headers of the latest pivot blocks with the execution commitments stored
+/// locally, both in the cache and in the database.
+///
+/// The roots are checked when a block is activated, so a mismatch found later
+/// means that the stored commitment is corrupted. The blocks produced on top
+/// of a corrupted commitment would carry wrong roots and be blamed by the
+/// other nodes, so the mismatch is reported before it gets there. Only the
+/// headers with no blame are audited, because the roots of the others cover
+/// several epochs.
+pub struct DeferredRootAuditor {
+    config: DeferredRootAuditorConfig,
+    consensus: Weak<ConsensusGraph>,
+    /// The pivot blocks whose mismatches have been reported, so they are
+    /// reported only once while they are within the audited range.
+    reported: Mutex<HashSet<H256>>,
+}
+
+impl DeferredRootAuditor {
+    pub fn new(
+        config: DeferredRootAuditorConfig, consensus: &Arc<ConsensusGraph>,
+    ) -> Self {
+        Self {
+            config,
+            consensus: Arc::downgrade(consensus),
+            reported: Default::default(),
+        }
+    }
+
+    pub fn audit_period(&self) -> Duration { self.config.audit_period }
+
+    /// Audit the latest pivot blocks once. Return `false` if the consensus
+    /// graph has been dropped, so the caller can stop auditing.
+    pub fn audit(&self) -> bool {
+        let consensus = match self.consensus.upgrade() {
+            Some(consensus) => consensus,
+            None => return false,
+        };
+        let mut audited_blocks = Vec::new();
+        {
+            let inner = consensus.inner.read();
+            let best_epoch_number = inner.best_epoch_number();
+            let start_height = (best_epoch_number + 1)
+                .saturating_sub(self.config.depth)
+                .max(inner.cur_era_genesis_height + 1);
+            for height in start_height..=best_epoch_number {
+                let index = match inner.checked_height_to_pivot_index(height) {
+                    Some(pivot_index) => inner.pivot_chain[pivot_index],
+                    None => continue,
+                };
+                // The deferred epochs of the blocks not validated yet may not
+                // be executed.
+                if inner.arena[index].data.state_valid != Some(true) {
+                    continue;
+                }
+                if let Ok(deferred) =
+                    inner.get_deferred_state_arena_index(index)
+                {
+                    audited_blocks.push((
+                        height,
+                        inner.arena[index].hash,
+                        inner.arena[deferred].hash,
+                    ));
+                }
+            }
+        }
+
+        let mut reported = self.reported.lock();
+        reported.retain(|hash| {
+            audited_blocks
+                .iter()
+                .any(|(_, pivot_hash, _)| pivot_hash == hash)
+        });
+        for (height, pivot_hash, deferred_epoch_hash) in audited_blocks {
+            if reported.contains(&pivot_hash) {
+                continue;
+            }
+            let mismatches = self.audit_block(
+                &consensus,
+                height,
+                pivot_hash,
+                deferred_epoch_hash,
+            );
+            if mismatches.is_empty() {
+                continue;
+            }
+            reported.insert(pivot_hash);
+            for mismatch in mismatches {
+                DEFERRED_ROOT_MISMATCH_COUNTER.inc(1);
+                error!("Deferred roots mismatch: {:?}", mismatch);
+                self.dump_mismatch(&mismatch);
+            }
+        }
+        true
+    }
+
+    fn audit_block(
+        &self, consensus: &ConsensusGraph, pivot_height: u64, pivot_hash: H256,
+        deferred_epoch_hash: H256,
+    ) -> Vec<DeferredRootMismatch>
+    {
+        let data_man = &consensus.data_man;
+        let header = match data_man.block_header_by_hash(&pivot_hash) {
+            Some(header) => header,
+            None => return Vec::new(),
+        };
+        if header.blame() != 0 {
+            return Vec::new();
+        }
+        let cached_commitment = data_man
+            .get_epoch_execution_commitment(&deferred_epoch_hash)
+            .map(|commitment| commitment.clone());
+        let db_commitment = data_man
+            .db_manager
+            .epoch_execution_commitment_from_db(&deferred_epoch_hash);
+        let commitments =
+            vec![(false, cached_commitment), (true, db_commitment)];
+
+        let mut mismatches = Vec::new();
+        for (from_db, maybe_commitment) in commitments {
+            let commitment = match maybe_commitment {
+                Some(commitment) => commitment,
+                None => continue,
+            };
+            let local_state_root =
+                commitment.state_root_with_aux_info.aux_info.state_root_hash;
+            if *header.deferred_state_root() != local_state_root
+                || *header.deferred_receipts_root() != commitment.receipts_root
+                || *header.deferred_logs_bloom_hash()
+                    != commitment.logs_bloom_hash
+            {
+                mismatches.push(DeferredRootMismatch {
+                    pivot_height,
+                    pivot_hash,
+                    deferred_epoch_hash,
+                    from_db,
+                    header_state_root: *header.deferred_state_root(),
+                    local_state_root,
+                    header_receipts_root: *header.deferred_receipts_root(),
+                    local_receipts_root: commitment.receipts_root,
+                    header_logs_bloom_hash: *header.deferred_logs_bloom_hash(),
+                    local_logs_bloom_hash: commitment.logs_bloom_hash,
+                });
+            }
+        }
+        mismatches
+    }
+
+    fn dump_mismatch(&self, mismatch: &DeferredRootMismatch) {
+        let dir = match &self.config.dump_dir {
+            Some(dir) => dir,
+            None => return,
+        };
+        let path = Path::new(dir).join(format!(
+            "{}_{:?}_{}.json",
+            mismatch.pivot_height,
+            mismatch.pivot_hash,
+            if mismatch.from_db { "db" } else { "cache" },
+        ));
+        let result = serde_json::to_string(mismatch)
+            .map_err(|e| e.to_string())
+            .and_then(|content| {
+                fs::create_dir_all(dir)
+                    .and_then(|_| fs::write(&path, content))
+                    .map_err(|e| e.to_string())
+            });
+        if let Err(e) = result {
+            warn!("Deferred root auditor: failed to write {:?}: {}", path, e);
+        }
+    }
+}
@@ -0,0 +1,68 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+/// `EpochFences` counts the fences held by the readers that copy arena indices
+/// out of `ConsensusGraphInner` and use them again after releasing the lock,
+/// e.g. long-running iterations over the pivot chain.
+///
+/// While any fence is held, the arena slots of the blocks removed from the
+/// consensus graph are retired instead of freed, so they are not reused by new
+/// blocks. A copied index thus refers either to the same block or to a retired
+/// slot, and never to another block inserted in the meantime. The retired
+/// slots are reclaimed when the graph is modified after all the fences are
+/// released.
+#[derive(Default)]
+pub struct EpochFences {
+    active_count: AtomicUsize,
+}
+
+impl EpochFences {
+    /// Acquire a fence. The fence must be acquired before the arena indices
+    /// are copied.
+    pub fn acquire(self: &Arc<Self>) -> EpochFence {
+        self.active_count.fetch_add(1, Ordering::SeqCst);
+        EpochFence {
+            fences: self.clone(),
+        }
+    }
+
+    pub fn is_fenced(&self) -> bool {
+        self.active_count.load(Ordering::SeqCst) != 0
+    }
+}
+
+/// A fence that is released when dropped.
+pub struct EpochFence {
+    fences: Arc<EpochFences>,
+}
+
+impl Drop for EpochFence {
+    fn drop(&mut self) {
+        self.fences.active_count.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EpochFences;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_fence_release() {
+        let fences = Arc::new(EpochFences::default());
+        assert!(!fences.is_fenced());
+        let fence1 = fences.acquire();
+        let fence2 = fences.acquire();
+        assert!(fences.is_fenced());
+        drop(fence1);
+        assert!(fences.is_fenced());
+        drop(fence2);
+        assert!(!fences.is_fenced());
+    }
+}
@@ -0,0 +1,175 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::block_data_manager::BlockDataManager;
+use cfx_statedb::{Result as DbResult, StateDb, StateDbExt};
+use cfx_storage::StorageManagerTrait;
+use cfx_types::H256;
+use parking_lot::Mutex;
+use primitives::{Action, Block};
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{channel, Sender},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+};
+
+/// The maximum number of epochs waiting to be prefetched. The epochs enqueued
+/// after the limit is reached are not prefetched, because their execution is
+/// too far away for the warmed cache entries to survive.
+const MAX_PENDING_PREFETCH_EPOCHS: usize = 8;
+
+struct EpochPrefetchTask {
+    epoch_hash: H256,
+    epoch_block_hashes: Vec<H256>,
+    /// The pivot block and the blocks of the epoch whose rewards are computed
+    /// in this epoch.
+    reward_epoch: Option<(H256, Vec<H256>)>,
+}
+
+/// `EpochDependencyPrefetcher` loads the data needed to execute an epoch into
+/// the caches when the epoch is enqueued, so the I/O overlaps with the
+/// execution of the epochs enqueued before it. The data includes the blocks
+/// of the epoch, the accounts touched by its transactions in the parent
+/// state, and the receipts of the reward epoch. All of them are loaded
+/// on demand during the execution anyway, so a task that fails or is skipped
+/// only loses the overlap.
+pub struct EpochDependencyPrefetcher {
+    task_sender: Mutex<Option<Sender<EpochPrefetchTask>>>,
+    pending_count: Arc<AtomicUsize>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl EpochDependencyPrefetcher {
+    pub fn start(data_man: Arc<BlockDataManager>) -> Self {
+        let (task_sender, task_receiver) = channel::<EpochPrefetchTask>();
+        let pending_count = Arc::new(AtomicUsize::new(0));
+        let worker_pending_count = pending_count.clone();
+        let thread = thread::Builder::new()
+            .name("Epoch dependency prefetcher".into())
+            .spawn(move || {
+                while let Ok(task) = task_receiver.recv() {
+                    prefetch_epoch(&data_man, &task);
+                    worker_pending_count.fetch_sub(1, Ordering::SeqCst);
+                }
+            })
+            .expect("Cannot fail");
+        EpochDependencyPrefetcher {
+            task_sender: Mutex::new(Some(task_sender)),
+            pending_count,
+            thread: Mutex::new(Some(thread)),
+        }
+    }
+
+    /// Prefetch the dependencies of the epoch `epoch_hash` asynchronously.
+    /// `reward_epoch_blocks` are the blocks whose rewards are computed in this
+    /// epoch, with the pivot block at last.
+    pub fn prefetch(
+        &self, epoch_hash: H256, epoch_block_hashes: &[H256],
+        reward_epoch_blocks: Option<&[Arc<Block>]>,
+    )
+    {
+        let task_sender = self.task_sender.lock();
+        let task_sender = match &*task_sender {
+            Some(task_sender) => task_sender,
+            None => return,
+        };
+        if self.pending_count.load(Ordering::SeqCst)
+            >= MAX_PENDING_PREFETCH_EPOCHS
+        {
+            debug!("Skip prefetching epoch {:?}", epoch_hash);
+            return;
+        }
+        let reward_epoch = reward_epoch_blocks.and_then(|blocks| {
+            blocks.last().map(|pivot| {
+                (pivot.hash(), blocks.iter().map(|b| b.hash()).collect())
+            })
+        });
+        self.pending_count.fetch_add(1, Ordering::SeqCst);
+        if task_sender
+            .send(EpochPrefetchTask {
+                epoch_hash,
+                epoch_block_hashes: epoch_block_hashes.to_vec(),
+                reward_epoch,
+            })
+            .is_err()
+        {
+            self.pending_count.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Stop the prefetcher thread after the pending tasks are processed.
+    pub fn stop(&self) {
+        self.task_sender.lock().take();
+        if let Some(thread) = self.thread.lock().take() {
+            thread.join().ok();
+        }
+    }
+}
+
+fn prefetch_epoch(data_man: &BlockDataManager, task: &EpochPrefetchTask) {
+    if data_man.epoch_executed(&task.epoch_hash) {
+        return;
+    }
+    let blocks = match data_man.blocks_by_hash_list(
+        &task.epoch_block_hashes,
+        true, /* update_cache */
+    ) {
+        Some(blocks) => blocks,
+        None => return,
+    };
+    if let Err(e) = prefetch_parent_state(data_man, &blocks) {
+        debug!(
+            "Failed to prefetch the parent state of epoch {:?}: {:?}",
+            task.epoch_hash, e
+        );
+    }
+    if let Some((reward_epoch_hash, reward_block_hashes)) = &task.reward_epoch {
+        for hash in reward_block_hashes {
+            data_man.block_execution_result_by_hash_with_epoch(
+                hash,
+                reward_epoch_hash,
+                false, /* update_pivot_assumption */
+                true,  /* update_cache */
+            );
+        }
+    }
+}
+
+/// Read the accounts touched by the transactions of the epoch from the state
+/// of the parent epoch, so the trie nodes on their paths are cached. It is
+/// skipped if the parent epoch is not executed yet.
+fn prefetch_parent_state(
+    data_man: &BlockDataManager, blocks: &[Arc<Block>],
+) -> DbResult<()> {
+    let parent_hash = match blocks.last() {
+        Some(pivot_block) => *pivot_block.block_header.parent_hash(),
+        None => return Ok(()),
+    };
+    if !data_man.epoch_executed(&parent_hash) {
+        return Ok(());
+    }
+    let state_index = match data_man.get_state_readonly_index(&parent_hash) {
+        Some(state_index) => state_index,
+        None => return Ok(()),
+    };
+    let state = match data_man
+        .storage_manager
+        .get_state_no_commit(state_index, /* try_open = */ true)?
+    {
+        Some(state) => StateDb::new(state),
+        None => return Ok(()),
+    };
+    for block in blocks {
+        for transaction in &block.transactions {
+            state.get_account(&transaction.sender)?;
+            if let Action::Call(address) = &transaction.action {
+                state.get_account(address)?;
+            }
+        }
+    }
+    Ok(())
+}
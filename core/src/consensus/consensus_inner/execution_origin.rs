@@ -0,0 +1,76 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use std::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// Where the execution of an epoch is requested.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ExecutionOrigin {
+    /// Epochs on the pivot chain enqueued by `on_new_block`.
+    NewBlock,
+    /// Epochs executed before the pivot chain reaches their deferred
+    /// execution.
+    Optimistic,
+    /// Epochs replayed to compute the state of a fork on request.
+    ForkReplay,
+    /// Epochs recomputed by `construct_pivot_state` at startup.
+    ConstructPivot,
+    /// Epochs recomputed for the debug dump of an invalid state root.
+    DebugRecompute,
+}
+
+impl ExecutionOrigin {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExecutionOrigin::NewBlock => "new_block",
+            ExecutionOrigin::Optimistic => "optimistic",
+            ExecutionOrigin::ForkReplay => "fork_replay",
+            ExecutionOrigin::ConstructPivot => "construct_pivot",
+            ExecutionOrigin::DebugRecompute => "debug_recompute",
+        }
+    }
+}
+
+static NEXT_PIPELINE_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// Identifies one epoch execution in the executor logs, the execution
+/// statistics and the debug dumps, so the load can be attributed to its
+/// origin.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ExecutionPipelineId {
+    pub origin: ExecutionOrigin,
+    /// Unique among the executions since the process starts.
+    pub seq: u64,
+}
+
+impl ExecutionPipelineId {
+    pub fn next(origin: ExecutionOrigin) -> Self {
+        Self {
+            origin,
+            seq: NEXT_PIPELINE_SEQ.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+}
+
+impl fmt::Display for ExecutionPipelineId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}#{}", self.origin.name(), self.seq)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ExecutionOrigin, ExecutionPipelineId};
+
+    #[test]
+    fn test_pipeline_ids() {
+        let first = ExecutionPipelineId::next(ExecutionOrigin::ForkReplay);
+        let second = ExecutionPipelineId::next(ExecutionOrigin::ForkReplay);
+        assert!(second.seq > first.seq);
+        assert_eq!(first.to_string(), format!("fork_replay#{}", first.seq));
+    }
+}
@@ -0,0 +1,155 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use std::{cmp::max, collections::VecDeque};
+
+/// The execution tasks are scheduled by classes, and tasks in the same class
+/// are executed in the order they are enqueued.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ExecutionTaskClass {
+    /// Epochs on the pivot chain that follow the chain tip.
+    Head = 0,
+    /// Epochs executed to compute the state of a fork on request.
+    Replay = 1,
+}
+
+const CLASS_COUNT: usize = 2;
+
+const CLASSES: [ExecutionTaskClass; CLASS_COUNT] =
+    [ExecutionTaskClass::Head, ExecutionTaskClass::Replay];
+
+/// The virtual time used by a task of weight 1.
+const VIRTUAL_TIME_UNIT: u64 = 1_000_000;
+
+/// `WeightedFairQueue` schedules the tasks of different classes with weighted
+/// fair queuing. When all classes are busy, each class gets a share of the
+/// dequeued tasks proportional to its weight. A class that has been idle does
+/// not accumulate credits, so it cannot starve the others when it becomes
+/// busy again.
+pub struct WeightedFairQueue<T> {
+    queues: [VecDeque<T>; CLASS_COUNT],
+    /// The virtual time consumed by one task of each class.
+    costs: [u64; CLASS_COUNT],
+    /// The virtual time of the next task of each class.
+    virtual_times: [u64; CLASS_COUNT],
+    /// The virtual time of the last dequeued task.
+    current_virtual_time: u64,
+}
+
+impl<T> WeightedFairQueue<T> {
+    /// A weight of 0 is treated as 1.
+    pub fn new(head_weight: u32, replay_weight: u32) -> Self {
+        let cost = |weight: u32| VIRTUAL_TIME_UNIT / max(weight, 1) as u64;
+        Self {
+            queues: [VecDeque::new(), VecDeque::new()],
+            costs: [cost(head_weight), cost(replay_weight)],
+            virtual_times: [0; CLASS_COUNT],
+            current_virtual_time: 0,
+        }
+    }
+
+    pub fn push(&mut self, class: ExecutionTaskClass, task: T) {
+        let index = class as usize;
+        if self.queues[index].is_empty() {
+            self.virtual_times[index] =
+                max(self.virtual_times[index], self.current_virtual_time);
+        }
+        self.queues[index].push_back(task);
+    }
+
+    pub fn len(&self) -> usize {
+        self.queues.iter().map(|queue| queue.len()).sum()
+    }
+
+    /// Dequeue the next task among the classes for which `is_available`
+    /// returns `true` with the first task of the class.
+    pub fn pop<F>(
+        &mut self, is_available: F,
+    ) -> Option<(ExecutionTaskClass, T)>
+    where F: Fn(ExecutionTaskClass, &T) -> bool {
+        let index = (0..CLASS_COUNT)
+            .filter(|i| {
+                self.queues[*i]
+                    .front()
+                    .map_or(false, |task| is_available(CLASSES[*i], task))
+            })
+            .min_by_key(|i| self.virtual_times[*i])?;
+        self.current_virtual_time = self.virtual_times[index];
+        self.virtual_times[index] += self.costs[index];
+        self.queues[index]
+            .pop_front()
+            .map(|task| (CLASSES[index], task))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ExecutionTaskClass, WeightedFairQueue};
+
+    fn pop_any<T>(queue: &mut WeightedFairQueue<T>) -> Option<T> {
+        queue.pop(|_, _| true).map(|(_, task)| task)
+    }
+
+    #[test]
+    fn test_weighted_share() {
+        let mut queue = WeightedFairQueue::new(3, 1);
+        for i in 0..8 {
+            queue.push(ExecutionTaskClass::Head, ('h', i));
+            queue.push(ExecutionTaskClass::Replay, ('r', i));
+        }
+        assert_eq!(queue.len(), 16);
+        let first: Vec<_> =
+            (0..8).map(|_| pop_any(&mut queue).unwrap()).collect();
+        assert_eq!(first.iter().filter(|(c, _)| *c == 'h').count(), 6);
+        // Tasks of the same class keep their order.
+        let heads: Vec<_> = first
+            .iter()
+            .filter(|(c, _)| *c == 'h')
+            .map(|t| t.1)
+            .collect();
+        assert_eq!(heads, vec![0, 1, 2, 3, 4, 5]);
+        let rest: Vec<_> =
+            (0..8).map(|_| pop_any(&mut queue).unwrap()).collect();
+        assert_eq!(rest.iter().filter(|(c, _)| *c == 'h').count(), 2);
+        assert!(pop_any(&mut queue).is_none());
+        assert_eq!(queue.len(), 0);
+    }
+
+    #[test]
+    fn test_idle_class_has_no_credit() {
+        let mut queue = WeightedFairQueue::new(1, 1);
+        for i in 0..10 {
+            queue.push(ExecutionTaskClass::Replay, i);
+        }
+        for _ in 0..5 {
+            pop_any(&mut queue);
+        }
+        for i in 100..105 {
+            queue.push(ExecutionTaskClass::Head, i);
+        }
+        // The head class does not get extra share for the time it was idle.
+        let order: Vec<_> =
+            (0..4).map(|_| pop_any(&mut queue).unwrap()).collect();
+        assert_eq!(order.iter().filter(|x| **x >= 100).count(), 3);
+        let rest: Vec<_> =
+            (0..6).map(|_| pop_any(&mut queue).unwrap()).collect();
+        assert_eq!(rest.iter().filter(|x| **x >= 100).count(), 2);
+        assert!(pop_any(&mut queue).is_none());
+    }
+
+    #[test]
+    fn test_unavailable_class_is_skipped() {
+        let mut queue = WeightedFairQueue::new(1, 1);
+        queue.push(ExecutionTaskClass::Head, 0);
+        queue.push(ExecutionTaskClass::Replay, 1);
+        queue.push(ExecutionTaskClass::Replay, 2);
+        let not_head = |class: ExecutionTaskClass, _: &i32| {
+            class != ExecutionTaskClass::Head
+        };
+        assert_eq!(queue.pop(not_head), Some((ExecutionTaskClass::Replay, 1)));
+        assert_eq!(queue.pop(not_head), Some((ExecutionTaskClass::Replay, 2)));
+        assert_eq!(queue.pop(not_head), None);
+        assert_eq!(pop_any(&mut queue), Some(0));
+    }
+}
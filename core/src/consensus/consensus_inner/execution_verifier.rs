@@ -0,0 +1,271 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::{
+    block_data_manager::BlockDataManager,
+    consensus::{
+        consensus_inner::consensus_executor::ConsensusExecutor,
+        ConsensusGraphInner,
+    },
+    statistics::lock_contention::InstrumentedRwLock,
+};
+use bit_set::BitSet;
+use cfx_types::H256;
+use parking_lot::Mutex;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+    thread,
+};
+
+/// The maximum number of claims kept with their verification status. The
+/// claims received after the limit is reached are not verified.
+const MAX_TRACKED_CLAIMS: usize = 100_000;
+
+/// The execution result of an epoch claimed by a peer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClaimedExecutionResult {
+    pub epoch_hash: H256,
+    pub epoch_height: u64,
+    pub state_root_hash: H256,
+    pub receipts_root: H256,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExecutionVerificationStatus {
+    /// The claim is waiting for or under verification.
+    Pending,
+    /// The claim matches the local execution result.
+    Verified,
+    Mismatched {
+        local_state_root_hash: H256,
+        local_receipts_root: H256,
+    },
+    /// The epoch cannot be executed locally, e.g. because the state before it
+    /// is not available.
+    Failed(String),
+}
+
+struct VerifierState {
+    claims:
+        HashMap<H256, (ClaimedExecutionResult, ExecutionVerificationStatus)>,
+    queue: VecDeque<H256>,
+    running_workers: usize,
+    /// The heights of the epochs whose claims are verified.
+    verified_heights: BitSet,
+    /// The heights of the epochs whose claims do not match the local
+    /// execution.
+    mismatched_heights: BitSet,
+}
+
+/// `ExecutionVerificationService` cross-checks the execution results claimed
+/// by peers during sync, e.g. the state roots and receipts roots in a synced
+/// snapshot manifest. A claim is verified against the local execution result
+/// if the epoch is already executed, otherwise the epoch is re-executed
+/// locally as a replay task. At most `max_concurrency` claims are verified at
+/// the same time.
+///
+/// The verified epochs are tracked by height, so the historical epochs that
+/// we still need to execute ourselves can be prioritized with
+/// `epochs_to_execute`.
+pub struct ExecutionVerificationService {
+    data_man: Arc<BlockDataManager>,
+    executor: Arc<ConsensusExecutor>,
+    inner: Arc<InstrumentedRwLock<ConsensusGraphInner>>,
+    max_concurrency: usize,
+    state: Mutex<VerifierState>,
+}
+
+impl ExecutionVerificationService {
+    /// Claims are ignored if `max_concurrency` is 0.
+    pub fn new(
+        data_man: Arc<BlockDataManager>, executor: Arc<ConsensusExecutor>,
+        inner: Arc<InstrumentedRwLock<ConsensusGraphInner>>, max_concurrency: usize,
+    ) -> Arc<Self>
+    {
+        Arc::new(ExecutionVerificationService {
+            data_man,
+            executor,
+            inner,
+            max_concurrency,
+            state: Mutex::new(VerifierState {
+                claims: Default::default(),
+                queue: Default::default(),
+                running_workers: 0,
+                verified_heights: BitSet::new(),
+                mismatched_heights: BitSet::new(),
+            }),
+        })
+    }
+
+    /// Accept the execution results claimed by a peer. They are verified
+    /// asynchronously. Only the first claim of an epoch is verified.
+    pub fn submit_claims(
+        self: &Arc<Self>, claims: Vec<ClaimedExecutionResult>,
+    ) {
+        if self.max_concurrency == 0 {
+            return;
+        }
+        let mut state = self.state.lock();
+        for claim in claims {
+            if let Some((existing, _)) = state.claims.get(&claim.epoch_hash) {
+                if *existing != claim {
+                    warn!(
+                        "Conflicting execution results claimed: existing={:?} new={:?}",
+                        existing, claim
+                    );
+                }
+                continue;
+            }
+            if state.claims.len() >= MAX_TRACKED_CLAIMS {
+                debug!("Skip verifying claim {:?}", claim);
+                continue;
+            }
+            state.claims.insert(
+                claim.epoch_hash,
+                (claim, ExecutionVerificationStatus::Pending),
+            );
+            state.queue.push_back(claim.epoch_hash);
+        }
+        while state.running_workers < self.max_concurrency
+            && !state.queue.is_empty()
+        {
+            let service = self.clone();
+            if let Err(e) = thread::Builder::new()
+                .name("Execution verifier".into())
+                .spawn(move || service.run_worker())
+            {
+                warn!("Failed to start execution verifier: {}", e);
+                break;
+            }
+            state.running_workers += 1;
+        }
+    }
+
+    pub fn status(
+        &self, epoch_hash: &H256,
+    ) -> Option<ExecutionVerificationStatus> {
+        self.state
+            .lock()
+            .claims
+            .get(epoch_hash)
+            .map(|(_, status)| status.clone())
+    }
+
+    pub fn is_verified(&self, epoch_height: u64) -> bool {
+        self.state
+            .lock()
+            .verified_heights
+            .contains(epoch_height as usize)
+    }
+
+    /// Return at most `limit` heights in `[start_height, end_height]` whose
+    /// claims are not verified. The epochs with mismatched claims are returned
+    /// first, and then the others in the ascending order of heights.
+    pub fn epochs_to_execute(
+        &self, start_height: u64, end_height: u64, limit: usize,
+    ) -> Vec<u64> {
+        let state = self.state.lock();
+        let mut mismatched = Vec::new();
+        let mut unverified = Vec::new();
+        for height in start_height..=end_height {
+            let index = height as usize;
+            if state.verified_heights.contains(index) {
+                continue;
+            }
+            if state.mismatched_heights.contains(index) {
+                mismatched.push(height);
+                if mismatched.len() >= limit {
+                    break;
+                }
+            } else if unverified.len() < limit {
+                unverified.push(height);
+            }
+        }
+        mismatched.extend(unverified);
+        mismatched.truncate(limit);
+        mismatched
+    }
+
+    fn run_worker(&self) {
+        loop {
+            let claim = {
+                let mut state = self.state.lock();
+                let claim = state
+                    .queue
+                    .pop_front()
+                    .and_then(|hash| state.claims.get(&hash))
+                    .map(|(claim, _)| *claim);
+                match claim {
+                    Some(claim) => claim,
+                    None => {
+                        state.running_workers -= 1;
+                        return;
+                    }
+                }
+            };
+            let status = self.verify(&claim);
+            let mut state = self.state.lock();
+            let index = claim.epoch_height as usize;
+            match &status {
+                ExecutionVerificationStatus::Verified => {
+                    state.verified_heights.insert(index);
+                    state.mismatched_heights.remove(index);
+                }
+                ExecutionVerificationStatus::Mismatched { .. } => {
+                    warn!(
+                        "Claimed execution result mismatches: claim={:?} result={:?}",
+                        claim, status
+                    );
+                    state.mismatched_heights.insert(index);
+                }
+                _ => {}
+            }
+            state.claims.insert(claim.epoch_hash, (claim, status));
+        }
+    }
+
+    fn verify(
+        &self, claim: &ClaimedExecutionResult,
+    ) -> ExecutionVerificationStatus {
+        let epoch_hash = &claim.epoch_hash;
+        if self
+            .data_man
+            .get_epoch_execution_commitment_with_db(epoch_hash)
+            .is_none()
+        {
+            if let Err(e) = self
+                .executor
+                .compute_state_for_block_unlocked(epoch_hash, &self.inner)
+            {
+                return ExecutionVerificationStatus::Failed(e.to_string());
+            }
+        }
+        match self
+            .data_man
+            .get_epoch_execution_commitment_with_db(epoch_hash)
+        {
+            Some(commitment) => {
+                let local_state_root_hash = commitment
+                    .state_root_with_aux_info
+                    .aux_info
+                    .state_root_hash;
+                let local_receipts_root = commitment.receipts_root;
+                if local_state_root_hash == claim.state_root_hash
+                    && local_receipts_root == claim.receipts_root
+                {
+                    ExecutionVerificationStatus::Verified
+                } else {
+                    ExecutionVerificationStatus::Mismatched {
+                        local_state_root_hash,
+                        local_receipts_root,
+                    }
+                }
+            }
+            None => ExecutionVerificationStatus::Failed(
+                "Execution commitment is not found".into(),
+            ),
+        }
+    }
+}
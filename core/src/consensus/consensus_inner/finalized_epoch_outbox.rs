@@ -0,0 +1,152 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::{
+    block_data_manager::{
+        BlockDataManager, FinalizedEpochEvent, FinalizedEpochOutboxProgress,
+    },
+    consensus::{
+        consensus_inner::confirmation_meter::ConfirmationMeter,
+        ConsensusGraphInner,
+    },
+};
+use cfx_types::H256;
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+/// `FinalizedEpochOutbox` is a persistent queue of finalized epochs for
+/// external indexers.
+///
+/// An epoch is appended once its execution is committed and its confirmation
+/// risk drops below `risk_threshold`. Epochs are appended strictly in the
+/// order of epoch numbers and each epoch number is appended exactly once, even
+/// across restarts. Consumers read the pending events and acknowledge them by
+/// sequence number, after which the events are removed from the database.
+pub struct FinalizedEpochOutbox {
+    data_man: Arc<BlockDataManager>,
+    risk_threshold: f64,
+    progress: Mutex<FinalizedEpochOutboxProgress>,
+}
+
+impl FinalizedEpochOutbox {
+    pub fn new(
+        data_man: Arc<BlockDataManager>, risk_threshold: f64,
+        inner: &ConsensusGraphInner,
+    ) -> Self
+    {
+        let mut progress = data_man
+            .finalized_epoch_outbox_progress()
+            .unwrap_or_else(|| FinalizedEpochOutboxProgress {
+                next_epoch_number: inner.cur_era_stable_height,
+                ..Default::default()
+            });
+        // The node may stop after an event is written but before the progress
+        // is updated, so we move forward over the events already in db.
+        while let Some(event) =
+            data_man.finalized_epoch_event(progress.next_seq)
+        {
+            progress.next_seq += 1;
+            progress.next_epoch_number = event.epoch_number + 1;
+            progress.last_epoch_hash = event.epoch_hash;
+        }
+        debug!("Finalized epoch outbox starts with {:?}", progress);
+        Self {
+            data_man,
+            risk_threshold,
+            progress: Mutex::new(progress),
+        }
+    }
+
+    /// Append all the epochs that become finalized. This is called after a new
+    /// block is processed in consensus.
+    pub fn on_new_block(
+        &self, inner: &ConsensusGraphInner, meter: &ConfirmationMeter,
+    ) {
+        let mut progress = self.progress.lock();
+        while progress.next_epoch_number <= inner.best_state_epoch_number() {
+            let epoch_number = progress.next_epoch_number;
+            let epoch_hash =
+                match inner.get_pivot_hash_from_epoch_number(epoch_number) {
+                    Ok(hash) => hash,
+                    Err(_) => break,
+                };
+            if self
+                .data_man
+                .get_epoch_execution_commitment_with_db(&epoch_hash)
+                .is_none()
+            {
+                // Not executed yet.
+                break;
+            }
+            match meter.confirmation_risk_by_hash(inner, epoch_hash) {
+                Some(risk) if risk <= self.risk_threshold => {}
+                _ => break,
+            }
+            let block_hashes = match inner.block_hashes_by_epoch(epoch_number) {
+                Ok(hashes) => hashes,
+                Err(_) => break,
+            };
+            self.check_parent(&progress.last_epoch_hash, &epoch_hash);
+
+            let event = FinalizedEpochEvent {
+                seq: progress.next_seq,
+                epoch_number,
+                epoch_hash,
+                block_hashes,
+            };
+            self.data_man.insert_finalized_epoch_event(&event);
+            progress.next_seq += 1;
+            progress.next_epoch_number += 1;
+            progress.last_epoch_hash = epoch_hash;
+            self.data_man
+                .insert_finalized_epoch_outbox_progress(&*progress);
+            debug!("Finalized epoch appended to outbox: {:?}", event);
+        }
+    }
+
+    /// Return at most `limit` events that are not acknowledged yet.
+    pub fn pending_events(&self, limit: usize) -> Vec<FinalizedEpochEvent> {
+        let progress = *self.progress.lock();
+        (progress.first_unacked_seq..progress.next_seq)
+            .filter_map(|seq| self.data_man.finalized_epoch_event(seq))
+            .take(limit)
+            .collect()
+    }
+
+    /// Acknowledge all events with sequence number not larger than `seq`.
+    pub fn acknowledge(&self, seq: u64) -> Result<(), String> {
+        let mut progress = self.progress.lock();
+        if seq >= progress.next_seq {
+            return Err(format!(
+                "Finalized epoch event {} does not exist, next_seq={}",
+                seq, progress.next_seq
+            ));
+        }
+        for acked_seq in progress.first_unacked_seq..=seq {
+            self.data_man.remove_finalized_epoch_event(acked_seq);
+        }
+        if seq >= progress.first_unacked_seq {
+            progress.first_unacked_seq = seq + 1;
+            self.data_man
+                .insert_finalized_epoch_outbox_progress(&*progress);
+        }
+        Ok(())
+    }
+
+    fn check_parent(&self, last_epoch_hash: &H256, epoch_hash: &H256) {
+        if last_epoch_hash.is_zero() {
+            return;
+        }
+        if let Some(header) = self.data_man.block_header_by_hash(epoch_hash) {
+            if header.parent_hash() != last_epoch_hash {
+                // Finalized events cannot be retracted, so we can only report
+                // it here.
+                warn!(
+                    "Pivot chain reverted below finalized epoch {:?}, new epoch {:?}",
+                    last_epoch_hash, epoch_hash
+                );
+            }
+        }
+    }
+}
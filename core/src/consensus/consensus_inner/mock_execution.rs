@@ -0,0 +1,146 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::block_data_manager::BlockDataManager;
+use cfx_internal_common::{EpochExecutionCommitment, StateRootWithAuxInfo};
+use cfx_types::{H256, KECCAK_EMPTY_BLOOM};
+use hash::keccak;
+use parking_lot::RwLock;
+use std::{collections::HashMap, sync::Arc};
+
+/// `MockExecutionBackend` replaces the transaction execution in bench mode.
+/// An epoch is "executed" when it is enqueued, and gets pseudo roots that
+/// are the hashes of its content and the pseudo state root of its parent.
+///
+/// Since the roots of an epoch depend on the roots of all the epochs before
+/// it, two runs get the same roots only if they execute the same epochs in
+/// the same order. An epoch executed before its parent fails, and waiting for
+/// its result returns the error.
+pub struct MockExecutionBackend {
+    data_man: Arc<BlockDataManager>,
+    results: RwLock<HashMap<H256, Result<EpochExecutionCommitment, String>>>,
+}
+
+impl MockExecutionBackend {
+    pub fn new(data_man: Arc<BlockDataManager>) -> Self {
+        MockExecutionBackend {
+            data_man,
+            results: Default::default(),
+        }
+    }
+
+    pub fn execute_epoch(
+        &self, epoch_hash: H256, epoch_block_hashes: &[H256],
+        start_block_number: u64,
+    )
+    {
+        let result = self.parent_state_root_hash(&epoch_hash).map(
+            |parent_state_root_hash| {
+                pseudo_commitment(
+                    &parent_state_root_hash,
+                    &epoch_hash,
+                    epoch_block_hashes,
+                    start_block_number,
+                )
+            },
+        );
+        if let Err(e) = &result {
+            warn!("Mock execution of epoch {:?} failed: {}", epoch_hash, e);
+        }
+        self.results.write().insert(epoch_hash, result);
+    }
+
+    /// Return the result of the epoch, or an error if it is not executed.
+    pub fn execution_result(
+        &self, epoch_hash: &H256,
+    ) -> Result<EpochExecutionCommitment, String> {
+        if let Some(result) = self.results.read().get(epoch_hash) {
+            return result.clone();
+        }
+        // The epochs executed for real, e.g. the genesis.
+        self.data_man
+            .get_epoch_execution_commitment_with_db(epoch_hash)
+            .ok_or_else(|| {
+                format!("Epoch {:?} is not executed by the mock", epoch_hash)
+            })
+    }
+
+    fn parent_state_root_hash(
+        &self, epoch_hash: &H256,
+    ) -> Result<H256, String> {
+        let parent_hash = *self
+            .data_man
+            .block_header_by_hash(epoch_hash)
+            .ok_or_else(|| format!("Header of {:?} not found", epoch_hash))?
+            .parent_hash();
+        self.execution_result(&parent_hash)
+            .map(|commitment| {
+                commitment.state_root_with_aux_info.aux_info.state_root_hash
+            })
+            .map_err(|e| {
+                format!(
+                    "Epoch executed before its parent {:?}: {}",
+                    parent_hash, e
+                )
+            })
+    }
+}
+
+fn pseudo_commitment(
+    parent_state_root_hash: &H256, epoch_hash: &H256,
+    epoch_block_hashes: &[H256], start_block_number: u64,
+) -> EpochExecutionCommitment
+{
+    let mut content = Vec::with_capacity(32 * (epoch_block_hashes.len() + 2));
+    content.extend_from_slice(epoch_hash.as_bytes());
+    for hash in epoch_block_hashes {
+        content.extend_from_slice(hash.as_bytes());
+    }
+    content.extend_from_slice(&start_block_number.to_be_bytes());
+    let receipts_root = keccak(&content);
+    content.extend_from_slice(parent_state_root_hash.as_bytes());
+    EpochExecutionCommitment {
+        state_root_with_aux_info: StateRootWithAuxInfo::genesis(&keccak(
+            &content,
+        )),
+        receipts_root,
+        logs_bloom_hash: KECCAK_EMPTY_BLOOM,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::pseudo_commitment;
+    use cfx_types::H256;
+
+    fn hash(n: u64) -> H256 { H256::from_low_u64_be(n) }
+
+    #[test]
+    fn test_pseudo_commitment_depends_on_history() {
+        let commitment = |parent: u64, blocks: &[H256]| {
+            pseudo_commitment(&hash(parent), &hash(10), blocks, 5)
+        };
+        let blocks = [hash(1), hash(10)];
+        let original = commitment(0, &blocks);
+        assert_eq!(
+            original.state_root_with_aux_info,
+            commitment(0, &blocks).state_root_with_aux_info
+        );
+
+        let other_parent = commitment(1, &blocks);
+        assert_ne!(
+            original.state_root_with_aux_info,
+            other_parent.state_root_with_aux_info
+        );
+        // The receipts only depend on the epoch itself.
+        assert_eq!(original.receipts_root, other_parent.receipts_root);
+
+        let reordered = commitment(0, &[hash(10), hash(1)]);
+        assert_ne!(original.receipts_root, reordered.receipts_root);
+        assert_ne!(
+            original.state_root_with_aux_info,
+            reordered.state_root_with_aux_info
+        );
+    }
+}
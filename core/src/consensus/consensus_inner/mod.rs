@@ -3,27 +3,62 @@
 // See http://www.gnu.org/licenses/
 
 mod blame_verifier;
+pub mod block_validation;
 pub mod confirmation_meter;
 pub mod consensus_executor;
 pub mod consensus_new_block_handler;
+pub mod deferred_root_auditor;
+pub mod epoch_fence;
+mod epoch_prefetcher;
+pub mod execution_origin;
+mod execution_queue;
+pub mod execution_verifier;
+pub mod finalized_epoch_outbox;
+mod mock_execution;
+mod optimistic_depth;
+pub mod pivot_history;
+pub mod risk_model;
+mod shadow_execution;
+pub mod state_regeneration;
+mod traversal;
+mod weight;
+pub mod weight_injection_detector;
 
 use crate::{
     block_data_manager::{
         BlockDataManager, BlockExecutionResultWithEpoch, DataVersionTuple,
-        EpochExecutionContext,
+        EpochExecutionContext, EvictedEpochSets, PartialInvalidCause,
     },
+    clock::SharedClock,
     consensus::{
         anticone_cache::AnticoneCache,
-        consensus_inner::consensus_executor::ConsensusExecutor,
-        debug_recompute::log_invalid_state_root, pastset_cache::PastSetCache,
+        anticone_index::AnticoneIndex,
+        consensus_inner::{
+            consensus_executor::ConsensusExecutor,
+            epoch_fence::EpochFences,
+            optimistic_depth::OptimisticDepthController,
+            pivot_history::PivotSwitch,
+            traversal::TraversalPool,
+            weight::{
+                add_cumulative_weight, difficulty_to_weight,
+                scaled_difficulty_to_weight, scaled_weight_threshold,
+                sum_cumulative_weights,
+            },
+        },
+        debug_recompute::log_invalid_state_root,
+        era_descriptor::EraDescriptor,
+        pastset_cache::PastSetCache,
         MaybeExecutedTxExtraInfo, TransactionInfo,
     },
     pow::{target_difficulty, PowComputer, ProofOfWorkConfig},
+    spec::DeferredStateEpochCount,
     state_exposer::{ConsensusGraphBlockExecutionState, STATE_EXPOSER},
     verification::VerificationConfig,
 };
 use cfx_internal_common::{
-    consensus_api::StateMaintenanceTrait, EpochExecutionCommitment,
+    consensus_api::StateMaintenanceTrait,
+    debug::{WeightAuditDebugRecord, WeightDivergence},
+    EpochExecutionCommitment,
 };
 use cfx_parameters::{consensus::*, consensus_internal::*};
 use cfx_types::{H256, U256, U512};
@@ -39,6 +74,7 @@ use metrics::{Counter, CounterUsize};
 use primitives::{Block, BlockHeader, BlockHeaderBuilder, EpochId};
 use slab::Slab;
 use std::{
+    borrow::Cow,
     cmp::{max, min},
     collections::{BinaryHeap, HashMap, HashSet, VecDeque},
     convert::TryFrom,
@@ -72,6 +108,11 @@ pub struct ConsensusInnerConfig {
     /// execution and the block packaging and verification.
     /// optimistic_executed_height is the number of step to go ahead
     pub enable_optimistic_execution: bool,
+    /// The bounds of the number of epochs that optimistic execution may run
+    /// ahead of the deferred execution boundary. The depth is adapted to the
+    /// execution backlog and the recent pivot chain switches within them.
+    pub optimistic_execution_min_depth: u64,
+    pub optimistic_execution_max_depth: u64,
     /// Control whether we enable the state exposer for the testing purpose.
     pub enable_state_expose: bool,
 
@@ -79,6 +120,18 @@ pub struct ConsensusInnerConfig {
     /// directory specified here. This is useful for testing.
     pub debug_dump_dir_invalid_state_root: Option<String>,
     pub debug_invalid_state_root_epoch: Option<H256>,
+
+    /// If set, after every this number of inserted blocks, the weights
+    /// maintained in the link-cut trees are checked against the ones
+    /// recomputed from scratch, and the divergences are dumped into the
+    /// directory specified by `debug_dump_dir_weight_audit`.
+    pub debug_weight_audit_interval: Option<u64>,
+    pub debug_dump_dir_weight_audit: Option<String>,
+
+    /// If set, the epoch sets of the pivot blocks at least this number of
+    /// epochs below the best epoch are evicted to the database, and reloaded
+    /// when they are accessed again.
+    pub epoch_set_eviction_depth: Option<u64>,
 }
 
 #[derive(Copy, Clone, DeriveMallocSizeOf)]
@@ -89,6 +142,39 @@ pub struct StateBlameInfo {
     pub logs_bloom_vec_root: H256,
 }
 
+/// An epoch whose receipts are recomputed to check a mismatch in the deferred
+/// receipts root of a block. See `ConsensusGraphInner::stale_receipts_epochs`.
+#[derive(Debug)]
+pub struct ReceiptsRecomputeTask {
+    pub epoch_hash: H256,
+    pub epoch_block_hashes: Vec<H256>,
+    pub start_block_number: u64,
+}
+
+/// The subtree weights of the blocks in the current era, computed from
+/// scratch and reused by `ConsensusGraphInner::predict_mining_adaptive_block`.
+#[derive(Default)]
+pub struct SubtreeWeightSnapshot {
+    era_genesis_hash: H256,
+    /// `sequence_number_of_block_entrance` when the weights are computed.
+    sequence_number: u64,
+    weights: Vec<i128>,
+}
+
+/// The filter used to select terminals with
+/// `ConsensusGraphInner::filtered_terminal_hashes`.
+#[derive(Clone, Debug, Default)]
+pub struct TerminalFilter {
+    /// Exclude partially invalid terminals.
+    pub exclude_partial_invalid: bool,
+    /// Only include terminals in the same era as the pivot chain tip.
+    pub current_era_only: bool,
+    /// The number of terminals to skip in the ordered result.
+    pub offset: usize,
+    /// The maximal number of terminals to return.
+    pub limit: Option<usize>,
+}
+
 /// ConsensusGraphNodeData contains all extra information of a block that will
 /// change as the consensus graph state evolves (e.g., pivot chain changes).
 /// Unlike the ConsensusGraphNode fields, fields in ConsensusGraphNodeData will
@@ -130,8 +216,8 @@ pub struct ConsensusGraphNodeData {
     /// For cur_era_genesis, this field should NOT be used because they contain
     /// out-of-era blocks not maintained in the memory.
     ordered_executable_epoch_blocks: Vec<usize>,
-    /// If an epoch has more than ``EPOCH_EXECUTED_BLOCK_BOUND''. We will only
-    /// execute the last ``EPOCH_EXECUTED_BLOCK_BOUND'' and skip the
+    /// If an epoch has more than ``epoch_executed_block_bound''. We will only
+    /// execute the last ``epoch_executed_block_bound'' and skip the
     /// remaining. The `skipped_epoch_blocks` also contain those blocks that
     /// are not in the same era of the pivot block.
     /// We use the block hashes instead of block arena indices here to ensure
@@ -140,6 +226,10 @@ pub struct ConsensusGraphNodeData {
     /// It indicates whether `blockset_in_own_view_of_epoch` and
     /// `skipped_epoch_blocks` are cleared due to its size.
     blockset_cleared: bool,
+    /// It indicates whether `blockset_in_own_view_of_epoch` and
+    /// `ordered_executable_epoch_blocks` are evicted to the database. They
+    /// are empty in memory until reloaded.
+    epoch_sets_evicted: bool,
     /// The sequence number is used to identify the order of each block
     /// entering the consensus. The sequence number of the genesis is used
     /// by the syncronization layer to determine whether a block exists in
@@ -169,6 +259,27 @@ pub struct ConsensusGraphNodeData {
     /// It stores the correct blame info for the block if its state is invalid.
     /// It's evaluated when needed and acts as a cache.
     blame_info: Option<StateBlameInfo>,
+    /// How `state_valid` is decided. It is `None` if `state_valid` is not
+    /// evaluated or set for the genesis.
+    state_valid_source: Option<StateValidSource>,
+    /// Whether the receipts of the epoch of this block have been recomputed
+    /// to check a receipts root mismatch. It is done at most once for an
+    /// epoch.
+    receipts_recomputed: bool,
+}
+
+/// How the `state_valid` verdict of a block is reached.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, DeriveMallocSizeOf)]
+pub enum StateValidSource {
+    /// Compared with the local execution commitment of the deferred epoch.
+    Commitment,
+    /// Only the receipts root mismatched with the local commitment, so the
+    /// block was compared with the commitment after the receipts of the
+    /// deferred epoch were recomputed from scratch and rewritten.
+    RecomputedReceipts,
+    /// Inferred from the blame of a later block when recovering from the
+    /// database.
+    RecoveredBlame,
 }
 
 impl ConsensusGraphNodeData {
@@ -186,6 +297,7 @@ impl ConsensusGraphNodeData {
             ordered_executable_epoch_blocks: Default::default(),
             skipped_epoch_blocks: Default::default(),
             blockset_cleared: true,
+            epoch_sets_evicted: false,
             sequence_number,
             past_view_timer_longest_difficulty: 0,
             past_view_last_timer_block_arena_index: NULL,
@@ -195,6 +307,8 @@ impl ConsensusGraphNodeData {
             last_pivot_in_past: 0,
             state_valid: None,
             blame_info: None,
+            state_valid_source: None,
+            receipts_recomputed: false,
         }
     }
 }
@@ -218,6 +332,117 @@ impl Default for ConsensusGraphPivotData {
     }
 }
 
+/// An ancestor of a block checked in its adaptive decision. The block is
+/// adaptive if `subtree_weight - sibling_weight` is below the adjusted beta
+/// for any checked ancestor.
+#[derive(Clone, Debug)]
+pub struct GhastAncestorDecision {
+    pub hash: H256,
+    pub height: u64,
+    pub timer_chain_height: u64,
+    /// Whether the ancestor is at least `timer_chain_beta` timer blocks
+    /// before the block, so that it is checked.
+    pub checked: bool,
+    /// f(x): the subtree weight of the ancestor in the past view of the block.
+    pub subtree_weight: i128,
+    /// g(x): the total subtree weight of the siblings of the ancestor in the
+    /// past view of the block.
+    pub sibling_weight: i128,
+}
+
+/// The GHAST decision of a block recomputed in its past view, returned by
+/// `ConsensusGraph::ghast_decision`.
+#[derive(Clone, Debug)]
+pub struct GhastDecision {
+    pub hash: H256,
+    pub parent_hash: H256,
+    pub force_confirm_hash: H256,
+    /// The timer chain height in the past view of the block.
+    pub timer_chain_height: u64,
+    pub timer_chain_beta: u64,
+    /// The adaptive weight threshold scaled by the difficulty of the block.
+    pub adjusted_beta: i128,
+    /// The ancestors from the parent down to the child of the force confirmed
+    /// block. It is empty if the parent is not in the subtree of the force
+    /// confirmed block.
+    pub ancestors: Vec<GhastAncestorDecision>,
+    pub adaptive: bool,
+    /// Whether the parent is the heaviest choice among the blocks in the past
+    /// view of the block.
+    pub correct_parent: bool,
+    /// The decisions recorded when the block was inserted.
+    pub recorded_adaptive: bool,
+    pub recorded_partial_invalid: bool,
+}
+
+/// The GHAST state of a block in the consensus graph, returned by
+/// `ConsensusGraph::get_block_consensus_info`.
+#[derive(Clone, Debug)]
+pub struct BlockConsensusInfo {
+    pub hash: H256,
+    pub stable_id: u64,
+    pub height: u64,
+    pub parent_hash: H256,
+    /// `None` if the block is not in any epoch yet.
+    pub epoch_number: Option<u64>,
+    /// The height of the last pivot block in the past set of the block.
+    pub last_pivot_in_past: u64,
+    pub adaptive: bool,
+    pub is_heavy: bool,
+    pub is_timer: bool,
+    pub activated: bool,
+    pub pending: bool,
+    pub partial_invalid: bool,
+    /// The cause recorded in the database when the block is found partially
+    /// invalid.
+    pub partial_invalid_cause: Option<PartialInvalidCause>,
+    /// Whether the epoch of the block is not after the stable block of the
+    /// current era.
+    pub stable: bool,
+    /// The weight of the block itself.
+    pub block_weight: i128,
+    /// The total weight of the subtree of the block in `weight_tree`.
+    pub subtree_weight: i128,
+    /// The total weight of the past set of the block since the era genesis.
+    /// It is only maintained for the pivot blocks.
+    pub past_era_weight: Option<i128>,
+    /// `None` if the anticone of the block is not in the anticone cache.
+    pub anticone_size: Option<usize>,
+    /// `None` if the deferred state of the block is not checked yet.
+    pub state_valid: Option<bool>,
+    pub state_valid_source: Option<StateValidSource>,
+}
+
+/// The identifiers of a block in the consensus graph. The arena index of a
+/// removed block may be reused by another block, while the stable ID, i.e.
+/// the sequence number of the block, is never reused in a run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConsensusBlockIds {
+    pub stable_id: u64,
+    pub hash: H256,
+    pub arena_index: usize,
+}
+
+/// A pivot block with the consensus information shown by explorers, returned
+/// by `ConsensusGraph::get_pivot_segment`.
+#[derive(Clone, Debug)]
+pub struct PivotBlockAnnotation {
+    pub epoch_number: u64,
+    pub hash: H256,
+    /// The number of executable blocks in the epoch, including the pivot
+    /// block.
+    pub epoch_size: usize,
+    /// The gas used by the blocks in the epoch, or `None` if the epoch is not
+    /// executed on the current pivot chain.
+    pub epoch_gas_used: Option<U256>,
+    pub difficulty: U256,
+    pub adaptive: bool,
+    /// Whether the pivot block is not after the stable block of the current
+    /// era, so it will stay on the pivot chain.
+    pub stable: bool,
+    pub confirmation_risk: Option<f64>,
+}
+
 /// # Implementation details of Eras, Timer chain and Checkpoints
 ///
 /// Era in Conflux is defined based on the height of a block. Every
@@ -427,6 +652,10 @@ pub struct ConsensusGraphInner {
     /// data_man is the handle to access raw block data
     pub data_man: Arc<BlockDataManager>,
     pub inner_conf: ConsensusInnerConfig,
+    /// The deferred execution depth of the chain.
+    pub deferred_state_epoch_count: DeferredStateEpochCount,
+    /// The maximum number of blocks executed and rewarded in an epoch.
+    pub epoch_executed_block_bound: usize,
     pub pow_config: ProofOfWorkConfig,
     pub pow: Arc<PowComputer>,
     //executor: Arc<ConsensusExecutor>,
@@ -481,6 +710,11 @@ pub struct ConsensusGraphInner {
     /// The cache to store Anticone information of each node. This could be
     /// very large so we periodically remove old ones in the cache.
     anticone_cache: AnticoneCache,
+    /// The blocks of the current era indexed by their epoch numbers, used to
+    /// compute the anticones missing in `anticone_cache`.
+    anticone_index: AnticoneIndex,
+    /// The scratch buffers of the traversals over the arena.
+    traversal_pool: TraversalPool,
     pastset_cache: PastSetCache,
     sequence_number_of_block_entrance: u64,
 
@@ -499,6 +733,34 @@ pub struct ConsensusGraphInner {
     /// `true` before we enter `CacheUpSyncBlock`. We need to execute
     /// transactions and process state if it's `false`.
     header_only: bool,
+    /// The blocks inserted before their bodies arrive in the header graph
+    /// mode.
+    blocks_without_body: HashSet<H256>,
+    /// The blocks inserted before their bodies arrive in the header graph
+    /// mode whose bodies turn out to be invalid. An epoch containing such a
+    /// block is never executed. Its descendants are all invalid in the sync
+    /// graph, so no honest block is built on it and the pivot chain switches
+    /// away from it, after which the epochs from the fork are evaluated again.
+    blocks_with_invalid_body: HashSet<H256>,
+    /// The height of the first epoch on the pivot chain whose execution waits
+    /// for the bodies in `blocks_without_body`. The later epochs are not
+    /// enqueued for execution either.
+    execution_deferred_at: Option<u64>,
+
+    /// The fences held by the readers using arena indices without the lock.
+    pub epoch_fences: Arc<EpochFences>,
+    /// The arena slots of the removed blocks that cannot be reused until all
+    /// the epoch fences are released.
+    retired_arena_indices: HashSet<usize>,
+    /// The epoch sets of the pivot blocks below this height have been
+    /// considered for eviction.
+    epoch_set_eviction_height: u64,
+    /// The pivot chain switches that have not been recorded in
+    /// `PivotHistory`.
+    pub pivot_switches: Vec<PivotSwitch>,
+    optimistic_depth: OptimisticDepthController,
+    /// The clock of the consensus graph, which is also used by the executor.
+    pub clock: SharedClock,
 }
 
 impl MallocSizeOf for ConsensusGraphInner {
@@ -517,6 +779,9 @@ impl MallocSizeOf for ConsensusGraphInner {
             + self.pow_config.size_of(ops)
             + self.data_man.size_of(ops)
             + self.anticone_cache.size_of(ops)
+            + self.anticone_index.size_of(ops)
+            + self.blocks_without_body.size_of(ops)
+            + self.blocks_with_invalid_body.size_of(ops)
             + self.pastset_cache.size_of(ops)
             + self.best_terminals_lca_height_cache.size_of(ops)
             + self.best_terminals_reorg_height.size_of(ops)
@@ -565,7 +830,9 @@ impl ConsensusGraphInner {
     pub fn with_era_genesis(
         pow_config: ProofOfWorkConfig, pow: Arc<PowComputer>,
         data_man: Arc<BlockDataManager>, inner_conf: ConsensusInnerConfig,
-        cur_era_genesis_block_hash: &H256, cur_era_stable_block_hash: &H256,
+        deferred_state_epoch_count: DeferredStateEpochCount,
+        epoch_executed_block_bound: usize, cur_era_genesis_block_hash: &H256,
+        cur_era_stable_block_hash: &H256, clock: SharedClock,
     ) -> Self
     {
         let genesis_block_header = data_man
@@ -577,6 +844,10 @@ impl ConsensusGraphInner {
             .expect("stable genesis block header should exist here");
         let cur_era_stable_height = stable_block_header.height();
         let initial_difficulty = pow_config.initial_difficulty;
+        let optimistic_depth = OptimisticDepthController::new(
+            inner_conf.optimistic_execution_min_depth,
+            inner_conf.optimistic_execution_max_depth,
+        );
         let mut inner = ConsensusGraphInner {
             arena: Slab::new(),
             hash_to_arena_indices: FastHashMap::new(),
@@ -603,13 +874,26 @@ impl ConsensusGraphInner {
             current_difficulty: initial_difficulty.into(),
             data_man: data_man.clone(),
             inner_conf,
+            deferred_state_epoch_count,
+            epoch_executed_block_bound,
             anticone_cache: AnticoneCache::new(),
+            anticone_index: Default::default(),
+            traversal_pool: Default::default(),
             pastset_cache: Default::default(),
             sequence_number_of_block_entrance: 0,
             best_terminals_lca_height_cache: Default::default(),
             best_terminals_reorg_height: NULLU64,
             has_timer_block_in_anticone_cache: Default::default(),
             header_only: true,
+            blocks_without_body: Default::default(),
+            blocks_with_invalid_body: Default::default(),
+            execution_deferred_at: None,
+            epoch_fences: Default::default(),
+            retired_arena_indices: Default::default(),
+            epoch_set_eviction_height: 0,
+            pivot_switches: Vec::new(),
+            optimistic_depth,
+            clock,
         };
 
         // NOTE: Only genesis block will be first inserted into consensus graph
@@ -630,13 +914,14 @@ impl ConsensusGraphInner {
         }
         inner.cur_era_genesis_block_arena_index = genesis_arena_index;
         inner.arena[genesis_arena_index].data.activated = true;
-        let genesis_block_weight = genesis_block_header.difficulty().low_u128();
+        let genesis_block_weight =
+            difficulty_to_weight(genesis_block_header.difficulty());
         inner
             .weight_tree
             .make_tree(inner.cur_era_genesis_block_arena_index);
         inner.weight_tree.path_apply(
             inner.cur_era_genesis_block_arena_index,
-            genesis_block_weight as i128,
+            genesis_block_weight,
         );
         inner
             .adaptive_tree
@@ -646,9 +931,10 @@ impl ConsensusGraphInner {
         inner
             .adaptive_tree
             .set(inner.cur_era_genesis_block_arena_index, 0);
-        inner.arena[inner.cur_era_genesis_block_arena_index]
-            .data
-            .epoch_number = cur_era_genesis_height;
+        inner.set_epoch_number(
+            inner.cur_era_genesis_block_arena_index,
+            cur_era_genesis_height,
+        );
         let genesis_epoch_size = inner
             .data_man
             .executed_epoch_set_hashes_from_db(cur_era_genesis_height)
@@ -673,7 +959,7 @@ impl ConsensusGraphInner {
             .insert(inner.cur_era_genesis_block_arena_index);
         inner.pivot_chain_metadata.push(ConsensusGraphPivotData {
             last_pivot_in_past_blocks,
-            past_weight: genesis_block_weight as i128,
+            past_weight: genesis_block_weight,
         });
         if inner.arena[inner.cur_era_genesis_block_arena_index].is_timer {
             inner
@@ -697,7 +983,8 @@ impl ConsensusGraphInner {
         let height = self.pivot_index_to_height(pivot_index);
         let arena_index = self.pivot_chain[pivot_index];
         let epoch_set_hashes = self
-            .get_ordered_executable_epoch_blocks(arena_index)
+            .ordered_executable_epoch_blocks(arena_index)
+            .clone()
             .iter()
             .map(|arena_index| self.arena[*arena_index].hash)
             .collect();
@@ -721,9 +1008,18 @@ impl ConsensusGraphInner {
     /// The caller should ensure that `height` is within the current
     /// `self.pivot_chain` range. Otherwise the function may panic.
     pub fn get_pivot_block_arena_index(&self, height: u64) -> usize {
-        let pivot_index = (height - self.cur_era_genesis_height) as usize;
-        assert!(pivot_index < self.pivot_chain.len());
-        self.pivot_chain[pivot_index]
+        self.checked_pivot_block_arena_index(height)
+            .expect("height should be on the current pivot chain")
+    }
+
+    /// Return `None` if `height` is not within the current `self.pivot_chain`
+    /// range.
+    #[inline]
+    pub fn checked_pivot_block_arena_index(
+        &self, height: u64,
+    ) -> Option<usize> {
+        let pivot_index = self.checked_height_to_pivot_index(height)?;
+        self.pivot_chain.get(pivot_index).cloned()
     }
 
     #[inline]
@@ -731,9 +1027,14 @@ impl ConsensusGraphInner {
         self.cur_era_genesis_height + self.pivot_chain.len() as u64
     }
 
+    /// Return `None` if `height` is before the current era genesis or cannot
+    /// be an index of `pivot_chain`. Heights are `u64` while the indices are
+    /// `usize`, so the conversion is checked for 32-bit platforms.
     #[inline]
-    pub fn height_to_pivot_index(&self, height: u64) -> usize {
-        (height - self.cur_era_genesis_height) as usize
+    pub fn checked_height_to_pivot_index(&self, height: u64) -> Option<usize> {
+        height
+            .checked_sub(self.cur_era_genesis_height)
+            .and_then(|pivot_index| usize::try_from(pivot_index).ok())
     }
 
     #[inline]
@@ -763,7 +1064,9 @@ impl ConsensusGraphInner {
 
     #[inline]
     fn ancestor_at(&self, me: usize, height: u64) -> usize {
-        let height_index = self.height_to_pivot_index(height);
+        let height_index = self
+            .checked_height_to_pivot_index(height)
+            .expect("the ancestor height should be in the current era");
         self.weight_tree.ancestor_at(me, height_index)
     }
 
@@ -803,16 +1106,51 @@ impl ConsensusGraphInner {
         self.ancestor_at(parent, era_genesis_height)
     }
 
+    /// The evicted epoch set is reloaded into the memory.
     #[inline]
     pub fn get_epoch_block_hashes(
-        &self, epoch_arena_index: usize,
+        &mut self, epoch_arena_index: usize,
     ) -> Vec<H256> {
-        self.get_ordered_executable_epoch_blocks(epoch_arena_index)
+        let epoch_blocks = self
+            .ordered_executable_epoch_blocks(epoch_arena_index)
+            .clone();
+        epoch_blocks
             .iter()
             .map(|idx| self.arena[*idx].hash)
             .collect()
     }
 
+    /// Return `true` if the body of any block in the epoch has not arrived
+    /// or is invalid.
+    pub fn epoch_misses_body(
+        &self, epoch_arena_index: usize,
+    ) -> Result<bool, String> {
+        if self.blocks_without_body.is_empty()
+            && self.blocks_with_invalid_body.is_empty()
+        {
+            return Ok(false);
+        }
+        let misses_body = |idx: usize| {
+            let hash = &self.arena[idx].hash;
+            self.blocks_without_body.contains(hash)
+                || self.blocks_with_invalid_body.contains(hash)
+        };
+        Ok(self
+            .get_ordered_executable_epoch_blocks(epoch_arena_index)?
+            .iter()
+            .any(|idx| misses_body(*idx)))
+    }
+
+    /// The height of the first epoch whose execution waits for missing block
+    /// bodies in the header graph mode.
+    pub fn execution_deferred_at(&self) -> Option<u64> {
+        self.execution_deferred_at
+    }
+
+    pub fn block_body_missing(&self, hash: &H256) -> bool {
+        self.blocks_without_body.contains(hash)
+    }
+
     #[inline]
     fn get_epoch_start_block_number(&self, epoch_arena_index: usize) -> u64 {
         let parent = self.arena[epoch_arena_index].parent;
@@ -821,6 +1159,38 @@ impl ConsensusGraphInner {
     }
 
     #[inline]
+    /// Remove the block at arena `index` that is no longer in the consensus
+    /// graph. Its slot is retired instead if any epoch fence is held.
+    pub fn remove_arena_node(&mut self, index: usize) {
+        self.anticone_index.remove(index);
+        self.blocks_without_body.remove(&self.arena[index].hash);
+        self.blocks_with_invalid_body
+            .remove(&self.arena[index].hash);
+        if self.epoch_fences.is_fenced() {
+            self.retired_arena_indices.insert(index);
+        } else {
+            self.arena.remove(index);
+        }
+    }
+
+    /// Free the retired arena slots if no epoch fence is held.
+    pub fn reclaim_retired_arena_indices(&mut self) {
+        if self.retired_arena_indices.is_empty()
+            || self.epoch_fences.is_fenced()
+        {
+            return;
+        }
+        for index in self.retired_arena_indices.drain() {
+            self.arena.remove(index);
+        }
+    }
+
+    /// A retired node is kept in the arena only to keep its slot from being
+    /// reused, so it should be skipped when iterating over the arena.
+    pub fn is_arena_index_retired(&self, index: usize) -> bool {
+        self.retired_arena_indices.contains(&index)
+    }
+
     fn is_legacy_block(&self, index: usize) -> bool {
         self.arena[index].era_block == NULL
     }
@@ -842,7 +1212,6 @@ impl ConsensusGraphInner {
     fn compute_blockset_in_own_view_of_epoch_impl(
         &mut self, lca: usize, pivot: usize,
     ) {
-        let pastset = self.pastset_cache.get(lca).unwrap();
         let mut path_to_lca = Vec::new();
         let mut cur = pivot;
         while cur != lca {
@@ -850,6 +1219,10 @@ impl ConsensusGraphInner {
             cur = self.arena[cur].parent;
         }
         path_to_lca.reverse();
+        for ancestor_arena_index in &path_to_lca {
+            self.reload_evicted_epoch_sets(*ancestor_arena_index);
+        }
+        let pastset = self.pastset_cache.get(lca).unwrap();
         let mut visited = BitSet::new();
         for ancestor_arena_index in path_to_lca {
             visited.add(ancestor_arena_index as u32);
@@ -903,13 +1276,14 @@ impl ConsensusGraphInner {
     /// This function computes the epoch block set under the view of
     /// block *pivot*. It also computes the ordered set of executable
     /// blocks in the epoch. This set has a bound specified by
-    /// EPOCH_EXECUTED_BLOCK_BOUND. To compute this set, it first
+    /// `epoch_executed_block_bound`. To compute this set, it first
     /// filters out the blocks in the raw epoch set but not in the
     /// same era with *pivot*. It then topologically sorts the retained
-    /// blocks and preserves at most the last EPOCH_EXECUTED_BLOCK_BOUND
+    /// blocks and preserves at most the last `epoch_executed_block_bound`
     /// blocks. All the filtered-out blocks are added into
     /// *skipped_epoch_blocks*.
     fn compute_blockset_in_own_view_of_epoch(&mut self, pivot: usize) {
+        self.reload_evicted_epoch_sets(pivot);
         if !self.arena[pivot].data.blockset_cleared {
             return;
         }
@@ -943,10 +1317,10 @@ impl ConsensusGraphInner {
         ordered_executable_epoch_blocks.push(pivot);
         let skipped_epoch_block_indices = if ordered_executable_epoch_blocks
             .len()
-            > EPOCH_EXECUTED_BLOCK_BOUND
+            > self.epoch_executed_block_bound
         {
             let cut_off = ordered_executable_epoch_blocks.len()
-                - EPOCH_EXECUTED_BLOCK_BOUND;
+                - self.epoch_executed_block_bound;
             let mut skipped_epoch_block_indices =
                 ordered_executable_epoch_blocks;
             ordered_executable_epoch_blocks =
@@ -971,6 +1345,7 @@ impl ConsensusGraphInner {
     fn exchange_or_compute_blockset_in_own_view_of_epoch(
         &mut self, index: usize, blockset_opt: Option<Vec<usize>>,
     ) -> Vec<usize> {
+        self.reload_evicted_epoch_sets(index);
         if let Some(blockset) = blockset_opt {
             mem::replace(
                 &mut self.arena[index].data.blockset_in_own_view_of_epoch,
@@ -987,13 +1362,221 @@ impl ConsensusGraphInner {
         }
     }
 
+    /// The evicted set is read from the database without being reloaded
+    /// into the memory.
     #[inline]
     pub fn get_ordered_executable_epoch_blocks(
         &self, index: usize,
+    ) -> Result<Cow<'_, Vec<usize>>, String> {
+        let data = &self.arena[index].data;
+        if data.epoch_sets_evicted {
+            Ok(Cow::Owned(self.load_evicted_epoch_sets(index)?.1))
+        } else if data.blockset_cleared
+            && data.ordered_executable_epoch_blocks.is_empty()
+        {
+            Err(format!(
+                "The epoch sets of {:?} are not computed",
+                self.arena[index].hash
+            ))
+        } else {
+            Ok(Cow::Borrowed(&data.ordered_executable_epoch_blocks))
+        }
+    }
+
+    /// Like `get_ordered_executable_epoch_blocks`, but the evicted set is
+    /// reloaded into the memory, and computed again if it cannot be loaded.
+    pub fn ordered_executable_epoch_blocks(
+        &mut self, index: usize,
     ) -> &Vec<usize> {
+        self.reload_evicted_epoch_sets(index);
+        if self.arena[index].data.blockset_cleared
+            && self.arena[index]
+                .data
+                .ordered_executable_epoch_blocks
+                .is_empty()
+        {
+            self.compute_blockset_in_own_view_of_epoch(index);
+        }
         &self.arena[index].data.ordered_executable_epoch_blocks
     }
 
+    /// The evicted set is read from the database without being reloaded
+    /// into the memory.
+    #[inline]
+    pub fn get_blockset_in_own_view_of_epoch(
+        &self, index: usize,
+    ) -> Result<Cow<'_, Vec<usize>>, String> {
+        if self.arena[index].data.epoch_sets_evicted {
+            Ok(Cow::Owned(self.load_evicted_epoch_sets(index)?.0))
+        } else {
+            Ok(Cow::Borrowed(
+                &self.arena[index].data.blockset_in_own_view_of_epoch,
+            ))
+        }
+    }
+
+    /// Like `get_blockset_in_own_view_of_epoch`, but the evicted set is
+    /// reloaded into the memory.
+    pub fn blockset_in_own_view_of_epoch(
+        &mut self, index: usize,
+    ) -> &Vec<usize> {
+        self.reload_evicted_epoch_sets(index);
+        &self.arena[index].data.blockset_in_own_view_of_epoch
+    }
+
+    /// Only keep the blocks satisfying `f` in the epoch set of `index`. An
+    /// evicted set is not reloaded, because the blocks removed from the graph
+    /// are filtered out when it is loaded.
+    pub fn retain_blockset_in_own_view_of_epoch<F>(
+        &mut self, index: usize, f: F,
+    ) where F: FnMut(&usize) -> bool {
+        if !self.arena[index].data.epoch_sets_evicted {
+            self.arena[index]
+                .data
+                .blockset_in_own_view_of_epoch
+                .retain(f);
+        }
+    }
+
+    /// Clear the epoch set of `index` if it is too large to keep in memory.
+    /// It is computed again when it is needed. An evicted set is not in the
+    /// memory, so it is not cleared.
+    pub fn try_clear_blockset_in_own_view_of_epoch(&mut self, index: usize) {
+        let data = &mut self.arena[index].data;
+        if !data.epoch_sets_evicted
+            && data.blockset_in_own_view_of_epoch.len() as u64
+                > BLOCKSET_IN_OWN_VIEW_OF_EPOCH_CAP
+        {
+            data.blockset_in_own_view_of_epoch = Default::default();
+            data.skipped_epoch_blocks = Default::default();
+            data.blockset_cleared = true;
+        }
+    }
+
+    /// Evict the epoch sets of the pivot blocks at least
+    /// `epoch_set_eviction_depth` epochs below the best epoch. The sets of
+    /// old epochs are rarely used but take most of the memory of the graph.
+    /// If the sets of an epoch fail to be written, the eviction stops there
+    /// and continues from it in the next call.
+    pub fn evict_cold_epoch_sets(&mut self) -> Result<(), String> {
+        let depth = match self.inner_conf.epoch_set_eviction_depth {
+            Some(depth) => depth,
+            None => return Ok(()),
+        };
+        let end_height = match self.best_epoch_number().checked_sub(depth) {
+            Some(height) => height,
+            None => return Ok(()),
+        };
+        // The sets of the era genesis contain out-of-era blocks and are not
+        // used, so it is skipped.
+        let start_height = max(
+            self.epoch_set_eviction_height,
+            self.cur_era_genesis_height + 1,
+        );
+        for height in start_height..end_height {
+            let pivot_arena_index = self.get_pivot_block_arena_index(height);
+            if let Err(e) = self.evict_epoch_sets(pivot_arena_index) {
+                self.epoch_set_eviction_height = height;
+                return Err(e);
+            }
+        }
+        self.epoch_set_eviction_height = max(start_height, end_height);
+        Ok(())
+    }
+
+    /// The pivot blocks after `fork_height` are changed, so they need to be
+    /// considered again.
+    pub fn reset_epoch_set_eviction_height(&mut self, fork_height: u64) {
+        self.epoch_set_eviction_height =
+            min(self.epoch_set_eviction_height, fork_height);
+    }
+
+    /// The sets are only dropped from the memory after they are written.
+    fn evict_epoch_sets(&mut self, index: usize) -> Result<(), String> {
+        if self.arena[index].data.epoch_sets_evicted {
+            return Ok(());
+        }
+        let to_hashes = |indices: &Vec<usize>| -> Vec<H256> {
+            indices.iter().map(|i| self.arena[*i].hash).collect()
+        };
+        let epoch_sets = EvictedEpochSets {
+            blockset_in_own_view_of_epoch: to_hashes(
+                &self.arena[index].data.blockset_in_own_view_of_epoch,
+            ),
+            ordered_executable_epoch_blocks: to_hashes(
+                &self.arena[index].data.ordered_executable_epoch_blocks,
+            ),
+        };
+        self.data_man
+            .insert_evicted_epoch_sets(&self.arena[index].hash, &epoch_sets)?;
+        let data = &mut self.arena[index].data;
+        data.blockset_in_own_view_of_epoch = Vec::new();
+        data.ordered_executable_epoch_blocks = Vec::new();
+        data.epoch_sets_evicted = true;
+        Ok(())
+    }
+
+    /// Load the evicted epoch sets back into the memory. This should be
+    /// called before the sets are modified. If they cannot be loaded, they
+    /// are marked as cleared to be computed again.
+    fn reload_evicted_epoch_sets(&mut self, index: usize) {
+        if !self.arena[index].data.epoch_sets_evicted {
+            return;
+        }
+        let loaded = self.load_evicted_epoch_sets(index);
+        self.data_man
+            .remove_evicted_epoch_sets(&self.arena[index].hash);
+        // The sets are evicted again if the epoch is still cold.
+        self.reset_epoch_set_eviction_height(self.arena[index].height);
+        let data = &mut self.arena[index].data;
+        data.epoch_sets_evicted = false;
+        match loaded {
+            Ok((blockset, ordered_blocks)) => {
+                data.blockset_in_own_view_of_epoch = blockset;
+                data.ordered_executable_epoch_blocks = ordered_blocks;
+            }
+            Err(e) => {
+                warn!("{}, so they are computed again", e);
+                data.blockset_cleared = true;
+            }
+        }
+    }
+
+    /// The blocks removed from the graph after the eviction, i.e. the blocks
+    /// outside the current era, are filtered out.
+    fn load_evicted_epoch_sets(
+        &self, index: usize,
+    ) -> Result<(Vec<usize>, Vec<usize>), String> {
+        let epoch_sets = self
+            .data_man
+            .evicted_epoch_sets(&self.arena[index].hash)
+            .ok_or_else(|| {
+                format!(
+                    "The evicted epoch sets of {:?} are missing in db",
+                    self.arena[index].hash
+                )
+            })?;
+        let to_indices = |hashes: Vec<H256>| -> Vec<usize> {
+            hashes
+                .iter()
+                .filter_map(|hash| self.hash_to_arena_indices.get(hash))
+                .cloned()
+                .collect()
+        };
+        Ok((
+            to_indices(epoch_sets.blockset_in_own_view_of_epoch),
+            to_indices(epoch_sets.ordered_executable_epoch_blocks),
+        ))
+    }
+
+    /// Remove the evicted epoch sets of a block removed from the graph.
+    pub fn remove_evicted_epoch_sets(&self, index: usize) {
+        if self.arena[index].data.epoch_sets_evicted {
+            self.data_man
+                .remove_evicted_epoch_sets(&self.arena[index].hash);
+        }
+    }
+
     #[inline]
     pub fn get_or_compute_skipped_epoch_blocks(
         &mut self, index: usize,
@@ -1033,7 +1616,11 @@ impl ConsensusGraphInner {
     {
         // this is the earliest block we need to consider; blocks before `from`
         // cannot have any information about the state root of `pivot_index`
-        let from = pivot_index + DEFERRED_STATE_EPOCH_COUNT as usize;
+        let from = self.checked_height_to_pivot_index(
+            self.deferred_state_epoch_count.first_committing_height(
+                self.pivot_index_to_height(pivot_index),
+            ),
+        )?;
 
         self.find_first_trusted_starting_from(from, blame_bound, min_vote_count)
     }
@@ -1164,7 +1751,121 @@ impl ConsensusGraphInner {
         difficulty: U256,
     ) -> bool
     {
-        // We first compute anticone barrier for newly mined block
+        let (anticone, anticone_barrier, _) = self
+            .compute_anticone_of_new_block(
+                parent_arena_index,
+                &referee_indices,
+            );
+        let timer_chain_tuple = self.compute_timer_chain_tuple(
+            parent_arena_index,
+            &referee_indices,
+            Some(&anticone),
+        );
+
+        self.adaptive_weight_impl(
+            parent_arena_index,
+            &anticone_barrier,
+            None,
+            Some(&timer_chain_tuple),
+            difficulty_to_weight(&difficulty),
+        )
+    }
+
+    /// Like `check_mining_adaptive_block`, but only reads the graph, so it can
+    /// be called with the read lock held.
+    ///
+    /// The subtree weights are taken from `snapshot`, which is recomputed if
+    /// any block is inserted or the era changes after it is taken, so the
+    /// result is always the same as the one of `check_mining_adaptive_block`.
+    pub fn predict_mining_adaptive_block(
+        &self, snapshot: &mut Option<SubtreeWeightSnapshot>,
+        parent_arena_index: usize, referee_indices: Vec<usize>,
+        difficulty: U256,
+    ) -> bool
+    {
+        let era_genesis_hash =
+            self.arena[self.cur_era_genesis_block_arena_index].hash;
+        let is_fresh = snapshot.as_ref().map_or(false, |s| {
+            s.era_genesis_hash == era_genesis_hash
+                && s.sequence_number == self.sequence_number_of_block_entrance
+        });
+        if !is_fresh {
+            *snapshot = Some(SubtreeWeightSnapshot {
+                era_genesis_hash,
+                sequence_number: self.sequence_number_of_block_entrance,
+                weights: self.compute_subtree_weights(NULL, &BitSet::new()),
+            });
+        }
+        let subtree_weight =
+            &mut snapshot.as_mut().expect("snapshot is set").weights;
+        if subtree_weight.len() < self.arena.capacity() {
+            subtree_weight.resize(self.arena.capacity(), 0);
+        }
+
+        let (anticone, anticone_barrier, _) = self
+            .compute_anticone_of_new_block(
+                parent_arena_index,
+                &referee_indices,
+            );
+        let timer_chain_tuple = self.compute_timer_chain_tuple(
+            parent_arena_index,
+            &referee_indices,
+            Some(&anticone),
+        );
+        let force_confirm =
+            self.compute_force_confirm(Some(&timer_chain_tuple));
+        let force_confirm_height = self.arena[force_confirm].height;
+        if self.arena[parent_arena_index].height < force_confirm_height
+            || self.ancestor_at(parent_arena_index, force_confirm_height)
+                != force_confirm
+        {
+            return false;
+        }
+
+        // The subtree of an anticone barrier block is entirely in the
+        // anticone, so its weight is removed from the blocks on the pivot
+        // chain of the new block below the lca, and added back afterwards.
+        let mut delta_at_lca: HashMap<usize, i128> = HashMap::new();
+        for index in anticone_barrier.iter() {
+            let lca = self.lca(index as usize, parent_arena_index);
+            if lca != NULL {
+                *delta_at_lca.entry(lca).or_insert(0) +=
+                    subtree_weight[index as usize];
+            }
+        }
+        let mut original_weights = Vec::new();
+        let mut delta = 0;
+        let mut index = parent_arena_index;
+        loop {
+            delta += delta_at_lca.get(&index).cloned().unwrap_or(0);
+            original_weights.push((index, subtree_weight[index]));
+            subtree_weight[index] -= delta;
+            if index == force_confirm {
+                break;
+            }
+            index = self.arena[index].parent;
+        }
+
+        let adaptive = self.adaptive_weight_impl_brutal(
+            parent_arena_index,
+            subtree_weight,
+            Some(&timer_chain_tuple),
+            force_confirm,
+            difficulty_to_weight(&difficulty),
+        );
+        for (index, weight) in original_weights {
+            subtree_weight[index] = weight;
+        }
+        adaptive
+    }
+
+    /// Compute the anticone, the anticone barrier and the blocks in the epoch
+    /// of a block with `parent_arena_index` and `referee_indices` that is not
+    /// inserted, e.g. a block to mine.
+    fn compute_anticone_of_new_block(
+        &self, parent_arena_index: usize, referee_indices: &Vec<usize>,
+    ) -> (BitSet, BitSet, BitSet)
+    {
         let parent_anticone_opt = self.anticone_cache.get(parent_arena_index);
         let mut anticone;
         if parent_anticone_opt.is_none() {
@@ -1176,13 +1877,14 @@ impl ConsensusGraphInner {
             }
         } else {
             anticone = self.compute_future_bitset(parent_arena_index);
-            for index in parent_anticone_opt.unwrap() {
-                anticone.add(*index as u32);
+            for index in parent_anticone_opt.unwrap().iter() {
+                anticone.add(index as u32);
             }
         }
-        let mut my_past = BitSet::new();
-        let mut queue: VecDeque<usize> = VecDeque::new();
-        for index in &referee_indices {
+        let mut scratch = self.traversal_pool.take(self.arena.capacity());
+        let my_past = &mut scratch.visited;
+        let queue = &mut scratch.queue;
+        for index in referee_indices {
             queue.push_back(*index);
         }
         while let Some(index) = queue.pop_front() {
@@ -1206,9 +1908,13 @@ impl ConsensusGraphInner {
                 }
             }
         }
+        let mut epoch_set = BitSet::new();
         for index in my_past.drain() {
-            anticone.remove(index);
+            if anticone.remove(index) {
+                epoch_set.add(index);
+            }
         }
+        self.traversal_pool.give_back(scratch);
 
         let mut anticone_barrier = BitSet::new();
         for index in (&anticone).iter() {
@@ -1219,20 +1925,7 @@ impl ConsensusGraphInner {
                 anticone_barrier.add(index);
             }
         }
-
-        let timer_chain_tuple = self.compute_timer_chain_tuple(
-            parent_arena_index,
-            &referee_indices,
-            Some(&anticone),
-        );
-
-        self.adaptive_weight_impl(
-            parent_arena_index,
-            &anticone_barrier,
-            None,
-            Some(&timer_chain_tuple),
-            i128::try_from(difficulty.low_u128()).unwrap(),
-        )
+        (anticone, anticone_barrier, epoch_set)
     }
 
     /// This function computes the subtree weight for each node
@@ -1284,6 +1977,226 @@ impl ConsensusGraphInner {
         subtree_weight
     }
 
+    /// Recompute the subtree weights of the blocks in the current era from
+    /// scratch, and compare them with the values in `weight_tree` and
+    /// `adaptive_tree`. Return `None` if no divergence is found. `me` is the
+    /// last inserted block.
+    pub fn audit_weights(&self, me: usize) -> Option<WeightAuditDebugRecord> {
+        let era_genesis = self.cur_era_genesis_block_arena_index;
+        let mut subtree_weight =
+            self.compute_subtree_weights(NULL, &BitSet::new());
+        // The era genesis may carry weight that is not counted by
+        // `block_weight`, e.g. the difficulty of the true genesis, so its
+        // maintained value is taken as is.
+        subtree_weight[era_genesis] = self.weight_tree.get(era_genesis);
+        let divergence =
+            |index: usize, maintained, recomputed| WeightDivergence {
+                block_hash: self.arena[index].hash,
+                height: self.arena[index].height,
+                maintained,
+                recomputed,
+            };
+        let mut subtree_weight_divergences = Vec::new();
+        let mut adaptive_weight_divergences = Vec::new();
+        let mut stack = vec![era_genesis];
+        while let Some(index) = stack.pop() {
+            // The value of the era genesis in `adaptive_tree` depends on its
+            // parent, which is not in the current era, and its subtree weight
+            // is not recomputed.
+            if index != era_genesis {
+                let maintained = self.weight_tree.get(index);
+                if maintained != subtree_weight[index] {
+                    subtree_weight_divergences.push(divergence(
+                        index,
+                        maintained,
+                        subtree_weight[index],
+                    ));
+                }
+                let parent = self.arena[index].parent;
+                let recomputed = 2 * subtree_weight[index]
+                    - subtree_weight[parent]
+                    + self.block_weight(parent);
+                let maintained = self.adaptive_tree.get(index);
+                if maintained != recomputed {
+                    adaptive_weight_divergences
+                        .push(divergence(index, maintained, recomputed));
+                }
+            }
+            stack.extend(self.arena[index].children.iter());
+        }
+        if subtree_weight_divergences.is_empty()
+            && adaptive_weight_divergences.is_empty()
+        {
+            return None;
+        }
+        Some(WeightAuditDebugRecord {
+            block_hash: self.arena[me].hash,
+            sequence_number: self.arena[me].data.sequence_number,
+            era_genesis_hash: self.arena[era_genesis].hash,
+            subtree_weight_divergences,
+            adaptive_weight_divergences,
+        })
+    }
+
+    pub fn block_consensus_info(&self, me: usize) -> BlockConsensusInfo {
+        let node = &self.arena[me];
+        let epoch_number = if node.data.epoch_number == NULLU64 {
+            None
+        } else {
+            Some(node.data.epoch_number)
+        };
+        let past_era_weight = epoch_number
+            .and_then(|epoch| self.checked_height_to_pivot_index(epoch))
+            .filter(|pivot_index| {
+                self.pivot_chain.get(*pivot_index) == Some(&me)
+            })
+            .map(|pivot_index| {
+                self.pivot_chain_metadata[pivot_index].past_weight
+            });
+        let partial_invalid_cause = if node.data.partial_invalid {
+            self.data_man
+                .local_block_info_by_hash(&node.hash)
+                .and_then(|info| info.get_partial_invalid_cause())
+        } else {
+            None
+        };
+        BlockConsensusInfo {
+            hash: node.hash,
+            stable_id: node.data.sequence_number,
+            height: node.height,
+            parent_hash: if node.parent == NULL {
+                H256::zero()
+            } else {
+                self.arena[node.parent].hash
+            },
+            epoch_number,
+            last_pivot_in_past: node.data.last_pivot_in_past,
+            adaptive: node.adaptive,
+            is_heavy: node.is_heavy,
+            is_timer: node.is_timer,
+            activated: node.data.activated,
+            pending: node.data.pending,
+            partial_invalid: node.data.partial_invalid,
+            partial_invalid_cause,
+            stable: epoch_number
+                .map_or(false, |epoch| epoch <= self.cur_era_stable_height),
+            block_weight: self.block_weight(me),
+            subtree_weight: self.weight_tree.get(me),
+            past_era_weight,
+            anticone_size: self.anticone_cache.get(me).map(|a| a.len()),
+            state_valid: node.data.state_valid,
+            state_valid_source: node.data.state_valid_source,
+        }
+    }
+
+    /// Recompute the adaptive and parent decisions of the block `me` in its
+    /// past view by brute force, without changing `weight_tree` and
+    /// `adaptive_tree`. This is slow and only used for debugging.
+    pub fn ghast_decision(&self, me: usize) -> Option<GhastDecision> {
+        let parent = self.arena[me].parent;
+        if parent == NULL || self.arena[me].era_block == NULL {
+            return None;
+        }
+        let referees = self.arena[me].referees.clone();
+        // The anticone of a new block with the same parent and referees is
+        // everything not in the past view of `me`, including `me` itself.
+        let (anticone, anticone_barrier, epoch_set) =
+            self.compute_anticone_of_new_block(parent, &referees);
+        let timer_chain_tuple =
+            self.compute_timer_chain_tuple(parent, &referees, Some(&anticone));
+        let force_confirm =
+            self.compute_force_confirm(Some(&timer_chain_tuple));
+        let force_confirm_height = self.arena[force_confirm].height;
+        let subtree_weight =
+            self.compute_subtree_weights(me, &anticone_barrier);
+        let timer_chain_height =
+            self.get_best_timer_tick(Some(&timer_chain_tuple));
+        let adjusted_beta = scaled_weight_threshold(
+            difficulty_to_weight(&self.arena[me].difficulty),
+            self.inner_conf.adaptive_weight_beta,
+        );
+
+        let mut ancestors = Vec::new();
+        let mut adaptive = false;
+        if self.arena[parent].height >= force_confirm_height
+            && self.ancestor_at(parent, force_confirm_height) == force_confirm
+        {
+            let mut ancestor = parent;
+            while self.arena[ancestor].height != force_confirm_height {
+                let grandparent = self.arena[ancestor].parent;
+                let ancestor_timer_chain_height =
+                    self.get_timer_tick(ancestor, Some(&timer_chain_tuple));
+                let checked = timer_chain_height
+                    .saturating_sub(ancestor_timer_chain_height)
+                    >= self.inner_conf.timer_chain_beta;
+                let f = subtree_weight[ancestor];
+                let g = subtree_weight[grandparent]
+                    - self.block_weight(grandparent)
+                    - f;
+                if checked && f - g < adjusted_beta {
+                    adaptive = true;
+                }
+                ancestors.push(GhastAncestorDecision {
+                    hash: self.arena[ancestor].hash,
+                    height: self.arena[ancestor].height,
+                    timer_chain_height: ancestor_timer_chain_height,
+                    checked,
+                    subtree_weight: f,
+                    sibling_weight: g,
+                });
+                ancestor = grandparent;
+            }
+        }
+
+        // The epoch sets of the partially invalid or pending ancestors are
+        // also checked, as in `check_correct_parent`.
+        let mut candidates: Vec<usize> =
+            epoch_set.iter().map(|index| index as usize).collect();
+        let mut p = parent;
+        while p != NULL
+            && (self.arena[p].data.partial_invalid
+                || self.arena[p].data.pending)
+        {
+            candidates
+                .extend(self.get_blockset_in_own_view_of_epoch(p).ok()?.iter());
+            p = self.arena[p].parent;
+        }
+        let mut correct_parent = true;
+        for candidate in candidates {
+            let lca = self.lca(candidate, parent);
+            if lca == NULL || self.arena[lca].height < force_confirm_height {
+                continue;
+            }
+            if lca == parent {
+                correct_parent = false;
+                break;
+            }
+            let fork = self.ancestor_at(candidate, self.arena[lca].height + 1);
+            let pivot = self.ancestor_at(parent, self.arena[lca].height + 1);
+            if ConsensusGraphInner::is_heavier(
+                (subtree_weight[fork], &self.arena[fork].hash),
+                (subtree_weight[pivot], &self.arena[pivot].hash),
+            ) {
+                correct_parent = false;
+                break;
+            }
+        }
+
+        Some(GhastDecision {
+            hash: self.arena[me].hash,
+            parent_hash: self.arena[parent].hash,
+            force_confirm_hash: self.arena[force_confirm].hash,
+            timer_chain_height,
+            timer_chain_beta: self.inner_conf.timer_chain_beta,
+            adjusted_beta,
+            ancestors,
+            adaptive,
+            correct_parent,
+            recorded_adaptive: self.arena[me].adaptive,
+            recorded_partial_invalid: self.arena[me].data.partial_invalid,
+        })
+    }
+
     fn get_best_timer_tick(
         &self,
         timer_chain_tuple: Option<&(
@@ -1341,8 +2254,10 @@ impl ConsensusGraphInner {
         let force_confirm_height = self.arena[force_confirm].height;
         let timer_me = self.get_best_timer_tick(timer_chain_tuple);
 
-        let adjusted_beta =
-            (self.inner_conf.adaptive_weight_beta as i128) * difficulty;
+        let adjusted_beta = scaled_weight_threshold(
+            difficulty,
+            self.inner_conf.adaptive_weight_beta,
+        );
 
         let mut adaptive = false;
         while self.arena[parent].height != force_confirm_height {
@@ -1439,7 +2354,10 @@ impl ConsensusGraphInner {
             let a = self
                 .adaptive_tree
                 .path_aggregate_chop(parent, force_confirm);
-            let b = self.inner_conf.adaptive_weight_beta as i128 * difficulty;
+            let b = scaled_weight_threshold(
+                difficulty,
+                self.inner_conf.adaptive_weight_beta,
+            );
 
             if a < b {
                 debug!("block is adaptive: {:?} < {:?}!", a, b);
@@ -1476,8 +2394,7 @@ impl ConsensusGraphInner {
         let parent = self.arena[me].parent;
         assert!(parent != NULL);
 
-        let difficulty =
-            i128::try_from(self.arena[me].difficulty.low_u128()).unwrap();
+        let difficulty = difficulty_to_weight(&self.arena[me].difficulty);
 
         self.adaptive_weight_impl(
             parent,
@@ -1599,6 +2516,7 @@ impl ConsensusGraphInner {
             }
         }
 
+        self.reclaim_retired_arena_indices();
         // actually, we only need these fields: `parent`, `referees`,
         // `children`, `referrers`, `era_block`
         let index = self.arena.insert(ConsensusGraphNode {
@@ -1643,7 +2561,7 @@ impl ConsensusGraphInner {
 
     fn get_timer_difficulty(&self, me: usize) -> i128 {
         if self.arena[me].is_timer && !self.arena[me].data.partial_invalid {
-            i128::try_from(self.arena[me].difficulty.low_u128()).unwrap()
+            difficulty_to_weight(&self.arena[me].difficulty)
         } else {
             0
         }
@@ -1730,6 +2648,7 @@ impl ConsensusGraphInner {
 
         let my_height = block_header.height();
         let sn = self.get_next_sequence_number();
+        self.reclaim_retired_arena_indices();
         let index = self.arena.insert(ConsensusGraphNode {
             hash,
             height: my_height,
@@ -1752,6 +2671,7 @@ impl ConsensusGraphInner {
             ),
         });
         self.hash_to_arena_indices.insert(hash, index);
+        self.anticone_index.set_epoch_number(index, NULLU64);
 
         if parent != NULL {
             self.arena[parent].children.push(index);
@@ -1763,7 +2683,7 @@ impl ConsensusGraphInner {
 
         self.compute_blockset_in_own_view_of_epoch(index);
         let executed_epoch_len =
-            self.get_ordered_executable_epoch_blocks(index).len();
+            self.ordered_executable_epoch_blocks(index).len();
 
         if parent != NULL {
             let past_num_blocks =
@@ -1782,8 +2702,10 @@ impl ConsensusGraphInner {
         }
 
         debug!(
-            "Block {} inserted into Consensus with index={}",
-            hash, index
+            "Block {} inserted into Consensus with index={} stable_id={}",
+            hash,
+            index,
+            self.stable_id(index)
         );
 
         (index, self.hash_to_arena_indices.len())
@@ -1791,7 +2713,8 @@ impl ConsensusGraphInner {
 
     fn compute_future_bitset(&self, me: usize) -> BitSet {
         // Compute future set of parent
-        let mut queue: VecDeque<usize> = VecDeque::new();
+        let mut scratch = self.traversal_pool.take(0);
+        let queue = &mut scratch.queue;
         let mut visited = BitSet::with_capacity(self.arena.len() as u32);
         queue.push_back(me);
         visited.add(me as u32);
@@ -1819,6 +2742,7 @@ impl ConsensusGraphInner {
                 }
             }
         }
+        self.traversal_pool.give_back(scratch);
         visited.remove(me as u32);
         visited
     }
@@ -1844,21 +2768,24 @@ impl ConsensusGraphInner {
     ///   Option<(i([Bi]), i([Ba]))>
     ///
     /// The gap between [Bj] and [Bi], i.e., h([Bj])-h([Bi]),
-    /// is REWARD_EPOCH_COUNT.
+    /// is `reward_epoch_count`.
     /// Let D is the gap between the parent of the genesis of next era and [Bi].
     /// The gap between [Ba] and [Bi] is
-    ///     min(ANTICONE_PENALTY_UPPER_EPOCH_COUNT, D).
+    ///     min(`anticone_penalty_upper_epoch_count`, D).
+    /// Both counts are from the chain spec.
     pub fn get_pivot_reward_index(
-        &self, epoch_arena_index: usize,
-    ) -> Option<(usize, usize)> {
+        &self, epoch_arena_index: usize, reward_epoch_count: u64,
+        anticone_penalty_upper_epoch_count: u64,
+    ) -> Option<(usize, usize)>
+    {
         // We are going to exclude the original genesis block here!
-        if self.arena[epoch_arena_index].height <= REWARD_EPOCH_COUNT {
+        if self.arena[epoch_arena_index].height <= reward_epoch_count {
             return None;
         }
         let parent_index = self.arena[epoch_arena_index].parent;
         // Recompute epoch.
         let anticone_cut_height =
-            REWARD_EPOCH_COUNT - ANTICONE_PENALTY_UPPER_EPOCH_COUNT;
+            reward_epoch_count - anticone_penalty_upper_epoch_count;
         let mut anticone_penalty_cutoff_epoch_block = parent_index;
         for _i in 1..anticone_cut_height {
             if anticone_penalty_cutoff_epoch_block == NULL {
@@ -1868,7 +2795,7 @@ impl ConsensusGraphInner {
                 self.arena[anticone_penalty_cutoff_epoch_block].parent;
         }
         let mut reward_epoch_block = anticone_penalty_cutoff_epoch_block;
-        for _i in 0..ANTICONE_PENALTY_UPPER_EPOCH_COUNT {
+        for _i in 0..anticone_penalty_upper_epoch_count {
             if reward_epoch_block == NULL {
                 break;
             }
@@ -1893,10 +2820,14 @@ impl ConsensusGraphInner {
     }
 
     fn get_executable_epoch_blocks(
-        &self, epoch_arena_index: usize,
+        &mut self, epoch_arena_index: usize,
     ) -> Vec<Arc<Block>> {
         let mut epoch_blocks = Vec::new();
-        for idx in self.get_ordered_executable_epoch_blocks(epoch_arena_index) {
+        for idx in self
+            .ordered_executable_epoch_blocks(epoch_arena_index)
+            .clone()
+            .iter()
+        {
             let block = self
                 .data_man
                 .block_by_hash(
@@ -2006,11 +2937,7 @@ impl ConsensusGraphInner {
     /// state will be used.
     pub fn best_state_epoch_number(&self) -> u64 {
         let pivot_height = self.pivot_index_to_height(self.pivot_chain.len());
-        if pivot_height < DEFERRED_STATE_EPOCH_COUNT {
-            0
-        } else {
-            pivot_height - DEFERRED_STATE_EPOCH_COUNT
-        }
+        self.deferred_state_epoch_count.state_height(pivot_height)
     }
 
     fn best_state_arena_index(&self) -> usize {
@@ -2021,6 +2948,20 @@ impl ConsensusGraphInner {
         self.arena[self.best_state_arena_index()].hash
     }
 
+    /// Return the epoch whose state is committed by a new block with the
+    /// parent `parent_hash`.
+    pub fn get_deferred_state_block_for_child(
+        &self, parent_hash: &H256,
+    ) -> Result<&H256, String> {
+        let parent = *self
+            .hash_to_arena_indices
+            .get(parent_hash)
+            .ok_or("Parent hash is too old for computing the deferred state")?;
+        let child_height = self.arena[parent].height + 1;
+        let delay = self.deferred_state_epoch_count.at(child_height) - 1;
+        self.get_state_block_with_delay(parent_hash, delay as usize)
+    }
+
     pub fn get_state_block_with_delay(
         &self, block_hash: &H256, delay: usize,
     ) -> Result<&H256, String> {
@@ -2060,6 +3001,45 @@ impl ConsensusGraphInner {
         self.cur_era_genesis_height + self.pivot_chain.len() as u64 - 1
     }
 
+    pub fn pivot_chain_len(&self) -> usize { self.pivot_chain.len() }
+
+    pub fn cur_era_genesis_hash_and_height(&self) -> (H256, u64) {
+        (
+            self.arena[self.cur_era_genesis_block_arena_index].hash,
+            self.cur_era_genesis_height,
+        )
+    }
+
+    /// The total weight of the blocks in the current era.
+    pub fn cur_era_total_weight(&self) -> i128 {
+        self.weight_tree.get(self.cur_era_genesis_block_arena_index)
+    }
+
+    /// Remove the cached anticone sets of the given blocks, so they are
+    /// recomputed the next time they are needed. Blocks not in the consensus
+    /// graph are ignored.
+    pub fn drop_anticone_cache_entries(&mut self, hashes: &[H256]) -> usize {
+        let mut dropped = 0;
+        for hash in hashes {
+            if let Some(index) = self.hash_to_arena_indices.get(hash) {
+                if self.anticone_cache.get(*index).is_some() {
+                    self.anticone_cache.remove(*index);
+                    dropped += 1;
+                }
+            }
+        }
+        dropped
+    }
+
+    /// Change the bounds of the optimistic execution depth at runtime. The
+    /// recent pivot chain switches are kept, so the depth still adapts to
+    /// them within the new bounds.
+    pub fn set_optimistic_execution_depth(
+        &mut self, min_depth: u64, max_depth: u64,
+    ) {
+        self.optimistic_depth.set_bounds(min_depth, max_depth);
+    }
+
     pub fn best_timer_chain_height(&self) -> u64 {
         self.cur_era_genesis_timer_chain_height + self.timer_chain.len() as u64
             - 1
@@ -2069,13 +3049,14 @@ impl ConsensusGraphInner {
         &self, epoch_number: u64,
     ) -> Result<usize, String> {
         if epoch_number >= self.cur_era_genesis_height {
-            let pivot_index =
-                (epoch_number - self.cur_era_genesis_height) as usize;
-            if pivot_index >= self.pivot_chain.len() {
-                Err("Epoch number larger than the current pivot chain tip"
-                    .into())
-            } else {
-                Ok(self.get_pivot_block_arena_index(epoch_number))
+            match self.checked_height_to_pivot_index(epoch_number) {
+                Some(pivot_index) if pivot_index < self.pivot_chain.len() => {
+                    Ok(self.pivot_chain[pivot_index])
+                }
+                _ => {
+                    Err("Epoch number larger than the current pivot chain tip"
+                        .into())
+                }
             }
         } else {
             Err("Invalid params: epoch number is too old and not maintained by consensus graph".to_owned())
@@ -2090,13 +3071,19 @@ impl ConsensusGraphInner {
     ) -> Result<EpochId, String> {
         let height = epoch_number;
         if height >= self.cur_era_genesis_height {
-            let pivot_index = (height - self.cur_era_genesis_height) as usize;
-            if pivot_index >= self.pivot_chain.len() {
-                Err("Epoch number larger than the current pivot chain tip"
-                    .into())
-            } else {
-                Ok(self.arena[self.get_pivot_block_arena_index(height)].hash)
+            match self.checked_height_to_pivot_index(height) {
+                Some(pivot_index) if pivot_index < self.pivot_chain.len() => {
+                    Ok(self.arena[self.pivot_chain[pivot_index]].hash)
+                }
+                _ => {
+                    Err("Epoch number larger than the current pivot chain tip"
+                        .into())
+                }
             }
+        } else if let Some(hash) =
+            self.data_man.pivot_hash_by_epoch_number(epoch_number)
+        {
+            Ok(hash)
         } else {
             self.data_man.executed_epoch_set_hashes_from_db(epoch_number).ok_or(
                 format!("get_hash_from_epoch_number: Epoch hash set not in db, epoch_number={}", epoch_number).into()
@@ -2109,12 +3096,136 @@ impl ConsensusGraphInner {
     /// This function differs from `get_pivot_hash_from_epoch_number` in that it
     /// only returns the hash if it is in the current consensus graph.
     pub fn epoch_hash(&self, epoch_number: u64) -> Option<H256> {
-        let pivot_index = self.height_to_pivot_index(epoch_number);
+        let pivot_index = self.checked_height_to_pivot_index(epoch_number)?;
         self.pivot_chain
             .get(pivot_index)
             .map(|idx| self.arena[*idx].hash)
     }
 
+    /// Return the epoch number of `hash` if it is a pivot block, either in
+    /// the current consensus graph or in the checkpointed history.
+    pub fn pivot_epoch_number_by_hash(&self, hash: &H256) -> Option<u64> {
+        match self.hash_to_arena_indices.get(hash) {
+            Some(index) => {
+                let height = self.arena[*index].height;
+                if self.epoch_hash(height) == Some(*hash) {
+                    Some(height)
+                } else {
+                    None
+                }
+            }
+            None => self.data_man.epoch_number_by_pivot_hash(hash).filter(
+                |epoch_number| *epoch_number < self.cur_era_genesis_height,
+            ),
+        }
+    }
+
+    /// Return the annotations of the pivot blocks from `from_height` to
+    /// `to_height` (both inclusive), which must be in the current era. The
+    /// fields that are not maintained here, i.e. `epoch_gas_used` and
+    /// `confirmation_risk`, are left as `None`.
+    pub fn pivot_segment(
+        &self, from_height: u64, to_height: u64,
+    ) -> Result<Vec<PivotBlockAnnotation>, String> {
+        if from_height < self.cur_era_genesis_height {
+            return Err(format!(
+                "Epoch {} is before the current era genesis {}",
+                from_height, self.cur_era_genesis_height
+            ));
+        }
+        if to_height
+            >= self.cur_era_genesis_height + self.pivot_chain.len() as u64
+        {
+            return Err(
+                "Epoch number larger than the current pivot chain tip".into()
+            );
+        }
+        let mut segment = Vec::with_capacity(
+            (to_height.saturating_sub(from_height) + 1) as usize,
+        );
+        for height in from_height..=to_height {
+            let pivot_arena_index = self.get_pivot_block_arena_index(height);
+            let pivot_block = &self.arena[pivot_arena_index];
+            segment.push(PivotBlockAnnotation {
+                epoch_number: height,
+                hash: pivot_block.hash,
+                epoch_size: self.block_hashes_by_epoch(height)?.len(),
+                epoch_gas_used: None,
+                difficulty: pivot_block.difficulty,
+                adaptive: pivot_block.adaptive,
+                stable: height <= self.cur_era_stable_height,
+                confirmation_risk: None,
+            });
+        }
+        Ok(segment)
+    }
+
+    /// Answer the queries of the ancestor of a block at a height in order.
+    /// An answer is `None` if the block is not in the tree of the current
+    /// era, or the height is not between the era genesis and the block.
+    pub fn ancestors_at_by_hash(
+        &self, queries: &[(H256, u64)],
+    ) -> Vec<Option<H256>> {
+        let mut tree_queries = Vec::with_capacity(queries.len());
+        let mut query_positions = Vec::with_capacity(queries.len());
+        for (hash, height) in queries {
+            let position = match self.hash_to_arena_indices.get(hash) {
+                Some(index)
+                    if !self.is_legacy_block(*index)
+                        && *height <= self.arena[*index].height =>
+                {
+                    self.checked_height_to_pivot_index(*height).map(
+                        |pivot_index| {
+                            tree_queries.push((*index, pivot_index));
+                            tree_queries.len() - 1
+                        },
+                    )
+                }
+                _ => None,
+            };
+            query_positions.push(position);
+        }
+        let ancestors = self.weight_tree.ancestors_at(&tree_queries);
+        query_positions
+            .into_iter()
+            .map(|position| position.map(|i| self.arena[ancestors[i]].hash))
+            .collect()
+    }
+
+    /// Answer the queries of the lowest common ancestor of two blocks in
+    /// order. An answer is `None` if either block is not in the tree of the
+    /// current era.
+    pub fn lcas_by_hash(&self, pairs: &[(H256, H256)]) -> Vec<Option<H256>> {
+        let mut tree_pairs = Vec::with_capacity(pairs.len());
+        let mut pair_positions = Vec::with_capacity(pairs.len());
+        for (a, b) in pairs {
+            let position = match (
+                self.hash_to_arena_indices.get(a),
+                self.hash_to_arena_indices.get(b),
+            ) {
+                (Some(a), Some(b))
+                    if !self.is_legacy_block(*a)
+                        && !self.is_legacy_block(*b) =>
+                {
+                    tree_pairs.push((*a, *b));
+                    Some(tree_pairs.len() - 1)
+                }
+                _ => None,
+            };
+            pair_positions.push(position);
+        }
+        let lcas = self.weight_tree.lcas(&tree_pairs);
+        pair_positions
+            .into_iter()
+            .map(|position| {
+                position
+                    .map(|i| lcas[i])
+                    .filter(|lca| *lca != NULL)
+                    .map(|lca| self.arena[lca].hash)
+            })
+            .collect()
+    }
+
     pub fn block_hashes_by_epoch(
         &self, epoch_number: u64,
     ) -> Result<Vec<H256>, String> {
@@ -2123,34 +3234,66 @@ impl ConsensusGraphInner {
             epoch_number,
             self.pivot_chain.len()
         );
+        self.block_hashes_by_epoch_page(epoch_number, 0, usize::MAX)
+    }
 
-        let e;
+    /// Return at most `limit` blocks of the epoch from the `offset`-th block
+    /// in the execution order, so that a giant epoch does not need to be
+    /// returned at once.
+    pub fn block_hashes_by_epoch_page(
+        &self, epoch_number: u64, offset: usize, limit: usize,
+    ) -> Result<Vec<H256>, String> {
+        match self.maintained_epoch_blocks(epoch_number) {
+            Ok(epoch_blocks) => Ok(epoch_blocks
+                .iter()
+                .skip(offset)
+                .take(limit)
+                .map(|index| self.arena[*index].hash)
+                .collect()),
+            Err(e) => Ok(self
+                .epoch_set_hashes_from_db(epoch_number, e)?
+                .into_iter()
+                .skip(offset)
+                .take(limit)
+                .collect()),
+        }
+    }
+
+    /// Return the number of blocks in the epoch.
+    pub fn epoch_size(&self, epoch_number: u64) -> Result<usize, String> {
+        match self.maintained_epoch_blocks(epoch_number) {
+            Ok(epoch_blocks) => Ok(epoch_blocks.len()),
+            Err(e) => Ok(self.epoch_set_hashes_from_db(epoch_number, e)?.len()),
+        }
+    }
+
+    /// Return the blocks of the epoch if it is maintained in the graph, or
+    /// why it is not.
+    fn maintained_epoch_blocks(
+        &self, epoch_number: u64,
+    ) -> Result<Cow<'_, Vec<usize>>, String> {
         // We first try to get it from the consensus. Note that we cannot use
         // the info for the genesis because it may contain out-of-era
         // blocks that is not maintained anymore.
-        match self.get_arena_index_from_epoch_number(epoch_number) {
-            Ok(pivot_arena_index) => {
-                if pivot_arena_index != self.cur_era_genesis_block_arena_index {
-                    return Ok(self
-                        .get_ordered_executable_epoch_blocks(pivot_arena_index)
-                        .iter()
-                        .map(|index| self.arena[*index].hash)
-                        .collect());
-                }
-                e = "Epoch set of the current genesis is not maintained".into();
-            }
-            Err(err) => e = err,
+        let pivot_arena_index =
+            self.get_arena_index_from_epoch_number(epoch_number)?;
+        if pivot_arena_index == self.cur_era_genesis_block_arena_index {
+            return Err(
+                "Epoch set of the current genesis is not maintained".into()
+            );
         }
+        self.get_ordered_executable_epoch_blocks(pivot_arena_index)
+    }
 
+    fn epoch_set_hashes_from_db(
+        &self, epoch_number: u64, in_mem_err: String,
+    ) -> Result<Vec<H256>, String> {
         self.data_man
             .executed_epoch_set_hashes_from_db(epoch_number)
-            .ok_or(
-                format!(
-                    "Epoch set not in db epoch_number={}, in mem err={:?}",
-                    epoch_number, e
-                )
-                .into(),
-            )
+            .ok_or(format!(
+                "Epoch set not in db epoch_number={}, in mem err={:?}",
+                epoch_number, in_mem_err
+            ))
     }
 
     pub fn skipped_block_hashes_by_epoch(
@@ -2210,6 +3353,39 @@ impl ConsensusGraphInner {
         }
     }
 
+    /// Return the terminals selected by `filter`, ordered by their subtree
+    /// weight (the heaviest first) and then by hash. The result is computed
+    /// from a single view of the graph, so it is consistent as long as the
+    /// caller holds the lock.
+    pub fn filtered_terminal_hashes(
+        &self, filter: &TerminalFilter,
+    ) -> Vec<H256> {
+        let best_era_block =
+            self.arena[*self.pivot_chain.last().unwrap()].era_block;
+        let mut terminals: Vec<(i128, H256)> = self
+            .terminal_hashes
+            .iter()
+            .filter_map(|hash| {
+                let index = *self.hash_to_arena_indices.get(hash)?;
+                let node = &self.arena[index];
+                if filter.exclude_partial_invalid && node.data.partial_invalid {
+                    return None;
+                }
+                if filter.current_era_only && node.era_block != best_era_block {
+                    return None;
+                }
+                Some((self.weight_tree.get(index), *hash))
+            })
+            .collect();
+        terminals.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+        terminals
+            .into_iter()
+            .skip(filter.offset)
+            .take(filter.limit.unwrap_or(usize::MAX))
+            .map(|(_, hash)| hash)
+            .collect()
+    }
+
     pub fn get_block_epoch_number(&self, hash: &H256) -> Option<u64> {
         self.hash_to_arena_indices.get(hash).and_then(|index| {
             match self.arena[*index].data.epoch_number {
@@ -2344,6 +3520,8 @@ impl ConsensusGraphInner {
                     } else {
                         Some(tx_exec_error_msg.clone())
                     },
+                    tx_exec_error_info: block_receipts
+                        .tx_execution_error_info(tx_index.index),
                 }
             });
 
@@ -2376,14 +3554,15 @@ impl ConsensusGraphInner {
         let is_adaptive = self.arena[me].adaptive;
         if is_adaptive {
             if is_heavy {
-                self.inner_conf.heavy_block_difficulty_ratio as i128
-                    * i128::try_from(self.arena[me].difficulty.low_u128())
-                        .unwrap()
+                scaled_difficulty_to_weight(
+                    &self.arena[me].difficulty,
+                    self.inner_conf.heavy_block_difficulty_ratio,
+                )
             } else {
                 0 as i128
             }
         } else {
-            i128::try_from(self.arena[me].difficulty.low_u128()).unwrap()
+            difficulty_to_weight(&self.arena[me].difficulty)
         }
     }
 
@@ -2659,7 +3838,19 @@ impl ConsensusGraphInner {
                 == state_blame_info.receipts_vec_root
             && *block_header.deferred_logs_bloom_hash()
                 == state_blame_info.logs_bloom_vec_root;
-
+        let mut state_valid_source = StateValidSource::Commitment;
+
+        // A mismatch only in the receipts root may be caused by stale local
+        // receipts instead of the block. The receipts of the deferred epochs
+        // in `stale_receipts_epochs` are recomputed before the state of the
+        // block is computed, so the block is found invalid here only if the
+        // recomputed receipts still mismatch.
+        if self.arena[deferred_state_arena_index]
+            .data
+            .receipts_recomputed
+        {
+            state_valid_source = StateValidSource::RecomputedReceipts;
+        }
         let mut debug_recompute = false;
         if state_valid {
             debug!(
@@ -2765,7 +3956,12 @@ impl ConsensusGraphInner {
             }
         }
 
+        debug!(
+            "compute_state_valid_for_block(): Block {:?} state_valid={} decided by {:?}",
+            block_hash, state_valid, state_valid_source
+        );
         self.arena[me].data.state_valid = Some(state_valid);
+        self.arena[me].data.state_valid_source = Some(state_valid_source);
         if !state_valid {
             self.arena[me].data.blame_info = Some(state_blame_info);
         }
@@ -2792,6 +3988,99 @@ impl ConsensusGraphInner {
         Ok(())
     }
 
+    /// Return the deferred epochs of a new block whose receipts should be
+    /// recomputed before the state of the block is checked, which is the case
+    /// if the deferred roots in `header` only mismatch the local receipts
+    /// roots. The parent of the block must be in the graph, and the epochs
+    /// which are not executed yet or have been recomputed are not returned.
+    ///
+    /// It only reads the graph, so the receipts can be recomputed without
+    /// holding the write lock.
+    pub fn stale_receipts_epochs(
+        &self, header: &BlockHeader,
+    ) -> Vec<ReceiptsRecomputeTask> {
+        let parent = match self.hash_to_arena_indices.get(header.parent_hash())
+        {
+            Some(parent) => *parent,
+            None => return Vec::new(),
+        };
+        let mut epochs = Vec::new();
+        let mut state_roots = Vec::new();
+        let mut receipts_roots = Vec::new();
+        let mut logs_bloom_hashes = Vec::new();
+        for i in 0..=header.blame() as u64 {
+            if i >= header.height() {
+                return Vec::new();
+            }
+            let epoch_height = self
+                .deferred_state_epoch_count
+                .state_height(header.height() - i);
+            if epoch_height <= self.cur_era_genesis_height {
+                return Vec::new();
+            }
+            let epoch = self.ancestor_at(parent, epoch_height);
+            let commitment = match self
+                .data_man
+                .get_epoch_execution_commitment(&self.arena[epoch].hash)
+            {
+                Some(commitment) => commitment.clone(),
+                None => return Vec::new(),
+            };
+            state_roots.push(
+                commitment.state_root_with_aux_info.aux_info.state_root_hash,
+            );
+            receipts_roots.push(commitment.receipts_root);
+            logs_bloom_hashes.push(commitment.logs_bloom_hash);
+            epochs.push(epoch);
+        }
+        let only_receipts_mismatch = *header.deferred_state_root()
+            == BlockHeaderBuilder::compute_blame_state_root_vec_root(
+                state_roots,
+            )
+            && *header.deferred_logs_bloom_hash()
+                == BlockHeaderBuilder::compute_blame_state_root_vec_root(
+                    logs_bloom_hashes,
+                )
+            && *header.deferred_receipts_root()
+                != BlockHeaderBuilder::compute_blame_state_root_vec_root(
+                    receipts_roots,
+                );
+        if !only_receipts_mismatch {
+            return Vec::new();
+        }
+        epochs
+            .into_iter()
+            .filter(|epoch| !self.arena[*epoch].data.receipts_recomputed)
+            .filter_map(|epoch| {
+                let epoch_blocks =
+                    match self.get_ordered_executable_epoch_blocks(epoch) {
+                        Ok(epoch_blocks) => epoch_blocks,
+                        Err(e) => {
+                            warn!("Skip recomputing the receipts: {}", e);
+                            return None;
+                        }
+                    };
+                Some(ReceiptsRecomputeTask {
+                    epoch_hash: self.arena[epoch].hash,
+                    epoch_block_hashes: epoch_blocks
+                        .iter()
+                        .map(|idx| self.arena[*idx].hash)
+                        .collect(),
+                    start_block_number: self
+                        .get_epoch_start_block_number(epoch),
+                })
+            })
+            .collect()
+    }
+
+    /// Record that the receipts of the epoch `epoch_hash` have been
+    /// recomputed, so they are not recomputed again.
+    pub fn set_receipts_recomputed(&mut self, epoch_hash: &H256) {
+        if let Some(epoch) = self.hash_to_arena_indices.get(epoch_hash) {
+            self.arena[*epoch].data.receipts_recomputed = true;
+        }
+    }
+
     fn compute_vote_valid_for_pivot_block(
         &mut self, me: usize, pivot_arena_index: usize,
     ) -> bool {
@@ -2886,7 +4175,8 @@ impl ConsensusGraphInner {
                     continue;
                 }
             }
-            total_weight += self.block_weight(*index);
+            total_weight =
+                add_cumulative_weight(total_weight, self.block_weight(*index));
         }
         total_weight
     }
@@ -2901,7 +4191,9 @@ impl ConsensusGraphInner {
         for i in start_at..pivot_height {
             let me = self.get_pivot_block_arena_index(i);
             self.arena[me].data.last_pivot_in_past = i;
-            let i_pivot_index = self.height_to_pivot_index(i);
+            let i_pivot_index = self
+                .checked_height_to_pivot_index(i)
+                .expect("the pivot height should be in the current era");
             self.pivot_chain_metadata[i_pivot_index]
                 .last_pivot_in_past_blocks
                 .clear();
@@ -2922,21 +4214,23 @@ impl ConsensusGraphInner {
                         me,
                         Some(blockset),
                     );
-                    self.pivot_chain_metadata[i_pivot_index - 1].past_weight
-                        + blockset_weight
-                        + self.block_weight(me)
+                    sum_cumulative_weights(&[
+                        self.pivot_chain_metadata[i_pivot_index - 1]
+                            .past_weight,
+                        blockset_weight,
+                        self.block_weight(me),
+                    ])
                 } else {
                     self.block_weight(me)
                 };
             to_update.remove(&me);
         }
-        let mut stack = Vec::new();
+        let mut scratch = self.traversal_pool.take(0);
         let to_visit = to_update.clone();
         for i in &to_update {
-            stack.push((0, *i));
+            scratch.stack.push((0, *i));
         }
-        while !stack.is_empty() {
-            let (stage, me) = stack.pop().unwrap();
+        while let Some((stage, me)) = scratch.stack.pop() {
             if !to_visit.contains(&me) {
                 continue;
             }
@@ -2944,10 +4238,10 @@ impl ConsensusGraphInner {
             if stage == 0 {
                 if to_update.contains(&me) {
                     to_update.remove(&me);
-                    stack.push((1, me));
-                    stack.push((0, parent));
+                    scratch.stack.push((1, me));
+                    scratch.stack.push((0, parent));
                     for referee in &self.arena[me].referees {
-                        stack.push((0, *referee));
+                        scratch.stack.push((0, *referee));
                     }
                 }
             } else if stage == 1 && me != self.cur_era_genesis_block_arena_index
@@ -2962,12 +4256,15 @@ impl ConsensusGraphInner {
                     last_pivot = max(last_pivot, x);
                 }
                 self.arena[me].data.last_pivot_in_past = last_pivot;
-                let last_pivot_index = self.height_to_pivot_index(last_pivot);
+                let last_pivot_index = self
+                    .checked_height_to_pivot_index(last_pivot)
+                    .expect("the last pivot should be in the current era");
                 self.pivot_chain_metadata[last_pivot_index]
                     .last_pivot_in_past_blocks
                     .insert(me);
             }
         }
+        self.traversal_pool.give_back(scratch);
     }
 
     fn get_timer_chain_index(&self, me: usize) -> usize {
@@ -3091,7 +4388,6 @@ impl ConsensusGraphInner {
                     self.predecessor_edges(i as usize)
                         .into_iter()
                         .map(|i| i as u32)
-                        .collect()
                 },
                 |_| true,
             );
@@ -3363,18 +4659,23 @@ impl ConsensusGraphInner {
             return None;
         }
         let arena_index = *arena_index_opt.unwrap();
-        let pivot_index =
-            self.height_to_pivot_index(self.arena[arena_index].height);
-        // the given checkpoint hash is invalid
-        if pivot_index >= self.pivot_chain.len()
-            || self.pivot_chain[pivot_index] != arena_index
+        let pivot_index = match self
+            .checked_height_to_pivot_index(self.arena[arena_index].height)
         {
-            debug!(
-                "get_trusted_blame_block: block {:?} not on pivot chain",
-                checkpoint_hash
-            );
-            return None;
-        }
+            Some(pivot_index)
+                if self.pivot_chain.get(pivot_index) == Some(&arena_index) =>
+            {
+                pivot_index
+            }
+            // the given checkpoint hash is invalid
+            _ => {
+                debug!(
+                    "get_trusted_blame_block: block {:?} not on pivot chain",
+                    checkpoint_hash
+                );
+                return None;
+            }
+        };
         self.find_first_index_with_correct_state_of(
             pivot_index + plus_depth,
             None, /* blame_bound */
@@ -3397,9 +4698,8 @@ impl ConsensusGraphInner {
     pub fn get_to_sync_epoch_id(&self) -> EpochId {
         let height_to_sync = self.latest_snapshot_height();
         // The height_to_sync is within the range of `self.pivit_chain`.
-        let epoch_to_sync = self.arena
-            [self.pivot_chain[self.height_to_pivot_index(height_to_sync)]]
-        .hash;
+        let epoch_to_sync =
+            self.arena[self.get_pivot_block_arena_index(height_to_sync)].hash;
         epoch_to_sync
     }
 
@@ -3466,6 +4766,17 @@ impl ConsensusGraphInner {
         }
         blocks_to_compute.reverse();
 
+        // The receipts of the deferred epochs may not be executed when the
+        // blocks are inserted, so they are checked again here.
+        for index in &blocks_to_compute {
+            if let Some(header) =
+                self.data_man.block_header_by_hash(&self.arena[*index].hash)
+            {
+                executor.enqueue_stale_receipts_recompute(&header, self);
+            }
+        }
+        executor.wait_for_receipts_recomputes(self);
+
         for index in blocks_to_compute {
             self.compute_state_valid_and_blame_info_for_block(index, executor)?;
         }
@@ -3493,40 +4804,42 @@ impl ConsensusGraphInner {
             None,
         );
         for idx in &block_set {
-            self.arena[*idx].data.epoch_number = epoch_number
+            self.set_epoch_number(*idx, epoch_number);
         }
         self.exchange_or_compute_blockset_in_own_view_of_epoch(
             pivot_arena_index,
             Some(block_set),
         );
-        self.arena[pivot_arena_index].data.epoch_number = epoch_number;
+        self.set_epoch_number(pivot_arena_index, epoch_number);
+    }
+
+    fn set_epoch_number(&mut self, index: usize, epoch_number: u64) {
+        self.arena[index].data.epoch_number = epoch_number;
+        self.anticone_index.set_epoch_number(index, epoch_number);
     }
 
     fn get_deferred_state_arena_index(
         &self, me: usize,
     ) -> Result<usize, String> {
         let height = self.arena[me].height;
+        let deferred_state_epoch_count =
+            self.deferred_state_epoch_count.at(height);
         // We are in the very early of the blockchain, here we can just
         // return cur_era_genesis_block_arena_index and it will be the true
         // genesis.
-        if height <= DEFERRED_STATE_EPOCH_COUNT {
+        if height <= deferred_state_epoch_count {
             return Ok(self.cur_era_genesis_block_arena_index);
         }
         // This is the case we cannot handle, the block is no longer maintained.
-        if self.cur_era_genesis_height + DEFERRED_STATE_EPOCH_COUNT > height {
+        if self.cur_era_genesis_height + deferred_state_epoch_count > height {
             return Err(
                 "Parent is too old for computing the deferred state".to_owned()
             );
         }
-        let target_height = height - DEFERRED_STATE_EPOCH_COUNT;
-        let pivot_idx = self.height_to_pivot_index(height);
+        let target_height = height - deferred_state_epoch_count;
         // If it is on the pivot chain already, we can avoid O(log n) lca query
-        if pivot_idx < self.pivot_chain.len()
-            && self.pivot_chain[pivot_idx] == me
-        {
-            return Ok(
-                self.pivot_chain[self.height_to_pivot_index(target_height)]
-            );
+        if self.checked_pivot_block_arena_index(height) == Some(me) {
+            return Ok(self.get_pivot_block_arena_index(target_height));
         } else {
             return Ok(self.ancestor_at(me, target_height));
         }
@@ -3538,14 +4851,16 @@ impl ConsensusGraphInner {
     pub fn recover_state_valid(&mut self) {
         // FIXME: Same here. Be explicit about whether a checkpoint or a synced
         // FIXME: snapshot is requested, and distinguish two cases.
-        let start_pivot_index =
-            (self.data_man.state_availability_boundary.read().lower_bound
-                - self.cur_era_genesis_height) as usize;
-        if start_pivot_index >= self.pivot_chain.len() {
+        let start_pivot_index = match self.checked_height_to_pivot_index(
+            self.data_man.state_availability_boundary.read().lower_bound,
+        ) {
+            Some(pivot_index) if pivot_index < self.pivot_chain.len() => {
+                pivot_index
+            }
             // TODO: Handle this after refactoring
             // `state_availability_boundary`.
-            return;
-        }
+            _ => return,
+        };
         let start_epoch_hash =
             self.arena[self.pivot_chain[start_pivot_index]].hash;
         // We will get the first
@@ -3570,6 +4885,8 @@ impl ConsensusGraphInner {
                     .blame();
                 for i in 0..blame + 1 {
                     self.arena[cur].data.state_valid = Some(i == 0);
+                    self.arena[cur].data.state_valid_source =
+                        Some(StateValidSource::RecoveredBlame);
                     trace!(
                         "recover_state_valid: index={} hash={} state_valid={}",
                         cur,
@@ -3593,6 +4910,47 @@ impl ConsensusGraphInner {
             .and_then(|arena_index| self.arena.get(*arena_index))
     }
 
+    /// The stable ID of the block at `index`, which is used to identify the
+    /// block in logs because arena indices are reused.
+    #[inline]
+    pub fn stable_id(&self, index: usize) -> u64 {
+        self.arena[index].data.sequence_number
+    }
+
+    pub fn block_ids_by_arena_index(
+        &self, index: usize,
+    ) -> Option<ConsensusBlockIds> {
+        if self.is_arena_index_retired(index) {
+            return None;
+        }
+        self.arena.get(index).map(|node| ConsensusBlockIds {
+            stable_id: node.data.sequence_number,
+            hash: node.hash,
+            arena_index: index,
+        })
+    }
+
+    pub fn block_ids_by_hash(
+        &self, block_hash: &H256,
+    ) -> Option<ConsensusBlockIds> {
+        self.hash_to_arena_indices
+            .get(block_hash)
+            .and_then(|index| self.block_ids_by_arena_index(*index))
+    }
+
+    /// This scans the arena, so it should only be used for debugging.
+    pub fn block_ids_by_stable_id(
+        &self, stable_id: u64,
+    ) -> Option<ConsensusBlockIds> {
+        self.arena
+            .iter()
+            .find(|(index, node)| {
+                node.data.sequence_number == stable_id
+                    && !self.is_arena_index_retired(*index)
+            })
+            .and_then(|(index, _)| self.block_ids_by_arena_index(index))
+    }
+
     /// Return the list of best terminals when respecting a bound (for
     /// referencing edges). We sort the terminals based on its lca so that
     /// it will not change the parent selection results if we exclude last
@@ -3757,6 +5115,64 @@ impl ConsensusGraphInner {
         Ok(chain)
     }
 
+    /// Return the total weight of the subtree of `hash`, or `None` if the
+    /// block is not in consensus. This only reads the weight tree.
+    pub fn get_block_total_weight(&self, hash: &H256) -> Option<i128> {
+        let index = *self.hash_to_arena_indices.get(hash)?;
+        Some(self.weight_tree.get(index))
+    }
+
+    pub fn era_descriptor(&self) -> EraDescriptor {
+        let era_genesis = &self.arena[self.cur_era_genesis_block_arena_index];
+        EraDescriptor::new(
+            era_genesis.hash,
+            self.cur_era_genesis_height,
+            self.cur_era_genesis_timer_chain_height,
+            &era_genesis.difficulty,
+            &self.inner_conf,
+        )
+    }
+
+    /// The era genesis of a peer before ours should be on our pivot chain,
+    /// and the one after ours should be in the subtree of our era genesis if
+    /// we already have it. An era genesis we do not know yet is accepted.
+    pub fn validate_era_descriptor(
+        &self, remote: &EraDescriptor,
+    ) -> Result<(), String> {
+        let height = remote.era_genesis_height;
+        if height == self.cur_era_genesis_height {
+            self.era_descriptor().check_same_height(remote)
+        } else if height < self.cur_era_genesis_height {
+            let pivot_hash = match self.get_pivot_hash_from_epoch_number(height)
+            {
+                Ok(pivot_hash) => pivot_hash,
+                Err(_) => return Ok(()),
+            };
+            if pivot_hash != remote.era_genesis_hash {
+                return Err(format!(
+                    "era genesis {:?} at height {} is not on our pivot chain",
+                    remote.era_genesis_hash, height
+                ));
+            }
+            Ok(())
+        } else {
+            match self.hash_to_arena_indices.get(&remote.era_genesis_hash) {
+                Some(index)
+                    if self.arena[*index].era_block == NULL
+                        || self.arena[*index].height != height =>
+                {
+                    Err(format!(
+                        "era genesis {:?} at height {} is not after our era genesis {:?}",
+                        remote.era_genesis_hash,
+                        height,
+                        self.arena[self.cur_era_genesis_block_arena_index].hash
+                    ))
+                }
+                _ => Ok(()),
+            }
+        }
+    }
+
     /// Return `None` if `root_block` is not in consensus.
     pub fn get_subtree(&self, root_block: &H256) -> Option<Vec<H256>> {
         let root_arena_index = *self.hash_to_arena_indices.get(root_block)?;
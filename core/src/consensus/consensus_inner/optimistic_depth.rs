@@ -0,0 +1,107 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use std::{
+    cmp::{max, min},
+    collections::VecDeque,
+    time::Duration,
+};
+
+/// The pivot chain switches within this period make optimistic execution
+/// back off.
+const OPTIMISTIC_DEPTH_REORG_WINDOW: Duration = Duration::from_secs(60);
+
+/// `OptimisticDepthController` decides how many epochs optimistic execution
+/// may run ahead of the deferred execution boundary.
+///
+/// The optimistically executed epochs are wasted if the pivot chain switches
+/// below them, and they delay the queued tasks, so the depth is halved for
+/// each pivot chain switch in the recent window and for each queued task.
+/// When the chain is calm and the executor is idle, the depth grows back to
+/// `max_depth`.
+pub struct OptimisticDepthController {
+    min_depth: u64,
+    max_depth: u64,
+    /// The times of the clock when the recent switches happen.
+    recent_switches: VecDeque<Duration>,
+}
+
+impl OptimisticDepthController {
+    pub fn new(min_depth: u64, max_depth: u64) -> Self {
+        Self {
+            min_depth: min(min_depth, max_depth),
+            max_depth,
+            recent_switches: VecDeque::new(),
+        }
+    }
+
+    /// Change the bounds of the depth. The recent switches are kept.
+    pub fn set_bounds(&mut self, min_depth: u64, max_depth: u64) {
+        self.min_depth = min(min_depth, max_depth);
+        self.max_depth = max_depth;
+    }
+
+    /// Called when the pivot chain switches to another branch. Extending the
+    /// pivot chain is not a switch.
+    pub fn on_pivot_switch(&mut self, now: Duration) {
+        self.recent_switches.push_back(now);
+    }
+
+    /// Return the current depth, given the number of tasks waiting in the
+    /// execution queue.
+    pub fn depth(&mut self, now: Duration, backlog: usize) -> u64 {
+        while self.recent_switches.front().map_or(false, |time| {
+            now.checked_sub(*time).unwrap_or_default()
+                >= OPTIMISTIC_DEPTH_REORG_WINDOW
+        }) {
+            self.recent_switches.pop_front();
+        }
+        let shift = self.recent_switches.len() + backlog;
+        let depth = if shift >= 64 {
+            0
+        } else {
+            self.max_depth >> shift
+        };
+        max(depth, self.min_depth)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OptimisticDepthController, OPTIMISTIC_DEPTH_REORG_WINDOW};
+    use std::time::Duration;
+
+    #[test]
+    fn test_backoff_and_recovery() {
+        let mut controller = OptimisticDepthController::new(1, 8);
+        let start = Duration::from_secs(1_000);
+        assert_eq!(controller.depth(start, 0), 8);
+        assert_eq!(controller.depth(start, 1), 4);
+
+        controller.on_pivot_switch(start);
+        controller.on_pivot_switch(start + Duration::from_secs(1));
+        assert_eq!(controller.depth(start + Duration::from_secs(1), 0), 2);
+        assert_eq!(controller.depth(start + Duration::from_secs(1), 2), 1);
+        assert_eq!(controller.depth(start + Duration::from_secs(1), 100), 1);
+
+        // The first switch has left the window.
+        assert_eq!(
+            controller.depth(start + OPTIMISTIC_DEPTH_REORG_WINDOW, 0),
+            4
+        );
+        assert_eq!(
+            controller.depth(
+                start + OPTIMISTIC_DEPTH_REORG_WINDOW + Duration::from_secs(1),
+                0
+            ),
+            8
+        );
+    }
+
+    #[test]
+    fn test_min_depth_bounded_by_max_depth() {
+        let mut controller = OptimisticDepthController::new(3, 2);
+        assert_eq!(controller.depth(Duration::from_secs(1_000), 100), 2);
+    }
+}
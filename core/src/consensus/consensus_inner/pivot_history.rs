@@ -0,0 +1,186 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::{
+    block_data_manager::{BlockDataManager, PivotSwitchEvent},
+    clock::SharedClock,
+};
+use cfx_types::H256;
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+/// A pivot chain switch found when a block is activated, waiting to be
+/// recorded by `PivotHistory`.
+#[derive(Clone, Debug)]
+pub struct PivotSwitch {
+    pub fork_height: u64,
+    pub old_tip: H256,
+    pub new_tip: H256,
+    pub trigger_block: H256,
+}
+
+/// A change of the pivot chain sent to the subscribers of
+/// `ConsensusGraph::subscribe_pivot_updates`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PivotUpdate {
+    /// The pivot chain is extended by the block `hash` at `height`.
+    NewPivot { height: u64, hash: H256 },
+    /// The pivot blocks from `from_height` are reverted and replaced by
+    /// `to_hashes`, which is the new pivot chain from `from_height`. No
+    /// `NewPivot` is sent for the blocks in `to_hashes`.
+    Revert {
+        from_height: u64,
+        to_hashes: Vec<H256>,
+    },
+}
+
+/// `PivotHistory` is an append-only log of the pivot chain switches in the
+/// database for auditing, e.g. when investigating a double-spend attempt.
+///
+/// The events are never removed. The sequence numbers are kept across
+/// restarts, so an event can be referred to by its `seq`.
+pub struct PivotHistory {
+    data_man: Arc<BlockDataManager>,
+    clock: SharedClock,
+    next_seq: Mutex<u64>,
+}
+
+impl PivotHistory {
+    pub fn new(data_man: Arc<BlockDataManager>, clock: SharedClock) -> Self {
+        let mut next_seq = data_man.pivot_switch_next_seq().unwrap_or(0);
+        // The node may stop after an event is written but before `next_seq`
+        // is updated.
+        while data_man.pivot_switch_event(next_seq).is_some() {
+            next_seq += 1;
+        }
+        Self {
+            data_man,
+            clock,
+            next_seq: Mutex::new(next_seq),
+        }
+    }
+
+    pub fn record(&self, switches: Vec<PivotSwitch>) {
+        if switches.is_empty() {
+            return;
+        }
+        let timestamp = self.clock.now_as_secs();
+        let mut next_seq = self.next_seq.lock();
+        for switch in switches {
+            let event = PivotSwitchEvent {
+                seq: *next_seq,
+                fork_height: switch.fork_height,
+                old_tip: switch.old_tip,
+                new_tip: switch.new_tip,
+                trigger_block: switch.trigger_block,
+                timestamp,
+            };
+            info!("Pivot chain switched: {:?}", event);
+            self.data_man.insert_pivot_switch_event(&event);
+            *next_seq += 1;
+        }
+        self.data_man.insert_pivot_switch_next_seq(*next_seq);
+    }
+
+    /// Return at most `limit` events from `from_seq` in order.
+    pub fn events(&self, from_seq: u64, limit: usize) -> Vec<PivotSwitchEvent> {
+        let next_seq = *self.next_seq.lock();
+        (from_seq..next_seq)
+            .filter_map(|seq| self.data_man.pivot_switch_event(seq))
+            .take(limit)
+            .collect()
+    }
+
+    pub fn next_seq(&self) -> u64 { *self.next_seq.lock() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PivotHistory, PivotSwitch};
+    use crate::{
+        block_data_manager::{DbType, PivotSwitchEvent},
+        clock::ManualClock,
+        pow::PowComputer,
+        sync::utils::initialize_data_manager,
+        vm_factory::VmFactory,
+    };
+    use cfx_types::H256;
+    use std::{fs, sync::Arc, time::Duration};
+
+    fn switch(i: u64) -> PivotSwitch {
+        PivotSwitch {
+            fork_height: i,
+            old_tip: H256::from_low_u64_be(i),
+            new_tip: H256::from_low_u64_be(i + 1),
+            trigger_block: H256::from_low_u64_be(i + 2),
+        }
+    }
+
+    fn seqs(events: &[PivotSwitchEvent]) -> Vec<u64> {
+        events.iter().map(|event| event.seq).collect()
+    }
+
+    #[test]
+    fn test_pivot_history_recovery_and_paging() {
+        let db_dir = "./test_pivot_history.db/";
+        {
+            let (data_man, _) = initialize_data_manager(
+                db_dir,
+                DbType::Rocksdb,
+                Arc::new(PowComputer::new(true)),
+                VmFactory::new(1024 * 32),
+            );
+            let clock = Arc::new(ManualClock::new(Duration::from_secs(100)));
+            let history = PivotHistory::new(data_man.clone(), clock.clone());
+            assert_eq!(history.next_seq(), 0);
+            history.record(vec![]);
+            history.record((0..3).map(switch).collect());
+            history.record((3..5).map(switch).collect());
+            assert_eq!(history.next_seq(), 5);
+            assert_eq!(data_man.pivot_switch_next_seq(), Some(5));
+
+            // The node stops after the events 5 and 6 are written, but
+            // before `next_seq` is updated.
+            for seq in 5..7 {
+                data_man.insert_pivot_switch_event(&PivotSwitchEvent {
+                    seq,
+                    fork_height: seq,
+                    old_tip: H256::from_low_u64_be(seq),
+                    new_tip: H256::from_low_u64_be(seq + 1),
+                    trigger_block: H256::from_low_u64_be(seq + 2),
+                    timestamp: 100,
+                });
+            }
+            let history = PivotHistory::new(data_man.clone(), clock.clone());
+            assert_eq!(history.next_seq(), 7);
+            clock.advance(Duration::from_secs(5));
+            history.record(vec![switch(7)]);
+            assert_eq!(history.next_seq(), 8);
+            assert_eq!(data_man.pivot_switch_next_seq(), Some(8));
+
+            // The pages of 3 events cover all the events in order, and the
+            // last page ends at `next_seq`.
+            let mut pages = Vec::new();
+            let mut from_seq = 0;
+            loop {
+                let page = history.events(from_seq, 3);
+                if page.is_empty() {
+                    break;
+                }
+                from_seq = page.last().unwrap().seq + 1;
+                pages.push(seqs(&page));
+            }
+            assert_eq!(pages, vec![vec![0, 1, 2], vec![3, 4, 5], vec![6, 7]]);
+            assert_eq!(seqs(&history.events(5, 100)), vec![5, 6, 7]);
+            assert!(history.events(8, 3).is_empty());
+            assert!(history.events(100, 3).is_empty());
+
+            let events = history.events(6, 2);
+            assert_eq!(events[0].timestamp, 100);
+            assert_eq!(events[1].timestamp, 105);
+            assert_eq!(events[1].trigger_block, H256::from_low_u64_be(9));
+        }
+        fs::remove_dir_all(db_dir).ok();
+    }
+}
@@ -0,0 +1,160 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+/// A model estimating the confirmation risk of a pivot block, i.e. the
+/// probability that it is no longer on the pivot chain in future.
+///
+/// The inputs are measured in blocks of the current difficulty. `m` is the
+/// number of blocks generated since the pivot block, and `n` is the lead of
+/// the subtree of the pivot block over its heaviest sibling subtree, after
+/// the blocks that may be delayed by the propagation are given to the
+/// sibling.
+pub trait RiskModel: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn risk(&self, m: u64, n: u64) -> f64;
+}
+
+/// The risk model used by `ConfirmationMeter`, selected in `ConsensusConfig`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RiskModelConfig {
+    Heuristic,
+    Analytic {
+        /// The fraction of the total hash power controlled by the attacker,
+        /// in `(0, 0.5)`.
+        attacker_hash_power_fraction: f64,
+    },
+}
+
+impl RiskModelConfig {
+    pub fn build(&self) -> Box<dyn RiskModel> {
+        match *self {
+            RiskModelConfig::Heuristic => Box::new(HeuristicRiskModel),
+            RiskModelConfig::Analytic {
+                attacker_hash_power_fraction,
+            } => Box::new(AnalyticRiskModel::new(attacker_hash_power_fraction)),
+        }
+    }
+}
+
+/// The risk levels of the heuristic model from the highest.
+const HEURISTIC_RISKS: [f64; 4] = [0.9, 0.0001, 0.000001, 0.00000001];
+
+/// The piecewise model fitted from simulations, which the confirmation meter
+/// has always used. The risk drops by levels as `m - n` falls below the
+/// thresholds linear in `m`.
+pub struct HeuristicRiskModel;
+
+impl RiskModel for HeuristicRiskModel {
+    fn name(&self) -> &'static str { "heuristic" }
+
+    fn risk(&self, m: u64, n: u64) -> f64 {
+        let m = m as f64;
+        let m_n_diff = m - n as f64;
+        let threshold_1 = if 0.75 * m - 22.0 < 2250.0 {
+            0.75 * m - 22.0
+        } else {
+            2250.0
+        };
+        if m_n_diff >= threshold_1 {
+            return HEURISTIC_RISKS[0];
+        }
+        let threshold_2 = if 0.70 * m - 22.0 < 1500.0 {
+            0.70 * m - 22.0
+        } else {
+            1500.0
+        };
+        if m_n_diff >= threshold_2 {
+            return HEURISTIC_RISKS[1];
+        }
+        let threshold_3 = if 0.65 * m - 22.0 < 750.0 {
+            0.65 * m
+        } else {
+            750.0
+        };
+        if m_n_diff >= threshold_3 {
+            return HEURISTIC_RISKS[2];
+        }
+        HEURISTIC_RISKS[3]
+    }
+}
+
+/// The risk of a private chain attack by an attacker with a fraction `q` of
+/// the hash power, derived in the same way as in the Bitcoin paper.
+///
+/// While the honest nodes generate the `m` blocks, the attacker generates a
+/// Poisson number of blocks with mean `m * q / p`, where `p = 1 - q`. With
+/// `k` such blocks, the attacker still needs to close a gap of `n - k`
+/// blocks, which succeeds with probability `(q / p)^(n - k)` by the
+/// gambler's ruin.
+pub struct AnalyticRiskModel {
+    attacker_hash_power_fraction: f64,
+}
+
+impl AnalyticRiskModel {
+    pub fn new(attacker_hash_power_fraction: f64) -> Self {
+        AnalyticRiskModel {
+            attacker_hash_power_fraction,
+        }
+    }
+}
+
+impl RiskModel for AnalyticRiskModel {
+    fn name(&self) -> &'static str { "analytic" }
+
+    fn risk(&self, m: u64, n: u64) -> f64 {
+        let q = self.attacker_hash_power_fraction;
+        if q <= 0.0 {
+            return 0.0;
+        }
+        let p = 1.0 - q;
+        if q >= p || n == 0 {
+            return 1.0;
+        }
+        let ratio = q / p;
+        let lambda = m as f64 * ratio;
+        // The probability that the attacker fails, summed over the number of
+        // its blocks less than `n`. The Poisson terms are computed in the
+        // log space to avoid the underflow of `exp(-lambda)` for a large `m`.
+        let mut log_poisson = -lambda;
+        let mut fail = 0.0;
+        for k in 0..n {
+            if k > 0 {
+                log_poisson += lambda.ln() - (k as f64).ln();
+            }
+            fail += log_poisson.exp() * (1.0 - ratio.powf((n - k) as f64));
+        }
+        (1.0 - fail).max(0.0).min(1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AnalyticRiskModel, HeuristicRiskModel, RiskModel};
+
+    #[test]
+    fn test_heuristic_risk_levels() {
+        let model = HeuristicRiskModel;
+        assert_eq!(model.risk(100, 1), 0.9);
+        assert_eq!(model.risk(100, 50), 0.0001);
+        assert_eq!(model.risk(100, 100), 0.00000001);
+    }
+
+    #[test]
+    fn test_analytic_risk() {
+        // The numbers in the Bitcoin paper, where `m = n = z`.
+        let model = AnalyticRiskModel::new(0.1);
+        assert!((model.risk(5, 5) - 0.0009137).abs() < 1e-6);
+        let model = AnalyticRiskModel::new(0.3);
+        assert!((model.risk(10, 10) - 0.0416605).abs() < 1e-6);
+
+        // The risk drops with a larger lead, and an attacker with the
+        // majority always succeeds.
+        assert!(model.risk(100, 100) < model.risk(100, 50));
+        assert_eq!(AnalyticRiskModel::new(0.5).risk(100, 100), 1.0);
+        assert_eq!(AnalyticRiskModel::new(0.0).risk(100, 1), 0.0);
+        // No underflow with a large `m`.
+        assert!(AnalyticRiskModel::new(0.2).risk(2000, 1900) < 1e-8);
+    }
+}
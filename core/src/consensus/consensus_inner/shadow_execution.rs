@@ -0,0 +1,207 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfx_internal_common::EpochExecutionCommitment;
+use cfx_types::H256;
+use metrics::{Counter, CounterUsize};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::{collections::VecDeque, fs, path::Path, sync::Arc};
+
+/// The maximum number of optimistic commitments waiting for the check. The
+/// epochs dropped from the pivot chain are never checked, so the oldest ones
+/// are discarded.
+const SHADOW_EXECUTION_MAX_PENDING_EPOCHS: usize = 1000;
+
+lazy_static! {
+    static ref SHADOW_EXECUTION_MISMATCH_COUNTER: Arc<dyn Counter<usize>> =
+        CounterUsize::register_with_group(
+            "system_metrics",
+            "shadow_execution_mismatch_count"
+        );
+}
+
+/// The commitments of an epoch computed by the optimistic execution and by
+/// the execution after the pivot chain reaches it.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShadowExecutionMismatch {
+    pub epoch_hash: H256,
+    pub optimistic_state_root: H256,
+    pub deferred_state_root: H256,
+    pub optimistic_receipts_root: H256,
+    pub deferred_receipts_root: H256,
+    pub optimistic_logs_bloom_hash: H256,
+    pub deferred_logs_bloom_hash: H256,
+}
+
+/// `ShadowExecution` checks the results of the optimistic execution.
+///
+/// The commitment of an optimistically executed epoch is kept until the
+/// execution task of the epoch is enqueued again when the pivot chain reaches
+/// it. That task would normally be skipped, but in the shadow mode the epoch
+/// is executed again and the two commitments are compared. A mismatch means a
+/// bug in the execution pipeline, so it is logged as an error and dumped to
+/// `dump_dir`.
+pub struct ShadowExecution {
+    dump_dir: String,
+    optimistic_commitments: Mutex<VecDeque<(H256, EpochExecutionCommitment)>>,
+}
+
+impl ShadowExecution {
+    pub fn new(dump_dir: String) -> Self {
+        Self {
+            dump_dir,
+            optimistic_commitments: Default::default(),
+        }
+    }
+
+    /// Keep the commitment of an optimistically executed epoch.
+    pub fn record_optimistic(
+        &self, epoch_hash: H256, commitment: EpochExecutionCommitment,
+    ) {
+        let mut commitments = self.optimistic_commitments.lock();
+        commitments.retain(|(hash, _)| *hash != epoch_hash);
+        if commitments.len() == SHADOW_EXECUTION_MAX_PENDING_EPOCHS {
+            commitments.pop_front();
+        }
+        commitments.push_back((epoch_hash, commitment));
+    }
+
+    /// Return the kept optimistic commitment of `epoch_hash`. The epoch
+    /// should be executed again if it is returned.
+    pub fn take_optimistic(
+        &self, epoch_hash: &H256,
+    ) -> Option<EpochExecutionCommitment> {
+        let mut commitments = self.optimistic_commitments.lock();
+        let position = commitments
+            .iter()
+            .position(|(hash, _)| hash == epoch_hash)?;
+        commitments
+            .remove(position)
+            .map(|(_, commitment)| commitment)
+    }
+
+    /// Compare the optimistic commitment with the one from the execution
+    /// again.
+    pub fn check(
+        &self, epoch_hash: H256, optimistic: &EpochExecutionCommitment,
+        deferred: &EpochExecutionCommitment,
+    )
+    {
+        let optimistic_state_root =
+            optimistic.state_root_with_aux_info.aux_info.state_root_hash;
+        let deferred_state_root =
+            deferred.state_root_with_aux_info.aux_info.state_root_hash;
+        if optimistic_state_root == deferred_state_root
+            && optimistic.receipts_root == deferred.receipts_root
+            && optimistic.logs_bloom_hash == deferred.logs_bloom_hash
+        {
+            debug!("Shadow execution check passed for epoch {:?}", epoch_hash);
+            return;
+        }
+        let mismatch = ShadowExecutionMismatch {
+            epoch_hash,
+            optimistic_state_root,
+            deferred_state_root,
+            optimistic_receipts_root: optimistic.receipts_root,
+            deferred_receipts_root: deferred.receipts_root,
+            optimistic_logs_bloom_hash: optimistic.logs_bloom_hash,
+            deferred_logs_bloom_hash: deferred.logs_bloom_hash,
+        };
+        SHADOW_EXECUTION_MISMATCH_COUNTER.inc(1);
+        error!("Optimistic execution result mismatch: {:?}", mismatch);
+        self.dump_mismatch(&mismatch);
+    }
+
+    fn dump_mismatch(&self, mismatch: &ShadowExecutionMismatch) {
+        let path = Path::new(&self.dump_dir)
+            .join(format!("{:?}.json.txt", mismatch.epoch_hash));
+        let result = serde_json::to_string(mismatch)
+            .map_err(|e| e.to_string())
+            .and_then(|content| {
+                fs::create_dir_all(&self.dump_dir)
+                    .and_then(|_| fs::write(&path, content))
+                    .map_err(|e| e.to_string())
+            });
+        if let Err(e) = result {
+            warn!("Shadow execution: failed to write {:?}: {}", path, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ShadowExecution, SHADOW_EXECUTION_MAX_PENDING_EPOCHS};
+    use cfx_internal_common::{EpochExecutionCommitment, StateRootWithAuxInfo};
+    use cfx_types::{H256, KECCAK_EMPTY_BLOOM};
+    use std::fs;
+    use tempdir::TempDir;
+
+    fn commitment(
+        state_root: u64, receipts_root: u64,
+    ) -> EpochExecutionCommitment {
+        EpochExecutionCommitment {
+            state_root_with_aux_info: StateRootWithAuxInfo::genesis(
+                &H256::from_low_u64_be(state_root),
+            ),
+            receipts_root: H256::from_low_u64_be(receipts_root),
+            logs_bloom_hash: KECCAK_EMPTY_BLOOM,
+        }
+    }
+
+    #[test]
+    fn test_take_optimistic() {
+        let shadow = ShadowExecution::new(String::new());
+        let epoch_hash = H256::from_low_u64_be(1);
+        shadow.record_optimistic(epoch_hash, commitment(1, 1));
+        // The commitment of an epoch executed again replaces the old one.
+        shadow.record_optimistic(epoch_hash, commitment(2, 2));
+        assert_eq!(
+            shadow.take_optimistic(&epoch_hash).map(|c| c.receipts_root),
+            Some(H256::from_low_u64_be(2))
+        );
+        assert!(shadow.take_optimistic(&epoch_hash).is_none());
+
+        // The oldest commitments are discarded.
+        for i in 0..SHADOW_EXECUTION_MAX_PENDING_EPOCHS as u64 + 1 {
+            shadow
+                .record_optimistic(H256::from_low_u64_be(i), commitment(i, i));
+        }
+        assert!(shadow.take_optimistic(&H256::from_low_u64_be(0)).is_none());
+        assert!(shadow.take_optimistic(&H256::from_low_u64_be(1)).is_some());
+    }
+
+    #[test]
+    fn test_mismatch_is_dumped() {
+        let dir = TempDir::new("shadow_execution").unwrap();
+        let dump_dir = dir.path().join("dump");
+        let shadow =
+            ShadowExecution::new(dump_dir.to_str().unwrap().to_string());
+
+        let epoch_hash = H256::from_low_u64_be(1);
+        shadow.check(epoch_hash, &commitment(1, 1), &commitment(1, 1));
+        assert!(!dump_dir.exists());
+
+        shadow.check(epoch_hash, &commitment(1, 1), &commitment(1, 2));
+        let content = fs::read_to_string(
+            dump_dir.join(format!("{:?}.json.txt", epoch_hash)),
+        )
+        .unwrap();
+        let mismatch: serde_json::Value =
+            serde_json::from_str(&content).unwrap();
+        assert_eq!(
+            mismatch["optimisticReceiptsRoot"],
+            format!("{:?}", H256::from_low_u64_be(1))
+        );
+        assert_eq!(
+            mismatch["deferredReceiptsRoot"],
+            format!("{:?}", H256::from_low_u64_be(2))
+        );
+        assert_eq!(
+            mismatch["optimisticStateRoot"],
+            mismatch["deferredStateRoot"]
+        );
+    }
+}
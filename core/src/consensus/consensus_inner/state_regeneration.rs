@@ -0,0 +1,313 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use super::{consensus_executor::ConsensusExecutor, ConsensusGraphInner};
+use crate::statistics::lock_contention::InstrumentedRwLock;
+use cfx_types::H256;
+use parking_lot::Mutex;
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// The limits of the work done by one `StateRegenerationService::regenerate`
+/// call. The call returns after either limit is reached, and the next call
+/// for the same block continues from there.
+#[derive(Clone, Copy, Debug)]
+pub struct StateRegenerationBudget {
+    pub max_epochs: usize,
+    /// An epoch being replayed is not interrupted, so a call may exceed this
+    /// by the execution time of one epoch.
+    pub max_wall_time: Duration,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StateRegenerationProgress {
+    pub block_hash: H256,
+    /// The number of epochs to replay in total, which is 0 if the state is
+    /// already available.
+    pub total_epochs: usize,
+    pub replayed_epochs: usize,
+}
+
+impl StateRegenerationProgress {
+    pub fn is_done(&self) -> bool { self.replayed_epochs == self.total_epochs }
+}
+
+struct RegenerationSession {
+    /// The epochs to replay in order, planned by the first call.
+    epochs: Vec<H256>,
+    replayed_epochs: usize,
+}
+
+/// Regenerates the states of old epochs in steps bounded by a budget, so a
+/// request for a missing state never replays an unbounded number of epochs
+/// at once.
+pub struct StateRegenerationService {
+    executor: Arc<ConsensusExecutor>,
+    sessions: RegenerationSessions,
+}
+
+impl StateRegenerationService {
+    pub fn new(executor: Arc<ConsensusExecutor>) -> Self {
+        Self {
+            executor,
+            sessions: Default::default(),
+        }
+    }
+
+    /// Replay the epochs needed for the state of `block_hash` within
+    /// `budget`, continuing the previous session of the block if any. The
+    /// session is dropped if an epoch fails to be replayed.
+    pub fn regenerate(
+        &self, block_hash: &H256,
+        inner: &InstrumentedRwLock<ConsensusGraphInner>,
+        budget: StateRegenerationBudget,
+    ) -> Result<StateRegenerationProgress, String>
+    {
+        self.sessions.regenerate(
+            block_hash,
+            budget,
+            || {
+                Ok(self
+                    .executor
+                    .plan_state_computation(block_hash, &*inner.read())?
+                    .unwrap_or_default())
+            },
+            |epoch_hash| self.executor.replay_epoch(epoch_hash, inner),
+        )
+    }
+
+    /// Return the progress of the unfinished session of `block_hash`.
+    pub fn progress(
+        &self, block_hash: &H256,
+    ) -> Option<StateRegenerationProgress> {
+        self.sessions.progress(block_hash)
+    }
+
+    /// Drop the unfinished session of `block_hash`. Return `false` if there
+    /// is no such session.
+    pub fn cancel(&self, block_hash: &H256) -> bool {
+        self.sessions.cancel(block_hash)
+    }
+}
+
+/// The unfinished sessions, kept by the block hash. A session is taken out
+/// while it is being worked on, so concurrent calls for the same block plan
+/// their own sessions, and the epochs replayed by one of them are skipped by
+/// the others.
+#[derive(Default)]
+struct RegenerationSessions {
+    sessions: Mutex<HashMap<H256, RegenerationSession>>,
+}
+
+impl RegenerationSessions {
+    /// `plan` returns the epochs to replay if there is no session of
+    /// `block_hash`, and `replay` replays an epoch.
+    fn regenerate<P, R>(
+        &self, block_hash: &H256, budget: StateRegenerationBudget, plan: P,
+        mut replay: R,
+    ) -> Result<StateRegenerationProgress, String>
+    where
+        P: FnOnce() -> Result<Vec<H256>, String>,
+        R: FnMut(&H256) -> Result<(), String>,
+    {
+        let maybe_session = self.sessions.lock().remove(block_hash);
+        let mut session = match maybe_session {
+            Some(session) => session,
+            None => RegenerationSession {
+                epochs: plan()?,
+                replayed_epochs: 0,
+            },
+        };
+
+        let start = Instant::now();
+        let mut replayed_in_call = 0;
+        while session.replayed_epochs < session.epochs.len()
+            && replayed_in_call < budget.max_epochs
+            && start.elapsed() < budget.max_wall_time
+        {
+            replay(&session.epochs[session.replayed_epochs])?;
+            session.replayed_epochs += 1;
+            replayed_in_call += 1;
+        }
+
+        let progress = StateRegenerationProgress {
+            block_hash: *block_hash,
+            total_epochs: session.epochs.len(),
+            replayed_epochs: session.replayed_epochs,
+        };
+        if !progress.is_done() {
+            debug!("State regeneration paused: {:?}", progress);
+            self.sessions.lock().insert(*block_hash, session);
+        }
+        Ok(progress)
+    }
+
+    fn progress(&self, block_hash: &H256) -> Option<StateRegenerationProgress> {
+        self.sessions.lock().get(block_hash).map(|session| {
+            StateRegenerationProgress {
+                block_hash: *block_hash,
+                total_epochs: session.epochs.len(),
+                replayed_epochs: session.replayed_epochs,
+            }
+        })
+    }
+
+    fn cancel(&self, block_hash: &H256) -> bool {
+        self.sessions.lock().remove(block_hash).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        RegenerationSessions, StateRegenerationBudget,
+        StateRegenerationProgress,
+    };
+    use cfx_types::H256;
+    use std::{thread::sleep, time::Duration};
+
+    fn epochs(count: u64) -> Vec<H256> {
+        (0..count).map(H256::from_low_u64_be).collect()
+    }
+
+    fn progress(
+        total_epochs: usize, replayed_epochs: usize,
+    ) -> StateRegenerationProgress {
+        StateRegenerationProgress {
+            block_hash: H256::from_low_u64_be(100),
+            total_epochs,
+            replayed_epochs,
+        }
+    }
+
+    #[test]
+    fn test_regeneration_bounded_by_max_epochs() {
+        let sessions = RegenerationSessions::default();
+        let block_hash = H256::from_low_u64_be(100);
+        let budget = StateRegenerationBudget {
+            max_epochs: 2,
+            max_wall_time: Duration::from_secs(3600),
+        };
+        let mut replayed = Vec::new();
+        let regenerate = |replayed: &mut Vec<H256>| {
+            sessions.regenerate(
+                &block_hash,
+                budget,
+                || Ok(epochs(5)),
+                |epoch_hash| {
+                    replayed.push(*epoch_hash);
+                    Ok(())
+                },
+            )
+        };
+
+        assert_eq!(regenerate(&mut replayed), Ok(progress(5, 2)));
+        assert_eq!(sessions.progress(&block_hash), Some(progress(5, 2)));
+        // The next calls continue without planning again.
+        assert_eq!(
+            sessions.regenerate(
+                &block_hash,
+                budget,
+                || panic!("planned again"),
+                |epoch_hash| {
+                    replayed.push(*epoch_hash);
+                    Ok(())
+                }
+            ),
+            Ok(progress(5, 4))
+        );
+        assert_eq!(regenerate(&mut replayed), Ok(progress(5, 5)));
+        assert_eq!(replayed, epochs(5));
+        assert_eq!(sessions.progress(&block_hash), None);
+        assert!(!sessions.cancel(&block_hash));
+
+        // A state already available needs no replay.
+        assert_eq!(
+            sessions.regenerate(
+                &block_hash,
+                budget,
+                || Ok(vec![]),
+                |_| panic!("replayed")
+            ),
+            Ok(progress(0, 0))
+        );
+    }
+
+    #[test]
+    fn test_regeneration_bounded_by_wall_time() {
+        let sessions = RegenerationSessions::default();
+        let block_hash = H256::from_low_u64_be(100);
+        let budget = StateRegenerationBudget {
+            max_epochs: 10,
+            max_wall_time: Duration::from_millis(20),
+        };
+        let slow_replay = |_: &H256| {
+            sleep(Duration::from_millis(25));
+            Ok(())
+        };
+
+        // The epoch being replayed is not interrupted, but no epoch is
+        // started after the wall time.
+        assert_eq!(
+            sessions.regenerate(
+                &block_hash,
+                budget,
+                || Ok(epochs(3)),
+                slow_replay
+            ),
+            Ok(progress(3, 1))
+        );
+        let no_time = StateRegenerationBudget {
+            max_epochs: 10,
+            max_wall_time: Duration::from_millis(0),
+        };
+        assert_eq!(
+            sessions.regenerate(
+                &block_hash,
+                no_time,
+                || Ok(vec![]),
+                slow_replay
+            ),
+            Ok(progress(3, 1))
+        );
+        assert_eq!(
+            sessions.regenerate(
+                &block_hash,
+                budget,
+                || Ok(vec![]),
+                slow_replay
+            ),
+            Ok(progress(3, 2))
+        );
+        assert!(sessions.cancel(&block_hash));
+        assert_eq!(sessions.progress(&block_hash), None);
+    }
+
+    #[test]
+    fn test_regeneration_failure_drops_session() {
+        let sessions = RegenerationSessions::default();
+        let block_hash = H256::from_low_u64_be(100);
+        let budget = StateRegenerationBudget {
+            max_epochs: 10,
+            max_wall_time: Duration::from_secs(3600),
+        };
+        let result = sessions.regenerate(
+            &block_hash,
+            budget,
+            || Ok(epochs(3)),
+            |h| {
+                if *h == H256::from_low_u64_be(1) {
+                    Err("replay failed".into())
+                } else {
+                    Ok(())
+                }
+            },
+        );
+        assert_eq!(result, Err("replay failed".into()));
+        assert_eq!(sessions.progress(&block_hash), None);
+    }
+}
@@ -0,0 +1,99 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use hibitset::BitSet;
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+
+/// The number of scratch buffers kept by a `TraversalPool`. The graph
+/// traversals are done under the consensus inner lock, so only a few of them
+/// run at the same time.
+const MAX_POOLED_SCRATCHES: usize = 4;
+
+/// The buffers of a BFS or DFS over the arena. They are empty when taken
+/// from a `TraversalPool`, but keep the capacity of the previous traversals.
+#[derive(Default)]
+pub struct TraversalScratch {
+    pub visited: BitSet,
+    pub queue: VecDeque<usize>,
+    /// The DFS stack of (stage, arena index).
+    pub stack: Vec<(u32, usize)>,
+}
+
+impl TraversalScratch {
+    fn clear(&mut self) {
+        self.visited.clear();
+        self.queue.clear();
+        self.stack.clear();
+    }
+}
+
+/// A pool of `TraversalScratch`, so the traversals on each new block do not
+/// allocate their visited sets and queues again.
+///
+/// The scratch is taken by value instead of through a guard, because the
+/// callers usually need to modify the graph holding the pool during the
+/// traversal. A scratch that is not given back is simply dropped.
+#[derive(Default)]
+pub struct TraversalPool {
+    scratches: Mutex<Vec<TraversalScratch>>,
+}
+
+impl TraversalPool {
+    /// Take an empty scratch whose visited set can hold the arena indices
+    /// below `arena_capacity` without growing.
+    pub fn take(&self, arena_capacity: usize) -> TraversalScratch {
+        let mut scratch = self.scratches.lock().pop().unwrap_or_default();
+        if arena_capacity > 0 {
+            // Adding the largest index grows all layers of the bitset.
+            scratch.visited.add(arena_capacity as u32 - 1);
+            scratch.visited.clear();
+        }
+        scratch
+    }
+
+    pub fn give_back(&self, mut scratch: TraversalScratch) {
+        scratch.clear();
+        let mut scratches = self.scratches.lock();
+        if scratches.len() < MAX_POOLED_SCRATCHES {
+            scratches.push(scratch);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TraversalPool, MAX_POOLED_SCRATCHES};
+    use hibitset::BitSetLike;
+
+    #[test]
+    fn test_scratch_is_empty_and_reused() {
+        let pool = TraversalPool::default();
+        let mut scratch = pool.take(100);
+        assert!(scratch.visited.is_empty());
+        scratch.visited.add(42);
+        scratch.queue.push_back(42);
+        scratch.stack.push((0, 42));
+        let queue_capacity = scratch.queue.capacity();
+        pool.give_back(scratch);
+
+        let scratch = pool.take(100);
+        assert!(scratch.visited.is_empty());
+        assert!(scratch.queue.is_empty());
+        assert!(scratch.stack.is_empty());
+        assert_eq!(scratch.queue.capacity(), queue_capacity);
+    }
+
+    #[test]
+    fn test_pool_is_bounded() {
+        let pool = TraversalPool::default();
+        let scratches: Vec<_> = (0..MAX_POOLED_SCRATCHES + 2)
+            .map(|_| pool.take(10))
+            .collect();
+        for scratch in scratches {
+            pool.give_back(scratch);
+        }
+        assert_eq!(pool.scratches.lock().len(), MAX_POOLED_SCRATCHES);
+    }
+}
@@ -0,0 +1,144 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+//! Conversions from block difficulties to the `i128` weights used in the
+//! consensus graph.
+//!
+//! The weight of a single block is saturated at `MAX_BLOCK_WEIGHT`, so a sum
+//! of at most `MAX_WEIGHT_SUMMED_BLOCK_COUNT` block weights never exceeds
+//! `MAX_CUMULATIVE_WEIGHT` and the intermediate values of the adaptive weight
+//! computation still fit in `i128`. Difficulties this large cannot be reached
+//! by a valid proof of work in practice, so the saturation only guards
+//! against malformed inputs.
+
+use cfx_parameters::consensus_internal::{
+    MAX_BLOCK_WEIGHT, MAX_CUMULATIVE_WEIGHT,
+};
+use cfx_types::U256;
+
+/// The weight of a block with `difficulty` whose weight is `ratio` times its
+/// difficulty, saturated at `MAX_BLOCK_WEIGHT`.
+pub fn scaled_difficulty_to_weight(difficulty: &U256, ratio: u64) -> i128 {
+    let max_weight = U256::from(MAX_BLOCK_WEIGHT as u128);
+    match difficulty.checked_mul(U256::from(ratio)) {
+        Some(weight) if weight <= max_weight => weight.low_u128() as i128,
+        _ => {
+            warn!(
+                "Block weight saturated: difficulty={} ratio={}",
+                difficulty, ratio
+            );
+            MAX_BLOCK_WEIGHT
+        }
+    }
+}
+
+pub fn difficulty_to_weight(difficulty: &U256) -> i128 {
+    scaled_difficulty_to_weight(difficulty, 1)
+}
+
+/// Add a block weight or a cumulative weight to a cumulative weight.
+///
+/// Saturating the block weights guarantees that cumulative weights stay within
+/// `MAX_CUMULATIVE_WEIGHT`, so exceeding it is a bug in the consensus graph and
+/// it panics instead of silently producing a wrong weight.
+pub fn add_cumulative_weight(total: i128, weight: i128) -> i128 {
+    match total.checked_add(weight) {
+        Some(sum) if sum.abs() <= MAX_CUMULATIVE_WEIGHT => sum,
+        _ => panic!(
+            "Cumulative weight overflow: total={} weight={}",
+            total, weight
+        ),
+    }
+}
+
+pub fn sum_cumulative_weights(weights: &[i128]) -> i128 {
+    weights
+        .iter()
+        .fold(0, |total, weight| add_cumulative_weight(total, *weight))
+}
+
+/// The threshold `ratio * weight` used to compare against a cumulative
+/// weight, e.g. the adaptive weight beta times the block difficulty. The
+/// result is saturated at `i128::MAX`, which is larger than any cumulative
+/// weight.
+pub fn scaled_weight_threshold(weight: i128, ratio: u64) -> i128 {
+    weight.saturating_mul(ratio as i128)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        add_cumulative_weight, difficulty_to_weight,
+        scaled_difficulty_to_weight, scaled_weight_threshold,
+        sum_cumulative_weights,
+    };
+    use cfx_parameters::consensus_internal::{
+        CUMULATIVE_WEIGHT_HEADROOM, MAX_BLOCK_WEIGHT, MAX_CUMULATIVE_WEIGHT,
+        MAX_WEIGHT_SUMMED_BLOCK_COUNT,
+    };
+    use cfx_types::U256;
+
+    #[test]
+    fn test_weight_bounds() {
+        assert!(MAX_BLOCK_WEIGHT > u64::MAX as i128);
+        assert_eq!(
+            MAX_CUMULATIVE_WEIGHT,
+            MAX_BLOCK_WEIGHT * MAX_WEIGHT_SUMMED_BLOCK_COUNT as i128
+        );
+        assert!(MAX_CUMULATIVE_WEIGHT
+            .checked_mul(CUMULATIVE_WEIGHT_HEADROOM as i128)
+            .is_some());
+    }
+
+    #[test]
+    fn test_difficulty_to_weight() {
+        assert_eq!(difficulty_to_weight(&U256::zero()), 0);
+        assert_eq!(difficulty_to_weight(&U256::from(12345)), 12345);
+        assert_eq!(
+            scaled_difficulty_to_weight(&U256::from(12345), 250),
+            12345 * 250
+        );
+        let max = U256::from(MAX_BLOCK_WEIGHT as u128);
+        assert_eq!(difficulty_to_weight(&max), MAX_BLOCK_WEIGHT);
+        assert_eq!(difficulty_to_weight(&(max + 1)), MAX_BLOCK_WEIGHT);
+        // Difficulties that do not fit in `u128` or `i128` saturate instead of
+        // being truncated.
+        assert_eq!(
+            difficulty_to_weight(&U256::from(u128::MAX)),
+            MAX_BLOCK_WEIGHT
+        );
+        assert_eq!(difficulty_to_weight(&U256::max_value()), MAX_BLOCK_WEIGHT);
+        assert_eq!(
+            scaled_difficulty_to_weight(&U256::max_value(), 250),
+            MAX_BLOCK_WEIGHT
+        );
+        assert_eq!(
+            scaled_difficulty_to_weight(&(max / 2 + 1), 2),
+            MAX_BLOCK_WEIGHT
+        );
+    }
+
+    #[test]
+    fn test_cumulative_weight() {
+        assert_eq!(add_cumulative_weight(1, 2), 3);
+        assert_eq!(add_cumulative_weight(5, -7), -2);
+        assert_eq!(sum_cumulative_weights(&[]), 0);
+        assert_eq!(sum_cumulative_weights(&[1, 2, 3]), 6);
+        assert_eq!(
+            add_cumulative_weight(MAX_CUMULATIVE_WEIGHT - 1, 1),
+            MAX_CUMULATIVE_WEIGHT
+        );
+        assert_eq!(
+            scaled_weight_threshold(MAX_BLOCK_WEIGHT, 1000),
+            MAX_BLOCK_WEIGHT * 1000
+        );
+        assert_eq!(scaled_weight_threshold(i128::MAX / 2, 1000), i128::MAX);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_cumulative_weight_overflow() {
+        add_cumulative_weight(MAX_CUMULATIVE_WEIGHT, 1);
+    }
+}
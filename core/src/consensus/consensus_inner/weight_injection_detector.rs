@@ -0,0 +1,461 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::{
+    clock::SharedClock,
+    consensus::{
+        consensus_inner::confirmation_meter::ConfirmationMeter,
+        ConsensusGraphInner,
+    },
+};
+use cfx_types::{Address, H256};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Duration,
+};
+
+/// The number of authors counted in the author concentration of a window.
+const WEIGHT_INJECTION_TOP_AUTHOR_COUNT: usize = 2;
+/// The number of recent alerts kept for queries.
+const WEIGHT_INJECTION_MAX_KEPT_ALERTS: usize = 100;
+/// The weight of the latest window in the moving average of window weights.
+const WEIGHT_INJECTION_BASELINE_DECAY: f64 = 0.2;
+
+#[derive(Clone, Debug)]
+pub struct WeightInjectionDetectorConfig {
+    /// The blocks activated within this period are checked together.
+    pub window: Duration,
+    /// The weight of a window is a burst if it is more than `burst_ratio`
+    /// times the average weight of the previous windows.
+    pub burst_ratio: f64,
+    /// A burst is suspicious if the top authors produce more than this
+    /// fraction of its weight.
+    pub author_concentration: f64,
+    /// A block is unusual if it is at least this number of epochs below the
+    /// best epoch when it is activated, or all its referees are.
+    pub stale_block_lag: u64,
+    /// A burst is suspicious if more than this fraction of its weight is in
+    /// unusual blocks.
+    pub unusual_weight_ratio: f64,
+    /// If set, the confirmation risks are raised to at least this value for
+    /// a window after an alert.
+    pub alert_confirmation_risk: Option<f64>,
+}
+
+/// An alert that a withheld subtree may be being released.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WeightInjectionAlert {
+    /// The unix timestamp in seconds.
+    pub timestamp: u64,
+    /// The latest activated block when the alert is raised.
+    pub trigger_block: H256,
+    pub block_count: usize,
+    pub window_weight: i128,
+    pub baseline_weight: i128,
+    /// The authors with the most weight in the window and their weights.
+    pub top_authors: Vec<(Address, i128)>,
+    pub author_concentration: f64,
+    pub unusual_weight_ratio: f64,
+}
+
+struct BlockObservation {
+    /// The time of the clock when the block is activated.
+    time: Duration,
+    author: Address,
+    weight: i128,
+    unusual: bool,
+}
+
+struct WeightInjectionDetectorInner {
+    /// The blocks activated within the latest window.
+    observations: VecDeque<BlockObservation>,
+    window_weight: i128,
+    /// The moving average of the weights of the past windows. `None` until
+    /// the first window is complete.
+    baseline_weight: Option<f64>,
+    period_start: Duration,
+    period_weight: i128,
+    alert_until: Option<Duration>,
+    alerts: VecDeque<WeightInjectionAlert>,
+}
+
+/// `WeightInjectionDetector` watches the weight of the newly activated blocks
+/// for the release of a withheld subtree.
+///
+/// Such a release shows as a sudden growth of the activated weight, and the
+/// released blocks are usually produced by few authors and attach to the
+/// blocks far below the best epoch. An alert is raised when a burst of weight
+/// also meets either of the last two conditions. These are heuristics, so an
+/// alert is a hint for operators rather than a proof of an attack.
+pub struct WeightInjectionDetector {
+    config: WeightInjectionDetectorConfig,
+    clock: SharedClock,
+    inner: Mutex<WeightInjectionDetectorInner>,
+}
+
+impl WeightInjectionDetector {
+    pub fn new(
+        config: WeightInjectionDetectorConfig, clock: SharedClock,
+    ) -> Self {
+        let period_start = clock.now();
+        Self {
+            config,
+            clock,
+            inner: Mutex::new(WeightInjectionDetectorInner {
+                observations: VecDeque::new(),
+                window_weight: 0,
+                baseline_weight: None,
+                period_start,
+                period_weight: 0,
+                alert_until: None,
+                alerts: VecDeque::new(),
+            }),
+        }
+    }
+
+    /// Called after `hash` is processed by the consensus graph. The blocks
+    /// not activated yet are ignored.
+    pub fn on_new_block(
+        &self, inner: &ConsensusGraphInner, meter: &ConfirmationMeter,
+        hash: &H256,
+    )
+    {
+        let index = match inner.hash_to_arena_indices.get(hash) {
+            Some(index) => *index,
+            None => return,
+        };
+        let weight = inner.block_weight(index);
+        if weight == 0 {
+            return;
+        }
+        let author = match inner.data_man.block_header_by_hash(hash) {
+            Some(header) => *header.author(),
+            None => return,
+        };
+        let best_epoch = inner.best_epoch_number();
+        let lag = self.config.stale_block_lag;
+        let lagging = |i: usize| inner.arena[i].height + lag <= best_epoch;
+        let referees = &inner.arena[index].referees;
+        let unusual = lagging(index)
+            || (!referees.is_empty() && referees.iter().all(|r| lagging(*r)));
+
+        let now = self.clock.now();
+        if self.observe_block(hash, author, weight, unusual, now) {
+            if let Some(risk) = self.config.alert_confirmation_risk {
+                meter.raise_risks_until(risk, now + self.config.window);
+            }
+        }
+    }
+
+    /// Add an activated block to the window and check it. Return whether an
+    /// alert is raised.
+    fn observe_block(
+        &self, hash: &H256, author: Address, weight: i128, unusual: bool,
+        now: Duration,
+    ) -> bool
+    {
+        let mut detector = self.inner.lock();
+        self.roll_period(&mut *detector, now);
+        while detector.observations.front().map_or(false, |observation| {
+            elapsed_since(now, observation.time) >= self.config.window
+        }) {
+            let expired = detector.observations.pop_front().unwrap();
+            detector.window_weight -= expired.weight;
+        }
+        detector.observations.push_back(BlockObservation {
+            time: now,
+            author,
+            weight,
+            unusual,
+        });
+        detector.window_weight += weight;
+        detector.period_weight += weight;
+
+        if let Some(alert_until) = detector.alert_until {
+            if now < alert_until {
+                return false;
+            }
+            detector.alert_until = None;
+        }
+        match self.check_window(&*detector, hash, now) {
+            Some(alert) => {
+                warn!("Possible weight injection: {:?}", alert);
+                detector.alert_until = Some(now + self.config.window);
+                if detector.alerts.len() == WEIGHT_INJECTION_MAX_KEPT_ALERTS {
+                    detector.alerts.pop_front();
+                }
+                detector.alerts.push_back(alert);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Return the recent alerts, the newest first.
+    pub fn recent_alerts(&self, limit: usize) -> Vec<WeightInjectionAlert> {
+        self.inner
+            .lock()
+            .alerts
+            .iter()
+            .rev()
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// Fold the weights of the complete windows into the baseline.
+    fn roll_period(
+        &self, detector: &mut WeightInjectionDetectorInner, now: Duration,
+    ) {
+        let window = self.config.window;
+        let elapsed = elapsed_since(now, detector.period_start).as_nanos();
+        let periods = elapsed / window.as_nanos();
+        if periods == 0 {
+            return;
+        }
+        let period_weight = detector.period_weight as f64;
+        let baseline = match detector.baseline_weight {
+            Some(baseline) => {
+                baseline * (1.0 - WEIGHT_INJECTION_BASELINE_DECAY)
+                    + period_weight * WEIGHT_INJECTION_BASELINE_DECAY
+            }
+            None => period_weight,
+        };
+        // The windows after the first one are idle.
+        let idle_periods = (periods - 1).min(i32::MAX as u128) as i32;
+        detector.baseline_weight = Some(
+            baseline
+                * (1.0 - WEIGHT_INJECTION_BASELINE_DECAY).powi(idle_periods),
+        );
+        detector.period_weight = 0;
+        detector.period_start =
+            now - Duration::from_nanos((elapsed % window.as_nanos()) as u64);
+    }
+
+    fn check_window(
+        &self, detector: &WeightInjectionDetectorInner, trigger_block: &H256,
+        now: Duration,
+    ) -> Option<WeightInjectionAlert>
+    {
+        let baseline = detector.baseline_weight?;
+        let window_weight = detector.window_weight as f64;
+        if baseline <= 0.0
+            || window_weight <= baseline * self.config.burst_ratio
+        {
+            return None;
+        }
+
+        let mut author_weights = HashMap::new();
+        let mut unusual_weight = 0;
+        for observation in &detector.observations {
+            *author_weights.entry(observation.author).or_insert(0) +=
+                observation.weight;
+            if observation.unusual {
+                unusual_weight += observation.weight;
+            }
+        }
+        let mut top_authors: Vec<(Address, i128)> =
+            author_weights.into_iter().collect();
+        top_authors.sort_by(|a, b| b.1.cmp(&a.1));
+        top_authors.truncate(WEIGHT_INJECTION_TOP_AUTHOR_COUNT);
+        let top_author_weight: i128 = top_authors.iter().map(|a| a.1).sum();
+        let author_concentration = top_author_weight as f64 / window_weight;
+        let unusual_weight_ratio = unusual_weight as f64 / window_weight;
+        if author_concentration <= self.config.author_concentration
+            && unusual_weight_ratio <= self.config.unusual_weight_ratio
+        {
+            return None;
+        }
+
+        Some(WeightInjectionAlert {
+            timestamp: now.as_secs(),
+            trigger_block: *trigger_block,
+            block_count: detector.observations.len(),
+            window_weight: detector.window_weight,
+            baseline_weight: baseline as i128,
+            top_authors,
+            author_concentration,
+            unusual_weight_ratio,
+        })
+    }
+}
+
+/// The clock may be set back, in which case no time has elapsed.
+fn elapsed_since(now: Duration, earlier: Duration) -> Duration {
+    now.checked_sub(earlier).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{WeightInjectionDetector, WeightInjectionDetectorConfig};
+    use crate::clock::ManualClock;
+    use cfx_types::{Address, H256};
+    use std::{sync::Arc, time::Duration};
+
+    fn new_detector(clock: &Arc<ManualClock>) -> WeightInjectionDetector {
+        WeightInjectionDetector::new(
+            WeightInjectionDetectorConfig {
+                window: Duration::from_secs(10),
+                burst_ratio: 2.0,
+                author_concentration: 0.8,
+                stale_block_lag: 5,
+                unusual_weight_ratio: 0.5,
+                alert_confirmation_risk: None,
+            },
+            clock.clone(),
+        )
+    }
+
+    fn author(i: u64) -> Address { Address::from_low_u64_be(i) }
+
+    /// Observe a block of `weight` by `author` at `secs`.
+    fn observe(
+        detector: &WeightInjectionDetector, clock: &ManualClock, secs: u64,
+        author: Address, weight: i128, unusual: bool,
+    ) -> bool
+    {
+        clock.set(Duration::from_secs(secs));
+        detector.observe_block(
+            &H256::from_low_u64_be(secs),
+            author,
+            weight,
+            unusual,
+            clock.now(),
+        )
+    }
+
+    /// Fill the first window with 4 blocks of weight 10 by different
+    /// authors, so the baseline becomes 40 when it completes.
+    fn fill_first_window(
+        detector: &WeightInjectionDetector, clock: &ManualClock,
+    ) {
+        for i in 0..4 {
+            assert!(!observe(detector, clock, 1000, author(i), 10, false));
+        }
+    }
+
+    fn assert_close(actual: Option<f64>, expected: f64) {
+        assert!((actual.unwrap() - expected).abs() < 1e-9, "{:?}", actual);
+    }
+
+    #[test]
+    fn test_alert_and_suppression() {
+        let clock = Arc::new(ManualClock::new(Duration::from_secs(1000)));
+        let detector = new_detector(&clock);
+        fill_first_window(&detector, &clock);
+
+        // The first window expires, and a single author releases 100 > 2 * 40.
+        assert!(observe(&detector, &clock, 1010, author(9), 100, false));
+        let alerts = detector.recent_alerts(10);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].trigger_block, H256::from_low_u64_be(1010));
+        assert_eq!(alerts[0].block_count, 1);
+        assert_eq!(alerts[0].window_weight, 100);
+        assert_eq!(alerts[0].baseline_weight, 40);
+        assert_eq!(alerts[0].top_authors, vec![(author(9), 100)]);
+        assert!((alerts[0].author_concentration - 1.0).abs() < 1e-9);
+
+        // No alert is raised within a window after an alert.
+        assert!(!observe(&detector, &clock, 1015, author(9), 100, false));
+        assert_eq!(detector.recent_alerts(10).len(), 1);
+
+        // The baseline rolls to 40 * 0.8 + 200 * 0.2 = 72, and the window
+        // holds the blocks at 1015 and 1021.
+        assert!(observe(&detector, &clock, 1021, author(9), 100, false));
+        let alerts = detector.recent_alerts(10);
+        assert_eq!(alerts.len(), 2);
+        assert_eq!(alerts[0].trigger_block, H256::from_low_u64_be(1021));
+        assert_eq!(alerts[0].window_weight, 200);
+        assert_eq!(alerts[0].baseline_weight, 72);
+        assert_eq!(detector.recent_alerts(1).len(), 1);
+    }
+
+    #[test]
+    fn test_author_concentration_and_unusual_weight() {
+        let clock = Arc::new(ManualClock::new(Duration::from_secs(1000)));
+        let detector = new_detector(&clock);
+        fill_first_window(&detector, &clock);
+
+        // A burst spread over many authors is not suspicious.
+        for i in 10..15 {
+            assert!(!observe(&detector, &clock, 1010, author(i), 20, false));
+        }
+        assert!(detector.recent_alerts(10).is_empty());
+
+        // The top 2 authors produce 140 / 220 of the weight, but 120 / 220
+        // of it is in an unusual block.
+        assert!(observe(&detector, &clock, 1011, author(15), 120, true));
+        let alert = detector.recent_alerts(1).pop().unwrap();
+        assert_eq!(alert.block_count, 6);
+        assert_eq!(alert.top_authors[0], (author(15), 120));
+        assert!((alert.author_concentration - 140.0 / 220.0).abs() < 1e-9);
+        assert!((alert.unusual_weight_ratio - 120.0 / 220.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_roll_period() {
+        let clock = Arc::new(ManualClock::new(Duration::from_secs(1000)));
+        let detector = new_detector(&clock);
+        observe(&detector, &clock, 1000, author(1), 40, false);
+        assert_eq!(detector.inner.lock().baseline_weight, None);
+
+        // The first complete window becomes the baseline.
+        observe(&detector, &clock, 1010, author(2), 90, false);
+        {
+            let inner = detector.inner.lock();
+            assert_close(inner.baseline_weight, 40.0);
+            assert_eq!(inner.period_start, Duration::from_secs(1010));
+            assert_eq!(inner.period_weight, 90);
+        }
+
+        let mut inner = detector.inner.lock();
+        // The period starts at the last window boundary.
+        detector.roll_period(&mut inner, Duration::from_secs(1025));
+        assert_close(inner.baseline_weight, 40.0 * 0.8 + 90.0 * 0.2);
+        assert_eq!(inner.period_start, Duration::from_secs(1020));
+        assert_eq!(inner.period_weight, 0);
+
+        // An empty window and two idle windows decay the baseline.
+        detector.roll_period(&mut inner, Duration::from_secs(1050));
+        assert_close(inner.baseline_weight, 50.0 * 0.8 * 0.8 * 0.8);
+        assert_eq!(inner.period_start, Duration::from_secs(1050));
+
+        // Nothing changes within a window or when the clock is set back.
+        detector.roll_period(&mut inner, Duration::from_secs(1059));
+        detector.roll_period(&mut inner, Duration::from_secs(900));
+        assert_close(inner.baseline_weight, 25.6);
+        assert_eq!(inner.period_start, Duration::from_secs(1050));
+    }
+
+    #[test]
+    fn test_window_expiry() {
+        let clock = Arc::new(ManualClock::new(Duration::from_secs(1000)));
+        let detector = new_detector(&clock);
+        observe(&detector, &clock, 1000, author(1), 10, false);
+        observe(&detector, &clock, 1005, author(2), 10, false);
+        observe(&detector, &clock, 1010, author(3), 10, false);
+        {
+            let inner = detector.inner.lock();
+            assert_eq!(inner.observations.len(), 2);
+            assert_eq!(inner.window_weight, 20);
+        }
+
+        // No block expires when the clock is set back.
+        observe(&detector, &clock, 990, author(4), 10, false);
+        {
+            let inner = detector.inner.lock();
+            assert_eq!(inner.observations.len(), 3);
+            assert_eq!(inner.window_weight, 30);
+        }
+
+        // All the blocks observed at least a window ago expire.
+        observe(&detector, &clock, 1020, author(5), 10, false);
+        let inner = detector.inner.lock();
+        assert_eq!(inner.observations.len(), 1);
+        assert_eq!(inner.window_weight, 10);
+    }
+}
@@ -6,6 +6,7 @@ use crate::{
     block_data_manager::BlockDataManager,
     consensus::{BestInformation, ConsensusConfig, TransactionInfo},
     rpc_errors::Result as RpcResult,
+    spec::DeferredStateEpochCount,
     state::State,
     statistics::SharedStatistics,
     transaction_pool::SharedTransactionPool,
@@ -25,6 +26,14 @@ pub trait ConsensusGraphTrait: Send + Sync {
 
     fn on_new_block(&self, hash: &H256);
 
+    /// Notify that the body of a block arrives after the block is inserted
+    /// with only its header in the header graph mode.
+    fn on_new_block_body(&self, _hash: &H256) {}
+
+    /// Notify that the body of a block inserted with only its header in the
+    /// header graph mode is invalid.
+    fn on_invalid_block_body(&self, _hash: &H256) {}
+
     fn update_total_weight_delta_heartbeat(&self) {}
 
     fn expected_difficulty(&self, parent_hash: &H256) -> U256;
@@ -51,6 +60,8 @@ pub trait ConsensusGraphTrait: Send + Sync {
 
     fn get_statistics(&self) -> &SharedStatistics;
 
+    fn deferred_state_epoch_count(&self) -> DeferredStateEpochCount;
+
     fn block_count(&self) -> u64;
 
     fn get_hash_from_epoch_number(
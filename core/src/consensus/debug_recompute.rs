@@ -18,7 +18,8 @@ pub fn log_debug_epoch_computation(
         .state_root_with_aux_info
         .clone();
 
-    let reward_index = inner.get_pivot_reward_index(epoch_arena_index);
+    let reward_index =
+        executor.get_pivot_reward_index(inner, epoch_arena_index);
 
     let reward_execution_info =
         executor.get_reward_execution_info_from_index(inner, reward_index);
@@ -28,6 +29,7 @@ pub fn log_debug_epoch_computation(
         reward_execution_info,
         false, /* on_local_pivot */
         false, /* force_recompute */
+        ExecutionOrigin::DebugRecompute,
     );
     let mut debug_record = ComputeEpochDebugRecord::default();
     {
@@ -145,9 +147,47 @@ pub fn log_invalid_state_root(
     Ok(())
 }
 
+/// Check the weights maintained in the link-cut trees after inserting the
+/// block `me`, and dump the divergences if any.
+pub fn log_weight_audit(
+    inner: &ConsensusGraphInner, me: usize,
+) -> std::io::Result<()> {
+    let debug_record = match inner.audit_weights(me) {
+        Some(debug_record) => debug_record,
+        None => {
+            debug!(
+                "Weight audit passed after block {:?}",
+                inner.arena[me].hash
+            );
+            return Ok(());
+        }
+    };
+    error!(
+        "Weight audit failed after block {:?}: {} subtree weight divergences, \
+         {} adaptive weight divergences",
+        debug_record.block_hash,
+        debug_record.subtree_weight_divergences.len(),
+        debug_record.adaptive_weight_divergences.len(),
+    );
+    if let Some(dump_dir) = inner.inner_conf.debug_dump_dir_weight_audit.clone()
+    {
+        std::fs::create_dir_all(&dump_dir)?;
+        let json_path = dump_dir
+            + &format!(
+                "{}_{:?}.json.txt",
+                debug_record.sequence_number, debug_record.block_hash
+            );
+        let mut json_file = File::create(&json_path)?;
+        json_file
+            .write_all(serde_json::to_string(&debug_record)?.as_bytes())?;
+    }
+    Ok(())
+}
+
 use crate::consensus::{
-    consensus_inner::consensus_executor::{
-        ConsensusExecutor, EpochExecutionTask,
+    consensus_inner::{
+        consensus_executor::{ConsensusExecutor, EpochExecutionTask},
+        execution_origin::ExecutionOrigin,
     },
     ConsensusGraphInner,
 };
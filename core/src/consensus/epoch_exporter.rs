@@ -0,0 +1,618 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::{
+    consensus::ConsensusGraph, statistics::lock_contention::LockCategory,
+};
+use cfx_types::{Address, H256, U256};
+use parking_lot::Mutex;
+use primitives::{Action, Block, Receipt};
+use rustc_hex::ToHex;
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::min,
+    fmt::Write as FmtWrite,
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+const TRANSACTIONS_FILE: &str = "transactions";
+const RECEIPTS_FILE: &str = "receipts";
+const LOGS_FILE: &str = "logs";
+const CURSOR_FILE: &str = "cursor.json";
+
+const TRANSACTIONS_CSV_HEADER: &str = "epoch_number,block_hash,transaction_index,hash,from,to,nonce,value,gas_price,gas,data";
+const RECEIPTS_CSV_HEADER: &str = "epoch_number,block_hash,transaction_index,transaction_hash,outcome_status,gas_used,gas_fee,log_count";
+const LOGS_CSV_HEADER: &str = "epoch_number,block_hash,transaction_index,transaction_hash,log_index,transaction_log_index,address,topics,data";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EpochExportFormat {
+    /// Comma-separated values with a header line. The topics of a log are
+    /// separated by `;`.
+    Csv,
+    /// One JSON object per line.
+    Jsonl,
+}
+
+impl EpochExportFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            EpochExportFormat::Csv => "csv",
+            EpochExportFormat::Jsonl => "jsonl",
+        }
+    }
+}
+
+impl FromStr for EpochExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(EpochExportFormat::Csv),
+            "jsonl" => Ok(EpochExportFormat::Jsonl),
+            _ => Err(format!("Invalid epoch export format: {}", s)),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct EpochExportConfig {
+    pub dir: String,
+    pub format: EpochExportFormat,
+}
+
+/// The position of the export, saved in the export directory after each
+/// epoch. The file lengths are the ones when `next_epoch_number` is saved, so
+/// the rows written after that by an interrupted export are truncated.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EpochExportCursor {
+    pub next_epoch_number: u64,
+    /// The pivot block of the last exported epoch, used to detect the pivot
+    /// chain switching below the exported epochs.
+    pub last_epoch_hash: H256,
+    pub transactions_file_len: u64,
+    pub receipts_file_len: u64,
+    pub logs_file_len: u64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TransactionRecord {
+    epoch_number: u64,
+    block_hash: H256,
+    transaction_index: usize,
+    hash: H256,
+    from: Address,
+    to: Option<Address>,
+    nonce: U256,
+    value: U256,
+    gas_price: U256,
+    gas: U256,
+    data: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ReceiptRecord {
+    epoch_number: u64,
+    block_hash: H256,
+    transaction_index: usize,
+    transaction_hash: H256,
+    outcome_status: u8,
+    gas_used: U256,
+    gas_fee: U256,
+    log_count: usize,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LogRecord {
+    epoch_number: u64,
+    block_hash: H256,
+    transaction_index: usize,
+    transaction_hash: H256,
+    log_index: usize,
+    transaction_log_index: usize,
+    address: Address,
+    topics: Vec<H256>,
+    data: String,
+}
+
+/// The rows of one epoch, written to the files together.
+#[derive(Default)]
+struct EpochRows {
+    transactions: String,
+    receipts: String,
+    logs: String,
+}
+
+struct CollectedEpoch {
+    /// The pivot block of the epoch.
+    epoch_hash: H256,
+    parent_hash: H256,
+    rows: EpochRows,
+}
+
+/// `EpochExporter` writes the transactions, receipts and logs of the
+/// confirmed and executed epochs to line-delimited files, one file for each
+/// kind of record, so bulk extracts do not need to go through `cfx_getLogs`.
+///
+/// Each call exports a bounded number of epochs from the cursor saved in the
+/// export directory, so an export can be resumed after the node restarts.
+/// Only the epochs that are unlikely to be reverted are exported, and an
+/// export fails if the pivot chain switches below the cursor anyway, because
+/// the exported rows cannot be retracted.
+pub struct EpochExporter {
+    config: EpochExportConfig,
+    /// Held during an export, so the exports do not interleave.
+    export_lock: Mutex<()>,
+}
+
+impl EpochExporter {
+    pub fn new(config: EpochExportConfig) -> Self {
+        Self {
+            config,
+            export_lock: Mutex::new(()),
+        }
+    }
+
+    /// Export at most `max_epochs` epochs after the cursor, and return the
+    /// new cursor.
+    pub fn export(
+        &self, consensus: &ConsensusGraph, max_epochs: u64,
+    ) -> Result<EpochExportCursor, String> {
+        let earliest_epoch = consensus.earliest_epoch_for_log_filter();
+        let last_epoch = min(
+            consensus.confirmation_meter.get_confirmed_epoch_num(),
+            consensus.best_executed_state_epoch_number(),
+        );
+        self.export_epochs(earliest_epoch, last_epoch, max_epochs, |n| {
+            self.collect_epoch(consensus, n)
+        })
+    }
+
+    /// Export the epochs from the cursor to `last_epoch`, at most
+    /// `max_epochs` of them, with the rows returned by `collect`.
+    fn export_epochs<F>(
+        &self, earliest_epoch: u64, last_epoch: u64, max_epochs: u64,
+        mut collect: F,
+    ) -> Result<EpochExportCursor, String>
+    where
+        F: FnMut(u64) -> Result<CollectedEpoch, String>,
+    {
+        let _export_lock = self.export_lock.lock();
+        let mut cursor = self.load_cursor()?;
+        let dir = Path::new(&self.config.dir);
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        let mut transactions_file = self.open_file(
+            TRANSACTIONS_FILE,
+            TRANSACTIONS_CSV_HEADER,
+            &mut cursor.transactions_file_len,
+        )?;
+        let mut receipts_file = self.open_file(
+            RECEIPTS_FILE,
+            RECEIPTS_CSV_HEADER,
+            &mut cursor.receipts_file_len,
+        )?;
+        let mut logs_file = self.open_file(
+            LOGS_FILE,
+            LOGS_CSV_HEADER,
+            &mut cursor.logs_file_len,
+        )?;
+
+        if cursor.next_epoch_number < earliest_epoch {
+            return Err(format!(
+                "Epoch {} has been pruned, the earliest available epoch is {}",
+                cursor.next_epoch_number, earliest_epoch
+            ));
+        }
+        let end_epoch = min(
+            last_epoch.saturating_add(1),
+            cursor.next_epoch_number.saturating_add(max_epochs),
+        );
+        for epoch_number in cursor.next_epoch_number..end_epoch {
+            let CollectedEpoch {
+                epoch_hash,
+                parent_hash,
+                rows,
+            } = collect(epoch_number)?;
+            if !cursor.last_epoch_hash.is_zero()
+                && parent_hash != cursor.last_epoch_hash
+            {
+                return Err(format!(
+                    "Pivot chain switched below the exported epoch {:?}",
+                    cursor.last_epoch_hash
+                ));
+            }
+            cursor.transactions_file_len +=
+                append(&mut transactions_file, &rows.transactions)?;
+            cursor.receipts_file_len +=
+                append(&mut receipts_file, &rows.receipts)?;
+            cursor.logs_file_len += append(&mut logs_file, &rows.logs)?;
+            cursor.next_epoch_number = epoch_number + 1;
+            cursor.last_epoch_hash = epoch_hash;
+            self.save_cursor(&cursor)?;
+        }
+        Ok(cursor)
+    }
+
+    fn collect_epoch(
+        &self, consensus: &ConsensusGraph, epoch_number: u64,
+    ) -> Result<CollectedEpoch, String> {
+        let block_hashes = consensus
+            .inner
+            .read_recursive_for(LockCategory::Persist)
+            .block_hashes_by_epoch(epoch_number)?;
+        let epoch_hash = *block_hashes.last().expect("Epoch set not empty");
+        let data_man = &consensus.data_man;
+        let parent_hash = *data_man
+            .block_header_by_hash(&epoch_hash)
+            .ok_or_else(|| format!("Block {:?} not found", epoch_hash))?
+            .parent_hash();
+
+        let mut rows = EpochRows::default();
+        for block_hash in &block_hashes {
+            let block = data_man
+                .block_by_hash(block_hash, false /* update_cache */)
+                .ok_or_else(|| format!("Block {:?} not found", block_hash))?;
+            let receipts = data_man
+                .block_execution_result_by_hash_with_epoch(
+                    block_hash,
+                    &epoch_hash,
+                    false, /* update_pivot_assumption */
+                    false, /* update_cache */
+                )
+                .ok_or_else(|| {
+                    format!("Block {:?} is not executed", block_hash)
+                })?
+                .block_receipts
+                .receipts
+                .clone();
+            self.collect_block(epoch_number, &block, &receipts, &mut rows)?;
+        }
+        Ok(CollectedEpoch {
+            epoch_hash,
+            parent_hash,
+            rows,
+        })
+    }
+
+    fn collect_block(
+        &self, epoch_number: u64, block: &Block, receipts: &[Receipt],
+        rows: &mut EpochRows,
+    ) -> Result<(), String>
+    {
+        let block_hash = block.hash();
+        if receipts.len() != block.transactions.len() {
+            return Err(format!(
+                "Block {:?} has {} receipts for {} transactions",
+                block_hash,
+                receipts.len(),
+                block.transactions.len()
+            ));
+        }
+        let mut log_index = 0;
+        let mut prev_gas_used = U256::zero();
+        for (index, (tx, receipt)) in
+            block.transactions.iter().zip(receipts).enumerate()
+        {
+            let to = match tx.action {
+                Action::Create => None,
+                Action::Call(address) => Some(address),
+            };
+            let transaction = TransactionRecord {
+                epoch_number,
+                block_hash,
+                transaction_index: index,
+                hash: tx.hash(),
+                from: tx.sender(),
+                to,
+                nonce: tx.nonce,
+                value: tx.value,
+                gas_price: tx.gas_price,
+                gas: tx.gas,
+                data: tx.data.to_hex(),
+            };
+            let receipt_record = ReceiptRecord {
+                epoch_number,
+                block_hash,
+                transaction_index: index,
+                transaction_hash: tx.hash(),
+                outcome_status: receipt.outcome_status,
+                gas_used: receipt.accumulated_gas_used - prev_gas_used,
+                gas_fee: receipt.gas_fee,
+                log_count: receipt.logs.len(),
+            };
+            prev_gas_used = receipt.accumulated_gas_used;
+            self.write_row(&mut rows.transactions, &transaction, || {
+                format!(
+                    "{},{:?},{},{:?},{:?},{},{},{},{},{},{}",
+                    transaction.epoch_number,
+                    transaction.block_hash,
+                    transaction.transaction_index,
+                    transaction.hash,
+                    transaction.from,
+                    transaction
+                        .to
+                        .map_or(String::new(), |to| format!("{:?}", to)),
+                    transaction.nonce,
+                    transaction.value,
+                    transaction.gas_price,
+                    transaction.gas,
+                    transaction.data,
+                )
+            })?;
+            self.write_row(&mut rows.receipts, &receipt_record, || {
+                format!(
+                    "{},{:?},{},{:?},{},{},{},{}",
+                    receipt_record.epoch_number,
+                    receipt_record.block_hash,
+                    receipt_record.transaction_index,
+                    receipt_record.transaction_hash,
+                    receipt_record.outcome_status,
+                    receipt_record.gas_used,
+                    receipt_record.gas_fee,
+                    receipt_record.log_count,
+                )
+            })?;
+            for (transaction_log_index, log) in receipt.logs.iter().enumerate()
+            {
+                let log_record = LogRecord {
+                    epoch_number,
+                    block_hash,
+                    transaction_index: index,
+                    transaction_hash: tx.hash(),
+                    log_index,
+                    transaction_log_index,
+                    address: log.address,
+                    topics: log.topics.clone(),
+                    data: log.data.to_hex(),
+                };
+                log_index += 1;
+                self.write_row(&mut rows.logs, &log_record, || {
+                    let topics: Vec<String> = log_record
+                        .topics
+                        .iter()
+                        .map(|topic| format!("{:?}", topic))
+                        .collect();
+                    format!(
+                        "{},{:?},{},{:?},{},{},{:?},{},{}",
+                        log_record.epoch_number,
+                        log_record.block_hash,
+                        log_record.transaction_index,
+                        log_record.transaction_hash,
+                        log_record.log_index,
+                        log_record.transaction_log_index,
+                        log_record.address,
+                        topics.join(";"),
+                        log_record.data,
+                    )
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_row<T: Serialize, F: FnOnce() -> String>(
+        &self, buffer: &mut String, record: &T, csv_row: F,
+    ) -> Result<(), String> {
+        let line = match self.config.format {
+            EpochExportFormat::Csv => csv_row(),
+            EpochExportFormat::Jsonl => {
+                serde_json::to_string(record).map_err(|e| e.to_string())?
+            }
+        };
+        writeln!(buffer, "{}", line).map_err(|e| e.to_string())
+    }
+
+    fn file_path(&self, name: &str) -> PathBuf {
+        Path::new(&self.config.dir).join(format!(
+            "{}.{}",
+            name,
+            self.config.format.extension()
+        ))
+    }
+
+    /// Open the file for appending, and drop the rows after `len`. A new CSV
+    /// file starts with the header.
+    fn open_file(
+        &self, name: &str, csv_header: &str, len: &mut u64,
+    ) -> Result<File, String> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.file_path(name))
+            .map_err(|e| e.to_string())?;
+        file.set_len(*len).map_err(|e| e.to_string())?;
+        if *len == 0 && self.config.format == EpochExportFormat::Csv {
+            *len += append(&mut file, &format!("{}\n", csv_header))?;
+        }
+        Ok(file)
+    }
+
+    fn load_cursor(&self) -> Result<EpochExportCursor, String> {
+        let path = Path::new(&self.config.dir).join(CURSOR_FILE);
+        if !path.exists() {
+            return Ok(Default::default());
+        }
+        let content = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+        serde_json::from_str(&content).map_err(|e| e.to_string())
+    }
+
+    fn save_cursor(&self, cursor: &EpochExportCursor) -> Result<(), String> {
+        let path = Path::new(&self.config.dir).join(CURSOR_FILE);
+        let tmp_path = path.with_extension("json.tmp");
+        let content =
+            serde_json::to_string(cursor).map_err(|e| e.to_string())?;
+        // Renaming replaces the cursor atomically.
+        fs::write(&tmp_path, content)
+            .and_then(|_| fs::rename(&tmp_path, &path))
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Append `content` and return the number of bytes written.
+fn append(file: &mut File, content: &str) -> Result<u64, String> {
+    file.write_all(content.as_bytes())
+        .and_then(|_| file.flush())
+        .map_err(|e| e.to_string())?;
+    Ok(content.len() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        CollectedEpoch, EpochExportConfig, EpochExportCursor,
+        EpochExportFormat, EpochExporter, EpochRows, LOGS_CSV_HEADER,
+        LOGS_FILE, RECEIPTS_CSV_HEADER, RECEIPTS_FILE, TRANSACTIONS_FILE,
+    };
+    use cfx_types::H256;
+    use std::{fs, io::Write};
+    use tempdir::TempDir;
+
+    /// The pivot chains `fork` share the epochs before 2.
+    fn pivot_hash(fork: u64, epoch_number: u64) -> H256 {
+        if epoch_number < 2 {
+            H256::from_low_u64_be(epoch_number + 1)
+        } else {
+            H256::from_low_u64_be((fork << 32) + epoch_number + 1)
+        }
+    }
+
+    fn collect(fork: u64, epoch_number: u64) -> CollectedEpoch {
+        CollectedEpoch {
+            epoch_hash: pivot_hash(fork, epoch_number),
+            parent_hash: match epoch_number {
+                0 => H256::zero(),
+                n => pivot_hash(fork, n - 1),
+            },
+            rows: EpochRows {
+                transactions: format!("t{}-{}\n", fork, epoch_number),
+                receipts: format!("r{}-{}\n", fork, epoch_number),
+                logs: String::new(),
+            },
+        }
+    }
+
+    fn new_exporter(dir: &TempDir) -> EpochExporter {
+        EpochExporter::new(EpochExportConfig {
+            dir: dir.path().to_str().unwrap().into(),
+            format: EpochExportFormat::Csv,
+        })
+    }
+
+    fn export(
+        exporter: &EpochExporter, fork: u64, last_epoch: u64, max_epochs: u64,
+    ) -> Result<EpochExportCursor, String> {
+        exporter
+            .export_epochs(0, last_epoch, max_epochs, |n| Ok(collect(fork, n)))
+    }
+
+    fn receipts_file(exporter: &EpochExporter) -> String {
+        fs::read_to_string(exporter.file_path(RECEIPTS_FILE)).unwrap()
+    }
+
+    /// The receipts file with the epochs in `epochs`.
+    fn expected_receipts(epochs: &[(u64, u64)]) -> String {
+        let mut content = format!("{}\n", RECEIPTS_CSV_HEADER);
+        for (fork, epoch_number) in epochs {
+            content += &collect(*fork, *epoch_number).rows.receipts;
+        }
+        content
+    }
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let dir = TempDir::new("epoch_exporter").unwrap();
+        let exporter = new_exporter(&dir);
+        assert_eq!(exporter.load_cursor().unwrap(), Default::default());
+
+        let cursor = export(&exporter, 0, 9, 3).unwrap();
+        assert_eq!(cursor.next_epoch_number, 3);
+        assert_eq!(cursor.last_epoch_hash, pivot_hash(0, 2));
+        assert_eq!(
+            cursor.receipts_file_len,
+            fs::metadata(exporter.file_path(RECEIPTS_FILE))
+                .unwrap()
+                .len()
+        );
+        assert_eq!(cursor.logs_file_len, LOGS_CSV_HEADER.len() as u64 + 1);
+        assert_eq!(exporter.load_cursor().unwrap(), cursor);
+
+        // A new exporter continues from the saved cursor, up to the last
+        // epoch.
+        let exporter = new_exporter(&dir);
+        let cursor = export(&exporter, 0, 4, 10).unwrap();
+        assert_eq!(cursor.next_epoch_number, 5);
+        assert_eq!(exporter.load_cursor().unwrap(), cursor);
+        assert_eq!(
+            receipts_file(&exporter),
+            expected_receipts(&[(0, 0), (0, 1), (0, 2), (0, 3), (0, 4)])
+        );
+        assert_eq!(export(&exporter, 0, 4, 10).unwrap(), cursor);
+
+        // The cursor is below the earliest available epoch.
+        assert!(exporter
+            .export_epochs(6, 9, 1, |n| Ok(collect(0, n)))
+            .is_err());
+    }
+
+    #[test]
+    fn test_resume_after_truncated_tail() {
+        let dir = TempDir::new("epoch_exporter").unwrap();
+        let exporter = new_exporter(&dir);
+        export(&exporter, 0, 9, 2).unwrap();
+
+        // An interrupted export writes the rows of an epoch but not the
+        // cursor.
+        for name in &[TRANSACTIONS_FILE, RECEIPTS_FILE, LOGS_FILE] {
+            fs::OpenOptions::new()
+                .append(true)
+                .open(exporter.file_path(name))
+                .unwrap()
+                .write_all(b"r0-2\npartial")
+                .unwrap();
+        }
+
+        let cursor = export(&exporter, 0, 9, 2).unwrap();
+        assert_eq!(cursor.next_epoch_number, 4);
+        assert_eq!(
+            receipts_file(&exporter),
+            expected_receipts(&[(0, 0), (0, 1), (0, 2), (0, 3)])
+        );
+        assert_eq!(
+            fs::read_to_string(exporter.file_path(LOGS_FILE)).unwrap(),
+            format!("{}\n", LOGS_CSV_HEADER)
+        );
+    }
+
+    #[test]
+    fn test_reexport_after_pivot_switch() {
+        let dir = TempDir::new("epoch_exporter").unwrap();
+        let exporter = new_exporter(&dir);
+        let cursor = export(&exporter, 0, 9, 4).unwrap();
+        let content = receipts_file(&exporter);
+
+        // The pivot chain switches below the cursor, and nothing is written.
+        let err = export(&exporter, 1, 9, 4).unwrap_err();
+        assert!(err.contains("Pivot chain switched"), "{}", err);
+        assert_eq!(exporter.load_cursor().unwrap(), cursor);
+        assert_eq!(receipts_file(&exporter), content);
+
+        // The export resumes when the pivot chain switches back.
+        assert_eq!(export(&exporter, 0, 9, 1).unwrap().next_epoch_number, 5);
+
+        // A new export has the epochs of the new pivot chain.
+        let dir = TempDir::new("epoch_exporter").unwrap();
+        let exporter = new_exporter(&dir);
+        assert_eq!(export(&exporter, 1, 3, 10).unwrap().next_epoch_number, 4);
+        assert_eq!(
+            receipts_file(&exporter),
+            expected_receipts(&[(1, 0), (1, 1), (1, 2), (1, 3)])
+        );
+    }
+}
@@ -0,0 +1,73 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfx_types::H256;
+use primitives::filter::FilterError;
+use std::fmt;
+
+/// An `EpochNumber` resolved against the pivot chain of the consensus graph.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ResolvedEpoch {
+    pub height: u64,
+    pub pivot_hash: H256,
+    /// Whether the epoch is not after the latest epoch whose state is
+    /// available for reading. The state of an old epoch may still have been
+    /// removed.
+    pub executed: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EpochResolveError {
+    /// The epoch number is larger than the best epoch number.
+    AfterBestEpoch { epoch: u64, best_epoch: u64 },
+    /// The state of the latest mined epoch is requested.
+    LatestMinedNotExecuted,
+    /// The state of an epoch after the latest state epoch is requested.
+    NotExecuted { epoch: u64, latest_state_epoch: u64 },
+    /// The pivot block of the epoch cannot be found.
+    UnknownPivotBlock { epoch: u64, reason: String },
+}
+
+impl fmt::Display for EpochResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EpochResolveError::AfterBestEpoch { .. } => write!(
+                f,
+                "Invalid params: expected a numbers with less than largest epoch number."
+            ),
+            EpochResolveError::LatestMinedNotExecuted => {
+                write!(f, "Latest mined epoch is not executed")
+            }
+            EpochResolveError::NotExecuted {
+                epoch,
+                latest_state_epoch,
+            } => write!(
+                f,
+                "Specified epoch {} is not executed, the latest state epoch is {}",
+                epoch, latest_state_epoch
+            ),
+            EpochResolveError::UnknownPivotBlock { reason, .. } => {
+                write!(f, "{}", reason)
+            }
+        }
+    }
+}
+
+impl From<EpochResolveError> for String {
+    fn from(e: EpochResolveError) -> String { e.to_string() }
+}
+
+impl From<EpochResolveError> for FilterError {
+    fn from(e: EpochResolveError) -> FilterError {
+        match e {
+            EpochResolveError::AfterBestEpoch { epoch, best_epoch } => {
+                FilterError::OutOfBoundEpochNumber {
+                    to_epoch: epoch,
+                    max_epoch: best_epoch,
+                }
+            }
+            e => FilterError::Custom(e.to_string()),
+        }
+    }
+}
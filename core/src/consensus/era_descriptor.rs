@@ -0,0 +1,128 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::consensus::ConsensusInnerConfig;
+use cfx_types::{H256, U256};
+use hash::keccak;
+use rlp::RlpStream;
+use rlp_derive::{RlpDecodable, RlpEncodable};
+
+/// The era genesis a node is on, to be exchanged in peer handshakes. Two
+/// nodes that disagree on an era genesis at the same height, or on the weight
+/// rules applied to it, cannot exchange blocks usefully.
+#[derive(Clone, Debug, PartialEq, Eq, RlpEncodable, RlpDecodable)]
+pub struct EraDescriptor {
+    pub era_genesis_hash: H256,
+    pub era_genesis_height: u64,
+    /// The digest of the cumulative timer chain height and the difficulty of
+    /// the era genesis together with the parameters of the weight rules.
+    pub weight_digest: H256,
+}
+
+impl EraDescriptor {
+    pub fn new(
+        era_genesis_hash: H256, era_genesis_height: u64,
+        era_genesis_timer_chain_height: u64, era_genesis_difficulty: &U256,
+        inner_conf: &ConsensusInnerConfig,
+    ) -> Self
+    {
+        let mut stream = RlpStream::new_list(8);
+        stream
+            .append(&era_genesis_hash)
+            .append(&era_genesis_timer_chain_height)
+            .append(era_genesis_difficulty)
+            .append(&inner_conf.adaptive_weight_beta)
+            .append(&inner_conf.heavy_block_difficulty_ratio)
+            .append(&inner_conf.timer_chain_block_difficulty_ratio)
+            .append(&inner_conf.timer_chain_beta)
+            .append(&inner_conf.era_epoch_count);
+        EraDescriptor {
+            era_genesis_hash,
+            era_genesis_height,
+            weight_digest: keccak(stream.out()),
+        }
+    }
+
+    /// Check a remote descriptor at the same era genesis height as ours.
+    pub fn check_same_height(
+        &self, remote: &EraDescriptor,
+    ) -> Result<(), String> {
+        debug_assert_eq!(self.era_genesis_height, remote.era_genesis_height);
+        if self.era_genesis_hash != remote.era_genesis_hash {
+            return Err(format!(
+                "era genesis mismatches at height {} (ours: {:?}, theirs: {:?})",
+                self.era_genesis_height,
+                self.era_genesis_hash,
+                remote.era_genesis_hash
+            ));
+        }
+        if self.weight_digest != remote.weight_digest {
+            return Err(format!(
+                "era weight digest mismatches (ours: {:?}, theirs: {:?})",
+                self.weight_digest, remote.weight_digest
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EraDescriptor;
+    use crate::consensus::ConsensusInnerConfig;
+    use cfx_types::{H256, U256};
+
+    fn inner_conf(heavy_block_difficulty_ratio: u64) -> ConsensusInnerConfig {
+        ConsensusInnerConfig {
+            adaptive_weight_beta: 1000,
+            heavy_block_difficulty_ratio,
+            timer_chain_block_difficulty_ratio: 180,
+            timer_chain_beta: 240,
+            era_epoch_count: 50000,
+            enable_optimistic_execution: false,
+            optimistic_execution_min_depth: 0,
+            optimistic_execution_max_depth: 0,
+            enable_state_expose: false,
+            debug_dump_dir_invalid_state_root: None,
+            debug_invalid_state_root_epoch: None,
+            debug_weight_audit_interval: None,
+            debug_dump_dir_weight_audit: None,
+            epoch_set_eviction_depth: None,
+        }
+    }
+
+    fn descriptor(
+        hash: H256, timer_chain_height: u64, heavy_ratio: u64,
+    ) -> EraDescriptor {
+        EraDescriptor::new(
+            hash,
+            50000,
+            timer_chain_height,
+            &U256::from(1000),
+            &inner_conf(heavy_ratio),
+        )
+    }
+
+    #[test]
+    fn test_same_era_matches() {
+        let ours = descriptor(H256::from_low_u64_be(1), 10, 1000);
+        assert!(ours.check_same_height(&ours.clone()).is_ok());
+    }
+
+    #[test]
+    fn test_different_era_genesis() {
+        let ours = descriptor(H256::from_low_u64_be(1), 10, 1000);
+        let theirs = descriptor(H256::from_low_u64_be(2), 10, 1000);
+        assert!(ours.check_same_height(&theirs).is_err());
+    }
+
+    #[test]
+    fn test_different_weight_rules() {
+        let ours = descriptor(H256::from_low_u64_be(1), 10, 1000);
+        let theirs = descriptor(H256::from_low_u64_be(1), 10, 500);
+        assert!(ours.check_same_height(&theirs).is_err());
+        let theirs = descriptor(H256::from_low_u64_be(1), 11, 1000);
+        assert!(ours.check_same_height(&theirs).is_err());
+    }
+}
@@ -0,0 +1,245 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfx_types::H256;
+use std::{cmp::min, sync::Arc};
+
+/// The epochs are grouped in chunks by their heights, so a new view shares the
+/// chunks before the first changed epoch with the old one.
+const EPOCHS_PER_CHUNK: u64 = 1024;
+
+/// An epoch on the pivot chain of a `ConsensusGraphView`.
+#[derive(Debug)]
+pub struct EpochView {
+    pub pivot_hash: H256,
+    /// The blocks of the epoch in execution order. `None` if they are not
+    /// available when the view is built.
+    pub block_hashes: Option<Vec<H256>>,
+}
+
+type EpochChunk = Arc<Vec<Arc<EpochView>>>;
+
+/// An immutable view of the pivot chain in the current era, indexed by epoch
+/// number. A new view is built after each pivot chain update and replaces the
+/// old one, so the readers holding a view never wait for the consensus
+/// graph. A view may be older than the consensus graph, like
+/// `BestInformation`.
+#[derive(Debug, Default)]
+pub struct ConsensusGraphView {
+    era_genesis_height: u64,
+    best_epoch_number: u64,
+    /// The epochs from `era_genesis_height` to `best_epoch_number`. All
+    /// chunks but the first and the last cover `EPOCHS_PER_CHUNK` epochs
+    /// starting at a multiple of it.
+    chunks: Vec<EpochChunk>,
+}
+
+impl ConsensusGraphView {
+    /// Build the view of the pivot chain from `era_genesis_height` to
+    /// `best_epoch_number`. The epochs before the first one whose pivot hash
+    /// differs from this view are shared with it.
+    pub fn update<P, B>(
+        &self, era_genesis_height: u64, best_epoch_number: u64, pivot_hash: P,
+        block_hashes: B,
+    ) -> Self
+    where
+        P: Fn(u64) -> H256,
+        B: Fn(u64) -> Option<Vec<H256>>,
+    {
+        let fork_height = self.fork_height(
+            era_genesis_height,
+            best_epoch_number,
+            &pivot_hash,
+        );
+        let mut chunks = Vec::new();
+        let mut chunk_start = era_genesis_height;
+        while chunk_start <= best_epoch_number {
+            let chunk_end = min(
+                (chunk_start / EPOCHS_PER_CHUNK + 1) * EPOCHS_PER_CHUNK,
+                best_epoch_number + 1,
+            );
+            let reusable = if chunk_end <= fork_height {
+                self.chunk(chunk_start).and_then(|(start, chunk)| {
+                    if start == chunk_start
+                        && chunk.len() as u64 == chunk_end - chunk_start
+                    {
+                        Some(chunk.clone())
+                    } else {
+                        None
+                    }
+                })
+            } else {
+                None
+            };
+            let chunk = match reusable {
+                Some(chunk) => chunk,
+                None => Arc::new(
+                    (chunk_start..chunk_end)
+                        .map(|height| match self.epoch(height) {
+                            Some(epoch) if height < fork_height => {
+                                epoch.clone()
+                            }
+                            _ => Arc::new(EpochView {
+                                pivot_hash: pivot_hash(height),
+                                block_hashes: block_hashes(height),
+                            }),
+                        })
+                        .collect(),
+                ),
+            };
+            chunks.push(chunk);
+            chunk_start = chunk_end;
+        }
+        ConsensusGraphView {
+            era_genesis_height,
+            best_epoch_number,
+            chunks,
+        }
+    }
+
+    pub fn era_genesis_height(&self) -> u64 { self.era_genesis_height }
+
+    pub fn best_epoch_number(&self) -> u64 { self.best_epoch_number }
+
+    pub fn epoch(&self, height: u64) -> Option<&Arc<EpochView>> {
+        let (start, chunk) = self.chunk(height)?;
+        chunk.get((height - start) as usize)
+    }
+
+    pub fn pivot_hash(&self, height: u64) -> Option<H256> {
+        self.epoch(height).map(|epoch| epoch.pivot_hash)
+    }
+
+    pub fn block_hashes(&self, height: u64) -> Option<&Vec<H256>> {
+        self.epoch(height)?.block_hashes.as_ref()
+    }
+
+    /// Return the chunk containing `height` and the height it starts at.
+    fn chunk(&self, height: u64) -> Option<(u64, &EpochChunk)> {
+        if self.chunks.is_empty()
+            || height < self.era_genesis_height
+            || height > self.best_epoch_number
+        {
+            return None;
+        }
+        let first_chunk = self.era_genesis_height / EPOCHS_PER_CHUNK;
+        let chunk_index = height / EPOCHS_PER_CHUNK - first_chunk;
+        let start = if chunk_index == 0 {
+            self.era_genesis_height
+        } else {
+            height / EPOCHS_PER_CHUNK * EPOCHS_PER_CHUNK
+        };
+        self.chunks
+            .get(chunk_index as usize)
+            .map(|chunk| (start, chunk))
+    }
+
+    /// Return the lowest height in the new range from which the epochs may
+    /// differ from this view. A pivot block decides its ancestors, so if the
+    /// pivot hashes at a height are equal, they are equal at all lower
+    /// heights.
+    fn fork_height<P>(
+        &self, era_genesis_height: u64, best_epoch_number: u64, pivot_hash: &P,
+    ) -> u64
+    where P: Fn(u64) -> H256 {
+        if self.chunks.is_empty() {
+            return era_genesis_height;
+        }
+        let low = era_genesis_height.max(self.era_genesis_height);
+        let high = best_epoch_number.min(self.best_epoch_number);
+        if low > high {
+            return era_genesis_height;
+        }
+        let same =
+            |height: u64| self.pivot_hash(height) == Some(pivot_hash(height));
+        if !same(low) {
+            return era_genesis_height;
+        }
+        // `same(low)` holds and the answer is in `(low, high + 1]`.
+        let (mut matched, mut end) = (low, high + 1);
+        while matched + 1 < end {
+            let mid = matched + (end - matched) / 2;
+            if same(mid) {
+                matched = mid;
+            } else {
+                end = mid;
+            }
+        }
+        end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConsensusGraphView, EPOCHS_PER_CHUNK};
+    use cfx_types::H256;
+    use std::sync::Arc;
+
+    fn pivot(fork: u64, height: u64) -> H256 {
+        H256::from_low_u64_be(fork << 32 | height)
+    }
+
+    fn build(
+        view: &ConsensusGraphView, genesis: u64, best: u64, fork: u64,
+        fork_height: u64,
+    ) -> ConsensusGraphView
+    {
+        view.update(
+            genesis,
+            best,
+            |height| {
+                if height < fork_height {
+                    pivot(0, height)
+                } else {
+                    pivot(fork, height)
+                }
+            },
+            |height| Some(vec![H256::from_low_u64_be(height)]),
+        )
+    }
+
+    #[test]
+    fn test_update_shares_unchanged_epochs() {
+        let genesis = EPOCHS_PER_CHUNK / 2;
+        let best = EPOCHS_PER_CHUNK * 3;
+        let view = build(&Default::default(), genesis, best, 0, 0);
+        assert_eq!(view.pivot_hash(genesis - 1), None);
+        assert_eq!(view.pivot_hash(genesis), Some(pivot(0, genesis)));
+        assert_eq!(view.pivot_hash(best), Some(pivot(0, best)));
+        assert_eq!(view.pivot_hash(best + 1), None);
+        assert_eq!(
+            view.block_hashes(best),
+            Some(&vec![H256::from_low_u64_be(best)])
+        );
+
+        // Switch the pivot chain from `fork_height` and extend it.
+        let fork_height = EPOCHS_PER_CHUNK * 2 + 10;
+        let new_view = build(&view, genesis, best + 5, 1, fork_height);
+        assert!(Arc::ptr_eq(&view.chunks[1], &new_view.chunks[1]));
+        assert!(Arc::ptr_eq(
+            view.epoch(fork_height - 1).unwrap(),
+            new_view.epoch(fork_height - 1).unwrap()
+        ));
+        assert_eq!(
+            new_view.pivot_hash(fork_height),
+            Some(pivot(1, fork_height))
+        );
+        assert_eq!(new_view.pivot_hash(best + 5), Some(pivot(1, best + 5)));
+        assert_eq!(view.pivot_hash(fork_height), Some(pivot(0, fork_height)));
+    }
+
+    #[test]
+    fn test_update_moves_era_genesis() {
+        let view = build(&Default::default(), 0, EPOCHS_PER_CHUNK * 2, 0, 0);
+        let genesis = EPOCHS_PER_CHUNK + 1;
+        let new_view = build(&view, genesis, EPOCHS_PER_CHUNK * 2, 0, 0);
+        assert_eq!(new_view.pivot_hash(genesis - 1), None);
+        assert_eq!(new_view.pivot_hash(genesis), Some(pivot(0, genesis)));
+        assert!(Arc::ptr_eq(
+            view.epoch(genesis).unwrap(),
+            new_view.epoch(genesis).unwrap()
+        ));
+        assert!(Arc::ptr_eq(&view.chunks[2], &new_view.chunks[1]));
+    }
+}
@@ -2,56 +2,111 @@
 // Conflux is free software and distributed under GNU General Public License.
 // See http://www.gnu.org/licenses/
 
+mod account_read_cache;
 mod anticone_cache;
+mod anticone_index;
+mod anticone_set;
+mod best_head;
+pub mod chain_weight_proof;
+pub mod chaos_hooks;
 pub mod consensus_inner;
 pub mod consensus_trait;
 pub mod debug_recompute;
+pub mod epoch_exporter;
+pub mod epoch_resolver;
+pub mod era_descriptor;
+pub mod graph_view;
 mod pastset_cache;
 
 pub use crate::consensus::{
-    consensus_inner::{ConsensusGraphInner, ConsensusInnerConfig},
+    consensus_inner::{
+        BlockConsensusInfo, ConsensusBlockIds, ConsensusGraphInner,
+        ConsensusInnerConfig, GhastAncestorDecision, GhastDecision,
+        PivotBlockAnnotation, StateValidSource, SubtreeWeightSnapshot,
+        TerminalFilter,
+    },
     consensus_trait::{ConsensusGraphTrait, SharedConsensusGraph},
+    graph_view::ConsensusGraphView,
 };
 
-use super::consensus::consensus_inner::{
-    confirmation_meter::ConfirmationMeter,
-    consensus_executor::ConsensusExecutor,
-    consensus_new_block_handler::ConsensusNewBlockHandler,
+use super::consensus::{
+    account_read_cache::AccountReadCache,
+    best_head::BestHead,
+    consensus_inner::{
+        confirmation_meter::ConfirmationMeter,
+        consensus_executor::ConsensusExecutor,
+        consensus_new_block_handler::ConsensusNewBlockHandler,
+        epoch_fence::{EpochFence, EpochFences},
+        execution_verifier::ExecutionVerificationService,
+        finalized_epoch_outbox::FinalizedEpochOutbox,
+        pivot_history::{PivotHistory, PivotSwitch, PivotUpdate},
+        risk_model::RiskModelConfig,
+        state_regeneration::{
+            StateRegenerationBudget, StateRegenerationProgress,
+            StateRegenerationService,
+        },
+        weight_injection_detector::{
+            WeightInjectionAlert, WeightInjectionDetector,
+            WeightInjectionDetectorConfig,
+        },
+    },
 };
 use crate::{
     block_data_manager::{
         BlockDataManager, BlockExecutionResultWithEpoch, DataVersionTuple,
+        EpochIssuance, FinalizedEpochEvent, LogPosition, PivotSwitchEvent,
+        SENDER_TRANSACTION_INDEX_BUCKET_SIZE,
     },
-    consensus::consensus_inner::{
-        consensus_executor::ConsensusExecutionConfiguration, StateBlameInfo,
+    channel::{Channel, Receiver},
+    clock::SharedClock,
+    config_cell::ConfigCell,
+    consensus::{
+        chain_weight_proof::{
+            ChainWeightProof, MAX_CHAIN_WEIGHT_PROOF_HEADERS,
+        },
+        consensus_inner::{
+            block_validation::{BlockValidationVerdict, DeferredRootsVerdict},
+            consensus_executor::{
+                ConsensusExecutionConfiguration, ExecutionWorkerStatus,
+            },
+            StateBlameInfo,
+        },
+        epoch_exporter::{EpochExportConfig, EpochExportCursor, EpochExporter},
+        epoch_resolver::{EpochResolveError, ResolvedEpoch},
+        era_descriptor::EraDescriptor,
     },
     executive::ExecutionOutcome,
     pow::{PowComputer, ProofOfWorkConfig},
     rpc_errors::{invalid_params_check, Result as RpcResult},
+    spec::DeferredStateEpochCount,
     state::State,
-    statistics::SharedStatistics,
+    statistics::{
+        lock_contention::{InstrumentedRwLock, LockCategory},
+        SharedStatistics,
+    },
     trace::{
         trace::{ActionType, BlockExecTraces, LocalizedTrace},
         trace_filter::TraceFilter,
     },
     transaction_pool::SharedTransactionPool,
-    verification::VerificationConfig,
+    verification::{
+        compute_epoch_receipts_with_proof, EpochReceiptsWithProof,
+        VerificationConfig,
+    },
     NodeType, Notifications,
 };
 use cfx_internal_common::ChainIdParams;
 use cfx_parameters::{
     consensus::*,
-    consensus_internal::REWARD_EPOCH_COUNT,
     rpc::{
         GAS_PRICE_BLOCK_SAMPLE_SIZE, GAS_PRICE_TRANSACTION_SAMPLE_SIZE,
         TRANSACTION_COUNT_PER_BLOCK_WATER_LINE_LOW,
         TRANSACTION_COUNT_PER_BLOCK_WATER_LINE_MEDIUM,
     },
 };
-use cfx_state::state_trait::StateOpsTrait;
 use cfx_statedb::StateDb;
 use cfx_storage::state_manager::StateManagerTrait;
-use cfx_types::{Bloom, H160, H256, U256};
+use cfx_types::{Address, Bloom, H160, H256, U256};
 use either::Either;
 use itertools::Itertools;
 use malloc_size_of::{MallocSizeOf, MallocSizeOfOps};
@@ -65,14 +120,17 @@ use primitives::{
     epoch::BlockHashOrEpochNumber,
     filter::{FilterError, LogFilter},
     log_entry::LocalizedLogEntry,
-    receipt::Receipt,
-    EpochId, EpochNumber, SignedTransaction, TransactionIndex,
+    receipt::{Receipt, TxExecutionErrorInfo},
+    Account, EpochId, EpochNumber, SignedTransaction, StorageValue,
+    TransactionIndex,
 };
 use rayon::prelude::*;
+use serde::Serialize;
 use std::{
     any::Any,
     cmp::{max, min},
     collections::HashSet,
+    mem,
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc,
@@ -93,6 +151,7 @@ pub struct MaybeExecutedTxExtraInfo {
     pub block_number: u64,
     pub prior_gas_used: U256,
     pub tx_exec_error_msg: Option<String>,
+    pub tx_exec_error_info: TxExecutionErrorInfo,
 }
 
 pub struct TransactionInfo {
@@ -100,14 +159,21 @@ pub struct TransactionInfo {
     pub maybe_executed_extra_info: Option<MaybeExecutedTxExtraInfo>,
 }
 
+/// A transaction found in the index by sender.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SenderTransaction {
+    pub epoch_number: u64,
+    pub transaction_hash: H256,
+}
+
 #[derive(Clone)]
 pub struct ConsensusConfig {
     /// Chain id configs.
     pub chain_id: ChainIdParams,
     /// When bench_mode is true, the PoW solution verification will be skipped.
-    /// The transaction execution will also be skipped and only return the
-    /// pair of (KECCAK_NULL_RLP, KECCAK_EMPTY_LIST_RLP) This is for testing
-    /// only
+    /// The transactions will not be executed, and the epochs get the
+    /// deterministic pseudo roots of `MockExecutionBackend` instead. This is
+    /// for testing only
     pub bench_mode: bool,
     /// The configuration used by inner data
     pub inner_conf: ConsensusInnerConfig,
@@ -121,8 +187,8 @@ pub struct ConsensusConfig {
     /// Epoch batch size used in log filtering.
     /// Larger batch sizes may improve performance but might also prevent
     /// consensus from making progress under high RPC load.
-    pub get_logs_epoch_batch_size: usize,
-    pub get_logs_filter_max_epoch_range: Option<u64>,
+    pub get_logs_epoch_batch_size: ConfigCell<usize>,
+    pub get_logs_filter_max_epoch_range: ConfigCell<Option<u64>>,
 
     /// TODO: These parameters are only utilized in catch-up now.
     /// TODO: They should be used in data garbage collection, too.
@@ -133,6 +199,23 @@ pub struct ConsensusConfig {
     /// The number of extra epochs that we want to keep
     /// states/receipts/transactions.
     pub sync_state_epoch_gap: Option<u64>,
+    /// If set, executed epochs whose confirmation risk drops below this
+    /// threshold are appended to a persistent outbox for external indexers.
+    pub finalized_epoch_outbox_risk_threshold: Option<f64>,
+    /// The model computing the confirmation risks.
+    pub confirmation_risk_model: RiskModelConfig,
+    /// If set, the newly activated blocks are watched for the release of a
+    /// withheld subtree.
+    pub weight_injection_detector: Option<WeightInjectionDetectorConfig>,
+    /// If set, the transactions, receipts and logs of the confirmed epochs
+    /// can be exported to flat files.
+    pub epoch_export: Option<EpochExportConfig>,
+    /// If set, blocks are inserted once their headers are graph-ready, and
+    /// the execution of their epochs waits until their bodies arrive.
+    pub header_graph_mode: bool,
+    /// The maximum number of account records cached for the state reads of
+    /// the RPCs. The cache is disabled if it is 0.
+    pub account_read_cache_size: usize,
 }
 
 #[derive(Debug)]
@@ -172,6 +255,84 @@ impl BestInformation {
     pub fn best_chain_id(&self) -> u32 { self.chain_id }
 }
 
+/// A snapshot of the chain status for status endpoints. The consensus part is
+/// read under a single acquisition of the consensus lock, so it is consistent.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainStatus {
+    pub chain_id: u32,
+    pub best_block_hash: H256,
+    /// The number of blocks in the past set of the best block, not including
+    /// the best block itself.
+    pub best_block_number: u64,
+    pub best_epoch_number: u64,
+    /// The latest epoch whose state is available and not only optimistically
+    /// executed.
+    pub best_state_epoch_number: u64,
+    pub latest_confirmed_epoch_number: u64,
+    pub pivot_chain_length: usize,
+    pub era_genesis_hash: H256,
+    pub era_genesis_height: u64,
+    /// The maximum number of blocks executed and rewarded in an epoch.
+    pub epoch_executed_block_bound: usize,
+    pub current_difficulty: U256,
+    /// The total weight of the blocks in the current era.
+    pub total_weight: i128,
+    /// The number of unpacked transactions in the transaction pool.
+    pub tx_pool_size: usize,
+    pub ready_for_mining: bool,
+    pub sync_graph_inserted_header_count: usize,
+    pub sync_graph_inserted_block_count: usize,
+    pub consensus_graph_processed_block_count: usize,
+}
+
+/// The maximum number of entries returned by
+/// `ConsensusGraph::get_storage_entries` at once.
+pub const MAX_STORAGE_ENTRIES_PAGE_SIZE: usize = 1000;
+
+/// The epochs whose states can be read from the storage, returned by
+/// `ConsensusGraph::state_availability`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StateAvailability {
+    /// The first epoch whose state is maintained.
+    pub lower_bound: u64,
+    /// The last executed epoch on the current pivot chain. The states after
+    /// the deferred execution boundary are only optimistically executed.
+    pub upper_bound: u64,
+    /// The epochs in `[lower_bound, upper_bound]` whose states cannot be read
+    /// anyway, in the ascending order. The state synced from other nodes only
+    /// keeps the part needed to execute the following epochs.
+    pub gaps: Vec<u64>,
+}
+
+impl StateAvailability {
+    /// Return whether the state of the pivot block at `epoch_number` can be
+    /// read without executing any epoch.
+    pub fn contains(&self, epoch_number: u64) -> bool {
+        self.lower_bound <= epoch_number
+            && epoch_number <= self.upper_bound
+            && self.gaps.binary_search(&epoch_number).is_err()
+    }
+}
+
+/// The maximum number of pivot blocks returned by
+/// `ConsensusGraph::get_pivot_segment` at once.
+pub const MAX_PIVOT_SEGMENT_SIZE: u64 = 1000;
+
+/// The maximum number of blocks returned by
+/// `ConsensusGraph::get_block_hashes_by_epoch_page` at once.
+pub const MAX_EPOCH_BLOCK_PAGE_SIZE: usize = 1000;
+
+/// A page of the storage entries of a contract, in the ascending order of the
+/// storage keys.
+#[derive(Clone, Debug, Default)]
+pub struct StorageEntriesPage {
+    pub entries: Vec<(Vec<u8>, StorageValue)>,
+    /// The storage key to start the next page after, or `None` if this is
+    /// the last page.
+    pub next_key: Option<Vec<u8>>,
+}
+
 /// ConsensusGraph is a layer on top of SynchronizationGraph. A SyncGraph
 /// collect all blocks that the client has received so far, but a block can only
 /// be delivered to the ConsensusGraph if 1) the whole block content is
@@ -189,7 +350,7 @@ impl BestInformation {
 /// status* for all blocks before era stable block (more restrictively speaking,
 /// whose past sets do not contain the stable block).
 pub struct ConsensusGraph {
-    pub inner: Arc<RwLock<ConsensusGraphInner>>,
+    pub inner: Arc<InstrumentedRwLock<ConsensusGraphInner>>,
     pub txpool: SharedTransactionPool,
     pub data_man: Arc<BlockDataManager>,
     executor: Arc<ConsensusExecutor>,
@@ -199,6 +360,12 @@ pub struct ConsensusGraph {
     /// Make sure that it is only modified when holding inner lock to prevent
     /// any inconsistency
     best_info: RwLock<Arc<BestInformation>>,
+    /// The best block hash and epoch number of `best_info`, read by the hot
+    /// paths without taking the `best_info` lock.
+    best_head: BestHead,
+    /// Replaced together with `best_info`, so the pivot chain can be read
+    /// without the inner lock.
+    graph_view: RwLock<Arc<ConsensusGraphView>>,
     /// Set to `true` when we enter NormalPhase
     ready_for_mining: AtomicBool,
 
@@ -206,6 +373,27 @@ pub struct ConsensusGraph {
     /// This is always `None` for archive nodes.
     pub synced_epoch_id: Mutex<Option<EpochId>>,
     pub config: ConsensusConfig,
+    /// `None` if `finalized_epoch_outbox_risk_threshold` is not set.
+    finalized_epoch_outbox: Option<FinalizedEpochOutbox>,
+    pivot_history: PivotHistory,
+    /// The pivot chain updates are only computed if it has subscribers.
+    pivot_updates: Channel<PivotUpdate>,
+    /// `None` if `weight_injection_detector` is not set in the config.
+    weight_injection_detector: Option<WeightInjectionDetector>,
+    /// `None` if `epoch_export` is not set in the config.
+    epoch_exporter: Option<EpochExporter>,
+    execution_verifier: Arc<ExecutionVerificationService>,
+    /// The same fences as in `inner`, so they can be acquired without the
+    /// lock.
+    epoch_fences: Arc<EpochFences>,
+    state_regeneration: StateRegenerationService,
+    /// The subtree weights used by `predict_mining_adaptive_block`.
+    mining_subtree_weights: Mutex<Option<SubtreeWeightSnapshot>>,
+    /// `None` if `account_read_cache_size` is 0.
+    account_read_cache: Option<AccountReadCache>,
+    /// The source of time for the consensus components. It is shared with
+    /// the synchronization graph in a node.
+    pub clock: SharedClock,
 }
 
 impl MallocSizeOf for ConsensusGraph {
@@ -220,7 +408,7 @@ impl MallocSizeOf for ConsensusGraph {
 
 impl ConsensusGraph {
     /// Build the ConsensusGraph with a specific era genesis block and various
-    /// other components. The execution will be skipped if bench_mode sets
+    /// other components. The execution will be mocked if bench_mode sets
     /// to true.
     pub fn with_era_genesis(
         conf: ConsensusConfig, txpool: SharedTransactionPool,
@@ -230,26 +418,71 @@ impl ConsensusGraph {
         notifications: Arc<Notifications>,
         execution_conf: ConsensusExecutionConfiguration,
         verification_config: VerificationConfig, node_type: NodeType,
+        clock: SharedClock,
     ) -> Self
     {
-        let inner =
-            Arc::new(RwLock::new(ConsensusGraphInner::with_era_genesis(
+        // In bench mode the execution is skipped, so the claims cannot be
+        // verified.
+        let execution_verification_concurrency = if conf.bench_mode {
+            0
+        } else {
+            execution_conf.execution_verification_concurrency
+        };
+        let inner = Arc::new(InstrumentedRwLock::new(
+            ConsensusGraphInner::with_era_genesis(
                 pow_config,
                 pow.clone(),
                 data_man.clone(),
                 conf.inner_conf.clone(),
+                txpool.machine().params().deferred_state_epoch_count,
+                txpool.machine().params().epoch_executed_block_bound,
                 era_genesis_block_hash,
                 era_stable_block_hash,
-            )));
+                clock.clone(),
+            ),
+            statistics.consensus_inner_lock_contention(),
+        ));
         let executor = ConsensusExecutor::start(
             txpool.clone(),
             data_man.clone(),
             inner.clone(),
             execution_conf,
             verification_config,
+            statistics.clone(),
             conf.bench_mode,
         );
-        let confirmation_meter = ConfirmationMeter::new();
+        let confirmation_meter = ConfirmationMeter::new(
+            conf.confirmation_risk_model.build(),
+            clock.clone(),
+        );
+        let finalized_epoch_outbox =
+            conf.finalized_epoch_outbox_risk_threshold.map(|threshold| {
+                FinalizedEpochOutbox::new(
+                    data_man.clone(),
+                    threshold,
+                    &*inner.read(),
+                )
+            });
+        let pivot_history = PivotHistory::new(data_man.clone(), clock.clone());
+        let weight_injection_detector =
+            conf.weight_injection_detector.clone().map(|detector_conf| {
+                WeightInjectionDetector::new(detector_conf, clock.clone())
+            });
+        let epoch_exporter = conf.epoch_export.clone().map(EpochExporter::new);
+        let execution_verifier = ExecutionVerificationService::new(
+            data_man.clone(),
+            executor.clone(),
+            inner.clone(),
+            execution_verification_concurrency,
+        );
+        let epoch_fences = inner.read().epoch_fences.clone();
+        let state_regeneration =
+            StateRegenerationService::new(executor.clone());
+        let account_read_cache = if conf.account_read_cache_size > 0 {
+            Some(AccountReadCache::new(conf.account_read_cache_size))
+        } else {
+            None
+        };
 
         let graph = ConsensusGraph {
             inner,
@@ -268,9 +501,22 @@ impl ConsensusGraph {
             ),
             confirmation_meter,
             best_info: RwLock::new(Arc::new(Default::default())),
+            best_head: Default::default(),
+            graph_view: RwLock::new(Arc::new(Default::default())),
             ready_for_mining: AtomicBool::new(false),
             synced_epoch_id: Default::default(),
             config: conf,
+            finalized_epoch_outbox,
+            pivot_history,
+            pivot_updates: Channel::new("pivot-updates"),
+            weight_injection_detector,
+            epoch_exporter,
+            execution_verifier,
+            epoch_fences,
+            state_regeneration,
+            mining_subtree_weights: Default::default(),
+            account_read_cache,
+            clock,
         };
         graph.update_best_info(false /* ready_for_mining */);
         graph
@@ -283,7 +529,7 @@ impl ConsensusGraph {
 
     /// Build the ConsensusGraph with the initial (checkpointed) genesis block
     /// in the data manager and various other components. The execution will
-    /// be skipped if bench_mode sets to true.
+    /// be mocked if bench_mode sets to true.
     pub fn new(
         conf: ConsensusConfig, txpool: SharedTransactionPool,
         statistics: SharedStatistics, data_man: Arc<BlockDataManager>,
@@ -291,6 +537,7 @@ impl ConsensusGraph {
         notifications: Arc<Notifications>,
         execution_conf: ConsensusExecutionConfiguration,
         verification_conf: VerificationConfig, node_type: NodeType,
+        clock: SharedClock,
     ) -> Self
     {
         let genesis_hash = data_man.get_cur_consensus_era_genesis_hash();
@@ -308,9 +555,22 @@ impl ConsensusGraph {
             execution_conf,
             verification_conf,
             node_type,
+            clock,
         )
     }
 
+    /// The service verifying the execution results claimed by peers.
+    pub fn execution_verifier(&self) -> &Arc<ExecutionVerificationService> {
+        &self.execution_verifier
+    }
+
+    /// Acquire an epoch fence. The arena indices read from `inner` after this
+    /// call keep referring to the same blocks until the fence is dropped, even
+    /// if the lock is released in between.
+    pub fn acquire_epoch_fence(&self) -> EpochFence {
+        self.epoch_fences.acquire()
+    }
+
     /// Wait for the generation and the execution completion of a block in the
     /// consensus graph. This API is used mainly for testing purpose
     pub fn wait_for_generation(&self, hash: &H256) {
@@ -358,10 +618,91 @@ impl ConsensusGraph {
         )
     }
 
+    /// Predict the adaptive flag of a block to mine with only the read lock
+    /// of `inner`, so that refreshing the mining template does not contend
+    /// with block insertion. The cached subtree weights are reused until a
+    /// new block is inserted.
+    pub fn predict_mining_adaptive_block(
+        &self, inner: &ConsensusGraphInner, parent_hash: &H256,
+        referees: &Vec<H256>, difficulty: &U256,
+    ) -> bool
+    {
+        let parent_index =
+            *inner.hash_to_arena_indices.get(parent_hash).expect(
+                "parent_hash is the pivot chain tip,\
+                 so should still exist in ConsensusInner",
+            );
+        let referee_indices: Vec<_> = referees
+            .iter()
+            .map(|h| {
+                *inner
+                    .hash_to_arena_indices
+                    .get(h)
+                    .expect("Checked by the caller")
+            })
+            .collect();
+        inner.predict_mining_adaptive_block(
+            &mut *self.mining_subtree_weights.lock(),
+            parent_index,
+            referee_indices,
+            *difficulty,
+        )
+    }
+
+    /// Judge a block against the current consensus graph without inserting
+    /// it. The header needs to be in the data manager, and the parent and the
+    /// referees need to be in the consensus graph.
+    ///
+    /// The blame and the deferred roots are compared with the local execution
+    /// results, so this may wait for the deferred epoch to be executed.
+    pub fn validate_block(
+        &self, hash: &H256,
+    ) -> Result<BlockValidationVerdict, String> {
+        let header = self
+            .data_man
+            .block_header_by_hash(hash)
+            .ok_or("Block header not found")?;
+        let partial_invalid_cause =
+            self.inner.read().check_block_partial_validity(&header)?;
+        let deferred_roots = match self
+            .get_blame_and_deferred_state_for_generation(header.parent_hash())
+        {
+            Ok(expected) => {
+                if expected.blame == header.blame()
+                    && expected.state_vec_root == *header.deferred_state_root()
+                    && expected.receipts_vec_root
+                        == *header.deferred_receipts_root()
+                    && expected.logs_bloom_vec_root
+                        == *header.deferred_logs_bloom_hash()
+                {
+                    DeferredRootsVerdict::Matched
+                } else {
+                    DeferredRootsVerdict::Mismatched {
+                        expected_blame: expected.blame,
+                        expected_state_root: expected.state_vec_root,
+                        expected_receipts_root: expected.receipts_vec_root,
+                        expected_logs_bloom_hash: expected.logs_bloom_vec_root,
+                    }
+                }
+            }
+            Err(e) => DeferredRootsVerdict::Unavailable(e),
+        };
+        Ok(BlockValidationVerdict {
+            partial_invalid_cause,
+            deferred_roots,
+        })
+    }
+
     /// Convert EpochNumber to height based on the current ConsensusGraph
     pub fn get_height_from_epoch_number(
         &self, epoch_number: EpochNumber,
     ) -> Result<u64, String> {
+        Ok(self.resolve_epoch_height(&epoch_number)?)
+    }
+
+    fn resolve_epoch_height(
+        &self, epoch_number: &EpochNumber,
+    ) -> Result<u64, EpochResolveError> {
         Ok(match epoch_number {
             EpochNumber::Earliest => 0,
             EpochNumber::LatestCheckpoint => {
@@ -373,15 +714,84 @@ impl ConsensusGraph {
             EpochNumber::LatestMined => self.best_epoch_number(),
             EpochNumber::LatestState => self.best_executed_state_epoch_number(),
             EpochNumber::Number(num) => {
-                let epoch_num = num;
-                if epoch_num > self.inner.read_recursive().best_epoch_number() {
-                    return Err("Invalid params: expected a numbers with less than largest epoch number.".to_owned());
+                let best_epoch = self.best_epoch_number();
+                if *num > best_epoch {
+                    return Err(EpochResolveError::AfterBestEpoch {
+                        epoch: *num,
+                        best_epoch,
+                    });
                 }
-                epoch_num
+                *num
             }
         })
     }
 
+    /// Resolve `epoch_number` to its height and pivot block on the current
+    /// pivot chain. The epoch may not be executed.
+    pub fn resolve_epoch(
+        &self, epoch_number: &EpochNumber,
+    ) -> Result<ResolvedEpoch, EpochResolveError> {
+        let height = self.resolve_epoch_height(epoch_number)?;
+        let pivot_hash =
+            self.pivot_hash_from_epoch_number(height)
+                .map_err(|reason| EpochResolveError::UnknownPivotBlock {
+                    epoch: height,
+                    reason,
+                })?;
+        Ok(ResolvedEpoch {
+            height,
+            pivot_hash,
+            executed: height <= self.best_executed_state_epoch_number(),
+        })
+    }
+
+    /// Resolve `epoch_number` for reading its state. `LatestMined` and the
+    /// epochs after the latest state epoch are rejected.
+    pub fn resolve_stated_epoch(
+        &self, epoch_number: &EpochNumber,
+    ) -> Result<ResolvedEpoch, EpochResolveError> {
+        let latest_state_epoch = self.best_executed_state_epoch_number();
+        match epoch_number {
+            EpochNumber::LatestMined => {
+                return Err(EpochResolveError::LatestMinedNotExecuted);
+            }
+            EpochNumber::Number(num) if *num > latest_state_epoch => {
+                return Err(EpochResolveError::NotExecuted {
+                    epoch: *num,
+                    latest_state_epoch,
+                });
+            }
+            _ => {}
+        }
+        let resolved = self.resolve_epoch(epoch_number)?;
+        if !resolved.executed {
+            return Err(EpochResolveError::NotExecuted {
+                epoch: resolved.height,
+                latest_state_epoch,
+            });
+        }
+        Ok(resolved)
+    }
+
+    /// The latest view of the pivot chain. It is not updated while held.
+    pub fn graph_view(&self) -> Arc<ConsensusGraphView> {
+        self.graph_view.read_recursive().clone()
+    }
+
+    /// Read the pivot hash from the graph view, or from the inner if the
+    /// epoch is not in the view.
+    fn pivot_hash_from_epoch_number(
+        &self, height: u64,
+    ) -> Result<H256, String> {
+        match self.graph_view().pivot_hash(height) {
+            Some(hash) => Ok(hash),
+            None => self
+                .inner
+                .read_recursive()
+                .get_pivot_hash_from_epoch_number(height),
+        }
+    }
+
     /// Get the average gas price of the last GAS_PRICE_TRANSACTION_SAMPLE_SIZE
     /// blocks
     pub fn gas_price(&self) -> Option<U256> {
@@ -454,26 +864,6 @@ impl ConsensusGraph {
         }
     }
 
-    fn validate_stated_epoch(
-        &self, epoch_number: &EpochNumber,
-    ) -> Result<(), String> {
-        match epoch_number {
-            EpochNumber::LatestMined => {
-                return Err("Latest mined epoch is not executed".into());
-            }
-            EpochNumber::Number(num) => {
-                let latest_state_epoch =
-                    self.best_executed_state_epoch_number();
-                if *num > latest_state_epoch {
-                    return Err(format!("Specified epoch {} is not executed, the latest state epoch is {}", num, latest_state_epoch));
-                }
-            }
-            _ => {}
-        }
-
-        Ok(())
-    }
-
     /// Force the engine to recompute the deferred state root for a particular
     /// block given a delay.
     pub fn force_compute_blame_and_deferred_state_for_generation(
@@ -482,10 +872,7 @@ impl ConsensusGraph {
         {
             let inner = &mut *self.inner.write();
             let hash = inner
-                .get_state_block_with_delay(
-                    parent_block_hash,
-                    DEFERRED_STATE_EPOCH_COUNT as usize - 1,
-                )?
+                .get_deferred_state_block_for_child(parent_block_hash)?
                 .clone();
             self.executor.compute_state_for_block(&hash, inner)?;
         }
@@ -504,25 +891,47 @@ impl ConsensusGraph {
         )
     }
 
-    pub fn best_block_hash(&self) -> H256 {
-        self.best_info.read_recursive().best_block_hash
-    }
+    pub fn best_block_hash(&self) -> H256 { self.best_head.block_hash() }
 
     /// Returns the latest epoch whose state can be exposed safely, which means
     /// its state is available and it's not only visible to optimistic
     /// execution.
     pub fn best_executed_state_epoch_number(&self) -> u64 {
-        let state_upper_bound =
-            self.data_man.state_availability_boundary.read().upper_bound;
         // Here we can also get `best_state_epoch` from `inner`, but that
         // would acquire the inner read lock.
-        let best_epoch_number = self.best_info.read().best_epoch_number;
-        let deferred_state_height =
-            if best_epoch_number < DEFERRED_STATE_EPOCH_COUNT {
-                0
-            } else {
-                best_epoch_number - DEFERRED_STATE_EPOCH_COUNT + 1
-            };
+        let best_epoch_number = self.best_head.epoch_number();
+        self.executed_state_epoch_number(best_epoch_number)
+    }
+
+    /// Return the epochs whose states are available in the storage. It only
+    /// reads the state availability boundary, so it is cheap enough to be
+    /// called before serving a request on the state of an epoch.
+    pub fn state_availability(&self) -> StateAvailability {
+        let boundary = self.data_man.state_availability_boundary.read();
+        let mut gaps = Vec::new();
+        // The same rule as `StateAvailabilityBoundary::check_availability`.
+        if boundary.synced_state_height != 0
+            && boundary.lower_bound <= boundary.synced_state_height
+            && boundary.synced_state_height <= boundary.upper_bound
+        {
+            gaps.push(boundary.synced_state_height);
+        }
+        StateAvailability {
+            lower_bound: boundary.lower_bound,
+            upper_bound: boundary.upper_bound,
+            gaps,
+        }
+    }
+
+    fn executed_state_epoch_number(&self, best_epoch_number: u64) -> u64 {
+        let state_upper_bound =
+            self.data_man.state_availability_boundary.read().upper_bound;
+        let deferred_state_height = self
+            .txpool
+            .machine()
+            .params()
+            .deferred_state_epoch_count
+            .state_height(best_epoch_number + 1);
         // state upper bound can be lower than deferred_state_height because
         // the execution is async. It can also be higher
         // because of optimistic execution. Here we guarantee
@@ -579,10 +988,54 @@ impl ConsensusGraph {
             ),
             BlockHashOrEpochNumber::EpochNumber(epoch_number) => epoch_number,
         };
-        let state =
-            self.get_state_by_epoch_number(epoch_number, rpc_param_name)?;
+        let account = self.get_account_by_epoch_number(
+            &address,
+            epoch_number,
+            rpc_param_name,
+        )?;
 
-        Ok(state.nonce(&address)?)
+        Ok(account.map_or(U256::zero(), |account| account.nonce))
+    }
+
+    /// Read the account at `address` in the state of `epoch_number`, from
+    /// the account read cache if it is enabled.
+    pub fn get_account_by_epoch_number(
+        &self, address: &Address, epoch_number: EpochNumber,
+        rpc_param_name: &str,
+    ) -> RpcResult<Option<Account>>
+    {
+        let epoch = invalid_params_check(
+            rpc_param_name,
+            self.resolve_stated_epoch(&epoch_number),
+        )?;
+        let cache = match &self.account_read_cache {
+            Some(cache) => cache,
+            None => {
+                return Ok(self
+                    .get_state_db_by_height_and_hash(
+                        epoch.height,
+                        &epoch.pivot_hash,
+                    )?
+                    .get_account(address)?);
+            }
+        };
+        // The cached records are not served for the states that are no
+        // longer available, so the errors are the same as without the cache.
+        let available = self
+            .data_man
+            .state_availability_boundary
+            .read()
+            .check_availability(epoch.height, &epoch.pivot_hash);
+        if available {
+            if let Some(account) = cache.get(&epoch.pivot_hash, address) {
+                return Ok(account);
+            }
+        }
+        let account = self
+            .get_state_db_by_height_and_hash(epoch.height, &epoch.pivot_hash)?
+            .get_account(address)?;
+        cache.insert(epoch.pivot_hash, epoch.height, *address, account.clone());
+        Ok(account)
     }
 
     fn earliest_epoch_for_log_filter(&self) -> u64 {
@@ -644,6 +1097,50 @@ impl ConsensusGraph {
             .filter(move |log_entry| filter.matches(&log_entry.entry))
     }
 
+    /// Like `filter_block_receipts`, but only the logs at `positions`, which
+    /// are in the ascending order, are checked.
+    fn filter_block_receipts_at_positions(
+        &self, filter: &LogFilter, epoch_number: u64, block_hash: H256,
+        receipts: &Vec<Receipt>, tx_hashes: &Vec<H256>,
+        positions: Vec<LogPosition>,
+    ) -> Vec<LocalizedLogEntry>
+    {
+        // The block log index of the first log of each transaction.
+        let mut first_log_indices = Vec::with_capacity(receipts.len());
+        let mut log_count = 0;
+        for receipt in receipts {
+            first_log_indices.push(log_count);
+            log_count += receipt.logs.len();
+        }
+
+        // iterate in reverse like `filter_block_receipts`
+        positions
+            .into_iter()
+            .rev()
+            .filter_map(|position| {
+                let transaction_index = position.transaction_index as usize;
+                let transaction_log_index =
+                    position.transaction_log_index as usize;
+                let entry = receipts
+                    .get(transaction_index)?
+                    .logs
+                    .get(transaction_log_index)?
+                    .clone();
+                Some(LocalizedLogEntry {
+                    entry,
+                    block_hash,
+                    epoch_number,
+                    transaction_hash: *tx_hashes.get(transaction_index)?,
+                    transaction_index,
+                    transaction_log_index,
+                    log_index: first_log_indices[transaction_index]
+                        + transaction_log_index,
+                })
+            })
+            .filter(|log_entry| filter.matches(&log_entry.entry))
+            .collect()
+    }
+
     fn filter_block<'a>(
         &self, filter: &'a LogFilter, bloom_possibilities: &'a Vec<Bloom>,
         epoch: u64, pivot_hash: H256, block_hash: H256,
@@ -685,6 +1182,22 @@ impl ConsensusGraph {
             return Ok(Either::Left(std::iter::empty()));
         }
 
+        // The log index tells whether the block matches the bloom only by
+        // false positives before loading the block body.
+        let indexed_positions = match &filter.address {
+            Some(addresses) => self
+                .data_man
+                .block_log_index_by_hash_with_epoch(&block_hash, &pivot_hash)
+                .map(|index| index.positions_of_any(addresses)),
+            None => None,
+        };
+        if indexed_positions
+            .as_ref()
+            .map_or(false, |positions| positions.is_empty())
+        {
+            return Ok(Either::Left(std::iter::empty()));
+        }
+
         // get block body from db
         let block = match self.data_man.block_by_hash(&block_hash, false) {
             Some(b) => b,
@@ -699,13 +1212,19 @@ impl ConsensusGraph {
             }
         };
 
-        Ok(Either::Right(self.filter_block_receipts(
-            &filter,
-            epoch,
-            block_hash,
-            receipts,
-            block.transaction_hashes(),
-        )))
+        let tx_hashes = block.transaction_hashes();
+        Ok(Either::Right(match indexed_positions {
+            Some(positions) => Either::Left(
+                self.filter_block_receipts_at_positions(
+                    &filter, epoch, block_hash, &receipts, &tx_hashes,
+                    positions,
+                )
+                .into_iter(),
+            ),
+            None => Either::Right(self.filter_block_receipts(
+                &filter, epoch, block_hash, receipts, tx_hashes,
+            )),
+        }))
     }
 
     fn filter_single_epoch<'a>(
@@ -787,10 +1306,10 @@ impl ConsensusGraph {
         // lock so that we have a consistent view
         let _inner = self.inner.read_recursive();
 
-        let from_epoch =
-            self.get_height_from_epoch_number(filter.from_epoch.clone())?;
-        let to_epoch =
-            self.get_height_from_epoch_number(filter.to_epoch.clone())?;
+        let to_epoch = self.resolve_epoch_height(&filter.to_epoch)?;
+        let from_epoch = self
+            .resolve_epoch_height(&filter.from_epoch)
+            .map_err(String::from)?;
 
         if from_epoch > to_epoch {
             return Err(FilterError::InvalidEpochNumber {
@@ -806,7 +1325,8 @@ impl ConsensusGraph {
             });
         }
 
-        if let Some(max_gap) = self.config.get_logs_filter_max_epoch_range {
+        if let Some(max_gap) = self.config.get_logs_filter_max_epoch_range.get()
+        {
             // The range includes both ends.
             if to_epoch - from_epoch + 1 > max_gap {
                 return Err(FilterError::EpochNumberGapTooLarge {
@@ -865,7 +1385,7 @@ impl ConsensusGraph {
             .get_log_filter_epoch_range(&filter)?
             // we process epochs in each batch in parallel
             // but batches are processed one-by-one
-            .chunks(self.config.get_logs_epoch_batch_size)
+            .chunks(self.config.get_logs_epoch_batch_size.get())
             .into_iter()
             .map(move |epochs| {
                 self.filter_epoch_batch(
@@ -1032,15 +1552,266 @@ impl ConsensusGraph {
         &self, tx: &SignedTransaction, epoch: EpochNumber,
     ) -> RpcResult<ExecutionOutcome> {
         // only allow to call against stated epoch
-        self.validate_stated_epoch(&epoch)?;
-        let (epoch_id, epoch_size) = if let Ok(v) =
-            self.get_block_hashes_by_epoch(epoch)
+        let epoch = self.resolve_stated_epoch(&epoch).map_err(String::from)?;
+        let epoch_size = if let Ok(v) = self
+            .inner
+            .read_recursive()
+            .block_hashes_by_epoch(epoch.height)
         {
-            (v.last().expect("pivot block always exist").clone(), v.len())
+            v.len()
         } else {
             bail!("cannot get block hashes in the specified epoch, maybe it does not exist?");
         };
-        self.executor.call_virtual(tx, &epoch_id, epoch_size)
+        self.executor
+            .call_virtual(tx, &epoch.pivot_hash, epoch_size)
+    }
+
+    /// Read a page of the storage entries of the contract `address` at
+    /// `epoch_number` whose storage keys start with `key_prefix`. The page
+    /// starts after the storage key `start_after` and has at most `limit`
+    /// entries, capped at `MAX_STORAGE_ENTRIES_PAGE_SIZE`.
+    pub fn get_storage_entries(
+        &self, address: &H160, epoch_number: EpochNumber, key_prefix: &[u8],
+        start_after: Option<&[u8]>, limit: usize,
+    ) -> RpcResult<StorageEntriesPage>
+    {
+        let mut state_db =
+            self.get_state_db_by_epoch_number(epoch_number, "epoch_number")?;
+        let (raw_entries, next_key) = state_db.read_storage_entries(
+            address,
+            key_prefix,
+            start_after,
+            min(limit, MAX_STORAGE_ENTRIES_PAGE_SIZE),
+        )?;
+        let mut entries = Vec::with_capacity(raw_entries.len());
+        for (key, value) in raw_entries {
+            entries.push((key, rlp::decode::<StorageValue>(&value)?));
+        }
+        Ok(StorageEntriesPage { entries, next_key })
+    }
+
+    /// Collect the chain status in one call. The consensus lock is only
+    /// acquired once.
+    pub fn chain_status(&self) -> ChainStatus {
+        let (
+            best_block_hash,
+            best_block_number,
+            best_epoch_number,
+            pivot_chain_length,
+            (era_genesis_hash, era_genesis_height),
+            current_difficulty,
+            total_weight,
+        ) = {
+            let inner = self.inner.read_recursive();
+            (
+                inner.best_block_hash(),
+                inner.best_block_number(),
+                inner.best_epoch_number(),
+                inner.pivot_chain_len(),
+                inner.cur_era_genesis_hash_and_height(),
+                inner.current_difficulty,
+                inner.cur_era_total_weight(),
+            )
+        };
+        let statistics = self.statistics.inner.read();
+        ChainStatus {
+            chain_id: self
+                .config
+                .chain_id
+                .read()
+                .get_chain_id(best_epoch_number),
+            best_block_hash,
+            best_block_number,
+            best_epoch_number,
+            best_state_epoch_number: self
+                .executed_state_epoch_number(best_epoch_number),
+            latest_confirmed_epoch_number: self
+                .confirmation_meter
+                .get_confirmed_epoch_num(),
+            pivot_chain_length,
+            era_genesis_hash,
+            era_genesis_height,
+            epoch_executed_block_bound: self
+                .txpool
+                .machine()
+                .params()
+                .epoch_executed_block_bound,
+            current_difficulty,
+            total_weight,
+            tx_pool_size: self.txpool.total_unpacked(),
+            ready_for_mining: self.ready_for_mining.load(Ordering::SeqCst),
+            sync_graph_inserted_header_count: statistics
+                .sync_graph
+                .inserted_header_count,
+            sync_graph_inserted_block_count: statistics
+                .sync_graph
+                .inserted_block_count,
+            consensus_graph_processed_block_count: statistics
+                .consensus_graph
+                .processed_block_count,
+        }
+    }
+
+    pub fn block_ids_by_hash(
+        &self, block_hash: &H256,
+    ) -> Option<ConsensusBlockIds> {
+        self.inner.read().block_ids_by_hash(block_hash)
+    }
+
+    pub fn block_ids_by_stable_id(
+        &self, stable_id: u64,
+    ) -> Option<ConsensusBlockIds> {
+        self.inner.read().block_ids_by_stable_id(stable_id)
+    }
+
+    /// Return the GHAST state of a block in the consensus graph, to inspect
+    /// why it is partially invalid.
+    pub fn get_block_consensus_info(
+        &self, block_hash: &H256,
+    ) -> Option<BlockConsensusInfo> {
+        let inner = self.inner.read_recursive();
+        inner
+            .hash_to_arena_indices
+            .get(block_hash)
+            .map(|index| inner.block_consensus_info(*index))
+    }
+
+    /// Recompute the GHAST decision of a block in the consensus graph. This
+    /// is slow and only used for debugging.
+    pub fn ghast_decision(&self, block_hash: &H256) -> Option<GhastDecision> {
+        let inner = self.inner.read_recursive();
+        inner
+            .hash_to_arena_indices
+            .get(block_hash)
+            .and_then(|index| inner.ghast_decision(*index))
+    }
+
+    /// Return the epoch number of the pivot block `hash`, including the
+    /// pivot blocks checkpointed before the current era.
+    pub fn get_pivot_epoch_number_by_hash(&self, hash: &H256) -> Option<u64> {
+        self.inner.read_recursive().pivot_epoch_number_by_hash(hash)
+    }
+
+    /// Return the transactions sent by `sender` in the executed epochs from
+    /// `from_epoch` to `to_epoch` on the pivot chain, in the order of
+    /// execution. The first `offset` transactions are skipped, and at most
+    /// `limit` transactions are returned.
+    pub fn get_transactions_by_sender(
+        &self, sender: &Address, from_epoch: u64, to_epoch: u64, offset: usize,
+        limit: usize,
+    ) -> Result<Vec<SenderTransaction>, String>
+    {
+        if !self.data_man.sender_transaction_index_enabled() {
+            return Err("The transaction index by sender is not enabled".into());
+        }
+        if from_epoch > to_epoch {
+            return Err(format!(
+                "from_epoch {} is larger than to_epoch {}",
+                from_epoch, to_epoch
+            ));
+        }
+        let to_epoch = min(to_epoch, self.best_executed_state_epoch_number());
+        let mut transactions = Vec::new();
+        if from_epoch > to_epoch {
+            return Ok(transactions);
+        }
+        let mut skipped = 0;
+        for bucket in from_epoch / SENDER_TRANSACTION_INDEX_BUCKET_SIZE
+            ..=to_epoch / SENDER_TRANSACTION_INDEX_BUCKET_SIZE
+        {
+            for entry in
+                self.data_man.sender_transactions_in_bucket(sender, bucket)
+            {
+                if entry.epoch_number < from_epoch
+                    || entry.epoch_number > to_epoch
+                {
+                    continue;
+                }
+                for transaction_hash in entry.transaction_hashes {
+                    if transactions.len() == limit {
+                        return Ok(transactions);
+                    }
+                    if skipped < offset {
+                        skipped += 1;
+                        continue;
+                    }
+                    transactions.push(SenderTransaction {
+                        epoch_number: entry.epoch_number,
+                        transaction_hash,
+                    });
+                }
+            }
+        }
+        Ok(transactions)
+    }
+
+    pub fn block_ids_by_arena_index(
+        &self, index: usize,
+    ) -> Option<ConsensusBlockIds> {
+        self.inner.read().block_ids_by_arena_index(index)
+    }
+
+    pub fn execution_worker_status(&self) -> ExecutionWorkerStatus {
+        self.executor.worker_status()
+    }
+
+    /// Change the number of threads executing the epochs.
+    pub fn set_execution_worker_count(&self, count: usize) {
+        self.executor.set_worker_count(count)
+    }
+
+    /// Change the bounds of the optimistic execution depth.
+    pub fn set_optimistic_execution_depth(
+        &self, min_depth: u64, max_depth: u64,
+    ) {
+        self.inner
+            .write()
+            .set_optimistic_execution_depth(min_depth, max_depth)
+    }
+
+    /// Replay the epochs needed for the state of `block_hash` within
+    /// `budget`. Call it again to continue until the returned progress is
+    /// done.
+    pub fn regenerate_state(
+        &self, block_hash: &H256, budget: StateRegenerationBudget,
+    ) -> Result<StateRegenerationProgress, String> {
+        self.state_regeneration
+            .regenerate(block_hash, &self.inner, budget)
+    }
+
+    pub fn state_regeneration_progress(
+        &self, block_hash: &H256,
+    ) -> Option<StateRegenerationProgress> {
+        self.state_regeneration.progress(block_hash)
+    }
+
+    pub fn cancel_state_regeneration(&self, block_hash: &H256) -> bool {
+        self.state_regeneration.cancel(block_hash)
+    }
+
+    /// Test only. Delay every execution of the epoch `epoch_hash` by
+    /// `delay_ms` milliseconds, or remove the delay if it is `None`.
+    pub fn set_epoch_execution_delay(
+        &self, epoch_hash: H256, delay_ms: Option<u64>,
+    ) {
+        self.executor.chaos_hooks.set_execution_delay(
+            epoch_hash,
+            delay_ms.map(Duration::from_millis),
+        )
+    }
+
+    /// Test only. Drop the cached anticone sets of the given blocks and return
+    /// the number of dropped entries.
+    pub fn drop_anticone_cache_entries(&self, hashes: &[H256]) -> usize {
+        self.inner.write().drop_anticone_cache_entries(hashes)
+    }
+
+    /// Test only. Make the next activated block re-evaluate the pivot chain
+    /// from `from_height`. Heights not after the stable block are ignored.
+    pub fn force_pivot_reevaluation(&self, from_height: u64) {
+        self.executor
+            .chaos_hooks
+            .force_pivot_reevaluation(from_height)
     }
 
     /// Get the number of processed blocks (i.e., the number of calls to
@@ -1049,6 +1820,380 @@ impl ConsensusGraph {
         self.statistics.get_consensus_graph_processed_block_count()
     }
 
+    /// Return the issuance recorded when the pivot block of `epoch_number`
+    /// was executed on the local pivot chain.
+    pub fn get_epoch_issuance(
+        &self, epoch_number: EpochNumber,
+    ) -> Result<EpochIssuance, String> {
+        let epoch_hash = self.get_hash_from_epoch_number(epoch_number)?;
+        self.data_man
+            .epoch_issuance_by_hash(&epoch_hash)
+            .ok_or(format!(
+                "Issuance of epoch {:?} is not available",
+                epoch_hash
+            ))
+    }
+
+    /// Return the terminal block hashes selected by `filter`, which can be
+    /// used for relaying terminals or selecting referees for mining.
+    pub fn get_terminal_hashes(&self, filter: &TerminalFilter) -> Vec<H256> {
+        self.inner.read_recursive().filtered_terminal_hashes(filter)
+    }
+
+    /// Return the confirmation risk of block `hash`, or `None` if the block is
+    /// not included in any pivot chain epoch.
+    pub fn confirmation_risk_by_hash(&self, hash: H256) -> Option<f64> {
+        let inner = self.inner.read_recursive();
+        self.confirmation_meter
+            .confirmation_risk_by_hash(&*inner, hash)
+    }
+
+    /// Return the confirmation risk of the pivot block of `epoch_number`, or
+    /// `None` if the epoch is not on the pivot chain.
+    pub fn epoch_confirmation_risk(&self, epoch_number: u64) -> Option<f64> {
+        let inner = self.inner.read_recursive();
+        let pivot_hash =
+            inner.get_pivot_hash_from_epoch_number(epoch_number).ok()?;
+        self.confirmation_meter
+            .confirmation_risk_by_hash(&*inner, pivot_hash)
+    }
+
+    /// Return the ancestors of the blocks at the heights of `queries` in
+    /// order, all read under one read lock. See
+    /// `ConsensusGraphInner::ancestors_at_by_hash`.
+    pub fn batch_ancestors_at(
+        &self, queries: &[(H256, u64)],
+    ) -> Vec<Option<H256>> {
+        self.inner.read_recursive().ancestors_at_by_hash(queries)
+    }
+
+    /// Return the lowest common ancestors of the pairs of blocks in order,
+    /// all read under one read lock. See `ConsensusGraphInner::lcas_by_hash`.
+    pub fn batch_lca(&self, pairs: &[(H256, H256)]) -> Vec<Option<H256>> {
+        self.inner.read_recursive().lcas_by_hash(pairs)
+    }
+
+    /// Given `(height, hash)` samples of the pivot chain of a peer, usually
+    /// exponentially spaced back from its tip, return the highest pivot block
+    /// shared by the two pivot chains that the samples tell, or `None` if no
+    /// sample is on the local pivot chain.
+    ///
+    /// A sample in the local consensus graph tells the exact fork point, i.e.
+    /// its lowest common ancestor with the local pivot tip, even if it is not
+    /// on the local pivot chain. The other samples only tell whether they are
+    /// on the local pivot chain, which may be before the current era.
+    pub fn locate_fork_point(
+        &self, peer_pivot_samples: Vec<(u64, H256)>,
+    ) -> Option<(u64, H256)> {
+        let inner = self.inner.read_recursive();
+        let pivot_tip = inner.best_block_hash();
+        let pairs: Vec<(H256, H256)> = peer_pivot_samples
+            .iter()
+            .map(|(_, hash)| (*hash, pivot_tip))
+            .collect();
+        let lcas = inner.lcas_by_hash(&pairs);
+
+        let mut fork_point: Option<(u64, H256)> = None;
+        for ((height, hash), lca) in peer_pivot_samples.iter().zip(lcas) {
+            let common = match lca {
+                Some(lca) => inner
+                    .hash_to_arena_indices
+                    .get(&lca)
+                    .map(|index| (inner.arena[*index].height, lca)),
+                None => match inner.get_pivot_hash_from_epoch_number(*height) {
+                    Ok(pivot_hash) if pivot_hash == *hash => {
+                        Some((*height, *hash))
+                    }
+                    _ => None,
+                },
+            };
+            if let Some((common_height, common_hash)) = common {
+                if fork_point.map_or(true, |(h, _)| common_height > h) {
+                    fork_point = Some((common_height, common_hash));
+                }
+            }
+        }
+        fork_point
+    }
+
+    /// Return at most `limit` blocks of the epoch from the `offset`-th block
+    /// in the execution order. An epoch after a partition heals may contain
+    /// too many blocks to be returned by `get_block_hashes_by_epoch`.
+    pub fn get_block_hashes_by_epoch_page(
+        &self, epoch_number: EpochNumber, offset: usize, limit: usize,
+    ) -> Result<Vec<H256>, String> {
+        if limit > MAX_EPOCH_BLOCK_PAGE_SIZE {
+            return Err(format!(
+                "At most {} blocks can be queried at once",
+                MAX_EPOCH_BLOCK_PAGE_SIZE
+            ));
+        }
+        self.get_height_from_epoch_number(epoch_number)
+            .and_then(|height| {
+                self.inner
+                    .read_recursive()
+                    .block_hashes_by_epoch_page(height, offset, limit)
+            })
+    }
+
+    /// Return the number of blocks in the epoch.
+    pub fn get_epoch_size(
+        &self, epoch_number: EpochNumber,
+    ) -> Result<usize, String> {
+        self.get_height_from_epoch_number(epoch_number)
+            .and_then(|height| self.inner.read_recursive().epoch_size(height))
+    }
+
+    /// Return the receipts of all the blocks in epoch `epoch_number` with the
+    /// proofs of their receipts roots against the receipts root of the epoch,
+    /// i.e. the `deferred_receipts_root` of the block that commits it, so a
+    /// light client can verify each block without trusting this node.
+    pub fn get_epoch_receipts_with_proof(
+        &self, epoch_number: u64,
+    ) -> Result<EpochReceiptsWithProof, String> {
+        let block_hashes =
+            self.get_block_hashes_by_epoch(EpochNumber::Number(epoch_number))?;
+        let pivot_hash = *block_hashes
+            .last()
+            .ok_or(format!("Epoch {} has no blocks", epoch_number))?;
+        let epoch_receipts = block_hashes
+            .iter()
+            .map(|hash| {
+                self.data_man
+                    .block_execution_result_by_hash_with_epoch(
+                        hash,
+                        &pivot_hash,
+                        false, /* update_pivot_assumption */
+                        false, /* update_cache */
+                    )
+                    .map(|result| result.block_receipts)
+                    .ok_or(format!(
+                        "Receipts of block {:?} in epoch {} not found",
+                        hash, epoch_number
+                    ))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(compute_epoch_receipts_with_proof(
+            epoch_number,
+            &block_hashes,
+            &epoch_receipts,
+        ))
+    }
+
+    /// Return the total weight of the subtree of a block in consensus. Only
+    /// the read lock is taken, so polling the weights does not block the
+    /// insertion of blocks behind a write lock.
+    pub fn get_block_total_weight(&self, hash: &H256) -> Option<i128> {
+        self.inner.read_recursive().get_block_total_weight(hash)
+    }
+
+    /// Return the descriptor of the current era genesis, to be sent to the
+    /// peers in handshakes.
+    pub fn era_descriptor(&self) -> EraDescriptor {
+        self.inner.read_recursive().era_descriptor()
+    }
+
+    /// Check the era descriptor of a peer against our own. A peer failing
+    /// the check follows a different chain or different weight rules, so it
+    /// should be disconnected before exchanging blocks.
+    pub fn validate_era_descriptor(
+        &self, remote: &EraDescriptor,
+    ) -> Result<(), String> {
+        self.inner.read_recursive().validate_era_descriptor(remote)
+    }
+
+    /// Return a proof of the cumulative weight of the pivot blocks from the
+    /// era genesis at `anchor_height` (exclusive) to `end_height`
+    /// (inclusive). A longer segment than `MAX_CHAIN_WEIGHT_PROOF_HEADERS`
+    /// should be proven in several proofs.
+    pub fn chain_weight_proof(
+        &self, anchor_height: u64, end_height: u64,
+    ) -> Result<ChainWeightProof, String> {
+        let era_epoch_count = self.config.inner_conf.era_epoch_count;
+        if anchor_height % era_epoch_count != 0 {
+            return Err(format!(
+                "anchor height {} is not an era boundary",
+                anchor_height
+            ));
+        }
+        if end_height <= anchor_height {
+            return Err("end_height is not larger than anchor_height".into());
+        }
+        if end_height - anchor_height > MAX_CHAIN_WEIGHT_PROOF_HEADERS {
+            return Err(format!(
+                "At most {} pivot headers can be proven at once",
+                MAX_CHAIN_WEIGHT_PROOF_HEADERS
+            ));
+        }
+        let inner = self.inner.read_recursive();
+        let anchor_hash =
+            inner.get_pivot_hash_from_epoch_number(anchor_height)?;
+        let mut headers = Vec::new();
+        for height in anchor_height + 1..=end_height {
+            let hash = inner.get_pivot_hash_from_epoch_number(height)?;
+            let header =
+                self.data_man.block_header_by_hash(&hash).ok_or_else(|| {
+                    format!("Header of pivot block {:?} not found", hash)
+                })?;
+            headers.push(header.as_ref().clone());
+        }
+        Ok(ChainWeightProof {
+            anchor_hash,
+            anchor_height,
+            headers,
+        })
+    }
+
+    /// Return the pivot blocks from `from_height` to `to_height` (both
+    /// inclusive) with their consensus annotations, so that explorers do not
+    /// need to query the epochs one by one. At most `MAX_PIVOT_SEGMENT_SIZE`
+    /// pivot blocks in the current era can be queried at once.
+    pub fn get_pivot_segment(
+        &self, from_height: u64, to_height: u64,
+    ) -> Result<Vec<PivotBlockAnnotation>, String> {
+        if from_height > to_height {
+            return Err("from_height is larger than to_height".into());
+        }
+        if to_height - from_height >= MAX_PIVOT_SEGMENT_SIZE {
+            return Err(format!(
+                "At most {} pivot blocks can be queried at once",
+                MAX_PIVOT_SEGMENT_SIZE
+            ));
+        }
+        let inner = self.inner.read_recursive();
+        let mut segment = inner.pivot_segment(from_height, to_height)?;
+        for annotation in &mut segment {
+            annotation.confirmation_risk = self
+                .confirmation_meter
+                .confirmation_risk_by_hash(&*inner, annotation.hash);
+            let mut epoch_gas_used = Some(U256::zero());
+            for block_hash in
+                inner.block_hashes_by_epoch(annotation.epoch_number)?
+            {
+                let block_gas_used = self
+                    .data_man
+                    .block_execution_result_by_hash_with_epoch(
+                        &block_hash,
+                        &annotation.hash,
+                        false, /* update_pivot_assumption */
+                        false, /* update_cache */
+                    )
+                    .map(|result| {
+                        result
+                            .block_receipts
+                            .receipts
+                            .last()
+                            .map_or(U256::zero(), |receipt| {
+                                receipt.accumulated_gas_used
+                            })
+                    });
+                epoch_gas_used = match (epoch_gas_used, block_gas_used) {
+                    (Some(total), Some(gas)) => Some(total + gas),
+                    _ => None,
+                };
+            }
+            annotation.epoch_gas_used = epoch_gas_used;
+        }
+        Ok(segment)
+    }
+
+    /// Return at most `limit` finalized epochs that are not acknowledged by
+    /// the consumer yet.
+    pub fn finalized_epoch_events(
+        &self, limit: usize,
+    ) -> Result<Vec<FinalizedEpochEvent>, String> {
+        match &self.finalized_epoch_outbox {
+            Some(outbox) => Ok(outbox.pending_events(limit)),
+            None => Err("Finalized epoch outbox is not enabled".into()),
+        }
+    }
+
+    /// Acknowledge the finalized epoch events up to `seq` (inclusive).
+    pub fn acknowledge_finalized_epoch_events(
+        &self, seq: u64,
+    ) -> Result<(), String> {
+        match &self.finalized_epoch_outbox {
+            Some(outbox) => outbox.acknowledge(seq),
+            None => Err("Finalized epoch outbox is not enabled".into()),
+        }
+    }
+
+    /// Return at most `limit` recent weight injection alerts, the newest
+    /// first.
+    pub fn weight_injection_alerts(
+        &self, limit: usize,
+    ) -> Result<Vec<WeightInjectionAlert>, String> {
+        match &self.weight_injection_detector {
+            Some(detector) => Ok(detector.recent_alerts(limit)),
+            None => Err("Weight injection detector is not enabled".into()),
+        }
+    }
+
+    /// Export at most `max_epochs` confirmed epochs after the export cursor,
+    /// and return the new cursor.
+    pub fn export_epochs(
+        &self, max_epochs: u64,
+    ) -> Result<EpochExportCursor, String> {
+        match &self.epoch_exporter {
+            Some(exporter) => exporter.export(self, max_epochs),
+            None => Err("Epoch exporter is not enabled".into()),
+        }
+    }
+
+    /// Return at most `limit` recorded pivot chain switches from `from_seq`.
+    pub fn pivot_switch_history(
+        &self, from_seq: u64, limit: usize,
+    ) -> Vec<PivotSwitchEvent> {
+        self.pivot_history.events(from_seq, limit)
+    }
+
+    /// Subscribe to the pivot chain updates made by `on_new_block`, so the
+    /// applications can roll back their state when the pivot chain is
+    /// reverted. The updates before the subscription are not sent.
+    pub fn subscribe_pivot_updates(&self) -> Receiver<PivotUpdate> {
+        self.pivot_updates.subscribe()
+    }
+
+    pub fn unsubscribe_pivot_updates(&self, id: u64) -> bool {
+        self.pivot_updates.unsubscribe(id)
+    }
+
+    /// Return the pivot chain updates from the pivot chain ending at
+    /// `old_best_epoch_number` to the current one, given the `switches`
+    /// found in between.
+    fn pivot_updates(
+        inner: &ConsensusGraphInner, old_best_epoch_number: u64,
+        switches: &[PivotSwitch],
+    ) -> Vec<PivotUpdate>
+    {
+        let best_epoch_number = inner.best_epoch_number();
+        let epoch_hash = |height: u64| {
+            inner.epoch_hash(height).expect("epoch in the current era")
+        };
+        match switches.iter().map(|switch| switch.fork_height).min() {
+            Some(from_height) => vec![PivotUpdate::Revert {
+                from_height,
+                to_hashes: (from_height..=best_epoch_number)
+                    .map(epoch_hash)
+                    .collect(),
+            }],
+            None => (old_best_epoch_number + 1..=best_epoch_number)
+                .map(|height| PivotUpdate::NewPivot {
+                    height,
+                    hash: epoch_hash(height),
+                })
+                .collect(),
+        }
+    }
+
+    /// Return the total issued tokens after the execution of `epoch_number`.
+    pub fn get_total_supply(
+        &self, epoch_number: EpochNumber,
+    ) -> Result<U256, String> {
+        self.get_epoch_issuance(epoch_number)
+            .map(|issuance| issuance.total_issued)
+    }
+
     fn get_state_db_by_height_and_hash(
         &self, height: u64, hash: &H256,
     ) -> RpcResult<StateDb> {
@@ -1127,7 +2272,23 @@ impl ConsensusGraph {
             current_difficulty: inner.current_difficulty,
             bounded_terminal_block_hashes,
         });
+        self.best_head
+            .store(&best_info.best_block_hash, best_info.best_epoch_number);
         debug!("update_best_info to {:?}", best_info);
+
+        let (_, era_genesis_height) = inner.cur_era_genesis_hash_and_height();
+        let inner_ref = &*inner;
+        let graph_view = self.graph_view().update(
+            era_genesis_height,
+            best_epoch_number,
+            |height| {
+                inner_ref
+                    .epoch_hash(height)
+                    .expect("epoch in the current era")
+            },
+            |height| inner_ref.block_hashes_by_epoch(height).ok(),
+        );
+        *self.graph_view.write() = Arc::new(graph_view);
     }
 
     fn filter_traces_by_epochs(
@@ -1287,13 +2448,63 @@ impl ConsensusGraphTrait for ConsensusGraph {
             MeterTimer::time_func(CONSENSIS_ON_NEW_BLOCK_TIMER.as_ref());
         self.statistics.inc_consensus_graph_processed_block_count();
 
-        self.new_block_handler.on_new_block(
-            &mut *self.inner.write(),
-            &self.confirmation_meter,
-            hash,
-        );
+        // The recompute runs on an execution worker.
+        if let Some(header) = self.data_man.block_header_by_hash(hash) {
+            self.executor
+                .enqueue_stale_receipts_recompute(&header, &*self.inner.read());
+        }
+        let (pivot_switches, pivot_updates) = {
+            let inner = &mut *self.inner.write();
+            let old_best_epoch_number = inner.best_epoch_number();
+            self.new_block_handler.on_new_block(
+                inner,
+                &self.confirmation_meter,
+                hash,
+            );
+            let pivot_switches = mem::take(&mut inner.pivot_switches);
+            let pivot_updates = if self.pivot_updates.num_subscriptions() > 0 {
+                Self::pivot_updates(
+                    inner,
+                    old_best_epoch_number,
+                    &pivot_switches,
+                )
+            } else {
+                Vec::new()
+            };
+            (pivot_switches, pivot_updates)
+        };
+        if let Some(cache) = &self.account_read_cache {
+            if let Some(fork_height) =
+                pivot_switches.iter().map(|switch| switch.fork_height).min()
+            {
+                cache.invalidate_after(fork_height);
+            }
+        }
+        for update in pivot_updates {
+            self.pivot_updates.send(update);
+        }
 
         let ready_for_mining = self.ready_for_mining.load(Ordering::SeqCst);
+        // The switches during the replay of the blocks in the database at
+        // startup have been recorded before.
+        if ready_for_mining {
+            self.pivot_history.record(pivot_switches);
+            if let Some(detector) = &self.weight_injection_detector {
+                detector.on_new_block(
+                    &*self.inner.read(),
+                    &self.confirmation_meter,
+                    hash,
+                );
+            }
+        }
+
+        if let Some(outbox) = &self.finalized_epoch_outbox {
+            outbox.on_new_block(
+                &*self.inner.read_for(LockCategory::Persist),
+                &self.confirmation_meter,
+            );
+        }
+
         self.update_best_info(ready_for_mining);
         if ready_for_mining {
             self.txpool
@@ -1304,6 +2515,16 @@ impl ConsensusGraphTrait for ConsensusGraph {
         debug!("Finish Consensus::on_new_block for {:?}", hash);
     }
 
+    fn on_new_block_body(&self, hash: &H256) {
+        let inner = &mut *self.inner.write();
+        self.new_block_handler.on_new_block_body(inner, hash);
+    }
+
+    fn on_invalid_block_body(&self, hash: &H256) {
+        let inner = &mut *self.inner.write();
+        self.new_block_handler.on_invalid_block_body(inner, hash);
+    }
+
     /// This function is a wrapper function for the function in the confirmation
     /// meter. The synchronization layer is supposed to call this function
     /// every 2 * BLOCK_PROPAGATION_DELAY seconds
@@ -1316,7 +2537,7 @@ impl ConsensusGraphTrait for ConsensusGraph {
     /// avoiding intermediate redundant computation triggered by
     /// on_new_block().
     fn construct_pivot_state(&self) {
-        let inner = &mut *self.inner.write();
+        let inner = &mut *self.inner.write_for(LockCategory::Persist);
         // Ensure that `state_valid` of the first valid block after
         // cur_era_stable_genesis is set
         inner.recover_state_valid();
@@ -1328,9 +2549,7 @@ impl ConsensusGraphTrait for ConsensusGraph {
         self.best_info.read_recursive().clone()
     }
 
-    fn best_epoch_number(&self) -> u64 {
-        self.best_info.read_recursive().best_epoch_number
-    }
+    fn best_epoch_number(&self) -> u64 { self.best_head.epoch_number() }
 
     fn latest_checkpoint_epoch_number(&self) -> u64 {
         self.data_man
@@ -1348,9 +2567,7 @@ impl ConsensusGraphTrait for ConsensusGraph {
         self.best_info.read_recursive().best_chain_id()
     }
 
-    fn best_block_hash(&self) -> H256 {
-        self.best_info.read_recursive().best_block_hash
-    }
+    fn best_block_hash(&self) -> H256 { self.best_head.block_hash() }
 
     /// Compute the expected difficulty of a new block given its parent
     fn expected_difficulty(&self, parent_hash: &H256) -> U256 {
@@ -1370,6 +2587,10 @@ impl ConsensusGraphTrait for ConsensusGraph {
 
     fn get_statistics(&self) -> &SharedStatistics { &self.statistics }
 
+    fn deferred_state_epoch_count(&self) -> DeferredStateEpochCount {
+        self.txpool.machine().params().deferred_state_epoch_count
+    }
+
     /// Returns the total number of blocks processed in consensus graph.
     ///
     /// This function should only be used in tests.
@@ -1384,17 +2605,18 @@ impl ConsensusGraphTrait for ConsensusGraph {
         &self, epoch_number: EpochNumber,
     ) -> Result<H256, String> {
         self.get_height_from_epoch_number(epoch_number)
-            .and_then(|height| {
-                self.inner.read().get_pivot_hash_from_epoch_number(height)
-            })
+            .and_then(|height| self.pivot_hash_from_epoch_number(height))
     }
 
     fn get_block_hashes_by_epoch(
         &self, epoch_number: EpochNumber,
     ) -> Result<Vec<H256>, String> {
         self.get_height_from_epoch_number(epoch_number)
-            .and_then(|height| {
-                self.inner.read_recursive().block_hashes_by_epoch(height)
+            .and_then(|height| match self.graph_view().block_hashes(height) {
+                Some(block_hashes) => Ok(block_hashes.clone()),
+                None => {
+                    self.inner.read_recursive().block_hashes_by_epoch(height)
+                }
             })
     }
 
@@ -1436,14 +2658,20 @@ impl ConsensusGraphTrait for ConsensusGraph {
         &self, block_hash: &H256,
     ) -> Result<Option<u64>, String> {
         let inner = self.inner.read_recursive();
-        let epoch_number = match inner.get_block_epoch_number(block_hash) {
+        // The blocks before the current era are resolved with the pivot
+        // index persisted at execution, so their numbers stay stable.
+        let epoch_number = match inner
+            .get_block_epoch_number(block_hash)
+            .or_else(|| self.data_man.block_epoch_number(block_hash))
+        {
             None => return Ok(None),
             Some(epoch_number) => epoch_number,
         };
-        let epoch_hash = match inner.epoch_hash(epoch_number) {
-            None => return Ok(None),
-            Some(hash) => hash,
-        };
+        let epoch_hash =
+            match inner.get_pivot_hash_from_epoch_number(epoch_number) {
+                Err(_) => return Ok(None),
+                Ok(hash) => hash,
+            };
         let blocks =
             self.get_block_hashes_by_epoch(EpochNumber::Number(epoch_number))?;
         let start_block_number =
@@ -1490,24 +2718,8 @@ impl ConsensusGraphTrait for ConsensusGraph {
     fn get_state_by_epoch_number(
         &self, epoch_number: EpochNumber, rpc_param_name: &str,
     ) -> RpcResult<State> {
-        invalid_params_check(
-            rpc_param_name,
-            self.validate_stated_epoch(&epoch_number),
-        )?;
-
-        let height = invalid_params_check(
-            rpc_param_name,
-            self.get_height_from_epoch_number(epoch_number),
-        )?;
-        let epoch_id = if let Ok(v) =
-            self.inner.read_recursive().block_hashes_by_epoch(height)
-        {
-            v.last().expect("pivot block always exist").clone()
-        } else {
-            bail!("cannot get block hashes in the specified epoch, maybe it does not exist?");
-        };
         let state_db =
-            self.get_state_db_by_height_and_hash(height, &epoch_id)?;
+            self.get_state_db_by_epoch_number(epoch_number, rpc_param_name)?;
 
         Ok(State::new(state_db)?)
     }
@@ -1517,21 +2729,15 @@ impl ConsensusGraphTrait for ConsensusGraph {
     fn get_state_db_by_epoch_number(
         &self, epoch_number: EpochNumber, rpc_param_name: &str,
     ) -> RpcResult<StateDb> {
-        invalid_params_check(
-            rpc_param_name,
-            self.validate_stated_epoch(&epoch_number),
-        )?;
-        let height = invalid_params_check(
+        let epoch = invalid_params_check(
             rpc_param_name,
-            self.get_height_from_epoch_number(epoch_number),
+            self.resolve_stated_epoch(&epoch_number),
         )?;
-        let hash =
-            self.inner.read().get_pivot_hash_from_epoch_number(height)?;
-        self.get_state_db_by_height_and_hash(height, &hash)
+        self.get_state_db_by_height_and_hash(epoch.height, &epoch.pivot_hash)
     }
 
     /// Return the blocks without bodies in the subtree of stable genesis and
-    /// the blocks in the `REWARD_EPOCH_COUNT` epochs before it. Block
+    /// the blocks in the reward window before it. Block
     /// bodies of other blocks in the consensus graph will never be needed
     /// for executions after this stable genesis, as long as the checkpoint
     /// is not reverted.
@@ -1544,7 +2750,7 @@ impl ConsensusGraphTrait for ConsensusGraph {
             .get_subtree(&stable_genesis)
             .expect("stable is in consensus")
         {
-            if self.data_man.block_by_hash(&block_hash, false).is_none() {
+            if !self.data_man.block_body_available(&block_hash) {
                 missing_body_blocks.insert(block_hash);
             }
         }
@@ -1554,8 +2760,10 @@ impl ConsensusGraphTrait for ConsensusGraph {
             .data_man
             .block_height_by_hash(&stable_genesis)
             .expect("stable exist");
-        let reward_start_epoch = if stable_height >= REWARD_EPOCH_COUNT {
-            stable_height - REWARD_EPOCH_COUNT + 1
+        let reward_epoch_count =
+            self.txpool.machine().params().reward_epoch_count;
+        let reward_start_epoch = if stable_height >= reward_epoch_count {
+            stable_height - reward_epoch_count + 1
         } else {
             1
         };
@@ -1565,7 +2773,7 @@ impl ConsensusGraphTrait for ConsensusGraph {
                 .executed_epoch_set_hashes_from_db(height)
                 .expect("epoch sets before stable should exist")
             {
-                if self.data_man.block_by_hash(&block_hash, false).is_none() {
+                if !self.data_man.block_body_available(&block_hash) {
                     missing_body_blocks.insert(block_hash);
                 }
             }
@@ -1614,14 +2822,19 @@ impl ConsensusGraphTrait for ConsensusGraph {
             self.data_man.get_cur_consensus_era_genesis_hash();
         let cur_era_stable_hash =
             self.data_man.get_cur_consensus_era_stable_hash();
-        let new_consensus_inner = ConsensusGraphInner::with_era_genesis(
+        let mut new_consensus_inner = ConsensusGraphInner::with_era_genesis(
             old_consensus_inner.pow_config.clone(),
             old_consensus_inner.pow.clone(),
             self.data_man.clone(),
             old_consensus_inner.inner_conf.clone(),
+            old_consensus_inner.deferred_state_epoch_count,
+            old_consensus_inner.epoch_executed_block_bound,
             &cur_era_genesis_hash,
             &cur_era_stable_hash,
+            self.clock.clone(),
         );
+        new_consensus_inner.epoch_fences =
+            old_consensus_inner.epoch_fences.clone();
         *old_consensus_inner = new_consensus_inner;
         debug!("Build new consensus graph for sync-recovery with identified genesis {} stable block {}", cur_era_genesis_hash, cur_era_stable_hash);
 
@@ -53,6 +53,10 @@ pub enum BlockError {
     DuplicateParentOrRefereeHashes(H256),
     /// The value in `custom` does not match the specification.
     InvalidCustom(Vec<Bytes>, Vec<Bytes>),
+    /// The headers of the parent or referees needed for verification are not
+    /// available yet. The block is not invalid and can be verified again
+    /// after they are received.
+    MissingAncestorHeaders(Vec<H256>),
 }
 
 impl fmt::Display for BlockError {
@@ -114,6 +118,9 @@ impl fmt::Display for BlockError {
                     expected_custom_prefix, header_custom
                 )
             }
+            MissingAncestorHeaders(ref hashes) => {
+                format!("Missing parent or referee headers: {:?}", hashes)
+            }
         };
 
         f.write_fmt(format_args!("Block error ({})", msg))
@@ -125,6 +125,20 @@ impl ExecutionOutcome {
             _ => None,
         }
     }
+
+    /// The reason decoded from the output of a reverted transaction. Return
+    /// `None` if the transaction is not reverted or reverted without a
+    /// recognized reason.
+    pub fn revert_reason(&self) -> Option<String> {
+        match self {
+            ExecutionOutcome::ExecutionErrorBumpNonce(
+                ExecutionError::VmError(vm::Error::Reverted),
+                executed,
+            ) => Some(revert_reason_decode(&executed.output))
+                .filter(|reason| !reason.is_empty()),
+            _ => None,
+        }
+    }
 }
 
 impl Executed {
@@ -170,16 +184,25 @@ impl Executed {
     }
 }
 
+/// The selector of `Error(string)`, used by `revert(reason)` and
+/// `require(condition, reason)`.
+const ERROR_SELECTOR: [u8; 4] = [8, 195, 121, 160];
+/// The selector of `Panic(uint256)`, used by the failed assertions and the
+/// arithmetic errors since Solidity 0.8.
+const PANIC_SELECTOR: [u8; 4] = [78, 72, 123, 113];
+
 pub fn revert_reason_decode(output: &Bytes) -> String {
     const MAX_LENGTH: usize = 50;
     let decode_result = if output.len() < 4 {
         Err(ABIDecodeError("Uncompleted Signature"))
     } else {
         let (sig, data) = output.split_at(4);
-        if sig != [8, 195, 121, 160] {
-            Err(ABIDecodeError("Unrecognized Signature"))
-        } else {
+        if sig == ERROR_SELECTOR {
             String::abi_decode(data)
+        } else if sig == PANIC_SELECTOR {
+            U256::abi_decode(data).map(|code| format!("Panic(0x{:x})", code))
+        } else {
+            Err(ABIDecodeError("Unrecognized Signature"))
         }
     };
     match decode_result {
@@ -194,6 +217,11 @@ pub fn revert_reason_decode(output: &Bytes) -> String {
     }
 }
 
+/// The execution error message of a reverted transaction in the receipts.
+pub fn reverted_error_message(output: &Bytes) -> String {
+    format!("Vm reverted, {}", revert_reason_decode(output))
+}
+
 use crate::trace::trace::ExecTrace;
 #[cfg(test)]
 use rustc_hex::FromHex;
@@ -210,3 +238,64 @@ fn test_decode_result() {
         revert_reason_decode(&input_hex.from_hex().unwrap())
     );
 }
+
+#[test]
+fn test_decode_panic_result() {
+    let input_hex =
+        "4e487b71\
+         0000000000000000000000000000000000000000000000000000000000000011";
+    assert_eq!(
+        "Panic(0x11)".to_string(),
+        revert_reason_decode(&input_hex.from_hex().unwrap())
+    );
+}
+
+#[test]
+fn test_execution_outcome_revert_reason() {
+    let input_hex =
+        "08c379a0\
+         0000000000000000000000000000000000000000000000000000000000000020\
+         0000000000000000000000000000000000000000000000000000000000000004\
+         6f6f707300000000000000000000000000000000000000000000000000000000";
+    let executed = |output: Bytes| Executed {
+        gas_used: U256::zero(),
+        fee: U256::zero(),
+        gas_charged: U256::zero(),
+        gas_sponsor_paid: false,
+        logs: vec![],
+        storage_sponsor_paid: false,
+        storage_collateralized: vec![],
+        storage_released: vec![],
+        contracts_created: vec![],
+        output,
+        trace: vec![],
+    };
+    let reverted = |output: Bytes| {
+        ExecutionOutcome::ExecutionErrorBumpNonce(
+            ExecutionError::VmError(vm::Error::Reverted),
+            executed(output),
+        )
+    };
+    assert_eq!(
+        reverted(input_hex.from_hex().unwrap()).revert_reason(),
+        Some("oops".to_string())
+    );
+    assert_eq!(
+        reverted_error_message(&input_hex.from_hex().unwrap()),
+        "Vm reverted, oops"
+    );
+    assert_eq!(reverted(vec![]).revert_reason(), None);
+    assert_eq!(
+        ExecutionOutcome::ExecutionErrorBumpNonce(
+            ExecutionError::VmError(vm::Error::OutOfGas),
+            executed(input_hex.from_hex().unwrap()),
+        )
+        .revert_reason(),
+        None
+    );
+    assert_eq!(
+        ExecutionOutcome::Finished(executed(input_hex.from_hex().unwrap()))
+            .revert_reason(),
+        None
+    );
+}
@@ -8,6 +8,7 @@ mod executive;
 #[cfg(test)]
 mod executive_tests;
 mod internal_contract;
+mod system_transaction;
 mod vm_exec;
 
 trait CollateralCheckResultToVmResult {
@@ -38,6 +39,10 @@ pub use self::{
         function, suicide, ABIDecodeError, InternalContractMap,
         InternalContractTrait, InternalRefContext, SolidityFunctionTrait,
     },
+    system_transaction::{
+        SystemTransactionCall, SystemTransactionPhase,
+        SystemTransactionProvider,
+    },
 };
 use crate::vm::Error as vmError;
 use cfx_state::CollateralCheckResult;
@@ -0,0 +1,64 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::bytes::Bytes;
+use cfx_types::{Address, U256};
+use primitives::{Action, SignedTransaction, Transaction};
+
+/// The point in an epoch where system transactions are executed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SystemTransactionPhase {
+    /// Before the transactions of the first block in the epoch, after the
+    /// block number is bumped.
+    BeforeUserTransactions,
+    /// After the transactions of the pivot block, before the rewards of the
+    /// epoch are given out.
+    AfterUserTransactions,
+}
+
+/// A message call or contract creation executed on behalf of the protocol.
+///
+/// The executor fills in the nonce from the state of `sender`, so the calls
+/// do not need to know the nonce, and the gas price is zero.
+#[derive(Clone, Debug)]
+pub struct SystemTransactionCall {
+    pub sender: Address,
+    pub action: Action,
+    pub value: U256,
+    /// It must cover the base gas of the call, otherwise the call is not
+    /// executed.
+    pub gas: U256,
+    pub storage_limit: u64,
+    pub data: Bytes,
+}
+
+impl SystemTransactionCall {
+    pub fn into_transaction(
+        self, nonce: U256, epoch_height: u64, chain_id: u32,
+    ) -> SignedTransaction {
+        Transaction {
+            nonce,
+            gas_price: U256::zero(),
+            gas: self.gas,
+            action: self.action,
+            value: self.value,
+            storage_limit: self.storage_limit,
+            epoch_height,
+            chain_id,
+            data: self.data,
+        }
+        .fake_sign(self.sender)
+    }
+}
+
+/// Provides the system transactions to execute at the boundaries of each
+/// epoch.
+///
+/// The result must only depend on the arguments, because every node executing
+/// the epoch needs to inject the same transactions to reach the same state.
+pub trait SystemTransactionProvider: Send + Sync {
+    fn system_transactions(
+        &self, phase: SystemTransactionPhase, epoch_height: u64,
+    ) -> Vec<SystemTransactionCall>;
+}
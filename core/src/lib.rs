@@ -29,6 +29,8 @@ pub mod cache_config;
 pub mod cache_manager;
 pub mod channel;
 pub mod client;
+pub mod clock;
+pub mod config_cell;
 pub mod consensus;
 pub mod db;
 pub mod error;
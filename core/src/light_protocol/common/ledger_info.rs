@@ -7,7 +7,6 @@ use crate::{
     light_protocol::{message::WitnessInfoWithHeight, Error, ErrorKind},
 };
 use cfx_internal_common::StateRootWithAuxInfo;
-use cfx_parameters::consensus::DEFERRED_STATE_EPOCH_COUNT;
 use cfx_statedb::{StateDb, StateDbGetOriginalMethods};
 use cfx_storage::{
     state::{State, StateTrait},
@@ -89,7 +88,10 @@ impl LedgerInfo {
     fn correct_deferred_state_root_hash_of(
         &self, height: u64,
     ) -> Result<H256, Error> {
-        let epoch = height.saturating_sub(DEFERRED_STATE_EPOCH_COUNT);
+        let epoch = self
+            .consensus
+            .deferred_state_epoch_count()
+            .state_height(height);
         let pivot = self.pivot_hash_of(epoch)?;
 
         let commitments = self
@@ -115,7 +117,10 @@ impl LedgerInfo {
     fn correct_deferred_receipts_root_hash_of(
         &self, height: u64,
     ) -> Result<H256, Error> {
-        let epoch = height.saturating_sub(DEFERRED_STATE_EPOCH_COUNT);
+        let epoch = self
+            .consensus
+            .deferred_state_epoch_count()
+            .state_height(height);
         let pivot = self.pivot_hash_of(epoch)?;
 
         let commitments = self
@@ -138,7 +143,10 @@ impl LedgerInfo {
     fn correct_deferred_logs_root_hash_of(
         &self, height: u64,
     ) -> Result<H256, Error> {
-        let epoch = height.saturating_sub(DEFERRED_STATE_EPOCH_COUNT);
+        let epoch = self
+            .consensus
+            .deferred_state_epoch_count()
+            .state_height(height);
         let pivot = self.pivot_hash_of(epoch)?;
 
         let commitments = self
@@ -84,6 +84,11 @@ error_chain! {
             display("Receipts root validation for epoch {} failed, expected={:?}, received={:?}", epoch, expected, received),
         }
 
+        InvalidReceiptsProof{ epoch: u64, hash: H256 } {
+            description("Receipts proof validation failed"),
+            display("Receipts proof validation for block {:?} in epoch {} failed", hash, epoch),
+        }
+
         InvalidStateProof{ epoch: u64, key: Vec<u8>, value: Option<Vec<u8>>, reason: &'static str } {
             description("Invalid state proof"),
             display("Invalid state proof for key {:?} and value {:?} in epoch {}: {:?}", value, key, epoch, reason),
@@ -235,6 +240,7 @@ pub fn handle(
         | ErrorKind::InvalidMessageFormat
         | ErrorKind::InvalidPreviousStateRoot{..}
         | ErrorKind::InvalidReceipts{..}
+        | ErrorKind::InvalidReceiptsProof{..}
         | ErrorKind::InvalidStateProof{..}
         | ErrorKind::InvalidStateRoot{..}
         | ErrorKind::InvalidStorageRootProof{..}
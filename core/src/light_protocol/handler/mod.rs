@@ -16,7 +16,8 @@ use crate::{
             BlockHeaders as GetBlockHeadersResponse,
             BlockTxs as GetBlockTxsResponse, Blooms as GetBloomsResponse,
             NewBlockHashes, NodeType, Receipts as GetReceiptsResponse,
-            SendRawTx, StateEntries as GetStateEntriesResponse,
+            ReceiptsWithProof as GetReceiptsWithProofResponse, SendRawTx,
+            StateEntries as GetStateEntriesResponse,
             StateRoots as GetStateRootsResponse, StatusPingDeprecatedV1,
             StatusPingV2, StatusPongDeprecatedV1, StatusPongV2,
             StorageRoots as GetStorageRootsResponse,
@@ -395,6 +396,7 @@ impl Handler {
             msgid::BLOOMS => self.on_blooms(io, peer, decode_rlp_and_check_deprecation(&rlp, min_supported_ver, protocol)?),
             msgid::NEW_BLOCK_HASHES => self.on_new_block_hashes(io, peer, decode_rlp_and_check_deprecation(&rlp, min_supported_ver, protocol)?),
             msgid::RECEIPTS => self.on_receipts(io, peer, decode_rlp_and_check_deprecation(&rlp, min_supported_ver, protocol)?),
+            msgid::RECEIPTS_WITH_PROOF => self.on_receipts_with_proof(io, peer, decode_rlp_and_check_deprecation(&rlp, min_supported_ver, protocol)?),
             msgid::STATE_ENTRIES => self.on_state_entries(io, peer, decode_rlp_and_check_deprecation(&rlp, min_supported_ver, protocol)?),
             msgid::STATE_ROOTS => self.on_state_roots(io, peer, decode_rlp_and_check_deprecation(&rlp, min_supported_ver, protocol)?),
             msgid::STORAGE_ROOTS => self.on_storage_roots(io, peer, decode_rlp_and_check_deprecation(&rlp, min_supported_ver, protocol)?),
@@ -726,6 +728,28 @@ impl Handler {
         Ok(())
     }
 
+    fn on_receipts_with_proof(
+        &self, io: &dyn NetworkContext, peer: &NodeId,
+        resp: GetReceiptsWithProofResponse,
+    ) -> Result<()>
+    {
+        debug!(
+            "received {} receipts with proof (request id = {})",
+            resp.receipts.len(),
+            resp.request_id
+        );
+        trace!("on_receipts_with_proof resp={:?}", resp);
+
+        self.receipts.receive_with_proof(
+            peer,
+            resp.request_id,
+            resp.receipts.into_iter(),
+        )?;
+
+        self.receipts.sync(io);
+        Ok(())
+    }
+
     fn on_state_entries(
         &self, io: &dyn NetworkContext, peer: &NodeId,
         resp: GetStateEntriesResponse,
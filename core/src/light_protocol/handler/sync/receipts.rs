@@ -12,10 +12,17 @@ use crate::{
     light_protocol::{
         common::{FullPeerState, Peers},
         error::*,
-        message::{msgid, GetReceipts, ReceiptsWithEpoch},
+        message::{
+            msgid, GetReceipts, GetReceiptsWithProof, ReceiptsWithEpoch,
+        },
+        LIGHT_PROTO_V3,
     },
     message::{Message, RequestId},
-    verification::compute_receipts_root,
+    verification::{
+        compute_block_receipts_root, compute_receipts_root,
+        compute_receipts_root_from_block_roots, is_valid_block_receipts_proof,
+        EpochReceiptsWithProof,
+    },
     UniqueId,
 };
 use cfx_parameters::light::{
@@ -42,6 +49,9 @@ type MissingReceipts = KeyOrdered<u64>;
 type PendingReceipts = PendingItem<Vec<BlockReceipts>, ClonableError>;
 
 pub struct Receipts {
+    // full peers, to pick the request message by their protocol version
+    peers: Arc<Peers<FullPeerState>>,
+
     // series of unique request ids
     request_id_allocator: Arc<UniqueId>,
 
@@ -61,12 +71,14 @@ impl Receipts {
         witnesses: Arc<Witnesses>,
     ) -> Self
     {
-        let sync_manager = SyncManager::new(peers.clone(), msgid::GET_RECEIPTS);
+        let sync_manager =
+            SyncManager::new(peers.clone(), msgid::GET_RECEIPTS_WITH_PROOF);
 
         let cache = LruCache::with_expiry_duration(*CACHE_TIMEOUT);
         let verified = Arc::new(RwLock::new(cache));
 
         Receipts {
+            peers,
             request_id_allocator,
             sync_manager,
             verified,
@@ -136,24 +148,76 @@ impl Receipts {
         Ok(())
     }
 
+    #[inline]
+    pub fn receive_with_proof(
+        &self, peer: &NodeId, id: RequestId,
+        receipts: impl Iterator<Item = EpochReceiptsWithProof>,
+    ) -> Result<()>
+    {
+        for epoch_receipts in receipts {
+            trace!(
+                "Validating receipts {:?} with epoch {}",
+                epoch_receipts.blocks,
+                epoch_receipts.epoch
+            );
+
+            match self.sync_manager.check_if_requested(
+                peer,
+                id,
+                &epoch_receipts.epoch,
+            )? {
+                None => continue,
+                Some(_) => {
+                    self.validate_and_store_with_proof(epoch_receipts)?
+                }
+            };
+        }
+
+        Ok(())
+    }
+
     #[inline]
     pub fn validate_and_store(
         &self, epoch: u64, receipts: Vec<BlockReceipts>,
     ) -> Result<()> {
-        // validate receipts
-        if let Err(e) = self.validate_receipts(epoch, &receipts) {
-            // forward error to both rpc caller(s) and sync handler
-            // so we need to make it clonable
-            let e = ClonableError::from(e);
-
-            self.verified
-                .write()
-                .entry(epoch)
-                .or_insert(PendingItem::pending())
-                .set_error(e.clone());
-
-            bail!(e);
-        }
+        let validated = self.validate_receipts(epoch, &receipts);
+        self.store(epoch, validated.map(|_| receipts))
+    }
+
+    #[inline]
+    pub fn validate_and_store_with_proof(
+        &self, epoch_receipts: EpochReceiptsWithProof,
+    ) -> Result<()> {
+        let epoch = epoch_receipts.epoch;
+        let validated = self.validate_receipts_with_proof(&epoch_receipts);
+        let receipts = epoch_receipts
+            .blocks
+            .into_iter()
+            .map(|block| block.block_receipts)
+            .collect();
+        self.store(epoch, validated.map(|_| receipts))
+    }
+
+    #[inline]
+    fn store(
+        &self, epoch: u64, validated: Result<Vec<BlockReceipts>>,
+    ) -> Result<()> {
+        let receipts = match validated {
+            Ok(receipts) => receipts,
+            Err(e) => {
+                // forward error to both rpc caller(s) and sync handler
+                // so we need to make it clonable
+                let e = ClonableError::from(e);
+
+                self.verified
+                    .write()
+                    .entry(epoch)
+                    .or_insert(PendingItem::pending())
+                    .set_error(e.clone());
+
+                bail!(e);
+            }
+        };
 
         // store receipts by epoch
         self.verified
@@ -188,15 +252,32 @@ impl Receipts {
 
         let request_id = self.request_id_allocator.next();
 
-        trace!(
-            "send_request GetReceipts peer={:?} id={:?} epochs={:?}",
-            peer,
-            request_id,
-            epochs
-        );
+        let peer_version = match self.peers.get(peer) {
+            Some(state) => state.read().protocol_version,
+            None => bail!(ErrorKind::InternalError(format!(
+                "Sending request to unknown peer={:?}",
+                peer
+            ))),
+        };
 
-        let msg: Box<dyn Message> =
-            Box::new(GetReceipts { request_id, epochs });
+        // the peers before V3 do not serve the receipts with proofs
+        let msg: Box<dyn Message> = if peer_version >= LIGHT_PROTO_V3 {
+            trace!(
+                "send_request GetReceiptsWithProof peer={:?} id={:?} epochs={:?}",
+                peer,
+                request_id,
+                epochs
+            );
+            Box::new(GetReceiptsWithProof { request_id, epochs })
+        } else {
+            trace!(
+                "send_request GetReceipts peer={:?} id={:?} epochs={:?}",
+                peer,
+                request_id,
+                epochs
+            );
+            Box::new(GetReceipts { request_id, epochs })
+        };
 
         msg.send(io, peer)?;
         Ok(Some(request_id))
@@ -240,4 +321,51 @@ impl Receipts {
 
         Ok(())
     }
+
+    #[inline]
+    fn validate_receipts_with_proof(
+        &self, epoch_receipts: &EpochReceiptsWithProof,
+    ) -> Result<()> {
+        let epoch = epoch_receipts.epoch;
+        let num_blocks = epoch_receipts.blocks.len();
+
+        // retrieve local receipts root
+        let expected = self.witnesses.root_hashes_of(epoch)?.receipts_root_hash;
+
+        let mut block_receipts_roots = Vec::with_capacity(num_blocks);
+        for (index, block) in epoch_receipts.blocks.iter().enumerate() {
+            let block_receipts_root =
+                compute_block_receipts_root(&block.block_receipts.receipts);
+
+            if !is_valid_block_receipts_proof(
+                expected,
+                index,
+                num_blocks,
+                block_receipts_root,
+                &block.block_index_proof,
+            ) {
+                bail!(ErrorKind::InvalidReceiptsProof {
+                    epoch,
+                    hash: block.block_hash,
+                });
+            }
+
+            block_receipts_roots.push(block_receipts_root);
+        }
+
+        // the proofs do not tell if blocks are missing at the end of the
+        // epoch, so we also check the root of all the blocks received
+        let received =
+            compute_receipts_root_from_block_roots(block_receipts_roots);
+
+        if received != expected {
+            bail!(ErrorKind::InvalidReceipts {
+                epoch,
+                expected,
+                received,
+            });
+        }
+
+        Ok(())
+    }
 }
@@ -14,14 +14,12 @@ use crate::{
         message::{msgid, GetWitnessInfo, WitnessInfoWithHeight},
     },
     message::{Message, RequestId},
+    spec::DeferredStateEpochCount,
     UniqueId,
 };
-use cfx_parameters::{
-    consensus::DEFERRED_STATE_EPOCH_COUNT,
-    light::{
-        MAX_WITNESSES_IN_FLIGHT, WITNESS_REQUEST_BATCH_SIZE,
-        WITNESS_REQUEST_TIMEOUT,
-    },
+use cfx_parameters::light::{
+    MAX_WITNESSES_IN_FLIGHT, WITNESS_REQUEST_BATCH_SIZE,
+    WITNESS_REQUEST_TIMEOUT,
 };
 use cfx_types::H256;
 use network::{node_table::NodeId, NetworkContext};
@@ -59,6 +57,9 @@ pub struct Witnesses {
     // block data manager
     data_man: Arc<BlockDataManager>,
 
+    // the deferred state epoch count of the chain
+    deferred_state_epoch_count: DeferredStateEpochCount,
+
     // height of the latest header for which we have trusted information
     pub height_of_latest_verified_header: RwLock<u64>,
 
@@ -85,6 +86,7 @@ impl Witnesses {
     ) -> Self
     {
         let data_man = consensus.get_data_manager().clone();
+        let deferred_state_epoch_count = consensus.deferred_state_epoch_count();
         let height_of_latest_verified_header = RwLock::new(0);
         let in_flight = RwLock::new(HashSet::new());
         let ledger = LedgerInfo::new(consensus.clone());
@@ -93,6 +95,7 @@ impl Witnesses {
 
         Witnesses {
             data_man,
+            deferred_state_epoch_count,
             height_of_latest_verified_header,
             in_flight,
             ledger,
@@ -120,7 +123,9 @@ impl Witnesses {
     /// Get root hashes for `epoch` from local cache.
     #[inline]
     pub fn root_hashes_of(&self, epoch: u64) -> Result<VerifiedRoots> {
-        let height = epoch + DEFERRED_STATE_EPOCH_COUNT;
+        let height = self
+            .deferred_state_epoch_count
+            .first_committing_height(epoch);
 
         if height > *self.height_of_latest_verified_header.read() {
             bail!(ErrorKind::WitnessUnavailable { epoch });
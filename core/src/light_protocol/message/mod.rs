@@ -10,11 +10,11 @@ pub use message::msgid;
 pub use protocol::{
     BlockHashes, BlockHeaders, BlockTxs, BlockTxsWithHash, BloomWithEpoch,
     Blooms, GetBlockHashesByEpoch, GetBlockHeaders, GetBlockTxs, GetBlooms,
-    GetReceipts, GetStateEntries, GetStateRoots, GetStorageRoots, GetTxInfos,
-    GetTxs, GetWitnessInfo, NewBlockHashes, Receipts, ReceiptsWithEpoch,
-    SendRawTx, StateEntries, StateEntryProof, StateEntryWithKey, StateKey,
-    StateRootWithEpoch, StateRoots, StatusPingDeprecatedV1, StatusPingV2,
-    StatusPongDeprecatedV1, StatusPongV2, StorageRootKey, StorageRootProof,
-    StorageRootWithKey, StorageRoots, TxInfo, TxInfos, Txs, WitnessInfo,
-    WitnessInfoWithHeight,
+    GetReceipts, GetReceiptsWithProof, GetStateEntries, GetStateRoots,
+    GetStorageRoots, GetTxInfos, GetTxs, GetWitnessInfo, NewBlockHashes,
+    Receipts, ReceiptsWithEpoch, ReceiptsWithProof, SendRawTx, StateEntries,
+    StateEntryProof, StateEntryWithKey, StateKey, StateRootWithEpoch,
+    StateRoots, StatusPingDeprecatedV1, StatusPingV2, StatusPongDeprecatedV1,
+    StatusPongV2, StorageRootKey, StorageRootProof, StorageRootWithKey,
+    StorageRoots, TxInfo, TxInfos, Txs, WitnessInfo, WitnessInfoWithHeight,
 };
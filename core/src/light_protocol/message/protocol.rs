@@ -6,7 +6,7 @@ use cfx_types::{Bloom, H160, H256};
 use rlp_derive::{RlpDecodable, RlpEncodable};
 
 use super::NodeType;
-use crate::message::RequestId;
+use crate::{message::RequestId, verification::EpochReceiptsWithProof};
 use cfx_internal_common::ChainIdParamsDeprecated;
 use cfx_storage::{NodeMerkleProof, StateProof, TrieProof};
 use primitives::{
@@ -98,6 +98,20 @@ pub struct Receipts {
     pub receipts: Vec<ReceiptsWithEpoch>,
 }
 
+#[derive(Clone, Debug, Default, RlpEncodable, RlpDecodable)]
+pub struct GetReceiptsWithProof {
+    pub request_id: RequestId,
+    pub epochs: Vec<u64>,
+}
+
+// the receipts of each block are verified against the receipts root in
+// the witness info retrieved previously using the proof
+#[derive(Clone, Debug, RlpEncodable, RlpDecodable)]
+pub struct ReceiptsWithProof {
+    pub request_id: RequestId,
+    pub receipts: Vec<EpochReceiptsWithProof>,
+}
+
 #[derive(Clone, Debug, Default, RlpEncodable, RlpDecodable)]
 pub struct GetTxs {
     pub request_id: RequestId,
@@ -4,7 +4,8 @@
 
 use crate::{
     consensus::{
-        MaybeExecutedTxExtraInfo, SharedConsensusGraph, TransactionInfo,
+        ConsensusGraph, MaybeExecutedTxExtraInfo, SharedConsensusGraph,
+        TransactionInfo,
     },
     light_protocol::{
         common::{
@@ -19,9 +20,11 @@ use crate::{
             BlockTxs as GetBlockTxsResponse, BlockTxsWithHash, BloomWithEpoch,
             Blooms as GetBloomsResponse, GetBlockHashesByEpoch,
             GetBlockHeaders, GetBlockTxs, GetBlooms, GetReceipts,
-            GetStateEntries, GetStateRoots, GetStorageRoots, GetTxInfos,
-            GetTxs, GetWitnessInfo, NewBlockHashes, NodeType,
-            Receipts as GetReceiptsResponse, ReceiptsWithEpoch, SendRawTx,
+            GetReceiptsWithProof, GetStateEntries, GetStateRoots,
+            GetStorageRoots, GetTxInfos, GetTxs, GetWitnessInfo,
+            NewBlockHashes, NodeType, Receipts as GetReceiptsResponse,
+            ReceiptsWithEpoch,
+            ReceiptsWithProof as GetReceiptsWithProofResponse, SendRawTx,
             StateEntries as GetStateEntriesResponse, StateEntryProof,
             StateEntryWithKey, StateKey, StateRootWithEpoch,
             StateRoots as GetStateRootsResponse, StatusPingDeprecatedV1,
@@ -193,6 +196,7 @@ impl Provider {
             msgid::GET_BLOCK_TXS => self.on_get_block_txs(io, peer, decode_rlp_and_check_deprecation(&rlp, min_supported_ver, protocol)?),
             msgid::GET_TX_INFOS => self.on_get_tx_infos(io, peer, decode_rlp_and_check_deprecation(&rlp, min_supported_ver, protocol)?),
             msgid::GET_STORAGE_ROOTS => self.on_get_storage_roots(io, peer, decode_rlp_and_check_deprecation(&rlp, min_supported_ver, protocol)?),
+            msgid::GET_RECEIPTS_WITH_PROOF => self.on_get_receipts_with_proof(io, peer, decode_rlp_and_check_deprecation(&rlp, min_supported_ver, protocol)?),
             _ => bail!(ErrorKind::UnknownMessage{id: msg_id}),
         }
     }
@@ -675,6 +679,45 @@ impl Provider {
         Ok(())
     }
 
+    fn on_get_receipts_with_proof(
+        &self, io: &dyn NetworkContext, peer: &NodeId,
+        req: GetReceiptsWithProof,
+    ) -> Result<()>
+    {
+        debug!("on_get_receipts_with_proof req={:?}", req);
+        self.throttle(peer, &req)?;
+        let request_id = req.request_id;
+
+        let consensus = self
+            .consensus
+            .as_any()
+            .downcast_ref::<ConsensusGraph>()
+            .expect("downcast should succeed");
+
+        let it = req
+            .epochs
+            .into_iter()
+            .take(MAX_ITEMS_TO_SEND)
+            .map(|epoch| consensus.get_epoch_receipts_with_proof(epoch));
+
+        let (receipts, errors) = partition_results(it);
+
+        if !errors.is_empty() {
+            debug!(
+                "Errors while serving GetReceiptsWithProof request: {:?}",
+                errors
+            );
+        }
+
+        let msg: Box<dyn Message> = Box::new(GetReceiptsWithProofResponse {
+            request_id,
+            receipts,
+        });
+
+        msg.send(io, peer)?;
+        Ok(())
+    }
+
     fn on_get_txs(
         &self, io: &dyn NetworkContext, peer: &NodeId, req: GetTxs,
     ) -> Result<()> {
@@ -18,7 +18,6 @@ use crate::{
 };
 use cfx_addr::Network;
 use cfx_parameters::{
-    consensus::DEFERRED_STATE_EPOCH_COUNT,
     internal_contract_addresses::{
         SPONSOR_WHITELIST_CONTROL_CONTRACT_ADDRESS,
         STORAGE_INTEREST_STAKING_CONTRACT_ADDRESS,
@@ -774,9 +773,11 @@ impl QueryService {
         // find highest epoch that we are able to verify based on witness info
         let latest_verified = self.handler.witnesses.latest_verified();
 
+        let deferred_state_epoch_count =
+            self.consensus.deferred_state_epoch_count();
         let latest_verifiable = match latest_verified {
-            n if n >= DEFERRED_STATE_EPOCH_COUNT => {
-                n - DEFERRED_STATE_EPOCH_COUNT
+            n if n >= deferred_state_epoch_count.at(n) => {
+                deferred_state_epoch_count.state_height(n)
             }
             _ => {
                 return Err(FilterError::UnableToVerify {
@@ -5,7 +5,7 @@
 use super::builtin::Builtin;
 use crate::{
     builtin::{builtin_factory, AltBn128PairingPricer, Linear, ModexpPricer},
-    executive::InternalContractMap,
+    executive::{InternalContractMap, SystemTransactionProvider},
     spec::CommonParams,
     vm::Spec,
     vm_factory::VmFactory,
@@ -22,6 +22,7 @@ pub struct Machine {
     builtins: Arc<BTreeMap<Address, Builtin>>,
     internal_contracts: Arc<InternalContractMap>,
     spec_rules: Option<Box<SpecCreationRules>>,
+    system_transaction_provider: Option<Box<dyn SystemTransactionProvider>>,
 }
 
 impl Machine {
@@ -42,6 +43,19 @@ impl Machine {
         self.spec_rules = Some(rules);
     }
 
+    /// Inject system transactions at the boundaries of each epoch.
+    pub fn set_system_transaction_provider(
+        &mut self, provider: Box<dyn SystemTransactionProvider>,
+    ) {
+        self.system_transaction_provider = Some(provider);
+    }
+
+    pub fn system_transaction_provider(
+        &self,
+    ) -> Option<&dyn SystemTransactionProvider> {
+        self.system_transaction_provider.as_deref()
+    }
+
     /// Get the general parameters of the chain.
     pub fn params(&self) -> &CommonParams { &self.params }
 
@@ -76,6 +90,7 @@ pub fn new_machine(params: CommonParams, vm: VmFactory) -> Machine {
         builtins: Arc::new(BTreeMap::new()),
         internal_contracts: Arc::new(InternalContractMap::default()),
         spec_rules: None,
+        system_transaction_provider: None,
     }
 }
 
@@ -159,5 +174,6 @@ pub fn new_machine_with_builtin(
         builtins: Arc::new(builtin),
         internal_contracts: Arc::new(internal_contracts),
         spec_rules: None,
+        system_transaction_provider: None,
     }
 }
@@ -22,11 +22,11 @@ use cfx_parameters::{
 use cfx_state::{state_trait::*, CleanupMode};
 use cfx_statedb::{Result as DbResult, StateDb};
 use cfx_storage::{StorageManager, StorageManagerTrait};
-use cfx_types::{address_util::AddressUtil, Address, U256};
+use cfx_types::{address_util::AddressUtil, Address, H256, U256};
 use keylib::KeyPair;
 use primitives::{
-    storage::STORAGE_LAYOUT_REGULAR_V0, Action, Block, BlockHeaderBuilder,
-    BlockReceipts, SignedTransaction, Transaction,
+    storage::STORAGE_LAYOUT_REGULAR_V0, Action, Block, BlockHeader,
+    BlockHeaderBuilder, BlockReceipts, SignedTransaction, Transaction,
 };
 use rustc_hex::FromHex;
 use secret_store::SecretStore;
@@ -153,6 +153,38 @@ pub fn genesis_contract_address_two_year() -> Address {
     )
 }
 
+/// The commitments of the genesis block given in the configuration. Each one
+/// that is set must match the genesis block computed from the genesis spec.
+#[derive(Clone, Debug, Default)]
+pub struct GenesisCommitments {
+    pub state_root: Option<H256>,
+    pub receipts_root: Option<H256>,
+}
+
+impl GenesisCommitments {
+    pub fn check(&self, genesis: &BlockHeader) -> Result<(), String> {
+        if let Some(state_root) = &self.state_root {
+            if genesis.deferred_state_root() != state_root {
+                return Err(format!(
+                    "Genesis state root {:?} mismatches the configured {:?}",
+                    genesis.deferred_state_root(),
+                    state_root
+                ));
+            }
+        }
+        if let Some(receipts_root) = &self.receipts_root {
+            if genesis.deferred_receipts_root() != receipts_root {
+                return Err(format!(
+                    "Genesis receipts root {:?} mismatches the configured {:?}",
+                    genesis.deferred_receipts_root(),
+                    receipts_root
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
 /// ` test_net_version` is used to update the genesis author so that after
 /// resetting, the chain of the older version will be discarded
 pub fn genesis_block(
@@ -401,6 +433,7 @@ pub fn genesis_block(
         block_number: 0,
         secondary_reward: U256::zero(),
         tx_execution_error_messages: vec![],
+        tx_execution_error_infos: vec![],
     })]);
 
     let mut genesis = Block::new(
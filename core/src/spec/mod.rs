@@ -4,4 +4,6 @@
 
 pub mod genesis;
 mod spec;
-pub use self::spec::{CommonParams, TransitionsEpochHeight};
+pub use self::spec::{
+    CommonParams, DeferredStateEpochCount, TransitionsEpochHeight,
+};
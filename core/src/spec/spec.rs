@@ -5,14 +5,19 @@
 use crate::{message::Bytes, vm};
 use cfx_internal_common::ChainIdParams;
 use cfx_parameters::{
-    consensus::{ONE_UCFX_IN_DRIP, TANZANITE_HEADER_CUSTOM_FIRST_ELEMENT},
+    consensus::{
+        DEFERRED_STATE_EPOCH_COUNT, ONE_UCFX_IN_DRIP,
+        TANZANITE_HEADER_CUSTOM_FIRST_ELEMENT,
+    },
     consensus_internal::{
-        ANTICONE_PENALTY_RATIO, INITIAL_BASE_MINING_REWARD_IN_UCFX,
+        ANTICONE_PENALTY_RATIO, ANTICONE_PENALTY_UPPER_EPOCH_COUNT,
+        EPOCH_EXECUTED_BLOCK_BOUND, INITIAL_BASE_MINING_REWARD_IN_UCFX,
+        REWARD_EPOCH_COUNT,
     },
 };
 use cfx_types::{Address, H256, U256, U512};
 use primitives::{block::BlockHeight, BlockNumber};
-use std::collections::BTreeMap;
+use std::{cmp::max, collections::BTreeMap};
 
 struct Spec {
     /// User friendly spec name
@@ -66,6 +71,25 @@ pub struct CommonParams {
     /// Anticone penalty ratio for reward processing.
     /// It should be less than `timer_chain_beta`.
     pub anticone_penalty_ratio: u64,
+    /// The rewards of the blocks in an epoch are computed in the epoch
+    /// `reward_epoch_count` epochs later.
+    pub reward_epoch_count: u64,
+    /// The maximum number of epochs after the reward epoch whose blocks are
+    /// counted in the anticone penalty of a block.
+    pub anticone_penalty_upper_epoch_count: u64,
+    /// The number of epochs between a block and the epoch whose state is
+    /// committed in its header.
+    pub deferred_state_epoch_count: DeferredStateEpochCount,
+    /// The maximum number of blocks executed and rewarded in an epoch. If an
+    /// epoch has more blocks, only the last ones in the topological order
+    /// are kept and the others are skipped. The rewarded blocks are bounded
+    /// at or after `transition_heights.epoch_reward_bound`.
+    pub epoch_executed_block_bound: usize,
+    /// The maximum total gas of the transactions of a sender executed in an
+    /// epoch at or after `transition_heights.sender_gas_budget`. The
+    /// transactions beyond it are skipped without bumping the nonce. `None`
+    /// means no limit.
+    pub max_sender_gas_per_epoch: Option<U256>,
     /// Initial base rewards according to block height.
     pub base_block_rewards: BTreeMap<BlockHeight, U256>,
 
@@ -78,6 +102,59 @@ pub struct CommonParams {
     pub transition_heights: TransitionsEpochHeight,
 }
 
+/// The deferred execution depth. A block at height `h` commits to the state
+/// of the epoch at height `h - self.at(h)`.
+///
+/// The depth may change once at `transition_height`. If the depth decreases,
+/// the states of some epochs around the transition are not committed by any
+/// block. If it increases, some states are committed by more blocks.
+#[derive(Debug, Clone, Copy)]
+pub struct DeferredStateEpochCount {
+    /// The depth for the blocks before `transition_height`.
+    pub initial: u64,
+    /// The depth for the blocks at and after `transition_height`.
+    pub after_transition: u64,
+    pub transition_height: BlockHeight,
+}
+
+impl Default for DeferredStateEpochCount {
+    fn default() -> Self {
+        Self {
+            initial: DEFERRED_STATE_EPOCH_COUNT,
+            after_transition: DEFERRED_STATE_EPOCH_COUNT,
+            transition_height: BlockHeight::MAX,
+        }
+    }
+}
+
+impl DeferredStateEpochCount {
+    /// Return the depth for the block at `height`.
+    pub fn at(&self, height: BlockHeight) -> u64 {
+        if height < self.transition_height {
+            self.initial
+        } else {
+            self.after_transition
+        }
+    }
+
+    /// Return the height of the state committed by the block at `height`.
+    pub fn state_height(&self, height: BlockHeight) -> BlockHeight {
+        height.saturating_sub(self.at(height))
+    }
+
+    /// Return the lowest height of the blocks that commit to the state of
+    /// `epoch` or a later epoch.
+    pub fn first_committing_height(&self, epoch: BlockHeight) -> BlockHeight {
+        if epoch + self.initial < self.transition_height {
+            epoch + self.initial
+        } else {
+            max(self.transition_height, epoch + self.after_transition)
+        }
+    }
+
+    pub fn max(&self) -> u64 { max(self.initial, self.after_transition) }
+}
+
 #[derive(Default, Debug, Clone)]
 pub struct TransitionsBlockNumber {
     /// CIP62: Enable EC-related builtin contract
@@ -103,6 +180,11 @@ pub struct TransitionsEpochHeight {
     pub cip76: BlockHeight,
     /// CIP72: Accept Ethereum transaction signature
     pub cip72a: BlockHeight,
+    /// The height to start applying `max_sender_gas_per_epoch`.
+    pub sender_gas_budget: BlockHeight,
+    /// The height to start bounding the rewarded blocks of an epoch by
+    /// `epoch_executed_block_bound`.
+    pub epoch_reward_bound: BlockHeight,
 }
 
 impl Default for CommonParams {
@@ -119,6 +201,12 @@ impl Default for CommonParams {
             gas_limit_bound_divisor: 0x0400.into(),
             max_transaction_size: 300 * 1024,
             anticone_penalty_ratio: ANTICONE_PENALTY_RATIO,
+            reward_epoch_count: REWARD_EPOCH_COUNT,
+            anticone_penalty_upper_epoch_count:
+                ANTICONE_PENALTY_UPPER_EPOCH_COUNT,
+            deferred_state_epoch_count: Default::default(),
+            epoch_executed_block_bound: EPOCH_EXECUTED_BLOCK_BOUND,
+            max_sender_gas_per_epoch: None,
             base_block_rewards,
             early_set_internal_contracts_states: false,
             transition_numbers: Default::default(),
@@ -149,7 +237,126 @@ impl CommonParams {
         }
     }
 
+    /// Return the gas budget of a sender in the epoch at `height`.
+    pub fn max_sender_gas_per_epoch(
+        &self, height: BlockHeight,
+    ) -> Option<U256> {
+        if height >= self.transition_heights.sender_gas_budget {
+            self.max_sender_gas_per_epoch
+        } else {
+            None
+        }
+    }
+
+    /// Return the maximum number of blocks rewarded in the epoch at `height`.
+    pub fn epoch_rewarded_block_bound(
+        &self, height: BlockHeight,
+    ) -> Option<usize> {
+        if height >= self.transition_heights.epoch_reward_bound {
+            Some(self.epoch_executed_block_bound)
+        } else {
+            None
+        }
+    }
+
+    /// Check the constraints between the reward parameters and the consensus
+    /// parameters. The receipts and the block bodies kept and synced for
+    /// reward computation cover `REWARD_EPOCH_COUNT` epochs, so it is the
+    /// upper bound of the reward window. Each epoch rewards at least its
+    /// pivot block, so `epoch_executed_block_bound` cannot be zero.
+    pub fn validate_reward_params(
+        &self, timer_chain_beta: u64,
+    ) -> Result<(), String> {
+        if self.anticone_penalty_ratio == 0
+            || self.anticone_penalty_ratio >= timer_chain_beta
+        {
+            return Err(format!(
+                "anticone_penalty_ratio={} should be in (0, timer_chain_beta={})",
+                self.anticone_penalty_ratio, timer_chain_beta
+            ));
+        }
+        if self.reward_epoch_count > REWARD_EPOCH_COUNT {
+            return Err(format!(
+                "reward_epoch_count={} should not be larger than {}",
+                self.reward_epoch_count, REWARD_EPOCH_COUNT
+            ));
+        }
+        if self.anticone_penalty_upper_epoch_count == 0
+            || self.anticone_penalty_upper_epoch_count
+                >= self.reward_epoch_count
+        {
+            return Err(format!(
+                "anticone_penalty_upper_epoch_count={} should be in (0, reward_epoch_count={})",
+                self.anticone_penalty_upper_epoch_count,
+                self.reward_epoch_count
+            ));
+        }
+        if self.epoch_executed_block_bound == 0 {
+            return Err("epoch_executed_block_bound should be positive".into());
+        }
+        Ok(())
+    }
+
+    /// The buffers of the executed epochs, the light client and the state
+    /// sync are sized by `DEFERRED_STATE_EPOCH_COUNT`, so it is the upper
+    /// bound of the deferred execution depth.
+    pub fn validate_deferred_state_params(&self) -> Result<(), String> {
+        let count = &self.deferred_state_epoch_count;
+        for depth in &[count.initial, count.after_transition] {
+            if *depth == 0 || *depth > DEFERRED_STATE_EPOCH_COUNT {
+                return Err(format!(
+                    "deferred_state_epoch_count={} should be in [1, {}]",
+                    depth, DEFERRED_STATE_EPOCH_COUNT
+                ));
+            }
+        }
+        Ok(())
+    }
+
     pub fn spec(&self, number: BlockNumber) -> vm::Spec {
         vm::Spec::new_spec_from_common_params(&self, number)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::DeferredStateEpochCount;
+
+    #[test]
+    fn test_deferred_state_epoch_count_decrease() {
+        let count = DeferredStateEpochCount {
+            initial: 5,
+            after_transition: 3,
+            transition_height: 100,
+        };
+        assert_eq!(count.at(99), 5);
+        assert_eq!(count.at(100), 3);
+        assert_eq!(count.state_height(3), 0);
+        assert_eq!(count.state_height(99), 94);
+        assert_eq!(count.state_height(100), 97);
+        assert_eq!(count.first_committing_height(94), 99);
+        // The states of the epochs 95 and 96 are not committed by any block.
+        assert_eq!(count.first_committing_height(95), 100);
+        assert_eq!(count.first_committing_height(97), 100);
+        assert_eq!(count.first_committing_height(98), 101);
+    }
+
+    #[test]
+    fn test_deferred_state_epoch_count_increase() {
+        let count = DeferredStateEpochCount {
+            initial: 3,
+            after_transition: 5,
+            transition_height: 100,
+        };
+        assert_eq!(count.at(99), 3);
+        assert_eq!(count.at(100), 5);
+        assert_eq!(count.state_height(99), 96);
+        assert_eq!(count.state_height(100), 95);
+        assert_eq!(count.state_height(102), 97);
+        assert_eq!(count.first_committing_height(96), 99);
+        // The states of the epochs 95 and 96 are committed again after the
+        // transition.
+        assert_eq!(count.first_committing_height(97), 102);
+        assert_eq!(count.max(), 5);
+    }
+}
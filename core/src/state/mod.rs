@@ -1180,6 +1180,16 @@ impl<StateDbStorage: StorageStateTrait> StateGeneric<StateDbStorage> {
         Ok(())
     }
 
+    /// Move the accounts to notify the transaction pool of to `next_state`,
+    /// which continues from the state committed by `self`, so they are
+    /// notified when `next_state` is committed.
+    pub fn move_accounts_to_notify(&mut self, next_state: &mut Self) {
+        let mut accounts_to_notify =
+            std::mem::take(&mut self.accounts_to_notify);
+        accounts_to_notify.append(&mut next_state.accounts_to_notify);
+        next_state.accounts_to_notify = accounts_to_notify;
+    }
+
     // FIXME: this should be part of the statetrait however transaction pool
     // creates circular dep.  if it proves impossible to break the loop we
     // use associated types for the tx pool.
@@ -0,0 +1,191 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfx_types::{Address, H256};
+use network::node_table::NodeId;
+use std::{
+    collections::{BTreeSet, HashMap, VecDeque},
+    hash::Hash,
+};
+
+/// The maximum number of authors and peers whose counters are kept. When the
+/// limit is reached, the entry with the fewest checks is evicted.
+const MAX_TRACKED_SOURCES: usize = 10000;
+/// The maximum number of blocks whose sending peers are remembered until the
+/// blocks are checked by consensus.
+const MAX_TRACKED_BLOCKS: usize = 100000;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AdaptiveFlagCounter {
+    /// The number of blocks whose adaptive flag is checked.
+    pub checked: u64,
+    /// The number of blocks whose claimed adaptive flag does not match the
+    /// one computed locally.
+    pub mismatched: u64,
+}
+
+impl AdaptiveFlagCounter {
+    pub fn mismatch_ratio(&self) -> f64 {
+        if self.checked == 0 {
+            0.0
+        } else {
+            self.mismatched as f64 / self.checked as f64
+        }
+    }
+
+    fn record(&mut self, mismatched: bool) {
+        self.checked += 1;
+        if mismatched {
+            self.mismatched += 1;
+        }
+    }
+}
+
+/// `AdaptiveFlagStatistics` cross-validates the adaptive flag in block headers
+/// against our own computation, grouped by block authors and by the peers
+/// that sent us the headers. A source that keeps producing mismatches is
+/// likely on a different rule set.
+#[derive(Default)]
+pub struct AdaptiveFlagStatistics {
+    by_author: BoundedCounters<Address>,
+    by_peer: BoundedCounters<NodeId>,
+    /// The peer that first sent each block that is not checked yet.
+    block_sources: HashMap<H256, NodeId>,
+    /// The blocks in `block_sources` in the order of insertion.
+    block_source_order: VecDeque<H256>,
+}
+
+impl AdaptiveFlagStatistics {
+    pub fn record_block_source(&mut self, hash: H256, peer: NodeId) {
+        if self.block_sources.contains_key(&hash) {
+            return;
+        }
+        self.block_sources.insert(hash, peer);
+        self.block_source_order.push_back(hash);
+        while self.block_source_order.len() > MAX_TRACKED_BLOCKS {
+            let evicted =
+                self.block_source_order.pop_front().expect("not empty");
+            self.block_sources.remove(&evicted);
+        }
+    }
+
+    pub fn record_check(
+        &mut self, hash: &H256, author: Address, mismatched: bool,
+    ) {
+        self.by_author.record(author, mismatched);
+        // The entry in `block_source_order` is left to be evicted later.
+        if let Some(peer) = self.block_sources.remove(hash) {
+            self.by_peer.record(peer, mismatched);
+        }
+    }
+
+    pub fn by_author(&self, author: &Address) -> AdaptiveFlagCounter {
+        self.by_author.get(author)
+    }
+
+    pub fn by_peer(&self, peer: &NodeId) -> AdaptiveFlagCounter {
+        self.by_peer.get(peer)
+    }
+
+    /// Return the authors with at least one mismatch, the most mismatches
+    /// first.
+    pub fn mismatched_authors(&self) -> Vec<(Address, AdaptiveFlagCounter)> {
+        self.by_author.mismatched_sources()
+    }
+
+    /// Return the peers with at least one mismatch, the most mismatches
+    /// first.
+    pub fn mismatched_peers(&self) -> Vec<(NodeId, AdaptiveFlagCounter)> {
+        self.by_peer.mismatched_sources()
+    }
+}
+
+/// The counters of at most `capacity` sources. The sources are also ordered
+/// by their numbers of checks, so the one to evict is found without a scan.
+struct BoundedCounters<K> {
+    capacity: usize,
+    counters: HashMap<K, AdaptiveFlagCounter>,
+    /// `(checked, source)` of each entry in `counters`.
+    by_checked: BTreeSet<(u64, K)>,
+}
+
+impl<K: Ord + Hash + Clone> Default for BoundedCounters<K> {
+    fn default() -> Self { Self::new(MAX_TRACKED_SOURCES) }
+}
+
+impl<K: Ord + Hash + Clone> BoundedCounters<K> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            counters: HashMap::new(),
+            by_checked: BTreeSet::new(),
+        }
+    }
+
+    fn record(&mut self, key: K, mismatched: bool) {
+        if let Some(counter) = self.counters.get_mut(&key) {
+            self.by_checked.remove(&(counter.checked, key.clone()));
+            counter.record(mismatched);
+            self.by_checked.insert((counter.checked, key));
+            return;
+        }
+        if self.counters.len() >= self.capacity {
+            if let Some(evicted) = self.by_checked.iter().next().cloned() {
+                self.by_checked.remove(&evicted);
+                self.counters.remove(&evicted.1);
+            }
+        }
+        let mut counter = AdaptiveFlagCounter::default();
+        counter.record(mismatched);
+        self.by_checked.insert((counter.checked, key.clone()));
+        self.counters.insert(key, counter);
+    }
+
+    fn get(&self, key: &K) -> AdaptiveFlagCounter {
+        self.counters.get(key).cloned().unwrap_or_default()
+    }
+
+    fn mismatched_sources(&self) -> Vec<(K, AdaptiveFlagCounter)> {
+        let mut sources: Vec<_> = self
+            .counters
+            .iter()
+            .filter(|(_, counter)| counter.mismatched != 0)
+            .map(|(key, counter)| (key.clone(), *counter))
+            .collect();
+        sources.sort_by(|a, b| b.1.mismatched.cmp(&a.1.mismatched));
+        sources
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AdaptiveFlagCounter, BoundedCounters};
+
+    #[test]
+    fn test_evict_fewest_checks() {
+        let mut counters = BoundedCounters::new(2);
+        counters.record(1u64, false);
+        counters.record(1, true);
+        counters.record(2, false);
+        counters.record(3, true);
+        assert_eq!(
+            counters.get(&1),
+            AdaptiveFlagCounter {
+                checked: 2,
+                mismatched: 1,
+            }
+        );
+        assert_eq!(counters.get(&2), AdaptiveFlagCounter::default());
+        assert_eq!(counters.get(&3).checked, 1);
+
+        // The checks of the kept sources are still counted.
+        counters.record(3, false);
+        counters.record(3, false);
+        counters.record(4, false);
+        assert_eq!(counters.get(&1), AdaptiveFlagCounter::default());
+        assert_eq!(counters.get(&3).checked, 3);
+        assert_eq!(counters.get(&4).checked, 1);
+        assert_eq!(counters.by_checked.len(), 2);
+    }
+}
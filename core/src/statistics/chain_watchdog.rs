@@ -0,0 +1,169 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::{
+    clock::SharedClock,
+    consensus::{ConsensusGraph, ConsensusGraphTrait},
+    statistics::SharedStatistics,
+};
+use parking_lot::Mutex;
+use std::{
+    fs,
+    path::Path,
+    sync::{Arc, Weak},
+    time::Duration,
+};
+
+#[derive(Clone, Debug)]
+pub struct ChainWatchdogConfig {
+    /// The interval between two samples.
+    pub sample_period: Duration,
+    /// Alert if the executed state falls behind the best epoch by more than
+    /// this number of epochs, not counting the deferred execution epochs.
+    pub max_execution_lag_epochs: u64,
+    /// Alert if neither the sync graph nor the best epoch makes progress
+    /// within this duration.
+    pub sync_stall_timeout: Duration,
+    /// If set, a diagnostic dump is written to this directory when an alert
+    /// is emitted.
+    pub diagnostic_dump_dir: Option<String>,
+}
+
+struct WatchdogState {
+    best_epoch_number: u64,
+    sync_inserted_count: (usize, usize),
+    /// The time of the clock when the last progress is observed.
+    last_progress_time: Duration,
+    execution_stalled: bool,
+    sync_stalled: bool,
+}
+
+/// `ChainWatchdog` periodically samples the chain head and reports stalled
+/// execution or stalled sync through statistics.
+///
+/// An alert is emitted once when a stall is detected, and is emitted again
+/// only after the node has recovered from the stall.
+pub struct ChainWatchdog {
+    config: ChainWatchdogConfig,
+    consensus: Weak<ConsensusGraph>,
+    statistics: SharedStatistics,
+    clock: SharedClock,
+    state: Mutex<WatchdogState>,
+}
+
+impl ChainWatchdog {
+    pub fn new(
+        config: ChainWatchdogConfig, consensus: &Arc<ConsensusGraph>,
+        statistics: SharedStatistics,
+    ) -> Self {
+        let clock = consensus.clock.clone();
+        let state = WatchdogState {
+            best_epoch_number: consensus.best_epoch_number(),
+            sync_inserted_count: statistics.get_sync_graph_inserted_count(),
+            last_progress_time: clock.now(),
+            execution_stalled: false,
+            sync_stalled: false,
+        };
+        Self {
+            config,
+            consensus: Arc::downgrade(consensus),
+            statistics,
+            clock,
+            state: Mutex::new(state),
+        }
+    }
+
+    pub fn sample_period(&self) -> Duration { self.config.sample_period }
+
+    /// Take one sample and emit alerts if necessary. Return `false` if the
+    /// consensus graph has been dropped, so the caller can stop sampling.
+    pub fn sample(&self) -> bool {
+        let consensus = match self.consensus.upgrade() {
+            Some(consensus) => consensus,
+            None => return false,
+        };
+        let best_epoch_number = consensus.best_epoch_number();
+        let best_executed_epoch_number =
+            consensus.best_executed_state_epoch_number();
+        let sync_inserted_count =
+            self.statistics.get_sync_graph_inserted_count();
+        let now = self.clock.now();
+        let mut state = self.state.lock();
+
+        // The executed state is expected to be the state committed by the
+        // next block on the pivot chain.
+        let execution_lag = consensus
+            .deferred_state_epoch_count()
+            .state_height(best_epoch_number + 1)
+            .saturating_sub(best_executed_epoch_number);
+        self.statistics
+            .set_execution_lag_epoch_count(execution_lag as usize);
+        // Execution may lag behind a lot during catch-up, which is expected.
+        if consensus.txpool.ready_for_mining()
+            && execution_lag > self.config.max_execution_lag_epochs
+        {
+            if !state.execution_stalled {
+                state.execution_stalled = true;
+                self.statistics.inc_execution_stall_alert_count();
+                warn!(
+                    "Chain watchdog: execution lags behind by {} epochs, best_epoch={} best_executed_epoch={}",
+                    execution_lag, best_epoch_number, best_executed_epoch_number
+                );
+                self.dump_diagnostics(&consensus, "execution_stall");
+            }
+        } else {
+            state.execution_stalled = false;
+        }
+
+        if best_epoch_number != state.best_epoch_number
+            || sync_inserted_count != state.sync_inserted_count
+        {
+            state.best_epoch_number = best_epoch_number;
+            state.sync_inserted_count = sync_inserted_count;
+            state.last_progress_time = now;
+            state.sync_stalled = false;
+        } else if !state.sync_stalled
+            && stalled_for(now, state.last_progress_time)
+                > self.config.sync_stall_timeout
+        {
+            state.sync_stalled = true;
+            self.statistics.inc_sync_stall_alert_count();
+            warn!(
+                "Chain watchdog: no sync progress in {:?}, best_epoch={} inserted (header, block) count={:?}",
+                stalled_for(now, state.last_progress_time),
+                best_epoch_number,
+                sync_inserted_count,
+            );
+            self.dump_diagnostics(&consensus, "sync_stall");
+        }
+        true
+    }
+
+    fn dump_diagnostics(&self, consensus: &ConsensusGraph, reason: &str) {
+        let dir = match &self.config.diagnostic_dump_dir {
+            Some(dir) => dir,
+            None => return,
+        };
+        let timestamp = self.clock.now_as_secs();
+        let content = format!(
+            "reason: {}\nbest_info: {:?}\nbest_executed_state_epoch_number: {}\nstate_availability_boundary: {:?}\nstatistics: {:?}\n",
+            reason,
+            consensus.best_info(),
+            consensus.best_executed_state_epoch_number(),
+            *consensus.data_man.state_availability_boundary.read(),
+            *self.statistics.inner.read(),
+        );
+        let path = Path::new(dir).join(format!("{}_{}.txt", reason, timestamp));
+        if let Err(e) =
+            fs::create_dir_all(dir).and_then(|_| fs::write(&path, content))
+        {
+            warn!("Chain watchdog: failed to write {:?}: {}", path, e);
+        }
+    }
+}
+
+/// The clock may be set back, in which case the stall is not extended.
+fn stalled_for(now: Duration, last_progress_time: Duration) -> Duration {
+    now.checked_sub(last_progress_time).unwrap_or_default()
+}
@@ -0,0 +1,94 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfx_types::H256;
+use std::collections::VecDeque;
+
+/// The maximum number of latest epochs whose packing counters are kept.
+const MAX_RECORDED_EPOCHS: usize = 1000;
+
+/// The counters of the transactions packed in an epoch that are not executed
+/// normally.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct EpochPackingCounters {
+    /// The number of transactions packed in the blocks of the epoch.
+    pub transaction_count: u64,
+    /// The transactions already executed earlier in the same epoch.
+    pub duplicated_count: u64,
+    /// The transactions whose nonces are lower than the sender nonces.
+    pub stale_nonce_count: u64,
+    /// The transactions whose nonces are higher than the sender nonces.
+    pub future_nonce_count: u64,
+    /// The transactions whose senders or sponsors cannot afford the costs.
+    pub insufficient_balance_count: u64,
+    /// The transactions skipped because their senders exceed the gas budget
+    /// of a sender in an epoch.
+    pub sender_gas_budget_exceeded_count: u64,
+}
+
+impl EpochPackingCounters {
+    fn accumulate(&mut self, other: &EpochPackingCounters) {
+        self.transaction_count += other.transaction_count;
+        self.duplicated_count += other.duplicated_count;
+        self.stale_nonce_count += other.stale_nonce_count;
+        self.future_nonce_count += other.future_nonce_count;
+        self.insufficient_balance_count += other.insufficient_balance_count;
+        self.sender_gas_budget_exceeded_count +=
+            other.sender_gas_budget_exceeded_count;
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EpochPackingRecord {
+    pub epoch_hash: H256,
+    pub epoch_height: u64,
+    pub counters: EpochPackingCounters,
+}
+
+/// `EpochPackingStatistics` keeps the packing counters of the latest executed
+/// epochs on the pivot chain, and the sum of the counters of all the epochs
+/// executed since the node starts.
+#[derive(Default)]
+pub struct EpochPackingStatistics {
+    records: VecDeque<EpochPackingRecord>,
+    total: EpochPackingCounters,
+}
+
+impl EpochPackingStatistics {
+    /// Record the counters of an executed epoch. An epoch executed again, e.g.
+    /// after the pivot chain switches back to it, replaces its old record and
+    /// is not counted in the total again. Return `false` in this case.
+    pub fn record(&mut self, record: EpochPackingRecord) -> bool {
+        if let Some(existing) = self
+            .records
+            .iter_mut()
+            .find(|existing| existing.epoch_hash == record.epoch_hash)
+        {
+            *existing = record;
+            return false;
+        }
+        self.total.accumulate(&record.counters);
+        self.records.push_back(record);
+        while self.records.len() > MAX_RECORDED_EPOCHS {
+            self.records.pop_front();
+        }
+        true
+    }
+
+    pub fn record_by_hash(
+        &self, epoch_hash: &H256,
+    ) -> Option<EpochPackingRecord> {
+        self.records
+            .iter()
+            .find(|record| record.epoch_hash == *epoch_hash)
+            .cloned()
+    }
+
+    /// Return at most `limit` latest records, the newest first.
+    pub fn recent_records(&self, limit: usize) -> Vec<EpochPackingRecord> {
+        self.records.iter().rev().take(limit).cloned().collect()
+    }
+
+    pub fn total(&self) -> EpochPackingCounters { self.total }
+}
@@ -0,0 +1,248 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use metrics::{register_meter_with_group, Meter};
+use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::{
+    ops::{Deref, DerefMut},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+lazy_static! {
+    static ref LOCK_CATEGORY_METERS: Vec<LockCategoryMeters> =
+        LockCategory::all()
+            .iter()
+            .map(|category| LockCategoryMeters::register(*category))
+            .collect();
+}
+
+/// The kinds of call sites that acquire an instrumented lock.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LockCategory {
+    /// Inserting blocks and updating the graph with them.
+    Insert,
+    /// Reading the graph, e.g. for RPCs, sync and transaction pool.
+    Query,
+    /// Reading or rebuilding the graph to persist or recover data.
+    Persist,
+}
+
+impl LockCategory {
+    pub fn all() -> &'static [LockCategory] {
+        &[
+            LockCategory::Insert,
+            LockCategory::Query,
+            LockCategory::Persist,
+        ]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LockCategory::Insert => "insert",
+            LockCategory::Query => "query",
+            LockCategory::Persist => "persist",
+        }
+    }
+
+    fn index(&self) -> usize {
+        match self {
+            LockCategory::Insert => 0,
+            LockCategory::Query => 1,
+            LockCategory::Persist => 2,
+        }
+    }
+}
+
+struct LockCategoryMeters {
+    acquire_tps: Arc<dyn Meter>,
+    wait_time: Arc<dyn Meter>,
+    hold_time: Arc<dyn Meter>,
+}
+
+impl LockCategoryMeters {
+    fn register(category: LockCategory) -> Self {
+        let group = format!("consensus_inner_lock_{}", category.name());
+        LockCategoryMeters {
+            acquire_tps: register_meter_with_group(&group, "acquires"),
+            wait_time: register_meter_with_group(&group, "wait_t"),
+            hold_time: register_meter_with_group(&group, "hold_t"),
+        }
+    }
+}
+
+#[derive(Default)]
+struct LockCategoryCounters {
+    acquire_count: AtomicU64,
+    wait_nanos: AtomicU64,
+    max_wait_nanos: AtomicU64,
+    hold_nanos: AtomicU64,
+    max_hold_nanos: AtomicU64,
+}
+
+/// The accumulated wait and hold durations of a lock category since the node
+/// starts.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LockContentionCounters {
+    pub acquire_count: u64,
+    pub total_wait: Duration,
+    pub max_wait: Duration,
+    pub total_hold: Duration,
+    pub max_hold: Duration,
+}
+
+/// `LockContention` accumulates the time spent waiting for and holding a
+/// lock, separately for each `LockCategory`. The durations are also marked to
+/// the meters in the group `consensus_inner_lock_<category>`.
+#[derive(Default)]
+pub struct LockContention {
+    counters: [LockCategoryCounters; 3],
+}
+
+impl LockContention {
+    fn record_wait(&self, category: LockCategory, wait: Duration) {
+        let nanos = wait.as_nanos() as u64;
+        let counters = &self.counters[category.index()];
+        counters.acquire_count.fetch_add(1, Ordering::Relaxed);
+        counters.wait_nanos.fetch_add(nanos, Ordering::Relaxed);
+        counters.max_wait_nanos.fetch_max(nanos, Ordering::Relaxed);
+        let meters = &LOCK_CATEGORY_METERS[category.index()];
+        meters.acquire_tps.mark(1);
+        meters.wait_time.mark(nanos as usize);
+    }
+
+    fn record_hold(&self, category: LockCategory, hold: Duration) {
+        let nanos = hold.as_nanos() as u64;
+        let counters = &self.counters[category.index()];
+        counters.hold_nanos.fetch_add(nanos, Ordering::Relaxed);
+        counters.max_hold_nanos.fetch_max(nanos, Ordering::Relaxed);
+        LOCK_CATEGORY_METERS[category.index()]
+            .hold_time
+            .mark(nanos as usize);
+    }
+
+    pub fn counters(&self, category: LockCategory) -> LockContentionCounters {
+        let counters = &self.counters[category.index()];
+        let load =
+            |a: &AtomicU64| Duration::from_nanos(a.load(Ordering::Relaxed));
+        LockContentionCounters {
+            acquire_count: counters.acquire_count.load(Ordering::Relaxed),
+            total_wait: load(&counters.wait_nanos),
+            max_wait: load(&counters.max_wait_nanos),
+            total_hold: load(&counters.hold_nanos),
+            max_hold: load(&counters.max_hold_nanos),
+        }
+    }
+}
+
+/// `InstrumentedRwLock` is a `RwLock` that records the wait and hold
+/// durations of each acquisition to a `LockContention`.
+///
+/// `read()` and `read_recursive()` are counted as `LockCategory::Query`, and
+/// `write()` and `try_write()` as `LockCategory::Insert`. The call sites of
+/// other categories use the `*_for` variants.
+pub struct InstrumentedRwLock<T> {
+    lock: RwLock<T>,
+    contention: Arc<LockContention>,
+}
+
+impl<T> InstrumentedRwLock<T> {
+    pub fn new(value: T, contention: Arc<LockContention>) -> Self {
+        Self {
+            lock: RwLock::new(value),
+            contention,
+        }
+    }
+
+    pub fn read(&self) -> InstrumentedLockGuard<'_, RwLockReadGuard<'_, T>> {
+        self.read_for(LockCategory::Query)
+    }
+
+    pub fn read_recursive(
+        &self,
+    ) -> InstrumentedLockGuard<'_, RwLockReadGuard<'_, T>> {
+        self.read_recursive_for(LockCategory::Query)
+    }
+
+    pub fn write(&self) -> InstrumentedLockGuard<'_, RwLockWriteGuard<'_, T>> {
+        self.write_for(LockCategory::Insert)
+    }
+
+    pub fn try_write(
+        &self,
+    ) -> Option<InstrumentedLockGuard<'_, RwLockWriteGuard<'_, T>>> {
+        let start = Instant::now();
+        let raw = self.lock.try_write()?;
+        Some(self.guard(LockCategory::Insert, start, raw))
+    }
+
+    pub fn read_for(
+        &self, category: LockCategory,
+    ) -> InstrumentedLockGuard<'_, RwLockReadGuard<'_, T>> {
+        let start = Instant::now();
+        let raw = self.lock.read();
+        self.guard(category, start, raw)
+    }
+
+    pub fn read_recursive_for(
+        &self, category: LockCategory,
+    ) -> InstrumentedLockGuard<'_, RwLockReadGuard<'_, T>> {
+        let start = Instant::now();
+        let raw = self.lock.read_recursive();
+        self.guard(category, start, raw)
+    }
+
+    pub fn write_for(
+        &self, category: LockCategory,
+    ) -> InstrumentedLockGuard<'_, RwLockWriteGuard<'_, T>> {
+        let start = Instant::now();
+        let raw = self.lock.write();
+        self.guard(category, start, raw)
+    }
+
+    fn guard<GUARD>(
+        &self, category: LockCategory, start: Instant, raw: GUARD,
+    ) -> InstrumentedLockGuard<'_, GUARD> {
+        let acquired = Instant::now();
+        self.contention
+            .record_wait(category, acquired.duration_since(start));
+        InstrumentedLockGuard {
+            raw,
+            category,
+            acquired,
+            contention: &self.contention,
+        }
+    }
+}
+
+pub struct InstrumentedLockGuard<'a, GUARD> {
+    raw: GUARD,
+    category: LockCategory,
+    acquired: Instant,
+    contention: &'a LockContention,
+}
+
+impl<'a, GUARD> Drop for InstrumentedLockGuard<'a, GUARD> {
+    fn drop(&mut self) {
+        self.contention
+            .record_hold(self.category, self.acquired.elapsed());
+    }
+}
+
+impl<'a, T, GUARD: Deref<Target = T>> Deref
+    for InstrumentedLockGuard<'a, GUARD>
+{
+    type Target = T;
+
+    fn deref(&self) -> &T { &self.raw }
+}
+
+impl<'a, T, GUARD: DerefMut<Target = T>> DerefMut
+    for InstrumentedLockGuard<'a, GUARD>
+{
+    fn deref_mut(&mut self) -> &mut T { &mut self.raw }
+}
@@ -1,6 +1,20 @@
+pub mod adaptive_flag;
+pub mod chain_watchdog;
+pub mod epoch_packing;
+pub mod lock_contention;
+pub mod nonce_order;
+
 use crate::{consensus::ConsensusGraphStatistics, sync::SyncGraphStatistics};
-use metrics::{Gauge, GaugeUsize};
-use parking_lot::RwLock;
+use adaptive_flag::{AdaptiveFlagCounter, AdaptiveFlagStatistics};
+use cfx_types::{Address, H256};
+use epoch_packing::{
+    EpochPackingCounters, EpochPackingRecord, EpochPackingStatistics,
+};
+use lock_contention::{LockCategory, LockContention, LockContentionCounters};
+use metrics::{Counter, CounterUsize, Gauge, GaugeUsize};
+use network::node_table::NodeId;
+use nonce_order::{EpochNonceOrder, NonceOrderStatistics, TxSkipDiagnosis};
+use parking_lot::{Mutex, RwLock};
 use std::sync::Arc;
 lazy_static! {
     static ref SYNC_INSERTED_HEADER_COUNT: Arc<dyn Gauge<usize>> =
@@ -18,6 +32,51 @@ lazy_static! {
             "graph_statistic",
             "consensus_graph_inserted_header_count"
         );
+    static ref EXECUTION_LAG_EPOCH_COUNT: Arc<dyn Gauge<usize>> =
+        GaugeUsize::register_with_group(
+            "graph_statistic",
+            "execution_lag_epoch_count"
+        );
+    static ref EXECUTION_STALL_ALERT_COUNTER: Arc<dyn Counter<usize>> =
+        CounterUsize::register_with_group(
+            "system_metrics",
+            "execution_stall_alert_count"
+        );
+    static ref SYNC_STALL_ALERT_COUNTER: Arc<dyn Counter<usize>> =
+        CounterUsize::register_with_group(
+            "system_metrics",
+            "sync_stall_alert_count"
+        );
+    static ref ADAPTIVE_FLAG_MISMATCH_COUNTER: Arc<dyn Counter<usize>> =
+        CounterUsize::register_with_group(
+            "graph_statistic",
+            "adaptive_flag_mismatch_count"
+        );
+    static ref DUPLICATED_TX_COUNTER: Arc<dyn Counter<usize>> =
+        CounterUsize::register_with_group(
+            "execution_statistic",
+            "duplicated_tx_count"
+        );
+    static ref STALE_NONCE_TX_COUNTER: Arc<dyn Counter<usize>> =
+        CounterUsize::register_with_group(
+            "execution_statistic",
+            "stale_nonce_tx_count"
+        );
+    static ref FUTURE_NONCE_TX_COUNTER: Arc<dyn Counter<usize>> =
+        CounterUsize::register_with_group(
+            "execution_statistic",
+            "future_nonce_tx_count"
+        );
+    static ref INSUFFICIENT_BALANCE_TX_COUNTER: Arc<dyn Counter<usize>> =
+        CounterUsize::register_with_group(
+            "execution_statistic",
+            "insufficient_balance_tx_count"
+        );
+    static ref SENDER_GAS_BUDGET_EXCEEDED_TX_COUNTER: Arc<dyn Counter<usize>> =
+        CounterUsize::register_with_group(
+            "execution_statistic",
+            "sender_gas_budget_exceeded_tx_count"
+        );
 }
 
 pub type SharedStatistics = Arc<Statistics>;
@@ -39,12 +98,20 @@ impl StatisticsInner {
 
 pub struct Statistics {
     pub inner: RwLock<StatisticsInner>,
+    adaptive_flag: Mutex<AdaptiveFlagStatistics>,
+    epoch_packing: Mutex<EpochPackingStatistics>,
+    nonce_order: Mutex<NonceOrderStatistics>,
+    consensus_inner_lock: Arc<LockContention>,
 }
 
 impl Statistics {
     pub fn new() -> Self {
         Statistics {
             inner: RwLock::new(StatisticsInner::new()),
+            adaptive_flag: Default::default(),
+            epoch_packing: Default::default(),
+            nonce_order: Default::default(),
+            consensus_inner_lock: Default::default(),
         }
     }
 
@@ -76,6 +143,137 @@ impl Statistics {
             .update(inner.consensus_graph.inserted_block_count)
     }
 
+    pub fn set_execution_lag_epoch_count(&self, count: usize) {
+        EXECUTION_LAG_EPOCH_COUNT.update(count);
+    }
+
+    pub fn inc_execution_stall_alert_count(&self) {
+        EXECUTION_STALL_ALERT_COUNTER.inc(1);
+    }
+
+    pub fn inc_sync_stall_alert_count(&self) {
+        SYNC_STALL_ALERT_COUNTER.inc(1);
+    }
+
+    /// Remember the peer that sent us the header of a block, so that the
+    /// adaptive flag check of the block can be attributed to the peer.
+    pub fn record_block_source(&self, hash: H256, peer: NodeId) {
+        self.adaptive_flag.lock().record_block_source(hash, peer);
+    }
+
+    pub fn record_adaptive_flag_check(
+        &self, hash: &H256, author: Address, mismatched: bool,
+    ) {
+        if mismatched {
+            ADAPTIVE_FLAG_MISMATCH_COUNTER.inc(1);
+        }
+        self.adaptive_flag
+            .lock()
+            .record_check(hash, author, mismatched);
+    }
+
+    pub fn adaptive_flag_counter_by_author(
+        &self, author: &Address,
+    ) -> AdaptiveFlagCounter {
+        self.adaptive_flag.lock().by_author(author)
+    }
+
+    /// The protocol layer can use this to deprioritize the peers that keep
+    /// sending blocks with mismatched adaptive flags.
+    pub fn adaptive_flag_counter_by_peer(
+        &self, peer: &NodeId,
+    ) -> AdaptiveFlagCounter {
+        self.adaptive_flag.lock().by_peer(peer)
+    }
+
+    pub fn adaptive_flag_mismatches(
+        &self,
+    ) -> (
+        Vec<(Address, AdaptiveFlagCounter)>,
+        Vec<(NodeId, AdaptiveFlagCounter)>,
+    ) {
+        let adaptive_flag = self.adaptive_flag.lock();
+        (
+            adaptive_flag.mismatched_authors(),
+            adaptive_flag.mismatched_peers(),
+        )
+    }
+
+    pub fn record_epoch_packing(
+        &self, epoch_hash: H256, epoch_height: u64,
+        counters: EpochPackingCounters,
+    )
+    {
+        let recorded = self.epoch_packing.lock().record(EpochPackingRecord {
+            epoch_hash,
+            epoch_height,
+            counters,
+        });
+        if recorded {
+            DUPLICATED_TX_COUNTER.inc(counters.duplicated_count as usize);
+            STALE_NONCE_TX_COUNTER.inc(counters.stale_nonce_count as usize);
+            FUTURE_NONCE_TX_COUNTER.inc(counters.future_nonce_count as usize);
+            INSUFFICIENT_BALANCE_TX_COUNTER
+                .inc(counters.insufficient_balance_count as usize);
+            SENDER_GAS_BUDGET_EXCEEDED_TX_COUNTER
+                .inc(counters.sender_gas_budget_exceeded_count as usize);
+        }
+    }
+
+    pub fn epoch_packing_record(
+        &self, epoch_hash: &H256,
+    ) -> Option<EpochPackingRecord> {
+        self.epoch_packing.lock().record_by_hash(epoch_hash)
+    }
+
+    /// Return the packing records of at most `limit` latest executed epochs,
+    /// the newest first, and the total counters since the node starts.
+    pub fn epoch_packing_records(
+        &self, limit: usize,
+    ) -> (Vec<EpochPackingRecord>, EpochPackingCounters) {
+        let epoch_packing = self.epoch_packing.lock();
+        (epoch_packing.recent_records(limit), epoch_packing.total())
+    }
+
+    pub fn record_epoch_nonce_order(&self, record: EpochNonceOrder) {
+        self.nonce_order.lock().record(record);
+    }
+
+    /// Explain why a transaction is not executed normally in one of the
+    /// latest executed epochs.
+    pub fn diagnose_skipped_transaction(
+        &self, tx_hash: &H256,
+    ) -> Option<TxSkipDiagnosis> {
+        self.nonce_order.lock().diagnose(tx_hash)
+    }
+
+    /// The contention counters shared with the lock of the consensus graph
+    /// inner.
+    pub fn consensus_inner_lock_contention(&self) -> Arc<LockContention> {
+        self.consensus_inner_lock.clone()
+    }
+
+    /// Return the wait and hold durations of the consensus graph inner lock
+    /// for each category of call sites.
+    pub fn consensus_inner_lock_counters(
+        &self,
+    ) -> Vec<(LockCategory, LockContentionCounters)> {
+        LockCategory::all()
+            .iter()
+            .map(|category| {
+                (*category, self.consensus_inner_lock.counters(*category))
+            })
+            .collect()
+    }
+
+    pub fn get_sync_graph_inserted_count(&self) -> (usize, usize) {
+        let inner = self.inner.read();
+        (
+            inner.sync_graph.inserted_header_count,
+            inner.sync_graph.inserted_block_count,
+        )
+    }
+
     pub fn get_consensus_graph_processed_block_count(&self) -> usize {
         self.inner.read().consensus_graph.processed_block_count
     }
@@ -89,5 +287,9 @@ impl Statistics {
     pub fn log_statistics(&self) {
         let inner = self.inner.read();
         info!("Statistics: {:?}", *inner);
+        info!(
+            "Consensus inner lock contention: {:?}",
+            self.consensus_inner_lock_counters()
+        );
     }
 }
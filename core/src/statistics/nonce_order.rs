@@ -0,0 +1,175 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfx_types::{Address, H256, U256};
+use std::collections::{HashMap, VecDeque};
+
+/// The maximum number of latest epochs whose transaction orders are kept.
+const MAX_RECORDED_EPOCHS: usize = 100;
+
+/// Why a transaction packed in an epoch is not executed normally.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TxSkipReason {
+    /// The transaction is already executed earlier in the same epoch.
+    Duplicated {
+        executed_block_hash: H256,
+        executed_index: usize,
+    },
+    /// The nonce is lower than the sender nonce, e.g. another transaction
+    /// with the same nonce is executed before it.
+    NonceTooOld { expected: U256 },
+    /// The nonce is higher than the sender nonce, e.g. the transaction with
+    /// the expected nonce is ordered after it or skipped.
+    NonceTooNew { expected: U256 },
+    /// The sender or the sponsor cannot afford the costs, e.g. after the
+    /// earlier transactions of the sender. The nonce is bumped if the sender
+    /// cannot afford it.
+    InsufficientBalance,
+    /// The sender exceeds the gas budget of a sender in an epoch.
+    SenderGasBudgetExceeded,
+    /// The epoch is too far from the epoch height of the transaction.
+    EpochHeightOutOfBound,
+    /// The recipient address is invalid.
+    InvalidRecipient,
+}
+
+/// A transaction of a sender at its position in the execution order of an
+/// epoch.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SenderTxRecord {
+    pub hash: H256,
+    pub nonce: U256,
+    pub block_hash: H256,
+    pub index: usize,
+    /// `None` if the transaction is executed normally.
+    pub skip_reason: Option<TxSkipReason>,
+}
+
+/// The execution order of the transactions of the senders with at least one
+/// transaction not executed normally in an executed epoch. The transactions
+/// executed before an execution checkpoint the epoch resumes from are not
+/// included.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EpochNonceOrder {
+    pub epoch_hash: H256,
+    pub epoch_height: u64,
+    pub senders: HashMap<Address, Vec<SenderTxRecord>>,
+}
+
+/// The explanation of a transaction not executed normally.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TxSkipDiagnosis {
+    pub epoch_hash: H256,
+    pub epoch_height: u64,
+    pub sender: Address,
+    pub reason: TxSkipReason,
+    /// The transactions of the sender in the epoch in the execution order,
+    /// including this one.
+    pub sender_order: Vec<SenderTxRecord>,
+}
+
+/// `NonceOrderStatistics` keeps the transaction orders of the latest executed
+/// epochs on the pivot chain, so the transactions skipped because the blocks
+/// of an epoch order them differently from their arrival can be explained.
+#[derive(Default)]
+pub struct NonceOrderStatistics {
+    records: VecDeque<EpochNonceOrder>,
+}
+
+impl NonceOrderStatistics {
+    /// Record the order of an executed epoch. An epoch executed again
+    /// replaces its old record.
+    pub fn record(&mut self, record: EpochNonceOrder) {
+        self.records
+            .retain(|existing| existing.epoch_hash != record.epoch_hash);
+        if record.senders.is_empty() {
+            return;
+        }
+        self.records.push_back(record);
+        while self.records.len() > MAX_RECORDED_EPOCHS {
+            self.records.pop_front();
+        }
+    }
+
+    /// Explain why the transaction `tx_hash` is not executed normally in the
+    /// latest recorded epoch that skips it.
+    pub fn diagnose(&self, tx_hash: &H256) -> Option<TxSkipDiagnosis> {
+        for record in self.records.iter().rev() {
+            for (sender, order) in &record.senders {
+                let skipped =
+                    order.iter().find_map(|tx| match &tx.skip_reason {
+                        Some(reason) if tx.hash == *tx_hash => Some(reason),
+                        _ => None,
+                    });
+                if let Some(reason) = skipped {
+                    return Some(TxSkipDiagnosis {
+                        epoch_hash: record.epoch_hash,
+                        epoch_height: record.epoch_height,
+                        sender: *sender,
+                        reason: reason.clone(),
+                        sender_order: order.clone(),
+                    });
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        EpochNonceOrder, NonceOrderStatistics, SenderTxRecord, TxSkipReason,
+    };
+    use cfx_types::{Address, H256, U256};
+
+    fn tx(
+        hash: u64, nonce: u64, skip_reason: Option<TxSkipReason>,
+    ) -> SenderTxRecord {
+        SenderTxRecord {
+            hash: H256::from_low_u64_be(hash),
+            nonce: nonce.into(),
+            block_hash: H256::zero(),
+            index: hash as usize,
+            skip_reason,
+        }
+    }
+
+    #[test]
+    fn test_diagnose() {
+        let sender = Address::random();
+        let mut statistics = NonceOrderStatistics::default();
+        let mut record = EpochNonceOrder {
+            epoch_hash: H256::from_low_u64_be(100),
+            epoch_height: 1,
+            senders: Default::default(),
+        };
+        // The transaction with nonce 1 is ordered before the one with nonce 0.
+        record.senders.insert(
+            sender,
+            vec![
+                tx(
+                    1,
+                    1,
+                    Some(TxSkipReason::NonceTooNew {
+                        expected: U256::zero(),
+                    }),
+                ),
+                tx(2, 0, None),
+            ],
+        );
+        statistics.record(record.clone());
+
+        let diagnosis = statistics.diagnose(&H256::from_low_u64_be(1)).unwrap();
+        assert_eq!(diagnosis.sender, sender);
+        assert_eq!(diagnosis.epoch_height, 1);
+        assert_eq!(diagnosis.sender_order.len(), 2);
+        assert!(statistics.diagnose(&H256::from_low_u64_be(2)).is_none());
+
+        // The epoch is executed again without skipping any transactions.
+        record.senders.clear();
+        statistics.record(record);
+        assert!(statistics.diagnose(&H256::from_low_u64_be(1)).is_none());
+    }
+}
@@ -0,0 +1,136 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::{
+    block_data_manager::{
+        BlockDataManager, BlockOrigin, BlockPropagationRecord,
+    },
+    clock::SharedClock,
+};
+use cfx_types::H256;
+use parking_lot::Mutex;
+use std::{
+    cmp::min,
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+};
+
+struct PendingBlocks {
+    /// The origin and first-seen time of blocks that are not graph-ready yet.
+    first_seen: HashMap<H256, (BlockOrigin, u64)>,
+    /// The blocks in `first_seen` in the order of insertion, used to bound
+    /// the memory when many blocks never become graph-ready.
+    order: VecDeque<H256>,
+    next_seq: u64,
+}
+
+/// `BlockPropagationRecorder` records where each block comes from, when it is
+/// first seen, and when it becomes graph-ready. Only blocks received in this
+/// run are recorded, and blocks recovered from the database are ignored.
+///
+/// The records of the latest `history_size` graph-ready blocks are kept in
+/// the database. The `i`-th record is referred by the slot `i % history_size`,
+/// so the oldest record is removed when its slot is reused.
+pub struct BlockPropagationRecorder {
+    data_man: Arc<BlockDataManager>,
+    clock: SharedClock,
+    history_size: u64,
+    pending: Mutex<PendingBlocks>,
+}
+
+impl BlockPropagationRecorder {
+    pub fn new(
+        data_man: Arc<BlockDataManager>, clock: SharedClock,
+        history_size: usize,
+    ) -> Self
+    {
+        let next_seq = data_man.block_propagation_next_seq().unwrap_or(0);
+        Self {
+            data_man,
+            clock,
+            history_size: history_size as u64,
+            pending: Mutex::new(PendingBlocks {
+                first_seen: HashMap::new(),
+                order: VecDeque::new(),
+                next_seq,
+            }),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool { self.history_size != 0 }
+
+    /// Record the first time we see a block. Later calls for the same block
+    /// are ignored, so the caller that knows the origin better should call
+    /// this first.
+    pub fn on_first_seen(&self, hash: H256, origin: BlockOrigin) {
+        if !self.is_enabled() {
+            return;
+        }
+        let now = self.now_ms();
+        let mut pending = self.pending.lock();
+        if pending.first_seen.contains_key(&hash) {
+            return;
+        }
+        pending.first_seen.insert(hash, (origin, now));
+        pending.order.push_back(hash);
+        while pending.order.len() as u64 > self.history_size {
+            let evicted = pending.order.pop_front().expect("not empty");
+            pending.first_seen.remove(&evicted);
+        }
+    }
+
+    /// Persist the record of a block once it becomes graph-ready.
+    pub fn on_graph_ready(&self, hash: &H256) {
+        if !self.is_enabled() {
+            return;
+        }
+        let now = self.now_ms();
+        let mut pending = self.pending.lock();
+        let (origin, first_seen_ms) = match pending.first_seen.remove(hash) {
+            Some(x) => x,
+            None => return,
+        };
+        let record = BlockPropagationRecord {
+            block_hash: *hash,
+            origin,
+            first_seen_ms,
+            graph_ready_ms: now,
+        };
+        let slot = pending.next_seq % self.history_size;
+        if let Some(old_hash) = self.data_man.block_propagation_slot(slot) {
+            self.data_man.remove_block_propagation_record(&old_hash);
+        }
+        self.data_man.insert_block_propagation_record(&record);
+        self.data_man.insert_block_propagation_slot(slot, hash);
+        pending.next_seq += 1;
+        self.data_man
+            .insert_block_propagation_next_seq(pending.next_seq);
+        trace!("Block propagation record: {:?}", record);
+    }
+
+    pub fn record_by_hash(
+        &self, hash: &H256,
+    ) -> Option<BlockPropagationRecord> {
+        self.data_man.block_propagation_record_by_hash(hash)
+    }
+
+    /// Return at most `limit` latest records, the newest first.
+    pub fn recent_records(&self, limit: usize) -> Vec<BlockPropagationRecord> {
+        if !self.is_enabled() {
+            return Vec::new();
+        }
+        let next_seq = self.pending.lock().next_seq;
+        let count = min(min(limit as u64, self.history_size), next_seq);
+        (next_seq - count..next_seq)
+            .rev()
+            .filter_map(|seq| {
+                self.data_man
+                    .block_propagation_slot(seq % self.history_size)
+            })
+            .filter_map(|hash| self.record_by_hash(&hash))
+            .collect()
+    }
+
+    fn now_ms(&self) -> u64 { self.clock.now().as_millis() as u64 }
+}
@@ -108,6 +108,11 @@ error_chain! {
             display("Cannot process the message due to the catch up mode: {:?}", reason),
         }
 
+        Busy(reason: String) {
+            description("Cannot process the message due to memory pressure."),
+            display("Cannot process the message due to memory pressure: {:?}", reason),
+        }
+
         InternalError(reason: String) {
             description("Internal error"),
             display("Internal error: {:?}", reason),
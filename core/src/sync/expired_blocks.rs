@@ -0,0 +1,135 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfx_types::H256;
+use malloc_size_of_derive::MallocSizeOf as DeriveMallocSizeOf;
+use std::collections::HashMap;
+
+/// The backoff before a block can be inserted again after its first expiry.
+/// It doubles with each further expiry of the same block.
+const BASE_REINSERTION_BACKOFF_SECS: u64 = 60;
+const MAX_REINSERTION_BACKOFF_SECS: u64 = 3600;
+/// The records of blocks not expired again within this time are dropped.
+const EXPIRY_RECORD_TIMEOUT_SECS: u64 = 2 * MAX_REINSERTION_BACKOFF_SECS;
+
+#[derive(DeriveMallocSizeOf)]
+struct ExpiryRecord {
+    expiry_count: u32,
+    last_expiry_secs: u64,
+}
+
+/// `ExpiredBlockTracker` rate-limits the blocks removed from the sync graph
+/// because they were not ready for too long. Such a block may arrive again
+/// and be inserted, but a block that never becomes ready would otherwise be
+/// verified and expired again in a loop.
+///
+/// The records are kept in memory only. After a restart, an expired block is
+/// allowed to be inserted again at once, because its persisted status is just
+/// `BlockStatus::Expired`, and its backoff starts again from the base.
+#[derive(DeriveMallocSizeOf)]
+pub struct ExpiredBlockTracker {
+    records: HashMap<H256, ExpiryRecord>,
+    /// The maximum number of tracked blocks. The expiries of other blocks are
+    /// not rate-limited until some records are dropped.
+    capacity: usize,
+}
+
+impl ExpiredBlockTracker {
+    pub fn new(capacity: usize) -> Self {
+        ExpiredBlockTracker {
+            records: HashMap::new(),
+            capacity,
+        }
+    }
+
+    pub fn on_expired(&mut self, hash: H256, now: u64) {
+        if !self.records.contains_key(&hash)
+            && self.records.len() >= self.capacity
+        {
+            return;
+        }
+        let record = self.records.entry(hash).or_insert(ExpiryRecord {
+            expiry_count: 0,
+            last_expiry_secs: now,
+        });
+        record.expiry_count = record.expiry_count.saturating_add(1);
+        record.last_expiry_secs = now;
+    }
+
+    /// Return whether an expired block can be inserted again at `now`.
+    pub fn reinsertion_allowed(&self, hash: &H256, now: u64) -> bool {
+        match self.records.get(hash) {
+            Some(record) => {
+                now >= record.last_expiry_secs
+                    + Self::backoff_secs(record.expiry_count)
+            }
+            None => true,
+        }
+    }
+
+    /// Drop the records of the blocks not expired recently.
+    pub fn prune(&mut self, now: u64) {
+        self.records.retain(|_, record| {
+            record.last_expiry_secs + EXPIRY_RECORD_TIMEOUT_SECS > now
+        });
+    }
+
+    pub fn len(&self) -> usize { self.records.len() }
+
+    fn backoff_secs(expiry_count: u32) -> u64 {
+        let shift = expiry_count.saturating_sub(1).min(32);
+        BASE_REINSERTION_BACKOFF_SECS
+            .saturating_mul(1u64 << shift)
+            .min(MAX_REINSERTION_BACKOFF_SECS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        ExpiredBlockTracker, EXPIRY_RECORD_TIMEOUT_SECS,
+        MAX_REINSERTION_BACKOFF_SECS,
+    };
+    use cfx_types::H256;
+
+    #[test]
+    fn test_backoff_doubles_on_each_expiry() {
+        let mut tracker = ExpiredBlockTracker::new(10);
+        let hash = H256::from_low_u64_be(1);
+        assert!(tracker.reinsertion_allowed(&hash, 0));
+
+        tracker.on_expired(hash, 1000);
+        assert!(!tracker.reinsertion_allowed(&hash, 1059));
+        assert!(tracker.reinsertion_allowed(&hash, 1060));
+
+        tracker.on_expired(hash, 2000);
+        assert!(!tracker.reinsertion_allowed(&hash, 2119));
+        assert!(tracker.reinsertion_allowed(&hash, 2120));
+
+        for _ in 0..40 {
+            tracker.on_expired(hash, 3000);
+        }
+        assert!(!tracker.reinsertion_allowed(
+            &hash,
+            3000 + MAX_REINSERTION_BACKOFF_SECS - 1
+        ));
+        assert!(tracker
+            .reinsertion_allowed(&hash, 3000 + MAX_REINSERTION_BACKOFF_SECS));
+    }
+
+    #[test]
+    fn test_prune_and_capacity() {
+        let mut tracker = ExpiredBlockTracker::new(2);
+        tracker.on_expired(H256::from_low_u64_be(1), 0);
+        tracker.on_expired(H256::from_low_u64_be(2), 100);
+        tracker.on_expired(H256::from_low_u64_be(3), 100);
+        assert_eq!(tracker.len(), 2);
+        assert!(tracker.reinsertion_allowed(&H256::from_low_u64_be(3), 100));
+
+        tracker.prune(EXPIRY_RECORD_TIMEOUT_SECS);
+        assert_eq!(tracker.len(), 1);
+        assert!(tracker.reinsertion_allowed(&H256::from_low_u64_be(1), 0));
+        assert!(!tracker.reinsertion_allowed(&H256::from_low_u64_be(2), 100));
+    }
+}
@@ -0,0 +1,133 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfx_types::H256;
+use malloc_size_of_derive::MallocSizeOf as DeriveMallocSizeOf;
+use std::collections::{HashMap, VecDeque};
+
+#[derive(DeriveMallocSizeOf)]
+struct SpilledGroup {
+    header_count: usize,
+    /// The sequence number of the latest spill into this group.
+    seq: u64,
+}
+
+/// `SpilledHeaderIndex` tracks the groups of pending headers moved out of the
+/// sync graph to the database, keyed by the missing parent hash they wait
+/// for. Only the keys and the sizes are kept in memory. When the number of
+/// spilled headers exceeds the capacity, the least recently spilled groups
+/// are dropped.
+#[derive(DeriveMallocSizeOf)]
+pub struct SpilledHeaderIndex {
+    groups: HashMap<H256, SpilledGroup>,
+    /// The spills in order. An entry is stale if its group is taken or
+    /// spilled into again later.
+    order: VecDeque<(u64, H256)>,
+    next_seq: u64,
+    header_count: usize,
+    capacity: usize,
+}
+
+impl SpilledHeaderIndex {
+    pub fn new(capacity: usize) -> Self {
+        SpilledHeaderIndex {
+            groups: HashMap::new(),
+            order: VecDeque::new(),
+            next_seq: 0,
+            header_count: 0,
+            capacity,
+        }
+    }
+
+    pub fn contains(&self, parent_hash: &H256) -> bool {
+        self.groups.contains_key(parent_hash)
+    }
+
+    pub fn header_count(&self) -> usize { self.header_count }
+
+    /// Iterate over the missing parent hashes with the numbers of headers
+    /// spilled for them.
+    pub fn iter(&self) -> impl Iterator<Item = (&H256, usize)> {
+        self.groups
+            .iter()
+            .map(|(hash, group)| (hash, group.header_count))
+    }
+
+    /// Record `header_count` more headers spilled into the group of
+    /// `parent_hash`. Return the parent hashes of the groups dropped to keep
+    /// the index within the capacity, which never include `parent_hash`.
+    pub fn on_spilled(
+        &mut self, parent_hash: H256, header_count: usize,
+    ) -> Vec<H256> {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        let group = self.groups.entry(parent_hash).or_insert(SpilledGroup {
+            header_count: 0,
+            seq,
+        });
+        group.header_count += header_count;
+        group.seq = seq;
+        self.header_count += header_count;
+        self.order.push_back((seq, parent_hash));
+
+        let mut dropped = Vec::new();
+        while self.header_count > self.capacity {
+            let (seq, hash) = match self.order.front() {
+                Some(front) => *front,
+                None => break,
+            };
+            if hash == parent_hash && seq == self.groups[&hash].seq {
+                break;
+            }
+            self.order.pop_front();
+            if self.groups.get(&hash).map_or(false, |g| g.seq == seq) {
+                self.take(&hash);
+                dropped.push(hash);
+            }
+        }
+        if self.order.len() > 2 * self.groups.len() + 16 {
+            let groups = &self.groups;
+            self.order.retain(|(seq, hash)| {
+                groups.get(hash).map_or(false, |g| g.seq == *seq)
+            });
+        }
+        dropped
+    }
+
+    /// Stop tracking the group of `parent_hash` and return its size.
+    pub fn take(&mut self, parent_hash: &H256) -> Option<usize> {
+        let group = self.groups.remove(parent_hash)?;
+        self.header_count -= group.header_count;
+        Some(group.header_count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SpilledHeaderIndex;
+    use cfx_types::H256;
+
+    #[test]
+    fn test_spilled_header_index() {
+        let hashes: Vec<H256> = (0..4).map(H256::from_low_u64_be).collect();
+        let mut index = SpilledHeaderIndex::new(10);
+        assert!(index.on_spilled(hashes[0], 4).is_empty());
+        assert!(index.on_spilled(hashes[1], 4).is_empty());
+        // Spilling into the first group again makes it the most recent one.
+        assert!(index.on_spilled(hashes[0], 1).is_empty());
+        assert_eq!(index.header_count(), 9);
+
+        assert_eq!(index.on_spilled(hashes[2], 2), vec![hashes[1]]);
+        assert_eq!(index.header_count(), 7);
+        assert!(!index.contains(&hashes[1]));
+
+        assert_eq!(index.take(&hashes[0]), Some(5));
+        assert_eq!(index.take(&hashes[0]), None);
+        assert_eq!(index.header_count(), 2);
+
+        // A group larger than the capacity is kept alone.
+        assert_eq!(index.on_spilled(hashes[3], 20), vec![hashes[2]]);
+        assert_eq!(index.header_count(), 20);
+    }
+}
@@ -0,0 +1,153 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// The memory pressure ends when the usage drops below this percentage of the
+/// budget, so that body requests are not paused and resumed on every update
+/// when the usage stays around the budget.
+const RESUME_PERCENTAGE: usize = 90;
+
+/// A snapshot of the memory held by block synchronization.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SyncMemoryUsage {
+    /// The heap size of the sync graph arena and its indices.
+    pub sync_graph_bytes: usize,
+    /// The heap size of the block data caches.
+    pub block_cache_bytes: usize,
+    /// The number of graph-ready blocks waiting to be processed by consensus.
+    pub consensus_queue_len: usize,
+    /// The number of blocks whose bodies are to be requested.
+    pub block_to_fill_len: usize,
+}
+
+impl SyncMemoryUsage {
+    pub fn total_bytes(&self) -> usize {
+        self.sync_graph_bytes + self.block_cache_bytes
+    }
+}
+
+/// `SyncMemoryGovernor` checks the memory held by block synchronization
+/// against a budget. While the budget is exceeded, the node stops requesting
+/// block bodies and rejects the headers that do not connect to the sync graph,
+/// so the memory is only used for the blocks that can make progress.
+///
+/// The usage is measured periodically with `update()`, and the insertion path
+/// only reads the latest result.
+pub struct SyncMemoryGovernor {
+    /// The budget of `SyncMemoryUsage::total_bytes()`. `None` means no limit.
+    max_bytes: Option<usize>,
+    /// The budget of `SyncMemoryUsage::consensus_queue_len`. `None` means no
+    /// limit.
+    max_consensus_queue_len: Option<usize>,
+    under_pressure: AtomicBool,
+    usage: Mutex<SyncMemoryUsage>,
+}
+
+impl SyncMemoryGovernor {
+    pub fn new(
+        max_bytes: Option<usize>, max_consensus_queue_len: Option<usize>,
+    ) -> Self
+    {
+        SyncMemoryGovernor {
+            max_bytes,
+            max_consensus_queue_len,
+            under_pressure: AtomicBool::new(false),
+            usage: Mutex::new(SyncMemoryUsage::default()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.max_bytes.is_some() || self.max_consensus_queue_len.is_some()
+    }
+
+    /// Return `true` if the latest measured usage exceeds the budget.
+    pub fn under_pressure(&self) -> bool {
+        self.under_pressure.load(Ordering::Relaxed)
+    }
+
+    pub fn usage(&self) -> SyncMemoryUsage { *self.usage.lock() }
+
+    /// Record the latest measured usage, and return whether the node is under
+    /// memory pressure after this update.
+    pub fn update(&self, usage: SyncMemoryUsage) -> bool {
+        let mut last_usage = self.usage.lock();
+        *last_usage = usage;
+        let was_under_pressure = self.under_pressure();
+        let under_pressure = if was_under_pressure {
+            self.exceeds(&usage, RESUME_PERCENTAGE)
+        } else {
+            self.exceeds(&usage, 100)
+        };
+        if under_pressure != was_under_pressure {
+            if under_pressure {
+                warn!(
+                    "Sync memory budget exceeded, pause block body requests: \
+                     usage={:?} max_bytes={:?} max_consensus_queue_len={:?}",
+                    usage, self.max_bytes, self.max_consensus_queue_len
+                );
+            } else {
+                info!(
+                    "Sync memory usage is back within budget, resume block \
+                     body requests: usage={:?}",
+                    usage
+                );
+            }
+        }
+        self.under_pressure.store(under_pressure, Ordering::Relaxed);
+        under_pressure
+    }
+
+    fn exceeds(&self, usage: &SyncMemoryUsage, percentage: usize) -> bool {
+        let over = |value: usize, limit: Option<usize>| {
+            limit.map_or(false, |limit| {
+                value as u128 * 100 > limit as u128 * percentage as u128
+            })
+        };
+        over(usage.total_bytes(), self.max_bytes)
+            || over(usage.consensus_queue_len, self.max_consensus_queue_len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SyncMemoryGovernor, SyncMemoryUsage};
+
+    fn usage(bytes: usize, consensus_queue_len: usize) -> SyncMemoryUsage {
+        SyncMemoryUsage {
+            sync_graph_bytes: bytes,
+            block_cache_bytes: 0,
+            consensus_queue_len,
+            block_to_fill_len: 0,
+        }
+    }
+
+    #[test]
+    fn test_disabled() {
+        let governor = SyncMemoryGovernor::new(None, None);
+        assert!(!governor.is_enabled());
+        assert!(!governor.update(usage(usize::MAX, usize::MAX)));
+    }
+
+    #[test]
+    fn test_pressure_hysteresis() {
+        let governor = SyncMemoryGovernor::new(Some(1000), None);
+        assert!(!governor.update(usage(1000, 0)));
+        assert!(governor.update(usage(1001, 0)));
+        // The pressure is kept until the usage drops below 90% of the budget.
+        assert!(governor.update(usage(950, 0)));
+        assert!(governor.under_pressure());
+        assert!(!governor.update(usage(900, 0)));
+        assert!(!governor.under_pressure());
+    }
+
+    #[test]
+    fn test_consensus_queue_limit() {
+        let governor = SyncMemoryGovernor::new(None, Some(10));
+        assert!(!governor.update(usage(usize::MAX, 10)));
+        assert!(governor.update(usage(0, 11)));
+        assert!(!governor.update(usage(0, 9)));
+    }
+}
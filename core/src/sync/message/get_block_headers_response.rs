@@ -21,10 +21,7 @@ use metrics::MeterTimer;
 use network::node_table::NodeId;
 use primitives::BlockHeader;
 use rlp_derive::{RlpDecodable, RlpEncodable};
-use std::{
-    collections::HashSet,
-    time::{Duration, SystemTime, UNIX_EPOCH},
-};
+use std::{collections::HashSet, time::Duration};
 
 #[derive(Debug, PartialEq, Default, RlpDecodable, RlpEncodable)]
 pub struct GetBlockHeadersResponse {
@@ -73,10 +70,7 @@ impl Handleable for GetBlockHeadersResponse {
         )?;
 
         // keep first time drift validation error to return later
-        let now_timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+        let now_timestamp = ctx.manager.graph.clock.now_as_secs();
 
         let timestamp_validation_result =
             if ctx.manager.graph.verification_config.verify_timestamp {
@@ -136,10 +130,7 @@ impl GetBlockHeadersResponse {
         let mut need_to_relay = Vec::new();
         let mut returned_headers = HashSet::new();
         let best_height = ctx.manager.graph.consensus.best_epoch_number();
-        let now_timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+        let now_timestamp = ctx.manager.graph.clock.now_as_secs();
         let mut has_invalid_header = false;
         for header in block_headers {
             let hash = header.hash();
@@ -209,9 +200,23 @@ impl GetBlockHeadersResponse {
             if insert_result.is_invalid() {
                 has_invalid_header = true;
                 continue;
+            } else if insert_result.is_busy()
+                || insert_result.is_recently_expired()
+            {
+                // Regard the header as not received, so it is requested
+                // again with a delay.
+                returned_headers.remove(&hash);
+                continue;
             } else if !insert_result.is_new_valid() {
                 continue;
             }
+            // Headers loaded from the local database have no source peer.
+            if !ctx.io.is_peer_self(&ctx.node_id) {
+                ctx.manager
+                    .graph
+                    .statistics
+                    .record_block_source(header.hash(), ctx.node_id);
+            }
 
             // check missing dependencies
             let parent = header.parent_hash();
@@ -85,6 +85,15 @@ fn on_new_decoded_block(
                 );
             if insert_result.is_new_valid() {
                 need_to_relay.extend(to_relay);
+            } else if insert_result.is_busy() {
+                return Err(Error::from_kind(ErrorKind::Busy(format!(
+                    "new block {:?} is not accepted",
+                    hash
+                ))));
+            } else if insert_result.is_recently_expired() {
+                // The block is not invalid, so the peer is not penalized.
+                debug!("new block {:?} expired recently, ignored", hash);
+                return Ok(need_to_relay);
             } else {
                 return Err(Error::from_kind(ErrorKind::InvalidBlock));
             }
@@ -18,10 +18,7 @@ use crate::{
         Error, ProtocolConfiguration, SYNC_PROTO_V1, SYNC_PROTO_V3,
     },
 };
-use cfx_parameters::{
-    consensus::DEFERRED_STATE_EPOCH_COUNT,
-    consensus_internal::REWARD_EPOCH_COUNT,
-};
+use cfx_parameters::consensus_internal::REWARD_EPOCH_COUNT;
 use cfx_types::H256;
 use malloc_size_of_derive::MallocSizeOf as DeriveMallocSizeOf;
 use network::service::ProtocolVersion;
@@ -219,7 +216,13 @@ impl SnapshotManifestRequest {
         let mut trusted_block_height = trusted_block.height();
         let mut blame_count = trusted_block.blame();
         let mut deferred_block_hash = block_hash;
-        for _ in 0..DEFERRED_STATE_EPOCH_COUNT {
+        let deferred_state_height = ctx
+            .manager
+            .graph
+            .consensus
+            .deferred_state_epoch_count()
+            .state_height(trusted_block.height());
+        for _ in deferred_state_height..trusted_block.height() {
             deferred_block_hash = *ctx
                 .manager
                 .graph
@@ -230,14 +233,9 @@ impl SnapshotManifestRequest {
         }
 
         let min_vec_len = if snapshot_epoch_block.height() == 0 {
-            trusted_block.height()
-                - DEFERRED_STATE_EPOCH_COUNT
-                - snapshot_epoch_block.height()
-                + 1
+            deferred_state_height - snapshot_epoch_block.height() + 1
         } else {
-            trusted_block.height()
-                - DEFERRED_STATE_EPOCH_COUNT
-                - snapshot_epoch_block.height()
+            deferred_state_height - snapshot_epoch_block.height()
                 + REWARD_EPOCH_COUNT
         };
         let mut state_root_vec = Vec::with_capacity(min_vec_len as usize);
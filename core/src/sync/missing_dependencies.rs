@@ -0,0 +1,138 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfx_types::H256;
+use malloc_size_of_derive::MallocSizeOf as DeriveMallocSizeOf;
+use std::collections::HashMap;
+
+/// The delay before a missing header is requested again. It doubles with
+/// each further request of the same header.
+const BASE_REREQUEST_BACKOFF_SECS: u64 = 5;
+const MAX_REREQUEST_BACKOFF_SECS: u64 = 600;
+
+#[derive(DeriveMallocSizeOf)]
+struct MissingDependency {
+    /// The number of headers waiting for this one, directly or spilled.
+    blocked_count: usize,
+    request_count: u32,
+    next_request_secs: u64,
+}
+
+/// `MissingDependencyTracker` tracks the parents and referees that the
+/// headers in the sync graph wait for but are not received. They are
+/// requested once when the waiting headers are received, and the tracker
+/// schedules the requests again with exponential backoff in case the first
+/// ones are lost, preferring the hashes that block the most headers.
+#[derive(Default, DeriveMallocSizeOf)]
+pub struct MissingDependencyTracker {
+    dependencies: HashMap<H256, MissingDependency>,
+}
+
+impl MissingDependencyTracker {
+    /// Replace the tracked hashes with those in `blocked_counts`. The backoff
+    /// of the hashes still missing is kept. The new ones have just been
+    /// requested when they are found missing, so they are due after the base
+    /// backoff.
+    pub fn update(&mut self, blocked_counts: HashMap<H256, usize>, now: u64) {
+        let mut dependencies = HashMap::with_capacity(blocked_counts.len());
+        for (hash, blocked_count) in blocked_counts {
+            let dependency = match self.dependencies.remove(&hash) {
+                Some(dependency) => MissingDependency {
+                    blocked_count,
+                    ..dependency
+                },
+                None => MissingDependency {
+                    blocked_count,
+                    request_count: 0,
+                    next_request_secs: now + BASE_REREQUEST_BACKOFF_SECS,
+                },
+            };
+            dependencies.insert(hash, dependency);
+        }
+        self.dependencies = dependencies;
+    }
+
+    /// Return at most `limit` tracked hashes with the numbers of headers
+    /// they block, the most blocking first.
+    pub fn most_wanted(&self, limit: usize) -> Vec<(H256, usize)> {
+        let mut wanted: Vec<_> = self
+            .dependencies
+            .iter()
+            .map(|(hash, dependency)| (*hash, dependency.blocked_count))
+            .collect();
+        wanted.sort_by(|(h1, c1), (h2, c2)| c2.cmp(c1).then(h1.cmp(h2)));
+        wanted.truncate(limit);
+        wanted
+    }
+
+    /// Return at most `limit` hashes due to be requested again at `now`, the
+    /// most blocking first, and back them off.
+    pub fn take_due(&mut self, now: u64, limit: usize) -> Vec<H256> {
+        let mut due: Vec<_> = self
+            .dependencies
+            .iter()
+            .filter(|(_, dependency)| dependency.next_request_secs <= now)
+            .map(|(hash, dependency)| (*hash, dependency.blocked_count))
+            .collect();
+        due.sort_by(|(h1, c1), (h2, c2)| c2.cmp(c1).then(h1.cmp(h2)));
+        due.truncate(limit);
+        due.into_iter()
+            .map(|(hash, _)| {
+                let dependency = self
+                    .dependencies
+                    .get_mut(&hash)
+                    .expect("collected from dependencies");
+                dependency.request_count =
+                    dependency.request_count.saturating_add(1);
+                dependency.next_request_secs =
+                    now + Self::backoff_secs(dependency.request_count);
+                hash
+            })
+            .collect()
+    }
+
+    pub fn len(&self) -> usize { self.dependencies.len() }
+
+    fn backoff_secs(request_count: u32) -> u64 {
+        let shift = request_count.min(32);
+        BASE_REREQUEST_BACKOFF_SECS
+            .saturating_mul(1u64 << shift)
+            .min(MAX_REREQUEST_BACKOFF_SECS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MissingDependencyTracker, BASE_REREQUEST_BACKOFF_SECS};
+    use cfx_types::H256;
+
+    #[test]
+    fn test_rerequest_backoff() {
+        let hashes: Vec<H256> = (0..3).map(H256::from_low_u64_be).collect();
+        let mut tracker = MissingDependencyTracker::default();
+        let base = BASE_REREQUEST_BACKOFF_SECS;
+        tracker.update(
+            vec![(hashes[0], 1), (hashes[1], 3)].into_iter().collect(),
+            0,
+        );
+        assert_eq!(tracker.most_wanted(1), vec![(hashes[1], 3)]);
+        // The new hashes have just been requested.
+        assert!(tracker.take_due(base - 1, 10).is_empty());
+        assert_eq!(tracker.take_due(base, 1), vec![hashes[1]]);
+        assert_eq!(tracker.take_due(base, 10), vec![hashes[0]]);
+
+        // The backoff is kept for the hashes still missing, and the received
+        // ones are dropped.
+        tracker.update(
+            vec![(hashes[1], 5), (hashes[2], 1)].into_iter().collect(),
+            base,
+        );
+        assert_eq!(tracker.len(), 2);
+        assert_eq!(tracker.take_due(2 * base, 10), vec![hashes[2]]);
+        assert_eq!(tracker.take_due(3 * base, 10), vec![hashes[1]]);
+        // The second backoff of `hashes[1]` is doubled.
+        assert_eq!(tracker.take_due(6 * base, 10), vec![hashes[2]]);
+        assert_eq!(tracker.take_due(7 * base, 10), vec![hashes[1]]);
+    }
+}
@@ -1,8 +1,15 @@
 // Copyright 2019 Conflux Foundation. All rights reserved.
 // Conflux is free software and distributed under GNU General Public License.
 // See http://www.gnu.org/licenses/
+mod block_propagation;
 mod error;
+mod expired_blocks;
+mod header_spill;
+mod memory_governor;
 pub mod message;
+mod missing_dependencies;
+pub mod replica_feed;
+pub mod replica_snapshot;
 pub mod request_manager;
 mod state;
 
@@ -17,8 +24,12 @@ mod synchronization_state;
 pub mod utils;
 
 pub use self::{
+    block_propagation::BlockPropagationRecorder,
     error::{Error, ErrorKind},
-    state::StateSyncConfiguration,
+    memory_governor::{SyncMemoryGovernor, SyncMemoryUsage},
+    state::{
+        snapshot_archive::SnapshotArchiveManifest, StateSyncConfiguration,
+    },
     synchronization_graph::{
         SharedSynchronizationGraph, SyncGraphConfig, SyncGraphStatistics,
         SynchronizationGraph, SynchronizationGraphInner,
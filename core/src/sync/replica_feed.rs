@@ -0,0 +1,349 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+//! The replica mode, in which a node ingests the blocks of a trusted primary
+//! node from a block feed instead of syncing them from peers.
+//!
+//! A block feed is a stream of blocks encoded with
+//! `Block::encode_with_tx_public`, in a topological order. It is read from a
+//! file that may still be appended to, or from a TCP connection that is
+//! reconnected when it is closed. The blocks are verified like the blocks
+//! received from peers, and each new block is processed by consensus and the
+//! best state is executed before the next block is inserted, so the replica
+//! stays in lockstep with the feed, and the execution results can be recorded
+//! in a consistency snapshot after each block.
+
+use crate::{
+    consensus::{ConsensusGraph, ConsensusGraphTrait},
+    sync::{
+        replica_snapshot::ConsistencySnapshotWriter,
+        SharedSynchronizationGraph, SynchronizationService,
+    },
+};
+use primitives::Block;
+use rlp::Rlp;
+use std::{
+    fs::File,
+    io::{self, Read},
+    net::TcpStream,
+    path::PathBuf,
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+
+/// The maximum encoded size of a block in a feed, to avoid allocating
+/// unbounded memory for a corrupted feed.
+const MAX_FEED_BLOCK_SIZE: usize = 32 * 1024 * 1024;
+/// The interval to poll a feed file for appended blocks, to reconnect a closed
+/// feed connection, or to check whether the initial catch-up finishes.
+const FEED_POLL_INTERVAL: Duration = Duration::from_secs(1);
+const FEED_PROGRESS_LOG_INTERVAL: u64 = 1000;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReplicaFeedSource {
+    File(PathBuf),
+    Tcp(String),
+}
+
+impl ReplicaFeedSource {
+    /// Parse a feed source, which is either `tcp://<host>:<port>` or a file
+    /// path.
+    pub fn parse(source: &str) -> Self {
+        if source.starts_with("tcp://") {
+            ReplicaFeedSource::Tcp(source["tcp://".len()..].to_owned())
+        } else {
+            ReplicaFeedSource::File(source.into())
+        }
+    }
+}
+
+/// Decode the blocks in a block feed one at a time.
+pub struct BlockFeedReader<R> {
+    reader: R,
+}
+
+impl<R: Read> BlockFeedReader<R> {
+    pub fn new(reader: R) -> Self { BlockFeedReader { reader } }
+
+    /// Read the next block. Return `None` if the feed ends before the next
+    /// block starts.
+    pub fn next_block(&mut self) -> Result<Option<Block>, String> {
+        let mut prefix = [0u8; 1];
+        loop {
+            match self.reader.read(&mut prefix) {
+                Ok(0) => return Ok(None),
+                Ok(_) => break,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(format!("Failed to read feed: {}", e)),
+            }
+        }
+        let prefix = prefix[0];
+        // A block is encoded as an RLP list.
+        let length_of_length = match prefix {
+            0xc0..=0xf7 => 0,
+            0xf8..=0xff => (prefix - 0xf7) as usize,
+            _ => {
+                return Err(format!(
+                    "Invalid block prefix in feed: {:#x}",
+                    prefix
+                ))
+            }
+        };
+        let mut item = vec![0u8; 1 + length_of_length];
+        item[0] = prefix;
+        self.read_exact(&mut item[1..])?;
+        let payload_len = if length_of_length == 0 {
+            (prefix - 0xc0) as usize
+        } else {
+            item[1..]
+                .iter()
+                .fold(0usize, |len, b| len.saturating_mul(256) + *b as usize)
+        };
+        if payload_len > MAX_FEED_BLOCK_SIZE {
+            return Err(format!("Block in feed is too large: {}", payload_len));
+        }
+        let header_len = item.len();
+        item.resize(header_len + payload_len, 0);
+        self.read_exact(&mut item[header_len..])?;
+        Block::decode_with_tx_public(&Rlp::new(&item))
+            .map(Some)
+            .map_err(|e| format!("Failed to decode block in feed: {:?}", e))
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), String> {
+        self.reader
+            .read_exact(buf)
+            .map_err(|e| format!("Failed to read feed: {}", e))
+    }
+}
+
+/// A reader that waits for more data at the end of a file instead of
+/// returning EOF, so the blocks appended to a feed file are read.
+struct FollowingReader<R> {
+    reader: R,
+}
+
+impl<R: Read> Read for FollowingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let n = self.reader.read(buf)?;
+            if n != 0 || buf.is_empty() {
+                return Ok(n);
+            }
+            thread::sleep(FEED_POLL_INTERVAL);
+        }
+    }
+}
+
+pub struct ReplicaBlockFeed {
+    source: ReplicaFeedSource,
+    sync_graph: SharedSynchronizationGraph,
+    consensus: Arc<ConsensusGraph>,
+    inserted_count: u64,
+    skipped_count: u64,
+    snapshot_path: Option<PathBuf>,
+    snapshot: Option<ConsistencySnapshotWriter>,
+}
+
+impl ReplicaBlockFeed {
+    /// Start ingesting the blocks from `source` in a new thread after the
+    /// blocks in the local database are recovered. If `snapshot_path` is set,
+    /// the execution results are recorded there after each inserted block.
+    pub fn start(
+        source: ReplicaFeedSource, sync: Arc<SynchronizationService>,
+        consensus: Arc<ConsensusGraph>, snapshot_path: Option<PathBuf>,
+    ) -> io::Result<()>
+    {
+        let mut feed = ReplicaBlockFeed {
+            source,
+            sync_graph: sync.get_synchronization_graph(),
+            consensus,
+            inserted_count: 0,
+            skipped_count: 0,
+            snapshot_path,
+            snapshot: None,
+        };
+        thread::Builder::new()
+            .name("Replica block feed".into())
+            .spawn(move || {
+                while sync.catch_up_mode() {
+                    thread::sleep(FEED_POLL_INTERVAL);
+                }
+                feed.run();
+            })?;
+        Ok(())
+    }
+
+    fn run(&mut self) {
+        info!("Start ingesting blocks from replica feed {:?}", self.source);
+        if let Some(path) = &self.snapshot_path {
+            match ConsistencySnapshotWriter::create(
+                path,
+                self.consensus.best_executed_state_epoch_number(),
+            ) {
+                Ok(snapshot) => self.snapshot = Some(snapshot),
+                Err(e) => error!(
+                    "Failed to create consistency snapshot {:?}: {}",
+                    path, e
+                ),
+            }
+        }
+        match self.source.clone() {
+            ReplicaFeedSource::File(path) => {
+                let file = match File::open(&path) {
+                    Ok(file) => file,
+                    Err(e) => {
+                        error!("Failed to open replica feed {:?}: {}", path, e);
+                        return;
+                    }
+                };
+                let mut reader =
+                    BlockFeedReader::new(FollowingReader { reader: file });
+                if let Err(e) = self.ingest(&mut reader) {
+                    error!("Replica feed {:?} stopped: {}", path, e);
+                }
+            }
+            ReplicaFeedSource::Tcp(address) => loop {
+                match TcpStream::connect(&address) {
+                    Ok(stream) => {
+                        let mut reader = BlockFeedReader::new(stream);
+                        match self.ingest(&mut reader) {
+                            Ok(()) => info!(
+                                "Replica feed connection to {} is closed",
+                                address
+                            ),
+                            Err(e) => warn!(
+                                "Replica feed connection to {} failed: {}",
+                                address, e
+                            ),
+                        }
+                    }
+                    Err(e) => warn!(
+                        "Failed to connect to replica feed {}: {}",
+                        address, e
+                    ),
+                }
+                thread::sleep(FEED_POLL_INTERVAL);
+            },
+        }
+    }
+
+    /// Insert the blocks from `reader` until the feed ends.
+    fn ingest<R: Read>(
+        &mut self, reader: &mut BlockFeedReader<R>,
+    ) -> Result<(), String> {
+        while let Some(block) = reader.next_block()? {
+            self.insert_block(block);
+        }
+        Ok(())
+    }
+
+    fn insert_block(&mut self, mut block: Block) {
+        let hash = block.hash();
+        if self.sync_graph.contains_block(&hash) {
+            // The blocks may be sent again after reconnection.
+            return;
+        }
+        let header = &block.block_header;
+        if !self.sync_graph.contains_block(header.parent_hash())
+            || header
+                .referee_hashes()
+                .iter()
+                .any(|referee| !self.sync_graph.contains_block(referee))
+        {
+            warn!(
+                "Skip block {:?} from replica feed: the parent or a referee is missing",
+                hash
+            );
+            self.skipped_count += 1;
+            return;
+        }
+
+        let (header_result, _) = self.sync_graph.insert_block_header(
+            &mut block.block_header,
+            true,  /* need_to_verify */
+            false, /* bench_mode */
+            false, /* insert_to_consensus */
+            true,  /* persistent */
+        );
+        if header_result.is_invalid() {
+            warn!("Invalid block header {:?} from replica feed", hash);
+            self.skipped_count += 1;
+            return;
+        }
+        let result = self.sync_graph.insert_block(
+            block, true,  /* need_to_verify */
+            true,  /* persistent */
+            false, /* recover_from_db */
+        );
+        if !result.should_relay() {
+            warn!("Block {:?} from replica feed is not inserted", hash);
+            self.skipped_count += 1;
+            return;
+        }
+        self.consensus.wait_for_generation(&hash);
+        self.inserted_count += 1;
+        if let Some(snapshot) = &mut self.snapshot {
+            if let Err(e) =
+                snapshot.record(self.inserted_count, &self.consensus)
+            {
+                error!("Stop recording consistency snapshot: {}", e);
+                self.snapshot = None;
+            }
+        }
+        if self.inserted_count % FEED_PROGRESS_LOG_INTERVAL == 0 {
+            info!(
+                "Replica feed progress: inserted={} skipped={} best_epoch={}",
+                self.inserted_count,
+                self.skipped_count,
+                self.consensus.best_epoch_number()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BlockFeedReader, ReplicaFeedSource};
+    use primitives::{Block, BlockHeaderBuilder};
+    use std::io::Cursor;
+
+    #[test]
+    fn test_read_block_feed() {
+        let mut feed = Vec::new();
+        let mut hashes = Vec::new();
+        for height in 1..4 {
+            let header = BlockHeaderBuilder::new().with_height(height).build();
+            let block = Block::new(header, vec![]);
+            hashes.push(block.hash());
+            feed.extend(block.encode_with_tx_public());
+        }
+
+        let mut reader = BlockFeedReader::new(Cursor::new(feed.clone()));
+        for hash in &hashes {
+            assert_eq!(reader.next_block().unwrap().unwrap().hash(), *hash);
+        }
+        assert!(reader.next_block().unwrap().is_none());
+
+        // A truncated block is an error.
+        let mut reader =
+            BlockFeedReader::new(Cursor::new(feed[..feed.len() - 1].to_vec()));
+        reader.next_block().unwrap();
+        reader.next_block().unwrap();
+        assert!(reader.next_block().is_err());
+    }
+
+    #[test]
+    fn test_parse_feed_source() {
+        assert_eq!(
+            ReplicaFeedSource::parse("tcp://127.0.0.1:32525"),
+            ReplicaFeedSource::Tcp("127.0.0.1:32525".into())
+        );
+        assert_eq!(
+            ReplicaFeedSource::parse("/data/blocks.feed"),
+            ReplicaFeedSource::File("/data/blocks.feed".into())
+        );
+    }
+}
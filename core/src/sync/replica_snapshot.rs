@@ -0,0 +1,300 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+//! Consistency snapshots of a replica, to detect nondeterminism in consensus
+//! processing and execution.
+//!
+//! A replica processes the blocks of its feed in lockstep, so two replicas
+//! started from the same database and fed with the same feed must have the
+//! same pivot chain and the same execution commitments after each block, no
+//! matter how many execution workers or pipeline stages they use. With
+//! `replica_consistency_snapshot` set, a replica appends one line for each
+//! newly executed epoch after each inserted block, and `conflux replica-diff`
+//! compares the snapshots of two runs epoch by epoch.
+
+use crate::consensus::{ConsensusGraph, ConsensusGraphTrait};
+use cfx_types::H256;
+use primitives::EpochNumber;
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    str::FromStr,
+};
+
+/// The number of recorded pivot blocks kept to detect pivot chain switches.
+/// Deeper switches are recorded from the oldest kept epoch.
+const MAX_SNAPSHOT_REORG_DEPTH: usize = 1000;
+
+/// The execution result of an epoch after some blocks in the feed are
+/// processed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SnapshotRecord {
+    /// The number of blocks inserted from the feed.
+    pub block_count: u64,
+    pub epoch_number: u64,
+    pub pivot_hash: H256,
+    pub state_root: H256,
+    pub receipts_root: H256,
+    pub logs_bloom_hash: H256,
+}
+
+impl SnapshotRecord {
+    pub fn to_line(&self) -> String {
+        format!(
+            "{} {} {:x} {:x} {:x} {:x}",
+            self.block_count,
+            self.epoch_number,
+            self.pivot_hash,
+            self.state_root,
+            self.receipts_root,
+            self.logs_bloom_hash
+        )
+    }
+
+    pub fn parse_line(line: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() != 6 {
+            return Err(format!("Invalid snapshot record: {:?}", line));
+        }
+        let number = |s: &str| {
+            u64::from_str(s)
+                .map_err(|e| format!("Invalid number {:?}: {}", s, e))
+        };
+        let hash = |s: &str| {
+            H256::from_str(s)
+                .map_err(|e| format!("Invalid hash {:?}: {}", s, e))
+        };
+        Ok(SnapshotRecord {
+            block_count: number(fields[0])?,
+            epoch_number: number(fields[1])?,
+            pivot_hash: hash(fields[2])?,
+            state_root: hash(fields[3])?,
+            receipts_root: hash(fields[4])?,
+            logs_bloom_hash: hash(fields[5])?,
+        })
+    }
+
+    /// Return the names of the fields different from `other`.
+    fn different_fields(&self, other: &SnapshotRecord) -> Vec<&'static str> {
+        let mut fields = Vec::new();
+        if self.pivot_hash != other.pivot_hash {
+            fields.push("pivot_hash");
+        }
+        if self.state_root != other.state_root {
+            fields.push("state_root");
+        }
+        if self.receipts_root != other.receipts_root {
+            fields.push("receipts_root");
+        }
+        if self.logs_bloom_hash != other.logs_bloom_hash {
+            fields.push("logs_bloom_hash");
+        }
+        fields
+    }
+}
+
+pub fn read_snapshot(path: &Path) -> Result<Vec<SnapshotRecord>, String> {
+    let file = File::open(path)
+        .map_err(|e| format!("Failed to open snapshot {:?}: {}", path, e))?;
+    let mut records = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|e| {
+            format!("Failed to read snapshot {:?}: {}", path, e)
+        })?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        records.push(SnapshotRecord::parse_line(&line)?);
+    }
+    Ok(records)
+}
+
+/// Appends the records of a replica to a snapshot file.
+pub struct ConsistencySnapshotWriter {
+    out: BufWriter<File>,
+    /// The first epoch not recorded on the current pivot chain.
+    next_epoch: u64,
+    /// The pivot hashes of the recorded epochs before `next_epoch`.
+    recorded_pivot: VecDeque<H256>,
+}
+
+impl ConsistencySnapshotWriter {
+    /// Create the snapshot file. The epochs after `executed_epoch` will be
+    /// recorded.
+    pub fn create(path: &Path, executed_epoch: u64) -> io::Result<Self> {
+        Ok(ConsistencySnapshotWriter {
+            out: BufWriter::new(File::create(path)?),
+            next_epoch: executed_epoch + 1,
+            recorded_pivot: VecDeque::new(),
+        })
+    }
+
+    /// Record the epochs executed after the `block_count`-th block in the
+    /// feed is processed, including the epochs whose pivot blocks change.
+    pub fn record(
+        &mut self, block_count: u64, consensus: &ConsensusGraph,
+    ) -> Result<(), String> {
+        let executed_epoch = consensus.best_executed_state_epoch_number();
+        while let Some(hash) = self.recorded_pivot.back().cloned() {
+            let epoch = self.next_epoch - 1;
+            if epoch <= executed_epoch
+                && consensus
+                    .get_hash_from_epoch_number(EpochNumber::Number(epoch))
+                    == Ok(hash)
+            {
+                break;
+            }
+            self.recorded_pivot.pop_back();
+            self.next_epoch = epoch;
+        }
+        while self.next_epoch <= executed_epoch {
+            let epoch = self.next_epoch;
+            let pivot_hash = consensus
+                .get_hash_from_epoch_number(EpochNumber::Number(epoch))?;
+            let commitment = consensus
+                .data_man
+                .get_epoch_execution_commitment_with_db(&pivot_hash)
+                .ok_or_else(|| {
+                    format!(
+                        "Execution commitment of epoch {} is missing",
+                        epoch
+                    )
+                })?;
+            let record = SnapshotRecord {
+                block_count,
+                epoch_number: epoch,
+                pivot_hash,
+                state_root: commitment
+                    .state_root_with_aux_info
+                    .state_root
+                    .compute_state_root_hash(),
+                receipts_root: commitment.receipts_root,
+                logs_bloom_hash: commitment.logs_bloom_hash,
+            };
+            writeln!(self.out, "{}", record.to_line())
+                .map_err(|e| format!("Failed to write snapshot: {}", e))?;
+            self.recorded_pivot.push_back(pivot_hash);
+            if self.recorded_pivot.len() > MAX_SNAPSHOT_REORG_DEPTH {
+                self.recorded_pivot.pop_front();
+            }
+            self.next_epoch += 1;
+        }
+        self.out
+            .flush()
+            .map_err(|e| format!("Failed to write snapshot: {}", e))
+    }
+}
+
+/// A record that differs between two snapshots, or that only one of them
+/// has.
+#[derive(Debug, PartialEq)]
+pub struct SnapshotDiff {
+    pub block_count: u64,
+    pub epoch_number: u64,
+    pub left: Option<SnapshotRecord>,
+    pub right: Option<SnapshotRecord>,
+    pub fields: Vec<&'static str>,
+}
+
+impl SnapshotDiff {
+    /// A different pivot chain means consensus processing is
+    /// nondeterministic, otherwise execution is.
+    pub fn is_pivot_mismatch(&self) -> bool {
+        self.left.is_none()
+            || self.right.is_none()
+            || self.fields.contains(&"pivot_hash")
+    }
+}
+
+/// Compare two snapshots epoch by epoch, up to the last block processed by
+/// both runs.
+pub fn diff_snapshots(
+    left: &[SnapshotRecord], right: &[SnapshotRecord],
+) -> Vec<SnapshotDiff> {
+    let last_block_count = match (left.last(), right.last()) {
+        (Some(l), Some(r)) => l.block_count.min(r.block_count),
+        _ => return Vec::new(),
+    };
+    let index = |records: &[SnapshotRecord]| {
+        records
+            .iter()
+            .filter(|r| r.block_count <= last_block_count)
+            .map(|r| ((r.block_count, r.epoch_number), r.clone()))
+            .collect::<BTreeMap<_, _>>()
+    };
+    let mut left = index(left);
+    let mut right = index(right);
+    let mut keys: Vec<_> = left.keys().chain(right.keys()).cloned().collect();
+    keys.sort();
+    keys.dedup();
+
+    let mut diffs = Vec::new();
+    for (block_count, epoch_number) in keys {
+        let l = left.remove(&(block_count, epoch_number));
+        let r = right.remove(&(block_count, epoch_number));
+        let fields = match (&l, &r) {
+            (Some(l), Some(r)) => l.different_fields(r),
+            _ => Vec::new(),
+        };
+        if l.is_some() && r.is_some() && fields.is_empty() {
+            continue;
+        }
+        diffs.push(SnapshotDiff {
+            block_count,
+            epoch_number,
+            left: l,
+            right: r,
+            fields,
+        });
+    }
+    diffs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff_snapshots, SnapshotRecord};
+    use cfx_types::H256;
+
+    fn record(
+        block_count: u64, epoch_number: u64, root: u64,
+    ) -> SnapshotRecord {
+        SnapshotRecord {
+            block_count,
+            epoch_number,
+            pivot_hash: H256::from_low_u64_be(epoch_number),
+            state_root: H256::from_low_u64_be(root),
+            receipts_root: H256::from_low_u64_be(root),
+            logs_bloom_hash: H256::zero(),
+        }
+    }
+
+    #[test]
+    fn test_record_line() {
+        let r = record(3, 2, 7);
+        assert_eq!(SnapshotRecord::parse_line(&r.to_line()).unwrap(), r);
+        assert!(SnapshotRecord::parse_line("3 2").is_err());
+    }
+
+    #[test]
+    fn test_diff_snapshots() {
+        let left = vec![record(1, 1, 1), record(2, 2, 2), record(3, 3, 3)];
+        let mut right = vec![record(1, 1, 1), record(2, 2, 5)];
+        let diffs = diff_snapshots(&left, &right);
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].epoch_number, 2);
+        assert_eq!(diffs[0].fields, vec!["state_root", "receipts_root"]);
+        assert!(!diffs[0].is_pivot_mismatch());
+
+        right[1] = record(2, 2, 2);
+        assert!(diff_snapshots(&left, &right).is_empty());
+
+        // The epoch is only executed in one run.
+        right.push(record(3, 2, 2));
+        let diffs = diff_snapshots(&left, &right);
+        assert_eq!(diffs.len(), 2);
+        assert!(diffs.iter().all(|d| d.is_pivot_mismatch()));
+    }
+}
@@ -0,0 +1,317 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use crate::sync::{
+    state::{
+        state_sync_chunk::restore::Restorer,
+        storage::{Chunk, RangedManifest, SnapshotSyncCandidate},
+    },
+    Error, ErrorKind,
+};
+use cfx_storage::{storage_db::SnapshotInfo, FullSyncVerifier, StorageManager};
+use cfx_types::H256;
+use hash::keccak;
+use primitives::{EpochId, MerkleHash};
+use rlp::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+const MANIFEST_FILE: &str = "manifest.rlp";
+
+/// The number of chunks cut from the snapshot MPT in one pass of the export.
+const MAX_CHUNKS_PER_PASS: usize = 1000;
+
+/// The manifest of a snapshot archive. It has the boundaries of all the
+/// chunks with their proofs, as in the manifests sent by the peers, and the
+/// hashes of the chunk files.
+#[derive(Clone, Default)]
+pub struct SnapshotArchiveManifest {
+    pub snapshot_epoch_id: EpochId,
+    pub snapshot_height: u64,
+    pub merkle_root: MerkleHash,
+    /// The chunk boundaries of the whole snapshot, so `next` is `None`.
+    pub ranged_manifest: RangedManifest,
+    /// The keccak hash of each chunk file, in the order of the chunks.
+    pub chunk_hashes: Vec<H256>,
+}
+
+impl Encodable for SnapshotArchiveManifest {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(5)
+            .append(&self.snapshot_epoch_id)
+            .append(&self.snapshot_height)
+            .append(&self.merkle_root)
+            .append(&self.ranged_manifest)
+            .append_list(&self.chunk_hashes);
+    }
+}
+
+impl Decodable for SnapshotArchiveManifest {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        Ok(SnapshotArchiveManifest {
+            snapshot_epoch_id: rlp.val_at(0)?,
+            snapshot_height: rlp.val_at(1)?,
+            merkle_root: rlp.val_at(2)?,
+            ranged_manifest: rlp.val_at(3)?,
+            chunk_hashes: rlp.list_at(4)?,
+        })
+    }
+}
+
+/// A snapshot exported to the files in a directory, one file for each chunk
+/// and the manifest written after all the chunks.
+///
+/// The archive itself is not trusted. It is only imported for a snapshot
+/// whose merkle root has been verified with the blame information, and each
+/// chunk is checked against its hash and its boundary proof.
+pub struct SnapshotArchive {
+    path: PathBuf,
+    pub manifest: SnapshotArchiveManifest,
+}
+
+impl SnapshotArchive {
+    /// Open the archive of `snapshot_epoch_id` in `dir`, or return `None` if
+    /// there is no complete archive of it.
+    pub fn open(
+        dir: &Path, snapshot_epoch_id: &EpochId,
+    ) -> Result<Option<Self>, Error> {
+        let path = archive_path(dir, snapshot_epoch_id);
+        let manifest_path = path.join(MANIFEST_FILE);
+        if !manifest_path.exists() {
+            return Ok(None);
+        }
+        let manifest: SnapshotArchiveManifest =
+            rlp::decode(&fs::read(manifest_path)?)?;
+        if manifest.snapshot_epoch_id != *snapshot_epoch_id {
+            bail!(ErrorKind::InvalidSnapshotManifest(
+                "snapshot epoch id mismatch in archive".into(),
+            ));
+        }
+        if manifest.chunk_hashes.len()
+            != manifest.ranged_manifest.chunk_boundaries.len() + 1
+        {
+            bail!(ErrorKind::InvalidSnapshotManifest(
+                "chunk and hash number do not match in archive".into(),
+            ));
+        }
+        Ok(Some(SnapshotArchive { path, manifest }))
+    }
+
+    pub fn num_chunks(&self) -> usize { self.manifest.chunk_hashes.len() }
+
+    /// Read the chunk at `index` and check it against its hash in the
+    /// manifest.
+    pub fn load_chunk(&self, index: usize) -> Result<Chunk, Error> {
+        let bytes = fs::read(chunk_path(&self.path, index))?;
+        if keccak(&bytes) != self.manifest.chunk_hashes[index] {
+            bail!(ErrorKind::InvalidSnapshotChunk(format!(
+                "hash mismatch of chunk {} in archive",
+                index
+            )));
+        }
+        Ok(rlp::decode(&bytes)?)
+    }
+}
+
+/// Export the snapshot of `snapshot_epoch_id` to an archive in `dir`, cut into
+/// chunks of about `chunk_size` bytes, and return its manifest. An existing
+/// archive of the snapshot is replaced.
+pub fn export_snapshot_archive(
+    storage_manager: &StorageManager, snapshot_epoch_id: &EpochId,
+    snapshot_height: u64, dir: &Path, chunk_size: u64,
+) -> Result<SnapshotArchiveManifest, Error>
+{
+    let path = archive_path(dir, snapshot_epoch_id);
+    fs::create_dir_all(&path)?;
+    // The archive is incomplete until the manifest is written again.
+    let manifest_path = path.join(MANIFEST_FILE);
+    if manifest_path.exists() {
+        fs::remove_file(&manifest_path)?;
+    }
+
+    let snapshot_to_sync = SnapshotSyncCandidate::FullSync {
+        height: snapshot_height,
+        snapshot_epoch_id: *snapshot_epoch_id,
+    };
+    let mut manifest = SnapshotArchiveManifest {
+        snapshot_epoch_id: *snapshot_epoch_id,
+        snapshot_height,
+        ..Default::default()
+    };
+    let mut start_key = None;
+    loop {
+        let (ranged_manifest, merkle_root) = RangedManifest::load(
+            &snapshot_to_sync,
+            start_key,
+            storage_manager,
+            chunk_size,
+            MAX_CHUNKS_PER_PASS,
+        )?
+        .ok_or_else(|| {
+            Error::from(format!("Snapshot {:?} not found", snapshot_epoch_id))
+        })?;
+        manifest.merkle_root = merkle_root;
+        manifest
+            .ranged_manifest
+            .chunk_boundaries
+            .extend(ranged_manifest.chunk_boundaries);
+        manifest
+            .ranged_manifest
+            .chunk_boundary_proofs
+            .extend(ranged_manifest.chunk_boundary_proofs);
+        start_key = ranged_manifest.next;
+        if start_key.is_none() {
+            break;
+        }
+    }
+
+    let chunk_keys = RangedManifest::convert_boundaries_to_chunks(
+        manifest.ranged_manifest.chunk_boundaries.clone(),
+    );
+    for (index, chunk_key) in chunk_keys.iter().enumerate() {
+        let chunk = Chunk::load(snapshot_epoch_id, chunk_key, storage_manager)?
+            .ok_or_else(|| {
+                Error::from(format!(
+                    "Snapshot {:?} removed during export",
+                    snapshot_epoch_id
+                ))
+            })?;
+        let bytes = chunk.rlp_bytes();
+        manifest.chunk_hashes.push(keccak(&bytes));
+        fs::write(chunk_path(&path, index), &bytes)?;
+    }
+    fs::write(manifest_path, &manifest.rlp_bytes())?;
+    info!(
+        "Exported snapshot {:?} at height {} with {} chunks to {:?}",
+        snapshot_epoch_id,
+        snapshot_height,
+        manifest.chunk_hashes.len(),
+        path
+    );
+    Ok(manifest)
+}
+
+/// Restore the snapshot of `snapshot_info` from its archive in `dir`. The
+/// merkle root in `snapshot_info` must be verified. Return `Ok(false)` if
+/// there is no archive of the snapshot.
+pub fn restore_snapshot_from_archive(
+    dir: &Path, snapshot_info: &SnapshotInfo,
+    storage_manager: Arc<StorageManager>,
+) -> Result<bool, Error>
+{
+    let snapshot_epoch_id = snapshot_info.get_snapshot_epoch_id();
+    let archive = match SnapshotArchive::open(dir, snapshot_epoch_id)? {
+        Some(archive) => archive,
+        None => return Ok(false),
+    };
+    let ranged_manifest = &archive.manifest.ranged_manifest;
+    if archive.manifest.merkle_root != snapshot_info.merkle_root {
+        bail!(ErrorKind::InvalidSnapshotManifest(
+            "merkle root mismatch in archive".into(),
+        ));
+    }
+    ranged_manifest.validate(&snapshot_info.merkle_root)?;
+
+    let verifier = FullSyncVerifier::new(
+        archive.num_chunks(),
+        ranged_manifest.chunk_boundaries.clone(),
+        ranged_manifest.chunk_boundary_proofs.clone(),
+        snapshot_info.merkle_root,
+        storage_manager
+            .get_storage_manager()
+            .get_snapshot_manager()
+            .get_snapshot_db_manager(),
+        snapshot_epoch_id,
+    )?;
+    let mut restorer =
+        Restorer::new(*snapshot_epoch_id, snapshot_info.merkle_root);
+    restorer.initialize_verifier(verifier);
+    let chunk_keys = RangedManifest::convert_boundaries_to_chunks(
+        ranged_manifest.chunk_boundaries.clone(),
+    );
+    for (index, chunk_key) in chunk_keys.into_iter().enumerate() {
+        let chunk = archive.load_chunk(index)?;
+        chunk.validate(&chunk_key)?;
+        if !restorer.append(chunk_key, chunk) {
+            bail!(ErrorKind::InvalidSnapshotChunk(format!(
+                "invalid proof of chunk {} in archive",
+                index
+            )));
+        }
+    }
+    restorer.finalize_restoration(storage_manager, snapshot_info.clone())?;
+    info!(
+        "Restored snapshot {:?} from {} chunks in archive",
+        snapshot_epoch_id,
+        archive.num_chunks()
+    );
+    Ok(true)
+}
+
+fn archive_path(dir: &Path, snapshot_epoch_id: &EpochId) -> PathBuf {
+    dir.join(format!("{:x}", snapshot_epoch_id))
+}
+
+fn chunk_path(path: &Path, index: usize) -> PathBuf {
+    path.join(format!("chunk_{}.rlp", index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        archive_path, chunk_path, SnapshotArchive, SnapshotArchiveManifest,
+        MANIFEST_FILE,
+    };
+    use crate::sync::state::storage::Chunk;
+    use cfx_types::H256;
+    use hash::keccak;
+    use rlp::Encodable;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_archive_chunks_are_hash_verified() {
+        let dir = TempDir::new("snapshot_archive").unwrap();
+        let snapshot_epoch_id = H256::from_low_u64_be(1);
+        let path = archive_path(dir.path(), &snapshot_epoch_id);
+        assert!(SnapshotArchive::open(dir.path(), &snapshot_epoch_id)
+            .unwrap()
+            .is_none());
+
+        let chunk = Chunk {
+            keys: vec![vec![1], vec![2]],
+            values: vec![vec![3], vec![4]],
+        };
+        let bytes = chunk.rlp_bytes();
+        fs::create_dir_all(&path).unwrap();
+        fs::write(chunk_path(&path, 0), &bytes).unwrap();
+        let manifest = SnapshotArchiveManifest {
+            snapshot_epoch_id,
+            snapshot_height: 2000,
+            merkle_root: H256::from_low_u64_be(2),
+            ranged_manifest: Default::default(),
+            chunk_hashes: vec![keccak(&bytes)],
+        };
+        fs::write(path.join(MANIFEST_FILE), &manifest.rlp_bytes()).unwrap();
+
+        let archive = SnapshotArchive::open(dir.path(), &snapshot_epoch_id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(archive.manifest.snapshot_height, 2000);
+        assert_eq!(archive.manifest.merkle_root, manifest.merkle_root);
+        assert_eq!(archive.num_chunks(), 1);
+        assert_eq!(archive.load_chunk(0).unwrap().keys, chunk.keys);
+
+        // A corrupted chunk is rejected.
+        fs::write(chunk_path(&path, 0), &[0xc0]).unwrap();
+        assert!(archive.load_chunk(0).is_err());
+        // The archive of another snapshot is not found.
+        assert!(SnapshotArchive::open(dir.path(), &H256::from_low_u64_be(3))
+            .unwrap()
+            .is_none());
+    }
+}
@@ -30,6 +30,7 @@ use primitives::EpochId;
 use std::{
     collections::HashSet,
     fmt::{Debug, Formatter},
+    path::Path,
     sync::Arc,
     time::{Duration, Instant},
 };
@@ -206,6 +207,10 @@ impl SnapshotChunkSync {
 
     pub fn status(&self) -> Status { self.inner.read().status }
 
+    pub fn archive_dir(&self) -> Option<&String> {
+        self.config.archive_dir.as_ref()
+    }
+
     pub fn handle_snapshot_manifest_response(
         &self, ctx: &Context, response: SnapshotManifestResponse,
         request: &SnapshotManifestRequest,
@@ -223,29 +228,63 @@ impl SnapshotChunkSync {
                 .handle_snapshot_manifest_response(ctx, response, request)?;
             if let Some(related_data) = r {
                 // update status
-                inner.status = Status::DownloadingChunks(Instant::now());
-                inner.chunk_manager =
-                    Some(SnapshotChunkManager::new_and_start(
-                        ctx,
-                        manifest_manager.snapshot_candidate.clone(),
-                        related_data.snapshot_info.clone(),
-                        manifest_manager.chunk_boundaries.clone(),
-                        manifest_manager.chunk_boundary_proofs.clone(),
-                        manifest_manager.active_peers.clone(),
-                        self.config.chunk_config(),
-                    )?);
+                if self.import_archive(ctx, &related_data) {
+                    inner.status = Status::Completed;
+                } else {
+                    inner.status = Status::DownloadingChunks(Instant::now());
+                    inner.chunk_manager =
+                        Some(SnapshotChunkManager::new_and_start(
+                            ctx,
+                            manifest_manager.snapshot_candidate.clone(),
+                            related_data.snapshot_info.clone(),
+                            manifest_manager.chunk_boundaries.clone(),
+                            manifest_manager.chunk_boundary_proofs.clone(),
+                            manifest_manager.active_peers.clone(),
+                            self.config.chunk_config(),
+                        )?);
+                }
                 inner.related_data = Some(related_data);
             }
             debug!("sync state progress: {:?}", *inner);
         } else {
             error!("manifest manager is None in status {:?}", inner.status);
         }
-        if matches!(inner.status, Status::DownloadingChunks(_)) {
+        if matches!(
+            inner.status,
+            Status::DownloadingChunks(_) | Status::Completed
+        ) {
             inner.manifest_manager = None;
         }
         Ok(())
     }
 
+    /// Restore the snapshot from the local archive if there is one, now that
+    /// its merkle root is verified by the manifest. Return false if the
+    /// chunks should be downloaded from the peers instead.
+    fn import_archive(
+        &self, ctx: &Context, related_data: &RelatedData,
+    ) -> bool {
+        let dir = match &self.config.archive_dir {
+            Some(dir) => dir,
+            None => return false,
+        };
+        match ctx.manager.graph.import_snapshot_archive(
+            Path::new(dir),
+            &related_data.snapshot_info,
+        ) {
+            Ok(imported) => imported,
+            Err(e) => {
+                warn!(
+                    "Failed to import snapshot {:?} from archive, download \
+                     it from peers: {}",
+                    related_data.snapshot_info.get_snapshot_epoch_id(),
+                    e
+                );
+                false
+            }
+        }
+    }
+
     pub fn handle_snapshot_chunk_response(
         &self, ctx: &Context, chunk_key: ChunkKey, chunk: Chunk,
     ) -> StorageResult<()> {
@@ -505,6 +544,9 @@ pub struct StateSyncConfiguration {
     pub candidate_request_timeout: Duration,
     pub chunk_request_timeout: Duration,
     pub manifest_request_timeout: Duration,
+    /// The directory of the snapshot archives to import before downloading
+    /// the chunks from the peers, and to export the snapshots to.
+    pub archive_dir: Option<String>,
 }
 
 impl StateSyncConfiguration {
@@ -2,6 +2,10 @@
 
 use crate::{
     block_data_manager::BlockExecutionResult,
+    consensus::{
+        consensus_inner::execution_verifier::ClaimedExecutionResult,
+        ConsensusGraph,
+    },
     message::NetworkContext,
     sync::{
         error::{Error, ErrorKind},
@@ -15,10 +19,7 @@ use crate::{
     verification::compute_receipts_root,
 };
 use cfx_internal_common::{StateRootAuxInfo, StateRootWithAuxInfo};
-use cfx_parameters::{
-    consensus::DEFERRED_STATE_EPOCH_COUNT,
-    consensus_internal::REWARD_EPOCH_COUNT,
-};
+use cfx_parameters::consensus_internal::REWARD_EPOCH_COUNT;
 use cfx_storage::{storage_db::SnapshotInfo, TrieProof};
 use cfx_types::H256;
 use network::node_table::NodeId;
@@ -172,6 +173,13 @@ impl SnapshotManifestManager {
                     "invalid chunk proofs in manifest".into(),
                 ));
             }
+            Self::submit_execution_claims(
+                ctx,
+                self.snapshot_candidate.get_snapshot_epoch_id(),
+                blame_vec_offset,
+                &response.state_root_vec,
+                &response.receipt_blame_vec,
+            );
             self.related_data = Some(RelatedData {
                 true_state_root_by_blame_info: state_root_with_aux_info,
                 blame_vec_offset,
@@ -300,24 +308,27 @@ impl SnapshotManifestManager {
             .expect("trusted_blame_block header must exist");
 
         // check snapshot position in `out_state_blame_vec`
-        let offset = (trusted_blame_block.height()
-            - (snapshot_block_header.height() + DEFERRED_STATE_EPOCH_COUNT))
-            as usize;
+        let deferred_state_height = ctx
+            .manager
+            .graph
+            .consensus
+            .deferred_state_epoch_count()
+            .state_height(trusted_blame_block.height());
+        if deferred_state_height < snapshot_block_header.height() {
+            warn!("validate_blame_states: trusted blame block too low");
+            return None;
+        }
+        let offset =
+            (deferred_state_height - snapshot_block_header.height()) as usize;
         if offset >= state_root_vec.len() {
             warn!("validate_blame_states: not enough state_root");
             return None;
         }
 
         let min_vec_len = if snapshot_block_header.height() == 0 {
-            trusted_blame_block.height()
-                - DEFERRED_STATE_EPOCH_COUNT
-                - snapshot_block_header.height()
-                + 1
+            offset as u64 + 1
         } else {
-            trusted_blame_block.height()
-                - DEFERRED_STATE_EPOCH_COUNT
-                - snapshot_block_header.height()
-                + REWARD_EPOCH_COUNT
+            offset as u64 + REWARD_EPOCH_COUNT
         };
         let mut trusted_blocks = Vec::new();
         let mut trusted_block_height = trusted_blame_block.height();
@@ -475,6 +486,53 @@ impl SnapshotManifestManager {
         ))
     }
 
+    /// Submit the state roots and receipts roots of the snapshot epoch and
+    /// its ancestors in the manifest to be verified by local execution later.
+    fn submit_execution_claims(
+        ctx: &Context, snapshot_epoch_id: &EpochId, blame_vec_offset: usize,
+        state_root_vec: &Vec<StateRoot>, receipt_blame_vec: &Vec<H256>,
+    )
+    {
+        let consensus = match ctx
+            .manager
+            .graph
+            .consensus
+            .as_any()
+            .downcast_ref::<ConsensusGraph>()
+        {
+            Some(consensus) => consensus,
+            None => return,
+        };
+        let mut claims = Vec::new();
+        let mut epoch_hash = *snapshot_epoch_id;
+        for idx in blame_vec_offset..state_root_vec.len() {
+            let receipts_root = match receipt_blame_vec.get(idx) {
+                Some(receipts_root) => *receipts_root,
+                None => break,
+            };
+            let header = match ctx
+                .manager
+                .graph
+                .data_man
+                .block_header_by_hash(&epoch_hash)
+            {
+                Some(header) => header,
+                None => break,
+            };
+            claims.push(ClaimedExecutionResult {
+                epoch_hash,
+                epoch_height: header.height(),
+                state_root_hash: state_root_vec[idx].compute_state_root_hash(),
+                receipts_root,
+            });
+            if header.height() == 0 {
+                break;
+            }
+            epoch_hash = *header.parent_hash();
+        }
+        consensus.execution_verifier().submit_claims(claims);
+    }
+
     pub fn validate_epoch_receipts(
         ctx: &Context, blame_vec_offset: usize, snapshot_epoch_id: &EpochId,
         receipt_blame_vec: &Vec<H256>, bloom_blame_vec: &Vec<H256>,
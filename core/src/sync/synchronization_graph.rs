@@ -3,25 +3,39 @@
 // See http://www.gnu.org/licenses/
 
 use crate::{
-    block_data_manager::{BlockDataManager, BlockStatus},
+    block_data_manager::{BlockDataManager, BlockOrigin, BlockStatus},
     channel::Channel,
+    clock::{system_clock, SharedClock},
     consensus::SharedConsensusGraph,
     error::{BlockError, Error, ErrorKind},
     machine::Machine,
     pow::{PowComputer, ProofOfWorkConfig},
     state_exposer::{SyncGraphBlockState, STATE_EXPOSER},
     statistics::SharedStatistics,
-    sync::synchronization_protocol_handler::FutureBlockContainer,
+    sync::{
+        block_propagation::BlockPropagationRecorder,
+        expired_blocks::ExpiredBlockTracker,
+        header_spill::SpilledHeaderIndex,
+        memory_governor::{SyncMemoryGovernor, SyncMemoryUsage},
+        missing_dependencies::MissingDependencyTracker,
+        state::snapshot_archive::{
+            export_snapshot_archive, restore_snapshot_from_archive,
+            SnapshotArchiveManifest,
+        },
+        synchronization_protocol_handler::FutureBlockContainer,
+    },
     verification::*,
     ConsensusGraph, Notifications,
 };
+use cfx_storage::storage_db::SnapshotInfo;
 use cfx_types::{H256, U256};
 use dag::{Graph, RichDAG, RichTreeGraph, TreeGraph, DAG};
 use futures::executor::block_on;
-use malloc_size_of::{MallocSizeOf, MallocSizeOfOps};
+use malloc_size_of::{new_malloc_size_ops, MallocSizeOf, MallocSizeOfOps};
 use malloc_size_of_derive::MallocSizeOf as DeriveMallocSizeOf;
 use metrics::{
-    register_meter_with_group, register_queue, Meter, MeterTimer, Queue,
+    register_meter_with_group, register_queue, Gauge, GaugeUsize, Histogram,
+    Meter, MeterTimer, Queue, Sample,
 };
 use parking_lot::RwLock;
 use primitives::{
@@ -32,12 +46,13 @@ use std::{
     cmp::max,
     collections::{BinaryHeap, HashMap, HashSet, VecDeque},
     mem, panic,
+    path::Path,
     sync::{
         atomic::{AtomicUsize, Ordering},
         Arc,
     },
     thread,
-    time::{Duration, SystemTime, UNIX_EPOCH},
+    time::{Duration, UNIX_EPOCH},
 };
 use tokio::sync::mpsc::error::TryRecvError;
 use unexpected::{Mismatch, OutOfBounds};
@@ -49,9 +64,41 @@ lazy_static! {
         register_meter_with_group("timer", "sync::insert_block");
     static ref CONSENSUS_WORKER_QUEUE: Arc<dyn Queue> =
         register_queue("consensus_worker_queue");
+    static ref SYNC_INVALID_CASCADE_SIZE: Arc<dyn Histogram> =
+        Sample::ExpDecay(0.015).register_with_group(
+            "graph_statistic",
+            "sync_graph_invalid_cascade_size",
+            1024
+        );
+    static ref SYNC_PENDING_INVALID_BLOCK_COUNT: Arc<dyn Gauge<usize>> =
+        GaugeUsize::register_with_group(
+            "graph_statistic",
+            "sync_graph_pending_invalid_block_count"
+        );
+    static ref SYNC_MEMORY_USAGE: Arc<dyn Gauge<usize>> =
+        GaugeUsize::register_with_group(
+            "graph_statistic",
+            "sync_graph_memory_usage"
+        );
 }
 
 const NULL: usize = !0;
+/// The maximum number of invalid blocks removed from the sync graph in one
+/// lock hold. The rest of a larger invalidation cascade is removed in later
+/// batches.
+const MAX_INVALID_BLOCKS_PER_BATCH: usize = 1000;
+/// The maximum number of reclaimed blocks whose header fields are cached for
+/// verifying their children.
+const MAX_RECLAIMED_PARENT_INFO_COUNT: usize = 10_000;
+/// The maximum number of expired blocks whose reinsertion is rate-limited.
+const MAX_TRACKED_EXPIRED_BLOCK_COUNT: usize = 100_000;
+/// The maximum number of orphan headers spilled to the database. The least
+/// recently spilled ones are removed beyond this.
+const MAX_SPILLED_HEADER_COUNT: usize = 1_000_000;
+/// The orphan headers are spilled until their number in memory drops to this
+/// percentage of `max_orphan_headers`, so the spilling does not run on every
+/// check when the number stays around the limit.
+const SPILL_TARGET_PERCENTAGE: usize = 90;
 const BLOCK_INVALID: u8 = 0;
 const BLOCK_HEADER_ONLY: u8 = 1;
 const BLOCK_HEADER_GRAPH_READY: u8 = 2;
@@ -62,6 +109,24 @@ pub struct SyncGraphConfig {
     pub future_block_buffer_capacity: usize,
     pub enable_state_expose: bool,
     pub is_consortium: bool,
+    /// The number of latest blocks whose propagation records are kept.
+    /// Set to 0 to disable recording.
+    pub block_propagation_history_size: usize,
+    /// The memory budget of the sync graph and the block data caches in
+    /// bytes. `None` means no limit.
+    pub memory_budget_bytes: Option<usize>,
+    /// The maximum number of graph-ready blocks waiting for consensus before
+    /// the node is considered under memory pressure. `None` means no limit.
+    pub max_consensus_queue_len: Option<usize>,
+    /// If set, blocks are sent to consensus once their headers are
+    /// graph-ready, so the pivot chain is extended before the bodies arrive.
+    pub header_graph_mode: bool,
+    /// The maximum number of orphan headers kept in the sync graph, i.e. the
+    /// headers whose parents are missing and the headers in their future.
+    /// Beyond this, the least recently updated ones are spilled to the
+    /// database and inserted again when their parents arrive. `None` means
+    /// no limit.
+    pub max_orphan_headers: Option<usize>,
 }
 
 #[derive(Debug)]
@@ -107,6 +172,9 @@ pub struct SynchronizationGraphNode {
     pub referrers: Vec<usize>,
     /// the timestamp in seconds when graph_status updated
     pub last_update_timestamp: u64,
+    /// Whether the block has been sent to consensus with only its header in
+    /// the header graph mode.
+    pub header_in_consensus: bool,
 }
 
 #[derive(DeriveMallocSizeOf)]
@@ -146,6 +214,32 @@ impl UnreadyBlockFrontier {
     pub fn len(&self) -> usize { self.frontier.len() }
 }
 
+/// A new era genesis inserted as a second root of the sync graph during a
+/// checkpoint transition, before the consensus graph moves to it. Blocks can
+/// be anchored at either root, except that a block at `cutover_height` must
+/// be in the subtree of the new root, so the blocks from the cutover height
+/// on are all anchored at the new root.
+#[derive(DeriveMallocSizeOf)]
+struct GenesisTransition {
+    old_genesis_hash: H256,
+    new_genesis_hash: H256,
+    new_genesis: usize,
+    cutover_height: u64,
+}
+
+/// The header fields of a block cleared from the sync graph that are needed to
+/// verify its children still in the graph.
+#[derive(DeriveMallocSizeOf)]
+struct ReclaimedParentInfo {
+    height: u64,
+    timestamp: u64,
+    gas_limit: U256,
+    difficulty: U256,
+    /// The number of the children still in the sync graph. The info is
+    /// removed when all of them are cleared or removed.
+    child_count: usize,
+}
+
 pub struct SynchronizationGraphInner {
     pub arena: Slab<SynchronizationGraphNode>,
     pub hash_to_arena_indices: HashMap<H256, usize>,
@@ -171,21 +265,62 @@ pub struct SynchronizationGraphInner {
     /// The set of blocks that we need to download block bodies in
     /// `CatchUpFillBlockBodyPhase`.
     pub block_to_fill_set: HashSet<H256>,
+    /// The blocks marked invalid but not removed yet, ordered by heights
+    /// within each invalidation cascade. The arena index of a block is kept
+    /// with its hash, because the block may be removed through other paths
+    /// in the meantime and its index reused.
+    pending_invalid_blocks: VecDeque<(usize, H256)>,
+    /// The cached header fields of the reclaimed parents, so verifying their
+    /// children does not read the headers from the database.
+    reclaimed_parent_info: HashMap<H256, ReclaimedParentInfo>,
+    /// The blocks whose verification needs the headers of their parents or
+    /// referees that are not available, keyed by the missing hashes. They are
+    /// verified again when the headers are inserted.
+    blocks_waiting_for_headers: HashMap<H256, Vec<(usize, H256)>>,
+    /// The blocks removed by `remove_expire_blocks`, whose reinsertion is
+    /// delayed if they expire repeatedly.
+    expired_blocks: ExpiredBlockTracker,
+    /// Set by `begin_genesis_transition` until the era genesis changes.
+    genesis_transition: Option<GenesisTransition>,
+    /// The orphan headers spilled to the database, keyed by the missing
+    /// parent hashes.
+    spilled_headers: SpilledHeaderIndex,
+    /// The headers inserted again from the spilled ones, whose bodies are to
+    /// be requested.
+    reloaded_headers: Vec<H256>,
+    /// The parents and referees that the headers in the graph wait for, to
+    /// be requested again if they are not received.
+    missing_dependencies: MissingDependencyTracker,
     machine: Arc<Machine>,
+    clock: SharedClock,
 }
 
 impl MallocSizeOf for SynchronizationGraphInner {
     fn size_of(&self, ops: &mut MallocSizeOfOps) -> usize {
+        self.graph_size_of(ops) + self.data_man.size_of(ops)
+        // Does not count size_of machine.
+    }
+}
+
+impl SynchronizationGraphInner {
+    /// The heap size of the graph itself, excluding the block data manager.
+    fn graph_size_of(&self, ops: &mut MallocSizeOfOps) -> usize {
         self.arena.size_of(ops)
             + self.hash_to_arena_indices.size_of(ops)
-            + self.data_man.size_of(ops)
             + self.children_by_hash.size_of(ops)
             + self.referrers_by_hash.size_of(ops)
             + self.pow_config.size_of(ops)
             + self.not_ready_blocks_frontier.size_of(ops)
             + self.old_era_blocks_frontier.size_of(ops)
             + self.old_era_blocks_frontier_set.size_of(ops)
-        // Does not count size_of machine.
+            + self.pending_invalid_blocks.size_of(ops)
+            + self.reclaimed_parent_info.size_of(ops)
+            + self.blocks_waiting_for_headers.size_of(ops)
+            + self.expired_blocks.size_of(ops)
+            + self.genesis_transition.size_of(ops)
+            + self.spilled_headers.size_of(ops)
+            + self.reloaded_headers.size_of(ops)
+            + self.missing_dependencies.size_of(ops)
     }
 }
 
@@ -194,6 +329,7 @@ impl SynchronizationGraphInner {
         genesis_header: Arc<BlockHeader>, pow_config: ProofOfWorkConfig,
         pow: Arc<PowComputer>, config: SyncGraphConfig,
         data_man: Arc<BlockDataManager>, machine: Arc<Machine>,
+        clock: SharedClock,
     ) -> Self
     {
         let mut inner = SynchronizationGraphInner {
@@ -209,8 +345,19 @@ impl SynchronizationGraphInner {
             old_era_blocks_frontier: Default::default(),
             old_era_blocks_frontier_set: Default::default(),
             block_to_fill_set: Default::default(),
+            pending_invalid_blocks: Default::default(),
+            reclaimed_parent_info: Default::default(),
+            blocks_waiting_for_headers: Default::default(),
+            expired_blocks: ExpiredBlockTracker::new(
+                MAX_TRACKED_EXPIRED_BLOCK_COUNT,
+            ),
+            genesis_transition: None,
+            spilled_headers: SpilledHeaderIndex::new(MAX_SPILLED_HEADER_COUNT),
+            reloaded_headers: Vec::new(),
+            missing_dependencies: Default::default(),
             locked_for_catchup: false,
             machine,
+            clock,
         };
         let genesis_hash = genesis_header.hash();
         let genesis_block_index = inner.insert(genesis_header);
@@ -253,7 +400,124 @@ impl SynchronizationGraphInner {
         (stable_hash, height)
     }
 
+    /// Insert the header of a new era genesis as a second root. Its subtree
+    /// becomes graph-ready without the blocks between the two roots, and the
+    /// blocks at `cutover_height` are only graph-ready in its subtree. Return
+    /// the blocks already in the graph that wait for it.
+    fn begin_genesis_transition(
+        &mut self, new_genesis_header: Arc<BlockHeader>, cutover_height: u64,
+    ) -> Result<Vec<usize>, String>
+    {
+        let new_genesis_hash = new_genesis_header.hash();
+        let old_genesis_hash =
+            self.data_man.get_cur_consensus_era_genesis_hash();
+        if let Some(transition) = &self.genesis_transition {
+            return Err(format!(
+                "transition to genesis {:?} is in progress",
+                transition.new_genesis_hash
+            ));
+        }
+        if new_genesis_hash == old_genesis_hash {
+            return Err(format!(
+                "{:?} is the current era genesis",
+                new_genesis_hash
+            ));
+        }
+        if self.hash_to_arena_indices.contains_key(&new_genesis_hash) {
+            return Err(format!(
+                "{:?} is already in sync graph",
+                new_genesis_hash
+            ));
+        }
+        if cutover_height < new_genesis_header.height() {
+            return Err(format!(
+                "cutover height {} is below the new genesis height {}",
+                cutover_height,
+                new_genesis_header.height()
+            ));
+        }
+
+        // Set before inserting so that `insert` makes the header a root.
+        self.genesis_transition = Some(GenesisTransition {
+            old_genesis_hash,
+            new_genesis_hash,
+            new_genesis: NULL,
+            cutover_height,
+        });
+        let new_genesis = self.insert(new_genesis_header);
+        if let Some(transition) = &mut self.genesis_transition {
+            transition.new_genesis = new_genesis;
+        }
+        info!(
+            "begin genesis transition from {:?} to {:?}, index {}, \
+             cutover height {}",
+            old_genesis_hash, new_genesis_hash, new_genesis, cutover_height
+        );
+
+        let mut waiting = self.arena[new_genesis].children.clone();
+        waiting.extend(self.arena[new_genesis].referrers.iter().cloned());
+        Ok(waiting)
+    }
+
+    /// End the genesis transition once the era genesis is no longer the old
+    /// root, no matter if it has moved to the new root. The root that is not
+    /// the era genesis is then cleared like other old era blocks. The
+    /// old-era frontier is rebuilt in the order of (height, hash) so the
+    /// blocks are cleared in the same order on all nodes.
+    fn try_complete_genesis_transition(&mut self) {
+        let cur_genesis_hash =
+            self.data_man.get_cur_consensus_era_genesis_hash();
+        let new_genesis = match &self.genesis_transition {
+            Some(transition)
+                if transition.old_genesis_hash != cur_genesis_hash =>
+            {
+                info!(
+                    "genesis transition to {:?} ends, era genesis {:?}",
+                    transition.new_genesis_hash, cur_genesis_hash
+                );
+                transition.new_genesis
+            }
+            _ => return,
+        };
+        self.genesis_transition = None;
+        if new_genesis == NULL {
+            return;
+        }
+
+        self.old_era_blocks_frontier_set.insert(new_genesis);
+        let mut frontier: Vec<usize> =
+            self.old_era_blocks_frontier_set.iter().cloned().collect();
+        frontier.sort_by_key(|index| {
+            let header = &self.arena[*index].block_header;
+            (header.height(), header.hash())
+        });
+        self.old_era_blocks_frontier = frontier.into_iter().collect();
+    }
+
+    /// Return if the block at `index` can be graph-ready under the genesis
+    /// transition. See `GenesisTransition`.
+    fn anchored_in_genesis_transition(&self, index: usize) -> bool {
+        let transition = match &self.genesis_transition {
+            Some(transition) => transition,
+            None => return true,
+        };
+        if self.arena[index].block_header.height() != transition.cutover_height
+        {
+            return true;
+        }
+        let new_genesis_height =
+            self.arena[transition.new_genesis].block_header.height();
+        let mut ancestor = index;
+        while ancestor != NULL
+            && self.arena[ancestor].block_header.height() > new_genesis_height
+        {
+            ancestor = self.arena[ancestor].parent;
+        }
+        ancestor == transition.new_genesis
+    }
+
     fn try_clear_old_era_blocks(&mut self) {
+        self.try_complete_genesis_transition();
         let max_num_of_cleared_blocks = 2;
         let mut num_cleared = 0;
         let era_genesis = self.get_genesis_in_current_era();
@@ -318,6 +582,23 @@ impl SynchronizationGraphInner {
 
             let children: Vec<usize> =
                 self.arena[index].children.iter().map(|x| *x).collect();
+            self.release_reclaimed_parent_info(index);
+            if !children.is_empty()
+                && self.reclaimed_parent_info.len()
+                    < MAX_RECLAIMED_PARENT_INFO_COUNT
+            {
+                let header = &self.arena[index].block_header;
+                self.reclaimed_parent_info.insert(
+                    hash,
+                    ReclaimedParentInfo {
+                        height: header.height(),
+                        timestamp: header.timestamp(),
+                        gas_limit: *header.gas_limit(),
+                        difficulty: *header.difficulty(),
+                        child_count: children.len(),
+                    },
+                );
+            }
             for child in children {
                 self.arena[child].parent = NULL;
                 self.arena[child].parent_reclaimed = true;
@@ -366,10 +647,8 @@ impl SynchronizationGraphInner {
             pending_referee_count: 0,
             referrers: Vec::new(),
             block_header: header,
-            last_update_timestamp: SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
+            last_update_timestamp: self.clock.now_as_secs(),
+            header_in_consensus: false,
         });
         self.hash_to_arena_indices.insert(hash, me);
 
@@ -398,8 +677,14 @@ impl SynchronizationGraphInner {
     /// Return the index of the inserted block.
     pub fn insert(&mut self, header: Arc<BlockHeader>) -> usize {
         let hash = header.hash();
-        let is_genesis =
-            hash == self.data_man.get_cur_consensus_era_genesis_hash();
+        let is_genesis = hash
+            == self.data_man.get_cur_consensus_era_genesis_hash()
+            || self
+                .genesis_transition
+                .as_ref()
+                .map_or(false, |transition| {
+                    transition.new_genesis_hash == hash
+                });
 
         let me = self.arena.insert(SynchronizationGraphNode {
             graph_status: if is_genesis {
@@ -415,10 +700,8 @@ impl SynchronizationGraphInner {
             pending_referee_count: 0,
             referrers: Vec::new(),
             block_header: header.clone(),
-            last_update_timestamp: SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
+            last_update_timestamp: self.clock.now_as_secs(),
+            header_in_consensus: false,
         });
         self.hash_to_arena_indices.insert(hash, me);
 
@@ -494,6 +777,51 @@ impl SynchronizationGraphInner {
         }
     }
 
+    /// Called when the block at `index` is removed from the sync graph. The
+    /// headers it waits for can only be those of its parent and referees.
+    fn stop_waiting_for_headers(&mut self, index: usize) {
+        let header = self.arena[index].block_header.clone();
+        let hash = header.hash();
+        for dependency in header
+            .referee_hashes()
+            .iter()
+            .chain(Some(header.parent_hash()))
+        {
+            let now_empty =
+                match self.blocks_waiting_for_headers.get_mut(dependency) {
+                    Some(blocks) => {
+                        blocks.retain(|(i, h)| !(*i == index && *h == hash));
+                        blocks.is_empty()
+                    }
+                    None => false,
+                };
+            if now_empty {
+                self.blocks_waiting_for_headers.remove(dependency);
+            }
+        }
+    }
+
+    /// Called when the block at `index` is removed from the sync graph. Its
+    /// parent info is removed if it is the last child of the parent.
+    fn release_reclaimed_parent_info(&mut self, index: usize) {
+        if self.arena[index].parent != NULL
+            || !self.arena[index].parent_reclaimed
+        {
+            return;
+        }
+        let parent_hash = *self.arena[index].block_header.parent_hash();
+        let released = match self.reclaimed_parent_info.get_mut(&parent_hash) {
+            Some(info) => {
+                info.child_count = info.child_count.saturating_sub(1);
+                info.child_count == 0
+            }
+            None => false,
+        };
+        if released {
+            self.reclaimed_parent_info.remove(&parent_hash);
+        }
+    }
+
     fn new_to_be_graph_ready(
         &mut self, index: usize, minimal_status: u8,
     ) -> bool {
@@ -506,6 +834,9 @@ impl SynchronizationGraphInner {
         if node_me.graph_status >= minimal_status {
             return false;
         }
+        if !self.anchored_in_genesis_transition(index) {
+            return false;
+        }
 
         let genesis_hash = self.data_man.get_cur_consensus_era_genesis_hash();
         let genesis_seq_num = self
@@ -568,40 +899,88 @@ impl SynchronizationGraphInner {
     }
 
     // Get parent (height, timestamp, gas_limit, difficulty)
-    // This function assumes that the parent and referee information MUST exist
-    // in memory or in disk.
+    // The parent and referee headers are read from memory or disk. If any of
+    // them is not available, e.g. the header is not persisted yet, return the
+    // missing hashes so the block can be verified again after they arrive.
     fn get_parent_and_referee_info(
         &self, index: usize,
-    ) -> (u64, u64, U256, U256) {
-        let parent_height;
-        let parent_timestamp;
-        let parent_gas_limit;
-        let parent_difficulty;
+    ) -> Result<(u64, u64, U256, U256), Vec<H256>> {
+        let header = &self.arena[index].block_header;
+        let mut missing_hashes = Vec::new();
+        for referee_hash in header.referee_hashes() {
+            if !self.hash_to_arena_indices.contains_key(referee_hash)
+                && self.data_man.block_header_by_hash(referee_hash).is_none()
+            {
+                missing_hashes.push(*referee_hash);
+            }
+        }
+
         let parent = self.arena[index].parent;
-        if parent != NULL {
-            parent_height = self.arena[parent].block_header.height();
-            parent_timestamp = self.arena[parent].block_header.timestamp();
-            parent_gas_limit = *self.arena[parent].block_header.gas_limit();
-            parent_difficulty = *self.arena[parent].block_header.difficulty();
+        let parent_info = if parent != NULL {
+            let parent_header = &self.arena[parent].block_header;
+            Some((
+                parent_header.height(),
+                parent_header.timestamp(),
+                *parent_header.gas_limit(),
+                *parent_header.difficulty(),
+            ))
+        } else if let Some(info) =
+            self.reclaimed_parent_info.get(header.parent_hash())
+        {
+            Some((info.height, info.timestamp, info.gas_limit, info.difficulty))
         } else {
-            let parent_hash = self.arena[index].block_header.parent_hash();
-            let parent_header = self
-                .data_man
-                .block_header_by_hash(parent_hash)
-                .unwrap()
-                .clone();
-            parent_height = parent_header.height();
-            parent_timestamp = parent_header.timestamp();
-            parent_gas_limit = *parent_header.gas_limit();
-            parent_difficulty = *parent_header.difficulty();
+            self.data_man
+                .block_header_by_hash(header.parent_hash())
+                .map(|parent_header| {
+                    (
+                        parent_header.height(),
+                        parent_header.timestamp(),
+                        *parent_header.gas_limit(),
+                        *parent_header.difficulty(),
+                    )
+                })
+        };
+
+        match parent_info {
+            Some(parent_info) if missing_hashes.is_empty() => Ok(parent_info),
+            Some(_) => Err(missing_hashes),
+            None => {
+                missing_hashes.push(*header.parent_hash());
+                Err(missing_hashes)
+            }
         }
+    }
 
-        (
-            parent_height,
-            parent_timestamp,
-            parent_gas_limit,
-            parent_difficulty,
-        )
+    /// Verify the block again when any of `missing_hashes` is inserted.
+    fn wait_for_headers(&mut self, index: usize, missing_hashes: &Vec<H256>) {
+        let waiting = (index, self.arena[index].block_header.hash());
+        for hash in missing_hashes {
+            let blocks = self
+                .blocks_waiting_for_headers
+                .entry(*hash)
+                .or_insert_with(Vec::new);
+            if !blocks.contains(&waiting) {
+                blocks.push(waiting);
+            }
+        }
+    }
+
+    /// Return the blocks waiting for the header of `hash` that are still in
+    /// the sync graph.
+    fn take_blocks_waiting_for_header(&mut self, hash: &H256) -> Vec<usize> {
+        match self.blocks_waiting_for_headers.remove(hash) {
+            Some(blocks) => blocks
+                .into_iter()
+                .filter(|(index, block_hash)| {
+                    self.arena.get(*index).map_or(false, |node| {
+                        node.block_header.hash() == *block_hash
+                            && node.graph_status == BLOCK_HEADER_ONLY
+                    })
+                })
+                .map(|(index, _)| index)
+                .collect(),
+            None => Vec::new(),
+        }
     }
 
     fn verify_header_graph_ready_block(
@@ -613,7 +992,9 @@ impl SynchronizationGraphInner {
             parent_timestamp,
             parent_gas_limit,
             parent_difficulty,
-        ) = self.get_parent_and_referee_info(index);
+        ) = self.get_parent_and_referee_info(index).map_err(|missing| {
+            Error::from(BlockError::MissingAncestorHeaders(missing))
+        })?;
 
         // Verify the height and epoch numbers are correct
         if parent_height + 1 != epoch {
@@ -734,7 +1115,76 @@ impl SynchronizationGraphInner {
         Ok(())
     }
 
+    /// Remove the blocks in `invalid_set`. If there are more than
+    /// `MAX_INVALID_BLOCKS_PER_BATCH` blocks, only the first batch is removed
+    /// now, and the others are kept as pending and removed by
+    /// `remove_pending_invalid_blocks` in later lock holds.
     fn process_invalid_blocks(&mut self, invalid_set: &HashSet<usize>) {
+        if invalid_set.is_empty() {
+            return;
+        }
+        SYNC_INVALID_CASCADE_SIZE.update(invalid_set.len() as u64);
+        if invalid_set.len() <= MAX_INVALID_BLOCKS_PER_BATCH {
+            self.remove_invalid_blocks(invalid_set);
+            return;
+        }
+
+        warn!(
+            "Large invalidation cascade of {} blocks, remove in batches",
+            invalid_set.len()
+        );
+        let mut cascade: Vec<usize> = invalid_set.iter().cloned().collect();
+        // Remove the ancestors before their descendants.
+        cascade.sort_by_key(|index| self.arena[*index].block_header.height());
+        for index in cascade {
+            let hash = self.arena[index].block_header.hash();
+            // The pending blocks are already `BLOCK_INVALID`, and they should
+            // not be processed as unready or old-era blocks before removal.
+            self.not_ready_blocks_frontier.remove(&index);
+            self.old_era_blocks_frontier_set.remove(&index);
+            self.block_to_fill_set.remove(&hash);
+            self.pending_invalid_blocks.push_back((index, hash));
+        }
+        self.data_man.insert_pending_invalid_blocks(
+            &self
+                .pending_invalid_blocks
+                .iter()
+                .map(|(_, hash)| *hash)
+                .collect(),
+        );
+        self.remove_pending_invalid_blocks();
+    }
+
+    /// Remove at most `MAX_INVALID_BLOCKS_PER_BATCH` pending invalid blocks.
+    /// Return `true` if there are still pending ones.
+    fn remove_pending_invalid_blocks(&mut self) -> bool {
+        let mut batch = HashSet::new();
+        while batch.len() < MAX_INVALID_BLOCKS_PER_BATCH {
+            match self.pending_invalid_blocks.pop_front() {
+                Some((index, hash)) => {
+                    if self
+                        .arena
+                        .get(index)
+                        .map_or(false, |node| node.block_header.hash() == hash)
+                    {
+                        batch.insert(index);
+                    }
+                }
+                None => break,
+            }
+        }
+        self.remove_invalid_blocks(&batch);
+        SYNC_PENDING_INVALID_BLOCK_COUNT
+            .update(self.pending_invalid_blocks.len());
+        if self.pending_invalid_blocks.is_empty() {
+            self.data_man.insert_pending_invalid_blocks(&Vec::new());
+            false
+        } else {
+            true
+        }
+    }
+
+    fn remove_invalid_blocks(&mut self, invalid_set: &HashSet<usize>) {
         for index in invalid_set {
             let hash = self.arena[*index].block_header.hash();
             // Mark this block as invalid, so we don't need to request/verify it
@@ -745,14 +1195,37 @@ impl SynchronizationGraphInner {
     }
 
     fn remove_blocks(&mut self, to_remove_set: &HashSet<usize>) {
+        for hash in self.remove_blocks_from_graph(to_remove_set) {
+            // remove header/block in memory cache and header/block in db
+            self.data_man
+                .remove_useless_block(&hash, true /* remove_db */);
+        }
+    }
+
+    /// Remove the blocks from the graph without removing their data, and
+    /// return their hashes.
+    fn remove_blocks_from_graph(
+        &mut self, to_remove_set: &HashSet<usize>,
+    ) -> Vec<H256> {
+        let mut removed_hashes = Vec::with_capacity(to_remove_set.len());
         for index in to_remove_set {
             let hash = self.arena[*index].block_header.hash();
+            if self
+                .genesis_transition
+                .as_ref()
+                .map_or(false, |transition| transition.new_genesis == *index)
+            {
+                warn!("remove the new genesis {:?} in transition", hash);
+                self.genesis_transition = None;
+            }
             self.not_ready_blocks_frontier.remove(index);
             self.old_era_blocks_frontier_set.remove(index);
             // This include invalid blocks and blocks not received after a long
             // time.
             self.block_to_fill_set.remove(&hash);
 
+            self.release_reclaimed_parent_info(*index);
+            self.stop_waiting_for_headers(*index);
             let parent = self.arena[*index].parent;
             if parent != NULL {
                 self.arena[parent].children.retain(|&x| x != *index);
@@ -797,23 +1270,147 @@ impl SynchronizationGraphInner {
             let children: Vec<usize> =
                 self.arena[*index].children.iter().map(|x| *x).collect();
             for child in children {
-                debug_assert!(to_remove_set.contains(&child));
+                // The descendants of an invalid block may be pending to be
+                // removed in a later batch.
+                debug_assert!(
+                    to_remove_set.contains(&child)
+                        || self.arena[child].graph_status == BLOCK_INVALID
+                );
                 self.arena[child].parent = NULL;
             }
 
             let referrers: Vec<usize> =
                 self.arena[*index].referrers.iter().map(|x| *x).collect();
             for referrer in referrers {
-                debug_assert!(to_remove_set.contains(&referrer));
+                debug_assert!(
+                    to_remove_set.contains(&referrer)
+                        || self.arena[referrer].graph_status == BLOCK_INVALID
+                );
                 self.arena[referrer].referees.retain(|&x| x != *index);
             }
 
             self.arena.remove(*index);
             self.hash_to_arena_indices.remove(&hash);
-            // remove header/block in memory cache and header/block in db
-            self.data_man
-                .remove_useless_block(&hash, true /* remove_db */);
+            removed_hashes.push(hash);
         }
+        removed_hashes
+    }
+
+    /// Count the headers waiting for each parent or referee not in the graph,
+    /// including the spilled ones. The headers already graph-ready, whose
+    /// parents are reclaimed or in the database, do not wait.
+    fn collect_missing_dependencies(&self) -> HashMap<H256, usize> {
+        let mut blocked_counts = HashMap::new();
+        for (hash, waiting) in self
+            .children_by_hash
+            .iter()
+            .chain(self.referrers_by_hash.iter())
+        {
+            let count = waiting
+                .iter()
+                .filter(|index| {
+                    self.arena[**index].graph_status == BLOCK_HEADER_ONLY
+                })
+                .count();
+            if count != 0 {
+                *blocked_counts.entry(*hash).or_insert(0) += count;
+            }
+        }
+        for (hash, count) in self.spilled_headers.iter() {
+            *blocked_counts.entry(*hash).or_insert(0) += count;
+        }
+        blocked_counts
+    }
+
+    /// Move the least recently updated orphan headers to the database until
+    /// at most `SPILL_TARGET_PERCENTAGE` of `max_orphan_headers` are left in
+    /// memory. The headers are spilled in groups, each of which is an orphan
+    /// block and its future, keyed by the missing parent hash. Return the
+    /// number of the spilled headers.
+    fn spill_orphan_headers(&mut self, max_orphan_headers: usize) -> usize {
+        let mut roots: Vec<usize> = self
+            .not_ready_blocks_frontier
+            .get_frontier()
+            .iter()
+            .filter(|index| {
+                let node = &self.arena[**index];
+                node.parent == NULL
+                    && !node.parent_reclaimed
+                    && node.graph_status == BLOCK_HEADER_ONLY
+            })
+            .cloned()
+            .collect();
+        let mut orphan_count =
+            self.get_future::<HashSet<_>, _>(roots.clone()).len();
+        if orphan_count <= max_orphan_headers {
+            return 0;
+        }
+        let target = max_orphan_headers * SPILL_TARGET_PERCENTAGE / 100;
+        roots.sort_by_key(|index| self.arena[*index].last_update_timestamp);
+
+        let mut spilled_count = 0;
+        for root in roots {
+            if orphan_count <= target {
+                break;
+            }
+            // The root may be in the future of a group spilled before.
+            if !self.arena.contains(root) {
+                continue;
+            }
+            let group: HashSet<usize> = self.get_future(vec![root]);
+            // The blocks with bodies are kept, because their bodies would be
+            // ignored if they arrived while their headers are spilled.
+            if group.iter().any(|index| {
+                self.arena[*index].block_ready
+                    || self.arena[*index].graph_status != BLOCK_HEADER_ONLY
+            }) {
+                continue;
+            }
+            let parent_hash = *self.arena[root].block_header.parent_hash();
+            // Sort by heights so the parents are inserted before their
+            // children when the group is reloaded.
+            let mut heights_and_hashes: Vec<(u64, H256)> = Vec::new();
+            for index in &group {
+                let header = &self.arena[*index].block_header;
+                // Orphan headers are not persisted on insertion.
+                self.data_man.insert_block_header(
+                    header.hash(),
+                    header.clone(),
+                    true, /* persistent */
+                );
+                heights_and_hashes.push((header.height(), header.hash()));
+            }
+            heights_and_hashes.sort();
+            let hashes: Vec<H256> = heights_and_hashes
+                .into_iter()
+                .map(|(_, hash)| hash)
+                .collect();
+
+            self.data_man.insert_spilled_headers(
+                &parent_hash,
+                &hashes,
+                self.spilled_headers.contains(&parent_hash),
+            );
+            for dropped in
+                self.spilled_headers.on_spilled(parent_hash, hashes.len())
+            {
+                for hash in self.data_man.take_spilled_headers(&dropped) {
+                    // The header may have been received and inserted again.
+                    if !self.hash_to_arena_indices.contains_key(&hash) {
+                        self.data_man.remove_useless_block(
+                            &hash, true, /* remove_db */
+                        );
+                    }
+                }
+            }
+            for hash in self.remove_blocks_from_graph(&group) {
+                self.data_man
+                    .remove_block_header(&hash, false /* remove_db */);
+            }
+            orphan_count = orphan_count.saturating_sub(group.len());
+            spilled_count += hashes.len();
+        }
+        spilled_count
     }
 
     fn set_and_propagate_invalid(
@@ -866,6 +1463,17 @@ pub struct SynchronizationGraph {
     pub future_blocks: FutureBlockContainer,
 
     machine: Arc<Machine>,
+
+    /// The source of the current time used to update node timestamps,
+    /// expire blocks, and release future blocks.
+    pub clock: SharedClock,
+
+    /// Records the origin and latency of blocks received in this run.
+    pub block_propagation: BlockPropagationRecorder,
+
+    /// Pauses block body requests and rejects headers that do not connect to
+    /// the graph when the memory budget is exceeded.
+    pub memory_governor: SyncMemoryGovernor,
 }
 
 impl MallocSizeOf for SynchronizationGraph {
@@ -897,6 +1505,28 @@ impl SynchronizationGraph {
         pow: Arc<PowComputer>, sync_config: SyncGraphConfig,
         notifications: Arc<Notifications>, machine: Arc<Machine>,
     ) -> Self
+    {
+        Self::with_clock(
+            consensus,
+            verification_config,
+            pow_config,
+            pow,
+            sync_config,
+            notifications,
+            machine,
+            system_clock(),
+        )
+    }
+
+    /// Create a sync graph with the given clock. Tests and simulations can
+    /// pass a `ManualClock` to control time-dependent behaviors.
+    pub fn with_clock(
+        consensus: SharedConsensusGraph,
+        verification_config: VerificationConfig, pow_config: ProofOfWorkConfig,
+        pow: Arc<PowComputer>, sync_config: SyncGraphConfig,
+        notifications: Arc<Notifications>, machine: Arc<Machine>,
+        clock: SharedClock,
+    ) -> Self
     {
         let data_man = consensus.get_data_manager().clone();
         let genesis_hash = data_man.get_cur_consensus_era_genesis_hash();
@@ -916,6 +1546,7 @@ impl SynchronizationGraph {
                 sync_config,
                 data_man.clone(),
                 machine.clone(),
+                clock.clone(),
             ),
         ));
         let sync_graph = SynchronizationGraph {
@@ -932,6 +1563,16 @@ impl SynchronizationGraph {
             consensus_unprocessed_count: consensus_unprocessed_count.clone(),
             new_block_hashes: notifications.new_block_hashes.clone(),
             machine,
+            block_propagation: BlockPropagationRecorder::new(
+                data_man.clone(),
+                clock.clone(),
+                sync_config.block_propagation_history_size,
+            ),
+            clock,
+            memory_governor: SyncMemoryGovernor::new(
+                sync_config.memory_budget_bytes,
+                sync_config.max_consensus_queue_len,
+            ),
         };
 
         // It receives `BLOCK_GRAPH_READY` blocks in order and handles them in
@@ -1036,16 +1677,14 @@ impl SynchronizationGraph {
         self.consensus.expected_difficulty(parent_hash)
     }
 
-    pub fn get_to_propagate_trans(
-        &self,
-    ) -> HashMap<H256, Arc<SignedTransaction>> {
+    pub fn get_to_propagate_trans(&self) -> Vec<Arc<SignedTransaction>> {
         self.consensus
             .get_tx_pool()
             .get_to_be_propagated_transactions()
     }
 
     pub fn set_to_propagate_trans(
-        &self, transactions: HashMap<H256, Arc<SignedTransaction>>,
+        &self, transactions: Vec<Arc<SignedTransaction>>,
     ) {
         self.consensus
             .get_tx_pool()
@@ -1059,6 +1698,14 @@ impl SynchronizationGraph {
     pub fn recover_graph_from_db(&self) {
         info!("Start fast recovery of the block DAG from database");
 
+        // Finish removing the invalid blocks left pending before the last
+        // shutdown.
+        let pending_invalid_count =
+            self.data_man.remove_pending_invalid_blocks_from_db();
+        if pending_invalid_count != 0 {
+            info!("Removed {} pending invalid blocks", pending_invalid_count);
+        }
+
         // Recover the initial sequence number in consensus graph
         // based on the sequence number of genesis block in db.
         let genesis_hash = self.data_man.get_cur_consensus_era_genesis_hash();
@@ -1111,6 +1758,9 @@ impl SynchronizationGraph {
         // era but are missed in db. The missed blocks will be fetched from
         // peers.
         let mut missed_hashes = HashSet::new();
+        // The blocks whose headers are in db but bodies are not, according to
+        // the persisted body availability.
+        let mut missing_body_count = 0;
         while let Some(hash) = queue.pop_front() {
             if hash == genesis_hash {
                 // Genesis block is already in consensus graph.
@@ -1143,6 +1793,13 @@ impl SynchronizationGraph {
                     true,  /* insert_to_consensus */
                     false, /* persistent */
                 );
+                if self
+                    .data_man
+                    .block_body_availability(&hash, block_header.height())
+                    == Some(false)
+                {
+                    missing_body_count += 1;
+                }
                 let parent = block_header.parent_hash().clone();
                 let referees = block_header.referee_hashes().clone();
                 if !visited_blocks.contains(&parent) {
@@ -1160,6 +1817,11 @@ impl SynchronizationGraph {
             }
         }
 
+        info!(
+            "Recovered headers from db: missing_headers={} missing_bodies={}",
+            missed_hashes.len(),
+            missing_body_count
+        );
         debug!(
             "Current frontier after recover from db: {:?}",
             self.inner.read().not_ready_blocks_frontier.get_frontier()
@@ -1218,10 +1880,7 @@ impl SynchronizationGraph {
         persistent: bool,
     ) -> (HashSet<usize>, Vec<H256>)
     {
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+        let now = self.clock.now_as_secs();
         let mut need_to_relay: Vec<H256> = Vec::new();
         let mut invalid_set: HashSet<usize> = HashSet::new();
         let mut queue = VecDeque::new();
@@ -1248,6 +1907,32 @@ impl SynchronizationGraph {
 
                 let r = inner.verify_header_graph_ready_block(index);
 
+                if let Err(Error(
+                    ErrorKind::Block(BlockError::MissingAncestorHeaders(
+                        missing_hashes,
+                    )),
+                    _,
+                )) = &r
+                {
+                    if need_to_verify {
+                        debug!(
+                            "Block {:?} waits for headers {:?} to be verified",
+                            inner.arena[index].block_header.hash(),
+                            missing_hashes
+                        );
+                        inner.arena[index].graph_status = BLOCK_HEADER_ONLY;
+                        inner.wait_for_headers(index, missing_hashes);
+                        if index == header_index_to_insert && persistent {
+                            self.data_man.insert_block_header(
+                                inner.arena[index].block_header.hash(),
+                                inner.arena[index].block_header.clone(),
+                                true,
+                            );
+                        }
+                        continue;
+                    }
+                }
+
                 if need_to_verify && r.is_err() {
                     warn!(
                         "Invalid header_arc! inserted_header={:?} err={:?}",
@@ -1284,7 +1969,13 @@ impl SynchronizationGraph {
                         true,
                     );
                 }
-                if insert_to_consensus {
+                let header_first = !insert_to_consensus
+                    && self.sync_config.header_graph_mode
+                    && !inner.locked_for_catchup;
+                if header_first {
+                    inner.arena[index].header_in_consensus = true;
+                }
+                if insert_to_consensus || header_first {
                     CONSENSUS_WORKER_QUEUE.enqueue(1);
 
                     self.consensus_unprocessed_count
@@ -1348,17 +2039,101 @@ impl SynchronizationGraph {
         let _timer = MeterTimer::time_func(SYNC_INSERT_HEADER.as_ref());
         self.statistics.inc_sync_graph_inserted_header_count();
         let inner = &mut *self.inner.write();
+        let result = self.insert_block_header_inner(
+            inner,
+            header,
+            need_to_verify,
+            bench_mode,
+            insert_to_consensus,
+            persistent,
+        );
+        if (result.0.is_new_valid() || result.0.is_invalid())
+            && inner.spilled_headers.contains(&header.hash())
+        {
+            self.reload_spilled_headers(
+                inner,
+                header.hash(),
+                insert_to_consensus,
+            );
+        }
+        result
+    }
+
+    /// Insert again the spilled headers waiting for `parent_hash`, and those
+    /// waiting for the reloaded ones in turn.
+    fn reload_spilled_headers(
+        &self, inner: &mut SynchronizationGraphInner, parent_hash: H256,
+        insert_to_consensus: bool,
+    )
+    {
+        let mut queue = VecDeque::new();
+        queue.push_back(parent_hash);
+        let mut reloaded_count = 0;
+        while let Some(parent_hash) = queue.pop_front() {
+            if inner.spilled_headers.take(&parent_hash).is_none() {
+                continue;
+            }
+            for hash in self.data_man.take_spilled_headers(&parent_hash) {
+                let header = match self.data_man.block_header_by_hash(&hash) {
+                    Some(header) => header,
+                    None => continue,
+                };
+                let (result, _) = self.insert_block_header_inner(
+                    inner,
+                    &mut header.as_ref().clone(),
+                    true,  /* need_to_verify */
+                    false, /* bench_mode */
+                    insert_to_consensus,
+                    false, /* persistent */
+                );
+                if result.is_new_valid() {
+                    inner.reloaded_headers.push(hash);
+                    reloaded_count += 1;
+                }
+                queue.push_back(hash);
+            }
+        }
+        debug!(
+            "Reloaded {} spilled headers in the future of {:?}",
+            reloaded_count, parent_hash
+        );
+    }
+
+    fn insert_block_header_inner(
+        &self, inner: &mut SynchronizationGraphInner, header: &mut BlockHeader,
+        need_to_verify: bool, bench_mode: bool, insert_to_consensus: bool,
+        persistent: bool,
+    ) -> (BlockHeaderInsertionResult, Vec<H256>)
+    {
         if inner.locked_for_catchup {
             // Ignore received headers when we are downloading block bodies.
             return (BlockHeaderInsertionResult::TemporarySkipped, Vec::new());
         }
         let hash = header.hash();
 
-        let (invalid, local_info_opt) = self.data_man.verified_invalid(&hash);
+        let (invalid, mut local_info_opt) =
+            self.data_man.verified_invalid(&hash);
         if invalid {
             return (BlockHeaderInsertionResult::Invalid, Vec::new());
         }
 
+        let expired = local_info_opt
+            .map(|info| info.get_status() == BlockStatus::Expired)
+            .unwrap_or(false);
+        if expired {
+            if !inner
+                .expired_blocks
+                .reinsertion_allowed(&hash, self.clock.now_as_secs())
+            {
+                return (
+                    BlockHeaderInsertionResult::RecentlyExpired,
+                    Vec::new(),
+                );
+            }
+            self.data_man.clear_block_expired(&hash);
+            local_info_opt = None;
+        }
+
         if let Some(info) = local_info_opt {
             // If the block is ordered before current era genesis or it has
             // already entered consensus graph in this run, we do not need to
@@ -1395,6 +2170,32 @@ impl SynchronizationGraph {
                 Vec::new(),
             );
         }
+        // Under memory pressure, only the headers that extend the existing
+        // graph are accepted, so the memory is used to make progress instead
+        // of growing new frontiers whose ancestors are still to be fetched.
+        // Locally generated headers are always accepted.
+        if need_to_verify
+            && self.memory_governor.under_pressure()
+            && !inner
+                .hash_to_arena_indices
+                .contains_key(header.parent_hash())
+            && self
+                .data_man
+                .block_header_by_hash(header.parent_hash())
+                .is_none()
+        {
+            debug!(
+                "Sync graph is under memory pressure, reject header {:?}",
+                hash
+            );
+            return (BlockHeaderInsertionResult::Busy, Vec::new());
+        }
+        if persistent {
+            // Locally generated blocks have been recorded before insertion,
+            // and blocks recovered from db are not persisted again.
+            self.block_propagation
+                .on_first_seen(hash, BlockOrigin::Peer);
+        }
 
         // skip check for consortium currently
         debug!("is_consortium={:?}", self.is_consortium());
@@ -1462,10 +2263,14 @@ impl SynchronizationGraph {
         debug!("insert_block_header() Block = {:?}, index = {}, need_to_verify = {}, bench_mode = {} insert_to_consensus = {}",
                header.hash(), me, need_to_verify, bench_mode, insert_to_consensus);
 
-        // Start to pass influence to descendants
+        // Start to pass influence to descendants, and verify again the blocks
+        // that wait for this header.
+        let mut frontier_index_list = vec![me];
+        frontier_index_list
+            .extend(inner.take_blocks_waiting_for_header(&header.hash()));
         let (invalid_set, need_to_relay) = self.propagate_header_graph_status(
             inner,
-            vec![me],
+            frontier_index_list,
             need_to_verify,
             me,
             insert_to_consensus,
@@ -1486,6 +2291,37 @@ impl SynchronizationGraph {
         (BlockHeaderInsertionResult::NewValid, need_to_relay)
     }
 
+    /// Accept the blocks anchored at the era genesis `new_genesis_hash` of a
+    /// checkpoint in addition to those anchored at the current era genesis,
+    /// until the consensus graph moves to a new era genesis. The header of
+    /// the new genesis must be in the data manager.
+    pub fn begin_genesis_transition(
+        &self, new_genesis_hash: &H256, cutover_height: u64,
+        insert_to_consensus: bool,
+    ) -> Result<(), String>
+    {
+        let header = self
+            .data_man
+            .block_header_by_hash(new_genesis_hash)
+            .ok_or_else(|| {
+                format!("header of new genesis {:?} missing", new_genesis_hash)
+            })?;
+        let inner = &mut *self.inner.write();
+        let waiting = inner.begin_genesis_transition(header, cutover_height)?;
+        if !waiting.is_empty() {
+            let (invalid_set, _) = self.propagate_header_graph_status(
+                inner,
+                waiting,
+                true, /* need_to_verify */
+                NULL, /* header_index_to_insert */
+                insert_to_consensus,
+                false, /* persistent */
+            );
+            inner.process_invalid_blocks(&invalid_set);
+        }
+        Ok(())
+    }
+
     pub fn contains_block(&self, hash: &H256) -> bool {
         let inner = self.inner.read();
         if let Some(index) = inner.hash_to_arena_indices.get(hash) {
@@ -1508,11 +2344,21 @@ impl SynchronizationGraph {
 
         let h = inner.arena[index].block_header.hash();
         debug!("Block {:?} is graph ready", h);
-        CONSENSUS_WORKER_QUEUE.enqueue(1);
+        self.block_propagation.on_graph_ready(&h);
+        if inner.arena[index].header_in_consensus {
+            // The body has been inserted into the data manager, so consensus
+            // can execute the epochs waiting for it.
+            self.consensus.on_new_block_body(&h);
+        } else {
+            CONSENSUS_WORKER_QUEUE.enqueue(1);
 
-        self.consensus_unprocessed_count
-            .fetch_add(1, Ordering::SeqCst);
-        assert!(self.new_block_hashes.send(h), "consensus receiver dropped");
+            self.consensus_unprocessed_count
+                .fetch_add(1, Ordering::SeqCst);
+            assert!(
+                self.new_block_hashes.send(h),
+                "consensus receiver dropped"
+            );
+        }
 
         if inner.config.enable_state_expose {
             STATE_EXPOSER.sync_graph.lock().ready_block_vec.push(
@@ -1588,8 +2434,31 @@ impl SynchronizationGraph {
 
         debug!("insert_block {:?}", hash);
 
+        // The body is verified before the write lock is acquired, so the
+        // blocks received at the same time are verified in parallel.
+        {
+            let inner = self.inner.read();
+            match inner.hash_to_arena_indices.get(&hash) {
+                None => return BlockInsertionResult::Ignored,
+                Some(index) if inner.arena[*index].block_ready => {
+                    return BlockInsertionResult::AlreadyProcessed;
+                }
+                Some(_) => {}
+            }
+        }
+        let verification_result = if need_to_verify {
+            Some(self.verification_config.verify_sync_graph_block_basic(
+                &block,
+                self.consensus.best_chain_id(),
+            ))
+        } else {
+            None
+        };
+
         let inner = &mut *self.inner.write();
 
+        // Check again because the graph may change before the write lock is
+        // acquired.
         let contains_block =
             if let Some(index) = inner.hash_to_arena_indices.get(&hash) {
                 inner.arena[*index].block_ready
@@ -1615,11 +2484,7 @@ impl SynchronizationGraph {
         debug_assert!(!inner.arena[me].block_ready);
         inner.arena[me].block_ready = true;
 
-        if need_to_verify {
-            let r = self.verification_config.verify_sync_graph_block_basic(
-                &block,
-                self.consensus.best_chain_id(),
-            );
+        if let Some(r) = verification_result {
             match r {
                 Err(Error(
                     ErrorKind::Block(BlockError::InvalidTransactionsRoot(e)),
@@ -1673,7 +2538,7 @@ impl SynchronizationGraph {
                 // Invalid blocks will also be removed from
                 // `block_to_fill_set`
                 // in `process_invalid_blocks`.
-                inner.process_invalid_blocks(&invalid_set);
+                self.process_invalid_blocks_in_consensus(inner, &invalid_set);
                 return BlockInsertionResult::Invalid;
             } else {
                 debug!("Downloaded block body for {:?}", hash);
@@ -1685,7 +2550,7 @@ impl SynchronizationGraph {
         let invalid_set = self.propagate_graph_status(inner, vec![me]);
 
         // Post-processing invalid blocks.
-        inner.process_invalid_blocks(&invalid_set);
+        self.process_invalid_blocks_in_consensus(inner, &invalid_set);
 
         debug!(
             "new block inserted into graph: block_header={:?}, tx_count={}, block_size={}",
@@ -1705,6 +2570,25 @@ impl SynchronizationGraph {
         }
     }
 
+    /// Remove the invalid blocks, and notify consensus of those already
+    /// inserted into consensus with only their headers in the header graph
+    /// mode, which cannot be removed from the consensus graph.
+    fn process_invalid_blocks_in_consensus(
+        &self, inner: &mut SynchronizationGraphInner,
+        invalid_set: &HashSet<usize>,
+    )
+    {
+        let invalid_in_consensus: Vec<H256> = invalid_set
+            .iter()
+            .filter(|index| inner.arena[**index].header_in_consensus)
+            .map(|index| inner.arena[*index].block_header.hash())
+            .collect();
+        inner.process_invalid_blocks(invalid_set);
+        for hash in &invalid_in_consensus {
+            self.consensus.on_invalid_block_body(hash);
+        }
+    }
+
     pub fn get_all_block_hashes_by_epoch(
         &self, epoch_number: u64,
     ) -> Result<Vec<H256>, String> {
@@ -1717,8 +2601,66 @@ impl SynchronizationGraph {
         Ok(res)
     }
 
+    /// Export the snapshot of the pivot block `snapshot_epoch_id` to an
+    /// archive in `dir`, which other nodes can import in state sync.
+    pub fn export_snapshot_archive(
+        &self, snapshot_epoch_id: &H256, snapshot_height: u64, dir: &Path,
+        chunk_size: u64,
+    ) -> Result<SnapshotArchiveManifest, String>
+    {
+        export_snapshot_archive(
+            &self.data_man.storage_manager,
+            snapshot_epoch_id,
+            snapshot_height,
+            dir,
+            chunk_size,
+        )
+        .map_err(|e| e.to_string())
+    }
+
+    /// Restore the snapshot of `snapshot_info` from its archive in `dir`
+    /// instead of downloading its chunks from the peers. The merkle root in
+    /// `snapshot_info` must be verified. Return `Ok(false)` if there is no
+    /// archive of the snapshot.
+    pub fn import_snapshot_archive(
+        &self, dir: &Path, snapshot_info: &SnapshotInfo,
+    ) -> Result<bool, String> {
+        restore_snapshot_from_archive(
+            dir,
+            snapshot_info,
+            self.data_man.storage_manager.clone(),
+        )
+        .map_err(|e| e.to_string())
+    }
+
     pub fn log_statistics(&self) { self.statistics.log_statistics(); }
 
+    /// Measure the memory held by block synchronization and update the
+    /// memory pressure state. Return `true` if the node is under memory
+    /// pressure.
+    pub fn update_memory_usage(&self) -> bool {
+        if !self.memory_governor.is_enabled() {
+            return false;
+        }
+        let (sync_graph_bytes, block_to_fill_len) = {
+            let inner = self.inner.read();
+            (
+                inner.graph_size_of(&mut new_malloc_size_ops()),
+                inner.block_to_fill_set.len(),
+            )
+        };
+        let usage = SyncMemoryUsage {
+            sync_graph_bytes,
+            block_cache_bytes: self.data_man.cache_size().total(),
+            consensus_queue_len: self
+                .consensus_unprocessed_count
+                .load(Ordering::SeqCst),
+            block_to_fill_len,
+        };
+        SYNC_MEMORY_USAGE.update(usage.total_bytes());
+        self.memory_governor.update(usage)
+    }
+
     pub fn update_total_weight_delta_heartbeat(&self) {
         self.consensus.update_total_weight_delta_heartbeat();
     }
@@ -1734,10 +2676,7 @@ impl SynchronizationGraph {
     /// blocks which can be reached by `not_ready_blocks_frontier`.
     pub fn remove_expire_blocks(&self, expire_time: u64) {
         let inner = &mut *self.inner.write();
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+        let now = self.clock.now_as_secs();
         let frontier = inner.not_ready_blocks_frontier.get_frontier().clone();
         let all_not_ready: HashSet<_> = inner.get_future(frontier);
         let mut expire_set = HashSet::new();
@@ -1750,6 +2689,14 @@ impl SynchronizationGraph {
         // find blocks reached by previous found expired blocks
         let all_expire: HashSet<_> = inner.get_future(expire_set);
         debug!("all_expire: {:?}", all_expire);
+        // Mark the blocks expired instead of leaving them like invalid ones,
+        // so they can be inserted again if they arrive later.
+        inner.expired_blocks.prune(now);
+        for index in &all_expire {
+            let hash = inner.arena[*index].block_header.hash();
+            inner.expired_blocks.on_expired(hash, now);
+            self.data_man.mark_block_expired(&hash);
+        }
         inner.remove_blocks(&all_expire);
     }
 
@@ -1767,6 +2714,66 @@ impl SynchronizationGraph {
         inner.remove_blocks(&index_set_and_future);
     }
 
+    /// Remove the pending blocks of large invalidation cascades. The lock is
+    /// released between batches so other operations are not stalled.
+    pub fn remove_pending_invalid_blocks(&self) {
+        loop {
+            let mut inner = self.inner.write();
+            if inner.pending_invalid_blocks.is_empty()
+                || !inner.remove_pending_invalid_blocks()
+            {
+                break;
+            }
+        }
+    }
+
+    /// Spill the least recently updated orphan headers to the database if
+    /// there are more than `max_orphan_headers` of them.
+    pub fn spill_orphan_headers(&self) {
+        let max_orphan_headers = match self.sync_config.max_orphan_headers {
+            Some(max_orphan_headers) => max_orphan_headers,
+            None => return,
+        };
+        let inner = &mut *self.inner.write();
+        if inner.locked_for_catchup {
+            return;
+        }
+        let spilled_count = inner.spill_orphan_headers(max_orphan_headers);
+        if spilled_count != 0 {
+            info!(
+                "Spilled {} orphan headers, {} spilled in total",
+                spilled_count,
+                inner.spilled_headers.header_count()
+            );
+        }
+    }
+
+    /// Return the headers inserted again from the spilled ones since the last
+    /// call, whose bodies are to be requested.
+    pub fn take_reloaded_headers(&self) -> Vec<H256> {
+        mem::replace(&mut self.inner.write().reloaded_headers, Vec::new())
+    }
+
+    /// Return at most `limit` missing parents and referees with the numbers
+    /// of headers waiting for them, the most wanted first. The numbers are
+    /// updated when the requests are scheduled.
+    pub fn most_wanted_dependencies(&self, limit: usize) -> Vec<(H256, usize)> {
+        self.inner.read().missing_dependencies.most_wanted(limit)
+    }
+
+    /// Return at most `limit` missing parents and referees to be requested
+    /// again, because their previous requests may have been lost.
+    pub fn schedule_dependency_requests(&self, limit: usize) -> Vec<H256> {
+        let inner = &mut *self.inner.write();
+        if inner.locked_for_catchup {
+            return Vec::new();
+        }
+        let now = self.clock.now_as_secs();
+        let blocked_counts = inner.collect_missing_dependencies();
+        inner.missing_dependencies.update(blocked_counts, now);
+        inner.missing_dependencies.take_due(now, limit)
+    }
+
     pub fn is_consensus_worker_busy(&self) -> bool {
         self.consensus_unprocessed_count.load(Ordering::SeqCst) != 0
     }
@@ -1826,8 +2833,13 @@ impl SynchronizationGraph {
                 .iter()
                 .map(|(_, i)| *i)
                 .collect();
-            // Send blocks in topological order.
-            let sorted_blocks = inner.topological_sort(all_block_indices);
+            // Send blocks in topological order. The ties are broken by block
+            // hashes as in the epoch block ordering of consensus, so the
+            // blocks are inserted in the same order as they are executed.
+            let sorted_blocks = inner.topological_sort_with_order_indicator(
+                all_block_indices,
+                |i| inner.arena[i].block_header.hash(),
+            );
             for i in sorted_blocks {
                 self.consensus
                     .on_new_block(&inner.arena[i].block_header.hash());
@@ -1934,6 +2946,14 @@ pub enum BlockHeaderInsertionResult {
     Invalid,
     // The header is received when we have locked sync graph.
     TemporarySkipped,
+    // The header is rejected because the node is under memory pressure and
+    // the header does not connect to the sync graph. It is not inserted and
+    // should be requested again later.
+    Busy,
+    // The block was removed from the sync graph as expired and arrives again
+    // before its reinsertion backoff elapses. It is not inserted and should
+    // be requested again later.
+    RecentlyExpired,
 }
 
 impl BlockHeaderInsertionResult {
@@ -1945,6 +2965,14 @@ impl BlockHeaderInsertionResult {
         matches!(self, BlockHeaderInsertionResult::Invalid)
     }
 
+    pub fn is_busy(&self) -> bool {
+        matches!(self, BlockHeaderInsertionResult::Busy)
+    }
+
+    pub fn is_recently_expired(&self) -> bool {
+        matches!(self, BlockHeaderInsertionResult::RecentlyExpired)
+    }
+
     pub fn should_process_body(&self) -> bool {
         matches!(
             self,
@@ -7,7 +7,8 @@ use super::{
     SharedSynchronizationGraph, SynchronizationState,
 };
 use crate::{
-    block_data_manager::BlockStatus,
+    block_data_manager::{BlockOrigin, BlockStatus},
+    config_cell::ConfigCell,
     light_protocol::Provider as LightProvider,
     message::{decode_msg, Message, MsgId},
     sync::{
@@ -46,7 +47,7 @@ use std::{
     cmp::{self, min},
     collections::{BTreeMap, HashMap, HashSet, VecDeque},
     sync::Arc,
-    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+    time::{Duration, Instant},
 };
 
 lazy_static! {
@@ -73,6 +74,7 @@ const CHECK_PEER_HEARTBEAT_TIMER: TimerToken = 6;
 const CHECK_FUTURE_BLOCK_TIMER: TimerToken = 7;
 const EXPIRE_BLOCK_GC_TIMER: TimerToken = 8;
 const HEARTBEAT_TIMER: TimerToken = 9;
+const INVALID_BLOCK_GC_TIMER: TimerToken = 10;
 pub const CHECK_RPC_REQUEST_TIMER: TimerToken = 11;
 
 const MAX_TXS_BYTES_TO_PROPAGATE: usize = 1024 * 1024; // 1MB
@@ -85,6 +87,8 @@ const EPOCH_SYNC_RESTART_TIMEOUT_S: u64 = 60 * 10;
 const EPOCH_SYNC_MAX_INFLIGHT: u64 = 300;
 const EPOCH_SYNC_BATCH_SIZE: u64 = 30;
 const BLOCK_SYNC_MAX_INFLIGHT: usize = 1000;
+/// The maximum number of missing headers requested again in each check.
+const MAX_DEPENDENCY_REREQUESTS_PER_CHECK: usize = 256;
 
 #[derive(Debug, Clone, Copy, Ord, PartialOrd, Eq, PartialEq)]
 pub enum SyncHandlerWorkType {
@@ -387,7 +391,8 @@ pub struct ProtocolConfiguration {
     pub heartbeat_timeout: Duration,
     pub block_cache_gc_period: Duration,
     pub expire_block_gc_period: Duration,
-    pub sync_expire_block_timeout: Duration,
+    #[ignore_malloc_size_of = "insignificant"]
+    pub sync_expire_block_timeout: ConfigCell<Duration>,
     pub headers_request_timeout: Duration,
     pub blocks_request_timeout: Duration,
     pub transaction_request_timeout: Duration,
@@ -476,12 +481,12 @@ impl SynchronizationProtocolHandler {
 
     pub fn is_consortium(&self) -> bool { self.protocol_config.is_consortium }
 
-    fn get_to_propagate_trans(&self) -> HashMap<H256, Arc<SignedTransaction>> {
+    fn get_to_propagate_trans(&self) -> Vec<Arc<SignedTransaction>> {
         self.graph.get_to_propagate_trans()
     }
 
     fn set_to_propagate_trans(
-        &self, transactions: HashMap<H256, Arc<SignedTransaction>>,
+        &self, transactions: Vec<Arc<SignedTransaction>>,
     ) {
         self.graph.set_to_propagate_trans(transactions);
     }
@@ -615,6 +620,10 @@ impl SynchronizationProtocolHandler {
                 disconnect = false;
                 warn = false;
             }
+            ErrorKind::Busy(_) => {
+                disconnect = false;
+                warn = false;
+            }
             ErrorKind::TooManyTrans => {}
             ErrorKind::InvalidTimestamp => {
                 op = Some(UpdateNodeOperation::Demotion)
@@ -791,15 +800,42 @@ impl SynchronizationProtocolHandler {
 
     /// Request missing block bodies from random peers in batches.
     pub fn request_block_bodies(&self, io: &dyn NetworkContext) {
+        if self.graph.memory_governor.under_pressure() {
+            debug!("Sync graph is under memory pressure, skip body requests");
+            return;
+        }
         let in_flight_blocks = self.request_manager.in_flight_blocks();
-        let to_request_blocks: Vec<_> = self
-            .graph
-            .inner
-            .read()
-            .block_to_fill_set
-            .difference(&in_flight_blocks)
-            .copied()
-            .collect();
+        let to_request_blocks: Vec<_> = {
+            let mut inner = self.graph.inner.write();
+            // The bodies persisted since the set is computed, e.g. inserted
+            // from a replica feed, are not requested again.
+            let persisted_blocks: Vec<H256> = inner
+                .block_to_fill_set
+                .iter()
+                .filter(|hash| {
+                    inner.hash_to_arena_indices.get(*hash).map_or(
+                        false,
+                        |index| {
+                            let height =
+                                inner.arena[*index].block_header.height();
+                            self.graph
+                                .data_man
+                                .block_body_availability(hash, height)
+                                == Some(true)
+                        },
+                    )
+                })
+                .copied()
+                .collect();
+            for hash in &persisted_blocks {
+                inner.block_to_fill_set.remove(hash);
+            }
+            inner
+                .block_to_fill_set
+                .difference(&in_flight_blocks)
+                .copied()
+                .collect()
+        };
         let n_blocks_to_request = min(
             BLOCK_SYNC_MAX_INFLIGHT - in_flight_blocks.len(),
             to_request_blocks.len(),
@@ -1036,6 +1072,14 @@ impl SynchronizationProtocolHandler {
                         false, // insert_into_consensus
                         true,  // persistent
                     );
+                    if insert_result.is_busy()
+                        || insert_result.is_recently_expired()
+                    {
+                        // Leave it not received so the block is requested
+                        // again after the memory pressure is relieved or the
+                        // reinsertion backoff elapses.
+                        continue;
+                    }
                     if !insert_result.should_process_body() {
                         // If the header is invalid or the block has been
                         // processed in consensus, we do not need to request the
@@ -1116,7 +1160,7 @@ impl SynchronizationProtocolHandler {
         self.on_message(io, &io.self_node_id(), task.message.as_slice());
     }
 
-    pub fn on_mined_block(&self, mut block: Block) {
+    pub fn on_mined_block(&self, mut block: Block, origin: BlockOrigin) {
         let hash = block.block_header.hash();
         info!("Mined block {:?} header={:?}", hash, block.block_header);
         let parent_hash = *block.block_header.parent_hash();
@@ -1126,6 +1170,7 @@ impl SynchronizationProtocolHandler {
             warn!("Mined an duplicate block, the mining power is wasted!");
             return;
         }
+        self.graph.block_propagation.on_first_seen(hash, origin);
         self.graph.insert_block_header(
             &mut block.block_header,
             false,
@@ -1334,7 +1379,9 @@ impl SynchronizationProtocolHandler {
 
             let received_pool =
                 self.request_manager.received_transactions.read();
-            for (_, tx) in transactions.iter() {
+            // The replacements of propagated transactions are placed first, so
+            // they are sent before the byte limit is reached.
+            for tx in transactions.iter() {
                 total_tx_bytes += tx.rlp_size();
                 if total_tx_bytes >= MAX_TXS_BYTES_TO_PROPAGATE {
                     break;
@@ -1346,16 +1393,10 @@ impl SynchronizationProtocolHandler {
                 }
             }
 
-            if short_ids_transactions.len() + tx_hashes_transactions.len()
-                != transactions.len()
-            {
-                for tx in short_ids_transactions.iter() {
-                    transactions.remove(&tx.hash);
-                }
-                for tx in tx_hashes_transactions.iter() {
-                    transactions.remove(&tx.hash);
-                }
-                self.set_to_propagate_trans(transactions);
+            let sent_count =
+                short_ids_transactions.len() + tx_hashes_transactions.len();
+            if sent_count != transactions.len() {
+                self.set_to_propagate_trans(transactions.split_off(sent_count));
             }
 
             (short_ids_transactions, tx_hashes_transactions)
@@ -1435,23 +1476,14 @@ impl SynchronizationProtocolHandler {
         }
 
         if resend_flag {
-            let mut resend_transactions: HashMap<H256, Arc<SignedTransaction>> =
-                HashMap::new();
-            for tx in short_ids_transactions {
-                resend_transactions.insert(tx.hash, tx.clone());
-            }
-            for tx in tx_hashes_transactions {
-                resend_transactions.insert(tx.hash, tx.clone());
-            }
+            let mut resend_transactions = short_ids_transactions;
+            resend_transactions.extend(tx_hashes_transactions);
             self.set_to_propagate_trans(resend_transactions);
         }
     }
 
     pub fn check_future_blocks(&self, io: &dyn NetworkContext) {
-        let now_timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+        let now_timestamp = self.graph.clock.now_as_secs();
 
         let mut missed_body_block_hashes = HashMap::new();
         let mut need_to_relay = HashSet::new();
@@ -1470,6 +1502,11 @@ impl SynchronizationProtocolHandler {
                 self.insert_header_to_consensus(),
                 true,
             );
+            if insert_result.is_busy() {
+                // Keep the header and retry it in the next check.
+                self.graph.future_blocks.insert(header, peer);
+                continue;
+            }
             if insert_result.is_new_valid() {
                 need_to_relay.extend(to_relay);
 
@@ -1530,6 +1567,38 @@ impl SynchronizationProtocolHandler {
         self.handle_cancelled_requests(cancelled_requests);
     }
 
+    /// Request the bodies of the headers inserted again after being spilled
+    /// from the sync graph, which are not requested when their headers are
+    /// received.
+    fn request_reloaded_blocks(&self, io: &dyn NetworkContext) {
+        let hashes: Vec<H256> = self
+            .graph
+            .take_reloaded_headers()
+            .into_iter()
+            .filter(|hash| !self.graph.contains_block(hash))
+            .collect();
+        if !hashes.is_empty() && self.need_requesting_blocks() {
+            self.request_missing_blocks(io, None, hashes);
+        }
+    }
+
+    /// Request again the missing parents and referees of the headers in the
+    /// sync graph, which are requested once when the headers are received.
+    fn request_missing_dependencies(&self, io: &dyn NetworkContext) {
+        if self.in_recover_from_db_phase() {
+            return;
+        }
+        let hashes = self
+            .graph
+            .schedule_dependency_requests(MAX_DEPENDENCY_REREQUESTS_PER_CHECK);
+        if !hashes.is_empty() {
+            debug!("Request missing dependencies again: {:?}", hashes);
+            self.request_block_headers(
+                io, None, hashes, false, /* ignore_db */
+            );
+        }
+    }
+
     /// Remove the blocks in `cancelled_requests` and their future set from sync
     /// graph, so if they are needed in the future, they will be requested
     /// again.
@@ -1558,7 +1627,10 @@ impl SynchronizationProtocolHandler {
         self.graph.data_man.cache_gc();
         self.graph
             .data_man
-            .database_gc(self.graph.consensus.best_epoch_number())
+            .database_gc(self.graph.consensus.best_epoch_number());
+        self.graph.spill_orphan_headers();
+        // Measure after the cache gc so the released memory is not counted.
+        self.graph.update_memory_usage();
     }
 
     fn log_statistics(&self) { self.graph.log_statistics(); }
@@ -1783,6 +1855,8 @@ impl NetworkProtocolHandler for SynchronizationProtocolHandler {
             self.protocol_config.expire_block_gc_period,
         )
         .expect("Error registering EXPIRE_BLOCK_GC_TIMER");
+        io.register_timer(INVALID_BLOCK_GC_TIMER, Duration::from_millis(100))
+            .expect("Error registering INVALID_BLOCK_GC_TIMER");
     }
 
     fn send_local_message(&self, io: &dyn NetworkContext, message: Vec<u8>) {
@@ -1872,6 +1946,8 @@ impl NetworkProtocolHandler for SynchronizationProtocolHandler {
             }
             CHECK_REQUEST_TIMER => {
                 self.remove_expired_flying_request(io);
+                self.request_reloaded_blocks(io);
+                self.request_missing_dependencies(io);
             }
             HEARTBEAT_TIMER => {
                 self.send_heartbeat(io);
@@ -1907,10 +1983,16 @@ impl NetworkProtocolHandler for SynchronizationProtocolHandler {
                 // exist in the frontier across two consecutive GC.
                 self.expire_block_gc(
                     io,
-                    self.protocol_config.sync_expire_block_timeout.as_secs(),
+                    self.protocol_config
+                        .sync_expire_block_timeout
+                        .get()
+                        .as_secs(),
                 )
                 .ok();
             }
+            INVALID_BLOCK_GC_TIMER => {
+                self.graph.remove_pending_invalid_blocks();
+            }
             _ => warn!("Unknown timer {} triggered.", timer),
         }
     }
@@ -6,11 +6,13 @@ use super::{
     Error, SharedSynchronizationGraph, SynchronizationProtocolHandler,
 };
 use crate::{
+    block_data_manager::BlockOrigin,
     light_protocol::Provider as LightProvider,
     sync::{
         request_manager::RequestManager, synchronization_phases::SyncPhaseType,
         synchronization_protocol_handler::ProtocolConfiguration,
-        StateSyncConfiguration, SynchronizationPhaseTrait,
+        SnapshotArchiveManifest, StateSyncConfiguration,
+        SynchronizationPhaseTrait,
     },
     NodeType,
 };
@@ -18,7 +20,7 @@ use cfx_types::H256;
 use malloc_size_of_derive::MallocSizeOf as DeriveMallocSizeOf;
 use network::{NetworkService, ProtocolId};
 use primitives::{transaction::SignedTransaction, Block};
-use std::sync::Arc;
+use std::{path::Path, sync::Arc};
 
 #[derive(DeriveMallocSizeOf)]
 pub struct SynchronizationService {
@@ -64,6 +66,28 @@ impl SynchronizationService {
         self.protocol_handler.get_synchronization_graph()
     }
 
+    /// Export the snapshot of the pivot block `snapshot_epoch_id` to an
+    /// archive in `snapshot_archive_dir`.
+    pub fn export_snapshot_archive(
+        &self, snapshot_epoch_id: &H256, snapshot_height: u64,
+    ) -> Result<SnapshotArchiveManifest, String> {
+        let dir = self
+            .protocol_handler
+            .state_sync
+            .archive_dir()
+            .ok_or("Snapshot archive is not enabled")?;
+        self.get_synchronization_graph().export_snapshot_archive(
+            snapshot_epoch_id,
+            snapshot_height,
+            Path::new(dir),
+            self.protocol_handler.protocol_config.chunk_size_byte,
+        )
+    }
+
+    pub fn protocol_config(&self) -> &ProtocolConfiguration {
+        &self.protocol_handler.protocol_config
+    }
+
     pub fn get_request_manager(&self) -> Arc<RequestManager> {
         self.protocol_handler.get_request_manager()
     }
@@ -96,9 +120,11 @@ impl SynchronizationService {
         )?
     }
 
-    pub fn on_mined_block(&self, block: Block) -> Result<(), Error> {
+    pub fn on_mined_block(
+        &self, block: Block, origin: BlockOrigin,
+    ) -> Result<(), Error> {
         let hash = block.hash();
-        self.protocol_handler.on_mined_block(block);
+        self.protocol_handler.on_mined_block(block, origin);
         self.relay_blocks(vec![hash])
     }
 
@@ -3,32 +3,55 @@
 // See http://www.gnu.org/licenses/
 
 use crate::{
-    block_data_manager::DbType,
+    block_data_manager::{
+        DbType, EpochSystemTransactionReceipts, PartialInvalidCause,
+        SystemTransactionReceipt, TerminalsDelta,
+    },
+    clock::{Clock, ManualClock},
+    consensus::ConsensusGraph,
+    pow::{self, PowComputer},
     sync::{
-        utils::{create_simple_block_impl, initialize_synchronization_graph},
-        SynchronizationGraphNode,
+        utils::{
+            create_simple_block_impl, initialize_data_manager,
+            initialize_header_graph_mode_synchronization_graph,
+            initialize_synchronization_graph,
+            initialize_synchronization_graph_with_clock,
+        },
+        SynchronizationGraph, SynchronizationGraphNode,
     },
+    verification::compute_transaction_root,
+    vm_factory::VmFactory,
+    ConsensusGraphTrait,
+};
+use cfx_parameters::consensus::GENESIS_GAS_LIMIT;
+use cfx_types::{
+    address_util::AddressUtil, Address, BigEndianHash, H256, U256,
+};
+use dag::DAG;
+use keylib::{Generator, Random};
+use primitives::{
+    receipt::TRANSACTION_OUTCOME_SUCCESS, Action, Block, BlockHeaderBuilder,
+    Receipt, Transaction,
 };
-use cfx_types::{BigEndianHash, H256, U256};
-use primitives::Block;
 use std::{
-    fs,
-    sync::Arc,
-    thread::sleep,
-    time::{Duration, SystemTime, UNIX_EPOCH},
+    collections::HashSet, fs, iter::once, sync::Arc, thread::sleep,
+    time::Duration,
 };
 
 #[test]
 fn test_remove_expire_blocks() {
+    let db_dir = "./test_remove_expire_blocks";
     {
-        let (sync, _, _, _) = initialize_synchronization_graph(
-            "./test.db/",
+        let clock = Arc::new(ManualClock::new(Duration::from_secs(10_000)));
+        let (sync, _, _, _) = initialize_synchronization_graph_with_clock(
+            db_dir,
             1,
             1,
             1,
             1,
             50000,
             DbType::Rocksdb,
+            clock.clone(),
         );
         // test initialization
         {
@@ -125,11 +148,8 @@ fn test_remove_expire_blocks() {
                     pending_referee_count: 0,
                     referrers: referrers[i as usize].clone(),
                     block_header: Arc::new(blocks[i].block_header.clone()),
-                    last_update_timestamp: SystemTime::now()
-                        .duration_since(UNIX_EPOCH)
-                        .unwrap()
-                        .as_secs()
-                        - 100,
+                    last_update_timestamp: clock.now_as_secs(),
+                    header_in_consensus: false,
                 });
                 assert_eq!(me, i);
                 inner
@@ -169,7 +189,8 @@ fn test_remove_expire_blocks() {
 
         // not expire any blocks
         {
-            sync.remove_expire_blocks(1000 /* expire_time */);
+            clock.advance(Duration::from_secs(500));
+            sync.remove_expire_blocks(500 /* expire_time */);
             let inner = sync.inner.read();
             assert!(inner.arena.len() == 12);
             assert!(inner.hash_to_arena_indices.len() == 12);
@@ -183,14 +204,16 @@ fn test_remove_expire_blocks() {
 
         // expire [10, 11]
         {
+            let now = clock.now_as_secs();
             let mut inner = sync.inner.write();
-            inner.arena[10].last_update_timestamp = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs()
-                - 1000;
+            for (index, node) in inner.arena.iter_mut() {
+                if index != 10 {
+                    node.last_update_timestamp = now;
+                }
+            }
         }
         {
+            clock.advance(Duration::from_secs(1));
             sync.remove_expire_blocks(500 /* expire_time */);
             let inner = sync.inner.read();
             assert!(inner.arena.len() == 10);
@@ -205,19 +228,16 @@ fn test_remove_expire_blocks() {
 
         // expire [9, 7]
         {
+            let now = clock.now_as_secs();
             let mut inner = sync.inner.write();
-            inner.arena[7].last_update_timestamp = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs()
-                - 1000;
-            inner.arena[9].last_update_timestamp = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs()
-                - 1000;
+            for (index, node) in inner.arena.iter_mut() {
+                if index != 7 && index != 9 {
+                    node.last_update_timestamp = now;
+                }
+            }
         }
         {
+            clock.advance(Duration::from_secs(500));
             sync.remove_expire_blocks(500 /* expire_time */);
             let inner = sync.inner.read();
             assert!(inner.arena.len() == 5);
@@ -227,11 +247,754 @@ fn test_remove_expire_blocks() {
         }
     }
 
+    remove_test_db(db_dir);
+}
+
+fn remove_test_db(db_dir: &str) {
     let mut retry = 3;
-    while let Err(e) = fs::remove_dir_all("./test.db") {
-        println!("failed to remove directory test.db, err = {:?}", e);
+    while let Err(e) = fs::remove_dir_all(db_dir) {
+        println!("failed to remove directory {}, err = {:?}", db_dir, e);
         assert!(retry > 0);
         retry -= 1;
         sleep(Duration::from_millis(300));
     }
 }
+
+/// Create a chain of `len` blocks on `parent_hash`.
+fn create_chain(
+    parent_hash: H256, parent_height: u64, len: u64, first_nonce: u64,
+) -> Vec<Block> {
+    let mut blocks: Vec<Block> = Vec::new();
+    for i in 0..len {
+        let parent_hash = blocks.last().map_or(parent_hash, |b| b.hash());
+        let (_, block) = create_simple_block_impl(
+            parent_hash,
+            vec![],
+            parent_height + i + 1,
+            U256::from(first_nonce + i),
+            U256::from(10),
+            1,
+            false,
+        );
+        blocks.push(block);
+    }
+    blocks
+}
+
+/// Create a block whose transactions exceed the gas limit in its header, so
+/// its header is valid but its body is not.
+fn create_block_with_invalid_body(
+    parent_hash: H256, height: u64, nonce: u64,
+) -> Block {
+    let transactions = vec![Arc::new(
+        Transaction {
+            nonce: U256::zero(),
+            gas_price: U256::one(),
+            gas: U256::from(GENESIS_GAS_LIMIT) + 1,
+            action: Action::Call(Address::random()),
+            value: U256::zero(),
+            storage_limit: 0,
+            epoch_height: 0,
+            chain_id: 0,
+            data: vec![],
+        }
+        .sign(Random.generate().unwrap().secret()),
+    )];
+    let mut author = Address::zero();
+    author.set_user_account_type_bits();
+    let mut header = BlockHeaderBuilder::new()
+        .with_parent_hash(parent_hash)
+        .with_height(height)
+        .with_transactions_root(compute_transaction_root(&transactions))
+        .with_gas_limit(GENESIS_GAS_LIMIT.into())
+        .with_nonce(U256::from(nonce))
+        .with_difficulty(U256::from(10))
+        .with_author(author)
+        .build();
+    header.compute_hash();
+    header.pow_hash =
+        Some(pow::pow_quality_to_hash(&U256::from(10), &header.nonce()));
+    Block::new(header, transactions)
+}
+
+fn insert_headers(
+    sync: &SynchronizationGraph, consensus: &ConsensusGraph, blocks: &[Block],
+) {
+    let expected = consensus.get_processed_block_count() + blocks.len();
+    for block in blocks {
+        let mut header = block.block_header.clone();
+        sync.insert_block_header(
+            &mut header,
+            false, /* need_to_verify */
+            true,  /* bench_mode */
+            false, /* insert_to_consensus */
+            true,  /* persistent */
+        );
+    }
+    // The headers are inserted into consensus by the consensus worker.
+    while consensus.get_processed_block_count() < expected {
+        sleep(Duration::from_millis(10));
+    }
+}
+
+fn insert_bodies(sync: &SynchronizationGraph, blocks: &[Block]) {
+    for block in blocks {
+        sync.insert_block(
+            block.clone(),
+            false, /* need_to_verify */
+            false, /* persistent */
+            false, /* recover_from_db */
+        );
+    }
+}
+
+#[test]
+fn test_header_graph_mode_deferred_execution() {
+    let db_dir = "./test_header_graph_mode_deferred.db/";
+    {
+        let (sync, consensus, _, genesis) =
+            initialize_header_graph_mode_synchronization_graph(
+                db_dir, 1000, 1000, 1000, 1000, 50000,
+            );
+        consensus.construct_pivot_state();
+
+        let blocks = create_chain(genesis.hash(), 0, 8, 1);
+        insert_headers(&sync, &consensus, &blocks);
+        assert_eq!(consensus.best_epoch_number(), 8);
+        // No body has arrived, so the execution is deferred from epoch 1.
+        assert_eq!(consensus.inner.read().execution_deferred_at(), Some(1));
+
+        // The execution resumes until the next missing body.
+        insert_bodies(&sync, &blocks[..2]);
+        assert_eq!(consensus.inner.read().execution_deferred_at(), Some(3));
+
+        insert_bodies(&sync, &blocks[2..]);
+        let inner = consensus.inner.read();
+        assert_eq!(inner.execution_deferred_at(), None);
+        assert!(blocks.iter().all(|b| !inner.block_body_missing(&b.hash())));
+    }
+    remove_test_db(db_dir);
+}
+
+#[test]
+fn test_header_graph_mode_invalid_body() {
+    let db_dir = "./test_header_graph_mode_invalid_body.db/";
+    {
+        let (sync, consensus, _, genesis) =
+            initialize_header_graph_mode_synchronization_graph(
+                db_dir, 1000, 1000, 1000, 1000, 50000,
+            );
+        consensus.construct_pivot_state();
+
+        let (_, first) = create_simple_block_impl(
+            genesis.hash(),
+            vec![],
+            1,
+            U256::from(1),
+            U256::from(10),
+            1,
+            false,
+        );
+        let invalid = create_block_with_invalid_body(first.hash(), 2, 2);
+        let mut attacker_chain = vec![invalid.clone()];
+        attacker_chain.extend(create_chain(invalid.hash(), 2, 7, 3));
+        insert_headers(&sync, &consensus, &[first.clone()]);
+        insert_headers(&sync, &consensus, &attacker_chain);
+        assert_eq!(consensus.best_epoch_number(), 9);
+        insert_bodies(&sync, &[first.clone()]);
+        assert_eq!(consensus.inner.read().execution_deferred_at(), Some(2));
+
+        // The invalid body invalidates the attacker chain in the sync graph,
+        // and the epoch of the block is never executed.
+        sync.insert_block(
+            invalid.clone(),
+            true,  /* need_to_verify */
+            false, /* persistent */
+            false, /* recover_from_db */
+        );
+        assert!(!sync.contains_block_header(&invalid.hash()));
+        {
+            let inner = consensus.inner.read();
+            assert!(!inner.block_body_missing(&invalid.hash()));
+            assert_eq!(inner.execution_deferred_at(), Some(2));
+        }
+
+        // The execution continues after the pivot chain switches to a heavier
+        // honest chain.
+        let honest_chain = create_chain(first.hash(), 1, 9, 100);
+        insert_headers(&sync, &consensus, &honest_chain);
+        assert_eq!(consensus.best_epoch_number(), 10);
+        insert_bodies(&sync, &honest_chain);
+        assert_eq!(consensus.inner.read().execution_deferred_at(), None);
+    }
+    remove_test_db(db_dir);
+}
+
+fn insert_blocks(
+    sync: &SynchronizationGraph, consensus: &ConsensusGraph, blocks: &[Block],
+) {
+    let expected = consensus.get_processed_block_count() + blocks.len();
+    for block in blocks {
+        let mut header = block.block_header.clone();
+        sync.insert_block_header(
+            &mut header,
+            false, /* need_to_verify */
+            true,  /* bench_mode */
+            false, /* insert_to_consensus */
+            true,  /* persistent */
+        );
+        sync.insert_block(
+            block.clone(),
+            false, /* need_to_verify */
+            false, /* persistent */
+            false, /* recover_from_db */
+        );
+    }
+    while consensus.get_processed_block_count() < expected {
+        sleep(Duration::from_millis(10));
+    }
+}
+
+fn check_predicted_adaptive_flag(
+    consensus: &ConsensusGraph, parent_hash: &H256, referees: &Vec<H256>,
+) {
+    let difficulty = U256::from(10);
+    let predicted = {
+        let inner = consensus.inner.read();
+        consensus.predict_mining_adaptive_block(
+            &inner,
+            parent_hash,
+            referees,
+            &difficulty,
+        )
+    };
+    let expected = consensus.check_mining_adaptive_block(
+        &mut *consensus.inner.write(),
+        parent_hash,
+        referees,
+        &difficulty,
+    );
+    assert_eq!(predicted, expected, "parent={:?}", parent_hash);
+}
+
+#[test]
+fn test_predict_mining_adaptive_block() {
+    let db_dir = "./test_predict_mining_adaptive_block.db/";
+    {
+        // Every block on the main chain is a timer block, and a light block
+        // forks from each of them.
+        let (sync, consensus, _, genesis) = initialize_synchronization_graph(
+            db_dir,
+            1000,
+            1000,
+            2,
+            4,
+            50000,
+            DbType::Rocksdb,
+        );
+        consensus.construct_pivot_state();
+
+        let mut main_chain = vec![genesis.hash()];
+        let mut forks = Vec::new();
+        let mut nonce = 1;
+        for round in 0..3 {
+            let mut blocks = Vec::new();
+            for _ in 0..6 {
+                let parent_hash = *main_chain.last().unwrap();
+                let height = main_chain.len() as u64;
+                let (fork_hash, fork) = create_simple_block_impl(
+                    parent_hash,
+                    vec![],
+                    height,
+                    U256::from(nonce),
+                    U256::from(10),
+                    1,
+                    false,
+                );
+                let (hash, block) = create_simple_block_impl(
+                    parent_hash,
+                    vec![],
+                    height,
+                    U256::from(nonce + 1),
+                    U256::from(10),
+                    2,
+                    false,
+                );
+                nonce += 2;
+                main_chain.push(hash);
+                forks.push(fork_hash);
+                blocks.push(fork);
+                blocks.push(block);
+            }
+            // The subtree weights cached in the previous round are stale
+            // after the blocks of this round are inserted.
+            insert_blocks(&sync, &consensus, &blocks);
+            let tip = vec![*main_chain.last().unwrap()];
+            for fork_hash in &forks {
+                check_predicted_adaptive_flag(&consensus, fork_hash, &tip);
+            }
+            for hash in &main_chain[round * 6..] {
+                check_predicted_adaptive_flag(&consensus, hash, &vec![]);
+            }
+        }
+    }
+    remove_test_db(db_dir);
+}
+
+#[test]
+fn test_validate_block() {
+    let db_dir = "./test_validate_block.db/";
+    {
+        let (sync, consensus, _, genesis) = initialize_synchronization_graph(
+            db_dir,
+            1000,
+            1000,
+            2,
+            4,
+            50000,
+            DbType::Rocksdb,
+        );
+        consensus.construct_pivot_state();
+        let difficulty =
+            consensus.inner.read().expected_difficulty(&genesis.hash());
+
+        // A chain of three blocks and a fork of one block.
+        let mut blocks = Vec::new();
+        let mut main_chain = vec![genesis.hash()];
+        for height in 1..=3 {
+            let (hash, block) = create_simple_block_impl(
+                *main_chain.last().unwrap(),
+                vec![],
+                height,
+                U256::from(height),
+                difficulty,
+                1,
+                false,
+            );
+            main_chain.push(hash);
+            blocks.push(block);
+        }
+        let (fork_hash, fork) = create_simple_block_impl(
+            genesis.hash(),
+            vec![],
+            1,
+            U256::from(4),
+            difficulty,
+            1,
+            false,
+        );
+        blocks.push(fork);
+        insert_blocks(&sync, &consensus, &blocks);
+
+        // The block choosing the light fork as its parent while referencing
+        // the heavy chain picks an incorrect parent.
+        let (invalid_hash, invalid) = create_simple_block_impl(
+            fork_hash,
+            vec![main_chain[3]],
+            2,
+            U256::from(5),
+            difficulty,
+            1,
+            false,
+        );
+        let (valid_hash, valid) = create_simple_block_impl(
+            main_chain[3],
+            vec![fork_hash],
+            4,
+            U256::from(6),
+            difficulty,
+            1,
+            false,
+        );
+        for block in &[&invalid, &valid] {
+            sync.data_man.insert_block_header(
+                block.hash(),
+                Arc::new(block.block_header.clone()),
+                false, /* persistent */
+            );
+        }
+        let best_block_hash = consensus.best_block_hash();
+        assert_eq!(
+            consensus
+                .validate_block(&invalid_hash)
+                .unwrap()
+                .partial_invalid_cause,
+            Some(PartialInvalidCause::IncorrectParent)
+        );
+        assert_eq!(
+            consensus
+                .validate_block(&valid_hash)
+                .unwrap()
+                .partial_invalid_cause,
+            None
+        );
+        // The validation does not change the graph.
+        assert_eq!(consensus.best_block_hash(), best_block_hash);
+        assert_eq!(
+            consensus
+                .validate_block(&invalid_hash)
+                .unwrap()
+                .partial_invalid_cause,
+            Some(PartialInvalidCause::IncorrectParent)
+        );
+
+        // The verdicts agree with the ones after the blocks are inserted.
+        insert_blocks(&sync, &consensus, &[invalid, valid]);
+        let inner = consensus.inner.read();
+        assert_eq!(inner.is_partial_invalid(&invalid_hash), Some(true));
+        assert_eq!(inner.is_partial_invalid(&valid_hash), Some(false));
+        assert_eq!(
+            sync.data_man
+                .local_block_info_by_hash(&invalid_hash)
+                .unwrap()
+                .get_partial_invalid_cause(),
+            Some(PartialInvalidCause::IncorrectParent)
+        );
+    }
+    remove_test_db(db_dir);
+}
+
+#[test]
+fn test_block_body_availability_by_height() {
+    let db_dir = "./test_block_body_availability_by_height.db/";
+    {
+        let (data_man, genesis) = initialize_data_manager(
+            db_dir,
+            DbType::Rocksdb,
+            Arc::new(PowComputer::new(true)),
+            VmFactory::new(1024 * 32),
+        );
+        // All the heights are tracked in a new database.
+        assert_eq!(
+            data_man.db_manager.block_body_availability_start_from_db(),
+            Some(0)
+        );
+        assert_eq!(
+            data_man.block_body_availability(&genesis.hash(), 0),
+            Some(true)
+        );
+
+        let fork = |nonce: u64| {
+            create_simple_block_impl(
+                genesis.hash(),
+                vec![],
+                1,
+                U256::from(nonce),
+                U256::from(10),
+                1,
+                false,
+            )
+            .1
+        };
+        let (a, b) = (Arc::new(fork(1)), Arc::new(fork(2)));
+        assert_eq!(data_man.block_body_availability(&a.hash(), 1), Some(false));
+        data_man.insert_block(a.clone(), true /* persistent */);
+        data_man.insert_block(b.clone(), true /* persistent */);
+        assert_eq!(data_man.block_body_availability(&a.hash(), 1), Some(true));
+        assert_eq!(data_man.block_body_availability(&b.hash(), 1), Some(true));
+
+        // Only the record of the height is rewritten, and it is removed once
+        // it becomes empty.
+        data_man.remove_block_body(&a.hash(), true /* remove_db */);
+        assert_eq!(data_man.block_body_availability(&a.hash(), 1), Some(false));
+        assert_eq!(
+            data_man.db_manager.block_body_availability_from_db(1),
+            Some(vec![b.hash()])
+        );
+        data_man.remove_block_body(&b.hash(), true /* remove_db */);
+        assert_eq!(
+            data_man.db_manager.block_body_availability_from_db(1),
+            None
+        );
+        assert_eq!(
+            data_man.db_manager.block_body_availability_from_db(0),
+            Some(vec![genesis.hash()])
+        );
+    }
+    remove_test_db(db_dir);
+}
+
+#[test]
+fn test_evict_and_reload_epoch_sets() {
+    let db_dir = "./test_evict_and_reload_epoch_sets.db/";
+    {
+        let (sync, consensus, data_man, genesis) =
+            initialize_synchronization_graph(
+                db_dir,
+                1000,
+                1000,
+                1000,
+                1000,
+                50000,
+                DbType::Rocksdb,
+            );
+        consensus.construct_pivot_state();
+
+        // The epoch of `merge` contains `fork` and `merge`.
+        let chain = create_chain(genesis.hash(), 0, 2, 1);
+        let (_, fork) = create_simple_block_impl(
+            genesis.hash(),
+            vec![],
+            1,
+            U256::from(100),
+            U256::from(10),
+            1,
+            false,
+        );
+        let (merge_hash, merge) = create_simple_block_impl(
+            chain[1].hash(),
+            vec![fork.hash()],
+            3,
+            U256::from(101),
+            U256::from(10),
+            1,
+            false,
+        );
+        let mut blocks = chain.clone();
+        blocks.push(fork);
+        blocks.push(merge);
+        blocks.extend(create_chain(merge_hash, 3, 5, 200));
+        insert_blocks(&sync, &consensus, &blocks);
+        assert_eq!(consensus.best_epoch_number(), 8);
+
+        let mut inner = consensus.inner.write();
+        inner.inner_conf.epoch_set_eviction_depth = Some(2);
+        let merge_index = inner.hash_to_arena_indices[&merge_hash];
+        let epoch_blocks = inner
+            .get_ordered_executable_epoch_blocks(merge_index)
+            .unwrap()
+            .into_owned();
+        assert_eq!(epoch_blocks.len(), 2);
+
+        // The evicted sets are read from the database without being
+        // reloaded.
+        inner.evict_cold_epoch_sets().unwrap();
+        assert!(data_man.evicted_epoch_sets(&merge_hash).is_some());
+        assert_eq!(
+            *inner
+                .get_ordered_executable_epoch_blocks(merge_index)
+                .unwrap(),
+            epoch_blocks
+        );
+        assert!(data_man.evicted_epoch_sets(&merge_hash).is_some());
+
+        // The reloaded sets are removed from the database.
+        assert_eq!(
+            *inner.ordered_executable_epoch_blocks(merge_index),
+            epoch_blocks
+        );
+        assert!(data_man.evicted_epoch_sets(&merge_hash).is_none());
+
+        // The sets missing in the database are an error for the read, and
+        // are computed again when they are reloaded.
+        inner.evict_cold_epoch_sets().unwrap();
+        assert!(data_man.evicted_epoch_sets(&merge_hash).is_some());
+        data_man.remove_evicted_epoch_sets(&merge_hash);
+        assert!(inner
+            .get_ordered_executable_epoch_blocks(merge_index)
+            .is_err());
+        assert_eq!(
+            *inner.ordered_executable_epoch_blocks(merge_index),
+            epoch_blocks
+        );
+    }
+    remove_test_db(db_dir);
+}
+
+#[test]
+fn test_epoch_order_matches_sync_order() {
+    let db_dir = "./test_epoch_order_matches_sync_order.db/";
+    {
+        let (sync, consensus, _, genesis) = initialize_synchronization_graph(
+            db_dir,
+            1000,
+            1000,
+            1000,
+            1000,
+            50000,
+            DbType::Rocksdb,
+        );
+        consensus.construct_pivot_state();
+
+        // The epoch of `merge` contains two forks and a block referring
+        // across them, so most of its blocks are only ordered by hashes.
+        let chain = create_chain(genesis.hash(), 0, 3, 1);
+        let fork_a = create_chain(genesis.hash(), 0, 2, 100);
+        let fork_b = create_chain(genesis.hash(), 0, 2, 200);
+        let (_, cross) = create_simple_block_impl(
+            fork_b[0].hash(),
+            vec![fork_a[0].hash()],
+            2,
+            U256::from(300),
+            U256::from(10),
+            1,
+            false,
+        );
+        let (merge_hash, merge) = create_simple_block_impl(
+            chain[2].hash(),
+            vec![fork_a[1].hash(), fork_b[1].hash(), cross.hash()],
+            4,
+            U256::from(301),
+            U256::from(10),
+            1,
+            false,
+        );
+        // The forks are inserted before their parents, so they get other
+        // indices in the sync graph arena than in the consensus graph arena.
+        let mut blocks = chain.clone();
+        blocks.push(cross);
+        blocks.push(fork_b[1].clone());
+        blocks.push(fork_a[1].clone());
+        blocks.push(fork_a[0].clone());
+        blocks.push(fork_b[0].clone());
+        blocks.push(merge);
+        insert_blocks(&sync, &consensus, &blocks);
+
+        // The epoch order computed by consensus in its own arena.
+        let by_consensus: Vec<H256> = {
+            let inner = consensus.inner.read();
+            let merge_index = inner.hash_to_arena_indices[&merge_hash];
+            inner
+                .get_ordered_executable_epoch_blocks(merge_index)
+                .unwrap()
+                .iter()
+                .map(|i| inner.arena[*i].hash)
+                .collect()
+        };
+        assert_eq!(by_consensus.len(), 6);
+        assert_eq!(by_consensus.last(), Some(&merge_hash));
+
+        // The order in which the sync graph sends the same blocks to
+        // consensus, which only matches if both graphs break the ties by
+        // hashes rather than by arena indices.
+        let by_sync: Vec<H256> = {
+            let inner = sync.inner.read();
+            let index_set: HashSet<usize> = by_consensus[..5]
+                .iter()
+                .map(|hash| inner.hash_to_arena_indices[hash])
+                .collect();
+            inner
+                .topological_sort_with_order_indicator(index_set, |i| {
+                    inner.arena[i].block_header.hash()
+                })
+                .into_iter()
+                .map(|i| inner.arena[i].block_header.hash())
+                .chain(once(merge_hash))
+                .collect()
+        };
+        assert_eq!(by_sync, by_consensus);
+    }
+    remove_test_db(db_dir);
+}
+
+#[test]
+fn test_terminals_delta_log_replay() {
+    let db_dir = "./test_terminals_delta_log_replay.db/";
+    {
+        let (data_man, _) = initialize_data_manager(
+            db_dir,
+            DbType::Rocksdb,
+            Arc::new(PowComputer::new(true)),
+            VmFactory::new(1024 * 32),
+        );
+        let hashes: Vec<H256> = (0..6).map(H256::from_low_u64_be).collect();
+        let terminals_from_db = || {
+            let mut terminals = data_man.terminals_from_db().unwrap();
+            terminals.sort();
+            terminals
+        };
+
+        // The deltas are applied on the full list of older versions.
+        data_man
+            .db_manager
+            .insert_terminals_to_db(&hashes[..2].to_vec());
+        data_man.insert_terminals_to_db(vec![hashes[1], hashes[2]]);
+        data_man.insert_terminals_to_db(vec![hashes[2], hashes[3], hashes[4]]);
+        assert_eq!(
+            data_man.db_manager.terminals_delta_count_from_db(),
+            Some(2)
+        );
+        assert_eq!(terminals_from_db(), hashes[2..5].to_vec());
+
+        // A crash after a delta is written but before the count is updated.
+        // The delta is not replayed, and is overwritten by the next one.
+        data_man.db_manager.insert_terminals_delta_to_db(
+            2,
+            &TerminalsDelta {
+                added: vec![hashes[5]],
+                removed: vec![hashes[2]],
+            },
+        );
+        assert_eq!(terminals_from_db(), hashes[2..5].to_vec());
+
+        // A crash after the compacted list is written but before the count is
+        // reset. The deltas replayed on the compacted list give the same set.
+        data_man
+            .db_manager
+            .insert_terminals_to_db(&hashes[2..5].to_vec());
+        assert_eq!(terminals_from_db(), hashes[2..5].to_vec());
+    }
+    remove_test_db(db_dir);
+}
+
+#[test]
+fn test_epoch_system_transaction_receipts_round_trip() {
+    let db_dir = "./test_epoch_system_transaction_receipts_round_trip.db/";
+    {
+        let (data_man, _) = initialize_data_manager(
+            db_dir,
+            DbType::Rocksdb,
+            Arc::new(PowComputer::new(true)),
+            VmFactory::new(1024 * 32),
+        );
+        let epoch_hash = H256::from_low_u64_be(1);
+        assert!(data_man
+            .epoch_system_transaction_receipts_by_hash(&epoch_hash)
+            .is_none());
+
+        let receipt =
+            |index: u64, error_message: &str| SystemTransactionReceipt {
+                transaction_hash: H256::from_low_u64_be(index),
+                receipt: Receipt::new(
+                    TRANSACTION_OUTCOME_SUCCESS,
+                    U256::from(index),
+                    U256::zero(),
+                    false,
+                    vec![],
+                    false,
+                    vec![],
+                    vec![],
+                ),
+                error_message: error_message.into(),
+            };
+        data_man.insert_epoch_system_transaction_receipts(
+            &epoch_hash,
+            &EpochSystemTransactionReceipts {
+                before_user_transactions: vec![receipt(2, "")],
+                after_user_transactions: vec![
+                    receipt(3, ""),
+                    receipt(4, "out of gas"),
+                ],
+            },
+        );
+
+        let loaded = data_man
+            .epoch_system_transaction_receipts_by_hash(&epoch_hash)
+            .unwrap();
+        assert_eq!(loaded.before_user_transactions.len(), 1);
+        assert_eq!(
+            loaded.before_user_transactions[0].transaction_hash,
+            H256::from_low_u64_be(2)
+        );
+        assert_eq!(loaded.after_user_transactions.len(), 2);
+        assert_eq!(
+            loaded.after_user_transactions[1].receipt,
+            receipt(4, "").receipt
+        );
+        assert_eq!(
+            loaded.after_user_transactions[1].error_message,
+            "out of gas"
+        );
+    }
+    remove_test_db(db_dir);
+}
@@ -1,8 +1,13 @@
 use crate::{
     block_data_manager::{BlockDataManager, DataManagerConfiguration, DbType},
     cache_config::CacheConfig,
+    clock::{system_clock, SharedClock},
+    config_cell::ConfigCell,
     consensus::{
-        consensus_inner::consensus_executor::ConsensusExecutionConfiguration,
+        consensus_inner::{
+            consensus_executor::ConsensusExecutionConfiguration,
+            risk_model::RiskModelConfig,
+        },
         ConsensusConfig, ConsensusInnerConfig,
     },
     db::NUM_COLUMNS,
@@ -95,6 +100,22 @@ pub fn create_simple_block(
 pub fn initialize_data_manager(
     db_dir: &str, dbtype: DbType, pow: Arc<PowComputer>, vm: VmFactory,
 ) -> (Arc<BlockDataManager>, Arc<Block>) {
+    initialize_data_manager_with_genesis_accounts(
+        db_dir,
+        dbtype,
+        pow,
+        vm,
+        HashMap::new(),
+    )
+}
+
+/// Initialize the data manager with the balances of `genesis_accounts` in
+/// the genesis state.
+pub fn initialize_data_manager_with_genesis_accounts(
+    db_dir: &str, dbtype: DbType, pow: Arc<PowComputer>, vm: VmFactory,
+    mut genesis_accounts: HashMap<Address, U256>,
+) -> (Arc<BlockDataManager>, Arc<Block>)
+{
     let ledger_db = db::open_database(
         db_dir,
         &db::db_config(
@@ -121,7 +142,6 @@ pub fn initialize_data_manager(
         .expect("Failed to initialize storage."),
     );
 
-    let mut genesis_accounts = HashMap::new();
     genesis_accounts.insert(
         Address::from_str("1000000000000000000000000000000000000008").unwrap(),
         U256::from(0),
@@ -161,6 +181,26 @@ pub fn initialize_synchronization_graph_with_data_manager(
     data_man: Arc<BlockDataManager>, beta: u64, h: u64, tcr: u64, tcb: u64,
     era_epoch_count: u64, pow: Arc<PowComputer>, vm: VmFactory,
 ) -> (Arc<SynchronizationGraph>, Arc<ConsensusGraph>)
+{
+    initialize_synchronization_graph_impl(
+        data_man,
+        beta,
+        h,
+        tcr,
+        tcb,
+        era_epoch_count,
+        pow,
+        vm,
+        false, /* header_graph_mode */
+        system_clock(),
+    )
+}
+
+fn initialize_synchronization_graph_impl(
+    data_man: Arc<BlockDataManager>, beta: u64, h: u64, tcr: u64, tcb: u64,
+    era_epoch_count: u64, pow: Arc<PowComputer>, vm: VmFactory,
+    header_graph_mode: bool, clock: SharedClock,
+) -> (Arc<SynchronizationGraph>, Arc<ConsensusGraph>)
 {
     let machine = Arc::new(new_machine_with_builtin(Default::default(), vm));
     let verification_config = VerificationConfig::new(
@@ -193,6 +233,11 @@ pub fn initialize_synchronization_graph_with_data_manager(
         future_block_buffer_capacity: 1,
         enable_state_expose: false,
         is_consortium: false,
+        block_propagation_history_size: 0,
+        memory_budget_bytes: None,
+        max_consensus_queue_len: None,
+        header_graph_mode,
+        max_orphan_headers: None,
     };
     let notifications = Notifications::init();
     let consensus = Arc::new(ConsensusGraph::new(
@@ -205,18 +250,29 @@ pub fn initialize_synchronization_graph_with_data_manager(
                 timer_chain_beta: tcb,
                 era_epoch_count,
                 enable_optimistic_execution: false,
+                optimistic_execution_min_depth: 0,
+                optimistic_execution_max_depth: 0,
                 enable_state_expose: false,
                 debug_dump_dir_invalid_state_root: None,
                 debug_invalid_state_root_epoch: None,
+                debug_weight_audit_interval: None,
+                debug_dump_dir_weight_audit: None,
+                epoch_set_eviction_depth: None,
             },
-            bench_mode: true, /* Set bench_mode to true so that we skip
+            bench_mode: true, /* Set bench_mode to true so that we mock
                                * execution */
             transaction_epoch_bound: TRANSACTION_DEFAULT_EPOCH_BOUND,
             referee_bound: REFEREE_DEFAULT_BOUND,
-            get_logs_epoch_batch_size: 32,
-            get_logs_filter_max_epoch_range: None,
+            get_logs_epoch_batch_size: ConfigCell::new(32),
+            get_logs_filter_max_epoch_range: ConfigCell::new(None),
             sync_state_starting_epoch: None,
             sync_state_epoch_gap: None,
+            finalized_epoch_outbox_risk_threshold: None,
+            confirmation_risk_model: RiskModelConfig::Heuristic,
+            weight_injection_detector: None,
+            epoch_export: None,
+            header_graph_mode,
+            account_read_cache_size: 0,
         },
         txpool.clone(),
         statistics.clone(),
@@ -226,12 +282,21 @@ pub fn initialize_synchronization_graph_with_data_manager(
         notifications.clone(),
         ConsensusExecutionConfiguration {
             executive_trace: false,
+            execution_worker_count: 1,
+            head_execution_weight: 1,
+            replay_execution_weight: 1,
+            enable_dependency_prefetch: true,
+            execution_verification_concurrency: 1,
+            enable_block_log_index: false,
+            epoch_execution_checkpoint_interval: None,
+            debug_dump_dir_shadow_execution: None,
         },
         verification_config.clone(),
         NodeType::Archive,
+        clock.clone(),
     ));
 
-    let sync = Arc::new(SynchronizationGraph::new(
+    let sync = Arc::new(SynchronizationGraph::with_clock(
         consensus.clone(),
         verification_config,
         pow_config,
@@ -239,6 +304,7 @@ pub fn initialize_synchronization_graph_with_data_manager(
         sync_config,
         notifications,
         machine,
+        clock,
     ));
 
     (sync, consensus)
@@ -254,6 +320,31 @@ pub fn initialize_synchronization_graph(
     Arc<BlockDataManager>,
     Arc<Block>,
 )
+{
+    initialize_synchronization_graph_with_clock(
+        db_dir,
+        beta,
+        h,
+        tcr,
+        tcb,
+        era_epoch_count,
+        dbtype,
+        system_clock(),
+    )
+}
+
+/// Same as `initialize_synchronization_graph`, except that the sync graph and
+/// the consensus graph read the time from `clock`, e.g. a `ManualClock`.
+/// This method is only used in tests.
+pub fn initialize_synchronization_graph_with_clock(
+    db_dir: &str, beta: u64, h: u64, tcr: u64, tcb: u64, era_epoch_count: u64,
+    dbtype: DbType, clock: SharedClock,
+) -> (
+    Arc<SynchronizationGraph>,
+    Arc<ConsensusGraph>,
+    Arc<BlockDataManager>,
+    Arc<Block>,
+)
 {
     let vm = VmFactory::new(1024 * 32);
     let pow = Arc::new(PowComputer::new(true));
@@ -261,7 +352,45 @@ pub fn initialize_synchronization_graph(
     let (data_man, genesis_block) =
         initialize_data_manager(db_dir, dbtype, pow.clone(), vm.clone());
 
-    let (sync, consensus) = initialize_synchronization_graph_with_data_manager(
+    let (sync, consensus) = initialize_synchronization_graph_impl(
+        data_man.clone(),
+        beta,
+        h,
+        tcr,
+        tcb,
+        era_epoch_count,
+        pow,
+        vm,
+        false, /* header_graph_mode */
+        clock,
+    );
+
+    (sync, consensus, data_man, genesis_block)
+}
+
+/// Same as `initialize_synchronization_graph`, except that the blocks are
+/// inserted into consensus with only their headers in the header graph mode.
+/// This method is only used in tests.
+pub fn initialize_header_graph_mode_synchronization_graph(
+    db_dir: &str, beta: u64, h: u64, tcr: u64, tcb: u64, era_epoch_count: u64,
+) -> (
+    Arc<SynchronizationGraph>,
+    Arc<ConsensusGraph>,
+    Arc<BlockDataManager>,
+    Arc<Block>,
+)
+{
+    let vm = VmFactory::new(1024 * 32);
+    let pow = Arc::new(PowComputer::new(true));
+
+    let (data_man, genesis_block) = initialize_data_manager(
+        db_dir,
+        DbType::Rocksdb,
+        pow.clone(),
+        vm.clone(),
+    );
+
+    let (sync, consensus) = initialize_synchronization_graph_impl(
         data_man.clone(),
         beta,
         h,
@@ -270,6 +399,8 @@ pub fn initialize_synchronization_graph(
         era_epoch_count,
         pow,
         vm,
+        true, /* header_graph_mode */
+        system_clock(),
     );
 
     (sync, consensus, data_man, genesis_block)
@@ -0,0 +1,142 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use super::SharedTransactionPool;
+use crate::{block_data_manager::LocalNonceRecord, clock::SharedClock};
+use cfx_statedb::Result as StateDbResult;
+use cfx_types::{Address, U256};
+use parking_lot::Mutex;
+
+/// The nonces allocated within this number of seconds are assumed to belong
+/// to transactions that have not reached the pool yet, so they are not
+/// allocated again even if the pool does not contain them.
+const LOCAL_NONCE_REALLOCATION_TIMEOUT_SECS: u64 = 60;
+
+/// Allocates the nonces of the transactions sent automatically by the local
+/// node account.
+///
+/// Each allocation is reconciled with the nonce in the latest state and the
+/// transactions of the account in the pool. After a pivot chain switch the
+/// state nonce may go back, and the transactions that were packed only on
+/// the old pivot chain may have been dropped from the pool. The nonces of
+/// these lost transactions are allocated again once no allocation has
+/// happened for `LOCAL_NONCE_REALLOCATION_TIMEOUT_SECS`, so that the account
+/// is not stuck behind a nonce gap.
+pub struct LocalNonceTracker {
+    address: Address,
+    txpool: SharedTransactionPool,
+    clock: SharedClock,
+    record: Mutex<LocalNonceRecord>,
+}
+
+impl LocalNonceTracker {
+    pub fn new(
+        address: Address, txpool: SharedTransactionPool, clock: SharedClock,
+    ) -> Self {
+        let record = txpool
+            .data_man
+            .local_nonce_record(&address)
+            .unwrap_or_default();
+        Self {
+            address,
+            txpool,
+            clock,
+            record: Mutex::new(record),
+        }
+    }
+
+    pub fn address(&self) -> &Address { &self.address }
+
+    /// Return the nonce that the next allocation will return.
+    pub fn next_nonce(&self) -> StateDbResult<U256> {
+        let record = self.record.lock();
+        self.reconciled_next_nonce(&record)
+    }
+
+    /// Allocate a nonce for a new transaction of the local account. The
+    /// transaction is expected to be inserted into the pool shortly.
+    pub fn allocate_next_nonce(&self) -> StateDbResult<U256> {
+        let mut record = self.record.lock();
+        let nonce = self.reconciled_next_nonce(&record)?;
+        if nonce < record.next_nonce {
+            warn!(
+                "Reallocate the nonces of local account {:?} from {}, \
+                 the transactions up to nonce {} are not found",
+                self.address, nonce, record.next_nonce
+            );
+        }
+        *record = LocalNonceRecord {
+            next_nonce: nonce + U256::from(1),
+            last_allocation_time: self.clock.now_as_secs(),
+        };
+        self.txpool
+            .data_man
+            .insert_local_nonce_record(&self.address, &record);
+        Ok(nonce)
+    }
+
+    fn reconciled_next_nonce(
+        &self, record: &LocalNonceRecord,
+    ) -> StateDbResult<U256> {
+        let (state_nonce, _balance) =
+            self.txpool.get_state_account_info(&self.address)?;
+        let pool_nonce = self
+            .txpool
+            .get_first_missing_nonce(&self.address, &state_nonce);
+        Ok(reconcile_next_nonce(
+            record,
+            pool_nonce,
+            self.clock.now_as_secs(),
+        ))
+    }
+}
+
+/// `pool_nonce` is the first nonce not used by the latest state or the pool.
+fn reconcile_next_nonce(
+    record: &LocalNonceRecord, pool_nonce: U256, now_secs: u64,
+) -> U256 {
+    let allocation_expired = now_secs
+        >= record.last_allocation_time + LOCAL_NONCE_REALLOCATION_TIMEOUT_SECS;
+    if record.next_nonce <= pool_nonce || allocation_expired {
+        pool_nonce
+    } else {
+        record.next_nonce
+    }
+}
+
+#[cfg(test)]
+mod local_nonce_tracker_test {
+    use super::{
+        reconcile_next_nonce, LocalNonceRecord,
+        LOCAL_NONCE_REALLOCATION_TIMEOUT_SECS,
+    };
+    use cfx_types::U256;
+
+    #[test]
+    fn test_reconcile_next_nonce() {
+        let record = LocalNonceRecord {
+            next_nonce: 10.into(),
+            last_allocation_time: 1000,
+        };
+        // Transactions sent by others or already in the pool.
+        assert_eq!(
+            reconcile_next_nonce(&record, 12.into(), 1001),
+            U256::from(12)
+        );
+        // Recent allocations are kept.
+        assert_eq!(
+            reconcile_next_nonce(&record, 7.into(), 1001),
+            U256::from(10)
+        );
+        // The transactions of old allocations are lost.
+        assert_eq!(
+            reconcile_next_nonce(
+                &record,
+                7.into(),
+                1000 + LOCAL_NONCE_REALLOCATION_TIMEOUT_SECS
+            ),
+            U256::from(7)
+        );
+    }
+}
@@ -9,12 +9,20 @@ mod test_treap;
 
 mod account_cache;
 mod garbage_collector;
+mod local_nonce_tracker;
 mod nonce_pool;
+mod pool_snapshot;
+mod propagation;
+mod provisional_packing;
 mod transaction_pool_inner;
+mod tx_expiry;
 
 extern crate rand;
 
-pub use self::{impls::TreapMap, transaction_pool_inner::TransactionStatus};
+pub use self::{
+    impls::TreapMap, local_nonce_tracker::LocalNonceTracker,
+    pool_snapshot::TxPoolSnapshot, transaction_pool_inner::TransactionStatus,
+};
 use crate::{
     block_data_manager::BlockDataManager, consensus::BestInformation,
     machine::Machine, state::State, verification::VerificationConfig,
@@ -37,10 +45,11 @@ use metrics::{
 };
 use parking_lot::{Mutex, RwLock};
 use primitives::{Account, SignedTransaction, TransactionWithSignature};
+use propagation::ToPropagateTransactions;
+use provisional_packing::ProvisionalPacking;
 use std::{
     cmp::{max, min},
-    collections::hash_map::HashMap,
-    mem,
+    collections::{hash_map::HashMap, HashSet},
     ops::DerefMut,
     sync::{
         atomic::{AtomicBool, Ordering},
@@ -85,6 +94,12 @@ lazy_static! {
 pub struct TxPoolConfig {
     pub capacity: usize,
     pub min_tx_price: u64,
+    /// The transactions with at least this gas price are packed round-robin
+    /// across their senders.
+    pub packing_fairness_min_gas_price: Option<u64>,
+    /// A transaction not packed within this many epochs after it enters the
+    /// pool is removed from the pool.
+    pub expiry_epochs: Option<u64>,
     pub max_tx_gas: RwLock<U256>,
     pub tx_weight_scaling: u64,
     pub tx_weight_exp: u8,
@@ -100,6 +115,8 @@ impl Default for TxPoolConfig {
         TxPoolConfig {
             capacity: 500_000,
             min_tx_price: 1,
+            packing_fairness_min_gas_price: None,
+            expiry_epochs: None,
             max_tx_gas: RwLock::new(U256::from(
                 DEFAULT_TARGET_BLOCK_GAS_LIMIT / 2,
             )),
@@ -112,11 +129,37 @@ impl Default for TxPoolConfig {
     }
 }
 
+/// The result of inserting a transaction with `insert_transactions`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TxInsertionResult {
+    /// The transaction is added to the pool.
+    Added,
+    /// The transaction is added to the pool and replaces the transaction of
+    /// the same sender and nonce with the given hash.
+    Replaced(H256),
+    /// The transaction is not added to the pool.
+    Rejected { reason: String },
+}
+
+impl TxInsertionResult {
+    fn rejected(reason: String) -> Self {
+        TxInsertionResult::Rejected { reason }
+    }
+
+    pub fn is_inserted(&self) -> bool {
+        !matches!(self, TxInsertionResult::Rejected { .. })
+    }
+}
+
 pub struct TransactionPool {
     config: TxPoolConfig,
     verification_config: VerificationConfig,
     inner: RwLock<TransactionPoolInner>,
-    to_propagate_trans: Arc<RwLock<HashMap<H256, Arc<SignedTransaction>>>>,
+    /// The latest snapshot of the pool content, taken on demand and shared
+    /// until the content changes.
+    content_snapshot: Mutex<Arc<TxPoolSnapshot>>,
+    to_propagate_trans: Arc<RwLock<ToPropagateTransactions>>,
+    provisional_packing: Mutex<ProvisionalPacking>,
     pub data_man: Arc<BlockDataManager>,
     best_executed_state: Mutex<Arc<State>>,
     consensus_best_info: Mutex<Arc<BestInformation>>,
@@ -134,6 +177,8 @@ impl MallocSizeOf for TransactionPool {
         let inner_size = self.inner.read().size_of(ops);
         let to_propagate_trans_size =
             self.to_propagate_trans.read().size_of(ops);
+        let provisional_packing_size =
+            self.provisional_packing.lock().size_of(ops);
         let consensus_best_info_size =
             self.consensus_best_info.lock().size_of(ops);
         let set_tx_requests_size = self.set_tx_requests.lock().size_of(ops);
@@ -142,11 +187,14 @@ impl MallocSizeOf for TransactionPool {
         self.config.size_of(ops)
             + inner_size
             + to_propagate_trans_size
+            + provisional_packing_size
             + self.data_man.size_of(ops)
             + consensus_best_info_size
             + set_tx_requests_size
             + recycle_tx_requests_size
         // Does not count size_of machine
+        // Does not count content_snapshot, whose transactions are shared with
+        // the pool
     }
 }
 
@@ -178,7 +226,9 @@ impl TransactionPool {
             config,
             verification_config,
             inner: RwLock::new(inner),
-            to_propagate_trans: Arc::new(RwLock::new(HashMap::new())),
+            content_snapshot: Default::default(),
+            to_propagate_trans: Arc::new(RwLock::new(Default::default())),
+            provisional_packing: Default::default(),
             data_man: data_man.clone(),
             best_executed_state,
             consensus_best_info: Mutex::new(Arc::new(Default::default())),
@@ -201,6 +251,37 @@ impl TransactionPool {
         self.inner.read().check_tx_packed_in_deferred_pool(tx_hash)
     }
 
+    /// Mark the transactions included in a block template of the local
+    /// miner. They are not propagated until the template is released.
+    pub fn mark_provisionally_packed(
+        &self, template_id: H256, transactions: &Vec<Arc<SignedTransaction>>,
+    ) {
+        self.provisional_packing
+            .lock()
+            .mark(template_id, transactions.iter().map(|tx| tx.hash).collect());
+    }
+
+    /// Remove the marks of a block template after it is mined or abandoned.
+    /// The transactions of an abandoned template are propagated again if no
+    /// other template includes them.
+    pub fn release_provisionally_packed(
+        &self, template_id: &H256, mined: bool,
+    ) {
+        let transactions =
+            self.provisional_packing.lock().release(template_id, mined);
+        if !transactions.is_empty() {
+            self.to_propagate_trans.write().restore(transactions);
+        }
+    }
+
+    /// Return the latest block template of the local miner that includes the
+    /// transaction.
+    pub fn provisionally_packed_template(
+        &self, tx_hash: &H256,
+    ) -> Option<H256> {
+        self.provisional_packing.lock().template_of(tx_hash)
+    }
+
     pub fn get_local_account_info(&self, address: &Address) -> (U256, U256) {
         self.inner
             .read()
@@ -214,6 +295,16 @@ impl TransactionPool {
         self.inner.read().get_account_pending_info(address)
     }
 
+    /// Return the first nonce from `start_nonce` without a transaction of
+    /// `address` in the pool.
+    pub fn get_first_missing_nonce(
+        &self, address: &Address, start_nonce: &U256,
+    ) -> U256 {
+        self.inner
+            .read()
+            .get_first_missing_nonce(address, start_nonce)
+    }
+
     /// Return `(pending_txs, first_tx_status, pending_count)`.
     pub fn get_account_pending_transactions(
         &self, address: &Address, maybe_start_nonce: Option<U256>,
@@ -224,11 +315,23 @@ impl TransactionPool {
         usize,
     )
     {
-        self.inner.read().get_account_pending_transactions(
-            address,
-            maybe_start_nonce,
-            maybe_limit,
-        )
+        let (pending_txs, first_tx_status, pending_count) =
+            self.inner.read().get_account_pending_transactions(
+                address,
+                maybe_start_nonce,
+                maybe_limit,
+            );
+        let first_tx_status = match (first_tx_status, pending_txs.first()) {
+            (Some(TransactionStatus::Ready), Some(first_tx))
+                if self
+                    .provisionally_packed_template(&first_tx.hash)
+                    .is_some() =>
+            {
+                Some(TransactionStatus::ProvisionallyPacked)
+            }
+            (first_tx_status, _) => first_tx_status,
+        };
+        (pending_txs, first_tx_status, pending_count)
     }
 
     pub fn get_state_account_info(
@@ -345,9 +448,7 @@ impl TransactionPool {
                         continue;
                     }
                     passed_transactions.push(tx.clone());
-                    if !to_prop.contains_key(&tx.hash) {
-                        to_prop.insert(tx.hash, tx);
-                    }
+                    to_prop.insert(tx);
                 }
             }
             Err(e) => {
@@ -367,6 +468,126 @@ impl TransactionPool {
         (passed_transactions, failure)
     }
 
+    /// Insert `transactions` into transaction pool and return the result of
+    /// each transaction in the input order, together with the inserted
+    /// transactions that should be propagated.
+    ///
+    /// Unlike `insert_new_transactions`, the transactions that are already in
+    /// the pool are reported as rejected instead of being ignored, and a
+    /// transaction whose public key cannot be recovered does not fail the
+    /// others.
+    pub fn insert_transactions(
+        &self, transactions: Vec<TransactionWithSignature>,
+    ) -> (Vec<Arc<SignedTransaction>>, Vec<TxInsertionResult>) {
+        INSERT_TPS.mark(1);
+        INSERT_TXS_TPS.mark(transactions.len());
+        let _timer = MeterTimer::time_func(TX_POOL_INSERT_TIMER.as_ref());
+
+        let mut results: Vec<Option<TxInsertionResult>> =
+            vec![None; transactions.len()];
+        let current_best_info = self.consensus_best_info.lock().clone();
+        let chain_id = current_best_info.best_chain_id();
+        let best_height = current_best_info.best_epoch_number;
+        let vm_spec = self.machine.spec(current_best_info.best_block_number);
+        let transitions = &self.machine.params().transition_heights;
+
+        // The indices and transactions that pass the basic verification.
+        let mut to_recover_indices = Vec::new();
+        let mut to_recover = Vec::new();
+        let mut seen = HashSet::new();
+        let quota = self
+            .inner
+            .write_with_metric(&INSERT_TXS_QUOTA_LOCK)
+            .remaining_quota();
+        for (index, tx) in transactions.into_iter().enumerate() {
+            let verify_result = if !seen.insert(tx.hash()) {
+                Err("duplicate transaction in the batch".into())
+            } else if self.inner.read().get(&tx.hash()).is_some() {
+                Err("tx already exist".into())
+            } else {
+                self.verify_transaction_tx_pool(
+                    &tx,
+                    /* basic_check = */ true,
+                    chain_id,
+                    best_height,
+                    transitions,
+                    &vm_spec,
+                )
+            };
+            match verify_result {
+                Err(e) => results[index] = Some(TxInsertionResult::rejected(e)),
+                Ok(()) if to_recover.len() >= quota => {
+                    results[index] = Some(TxInsertionResult::rejected(
+                        "txpool is full".into(),
+                    ))
+                }
+                Ok(()) => {
+                    to_recover_indices.push(index);
+                    to_recover.push(tx);
+                }
+            }
+        }
+
+        let mut passed_transactions = Vec::new();
+        if !to_recover.is_empty() {
+            let recovered = self.data_man.recover_unsigned_tx_each(&to_recover);
+            let account_cache = self.get_best_state_account_cache();
+            let mut inner =
+                self.inner.write_with_metric(&INSERT_TXS_ENQUEUE_LOCK);
+            let mut to_prop = self.to_propagate_trans.write();
+            for (index, recover_result) in
+                to_recover_indices.into_iter().zip(recovered)
+            {
+                let tx = match recover_result {
+                    Ok(tx) => tx,
+                    Err(e) => {
+                        results[index] = Some(TxInsertionResult::rejected(
+                            format!("{:?}", e),
+                        ));
+                        continue;
+                    }
+                };
+                results[index] = Some(
+                    match self.add_transaction_with_readiness_check(
+                        &mut *inner,
+                        &account_cache,
+                        tx.clone(),
+                        false,
+                        false,
+                    ) {
+                        Ok(replaced) => {
+                            passed_transactions.push(tx.clone());
+                            to_prop.insert(tx);
+                            match replaced {
+                                Some(hash) => TxInsertionResult::Replaced(hash),
+                                None => TxInsertionResult::Added,
+                            }
+                        }
+                        Err(e) => {
+                            debug!(
+                                "tx {:?} fails to be inserted to pool, err={:?}",
+                                &tx.hash, e
+                            );
+                            TxInsertionResult::rejected(e)
+                        }
+                    },
+                );
+            }
+        }
+
+        TX_POOL_DEFERRED_GAUGE.update(self.total_deferred());
+        TX_POOL_UNPACKED_GAUGE.update(self.total_unpacked());
+        TX_POOL_READY_GAUGE.update(self.total_ready_accounts());
+
+        let results: Vec<TxInsertionResult> = results
+            .into_iter()
+            .map(|result| result.expect("every transaction has a result"))
+            .collect();
+        INSERT_TXS_SUCCESS_TPS.mark(passed_transactions.len());
+        INSERT_TXS_FAILURE_TPS.mark(results.len() - passed_transactions.len());
+        (passed_transactions, results)
+    }
+
     /// Try to insert `signed_transaction` into transaction pool.
     ///
     /// If some tx is already in our tx_cache, it will be ignored and will not
@@ -461,9 +682,7 @@ impl TransactionPool {
                     continue;
                 }
                 passed_transactions.push(tx.clone());
-                if !to_prop.contains_key(&tx.hash) {
-                    to_prop.insert(tx.hash, tx);
-                }
+                to_prop.insert(tx);
             }
             //RwLock is dropped here
         }
@@ -490,6 +709,13 @@ impl TransactionPool {
         let mode = VerifyTxMode::Local(VerifyTxLocalMode::MaybeLater, spec);
 
         if basic_check {
+            if transaction.rlp_size() > spec.max_transaction_size {
+                return Err(format!(
+                    "transaction size {} exceeds the maximum value {}",
+                    transaction.rlp_size(),
+                    spec.max_transaction_size
+                ));
+            }
             if let Err(e) = self.verification_config.verify_transaction_common(
                 transaction,
                 chain_id,
@@ -538,30 +764,44 @@ impl TransactionPool {
     pub fn add_transaction_with_readiness_check(
         &self, inner: &mut TransactionPoolInner, account_cache: &AccountCache,
         transaction: Arc<SignedTransaction>, packed: bool, force: bool,
-    ) -> Result<(), String>
+    ) -> Result<Option<H256>, String>
     {
-        inner.insert_transaction_with_readiness_check(
+        let tx_hash = transaction.hash();
+        let result = inner.insert_transaction_with_readiness_check(
             account_cache,
             transaction,
             packed,
             force,
-        )
+        );
+        if result.is_ok() && !packed {
+            if let Some(expiry_epochs) = self.config.expiry_epochs {
+                let best_epoch =
+                    self.consensus_best_info.lock().best_epoch_number;
+                inner.set_tx_expiry(tx_hash, best_epoch + expiry_epochs);
+            }
+        }
+        result
     }
 
+    /// Take the transactions to be propagated. The replacements of the
+    /// transactions propagated before are placed first.
+    /// The transactions in the block templates of the local miner are held
+    /// back.
     pub fn get_to_be_propagated_transactions(
         &self,
-    ) -> HashMap<H256, Arc<SignedTransaction>> {
-        let mut to_prop = self.to_propagate_trans.write();
-        let mut res = HashMap::new();
-        mem::swap(&mut *to_prop, &mut res);
-        res
+    ) -> Vec<Arc<SignedTransaction>> {
+        let mut transactions = self.to_propagate_trans.write().take();
+        let mut provisional_packing = self.provisional_packing.lock();
+        transactions.retain(|tx| !provisional_packing.hold_back(tx));
+        transactions
     }
 
+    /// Put back the transactions taken but not propagated. The transactions
+    /// replaced in the meantime are dropped.
     pub fn set_to_be_propagated_transactions(
-        &self, transactions: HashMap<H256, Arc<SignedTransaction>>,
+        &self, transactions: Vec<Arc<SignedTransaction>>,
     ) {
-        let mut to_prop = self.to_propagate_trans.write();
-        to_prop.extend(transactions);
+        self.to_propagate_trans.write().restore(transactions);
     }
 
     pub fn remove_to_be_propagated_transactions(&self, tx_hash: &H256) {
@@ -613,6 +853,7 @@ impl TransactionPool {
             best_block_number,
             &self.verification_config,
             &self.machine,
+            self.config.packing_fairness_min_gas_price.map(U256::from),
         )
     }
 
@@ -648,14 +889,20 @@ impl TransactionPool {
         inner.total_unpacked()
     }
 
+    pub fn total_expired(&self) -> usize {
+        let inner = self.inner.read();
+        inner.total_expired()
+    }
+
     /// stats retrieves the length of ready and deferred pool.
-    pub fn stats(&self) -> (usize, usize, usize, usize) {
+    pub fn stats(&self) -> (usize, usize, usize, usize, usize) {
         let inner = self.inner.read();
         (
             inner.total_ready_accounts(),
             inner.total_deferred(),
             inner.total_received(),
             inner.total_unpacked(),
+            inner.total_expired(),
         )
     }
 
@@ -663,8 +910,18 @@ impl TransactionPool {
     pub fn content(
         &self, address: Option<Address>,
     ) -> (Vec<Arc<SignedTransaction>>, Vec<Arc<SignedTransaction>>) {
+        self.snapshot().content(address)
+    }
+
+    /// Return a consistent view of the ready and deferred transactions. The
+    /// snapshot is only taken again after the pool content changes.
+    pub fn snapshot(&self) -> Arc<TxPoolSnapshot> {
         let inner = self.inner.read();
-        inner.content(address)
+        let mut snapshot = self.content_snapshot.lock();
+        if snapshot.version() != inner.content_version() {
+            *snapshot = Arc::new(inner.snapshot());
+        }
+        snapshot.clone()
     }
 
     pub fn notify_new_best_info(
@@ -693,6 +950,11 @@ impl TransactionPool {
             .ok();
         }
 
+        if self.config.expiry_epochs.is_some() {
+            // The next block is in the epoch after the best epoch.
+            inner.remove_expired_transactions(best_info.best_epoch_number + 1);
+        }
+
         let (chain_id, best_height, best_block_number) = {
             (
                 best_info.best_chain_id(),
@@ -712,7 +974,8 @@ impl TransactionPool {
                 &tx.nonce, &tx.sender,
                 account_cache.get_nonce(&tx.sender)?, tx.hash);
 
-            if let Err(e) = self.verify_transaction_tx_pool(
+            let hash = tx.hash();
+            let result = match self.verify_transaction_tx_pool(
                 &tx,
                 /* basic_check = */ false,
                 chain_id,
@@ -720,19 +983,25 @@ impl TransactionPool {
                 transitions,
                 &vm_spec,
             ) {
+                Err(e) => TxInsertionResult::rejected(e),
+                Ok(()) => match self.add_transaction_with_readiness_check(
+                    inner,
+                    &account_cache,
+                    tx,
+                    false,
+                    true,
+                ) {
+                    Ok(Some(replaced)) => TxInsertionResult::Replaced(replaced),
+                    Ok(None) => TxInsertionResult::Added,
+                    Err(e) => TxInsertionResult::rejected(e),
+                },
+            };
+            if let TxInsertionResult::Rejected { reason } = result {
                 warn!(
                     "Recycled transaction {:?} discarded due to not passing verification {}.",
-                    tx.hash(), e
+                    hash, reason
                 );
             }
-            self.add_transaction_with_readiness_check(
-                inner,
-                &account_cache,
-                tx,
-                false,
-                true,
-            )
-            .ok();
         }
         debug!(
             "notify_new_best_info: {:?}",
@@ -0,0 +1,96 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfx_types::Address;
+use primitives::SignedTransaction;
+use std::sync::Arc;
+
+/// A point-in-time view of the ready and deferred transactions in the pool.
+/// It is immutable and shared by the readers until the pool changes, so the
+/// readers neither observe the pool in the middle of a change nor hold the
+/// pool lock while they process the transactions.
+#[derive(Default)]
+pub struct TxPoolSnapshot {
+    /// The content version of the pool when the snapshot is taken.
+    version: u64,
+    ready: Vec<Arc<SignedTransaction>>,
+    deferred: Vec<Arc<SignedTransaction>>,
+}
+
+impl TxPoolSnapshot {
+    pub fn new(
+        version: u64, ready: Vec<Arc<SignedTransaction>>,
+        deferred: Vec<Arc<SignedTransaction>>,
+    ) -> Self
+    {
+        TxPoolSnapshot {
+            version,
+            ready,
+            deferred,
+        }
+    }
+
+    pub fn version(&self) -> u64 { self.version }
+
+    /// Return the ready and deferred transactions sent by `address`, or all
+    /// of them if `address` is `None`.
+    pub fn content(
+        &self, address: Option<Address>,
+    ) -> (Vec<Arc<SignedTransaction>>, Vec<Arc<SignedTransaction>>) {
+        let filter = |txs: &Vec<Arc<SignedTransaction>>| {
+            txs.iter()
+                .filter(|tx| address.map_or(true, |addr| tx.sender == addr))
+                .cloned()
+                .collect()
+        };
+        (filter(&self.ready), filter(&self.deferred))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TxPoolSnapshot;
+    use cfx_types::{Address, U256};
+    use keylib::{Generator, Random};
+    use primitives::{Action, Transaction};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_snapshot_content() {
+        let alice = Random.generate().unwrap();
+        let bob = Random.generate().unwrap();
+        let txs: Vec<_> = [&alice, &alice, &bob]
+            .iter()
+            .enumerate()
+            .map(|(nonce, sender)| {
+                Arc::new(
+                    Transaction {
+                        nonce: U256::from(nonce),
+                        gas_price: U256::from(1),
+                        gas: U256::from(21000),
+                        action: Action::Call(Address::random()),
+                        value: U256::zero(),
+                        storage_limit: 0,
+                        epoch_height: 0,
+                        chain_id: 0,
+                        data: Vec::new(),
+                    }
+                    .sign(sender.secret()),
+                )
+            })
+            .collect();
+        let snapshot = TxPoolSnapshot::new(
+            1,
+            vec![txs[0].clone(), txs[2].clone()],
+            txs.clone(),
+        );
+
+        let (ready, deferred) = snapshot.content(None);
+        assert_eq!((ready.len(), deferred.len()), (2, 3));
+        let (ready, deferred) = snapshot.content(Some(alice.address()));
+        assert_eq!(ready, vec![txs[0].clone()]);
+        assert_eq!(deferred, vec![txs[0].clone(), txs[1].clone()]);
+        assert!(snapshot.content(Some(Address::random())).1.is_empty());
+    }
+}
@@ -0,0 +1,226 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfx_types::{Address, H256, U256};
+use malloc_size_of::{MallocSizeOf, MallocSizeOfOps};
+use malloc_size_of_derive::MallocSizeOf as DeriveMallocSizeOf;
+use primitives::SignedTransaction;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::Arc,
+};
+
+/// The maximum number of (sender, nonce) pairs whose latest transaction is
+/// remembered for detecting replacements.
+const MAX_TRACKED_SENDER_NONCES: usize = 100_000;
+
+#[derive(DeriveMallocSizeOf)]
+struct LatestTransaction {
+    hash: H256,
+    /// Whether the transaction has been taken for propagation.
+    announced: bool,
+    /// Whether the transaction replaces an announced transaction of the same
+    /// sender and nonce, directly or through a chain of replacements that
+    /// were not announced.
+    is_replacement: bool,
+}
+
+/// The transactions waiting to be announced to peers.
+///
+/// A transaction of the same sender and nonce as an earlier one replaces it
+/// in the pool. The latest transaction of each (sender, nonce) is remembered
+/// here, so the replaced transaction is dropped if it is still waiting, and
+/// the replacement is announced before the other transactions when the
+/// replaced one has been announced, because the peers that only saw the
+/// replaced transaction would not learn about the replacement otherwise.
+#[derive(Default)]
+pub struct ToPropagateTransactions {
+    pending: HashMap<H256, Arc<SignedTransaction>>,
+    /// The pending transactions to be announced first.
+    replacements: HashSet<H256>,
+    latest: HashMap<(Address, U256), LatestTransaction>,
+    /// The keys of `latest` in the order of insertion, used to bound its size.
+    latest_order: VecDeque<(Address, U256)>,
+}
+
+impl MallocSizeOf for ToPropagateTransactions {
+    fn size_of(&self, ops: &mut MallocSizeOfOps) -> usize {
+        self.pending.size_of(ops)
+            + self.replacements.size_of(ops)
+            + self.latest.size_of(ops)
+            + self.latest_order.size_of(ops)
+    }
+}
+
+impl ToPropagateTransactions {
+    /// Queue a transaction newly inserted into the pool.
+    pub fn insert(&mut self, tx: Arc<SignedTransaction>) {
+        if self.pending.contains_key(&tx.hash) {
+            return;
+        }
+        let key = (tx.sender, tx.nonce);
+        let is_replacement = match self.latest.get(&key) {
+            Some(latest) if latest.hash == tx.hash => latest.is_replacement,
+            Some(latest) => {
+                let superseded = latest.hash;
+                let is_replacement = latest.announced || latest.is_replacement;
+                if self.pending.remove(&superseded).is_some() {
+                    debug!(
+                        "Drop superseded transaction {:?} replaced by {:?} \
+                         from propagation",
+                        superseded, tx.hash
+                    );
+                }
+                self.replacements.remove(&superseded);
+                is_replacement
+            }
+            None => {
+                self.track(key);
+                false
+            }
+        };
+        self.latest.insert(
+            key,
+            LatestTransaction {
+                hash: tx.hash,
+                announced: false,
+                is_replacement,
+            },
+        );
+        if is_replacement {
+            self.replacements.insert(tx.hash);
+        }
+        self.pending.insert(tx.hash, tx);
+    }
+
+    /// Take all the pending transactions, with the replacements of announced
+    /// transactions placed first.
+    pub fn take(&mut self) -> Vec<Arc<SignedTransaction>> {
+        let mut replacements = Vec::with_capacity(self.replacements.len());
+        let mut others = Vec::with_capacity(self.pending.len());
+        for (hash, tx) in self.pending.drain() {
+            if let Some(latest) = self.latest.get_mut(&(tx.sender, tx.nonce)) {
+                if latest.hash == hash {
+                    latest.announced = true;
+                }
+            }
+            if self.replacements.contains(&hash) {
+                replacements.push(tx);
+            } else {
+                others.push(tx);
+            }
+        }
+        self.replacements.clear();
+        replacements.extend(others);
+        replacements
+    }
+
+    /// Put back the transactions that are taken but not announced. The
+    /// transactions replaced since they are taken are dropped.
+    pub fn restore(&mut self, transactions: Vec<Arc<SignedTransaction>>) {
+        for tx in transactions {
+            if self.pending.contains_key(&tx.hash) {
+                continue;
+            }
+            let is_replacement = match self.latest.get(&(tx.sender, tx.nonce)) {
+                Some(latest) if latest.hash != tx.hash => continue,
+                Some(latest) => latest.is_replacement,
+                None => false,
+            };
+            if is_replacement {
+                self.replacements.insert(tx.hash);
+            }
+            self.pending.insert(tx.hash, tx);
+        }
+    }
+
+    pub fn remove(&mut self, tx_hash: &H256) {
+        self.pending.remove(tx_hash);
+        self.replacements.remove(tx_hash);
+    }
+
+    fn track(&mut self, key: (Address, U256)) {
+        self.latest_order.push_back(key);
+        while self.latest_order.len() > MAX_TRACKED_SENDER_NONCES {
+            let evicted = self.latest_order.pop_front().expect("not empty");
+            self.latest.remove(&evicted);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ToPropagateTransactions;
+    use cfx_types::{Address, U256};
+    use keylib::{Generator, KeyPair, Random};
+    use primitives::{Action, SignedTransaction, Transaction};
+    use std::sync::Arc;
+
+    fn new_test_tx(
+        sender: &KeyPair, nonce: usize, gas_price: usize,
+    ) -> Arc<SignedTransaction> {
+        Arc::new(
+            Transaction {
+                nonce: U256::from(nonce),
+                gas_price: U256::from(gas_price),
+                gas: U256::from(50000),
+                action: Action::Call(Address::random()),
+                value: U256::from(100),
+                storage_limit: 0,
+                epoch_height: 0,
+                chain_id: 0,
+                data: Vec::new(),
+            }
+            .sign(sender.secret()),
+        )
+    }
+
+    #[test]
+    fn test_drop_superseded_pending_transaction() {
+        let alice = Random.generate().unwrap();
+        let mut to_propagate = ToPropagateTransactions::default();
+        let tx = new_test_tx(&alice, 1, 10);
+        let tx_new = new_test_tx(&alice, 1, 11);
+        to_propagate.insert(tx.clone());
+        to_propagate.insert(tx_new.clone());
+        let taken = to_propagate.take();
+        assert_eq!(taken.len(), 1);
+        assert_eq!(taken[0].hash, tx_new.hash);
+    }
+
+    #[test]
+    fn test_announce_replacement_first() {
+        let alice = Random.generate().unwrap();
+        let bob = Random.generate().unwrap();
+        let mut to_propagate = ToPropagateTransactions::default();
+        let alice_tx = new_test_tx(&alice, 1, 10);
+        to_propagate.insert(alice_tx.clone());
+        assert_eq!(to_propagate.take().len(), 1);
+
+        for nonce in 0..10 {
+            to_propagate.insert(new_test_tx(&bob, nonce, 10));
+        }
+        let alice_tx_new = new_test_tx(&alice, 1, 11);
+        to_propagate.insert(alice_tx_new.clone());
+        let taken = to_propagate.take();
+        assert_eq!(taken.len(), 11);
+        assert_eq!(taken[0].hash, alice_tx_new.hash);
+    }
+
+    #[test]
+    fn test_restore_skips_replaced_transaction() {
+        let alice = Random.generate().unwrap();
+        let mut to_propagate = ToPropagateTransactions::default();
+        let tx = new_test_tx(&alice, 1, 10);
+        to_propagate.insert(tx.clone());
+        let taken = to_propagate.take();
+
+        let tx_new = new_test_tx(&alice, 1, 11);
+        to_propagate.insert(tx_new.clone());
+        to_propagate.restore(taken);
+        let taken = to_propagate.take();
+        assert_eq!(taken.len(), 1);
+        assert_eq!(taken[0].hash, tx_new.hash);
+    }
+}
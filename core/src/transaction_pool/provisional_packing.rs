@@ -0,0 +1,171 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfx_types::H256;
+use malloc_size_of::{MallocSizeOf, MallocSizeOfOps};
+use primitives::SignedTransaction;
+use std::{collections::HashMap, sync::Arc};
+
+/// The transactions included in the block templates assembled by the local
+/// miner, identified by the problem hash of the template.
+///
+/// These transactions are expected to be included in the block once it is
+/// mined, so they are held back from propagation. The held transactions are
+/// propagated again if all the templates including them are abandoned, and
+/// dropped if one of the templates is mined because the block carries them.
+#[derive(Default)]
+pub struct ProvisionalPacking {
+    templates: HashMap<H256, Vec<H256>>,
+    /// The templates including each transaction, the latest last.
+    tx_templates: HashMap<H256, Vec<H256>>,
+    held_back: HashMap<H256, Arc<SignedTransaction>>,
+}
+
+impl MallocSizeOf for ProvisionalPacking {
+    fn size_of(&self, ops: &mut MallocSizeOfOps) -> usize {
+        self.templates.size_of(ops)
+            + self.tx_templates.size_of(ops)
+            + self.held_back.size_of(ops)
+    }
+}
+
+impl ProvisionalPacking {
+    pub fn mark(&mut self, template_id: H256, tx_hashes: Vec<H256>) {
+        if self.templates.contains_key(&template_id) {
+            return;
+        }
+        for tx_hash in &tx_hashes {
+            self.tx_templates
+                .entry(*tx_hash)
+                .or_insert_with(Vec::new)
+                .push(template_id);
+        }
+        self.templates.insert(template_id, tx_hashes);
+    }
+
+    /// Remove the marks of a template. Return the held transactions to be
+    /// propagated again, which are not in any other template and the template
+    /// is not mined.
+    pub fn release(
+        &mut self, template_id: &H256, mined: bool,
+    ) -> Vec<Arc<SignedTransaction>> {
+        let tx_hashes = match self.templates.remove(template_id) {
+            Some(tx_hashes) => tx_hashes,
+            None => return Vec::new(),
+        };
+        let mut to_propagate = Vec::new();
+        for tx_hash in tx_hashes {
+            let released = match self.tx_templates.get_mut(&tx_hash) {
+                Some(templates) => {
+                    templates.retain(|id| id != template_id);
+                    templates.is_empty()
+                }
+                None => false,
+            };
+            if released {
+                self.tx_templates.remove(&tx_hash);
+            }
+            if released || mined {
+                if let Some(tx) = self.held_back.remove(&tx_hash) {
+                    if !mined {
+                        to_propagate.push(tx);
+                    }
+                }
+            }
+        }
+        to_propagate
+    }
+
+    /// Keep `tx` from propagation if it is in a template. Return whether it
+    /// is held back.
+    pub fn hold_back(&mut self, tx: &Arc<SignedTransaction>) -> bool {
+        if self.tx_templates.contains_key(&tx.hash) {
+            self.held_back.insert(tx.hash, tx.clone());
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Return the latest template including the transaction.
+    pub fn template_of(&self, tx_hash: &H256) -> Option<H256> {
+        self.tx_templates
+            .get(tx_hash)
+            .and_then(|templates| templates.last().cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ProvisionalPacking;
+    use cfx_types::{Address, H256, U256};
+    use keylib::{Generator, Random};
+    use primitives::{Action, SignedTransaction, Transaction};
+    use std::sync::Arc;
+
+    fn new_test_tx(nonce: usize) -> Arc<SignedTransaction> {
+        let sender = Random.generate().unwrap();
+        Arc::new(
+            Transaction {
+                nonce: U256::from(nonce),
+                gas_price: U256::from(10),
+                gas: U256::from(50000),
+                action: Action::Call(Address::random()),
+                value: U256::from(100),
+                storage_limit: 0,
+                epoch_height: 0,
+                chain_id: 0,
+                data: Vec::new(),
+            }
+            .sign(sender.secret()),
+        )
+    }
+
+    #[test]
+    fn test_abandoned_template_propagates_again() {
+        let mut packing = ProvisionalPacking::default();
+        let tx = new_test_tx(0);
+        let template = H256::from_low_u64_be(1);
+        packing.mark(template, vec![tx.hash]);
+        assert_eq!(packing.template_of(&tx.hash), Some(template));
+        assert!(packing.hold_back(&tx));
+
+        let released = packing.release(&template, false);
+        assert_eq!(released.len(), 1);
+        assert_eq!(released[0].hash, tx.hash);
+        assert_eq!(packing.template_of(&tx.hash), None);
+        assert!(!packing.hold_back(&tx));
+    }
+
+    #[test]
+    fn test_transaction_in_multiple_templates() {
+        let mut packing = ProvisionalPacking::default();
+        let tx = new_test_tx(0);
+        let old_template = H256::from_low_u64_be(1);
+        let new_template = H256::from_low_u64_be(2);
+        packing.mark(old_template, vec![tx.hash]);
+        packing.mark(new_template, vec![tx.hash]);
+        assert_eq!(packing.template_of(&tx.hash), Some(new_template));
+        assert!(packing.hold_back(&tx));
+
+        assert!(packing.release(&new_template, false).is_empty());
+        assert_eq!(packing.template_of(&tx.hash), Some(old_template));
+        assert_eq!(packing.release(&old_template, false).len(), 1);
+    }
+
+    #[test]
+    fn test_mined_template_drops_held_transactions() {
+        let mut packing = ProvisionalPacking::default();
+        let tx = new_test_tx(0);
+        let mined_template = H256::from_low_u64_be(1);
+        let other_template = H256::from_low_u64_be(2);
+        packing.mark(mined_template, vec![tx.hash]);
+        packing.mark(other_template, vec![tx.hash]);
+        assert!(packing.hold_back(&tx));
+
+        assert!(packing.release(&mined_template, true).is_empty());
+        assert!(packing.release(&other_template, false).is_empty());
+        assert_eq!(packing.template_of(&tx.hash), None);
+    }
+}
@@ -3,6 +3,8 @@ use super::{
     garbage_collector::GarbageCollector,
     impls::TreapMap,
     nonce_pool::{InsertResult, NoncePool, TxWithReadyInfo},
+    pool_snapshot::TxPoolSnapshot,
+    tx_expiry::TxExpiry,
 };
 use crate::{
     machine::Machine,
@@ -21,7 +23,7 @@ use primitives::{
 use rlp::*;
 use serde::Serialize;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     sync::Arc,
     time::{SystemTime, UNIX_EPOCH},
 };
@@ -56,6 +58,8 @@ lazy_static! {
         CounterUsize::register_with_group("txpool", "gc_ready");
     static ref GC_METER: Arc<dyn Meter> =
         register_meter_with_group("txpool", "gc_txs_tps");
+    static ref EXPIRED_COUNTER: Arc<dyn Counter<usize>> =
+        CounterUsize::register_with_group("txpool", "expired_txs");
 }
 
 #[derive(DeriveMallocSizeOf)]
@@ -93,6 +97,20 @@ impl DeferredPool {
         }
     }
 
+    /// Return the first nonce from `start_nonce` without a transaction of
+    /// `sender` in the pool.
+    fn first_missing_nonce(
+        &self, sender: &Address, start_nonce: &U256,
+    ) -> U256 {
+        let mut nonce = *start_nonce;
+        if let Some(bucket) = self.buckets.get(sender) {
+            while bucket.check_nonce_exists(&nonce) {
+                nonce += 1.into();
+            }
+        }
+        nonce
+    }
+
     fn count_less(&self, sender: &Address, nonce: &U256) -> usize {
         if let Some(bucket) = self.buckets.get(sender) {
             bucket.count_less(nonce)
@@ -116,6 +134,21 @@ impl DeferredPool {
         }
     }
 
+    fn remove(
+        &mut self, addr: &Address, nonce: &U256,
+    ) -> Option<TxWithReadyInfo> {
+        match self.buckets.get_mut(addr) {
+            None => None,
+            Some(bucket) => {
+                let ret = bucket.remove(nonce);
+                if bucket.is_empty() {
+                    self.buckets.remove(addr);
+                }
+                ret
+            }
+        }
+    }
+
     fn get_lowest_nonce(&self, addr: &Address) -> Option<&U256> {
         self.buckets
             .get(addr)
@@ -267,6 +300,9 @@ impl ReadyAccountPool {
 pub enum TransactionStatus {
     Packed,
     Ready,
+    /// Ready and included in a block template of the local miner, so it is
+    /// not propagated until the template is abandoned.
+    ProvisionallyPacked,
     Pending(PendingReason),
 }
 
@@ -282,6 +318,9 @@ pub struct TransactionPoolInner {
     capacity: usize,
     total_received_count: usize,
     unpacked_transaction_count: usize,
+    /// The number of transactions removed from the pool because they are not
+    /// packed before they expire.
+    total_expired_count: usize,
     /// Tracks all transactions in the transaction pool by account and nonce.
     /// Packed and executed transactions will eventually be garbage collected.
     deferred_pool: DeferredPool,
@@ -299,6 +338,11 @@ pub struct TransactionPoolInner {
     /// It should contain the same transaction set as `deferred_pool`.
     txs: HashMap<H256, Arc<SignedTransaction>>,
     tx_sponsored_gas_map: HashMap<H256, (U256, u64)>,
+    /// The last epochs in which the transactions may be packed.
+    tx_expiry: TxExpiry,
+    /// Incremented by each operation that may change the ready or deferred
+    /// transactions, so a snapshot is stale if its version differs.
+    content_version: u64,
 }
 
 impl TransactionPoolInner {
@@ -309,6 +353,7 @@ impl TransactionPoolInner {
             capacity,
             total_received_count: 0,
             unpacked_transaction_count: 0,
+            total_expired_count: 0,
             deferred_pool: DeferredPool::new(),
             ready_account_pool: ReadyAccountPool::new(
                 tx_weight_scaling,
@@ -318,17 +363,22 @@ impl TransactionPoolInner {
             garbage_collector: GarbageCollector::default(),
             txs: HashMap::new(),
             tx_sponsored_gas_map: HashMap::new(),
+            tx_expiry: TxExpiry::default(),
+            content_version: 0,
         }
     }
 
     pub fn clear(&mut self) {
+        self.content_version += 1;
         self.deferred_pool.clear();
         self.ready_account_pool.clear();
         self.ready_nonces_and_balances.clear();
         self.garbage_collector.clear();
         self.txs.clear();
+        self.tx_expiry.clear();
         self.total_received_count = 0;
         self.unpacked_transaction_count = 0;
+        self.total_expired_count = 0;
     }
 
     pub fn total_deferred(&self) -> usize { self.txs.len() }
@@ -341,6 +391,10 @@ impl TransactionPoolInner {
 
     pub fn total_unpacked(&self) -> usize { self.unpacked_transaction_count }
 
+    pub fn total_expired(&self) -> usize { self.total_expired_count }
+
+    pub fn content_version(&self) -> u64 { self.content_version }
+
     pub fn get(&self, tx_hash: &H256) -> Option<Arc<SignedTransaction>> {
         self.txs.get(tx_hash).map(|x| x.clone())
     }
@@ -445,6 +499,7 @@ impl TransactionPoolInner {
             // maintain txs
             self.txs.remove(&removed_tx.hash());
             self.tx_sponsored_gas_map.remove(&removed_tx.hash());
+            self.tx_expiry.remove(&removed_tx.hash());
         }
 
         GC_METER.mark(count_before_gc - self.total_deferred());
@@ -530,6 +585,9 @@ impl TransactionPoolInner {
                 }
                 self.txs.remove(&replaced_tx.hash());
                 self.txs.insert(transaction.hash(), transaction.clone());
+                if replaced_tx.hash() != transaction.hash() {
+                    self.tx_expiry.remove(&replaced_tx.hash());
+                }
                 self.tx_sponsored_gas_map.remove(&replaced_tx.hash());
                 self.tx_sponsored_gas_map.insert(
                     transaction.hash(),
@@ -563,6 +621,12 @@ impl TransactionPoolInner {
         }
     }
 
+    pub fn get_first_missing_nonce(
+        &self, address: &Address, start_nonce: &U256,
+    ) -> U256 {
+        self.deferred_pool.first_missing_nonce(address, start_nonce)
+    }
+
     pub fn get_account_pending_transactions(
         &self, address: &Address, maybe_start_nonce: Option<U256>,
         maybe_limit: Option<usize>,
@@ -718,28 +782,63 @@ impl TransactionPoolInner {
     }
 
     /// pack at most num_txs transactions randomly
+    ///
+    /// If `fairness_min_gas_price` is set, the transactions with at least this
+    /// gas price are packed in rounds, in which each sender gets at most one
+    /// of them. A new round starts when the ready pool only has the senders
+    /// packed in the current round.
     pub fn pack_transactions<'a>(
         &mut self, num_txs: usize, block_gas_limit: U256,
         block_size_limit: usize, best_epoch_height: u64,
         best_block_number: u64, verification_config: &VerificationConfig,
-        machine: &Machine,
+        machine: &Machine, fairness_min_gas_price: Option<U256>,
     ) -> Vec<Arc<SignedTransaction>>
     {
         let mut packed_transactions: Vec<Arc<SignedTransaction>> = Vec::new();
         if num_txs == 0 {
             return packed_transactions;
         }
+        // The packed transactions are put back, but the dropped ones are not.
+        self.content_version += 1;
 
         let mut total_tx_gas_limit: U256 = 0.into();
         let mut total_tx_size: usize = 0;
 
         let mut big_tx_resample_times_limit = 10;
         let mut recycle_txs = Vec::new();
+        // The senders packed in the current fairness round, and their ready
+        // transactions held for the next round.
+        let mut round_senders = HashSet::new();
+        let mut next_round_txs = Vec::new();
 
         let spec = machine.spec(best_block_number);
         let transitions = &machine.params().transition_heights;
 
-        'out: while let Some(tx) = self.ready_account_pool.pop() {
+        'out: loop {
+            let tx = match self.ready_account_pool.pop() {
+                Some(tx) => tx,
+                None if !next_round_txs.is_empty() => {
+                    round_senders.clear();
+                    for tx in next_round_txs.drain(..) {
+                        self.ready_account_pool.insert(tx);
+                    }
+                    continue 'out;
+                }
+                None => break 'out,
+            };
+            let in_fairness_round = fairness_min_gas_price
+                .map_or(false, |floor| *tx.gas_price() >= floor);
+            if in_fairness_round && round_senders.contains(&tx.sender()) {
+                next_round_txs.push(tx);
+                continue 'out;
+            }
+
+            // The expired transactions are removed from the pool with the next
+            // best info.
+            if self.tx_expiry.is_expired(&tx.hash(), best_epoch_height) {
+                continue 'out;
+            }
+
             let tx_size = tx.rlp_size();
             if block_gas_limit - total_tx_gas_limit < *tx.gas_limit()
                 || block_size_limit - total_tx_size < tx_size
@@ -772,6 +871,9 @@ impl TransactionPoolInner {
 
             total_tx_gas_limit += *tx.gas_limit();
             total_tx_size += tx_size;
+            if in_fairness_round {
+                round_senders.insert(tx.sender());
+            }
 
             packed_transactions.push(tx.clone());
             self.insert_transaction_without_readiness_check(
@@ -791,7 +893,7 @@ impl TransactionPoolInner {
             }
         }
 
-        for tx in recycle_txs {
+        for tx in recycle_txs.into_iter().chain(next_round_txs) {
             self.ready_account_pool.insert(tx);
         }
 
@@ -826,9 +928,60 @@ impl TransactionPoolInner {
         packed_transactions
     }
 
+    /// Set the last epoch in which a transaction in the pool may be packed.
+    pub fn set_tx_expiry(&mut self, tx_hash: H256, last_epoch: u64) {
+        if self.txs.contains_key(&tx_hash) {
+            self.tx_expiry.insert(tx_hash, last_epoch);
+        }
+    }
+
+    /// Remove the unpacked transactions that are not packed before `epoch`,
+    /// and return the number of them. The packed ones are kept until they are
+    /// garbage collected.
+    pub fn remove_expired_transactions(&mut self, epoch: u64) -> usize {
+        let mut removed = 0;
+        for hash in self.tx_expiry.take_expired(epoch) {
+            let tx = match self.txs.get(&hash) {
+                Some(tx) => tx.clone(),
+                None => continue,
+            };
+            let (sender, nonce) = (tx.sender(), tx.nonce());
+            if self.deferred_pool.check_tx_packed(sender, nonce) {
+                continue;
+            }
+            self.deferred_pool.remove(&sender, &nonce);
+            self.unpacked_transaction_count -= 1;
+            self.txs.remove(&hash);
+            self.tx_sponsored_gas_map.remove(&hash);
+
+            let (local_nonce, _) = self
+                .get_local_nonce_and_balance(&sender)
+                .unwrap_or((0.into(), 0.into()));
+            let count = self.deferred_pool.count_less(&sender, &local_nonce);
+            let timestamp = self
+                .garbage_collector
+                .get_timestamp(&sender)
+                .unwrap_or(self.get_current_timestamp());
+            self.garbage_collector.insert(&sender, count, timestamp);
+            self.recalculate_readiness_with_local_info(&sender);
+            if !self.deferred_pool.contain_address(&sender) {
+                self.ready_nonces_and_balances.remove(&sender);
+            }
+            removed += 1;
+        }
+        if removed > 0 {
+            self.content_version += 1;
+            debug!("{} expired transactions are removed from pool", removed);
+            EXPIRED_COUNTER.inc(removed);
+        }
+        self.total_expired_count += removed;
+        removed
+    }
+
     pub fn notify_modified_accounts(
         &mut self, accounts_from_execution: Vec<Account>,
     ) {
+        self.content_version += 1;
         for account in &accounts_from_execution {
             self.recalculate_readiness_with_fixed_info(
                 account.address(),
@@ -838,39 +991,30 @@ impl TransactionPoolInner {
         }
     }
 
-    /// content retrieves the ready and deferred transactions.
-    pub fn content(
-        &self, address: Option<Address>,
-    ) -> (Vec<Arc<SignedTransaction>>, Vec<Arc<SignedTransaction>>) {
+    /// Take a snapshot of the ready and deferred transactions.
+    pub fn snapshot(&self) -> TxPoolSnapshot {
         let ready_txs = self
             .ready_account_pool
             .treap
             .iter()
-            .filter(|address_tx| {
-                address == None || &address.unwrap() == address_tx.0
-            })
             .map(|(_, tx)| tx.clone())
             .collect();
-
-        let deferred_txs = self
-            .txs
-            .values()
-            .filter(|tx| address == None || tx.sender == address.unwrap())
-            .map(|v| v.clone())
-            .collect();
-
-        (ready_txs, deferred_txs)
+        let deferred_txs = self.txs.values().map(|v| v.clone()).collect();
+        TxPoolSnapshot::new(self.content_version, ready_txs, deferred_txs)
     }
 
     // Add transaction into deferred pool and maintain its readiness
     // the packed tag provided
     // if force tag is true, the replacement in nonce pool must be happened
+    // Return the hash of the replaced transaction of the same sender and nonce
+    // if there is one.
     pub fn insert_transaction_with_readiness_check(
         &mut self, account_cache: &AccountCache,
         transaction: Arc<SignedTransaction>, packed: bool, force: bool,
-    ) -> Result<(), String>
+    ) -> Result<Option<H256>, String>
     {
         let _timer = MeterTimer::time_func(TX_POOL_INNER_INSERT_TIMER.as_ref());
+        self.content_version += 1;
         let mut sponsored_gas = U256::from(0);
         let mut sponsored_storage = 0;
 
@@ -971,9 +1115,16 @@ impl TransactionPoolInner {
             Some((state_nonce, state_balance)),
             (sponsored_gas, sponsored_storage),
         );
-        if let InsertResult::Failed(info) = result {
-            return Err(format!("Failed imported to deferred pool: {}", info));
-        }
+        let replaced = match result {
+            InsertResult::Failed(info) => {
+                return Err(format!(
+                    "Failed imported to deferred pool: {}",
+                    info
+                ));
+            }
+            InsertResult::NewAdded => None,
+            InsertResult::Updated(replaced_tx) => Some(replaced_tx.hash()),
+        };
 
         self.recalculate_readiness_with_state(
             &transaction.sender,
@@ -983,7 +1134,7 @@ impl TransactionPoolInner {
             format!("Failed to read account_cache from storage: {}", e)
         })?;
 
-        Ok(())
+        Ok(replaced)
     }
 }
 
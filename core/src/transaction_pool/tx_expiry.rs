@@ -0,0 +1,87 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfx_types::H256;
+use malloc_size_of::{MallocSizeOf, MallocSizeOfOps};
+use std::collections::{BTreeSet, HashMap};
+
+/// TxExpiry tracks the last epoch in which each transaction in the pool may
+/// be packed. It is only used with `tx_pool_expiry_epochs` set.
+#[derive(Default)]
+pub struct TxExpiry {
+    by_epoch: BTreeSet<(u64, H256)>,
+    expiry_of: HashMap<H256, u64>,
+}
+
+impl MallocSizeOf for TxExpiry {
+    fn size_of(&self, ops: &mut MallocSizeOfOps) -> usize {
+        // `BTreeSet` is not supported, so its size is estimated.
+        self.by_epoch.len() * std::mem::size_of::<(u64, H256)>()
+            + self.expiry_of.size_of(ops)
+    }
+}
+
+impl TxExpiry {
+    pub fn clear(&mut self) {
+        self.by_epoch.clear();
+        self.expiry_of.clear();
+    }
+
+    pub fn insert(&mut self, hash: H256, last_epoch: u64) {
+        if let Some(old) = self.expiry_of.insert(hash, last_epoch) {
+            self.by_epoch.remove(&(old, hash));
+        }
+        self.by_epoch.insert((last_epoch, hash));
+    }
+
+    pub fn remove(&mut self, hash: &H256) {
+        if let Some(old) = self.expiry_of.remove(hash) {
+            self.by_epoch.remove(&(old, *hash));
+        }
+    }
+
+    pub fn is_expired(&self, hash: &H256, epoch: u64) -> bool {
+        self.expiry_of
+            .get(hash)
+            .map_or(false, |last_epoch| *last_epoch < epoch)
+    }
+
+    /// Stop tracking and return the transactions expired at `epoch`.
+    pub fn take_expired(&mut self, epoch: u64) -> Vec<H256> {
+        let mut expired = Vec::new();
+        while let Some(&(last_epoch, hash)) = self.by_epoch.iter().next() {
+            if last_epoch >= epoch {
+                break;
+            }
+            self.remove(&hash);
+            expired.push(hash);
+        }
+        expired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TxExpiry;
+    use cfx_types::H256;
+
+    #[test]
+    fn test_take_expired() {
+        let mut expiry = TxExpiry::default();
+        let hashes: Vec<H256> = (0..3).map(H256::from_low_u64_be).collect();
+        expiry.insert(hashes[0], 10);
+        expiry.insert(hashes[1], 5);
+        expiry.insert(hashes[2], 20);
+        assert!(!expiry.is_expired(&hashes[1], 5));
+        assert!(expiry.is_expired(&hashes[1], 6));
+
+        // A recycled transaction gets a new expiry.
+        expiry.insert(hashes[1], 15);
+        expiry.remove(&hashes[2]);
+        assert_eq!(expiry.take_expired(11), vec![hashes[0]]);
+        assert_eq!(expiry.take_expired(16), vec![hashes[1]]);
+        assert!(expiry.take_expired(100).is_empty());
+        assert!(!expiry.is_expired(&hashes[0], 100));
+    }
+}
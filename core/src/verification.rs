@@ -16,14 +16,17 @@ use cfx_storage::{
     into_simple_mpt_key, make_simple_mpt, simple_mpt_merkle_root,
     simple_mpt_proof, SimpleMpt, TrieProof,
 };
-use cfx_types::{BigEndianHash, H256, U256};
+use cfx_types::{BigEndianHash, Bloom, H256, U256};
+use hash::keccak;
 use primitives::{
     block::BlockHeight,
     transaction::{TransactionError, TransactionType},
-    Action, Block, BlockHeader, BlockReceipts, MerkleHash, Receipt,
-    SignedTransaction, TransactionWithSignature,
+    Action, Block, BlockHeader, BlockHeaderBuilder, BlockReceipts, MerkleHash,
+    Receipt, SignedTransaction, TransactionWithSignature,
 };
+use rayon::prelude::*;
 use rlp::Encodable;
+use rlp_derive::{RlpDecodable, RlpEncodable};
 use std::{collections::HashSet, convert::TryInto, sync::Arc};
 use unexpected::{Mismatch, OutOfBounds};
 
@@ -77,7 +80,9 @@ fn block_receipts_trie(block_receipts: &Vec<Receipt>) -> SimpleMpt {
 }
 
 /// Compute block receipts root.
-fn compute_block_receipts_root(block_receipts: &Vec<Receipt>) -> MerkleHash {
+pub fn compute_block_receipts_root(
+    block_receipts: &Vec<Receipt>,
+) -> MerkleHash {
     simple_mpt_merkle_root(&mut block_receipts_trie(block_receipts))
 }
 
@@ -91,15 +96,25 @@ pub fn compute_block_receipt_proof(
     )
 }
 
-/// Create an MPT from the ordered list of epoch receipts.
+/// Create an MPT from the ordered block receipts roots of an epoch.
 /// Keys are block indices in the epoch, values are block receipts roots.
-fn epoch_receipts_trie(epoch_receipts: &Vec<Arc<BlockReceipts>>) -> SimpleMpt {
+fn block_receipts_roots_trie<I>(block_receipts_roots: I) -> SimpleMpt
+where I: IntoIterator<Item = MerkleHash> {
     make_simple_mpt(
+        block_receipts_roots
+            .into_iter()
+            .map(|root| root.as_bytes().into())
+            .collect(),
+    )
+}
+
+/// Create an MPT from the ordered list of epoch receipts.
+fn epoch_receipts_trie(epoch_receipts: &Vec<Arc<BlockReceipts>>) -> SimpleMpt {
+    block_receipts_roots_trie(
         epoch_receipts
             .iter()
             .map(|block_receipts| &block_receipts.receipts)
-            .map(|rs| compute_block_receipts_root(&rs).as_bytes().into())
-            .collect(),
+            .map(compute_block_receipts_root),
     )
 }
 
@@ -111,6 +126,58 @@ pub fn compute_receipts_root(
     simple_mpt_merkle_root(&mut epoch_receipts_trie(epoch_receipts))
 }
 
+/// Compute epoch receipts root from the receipts roots of the blocks in the
+/// epoch, computed by `compute_block_receipts_root`. It gives the same result
+/// as `compute_receipts_root`, but the caller does not need to keep the
+/// receipts of the whole epoch in memory.
+pub fn compute_receipts_root_from_block_roots<I>(
+    block_receipts_roots: I,
+) -> MerkleHash
+where I: IntoIterator<Item = MerkleHash> {
+    simple_mpt_merkle_root(&mut block_receipts_roots_trie(block_receipts_roots))
+}
+
+/// `EpochReceiptsFunnel` takes the receipts of the blocks in an epoch one by
+/// one in order, and computes the same receipts root and logs bloom hash as
+/// `compute_receipts_root` and
+/// `BlockHeaderBuilder::compute_block_logs_bloom_hash`. Only the receipts root
+/// and the logs bloom of each block are kept, so the receipts of the whole
+/// epoch are not held in memory at the same time.
+///
+/// The block receipts roots are combined here rather than in
+/// `BlockHeaderBuilder`, because the simple MPT is not available in
+/// `primitives`.
+pub struct EpochReceiptsFunnel {
+    block_receipts_roots: Vec<MerkleHash>,
+    logs_bloom: Bloom,
+}
+
+impl EpochReceiptsFunnel {
+    pub fn with_capacity(block_count: usize) -> Self {
+        Self {
+            block_receipts_roots: Vec::with_capacity(block_count),
+            logs_bloom: Bloom::zero(),
+        }
+    }
+
+    pub fn push(&mut self, block_receipts: &BlockReceipts) {
+        self.block_receipts_roots
+            .push(compute_block_receipts_root(&block_receipts.receipts));
+        BlockHeaderBuilder::accrue_block_logs_bloom(
+            &mut self.logs_bloom,
+            block_receipts,
+        );
+    }
+
+    pub fn receipts_root(&self) -> MerkleHash {
+        compute_receipts_root_from_block_roots(
+            self.block_receipts_roots.iter().cloned(),
+        )
+    }
+
+    pub fn logs_bloom_hash(&self) -> H256 { keccak(self.logs_bloom) }
+}
+
 pub struct EpochReceiptProof {
     pub block_index_proof: TrieProof,
     pub block_receipt_proof: TrieProof,
@@ -139,6 +206,49 @@ pub fn compute_epoch_receipt_proof(
     }
 }
 
+/// The receipts of a block in an epoch, with the proof of their receipts root
+/// in the epoch receipts trie.
+#[derive(Clone, Debug, RlpEncodable, RlpDecodable)]
+pub struct BlockReceiptsWithProof {
+    pub block_hash: H256,
+    pub block_receipts: BlockReceipts,
+    pub block_index_proof: TrieProof,
+}
+
+/// The receipts of all the blocks in an epoch in the execution order.
+#[derive(Clone, Debug, RlpEncodable, RlpDecodable)]
+pub struct EpochReceiptsWithProof {
+    pub epoch: u64,
+    pub blocks: Vec<BlockReceiptsWithProof>,
+}
+
+/// Compute the proofs of the receipts roots of all the blocks in an epoch.
+/// The trie is only built once for the whole epoch.
+pub fn compute_epoch_receipts_with_proof(
+    epoch: u64, block_hashes: &[H256], epoch_receipts: &Vec<Arc<BlockReceipts>>,
+) -> EpochReceiptsWithProof {
+    let mut trie = epoch_receipts_trie(epoch_receipts);
+    let blocks = block_hashes
+        .iter()
+        .zip(epoch_receipts)
+        .enumerate()
+        .map(|(block_index_in_epoch, (block_hash, block_receipts))| {
+            BlockReceiptsWithProof {
+                block_hash: *block_hash,
+                block_receipts: (**block_receipts).clone(),
+                block_index_proof: simple_mpt_proof(
+                    &mut trie,
+                    &into_simple_mpt_key(
+                        block_index_in_epoch,
+                        epoch_receipts.len(),
+                    ),
+                ),
+            }
+        })
+        .collect();
+    EpochReceiptsWithProof { epoch, blocks }
+}
+
 /// Use `proof` to verify that `tx_hash` is indeed the `tx_index_in_block`-th
 /// transaction in a block with `num_txs_in_block` transactions and transaction
 /// root `block_tx_root`.
@@ -200,6 +310,24 @@ pub fn is_valid_receipt_inclusion_proof(
     )
 }
 
+/// Use `block_index_proof` to verify that `block_receipts_root` is indeed the
+/// receipts root of the `block_index_in_epoch`-th block in an epoch with
+/// `num_blocks_in_epoch` blocks and receipts root
+/// `verified_epoch_receipts_root`.
+pub fn is_valid_block_receipts_proof(
+    verified_epoch_receipts_root: MerkleHash, block_index_in_epoch: usize,
+    num_blocks_in_epoch: usize, block_receipts_root: MerkleHash,
+    block_index_proof: &TrieProof,
+) -> bool
+{
+    let key = &into_simple_mpt_key(block_index_in_epoch, num_blocks_in_epoch);
+    block_index_proof.is_valid_kv(
+        key,
+        Some(block_receipts_root.as_bytes()),
+        &verified_epoch_receipts_root,
+    )
+}
+
 impl VerificationConfig {
     pub fn new(
         test_mode: bool, referee_bound: usize, max_block_size_in_bytes: usize,
@@ -420,19 +548,23 @@ impl VerificationConfig {
     ) -> Result<(), Error> {
         self.verify_block_integrity(block)?;
 
-        let mut block_size = 0;
-        let mut block_total_gas = U256::zero();
-
         let block_height = block.block_header.height();
         let transitions = &self.machine.params().transition_heights;
-        for t in &block.transactions {
+        // The transactions are checked in parallel so a large block does not
+        // occupy a single core.
+        block.transactions.par_iter().try_for_each(|t| {
             self.verify_transaction_common(
                 t,
                 chain_id,
                 block_height,
                 transitions,
                 VerifyTxMode::Remote,
-            )?;
+            )
+        })?;
+
+        let mut block_size = 0;
+        let mut block_total_gas = U256::zero();
+        for t in &block.transactions {
             block_size += t.rlp_size();
             block_total_gas += *t.gas_limit();
         }
@@ -688,3 +820,101 @@ impl<'a> VerifyTxMode<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        compute_block_receipts_root, compute_epoch_receipts_with_proof,
+        compute_receipts_root, is_valid_block_receipts_proof,
+        EpochReceiptsFunnel,
+    };
+    use cfx_types::{Bloom, H256, U256};
+    use primitives::{BlockHeaderBuilder, BlockReceipts, Receipt};
+    use std::sync::Arc;
+
+    fn block_receipts(gas_used: &[u64]) -> Arc<BlockReceipts> {
+        Arc::new(BlockReceipts {
+            receipts: gas_used
+                .iter()
+                .map(|gas| {
+                    Receipt::new(
+                        0,
+                        U256::from(*gas),
+                        U256::zero(),
+                        false,
+                        vec![],
+                        false,
+                        vec![],
+                        vec![],
+                    )
+                })
+                .collect(),
+            block_number: 0,
+            secondary_reward: U256::zero(),
+            tx_execution_error_messages: vec![String::new(); gas_used.len()],
+            tx_execution_error_infos: vec![],
+        })
+    }
+
+    #[test]
+    fn test_epoch_receipts_with_proof() {
+        let epoch_receipts = vec![
+            block_receipts(&[1, 2]),
+            block_receipts(&[]),
+            block_receipts(&[3]),
+        ];
+        let block_hashes: Vec<_> =
+            (0..3).map(|i| H256::from_low_u64_be(i)).collect();
+        let root = compute_receipts_root(&epoch_receipts);
+        let proof = compute_epoch_receipts_with_proof(
+            7,
+            &block_hashes,
+            &epoch_receipts,
+        );
+        assert_eq!(proof.epoch, 7);
+        assert_eq!(proof.blocks.len(), 3);
+        for (index, block) in proof.blocks.iter().enumerate() {
+            assert_eq!(block.block_hash, block_hashes[index]);
+            assert!(is_valid_block_receipts_proof(
+                root,
+                index,
+                3,
+                compute_block_receipts_root(&block.block_receipts.receipts),
+                &block.block_index_proof,
+            ));
+        }
+        // The receipts of another block do not match the proof.
+        assert!(!is_valid_block_receipts_proof(
+            root,
+            0,
+            3,
+            compute_block_receipts_root(
+                &proof.blocks[2].block_receipts.receipts
+            ),
+            &proof.blocks[0].block_index_proof,
+        ));
+    }
+
+    #[test]
+    fn test_epoch_receipts_funnel() {
+        let mut epoch_receipts = vec![
+            block_receipts(&[1, 2]),
+            block_receipts(&[]),
+            block_receipts(&[3]),
+        ];
+        Arc::get_mut(&mut epoch_receipts[2]).unwrap().receipts[0].log_bloom =
+            Bloom::from_low_u64_be(0x10);
+        let mut funnel = EpochReceiptsFunnel::with_capacity(3);
+        for block_receipts in &epoch_receipts {
+            funnel.push(block_receipts);
+        }
+        assert_eq!(
+            funnel.receipts_root(),
+            compute_receipts_root(&epoch_receipts)
+        );
+        assert_eq!(
+            funnel.logs_bloom_hash(),
+            BlockHeaderBuilder::compute_block_logs_bloom_hash(&epoch_receipts)
+        );
+    }
+}
@@ -293,6 +293,51 @@ mod impls {
             Ok(deleted_kvs)
         }
 
+        /// Read a page of the storage entries of the contract `address` whose
+        /// storage keys start with `key_prefix`, in the ascending order of
+        /// the storage keys. The page starts after the storage key
+        /// `start_after` if it is set, and has at most `limit` entries. The
+        /// returned entries are keyed by the storage keys instead of the full
+        /// keys. The returned next key is passed as `start_after` to read the
+        /// next page, and it is `None` after the last page.
+        ///
+        /// The storage keys are hashed in the delta trie, so all storage
+        /// entries of the contract are read to build each page.
+        pub fn read_storage_entries(
+            &mut self, address: &Address, key_prefix: &[u8],
+            start_after: Option<&[u8]>, limit: usize,
+        ) -> Result<(Vec<MptKeyValue>, Option<Vec<u8>>)>
+        {
+            let mut entries: Vec<MptKeyValue> = self
+                .delete_all::<access_mode::Read>(
+                    StorageKey::new_storage_root_key(address),
+                    None,
+                )?
+                .into_iter()
+                .filter_map(|(key, value)| {
+                    match StorageKey::from_key_bytes::<SkipInputCheck>(&key) {
+                        StorageKey::StorageKey { storage_key, .. }
+                            if storage_key.starts_with(key_prefix)
+                                && start_after.map_or(true, |start| {
+                                    storage_key > start
+                                }) =>
+                        {
+                            Some((storage_key.to_vec(), value))
+                        }
+                        _ => None,
+                    }
+                })
+                .collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            let next_key = if limit > 0 && entries.len() > limit {
+                Some(entries[limit - 1].0.clone())
+            } else {
+                None
+            };
+            entries.truncate(limit);
+            Ok((entries, next_key))
+        }
+
         /// Load the storage layout for state commits.
         /// Modification to storage layout is the same as modification of
         /// any other key-values. But as required by MPT structure we
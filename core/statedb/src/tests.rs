@@ -7,6 +7,7 @@ use cfx_internal_common::StateRootWithAuxInfo;
 use cfx_storage::{
     utils::access_mode, ErrorKind, MptKeyValue, Result, StorageStateTrait,
 };
+use cfx_types::Address;
 use primitives::{EpochId, StorageKey, MERKLE_NULL_NODE};
 use std::{cell::RefCell, collections::HashMap};
 
@@ -296,3 +297,57 @@ fn test_checkpoint_evict_memory() {
     // value is removed from state-db
     assert_eq!(state_db.get_from_cache(&key(b"00")), None);
 }
+
+#[test]
+fn test_read_storage_entries() {
+    let contract = Address::from_low_u64_be(1);
+    let other_contract = Address::from_low_u64_be(2);
+    let entry_key = |address: &Address, storage_key: &'static [u8]| {
+        StorageKey::new_storage_key(address, storage_key).to_key_bytes()
+    };
+
+    let mut contents = RawStorage::new();
+    contents.insert(entry_key(&contract, b"a2"), value(b"v2"));
+    contents.insert(entry_key(&contract, b"a1"), value(b"v1"));
+    contents.insert(entry_key(&contract, b"b1"), value(b"v3"));
+    contents.insert(entry_key(&other_contract, b"a3"), value(b"v4"));
+    contents.insert(
+        StorageKey::new_storage_root_key(&contract).to_key_bytes(),
+        value(b"root"),
+    );
+    let mut state_db = StateDbTest::new(MockStorage::with_contents(contents));
+
+    // The modified entries are read together with the ones in the storage.
+    state_db
+        .set_raw(
+            StorageKey::new_storage_key(&contract, b"a0"),
+            value(b"v0"),
+            None,
+        )
+        .unwrap();
+
+    let (entries, next_key) = state_db
+        .read_storage_entries(&contract, b"a", None, 2)
+        .unwrap();
+    assert_eq!(
+        entries,
+        vec![
+            (b"a0".to_vec(), value(b"v0")),
+            (b"a1".to_vec(), value(b"v1")),
+        ]
+    );
+    assert_eq!(next_key, Some(b"a1".to_vec()));
+
+    let (entries, next_key) = state_db
+        .read_storage_entries(&contract, b"a", next_key.as_deref(), 2)
+        .unwrap();
+    assert_eq!(entries, vec![(b"a2".to_vec(), value(b"v2"))]);
+    assert_eq!(next_key, None);
+
+    let (entries, next_key) = state_db
+        .read_storage_entries(&contract, b"", None, 10)
+        .unwrap();
+    assert_eq!(entries.len(), 4);
+    assert_eq!(entries[3], (b"b1".to_vec(), value(b"v3")));
+    assert_eq!(next_key, None);
+}
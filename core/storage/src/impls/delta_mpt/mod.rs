@@ -280,6 +280,66 @@ impl MultiVersionMerklePatriciaTrie {
         }
     }
 
+    /// Forget the states committed for `transient_epoch_ids`, which are not
+    /// states of any epoch but steps between `epoch_id` and its parent
+    /// epoch, e.g. the checkpoints of an unfinished execution. The parent of
+    /// `epoch_id` is set to its first ancestor not removed, so the pivot
+    /// chain is still found from the parent epochs. The trie nodes are
+    /// released with the whole delta MPT.
+    pub fn remove_transient_epoch_states(
+        &self, epoch_id: &EpochId, transient_epoch_ids: &[EpochId],
+    ) -> Result<()> {
+        let mut parent_epoch_id = self.get_parent_epoch(epoch_id)?;
+        while let Some(parent) = parent_epoch_id {
+            if !transient_epoch_ids.contains(&parent) {
+                break;
+            }
+            parent_epoch_id = self.get_parent_epoch(&parent)?;
+        }
+
+        let mut commit_transaction = self.start_commit()?;
+        if let Some(parent_epoch_id) = &parent_epoch_id {
+            commit_transaction.transaction.put(
+                ["parent_epoch_id_".as_bytes(), epoch_id.as_ref()]
+                    .concat()
+                    .as_slice(),
+                parent_epoch_id.as_ref().to_hex::<String>().as_bytes(),
+            )?;
+        }
+        for transient_epoch_id in transient_epoch_ids {
+            commit_transaction.transaction.delete(
+                [
+                    "db_key_for_epoch_id_".as_bytes(),
+                    transient_epoch_id.as_ref(),
+                ]
+                .concat()
+                .as_slice(),
+            )?;
+            commit_transaction.transaction.delete(
+                ["parent_epoch_id_".as_bytes(), transient_epoch_id.as_ref()]
+                    .concat()
+                    .as_slice(),
+            )?;
+        }
+        {
+            let arc_db = self.get_arc_db()?;
+            commit_transaction
+                .transaction
+                .commit(arc_db.db_ref().as_any())?;
+        }
+
+        if let Some(parent_epoch_id) = parent_epoch_id {
+            self.set_parent_epoch(*epoch_id, parent_epoch_id);
+        }
+        let mut root_node_by_epoch = self.root_node_by_epoch.write();
+        let mut parent_epoch_by_epoch = self.parent_epoch_by_epoch.write();
+        for transient_epoch_id in transient_epoch_ids {
+            root_node_by_epoch.remove(transient_epoch_id);
+            parent_epoch_by_epoch.remove(transient_epoch_id);
+        }
+        Ok(())
+    }
+
     // These set methods are private to storage mod. Writing to db happens at
     // state commitment.
     fn set_epoch_root(&self, epoch_id: EpochId, root: Option<NodeRefDeltaMpt>) {
@@ -430,4 +490,5 @@ use cfx_types::hexstr_to_h256;
 use malloc_size_of::{MallocSizeOf, MallocSizeOfOps};
 use parking_lot::{Mutex, MutexGuard, RwLock};
 use primitives::{EpochId, MerkleHash, MERKLE_NULL_NODE};
+use rustc_hex::ToHex;
 use std::{borrow::BorrowMut, collections::HashMap, sync::Arc};
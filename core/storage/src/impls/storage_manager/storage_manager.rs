@@ -424,6 +424,18 @@ impl StorageManager {
         )
     }
 
+    /// Forget the transient states committed for `transient_epoch_ids` in
+    /// the delta MPT of `snapshot_epoch_id`, which are steps between the
+    /// state of `epoch_id` and its parent epoch.
+    pub fn remove_transient_epoch_states(
+        &self, snapshot_epoch_id: &EpochId, epoch_id: &EpochId,
+        transient_epoch_ids: &[EpochId],
+    ) -> Result<()>
+    {
+        self.get_delta_mpt(snapshot_epoch_id)?
+            .remove_transient_epoch_states(epoch_id, transient_epoch_ids)
+    }
+
     pub fn get_intermediate_mpt(
         &self, snapshot_epoch_id: &EpochId,
     ) -> Result<Option<Arc<DeltaMpt>>> {
@@ -492,17 +492,25 @@ impl BlockHeaderBuilder {
     pub fn compute_block_logs_bloom_hash(
         receipts: &Vec<Arc<BlockReceipts>>,
     ) -> H256 {
-        let bloom = receipts.iter().map(|x| &x.receipts).flatten().fold(
-            Bloom::zero(),
-            |mut b, r| {
-                b.accrue_bloom(&r.log_bloom);
-                b
-            },
-        );
+        let bloom = receipts.iter().fold(Bloom::zero(), |mut b, r| {
+            BlockHeaderBuilder::accrue_block_logs_bloom(&mut b, r);
+            b
+        });
 
         keccak(bloom)
     }
 
+    /// Accrue the logs blooms of the receipts of a block into `bloom`. The
+    /// logs bloom hash of an epoch is the hash of the bloom accrued from all
+    /// its blocks, so it can be computed as the blocks are executed.
+    pub fn accrue_block_logs_bloom(
+        bloom: &mut Bloom, block_receipts: &BlockReceipts,
+    ) {
+        for receipt in &block_receipts.receipts {
+            bloom.accrue_bloom(&receipt.log_bloom);
+        }
+    }
+
     pub fn compute_aggregated_bloom(blooms: Vec<Bloom>) -> Bloom {
         blooms.into_iter().fold(Bloom::zero(), |mut res, bloom| {
             res.accrue_bloom(&bloom);
@@ -595,6 +603,7 @@ mod tests {
                     block_number: 0,
                     secondary_reward: U256::zero(),
                     tx_execution_error_messages: vec![],
+                    tx_execution_error_infos: vec![],
                 })
             })
             .collect(); // Vec<Arc<Vec<_>>>
@@ -624,6 +633,7 @@ mod tests {
                     block_number: 0,
                     secondary_reward: U256::zero(),
                     tx_execution_error_messages: vec!["".into(); 10],
+                    tx_execution_error_infos: vec![],
                 })
             })
             .collect();
@@ -697,6 +707,7 @@ mod tests {
             block_number: 0,
             secondary_reward: U256::zero(),
             tx_execution_error_messages: vec!["".into(); 2],
+            tx_execution_error_infos: vec![],
         };
 
         let block2 = BlockReceipts {
@@ -732,6 +743,7 @@ mod tests {
             block_number: 0,
             secondary_reward: U256::zero(),
             tx_execution_error_messages: vec!["".into()],
+            tx_execution_error_infos: vec![],
         };
 
         let expected = keccak(
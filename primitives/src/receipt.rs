@@ -2,15 +2,20 @@
 // Conflux is free software and distributed under GNU General Public License.
 // See http://www.gnu.org/licenses/
 
-use crate::log_entry::LogEntry;
+use crate::{log_entry::LogEntry, transaction_index::TransactionIndex};
 use cfx_types::{Address, Bloom, U256, U64};
 use malloc_size_of::{MallocSizeOf, MallocSizeOfOps};
+use rlp::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
 use rlp_derive::{RlpDecodable, RlpEncodable};
 
 pub const TRANSACTION_OUTCOME_SUCCESS: u8 = 0;
 pub const TRANSACTION_OUTCOME_EXCEPTION_WITH_NONCE_BUMPING: u8 = 1; // gas fee charged
 pub const TRANSACTION_OUTCOME_EXCEPTION_WITHOUT_NONCE_BUMPING: u8 = 2; // no gas fee charged
 
+/// The execution error message of a transaction that is not executed because
+/// it is already executed earlier in the same epoch.
+pub const TX_ALREADY_EXECUTED_ERROR_MESSAGE: &str = "tx already executed";
+
 #[derive(Debug, Clone, PartialEq, Eq, RlpDecodable, RlpEncodable)]
 pub struct StorageChange {
     pub address: Address,
@@ -79,7 +84,7 @@ impl MallocSizeOf for Receipt {
 }
 
 /// Information describing execution of a block.
-#[derive(Debug, Clone, PartialEq, Eq, RlpDecodable, RlpEncodable)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct BlockReceipts {
     /// This is the receipts of transaction execution in this block.
     pub receipts: Vec<Receipt>,
@@ -91,6 +96,50 @@ pub struct BlockReceipts {
     /// The error messages for each transaction. A successful transaction has
     /// empty error_messages.
     pub tx_execution_error_messages: Vec<String>,
+    /// The structured details of the execution errors for each transaction.
+    /// It is empty for the receipts encoded without it.
+    pub tx_execution_error_infos: Vec<TxExecutionErrorInfo>,
+}
+
+impl BlockReceipts {
+    /// The details of the execution error of the transaction at `index`. The
+    /// details are empty if they are not recorded in the receipts.
+    pub fn tx_execution_error_info(
+        &self, index: usize,
+    ) -> TxExecutionErrorInfo {
+        self.tx_execution_error_infos
+            .get(index)
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+impl Encodable for BlockReceipts {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(5)
+            .append_list(&self.receipts)
+            .append(&self.block_number)
+            .append(&self.secondary_reward)
+            .append_list::<String, String>(&self.tx_execution_error_messages)
+            .append_list(&self.tx_execution_error_infos);
+    }
+}
+
+impl Decodable for BlockReceipts {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        let tx_execution_error_infos = match rlp.item_count()? {
+            4 => Vec::new(),
+            5 => rlp.list_at(4)?,
+            _ => return Err(DecoderError::RlpIncorrectListLen),
+        };
+        Ok(BlockReceipts {
+            receipts: rlp.list_at(0)?,
+            block_number: rlp.val_at(1)?,
+            secondary_reward: rlp.val_at(2)?,
+            tx_execution_error_messages: rlp.list_at(3)?,
+            tx_execution_error_infos,
+        })
+    }
 }
 
 impl MallocSizeOf for BlockReceipts {
@@ -98,3 +147,104 @@ impl MallocSizeOf for BlockReceipts {
         self.receipts.size_of(ops)
     }
 }
+
+/// The details of the execution error of a transaction which are kept beside
+/// its error message, so they need not be parsed from the message.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TxExecutionErrorInfo {
+    /// The position where the transaction is executed, if it is not executed
+    /// because it is already executed earlier in the same epoch.
+    pub executed_position: Option<TransactionIndex>,
+    /// The reason decoded from the output of the transaction, if it is
+    /// reverted with a recognized reason.
+    pub revert_reason: Option<String>,
+}
+
+impl Encodable for TxExecutionErrorInfo {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(2);
+        match &self.executed_position {
+            Some(position) => s.append(position),
+            None => s.begin_list(0),
+        };
+        match &self.revert_reason {
+            Some(reason) => s.append(reason),
+            None => s.begin_list(0),
+        };
+    }
+}
+
+impl Decodable for TxExecutionErrorInfo {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        let executed_position = rlp.at(0)?;
+        let revert_reason = rlp.at(1)?;
+        Ok(TxExecutionErrorInfo {
+            executed_position: if executed_position.is_empty() {
+                None
+            } else {
+                Some(executed_position.as_val()?)
+            },
+            revert_reason: if revert_reason.is_empty() {
+                None
+            } else {
+                Some(revert_reason.as_val()?)
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+use cfx_types::H256;
+
+#[test]
+fn test_block_receipts_error_infos() {
+    let position = TransactionIndex {
+        block_hash: H256::repeat_byte(0xab),
+        index: 7,
+    };
+    let block_receipts = BlockReceipts {
+        receipts: vec![],
+        block_number: 3,
+        secondary_reward: U256::from(5),
+        tx_execution_error_messages: vec![
+            "Vm reverted, oops".into(),
+            TX_ALREADY_EXECUTED_ERROR_MESSAGE.into(),
+        ],
+        tx_execution_error_infos: vec![
+            TxExecutionErrorInfo {
+                executed_position: None,
+                revert_reason: Some("oops".into()),
+            },
+            TxExecutionErrorInfo {
+                executed_position: Some(position.clone()),
+                revert_reason: None,
+            },
+        ],
+    };
+    let decoded: BlockReceipts =
+        rlp::decode(&rlp::encode(&block_receipts)).unwrap();
+    assert_eq!(decoded, block_receipts);
+    assert_eq!(decoded.tx_execution_error_info(0).executed_position, None);
+    assert_eq!(
+        decoded.tx_execution_error_info(0).revert_reason,
+        Some("oops".into())
+    );
+    assert_eq!(
+        decoded.tx_execution_error_info(1).executed_position,
+        Some(position)
+    );
+    assert_eq!(decoded.tx_execution_error_info(2).executed_position, None);
+
+    // The receipts encoded without the error infos.
+    let mut stream = RlpStream::new_list(4);
+    stream
+        .append_list(&block_receipts.receipts)
+        .append(&block_receipts.block_number)
+        .append(&block_receipts.secondary_reward)
+        .append_list::<String, String>(
+            &block_receipts.tx_execution_error_messages,
+        );
+    let decoded: BlockReceipts = rlp::decode(&stream.out()).unwrap();
+    assert!(decoded.tx_execution_error_infos.is_empty());
+    assert_eq!(decoded.tx_execution_error_info(1).executed_position, None);
+}
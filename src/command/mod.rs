@@ -4,4 +4,5 @@
 
 pub mod account;
 pub mod helpers;
+pub mod replica;
 pub mod rpc;
@@ -0,0 +1,85 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use cfxcore::sync::replica_snapshot::{diff_snapshots, read_snapshot};
+use clap;
+use std::path::PathBuf;
+
+#[derive(Debug, PartialEq)]
+pub struct ReplicaDiff {
+    pub left: PathBuf,
+    pub right: PathBuf,
+    pub max_diffs: usize,
+}
+
+impl ReplicaDiff {
+    pub fn new(matches: &clap::ArgMatches) -> Self {
+        let path = |name: &str| {
+            PathBuf::from(
+                matches
+                    .value_of(name)
+                    .expect("CLI argument is required; qed"),
+            )
+        };
+        let max_diffs = matches
+            .value_of("max-diffs")
+            .unwrap_or("10")
+            .parse()
+            .unwrap();
+        Self {
+            left: path("left"),
+            right: path("right"),
+            max_diffs,
+        }
+    }
+}
+
+pub fn execute(cmd: ReplicaDiff) -> Result<String, String> {
+    let left = read_snapshot(&cmd.left)?;
+    let right = read_snapshot(&cmd.right)?;
+    let compared_blocks = match (left.last(), right.last()) {
+        (Some(l), Some(r)) => l.block_count.min(r.block_count),
+        _ => 0,
+    };
+    let diffs = diff_snapshots(&left, &right);
+    let first = match diffs.first() {
+        None => {
+            return Ok(format!(
+                "The snapshots are consistent in the first {} blocks",
+                compared_blocks
+            ))
+        }
+        Some(first) => first,
+    };
+
+    let mut lines = vec![format!(
+        "{} mismatches in the first {} blocks, the first one is in epoch {} after block {}: {} is nondeterministic",
+        diffs.len(),
+        compared_blocks,
+        first.epoch_number,
+        first.block_count,
+        if first.is_pivot_mismatch() {
+            "consensus processing"
+        } else {
+            "execution"
+        }
+    )];
+    for diff in diffs.iter().take(cmd.max_diffs) {
+        let line = match (&diff.left, &diff.right) {
+            (Some(_), None) => "only executed in left".to_string(),
+            (None, Some(_)) => "only executed in right".to_string(),
+            _ => format!("{} differ", diff.fields.join(", ")),
+        };
+        lines.push(format!(
+            "block {} epoch {}: {}",
+            diff.block_count, diff.epoch_number, line
+        ));
+        for (side, record) in &[("left", &diff.left), ("right", &diff.right)] {
+            if let Some(record) = record {
+                lines.push(format!("  {:<5} {}", side, record.to_line()));
+            }
+        }
+    }
+    Ok(lines.join("\n"))
+}
@@ -155,7 +155,7 @@ class TestContract(RpcClient):
             self.call(contract_addr, encode_hex_0x(keccak(b"foo()")))
             assert(False) # should throw before this line
         except ReceivedErrorResponseError as e:
-            assert_equal(e.response.message, "Transaction reverted")
+            assert_equal(e.response.message, "Transaction reverted: A")
 
             # error string encoding details: https://ethereum.stackexchange.com/a/66404/18295
             assert_equal(e.response.data, (
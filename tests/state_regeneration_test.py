@@ -0,0 +1,45 @@
+#!/usr/bin/env python3
+import os, sys
+
+sys.path.insert(1, os.path.join(sys.path[0], '..'))
+
+from conflux.rpc import RpcClient
+from test_framework.test_framework import ConfluxTestFramework
+from test_framework.util import *
+
+'''
+The state of a fork block is regenerated in steps bounded by the budget of
+`test_regenerateState`, and each call continues from the previous one.
+'''
+class StateRegenerationTest(ConfluxTestFramework):
+    def set_test_params(self):
+        self.num_nodes = 1
+
+    def setup_network(self):
+        self.setup_nodes()
+
+    def run_test(self):
+        client = RpcClient(self.nodes[0])
+        pivot_chain = client.generate_empty_blocks(30)
+        fork_tip = pivot_chain[9]
+        for _ in range(3):
+            fork_tip = client.generate_block_with_parent(fork_tip)
+        client.generate_empty_blocks(10)
+
+        # Each call replays one epoch of the fork.
+        for replayed_epochs in range(1, 4):
+            progress = self.nodes[0].test_regenerateState(fork_tip, 1, 60000)
+            assert_equal(progress["blockHash"], fork_tip)
+            assert_equal(int(progress["totalEpochs"], 0), 3)
+            assert_equal(int(progress["replayedEpochs"], 0), replayed_epochs)
+            assert_equal(progress["done"], replayed_epochs == 3)
+
+        # The state is available now, so nothing is replayed.
+        progress = self.nodes[0].test_regenerateState(fork_tip, 1, 60000)
+        assert_equal(int(progress["totalEpochs"], 0), 0)
+        assert progress["done"]
+        self.log.info("Pass!")
+
+
+if __name__ == '__main__':
+    StateRegenerationTest().main()
@@ -6,16 +6,28 @@ use std::{
     hash::Hash,
 };
 
-/// Topologically sort `index_set` and return a sorted `Vec`.
+/// Topologically sort `index_set` with Kahn's algorithm and return a sorted
+/// `Vec`. Only the edges between the nodes in `index_set` are considered.
 /// For the nodes without order-before relationship, the ones with smaller
-/// `order_indicator` output will be ordered first.
-pub fn topological_sort<InIndex, OutIndex, F, OrderIndicator, FOrd, Set>(
+/// `order_indicator` output will be ordered first, and the ties are broken by
+/// the node index. Thus, the result only depends on the graph and
+/// `order_indicator`, not on the iteration order of `index_set`.
+pub fn topological_sort<
+    InIndex,
+    OutIndex,
+    F,
+    Edges,
+    OrderIndicator,
+    FOrd,
+    Set,
+>(
     index_set: Set, predecessor_edges: F, order_indicator: FOrd,
 ) -> Vec<OutIndex>
 where
     InIndex: Copy + Hash + Eq + PartialEq + Ord + TryInto<OutIndex>,
     <InIndex as TryInto<OutIndex>>::Error: Debug,
-    F: Fn(InIndex) -> Vec<InIndex>,
+    F: Fn(InIndex) -> Edges,
+    Edges: IntoIterator<Item = InIndex>,
     OrderIndicator: Ord,
     FOrd: Fn(InIndex) -> OrderIndicator,
     Set: SetLike<InIndex> + Default + Clone + IntoIterator<Item = InIndex>,
@@ -203,3 +215,83 @@ impl SetLike<u32> for BitSet {
 
     fn contains(&self, i: &u32) -> bool { self.contains(*i) }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Graph, TreeGraph, DAG};
+    use std::collections::HashSet;
+
+    /// A random tree-graph where each node refers to nodes with smaller
+    /// indices.
+    struct TestGraph {
+        parents: Vec<Option<usize>>,
+        referees: Vec<Vec<usize>>,
+        /// Used as the order indicator, like block hashes.
+        keys: Vec<u64>,
+    }
+
+    impl TestGraph {
+        fn random(size: usize, seed: u64) -> Self {
+            let mut state = seed;
+            let mut next = move || {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                state >> 33
+            };
+            let mut graph = TestGraph {
+                parents: vec![None],
+                referees: vec![vec![]],
+                keys: vec![next()],
+            };
+            for i in 1..size {
+                graph.parents.push(Some(next() as usize % i));
+                let referee_count = next() as usize % 3;
+                graph.referees.push(
+                    (0..referee_count).map(|_| next() as usize % i).collect(),
+                );
+                graph.keys.push(next());
+            }
+            graph
+        }
+    }
+
+    impl Graph for TestGraph {
+        type NodeIndex = usize;
+    }
+
+    impl TreeGraph for TestGraph {
+        fn parent(&self, node_index: usize) -> Option<usize> {
+            self.parents[node_index]
+        }
+
+        fn referees(&self, node_index: usize) -> Vec<usize> {
+            self.referees[node_index].clone()
+        }
+    }
+
+    #[test]
+    fn test_topological_order_is_valid() {
+        for seed in 0..20 {
+            let graph = TestGraph::random(200, seed);
+            // Sort a subset to check that edges to outside nodes are ignored.
+            let index_set: HashSet<usize> = (0..200).step_by(3).collect();
+            let sorted = graph.topological_sort_with_order_indicator(
+                index_set.clone(),
+                |i| graph.keys[i],
+            );
+            assert_eq!(sorted.len(), index_set.len());
+            let mut position = vec![None; 200];
+            for (pos, i) in sorted.iter().enumerate() {
+                position[*i] = Some(pos);
+            }
+            for i in &index_set {
+                for prev in graph.predecessor_edges(*i) {
+                    if index_set.contains(&prev) {
+                        assert!(position[prev] < position[*i]);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -49,6 +49,21 @@ impl<
         self.inner.lock().ancestor_at(v, at)
     }
 
+    /// Answer the `lca` queries of `pairs` in order with one lock.
+    pub fn lcas(&self, pairs: &[(usize, usize)]) -> Vec<usize> {
+        let mut inner = self.inner.lock();
+        pairs.iter().map(|(v, w)| inner.lca(*v, *w)).collect()
+    }
+
+    /// Answer the `ancestor_at` queries of `(v, at)` in order with one lock.
+    pub fn ancestors_at(&self, queries: &[(usize, usize)]) -> Vec<usize> {
+        let mut inner = self.inner.lock();
+        queries
+            .iter()
+            .map(|(v, at)| inner.ancestor_at(*v, *at))
+            .collect()
+    }
+
     pub fn set(&mut self, v: usize, value: i128) {
         self.inner.lock().set(v, value);
     }
@@ -233,6 +248,9 @@ mod tests {
         assert_eq!(tree.ancestor_at(5, 2), 3);
         assert_eq!(tree.ancestor_at(3, 1), 1);
         assert_eq!(tree.ancestor_at(4, 1), 4);
+
+        assert_eq!(tree.ancestors_at(&[(4, 0), (5, 2), (5, 1)]), vec![0, 3, 1]);
+        assert_eq!(tree.lcas(&[(2, 5), (4, 5), (3, 5)]), vec![1, 0, 3]);
     }
 
     #[test]